@@ -0,0 +1,67 @@
+//! Audit record types for per-block commit history.
+//!
+//! An audit record captures what a node committed at a given height so that
+//! post-incident analysis can be done without re-executing blocks.
+
+use alloy_primitives::B256;
+
+/// Encoded length of an `AuditRecord` in bytes.
+pub const AUDIT_RECORD_LEN: usize = 8 + 32 + 32 + 8 * 4 + 32;
+
+/// Summary of a single block commit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// Block number the commit belongs to
+    pub block_number: u64,
+    /// State root before applying the block
+    pub root_before: B256,
+    /// State root after applying the block
+    pub root_after: B256,
+    /// Count of updated and inserted trie nodes
+    pub updates: u64,
+    /// Count of deleted trie nodes
+    pub deletes: u64,
+    /// Count of collected leaves
+    pub leaves: u64,
+    /// Count of updated storage roots
+    pub storage_roots: u64,
+    /// Aggregate signature of the committed node sets (`MergedNodeSet::signature_v2`)
+    pub signature: B256,
+}
+
+impl AuditRecord {
+    /// Encodes the record into its fixed-length binary layout.
+    ///
+    /// Layout: `block_number || root_before || root_after || updates || deletes ||
+    /// leaves || storage_roots || signature`, integers as big-endian u64.
+    pub fn encode(&self) -> Vec<u8> {
+        let mut buf = Vec::with_capacity(AUDIT_RECORD_LEN);
+        buf.extend_from_slice(&self.block_number.to_be_bytes());
+        buf.extend_from_slice(self.root_before.as_slice());
+        buf.extend_from_slice(self.root_after.as_slice());
+        buf.extend_from_slice(&self.updates.to_be_bytes());
+        buf.extend_from_slice(&self.deletes.to_be_bytes());
+        buf.extend_from_slice(&self.leaves.to_be_bytes());
+        buf.extend_from_slice(&self.storage_roots.to_be_bytes());
+        buf.extend_from_slice(self.signature.as_slice());
+        buf
+    }
+
+    /// Decodes a record, returns `None` if the length doesn't match.
+    pub fn decode(buf: &[u8]) -> Option<Self> {
+        if buf.len() != AUDIT_RECORD_LEN {
+            return None;
+        }
+        let u64_at = |offset: usize| u64::from_be_bytes(buf[offset..offset + 8].try_into().unwrap());
+        Some(Self {
+            block_number: u64_at(0),
+            root_before: B256::from_slice(&buf[8..40]),
+            root_after: B256::from_slice(&buf[40..72]),
+            updates: u64_at(72),
+            deletes: u64_at(80),
+            leaves: u64_at(88),
+            storage_roots: u64_at(96),
+            signature: B256::from_slice(&buf[104..136]),
+        })
+    }
+}
@@ -0,0 +1,32 @@
+//! Cooperative cancellation of long-running operations.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+
+/// Shared flag asking long-running operations to stop early.
+///
+/// Clones share the flag. Operations taking a token check it between units
+/// of work, stop at the next consistent point once it is cancelled and
+/// report the progress made so far, so shutdown or a reorg doesn't have to
+/// kill them mid-write. A cancelled token stays cancelled.
+#[derive(Debug, Clone, Default)]
+pub struct CancellationToken {
+    cancelled: Arc<AtomicBool>,
+}
+
+impl CancellationToken {
+    /// Create a token that is not cancelled.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Ask the operations holding this token or a clone of it to stop.
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::Release);
+    }
+
+    /// Whether [`cancel`](Self::cancel) was called on this token or a clone of it.
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::Acquire)
+    }
+}
@@ -5,24 +5,32 @@
 
 use std::sync::Arc;
 use std::collections::HashMap;
-use alloy_primitives::B256;
+use alloy_primitives::{Bytes, B256};
+
+use crate::audit::AuditRecord;
 
 // Trie state storage keys
 pub const TRIE_STATE_ROOT_KEY: &[u8] = b"state_root";
 pub const TRIE_STATE_BLOCK_NUMBER_KEY: &[u8] = b"block_number";
 
+// Trie node storage prefixes
+pub const TRIE_NODE_STORAGE_PREFIX: &[u8] = b"O";
+pub const TRIE_NODE_ACCOUNT_PREFIX: &[u8] = b"A";
+
 /// Represents a trie node with its hash and encoded data
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub struct TrieNode {
     /// Node hash, empty for deleted node
     pub hash: Option<B256>,
-    /// Encoded node data, empty for deleted node
-    pub blob: Option<Vec<u8>>,
+    /// Encoded node data, empty for deleted node.
+    ///
+    /// Shared with the database caches and readers, cloning is cheap.
+    pub blob: Option<Bytes>,
 }
 
 impl TrieNode {
     /// Creates a new trie node
-    pub fn new(hash: Option<B256>, blob: Option<Vec<u8>>) -> Self {
+    pub fn new(hash: Option<B256>, blob: Option<Bytes>) -> Self {
         Self { hash, blob }
     }
 
@@ -89,12 +97,72 @@ pub struct DiffLayer {
     /// Only accounts whose storage has been modified in this block will have entries
     /// in this map. Unmodified accounts are not included.
     pub diff_storage_roots: HashMap<B256, B256>,
+
+    /// Trie node key ranges `[start, end)` wiped in the current block.
+    ///
+    /// Used to drop the whole storage trie of a self-destructed account
+    /// without listing its nodes. Ranges apply before `diff_nodes`, so nodes
+    /// written in the same block are kept.
+    pub deleted_ranges: Vec<(Vec<u8>, Vec<u8>)>,
+
+    /// Code hashes of the accounts changed in the current block, keyed by
+    /// hashed address, `None` for accounts without code or deleted.
+    ///
+    /// Only filled when the code hash index is maintained, and applied to the
+    /// index when the layer is persisted.
+    pub code_hashes: HashMap<B256, Option<B256>>,
+
+    /// Storage slot count changes of the accounts changed in the current
+    /// block, keyed by hashed address.
+    ///
+    /// Only filled when slot counts are tracked, and applied to the stored
+    /// counts when the layer is persisted.
+    pub slot_count_changes: HashMap<B256, SlotCountChange>,
+
+    /// Audit record of the block, if the commit was audited.
+    ///
+    /// Written to the audit log in the same batch that persists the layer,
+    /// so the log never holds a block that isn't persisted.
+    pub audit_record: Option<AuditRecord>,
+}
+
+/// Change of the storage slot count of one account in a diff layer.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum SlotCountChange {
+    /// Slots created minus slots cleared.
+    Delta(i64),
+    /// The storage was wiped, then the given number of slots was written.
+    Reset(u64),
 }
 
 impl DiffLayer {
     /// Create a new diff layer
     pub fn new(diff_nodes: HashMap<Vec<u8>, Arc<TrieNode>>, diff_storage_roots: HashMap<B256, B256>) -> Self {
-        Self { diff_nodes, diff_storage_roots }
+        Self { diff_nodes, diff_storage_roots, deleted_ranges: Vec::new(), code_hashes: HashMap::new(), slot_count_changes: HashMap::new(), audit_record: None }
+    }
+
+    /// Set the trie node key ranges wiped by this diff layer
+    pub fn with_deleted_ranges(mut self, deleted_ranges: Vec<(Vec<u8>, Vec<u8>)>) -> Self {
+        self.deleted_ranges = deleted_ranges;
+        self
+    }
+
+    /// Set the code hashes of the accounts changed by this diff layer
+    pub fn with_code_hashes(mut self, code_hashes: HashMap<B256, Option<B256>>) -> Self {
+        self.code_hashes = code_hashes;
+        self
+    }
+
+    /// Set the storage slot count changes of the accounts changed by this diff layer
+    pub fn with_slot_count_changes(mut self, slot_count_changes: HashMap<B256, SlotCountChange>) -> Self {
+        self.slot_count_changes = slot_count_changes;
+        self
+    }
+
+    /// Set the audit record of the block of this diff layer
+    pub fn with_audit_record(mut self, audit_record: AuditRecord) -> Self {
+        self.audit_record = Some(audit_record);
+        self
     }
 
     /// Get a trie node by prefix
@@ -102,6 +170,11 @@ impl DiffLayer {
         self.diff_nodes.get(&prefix).map(|node: &Arc<TrieNode>| node.clone())
     }
 
+    /// Returns true if `key` falls into one of the deleted ranges
+    pub fn is_range_deleted(&self, key: &[u8]) -> bool {
+        self.deleted_ranges.iter().any(|(start, end)| start.as_slice() <= key && key < end.as_slice())
+    }
+
     /// Get a storage root by hased address
     pub fn get_storage_root(&self, hased_address: B256) -> Option<B256> {
         self.diff_storage_roots.get(&hased_address).map(|root| *root)
@@ -109,10 +182,21 @@ impl DiffLayer {
 
     /// Returns true if the diff layer is empty
     pub fn is_empty(&self) -> bool {
-        self.diff_nodes.is_empty() && self.diff_storage_roots.is_empty()
+        self.diff_nodes.is_empty() && self.diff_storage_roots.is_empty() && self.deleted_ranges.is_empty() && self.audit_record.is_none()
     }
 }
 
+/// Where a trie node lookup in `DiffLayers` ended.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum DiffLayerLookup {
+    /// Found in the most recent layer.
+    NewestLayer,
+    /// Found in an older layer.
+    OlderLayer,
+    /// Not found in any layer, the database has to be read.
+    Miss,
+}
+
 /// A collection of diff layers for uncommitted blocks in the trie state.
 ///
 /// `DiffLayers` maintains a stack of `DiffLayer` instances, where each layer
@@ -173,13 +257,26 @@ impl DiffLayers {
     }
 
     /// Get a trie node by prefix
+    ///
+    /// A key in a deleted range of a layer is returned as a deleted node, so
+    /// older layers and the database don't resurrect it.
     pub fn get_trie_nodes(&self, prefix: Vec<u8>) -> Option<Arc<TrieNode>> {
-        for difflayer in &self.diff_layers {
-            if let Some(node) = difflayer.get_trie_nodes(prefix.clone()) {
-                return Some(node);
-            }
+        self.lookup_trie_node(&prefix).0
+    }
+
+    /// Get a trie node by prefix like `get_trie_nodes`, also returning where
+    /// the lookup ended and the number of layers probed.
+    pub fn lookup_trie_node(&self, prefix: &[u8]) -> (Option<Arc<TrieNode>>, DiffLayerLookup, usize) {
+        for (index, difflayer) in self.diff_layers.iter().enumerate() {
+            let node = match difflayer.diff_nodes.get(prefix) {
+                Some(node) => node.clone(),
+                None if difflayer.is_range_deleted(prefix) => Arc::new(TrieNode::default()),
+                None => continue,
+            };
+            let lookup = if index == 0 { DiffLayerLookup::NewestLayer } else { DiffLayerLookup::OlderLayer };
+            return (Some(node), lookup, index + 1);
         }
-        None
+        (None, DiffLayerLookup::Miss, self.diff_layers.len())
     }
 
     /// Get a storage root by hased address
@@ -0,0 +1,58 @@
+//! Instrumentation hooks for external profilers.
+//!
+//! Embedders can register a `TrieHooks` implementation to integrate trie
+//! operations with their own profiling or QoS systems without forking the crate.
+
+use std::fmt::Debug;
+use std::time::Duration;
+use alloy_primitives::B256;
+
+/// Phases of a block update reported to `TrieHooks`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum TriePhase {
+    /// Preparing accounts and storage roots before the update.
+    UpdatePrepare,
+    /// Applying account and storage updates to the tries.
+    Update,
+    /// Hashing the tries.
+    Hash,
+    /// Committing the tries into node sets.
+    Commit,
+    /// Persisting a diff layer to the database.
+    Flush,
+}
+
+/// Where a trie node read was served from.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum NodeReadSource {
+    /// The node was found in an uncommitted diff layer.
+    DiffLayer,
+    /// The node was read from the database.
+    Database,
+}
+
+/// Callbacks invoked by trie operations.
+///
+/// All methods have empty default implementations, so implementors only
+/// override what they need. Callbacks run inline on the calling thread, which
+/// may be a rayon worker, and must be cheap and non-blocking.
+pub trait TrieHooks: Send + Sync + Debug {
+    /// Called when `phase` starts.
+    fn on_phase_start(&self, phase: TriePhase) {
+        let _ = phase;
+    }
+
+    /// Called when `phase` ends, with its duration, also when it is cut
+    /// short by an error.
+    fn on_phase_end(&self, phase: TriePhase, elapsed: Duration) {
+        let _ = (phase, elapsed);
+    }
+
+    /// Called for every trie node resolved while walking a trie.
+    ///
+    /// `owner` is zero for the account trie, `path` is the nibble path of the
+    /// node and `size` the length of its encoded blob.
+    fn on_node_read(&self, owner: B256, path: &[u8], source: NodeReadSource, size: usize) {
+        let _ = (owner, path, source, size);
+    }
+}
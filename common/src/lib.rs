@@ -5,8 +5,32 @@
 
 /// Database traits for trie operations.
 mod traits;
-pub use traits::TrieDatabase;
+pub use traits::{TrieDatabase, TrieDBErrorSource};
 
 /// DiffLayer types for tracking trie node changes.
 mod difflayer;
-pub use difflayer::{Leaf, TrieNode, DiffLayer, DiffLayers, TRIE_STATE_ROOT_KEY, TRIE_STATE_BLOCK_NUMBER_KEY};
+pub use difflayer::{Leaf, TrieNode, DiffLayer, DiffLayers, DiffLayerLookup, SlotCountChange, TRIE_STATE_ROOT_KEY, TRIE_STATE_BLOCK_NUMBER_KEY, TRIE_NODE_STORAGE_PREFIX, TRIE_NODE_ACCOUNT_PREFIX};
+
+/// Audit record types for per-block commit history.
+mod audit;
+pub use audit::{AuditRecord, AUDIT_RECORD_LEN};
+
+/// Instrumentation hooks for external profilers.
+mod hooks;
+pub use hooks::{TrieHooks, TriePhase, NodeReadSource};
+
+/// In-memory write layer over a trie database.
+mod overlay;
+pub use overlay::{OverlayDB, MemoryLimitExceeded, Savepoint};
+
+/// Process-wide metric totals for metrics snapshots.
+mod metric_totals;
+pub use metric_totals::{CounterTotal, GaugeValue, HistogramTotal, HistogramSummary};
+
+/// Cooperative cancellation of long-running operations.
+mod cancellation;
+pub use cancellation::CancellationToken;
+
+/// In-memory trie database with file snapshots for tests and fuzzers.
+mod memory;
+pub use memory::MemoryDB;
@@ -0,0 +1,519 @@
+//! In-memory trie database for tests and fuzzers.
+
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Read, Write};
+use std::path::Path;
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use alloy_primitives::{b256, Bytes, B256};
+use reth_metrics::{
+    metrics::{Counter, Gauge},
+    Metrics,
+};
+
+use crate::difflayer::DiffLayer;
+use crate::overlay::{shard_index, MemoryLimitExceeded, Savepoint, OVERLAY_SHARD_COUNT};
+use crate::traits::TrieDatabase;
+
+/// Magic bytes at the start of a MemoryDB dump.
+const MEMORY_DB_MAGIC: &[u8; 8] = b"TRIEDBMM";
+
+/// Version of the MemoryDB dump format.
+const MEMORY_DB_VERSION: u8 = 1;
+
+/// Root of the empty trie, the persisted state root of a new MemoryDB.
+const EMPTY_ROOT_HASH: B256 = b256!("56e81f171bcc55a6ff8345e692c0f86e5b48e01b996cadc001622fb5e363b421");
+
+/// Bytes accounted for a stored storage root, its key and value.
+const STORAGE_ROOT_ENTRY_SIZE: usize = 64;
+
+/// A [`TrieDatabase`] keeping trie nodes, storage roots and the persisted
+/// state in memory.
+///
+/// Meant for unit tests and fuzzers: a populated store can be written to a
+/// file with [`dump_to_file`](Self::dump_to_file) and reloaded instantly
+/// with [`load_from_file`](Self::load_from_file) instead of rebuilding the
+/// trie. Clones share the store, [`fork`](Self::fork) branches it. Flat
+/// snapshots, audit records and the other optional parts of
+/// [`TrieDatabase`] keep their default no-op behavior.
+///
+/// Trie nodes are kept in shards keyed by path hash, like the buffered
+/// writes of an [`OverlayDB`](crate::OverlayDB), so parallel storage trie
+/// commits rarely contend.
+///
+/// [`savepoint`](Self::savepoint) and [`rollback_to`](Self::rollback_to)
+/// undo writes like on an overlay, e.g. to simulate reorgs in tests.
+///
+/// With [`with_memory_limit`](Self::with_memory_limit), writes taking the
+/// stored bytes past the limit fail with [`MemoryLimitExceeded`], so
+/// runaway growth in tests surfaces as an error instead of an OOM kill.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryDB {
+    state: Arc<MemoryState>,
+    /// Maximum number of stored bytes, `None` for no limit
+    memory_limit: Option<usize>,
+}
+
+/// Metrics for the `MemoryDB`.
+#[derive(Metrics, Clone)]
+#[metrics(scope = "rust.eth.triedb.memory")]
+struct MemoryDBMetrics {
+    /// Bytes of keys and values stored by all in-memory databases
+    memory_usage: Gauge,
+    /// Counter of writes rejected for exceeding the memory limit
+    memory_limit_rejections: Counter,
+}
+
+/// Trie nodes of the paths hashing to one shard.
+type NodeShard = Arc<BTreeMap<Vec<u8>, Bytes>>;
+
+/// The store of a [`MemoryDB`], shared by its clones.
+///
+/// Locks are taken shard first, then the layer, and all shards in index
+/// order, so writers of single shards and writers of all of them can't
+/// deadlock. The maps are shared with forks until either side writes them.
+struct MemoryState {
+    /// Trie nodes by path hash
+    shards: Box<[RwLock<NodeShard>]>,
+    /// Storage roots and the persisted state
+    layer: RwLock<MemoryLayer>,
+    /// Bytes of the keys and values of the stored trie nodes and storage roots
+    memory_usage: AtomicUsize,
+    /// Sequence number of the next write
+    next_seq: AtomicU64,
+    /// Number of writes making up the stored state, undone ones excluded
+    writes: AtomicUsize,
+    /// The store at each live savepoint, by sequence number
+    savepoints: Mutex<BTreeMap<u64, MemorySnapshot>>,
+    metrics: MemoryDBMetrics,
+}
+
+/// The store at a savepoint. The maps are shared with the live store, so
+/// it costs the copies of the maps written since.
+struct MemorySnapshot {
+    shards: Vec<NodeShard>,
+    layer: MemoryLayer,
+    memory_usage: usize,
+    writes: usize,
+}
+
+/// Everything a [`MemoryDB`] stores besides the trie nodes.
+#[derive(Debug, Clone, Default)]
+struct MemoryLayer {
+    storage_roots: Arc<BTreeMap<B256, B256>>,
+    /// Persisted block and state root, `None` before the first commit
+    persist_state: Option<(u64, B256)>,
+}
+
+/// Shared access to all shards and the layer of a [`MemoryState`].
+struct MemoryReadGuard<'a> {
+    shards: Vec<RwLockReadGuard<'a, NodeShard>>,
+    layer: RwLockReadGuard<'a, MemoryLayer>,
+}
+
+/// Exclusive access to all shards and the layer of a [`MemoryState`].
+struct MemoryWriteGuard<'a> {
+    shards: Vec<RwLockWriteGuard<'a, NodeShard>>,
+    layer: RwLockWriteGuard<'a, MemoryLayer>,
+}
+
+impl std::fmt::Debug for MemoryState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MemoryState")
+            .field("memory_usage", &self.memory_usage.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+impl Default for MemoryState {
+    fn default() -> Self {
+        Self::with_contents((0..OVERLAY_SHARD_COUNT).map(|_| NodeShard::default()).collect(), MemoryLayer::default())
+    }
+}
+
+impl MemoryState {
+    /// A store holding `shards` and `layer`, accounting for their bytes.
+    fn with_contents(shards: Vec<NodeShard>, layer: MemoryLayer) -> Self {
+        let memory_usage = shards.iter()
+            .flat_map(|shard| shard.iter())
+            .map(|(path, blob)| trie_node_size(path, blob))
+            .sum::<usize>()
+            + layer.storage_roots.len() * STORAGE_ROOT_ENTRY_SIZE;
+        let metrics = MemoryDBMetrics::default();
+        metrics.memory_usage.increment(memory_usage as f64);
+        Self {
+            shards: shards.into_iter().map(RwLock::new).collect(),
+            layer: RwLock::new(layer),
+            memory_usage: AtomicUsize::new(memory_usage),
+            next_seq: AtomicU64::new(0),
+            writes: AtomicUsize::new(0),
+            savepoints: Mutex::new(BTreeMap::new()),
+            metrics,
+        }
+    }
+
+    fn shard(&self, path: &[u8]) -> &RwLock<NodeShard> {
+        &self.shards[shard_index(path)]
+    }
+
+    fn read_all(&self) -> MemoryReadGuard<'_> {
+        MemoryReadGuard {
+            shards: self.shards.iter().map(|shard| shard.read().unwrap()).collect(),
+            layer: self.layer.read().unwrap(),
+        }
+    }
+
+    fn lock_all(&self) -> MemoryWriteGuard<'_> {
+        MemoryWriteGuard {
+            shards: self.shards.iter().map(|shard| shard.write().unwrap()).collect(),
+            layer: self.layer.write().unwrap(),
+        }
+    }
+
+    /// Account for `old` stored bytes replaced by `new` ones, unless it
+    /// takes the stored bytes past `limit`. Writes not growing the store
+    /// always pass.
+    fn resize(&self, old: usize, new: usize, limit: Option<usize>) -> Result<(), MemoryLimitExceeded> {
+        let limit = limit.unwrap_or(usize::MAX);
+        self.memory_usage.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |usage| {
+            let resized = usage - old + new;
+            (new <= old || resized <= limit).then_some(resized)
+        }).map_err(|usage| {
+            self.metrics.memory_limit_rejections.increment(1);
+            MemoryLimitExceeded { usage, write: new, limit }
+        })?;
+        self.metrics.memory_usage.increment(new as f64);
+        self.metrics.memory_usage.decrement(old as f64);
+        Ok(())
+    }
+
+    /// Count a write, while holding the locks it was made under.
+    fn record_write(&self) {
+        self.next_seq.fetch_add(1, Ordering::Relaxed);
+        self.writes.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+impl Drop for MemoryState {
+    fn drop(&mut self) {
+        // The gauge sums over all databases, take this one's share back out
+        self.metrics.memory_usage.decrement(*self.memory_usage.get_mut() as f64);
+    }
+}
+
+/// Bytes accounted for the trie node `blob` stored at `path`.
+fn trie_node_size(path: &[u8], blob: &[u8]) -> usize {
+    path.len() + blob.len()
+}
+
+impl MemoryDB {
+    /// Create an empty database.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Create a copy-on-write child holding the current state of this
+    /// database, e.g. to branch the state in property tests exploring
+    /// alternative update sequences.
+    ///
+    /// The fork is O(1): it shares the maps with the parent. The first write
+    /// to a map on either side copies its index, the node blobs stay shared.
+    /// Later writes to the parent aren't seen by the fork and vice versa.
+    ///
+    /// The fork accounts for its copy of the stored bytes on its own and
+    /// keeps the memory limit.
+    pub fn fork(&self) -> Self {
+        let guard = self.state.read_all();
+        let state = MemoryState::with_contents(guard.shards.iter().map(|shard| NodeShard::clone(shard)).collect(), guard.layer.clone());
+        Self { state: Arc::new(state), memory_limit: self.memory_limit }
+    }
+
+    /// Reject writes taking the stored bytes past `memory_limit`.
+    ///
+    /// Trie node removals always pass, `remove_trie_node` can't fail.
+    pub fn with_memory_limit(mut self, memory_limit: usize) -> Self {
+        self.memory_limit = Some(memory_limit);
+        self
+    }
+
+    /// Mark the current state for [`rollback_to`](Self::rollback_to).
+    ///
+    /// The savepoint shares the maps with the store like a
+    /// [`fork`](Self::fork), so it holds on to the nodes overwritten or
+    /// removed after it. Those aren't counted by
+    /// [`memory_usage`](Self::memory_usage).
+    pub fn savepoint(&self) -> Savepoint {
+        // Writes hold a shard or the layer locked while counted
+        let guard = self.state.read_all();
+        let seq = self.state.next_seq.load(Ordering::Relaxed);
+        self.state.savepoints.lock().unwrap().entry(seq).or_insert_with(|| MemorySnapshot {
+            shards: guard.shards.iter().map(|shard| NodeShard::clone(shard)).collect(),
+            layer: guard.layer.clone(),
+            memory_usage: self.state.memory_usage.load(Ordering::Relaxed),
+            writes: self.state.writes.load(Ordering::Relaxed),
+        });
+        Savepoint { seq }
+    }
+
+    /// Undo the writes made since `savepoint`, returning the number of
+    /// writes undone. Savepoints taken after `savepoint` are dropped.
+    ///
+    /// # Panics
+    ///
+    /// If `savepoint` wasn't taken on this database or was dropped by a
+    /// rollback to an earlier one.
+    pub fn rollback_to(&self, savepoint: Savepoint) -> usize {
+        let mut guard = self.state.lock_all();
+        let mut savepoints = self.state.savepoints.lock().unwrap();
+        savepoints.retain(|seq, _| *seq <= savepoint.seq);
+        let snapshot = savepoints.get(&savepoint.seq).expect("savepoint of this database");
+
+        for (shard, saved) in guard.shards.iter_mut().zip(&snapshot.shards) {
+            **shard = NodeShard::clone(saved);
+        }
+        *guard.layer = snapshot.layer.clone();
+        let memory_usage = self.state.memory_usage.swap(snapshot.memory_usage, Ordering::Relaxed);
+        self.state.metrics.memory_usage.increment(snapshot.memory_usage as f64);
+        self.state.metrics.memory_usage.decrement(memory_usage as f64);
+        self.state.writes.swap(snapshot.writes, Ordering::Relaxed) - snapshot.writes
+    }
+
+    /// Approximate number of key and value bytes of the stored trie nodes
+    /// and storage roots.
+    pub fn memory_usage(&self) -> usize {
+        self.state.memory_usage.load(Ordering::Relaxed)
+    }
+
+    /// Number of trie nodes stored.
+    pub fn trie_node_count(&self) -> usize {
+        self.state.read_all().shards.iter().map(|shard| shard.len()).sum()
+    }
+
+    /// Write the whole store to a new file at `path`, overwriting it if it
+    /// exists.
+    ///
+    /// # File Format
+    ///
+    /// - **Header**: `b"TRIEDBMM"` || `u8` version || `u8` persisted flag
+    ///   || `u64 BE` block number || `B256` state root
+    /// - **Trie nodes**: `u64 BE` count, then per node in path order
+    ///   `u32 BE` path length || path || `u32 BE` blob length || blob
+    /// - **Storage roots**: `u64 BE` count, then per account in hashed
+    ///   address order `hashed_address || storage_root`
+    pub fn dump_to_file(&self, path: impl AsRef<Path>) -> io::Result<()> {
+        let guard = self.state.read_all();
+        let mut writer = BufWriter::new(File::create(path)?);
+
+        let persist_state = guard.layer.persist_state;
+        let (block_number, state_root) = persist_state.unwrap_or((0, B256::ZERO));
+        writer.write_all(MEMORY_DB_MAGIC)?;
+        writer.write_all(&[MEMORY_DB_VERSION, persist_state.is_some() as u8])?;
+        writer.write_all(&block_number.to_be_bytes())?;
+        writer.write_all(state_root.as_slice())?;
+
+        // The shards are ordered by path on their own, the dump across them
+        let mut trie_nodes: Vec<_> = guard.shards.iter().flat_map(|shard| shard.iter()).collect();
+        trie_nodes.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        writer.write_all(&(trie_nodes.len() as u64).to_be_bytes())?;
+        for (path, blob) in trie_nodes {
+            write_bytes(&mut writer, path)?;
+            write_bytes(&mut writer, blob)?;
+        }
+        writer.write_all(&(guard.layer.storage_roots.len() as u64).to_be_bytes())?;
+        for (hashed_address, storage_root) in guard.layer.storage_roots.iter() {
+            writer.write_all(hashed_address.as_slice())?;
+            writer.write_all(storage_root.as_slice())?;
+        }
+
+        writer.into_inner().map_err(|e| e.into_error())?.sync_all()
+    }
+
+    /// Load a store written by [`dump_to_file`](Self::dump_to_file).
+    ///
+    /// Files that are truncated, carry trailing data or were written by
+    /// another format version fail with [`io::ErrorKind::InvalidData`].
+    pub fn load_from_file(path: impl AsRef<Path>) -> io::Result<Self> {
+        let mut reader = BufReader::new(File::open(path)?);
+
+        let mut header = [0u8; 8 + 2 + 8 + 32];
+        read_exact(&mut reader, &mut header)?;
+        if &header[..8] != MEMORY_DB_MAGIC {
+            return Err(invalid_data("Not a MemoryDB dump".to_string()));
+        }
+        if header[8] != MEMORY_DB_VERSION {
+            return Err(invalid_data(format!("Unsupported MemoryDB dump version {}", header[8])));
+        }
+        let block_number = u64::from_be_bytes(header[10..18].try_into().expect("8 bytes"));
+        let state_root = B256::from_slice(&header[18..50]);
+        let persist_state = match header[9] {
+            0 => None,
+            1 => Some((block_number, state_root)),
+            flag => return Err(invalid_data(format!("Invalid persisted state flag {}", flag))),
+        };
+
+        let mut shards = vec![BTreeMap::new(); OVERLAY_SHARD_COUNT];
+        for _ in 0..read_u64(&mut reader)? {
+            let path = read_bytes(&mut reader)?;
+            let blob = read_bytes(&mut reader)?;
+            shards[shard_index(&path)].insert(path, blob.into());
+        }
+        let mut storage_roots = BTreeMap::new();
+        let mut entry = [0u8; 64];
+        for _ in 0..read_u64(&mut reader)? {
+            read_exact(&mut reader, &mut entry)?;
+            storage_roots.insert(B256::from_slice(&entry[..32]), B256::from_slice(&entry[32..]));
+        }
+        if reader.read(&mut [0u8; 1])? != 0 {
+            return Err(invalid_data("MemoryDB dump has trailing data".to_string()));
+        }
+
+        let layer = MemoryLayer { storage_roots: Arc::new(storage_roots), persist_state };
+        let state = MemoryState::with_contents(shards.into_iter().map(Arc::new).collect(), layer);
+        Ok(Self { state: Arc::new(state), memory_limit: None })
+    }
+}
+
+impl TrieDatabase for MemoryDB {
+    type Error = MemoryLimitExceeded;
+
+    fn get_trie_node(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        Ok(self.state.shard(path).read().unwrap().get(path).cloned())
+    }
+
+    fn insert_trie_node(&self, path: &[u8], data: Vec<u8>) -> Result<(), Self::Error> {
+        let mut shard = self.state.shard(path).write().unwrap();
+        let old = shard.get(path).map_or(0, |blob| trie_node_size(path, blob));
+        self.state.resize(old, trie_node_size(path, &data), self.memory_limit)?;
+        Arc::make_mut(&mut *shard).insert(path.to_vec(), data.into());
+        self.state.record_write();
+        Ok(())
+    }
+
+    fn contains_trie_node(&self, path: &[u8]) -> Result<bool, Self::Error> {
+        Ok(self.state.shard(path).read().unwrap().contains_key(path))
+    }
+
+    fn remove_trie_node(&self, path: &[u8]) {
+        let mut shard = self.state.shard(path).write().unwrap();
+        if shard.contains_key(path) {
+            let blob = Arc::make_mut(&mut *shard).remove(path).expect("node is stored");
+            self.state.resize(trie_node_size(path, &blob), 0, None).expect("removals always pass");
+        }
+        self.state.record_write();
+    }
+
+    fn iter_trie_node_range(&self, start: &[u8], end: Option<&[u8]>, limit: usize) -> Result<Vec<(Vec<u8>, Bytes)>, Self::Error> {
+        let guard = self.state.read_all();
+        // The first `limit` nodes of the range are among the first `limit` of each shard
+        let mut nodes: Vec<(Vec<u8>, Bytes)> = guard.shards.iter()
+            .flat_map(|shard| {
+                shard.range(start.to_vec()..)
+                    .take_while(|(path, _)| end.map_or(true, |end| path.as_slice() < end))
+                    .take(limit)
+                    .map(|(path, blob)| (path.clone(), blob.clone()))
+            })
+            .collect();
+        nodes.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        nodes.truncate(limit);
+        Ok(nodes)
+    }
+
+    fn get_storage_root(&self, hashed_address: B256) -> Result<Option<B256>, Self::Error> {
+        Ok(self.state.layer.read().unwrap().storage_roots.get(&hashed_address).copied())
+    }
+
+    fn commit_difflayer(&self, block_number: u64, state_root: B256, difflayer: &Option<Arc<DiffLayer>>) -> Result<(), Self::Error> {
+        let mut guard = self.state.lock_all();
+        if let Some(difflayer) = difflayer {
+            // Previous node per written path, to undo the layer if it's rejected
+            let mut undo = Vec::new();
+            let (mut removed, mut added) = (0, 0);
+            // Ranges apply before the nodes written in the same block
+            for (start, end) in difflayer.deleted_ranges.iter().filter(|(start, end)| start < end) {
+                for shard in guard.shards.iter_mut() {
+                    let wiped: Vec<Vec<u8>> = shard.range(start.clone()..end.clone()).map(|(path, _)| path.clone()).collect();
+                    for path in wiped {
+                        let blob = Arc::make_mut(&mut **shard).remove(&path).expect("wiped node is stored");
+                        removed += trie_node_size(&path, &blob);
+                        undo.push((path, Some(blob)));
+                    }
+                }
+            }
+            for (path, node) in &difflayer.diff_nodes {
+                let shard = Arc::make_mut(&mut *guard.shards[shard_index(path)]);
+                let old = match &node.blob {
+                    Some(blob) if !node.is_deleted() => {
+                        added += trie_node_size(path, blob);
+                        shard.insert(path.clone(), blob.clone())
+                    }
+                    _ => shard.remove(path),
+                };
+                removed += old.as_ref().map_or(0, |blob| trie_node_size(path, blob));
+                undo.push((path.clone(), old));
+            }
+            added += difflayer.diff_storage_roots.keys().filter(|hashed_address| !guard.layer.storage_roots.contains_key(*hashed_address)).count()
+                * STORAGE_ROOT_ENTRY_SIZE;
+
+            if let Err(e) = self.state.resize(removed, added, self.memory_limit) {
+                for (path, old) in undo.into_iter().rev() {
+                    let shard = Arc::make_mut(&mut *guard.shards[shard_index(&path)]);
+                    match old {
+                        Some(blob) => shard.insert(path, blob),
+                        None => shard.remove(&path),
+                    };
+                }
+                return Err(e);
+            }
+            if !difflayer.diff_storage_roots.is_empty() {
+                Arc::make_mut(&mut guard.layer.storage_roots).extend(&difflayer.diff_storage_roots);
+            }
+        }
+        guard.layer.persist_state = Some((block_number, state_root));
+        self.state.record_write();
+        Ok(())
+    }
+
+    fn latest_persist_state(&self) -> Result<(u64, B256), Self::Error> {
+        Ok(self.state.layer.read().unwrap().persist_state.unwrap_or((0, EMPTY_ROOT_HASH)))
+    }
+
+    fn clear_cache(&self) {}
+}
+
+fn write_bytes(writer: &mut impl Write, bytes: &[u8]) -> io::Result<()> {
+    let len = u32::try_from(bytes.len()).map_err(|_| invalid_data(format!("Entry of {} bytes is too large to dump", bytes.len())))?;
+    writer.write_all(&len.to_be_bytes())?;
+    writer.write_all(bytes)
+}
+
+fn read_exact(reader: &mut impl Read, buf: &mut [u8]) -> io::Result<()> {
+    reader.read_exact(buf).map_err(|e| match e.kind() {
+        io::ErrorKind::UnexpectedEof => invalid_data("Truncated MemoryDB dump".to_string()),
+        _ => e,
+    })
+}
+
+fn read_u64(reader: &mut impl Read) -> io::Result<u64> {
+    let mut bytes = [0u8; 8];
+    read_exact(reader, &mut bytes)?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+fn read_bytes(reader: &mut impl Read) -> io::Result<Vec<u8>> {
+    let mut len = [0u8; 4];
+    read_exact(reader, &mut len)?;
+    let len = u32::from_be_bytes(len) as u64;
+    // Grown as the data is read, a corrupt length can't force a huge allocation
+    let mut bytes = Vec::new();
+    reader.by_ref().take(len).read_to_end(&mut bytes)?;
+    if bytes.len() as u64 != len {
+        return Err(invalid_data("Truncated MemoryDB dump".to_string()));
+    }
+    Ok(bytes)
+}
+
+fn invalid_data(message: String) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, message)
+}
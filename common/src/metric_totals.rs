@@ -0,0 +1,119 @@
+//! Process-wide metric totals backing metrics snapshots.
+//!
+//! Metrics recorded through the `metrics` facade can't be read back, so the
+//! trie crates mirror every recorded value into these atomics. They sum over
+//! all instances of a component; gauges keep the last value set by any of them.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+
+/// Total of a counter.
+#[derive(Debug)]
+pub struct CounterTotal(AtomicU64);
+
+impl CounterTotal {
+    /// Create a zero total.
+    pub const fn new() -> Self {
+        Self(AtomicU64::new(0))
+    }
+
+    /// Add `value` to the total.
+    pub fn increment(&self, value: u64) {
+        self.0.fetch_add(value, Ordering::Relaxed);
+    }
+
+    /// Get the current total.
+    pub fn get(&self) -> u64 {
+        self.0.load(Ordering::Relaxed)
+    }
+}
+
+impl Default for CounterTotal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Last value of a gauge.
+#[derive(Debug)]
+pub struct GaugeValue(AtomicU64);
+
+impl GaugeValue {
+    /// Create a gauge at zero.
+    pub const fn new() -> Self {
+        // 0.0 is all zero bits
+        Self(AtomicU64::new(0))
+    }
+
+    /// Set the gauge to `value`.
+    pub fn set(&self, value: f64) {
+        self.0.store(value.to_bits(), Ordering::Relaxed);
+    }
+
+    /// Add `value` to the gauge.
+    pub fn increment(&self, value: f64) {
+        let _ = self.0.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| Some((f64::from_bits(bits) + value).to_bits()));
+    }
+
+    /// Subtract `value` from the gauge.
+    pub fn decrement(&self, value: f64) {
+        self.increment(-value);
+    }
+
+    /// Get the current value.
+    pub fn get(&self) -> f64 {
+        f64::from_bits(self.0.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for GaugeValue {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Count and sum of the samples recorded in a histogram.
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct HistogramSummary {
+    /// Number of recorded samples
+    pub count: u64,
+    /// Sum of the recorded samples
+    pub sum: f64,
+}
+
+impl HistogramSummary {
+    /// Mean of the recorded samples, `None` without samples.
+    pub fn mean(&self) -> Option<f64> {
+        (self.count > 0).then(|| self.sum / self.count as f64)
+    }
+}
+
+/// Totals of a histogram.
+#[derive(Debug)]
+pub struct HistogramTotal {
+    count: AtomicU64,
+    sum: AtomicU64,
+}
+
+impl HistogramTotal {
+    /// Create a histogram total without samples.
+    pub const fn new() -> Self {
+        Self { count: AtomicU64::new(0), sum: AtomicU64::new(0) }
+    }
+
+    /// Record a sample.
+    pub fn record(&self, value: f64) {
+        let _ = self.sum.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |bits| Some((f64::from_bits(bits) + value).to_bits()));
+        self.count.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// Get the count and sum of the recorded samples.
+    pub fn get(&self) -> HistogramSummary {
+        HistogramSummary { count: self.count.load(Ordering::Relaxed), sum: f64::from_bits(self.sum.load(Ordering::Relaxed)) }
+    }
+}
+
+impl Default for HistogramTotal {
+    fn default() -> Self {
+        Self::new()
+    }
+}
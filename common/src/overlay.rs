@@ -0,0 +1,742 @@
+//! In-memory write layer over a trie database.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{BTreeMap, HashMap};
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard};
+
+use alloy_primitives::{Bytes, B256};
+use reth_metrics::{
+    metrics::{Counter, Gauge},
+    Metrics,
+};
+
+use crate::audit::{AuditRecord, AUDIT_RECORD_LEN};
+use crate::difflayer::{DiffLayer, SlotCountChange};
+use crate::traits::{TrieDatabase, TrieDBErrorSource};
+
+/// A [`TrieDatabase`] buffering all writes in memory on top of a base
+/// database, e.g. a PathDB.
+///
+/// Reads see the buffered writes first and fall back to the base, so a
+/// block can be executed speculatively without touching the base. The
+/// writes are applied to the base in their original order by
+/// [`flatten_into_base`](Self::flatten_into_base), or dropped by
+/// [`discard`](Self::discard). Clones share the write layer.
+///
+/// Trie node inserts and removals are buffered in shards keyed by path
+/// hash, so parallel storage trie commits rarely contend; a global
+/// sequence number keeps the order of all writes for the flatten.
+///
+/// Buffered writes are lost on process exit; `shutdown` only shuts down
+/// the base.
+///
+/// With [`with_memory_limit`](Self::with_memory_limit), writes taking the
+/// buffered bytes past the limit fail with [`MemoryLimitExceeded`], so
+/// runaway growth in tests surfaces as an error instead of an OOM kill.
+#[derive(Debug, Clone)]
+pub struct OverlayDB<Base> {
+    base: Base,
+    state: Arc<OverlayState>,
+    /// Maximum number of buffered bytes, `None` for no limit
+    memory_limit: Option<usize>,
+}
+
+/// A write rejected by an [`OverlayDB`] or a [`MemoryDB`](crate::MemoryDB)
+/// because it would take the bytes held in memory past the memory limit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, thiserror::Error)]
+#[error("Memory limit of {limit} bytes exceeded: {usage} bytes held, write of {write} bytes")]
+pub struct MemoryLimitExceeded {
+    /// Bytes held before the write
+    pub usage: usize,
+    /// Bytes the rejected write would have held
+    pub write: usize,
+    /// The memory limit
+    pub limit: usize,
+}
+
+/// Memory is only freed by the caller, so a retry fails the same way.
+impl TrieDBErrorSource for MemoryLimitExceeded {}
+
+/// A point in the writes of an [`OverlayDB`] or a [`MemoryDB`](crate::MemoryDB)
+/// to roll back to, taken by [`OverlayDB::savepoint`] or
+/// [`MemoryDB::savepoint`](crate::MemoryDB::savepoint).
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct Savepoint {
+    /// Sequence number of the first write after the savepoint
+    pub(crate) seq: u64,
+}
+
+/// Metrics for the `OverlayDB`.
+#[derive(Metrics, Clone)]
+#[metrics(scope = "rust.eth.triedb.overlay")]
+struct OverlayMetrics {
+    /// Bytes of keys and values buffered by all overlays
+    memory_usage: Gauge,
+    /// Number of writes buffered by all overlays
+    buffered_writes: Gauge,
+    /// Counter of writes rejected for exceeding the memory limit
+    memory_limit_rejections: Counter,
+}
+
+/// Number of trie node shards of an [`OverlayDB`], and of a
+/// [`MemoryDB`](crate::MemoryDB).
+pub(crate) const OVERLAY_SHARD_COUNT: usize = 16;
+
+/// The buffered writes of an [`OverlayDB`], shared by its clones.
+///
+/// Locks are taken shard first, then the layer, and all shards in index
+/// order, so writers of single shards and writers of all of them can't
+/// deadlock.
+struct OverlayState {
+    /// Buffered trie node inserts and removals, by path hash
+    shards: Box<[RwLock<NodeShard>]>,
+    /// All other buffered writes
+    layer: RwLock<OverlayLayer>,
+    /// Sequence number of the next write
+    next_seq: AtomicU64,
+    /// Sum of the sizes of the buffered writes
+    memory_usage: AtomicUsize,
+    /// Number of buffered writes
+    buffered_writes: AtomicUsize,
+    metrics: OverlayMetrics,
+}
+
+/// Buffered trie node writes of the paths hashing to one shard.
+#[derive(Debug, Default)]
+struct NodeShard {
+    writes: Vec<(u64, OverlayWrite)>,
+    /// Latest trie node per path, `None` for removed nodes
+    nodes: HashMap<Vec<u8>, Option<Bytes>>,
+}
+
+/// Buffered writes other than trie node inserts and removals.
+#[derive(Debug, Default)]
+struct OverlayLayer {
+    writes: Vec<(u64, OverlayWrite)>,
+    view: OverlayView,
+}
+
+/// Exclusive access to all shards and the layer of an [`OverlayState`].
+struct OverlayGuard<'a> {
+    shards: Vec<RwLockWriteGuard<'a, NodeShard>>,
+    layer: RwLockWriteGuard<'a, OverlayLayer>,
+}
+
+impl std::fmt::Debug for OverlayState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("OverlayState")
+            .field("buffered_writes", &self.buffered_writes.load(Ordering::Relaxed))
+            .field("memory_usage", &self.memory_usage.load(Ordering::Relaxed))
+            .finish_non_exhaustive()
+    }
+}
+
+pub(crate) fn shard_index(path: &[u8]) -> usize {
+    let mut hasher = DefaultHasher::new();
+    path.hash(&mut hasher);
+    (hasher.finish() % OVERLAY_SHARD_COUNT as u64) as usize
+}
+
+impl OverlayState {
+    fn new() -> Self {
+        Self {
+            shards: (0..OVERLAY_SHARD_COUNT).map(|_| RwLock::new(NodeShard::default())).collect(),
+            layer: RwLock::new(OverlayLayer::default()),
+            next_seq: AtomicU64::new(0),
+            memory_usage: AtomicUsize::new(0),
+            buffered_writes: AtomicUsize::new(0),
+            metrics: OverlayMetrics::default(),
+        }
+    }
+
+    fn shard(&self, path: &[u8]) -> &RwLock<NodeShard> {
+        &self.shards[shard_index(path)]
+    }
+
+    fn lock_all(&self) -> OverlayGuard<'_> {
+        OverlayGuard {
+            shards: self.shards.iter().map(|shard| shard.write().unwrap()).collect(),
+            layer: self.layer.write().unwrap(),
+        }
+    }
+
+    /// Account for a write of `size` bytes, unless it takes the buffered
+    /// bytes past `limit`.
+    fn reserve(&self, size: usize, limit: Option<usize>) -> Result<(), MemoryLimitExceeded> {
+        match limit {
+            Some(limit) => {
+                self.memory_usage.fetch_update(Ordering::Relaxed, Ordering::Relaxed, |usage| {
+                    (usage + size <= limit).then_some(usage + size)
+                }).map_err(|usage| {
+                    self.metrics.memory_limit_rejections.increment(1);
+                    MemoryLimitExceeded { usage, write: size, limit }
+                })?;
+            }
+            None => {
+                self.memory_usage.fetch_add(size, Ordering::Relaxed);
+            }
+        }
+        self.buffered_writes.fetch_add(1, Ordering::Relaxed);
+        self.metrics.memory_usage.increment(size as f64);
+        self.metrics.buffered_writes.increment(1.0);
+        Ok(())
+    }
+
+    /// Take back the accounting of `writes` writes of `size` bytes in total.
+    fn release(&self, size: usize, writes: usize) {
+        self.memory_usage.fetch_sub(size, Ordering::Relaxed);
+        self.buffered_writes.fetch_sub(writes, Ordering::Relaxed);
+        self.metrics.memory_usage.decrement(size as f64);
+        self.metrics.buffered_writes.decrement(writes as f64);
+    }
+
+    /// The trie node at `path`: `Some(node)` if the overlay decides it,
+    /// `None` if the base has to be read, with the locks to hold while it is.
+    fn trie_node(&self, path: &[u8]) -> (Option<Option<Bytes>>, OverlayReadGuard<'_>) {
+        let shard = self.shard(path).read().unwrap();
+        if let Some(node) = shard.nodes.get(path) {
+            return (Some(node.clone()), OverlayReadGuard { _shard: shard, _layer: None });
+        }
+        let layer = self.layer.read().unwrap();
+        let node = layer.view.is_range_deleted(path).then_some(None);
+        (node, OverlayReadGuard { _shard: shard, _layer: Some(layer) })
+    }
+}
+
+impl Drop for OverlayState {
+    fn drop(&mut self) {
+        // The gauges sum over all overlays, take this one's share back out
+        let (memory_usage, buffered_writes) = (*self.memory_usage.get_mut(), *self.buffered_writes.get_mut());
+        self.release(memory_usage, buffered_writes);
+    }
+}
+
+/// Read locks keeping a trie node lookup consistent with a concurrent flatten.
+struct OverlayReadGuard<'a> {
+    _shard: RwLockReadGuard<'a, NodeShard>,
+    _layer: Option<RwLockReadGuard<'a, OverlayLayer>>,
+}
+
+impl NodeShard {
+    fn push(&mut self, seq: u64, write: OverlayWrite) {
+        match &write {
+            OverlayWrite::InsertTrieNode(path, data) => {
+                self.nodes.insert(path.clone(), Some(Bytes::from(data.clone())));
+            }
+            OverlayWrite::RemoveTrieNode(path) => {
+                self.nodes.insert(path.clone(), None);
+            }
+            _ => unreachable!("only trie node writes are sharded"),
+        }
+        self.writes.push((seq, write));
+    }
+}
+
+impl OverlayGuard<'_> {
+    /// Buffer `write` with sequence number `seq`.
+    fn push(&mut self, seq: u64, write: OverlayWrite) {
+        match &write {
+            OverlayWrite::InsertTrieNode(path, _) | OverlayWrite::RemoveTrieNode(path) => {
+                self.shards[shard_index(path)].push(seq, write);
+                return;
+            }
+            OverlayWrite::CommitDiffLayer(_, _, Some(difflayer)) => {
+                // Ranges first, nodes written by the same block are kept
+                for (start, end) in &difflayer.deleted_ranges {
+                    for shard in self.shards.iter_mut() {
+                        shard.nodes.retain(|path, _| path < start || path >= end);
+                    }
+                }
+                for (path, node) in &difflayer.diff_nodes {
+                    let blob = if node.is_deleted() { None } else { node.blob.clone() };
+                    self.shards[shard_index(path)].nodes.insert(path.clone(), blob);
+                }
+            }
+            _ => {}
+        }
+        self.layer.view.apply(&write);
+        self.layer.writes.push((seq, write));
+    }
+
+    /// Remove all buffered writes, in sequence order.
+    fn take_writes(&mut self) -> Vec<(u64, OverlayWrite)> {
+        let mut writes = std::mem::take(&mut self.layer.writes);
+        for shard in self.shards.iter_mut() {
+            writes.append(&mut shard.writes);
+            shard.nodes.clear();
+        }
+        self.layer.view = OverlayView::default();
+        writes.sort_unstable_by_key(|(seq, _)| *seq);
+        writes
+    }
+}
+
+/// A buffered write, replayed on the base by `OverlayDB::flatten_into_base`.
+#[derive(Debug, Clone)]
+enum OverlayWrite {
+    InsertTrieNode(Vec<u8>, Vec<u8>),
+    RemoveTrieNode(Vec<u8>),
+    CommitDiffLayer(u64, B256, Option<Arc<DiffLayer>>),
+    AuditRecord(AuditRecord),
+    AccountSnapshot(B256, Option<Bytes>),
+    StorageSnapshot(B256, B256, Option<Bytes>),
+}
+
+/// The combined effect of the buffered writes other than trie nodes.
+#[derive(Debug, Default)]
+struct OverlayView {
+    /// Trie node key ranges `[start, end)` wiped by committed diff layers
+    deleted_ranges: Vec<(Vec<u8>, Vec<u8>)>,
+    /// Latest committed storage root per account
+    storage_roots: HashMap<B256, B256>,
+    /// Committed diff layers with storage roots, by block, for `get_storage_root_at`
+    storage_root_layers: Vec<(u64, Arc<DiffLayer>)>,
+    /// Latest committed code hash per account
+    code_hashes: HashMap<B256, Option<B256>>,
+    /// Committed slot count changes per account, in commit order
+    slot_count_changes: HashMap<B256, Vec<SlotCountChange>>,
+    persist_state: Option<(u64, B256)>,
+    audit_records: Vec<AuditRecord>,
+    /// Latest account snapshot entry per account, `None` for removed entries
+    account_snapshots: BTreeMap<B256, Option<Bytes>>,
+    /// Latest storage snapshot entry per slot, `None` for removed entries
+    storage_snapshots: BTreeMap<(B256, B256), Option<Bytes>>,
+}
+
+impl OverlayView {
+    fn apply(&mut self, write: &OverlayWrite) {
+        match write {
+            OverlayWrite::InsertTrieNode(..) | OverlayWrite::RemoveTrieNode(..) => {}
+            OverlayWrite::CommitDiffLayer(block_number, state_root, difflayer) => {
+                self.persist_state = Some((*block_number, *state_root));
+                let Some(difflayer) = difflayer else {
+                    return;
+                };
+                self.deleted_ranges.extend(difflayer.deleted_ranges.iter().cloned());
+                if !difflayer.diff_storage_roots.is_empty() {
+                    self.storage_roots.extend(&difflayer.diff_storage_roots);
+                    self.storage_root_layers.push((*block_number, difflayer.clone()));
+                }
+                self.code_hashes.extend(&difflayer.code_hashes);
+                for (hashed_address, change) in &difflayer.slot_count_changes {
+                    self.slot_count_changes.entry(*hashed_address).or_default().push(*change);
+                }
+                self.audit_records.extend(difflayer.audit_record);
+            }
+            OverlayWrite::AuditRecord(record) => self.audit_records.push(*record),
+            OverlayWrite::AccountSnapshot(hashed_address, account) => {
+                self.account_snapshots.insert(*hashed_address, account.clone());
+            }
+            OverlayWrite::StorageSnapshot(hashed_address, hashed_key, value) => {
+                self.storage_snapshots.insert((*hashed_address, *hashed_key), value.clone());
+            }
+        }
+    }
+
+    /// Whether a buffered diff layer wiped the trie node at `path`.
+    fn is_range_deleted(&self, path: &[u8]) -> bool {
+        self.deleted_ranges.iter().any(|(start, end)| start.as_slice() <= path && path < end.as_slice())
+    }
+}
+
+impl OverlayWrite {
+    /// Approximate number of key and value bytes held by the write.
+    fn size(&self) -> usize {
+        match self {
+            Self::InsertTrieNode(path, data) => path.len() + data.len(),
+            Self::RemoveTrieNode(path) => path.len(),
+            Self::CommitDiffLayer(_, _, difflayer) => {
+                let Some(difflayer) = difflayer else {
+                    return 8 + 32;
+                };
+                8 + 32
+                    + difflayer.diff_nodes.iter().map(|(path, node)| path.len() + node.size()).sum::<usize>()
+                    + difflayer.diff_storage_roots.len() * 64
+                    + difflayer.deleted_ranges.iter().map(|(start, end)| start.len() + end.len()).sum::<usize>()
+                    + difflayer.code_hashes.len() * 64
+                    + difflayer.slot_count_changes.len() * (32 + 8)
+                    + difflayer.audit_record.map_or(0, |_| AUDIT_RECORD_LEN)
+            }
+            Self::AuditRecord(_) => AUDIT_RECORD_LEN,
+            Self::AccountSnapshot(_, account) => 32 + account.as_ref().map_or(0, Bytes::len),
+            Self::StorageSnapshot(_, _, value) => 64 + value.as_ref().map_or(0, Bytes::len),
+        }
+    }
+
+    fn apply_to<Base: TrieDatabase>(&self, base: &Base) -> Result<(), Base::Error> {
+        match self {
+            Self::InsertTrieNode(path, data) => base.insert_trie_node(path, data.clone()),
+            Self::RemoveTrieNode(path) => {
+                base.remove_trie_node(path);
+                Ok(())
+            }
+            Self::CommitDiffLayer(block_number, state_root, difflayer) => base.commit_difflayer(*block_number, *state_root, difflayer),
+            Self::AuditRecord(record) => base.put_audit_record(record),
+            Self::AccountSnapshot(hashed_address, account) => base.put_account_snapshot(*hashed_address, account.as_deref()),
+            Self::StorageSnapshot(hashed_address, hashed_key, value) => {
+                base.put_storage_snapshot(*hashed_address, *hashed_key, value.as_deref())
+            }
+        }
+    }
+}
+
+impl<Base: TrieDatabase> OverlayDB<Base> {
+    /// Create an overlay without buffered writes over `base`.
+    pub fn new(base: Base) -> Self {
+        Self { base, state: Arc::new(OverlayState::new()), memory_limit: None }
+    }
+
+    /// Reject writes taking the buffered bytes past `memory_limit`.
+    ///
+    /// Trie node removals are always buffered, `remove_trie_node` can't fail.
+    pub fn with_memory_limit(mut self, memory_limit: usize) -> Self {
+        self.memory_limit = Some(memory_limit);
+        self
+    }
+
+    /// Approximate number of key and value bytes of the buffered writes.
+    ///
+    /// The read view holds a second copy of most of them, so the heap used
+    /// by the overlay is up to about twice this.
+    pub fn memory_usage(&self) -> usize {
+        self.state.memory_usage.load(Ordering::Relaxed)
+    }
+
+    /// The base database.
+    pub fn base(&self) -> &Base {
+        &self.base
+    }
+
+    /// Create a child overlay reading through this one.
+    ///
+    /// The child only buffers its own writes, which this overlay doesn't see
+    /// until the child is flattened into it. It holds no snapshot: writes to
+    /// this overlay after the child was created show through in the child
+    /// where it didn't overwrite them. Use [`MemoryDB::fork`](crate::MemoryDB::fork)
+    /// to branch independent states. The child inherits the memory limit for
+    /// its own writes.
+    pub fn child(&self) -> OverlayDB<Self>
+    where
+        Base: Clone,
+    {
+        OverlayDB { base: self.clone(), state: Arc::new(OverlayState::new()), memory_limit: self.memory_limit }
+    }
+
+    /// The trie nodes decided by the buffered writes, ordered by path, with
+    /// `None` for removed nodes.
+    ///
+    /// Nodes inside ranges wiped by buffered diff layers are only listed if
+    /// written after the wipe. The order doesn't depend on the sharding, so
+    /// dumps of the committed node set can be compared across runs.
+    pub fn buffered_trie_nodes(&self) -> BTreeMap<Vec<u8>, Option<Bytes>> {
+        let guard = self.state.lock_all();
+        guard.shards.iter().flat_map(|shard| shard.nodes.iter()).map(|(path, node)| (path.clone(), node.clone())).collect()
+    }
+
+    /// Number of buffered writes.
+    pub fn pending_writes(&self) -> usize {
+        self.state.buffered_writes.load(Ordering::Relaxed)
+    }
+
+    /// Apply the buffered writes to the base in their original order.
+    ///
+    /// Reads and writes wait until the writes are applied. If a write fails,
+    /// the writes applied before it are dropped from the overlay and the
+    /// failed one and all after it stay buffered, so the flatten can be
+    /// retried.
+    pub fn flatten_into_base(&self) -> Result<(), Base::Error> {
+        let mut guard = self.state.lock_all();
+        let mut writes = guard.take_writes().into_iter();
+        while let Some((seq, write)) = writes.next() {
+            if let Err(e) = write.apply_to(&self.base) {
+                for (seq, write) in std::iter::once((seq, write)).chain(writes) {
+                    guard.push(seq, write);
+                }
+                return Err(e);
+            }
+            self.state.release(write.size(), 1);
+        }
+        Ok(())
+    }
+
+    /// Mark the current point in the writes for [`rollback_to`](Self::rollback_to).
+    pub fn savepoint(&self) -> Savepoint {
+        // All writes with a lower sequence number are buffered once the locks are taken
+        let _guard = self.state.lock_all();
+        Savepoint { seq: self.state.next_seq.load(Ordering::Relaxed) }
+    }
+
+    /// Undo the writes buffered since `savepoint`, e.g. to simulate a reorg,
+    /// returning the number of writes undone.
+    ///
+    /// Writes flattened into the base since the savepoint stay in the base,
+    /// only the buffered ones are undone. Savepoints taken after `savepoint`
+    /// are invalidated by writes made after the rollback.
+    pub fn rollback_to(&self, savepoint: Savepoint) -> usize {
+        let mut guard = self.state.lock_all();
+        let (kept, undone): (Vec<_>, Vec<_>) = guard.take_writes().into_iter().partition(|(seq, _)| *seq < savepoint.seq);
+        // The read views are rebuilt from the kept writes
+        for (seq, write) in kept {
+            guard.push(seq, write);
+        }
+        self.state.release(undone.iter().map(|(_, write)| write.size()).sum(), undone.len());
+        undone.len()
+    }
+
+    /// Drop the buffered writes, leaving the base untouched.
+    pub fn discard(&self) {
+        let mut guard = self.state.lock_all();
+        let writes = guard.take_writes();
+        self.state.release(writes.iter().map(|(_, write)| write.size()).sum(), writes.len());
+    }
+
+    /// Buffer `write` unless it takes the buffered bytes past `limit`.
+    fn buffer(&self, write: OverlayWrite, limit: Option<usize>) -> Result<(), MemoryLimitExceeded> {
+        self.state.reserve(write.size(), limit)?;
+        match &write {
+            // The sequence number is taken under the lock, so it orders the
+            // write after every write it may overwrite
+            OverlayWrite::InsertTrieNode(path, _) | OverlayWrite::RemoveTrieNode(path) => {
+                let mut shard = self.state.shard(path).write().unwrap();
+                shard.push(self.state.next_seq.fetch_add(1, Ordering::Relaxed), write);
+            }
+            OverlayWrite::CommitDiffLayer(..) => {
+                let mut guard = self.state.lock_all();
+                guard.push(self.state.next_seq.fetch_add(1, Ordering::Relaxed), write);
+            }
+            _ => {
+                let mut layer = self.state.layer.write().unwrap();
+                let seq = self.state.next_seq.fetch_add(1, Ordering::Relaxed);
+                layer.view.apply(&write);
+                layer.writes.push((seq, write));
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Merge up to `limit` overlay entries from `start` on, `None` for removed
+/// ones, with the entries of the base read by `read_base`.
+fn merge_range<K: Ord + Clone, E>(
+    overlay: Vec<(K, Option<Bytes>)>,
+    start: K,
+    limit: usize,
+    read_base: impl FnOnce(K, usize) -> Result<Vec<(K, Bytes)>, E>,
+) -> Result<Vec<(K, Bytes)>, E> {
+    // Every removed entry hides at most one base entry
+    let removed = overlay.iter().filter(|(_, value)| value.is_none()).count();
+    let base_limit = limit.saturating_add(removed);
+    let base = read_base(start, base_limit)?;
+    // A full page may stop before later base entries, overlay entries past it wait for the next page
+    let bound = if base.len() >= base_limit { base.last().map(|(key, _)| key.clone()) } else { None };
+
+    let mut merged: BTreeMap<K, Option<Bytes>> = base.into_iter().map(|(key, value)| (key, Some(value))).collect();
+    for (key, value) in overlay {
+        if bound.as_ref().map_or(true, |bound| &key <= bound) {
+            merged.insert(key, value);
+        }
+    }
+    Ok(merged.into_iter().filter_map(|(key, value)| Some((key, value?))).take(limit).collect())
+}
+
+impl<Base> TrieDatabase for OverlayDB<Base>
+where
+    Base: TrieDatabase,
+    Base::Error: From<MemoryLimitExceeded>,
+{
+    type Error = Base::Error;
+
+    fn get_trie_node(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        let (node, _guard) = self.state.trie_node(path);
+        match node {
+            Some(node) => Ok(node),
+            None => self.base.get_trie_node(path),
+        }
+    }
+
+    fn get_trie_nodes(&self, paths: &[Vec<u8>]) -> Result<Vec<Option<Bytes>>, Self::Error> {
+        // Paths are looked up one by one, a flatten in between can only move
+        // nodes from the overlay to the base
+        let mut nodes: Vec<Option<Option<Bytes>>> = paths.iter().map(|path| self.state.trie_node(path).0).collect();
+        let misses: Vec<Vec<u8>> = paths.iter().zip(&nodes).filter(|(_, node)| node.is_none()).map(|(path, _)| path.clone()).collect();
+        if !misses.is_empty() {
+            let mut base_nodes = self.base.get_trie_nodes(&misses)?.into_iter();
+            for node in nodes.iter_mut().filter(|node| node.is_none()) {
+                *node = base_nodes.next();
+            }
+        }
+        Ok(nodes.into_iter().map(Option::flatten).collect())
+    }
+
+    fn get_trie_node_verified(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        let (node, _guard) = self.state.trie_node(path);
+        match node {
+            Some(node) => Ok(node),
+            None => self.base.get_trie_node_verified(path),
+        }
+    }
+
+    fn insert_trie_node(&self, path: &[u8], data: Vec<u8>) -> Result<(), Self::Error> {
+        self.buffer(OverlayWrite::InsertTrieNode(path.to_vec(), data), self.memory_limit).map_err(Into::into)
+    }
+
+    fn contains_trie_node(&self, path: &[u8]) -> Result<bool, Self::Error> {
+        let (node, _guard) = self.state.trie_node(path);
+        match node {
+            Some(node) => Ok(node.is_some()),
+            None => self.base.contains_trie_node(path),
+        }
+    }
+
+    fn remove_trie_node(&self, path: &[u8]) {
+        // Without a limit the write can't be rejected
+        let _ = self.buffer(OverlayWrite::RemoveTrieNode(path.to_vec()), None);
+    }
+
+    fn iter_trie_node_range(&self, start: &[u8], end: Option<&[u8]>, limit: usize) -> Result<Vec<(Vec<u8>, Bytes)>, Self::Error> {
+        if limit == 0 {
+            return Ok(Vec::new());
+        }
+        // Held while the base is read, like `OverlayState::trie_node`
+        let shards: Vec<_> = self.state.shards.iter().map(|shard| shard.read().unwrap()).collect();
+        let layer = self.state.layer.read().unwrap();
+        let overlay = shards.iter()
+            .flat_map(|shard| shard.nodes.iter())
+            .filter(|(path, _)| start <= path.as_slice() && end.map_or(true, |end| path.as_slice() < end))
+            .map(|(path, node)| (path.clone(), node.clone()))
+            .collect();
+        merge_range(overlay, start.to_vec(), limit, |mut start, limit| {
+            // Base nodes in ranges wiped by buffered diff layers are hidden, read on until enough are left
+            let mut nodes = Vec::new();
+            loop {
+                let page = self.base.iter_trie_node_range(&start, end, limit)?;
+                let full = page.len() >= limit;
+                if let Some((last, _)) = page.last() {
+                    start = [last.as_slice(), &[0]].concat();
+                }
+                nodes.extend(page.into_iter().filter(|(path, _)| !layer.view.is_range_deleted(path)));
+                if !full || nodes.len() >= limit {
+                    return Ok(nodes);
+                }
+            }
+        })
+    }
+
+    fn get_storage_root(&self, hased_address: B256) -> Result<Option<B256>, Self::Error> {
+        let layer = self.state.layer.read().unwrap();
+        match layer.view.storage_roots.get(&hased_address) {
+            Some(root) => Ok(Some(*root)),
+            None => self.base.get_storage_root(hased_address),
+        }
+    }
+
+    fn commit_difflayer(&self, block_number: u64, state_root: B256, difflayer: &Option<Arc<DiffLayer>>) -> Result<(), Self::Error> {
+        self.buffer(OverlayWrite::CommitDiffLayer(block_number, state_root, difflayer.clone()), self.memory_limit).map_err(Into::into)
+    }
+
+    fn latest_persist_state(&self) -> Result<(u64, B256), Self::Error> {
+        let layer = self.state.layer.read().unwrap();
+        match layer.view.persist_state {
+            Some(state) => Ok(state),
+            None => self.base.latest_persist_state(),
+        }
+    }
+
+    fn clear_cache(&self) {
+        self.base.clear_cache();
+    }
+
+    fn put_audit_record(&self, record: &AuditRecord) -> Result<(), Self::Error> {
+        self.buffer(OverlayWrite::AuditRecord(*record), self.memory_limit).map_err(Into::into)
+    }
+
+    fn get_audit_records(&self, block_number: u64) -> Result<Vec<AuditRecord>, Self::Error> {
+        let layer = self.state.layer.read().unwrap();
+        let mut records = self.base.get_audit_records(block_number)?;
+        records.extend(layer.view.audit_records.iter().filter(|record| record.block_number == block_number));
+        Ok(records)
+    }
+
+    fn get_addresses_by_code_hash(&self, code_hash: B256) -> Result<Vec<B256>, Self::Error> {
+        let layer = self.state.layer.read().unwrap();
+        let code_hashes = &layer.view.code_hashes;
+        let mut addresses: Vec<B256> = self.base.get_addresses_by_code_hash(code_hash)?
+            .into_iter()
+            .filter(|hashed_address| !code_hashes.contains_key(hashed_address))
+            .collect();
+        addresses.extend(code_hashes.iter().filter(|(_, hash)| **hash == Some(code_hash)).map(|(hashed_address, _)| *hashed_address));
+        addresses.sort_unstable();
+        Ok(addresses)
+    }
+
+    fn get_storage_slot_count(&self, hashed_address: B256) -> Result<Option<u64>, Self::Error> {
+        let layer = self.state.layer.read().unwrap();
+        let base = self.base.get_storage_slot_count(hashed_address)?;
+        let Some(changes) = layer.view.slot_count_changes.get(&hashed_address) else {
+            return Ok(base);
+        };
+        // Applied one by one like the base does, deltas saturate at zero
+        let count = changes.iter().fold(base.unwrap_or_default(), |count, change| match change {
+            SlotCountChange::Reset(reset) => *reset,
+            SlotCountChange::Delta(delta) => count.saturating_add_signed(*delta),
+        });
+        Ok((count != 0).then_some(count))
+    }
+
+    fn get_storage_root_at(&self, hashed_address: B256, block_number: u64) -> Result<Option<B256>, Self::Error> {
+        let layer = self.state.layer.read().unwrap();
+        let buffered = layer.view.storage_root_layers.iter().rev()
+            .filter(|(block, _)| *block <= block_number)
+            .find_map(|(_, difflayer)| difflayer.get_storage_root(hashed_address));
+        match buffered {
+            Some(root) => Ok(Some(root)),
+            None => self.base.get_storage_root_at(hashed_address, block_number),
+        }
+    }
+
+    fn get_account_snapshot(&self, hashed_address: B256) -> Result<Option<Bytes>, Self::Error> {
+        let layer = self.state.layer.read().unwrap();
+        match layer.view.account_snapshots.get(&hashed_address) {
+            Some(account) => Ok(account.clone()),
+            None => self.base.get_account_snapshot(hashed_address),
+        }
+    }
+
+    fn put_account_snapshot(&self, hashed_address: B256, account: Option<&[u8]>) -> Result<(), Self::Error> {
+        self.buffer(OverlayWrite::AccountSnapshot(hashed_address, account.map(Bytes::copy_from_slice)), self.memory_limit).map_err(Into::into)
+    }
+
+    fn get_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<Bytes>, Self::Error> {
+        let layer = self.state.layer.read().unwrap();
+        match layer.view.storage_snapshots.get(&(hashed_address, hashed_key)) {
+            Some(value) => Ok(value.clone()),
+            None => self.base.get_storage_snapshot(hashed_address, hashed_key),
+        }
+    }
+
+    fn put_storage_snapshot(&self, hashed_address: B256, hashed_key: B256, value: Option<&[u8]>) -> Result<(), Self::Error> {
+        self.buffer(OverlayWrite::StorageSnapshot(hashed_address, hashed_key, value.map(Bytes::copy_from_slice)), self.memory_limit).map_err(Into::into)
+    }
+
+    fn iter_account_snapshot(&self, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        let layer = self.state.layer.read().unwrap();
+        let overlay = layer.view.account_snapshots.range(start_hash..)
+            .map(|(hashed_address, account)| (*hashed_address, account.clone()))
+            .collect();
+        merge_range(overlay, start_hash, limit, |start, limit| self.base.iter_account_snapshot(start, limit))
+    }
+
+    fn iter_storage_snapshot(&self, hashed_address: B256, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        let layer = self.state.layer.read().unwrap();
+        let overlay = layer.view.storage_snapshots.range((hashed_address, start_hash)..=(hashed_address, B256::repeat_byte(0xff)))
+            .map(|((_, hashed_key), value)| (*hashed_key, value.clone()))
+            .collect();
+        merge_range(overlay, start_hash, limit, |start, limit| self.base.iter_storage_snapshot(hashed_address, start, limit))
+    }
+
+    fn shutdown(&self) -> Result<(), Self::Error> {
+        self.base.shutdown()
+    }
+}
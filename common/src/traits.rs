@@ -1,8 +1,9 @@
 //! Database traits for trie operations.
 
 use std::sync::Arc;
-use alloy_primitives::B256;
+use alloy_primitives::{Bytes, B256};
 use auto_impl::auto_impl;
+use crate::audit::AuditRecord;
 use crate::difflayer::DiffLayer;
 
 /// A trait defining the interface for trie database operations.
@@ -56,7 +57,8 @@ pub trait TrieDatabase {
     /// # Returns
     ///
     /// * `Ok(Some(data))` - The node was found and `data` contains the encoded
-    ///   node data (RLP-encoded or similar format).
+    ///   node data (RLP-encoded or similar format). Backends may share `data`
+    ///   with their caches, so cloning it doesn't copy the node.
     /// * `Ok(None)` - The node does not exist in the database.
     /// * `Err(error)` - An error occurred during the database lookup.
     ///
@@ -64,7 +66,52 @@ pub trait TrieDatabase {
     ///
     /// This method may return errors related to database I/O, serialization,
     /// or backend-specific failures.
-    fn get_trie_node(&self, path: &[u8]) -> Result<Option<Vec<u8>>, Self::Error>;
+    fn get_trie_node(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error>;
+
+    /// Retrieves several trie nodes in one lookup.
+    ///
+    /// Used to resolve a whole level of trie paths at once, e.g. when
+    /// prefetching the access list of a block.
+    ///
+    /// # Arguments
+    ///
+    /// * `paths` - The paths of the nodes to retrieve.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(nodes)` - One entry per path in the order of `paths`, `None` for
+    ///   nodes that don't exist in the database.
+    /// * `Err(error)` - An error occurred during the database lookup.
+    ///
+    /// # Note
+    ///
+    /// The default implementation calls `get_trie_node` for each path;
+    /// backends with a vectorized read should override it.
+    fn get_trie_nodes(&self, paths: &[Vec<u8>]) -> Result<Vec<Option<Bytes>>, Self::Error> {
+        paths.iter().map(|path| self.get_trie_node(path)).collect()
+    }
+
+    /// Retrieves a trie node like `get_trie_node`, verifying its integrity
+    /// on disk even if the backend skips verification on regular reads.
+    ///
+    /// Used for consensus-critical reads, e.g. the nodes resolved while
+    /// updating the state, while prefetches keep the fast path.
+    ///
+    /// # Arguments
+    ///
+    /// * `path` - A byte slice representing the path to the trie node.
+    ///
+    /// # Returns
+    ///
+    /// Same as `get_trie_node`; a node failing verification is an error.
+    ///
+    /// # Note
+    ///
+    /// The default implementation calls `get_trie_node`; backends with
+    /// configurable checksum verification should override it.
+    fn get_trie_node_verified(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        self.get_trie_node(path)
+    }
 
     /// Inserts or updates a trie node in the database.
     ///
@@ -128,6 +175,34 @@ pub trait TrieDatabase {
     /// are not critical.
     fn remove_trie_node(&self, path: &[u8]);
 
+    /// Returns up to `limit` trie nodes with paths in `[start, end)`, in path
+    /// order.
+    ///
+    /// Used to walk a subtree, e.g. all storage trie nodes of one owner, for
+    /// pruning and debugging. Pass the path after the last returned one as
+    /// `start` to read the next page.
+    ///
+    /// # Arguments
+    ///
+    /// * `start` - The first path of the range, inclusive.
+    /// * `end` - The end of the range, exclusive; `None` walks to the last node.
+    /// * `limit` - The maximum number of nodes to return.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(nodes)` - Paths and encoded nodes; fewer than `limit` nodes
+    ///   means the end of the range was reached.
+    /// * `Err(error)` - An error occurred while reading the nodes.
+    ///
+    /// # Note
+    ///
+    /// The default implementation returns no nodes, for backends that can't
+    /// iterate.
+    fn iter_trie_node_range(&self, start: &[u8], end: Option<&[u8]>, limit: usize) -> Result<Vec<(Vec<u8>, Bytes)>, Self::Error> {
+        let _ = (start, end, limit);
+        Ok(Vec::new())
+    }
+
     /// Retrieves the storage trie root for a given account address.
     ///
     /// Each Ethereum account has its own storage trie, and this method retrieves
@@ -217,6 +292,17 @@ pub trait TrieDatabase {
     /// appropriate error or a default value (e.g., block 0 with empty root).
     fn latest_persist_state(&self) -> Result<(u64, B256), Self::Error>;
 
+    /// Returns a counter that changes whenever the persisted state does,
+    /// shared by all handles of the database.
+    ///
+    /// Lets callers cache `latest_persist_state`: a value read after
+    /// observing a generation stays valid while the generation is unchanged.
+    /// The default `None` means the implementation has no such counter, and
+    /// the persisted state must be read every time.
+    fn persist_generation(&self) -> Option<u64> {
+        None
+    }
+
     /// Clears all cached data in the database implementation.
     ///
     /// This method invalidates any internal caches maintained by the database
@@ -239,4 +325,298 @@ pub trait TrieDatabase {
     /// implementation-dependent, and some implementations may be no-ops if
     /// they don't maintain caches.
     fn clear_cache(&self);
+
+    /// Appends an audit record describing a block commit.
+    ///
+    /// Audit records form an append-only log keyed by block number, allowing
+    /// post-incident analysis of what was committed at each height.
+    ///
+    /// # Arguments
+    ///
+    /// * `record` - The `AuditRecord` to persist.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The record was persisted, or the backend keeps no audit log.
+    /// * `Err(error)` - An error occurred while writing the record.
+    ///
+    /// # Note
+    ///
+    /// The default implementation discards the record, so backends without
+    /// audit log support don't need to implement this method.
+    fn put_audit_record(&self, record: &AuditRecord) -> Result<(), Self::Error> {
+        let _ = record;
+        Ok(())
+    }
+
+    /// Returns the audit records of all commits at `block_number`.
+    ///
+    /// There is more than one record if the block was committed on several
+    /// forks.
+    ///
+    /// # Arguments
+    ///
+    /// * `block_number` - The block number to look up.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(records)` - The records, empty if none were written.
+    /// * `Err(error)` - An error occurred while reading the audit log.
+    ///
+    /// # Note
+    ///
+    /// The default implementation returns no records, matching the default
+    /// `put_audit_record`.
+    fn get_audit_records(&self, block_number: u64) -> Result<Vec<AuditRecord>, Self::Error> {
+        let _ = block_number;
+        Ok(Vec::new())
+    }
+
+    /// Returns the hashed addresses of all accounts whose code hash is
+    /// `code_hash`, in hashed address order.
+    ///
+    /// Served from the code hash index, which is updated from the
+    /// `code_hashes` of persisted diff layers.
+    ///
+    /// # Arguments
+    ///
+    /// * `code_hash` - The code hash to look up.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(addresses)` - The hashed addresses, empty if none are indexed.
+    /// * `Err(error)` - An error occurred while reading the index.
+    ///
+    /// # Note
+    ///
+    /// The default implementation returns no addresses, so backends without
+    /// an index don't need to implement this method.
+    fn get_addresses_by_code_hash(&self, code_hash: B256) -> Result<Vec<B256>, Self::Error> {
+        let _ = code_hash;
+        Ok(Vec::new())
+    }
+
+    /// Returns the approximate number of storage slots of the account
+    /// `hashed_address`.
+    ///
+    /// Maintained from the `slot_count_changes` of persisted diff layers.
+    ///
+    /// # Arguments
+    ///
+    /// * `hashed_address` - The hashed address of the account.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(count))` - The slot count of the account.
+    /// * `Ok(None)` - No slot count is stored for the account, e.g. because
+    ///   it has no storage or counts are not tracked.
+    /// * `Err(error)` - An error occurred while reading the count.
+    ///
+    /// # Note
+    ///
+    /// The default implementation returns `None`, so backends without slot
+    /// counts don't need to implement this method.
+    fn get_storage_slot_count(&self, hashed_address: B256) -> Result<Option<u64>, Self::Error> {
+        let _ = hashed_address;
+        Ok(None)
+    }
+
+    /// Returns the storage root of the account `hashed_address` as of the
+    /// block `block_number`.
+    ///
+    /// Serves historical queries, e.g. `eth_getProof` against an old state
+    /// root, on backends keeping storage roots per block.
+    ///
+    /// # Arguments
+    ///
+    /// * `hashed_address` - The hashed address of the account.
+    /// * `block_number` - The block the storage root is looked up at.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(root))` - The storage root of the account at the block.
+    /// * `Ok(None)` - No storage root is recorded for the account at the
+    ///   block, e.g. because history is not kept or was pruned.
+    /// * `Err(error)` - An error occurred while reading the history.
+    ///
+    /// # Note
+    ///
+    /// The default implementation returns `None`, so backends without
+    /// storage root history don't need to implement this method.
+    fn get_storage_root_at(&self, hashed_address: B256, block_number: u64) -> Result<Option<B256>, Self::Error> {
+        let _ = (hashed_address, block_number);
+        Ok(None)
+    }
+
+    /// Retrieves the encoded account of `hashed_address` from the flat
+    /// account snapshot.
+    ///
+    /// The snapshot maps hashed addresses straight to their accounts, so a
+    /// read doesn't walk the account trie.
+    ///
+    /// # Arguments
+    ///
+    /// * `hashed_address` - The hashed address of the account.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(account))` - The RLP-encoded account.
+    /// * `Ok(None)` - The snapshot has no entry for the account.
+    /// * `Err(error)` - An error occurred while reading the snapshot.
+    ///
+    /// # Note
+    ///
+    /// The default implementation returns `None`, so backends without a
+    /// flat snapshot don't need to implement this method.
+    fn get_account_snapshot(&self, hashed_address: B256) -> Result<Option<Bytes>, Self::Error> {
+        let _ = hashed_address;
+        Ok(None)
+    }
+
+    /// Stores or removes the encoded account of `hashed_address` in the flat
+    /// account snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `hashed_address` - The hashed address of the account.
+    /// * `account` - The RLP-encoded account, `None` to remove the entry.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The entry was written, or the backend keeps no snapshot.
+    /// * `Err(error)` - An error occurred while writing the entry.
+    ///
+    /// # Note
+    ///
+    /// The default implementation discards the entry, matching the default
+    /// `get_account_snapshot`.
+    fn put_account_snapshot(&self, hashed_address: B256, account: Option<&[u8]>) -> Result<(), Self::Error> {
+        let _ = (hashed_address, account);
+        Ok(())
+    }
+
+    /// Retrieves the value of a storage slot from the flat storage snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `hashed_address` - The hashed address of the account.
+    /// * `hashed_key` - The hashed key of the storage slot.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(Some(value))` - The slot value, in the format returned by
+    ///   `get_storage_with_hash_state`.
+    /// * `Ok(None)` - The snapshot has no entry for the slot.
+    /// * `Err(error)` - An error occurred while reading the snapshot.
+    ///
+    /// # Note
+    ///
+    /// The default implementation returns `None`, like `get_account_snapshot`.
+    fn get_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<Bytes>, Self::Error> {
+        let _ = (hashed_address, hashed_key);
+        Ok(None)
+    }
+
+    /// Stores or removes the value of a storage slot in the flat storage snapshot.
+    ///
+    /// # Arguments
+    ///
+    /// * `hashed_address` - The hashed address of the account.
+    /// * `hashed_key` - The hashed key of the storage slot.
+    /// * `value` - The slot value, `None` to remove the entry.
+    ///
+    /// # Note
+    ///
+    /// The default implementation discards the entry, like `put_account_snapshot`.
+    fn put_storage_snapshot(&self, hashed_address: B256, hashed_key: B256, value: Option<&[u8]>) -> Result<(), Self::Error> {
+        let _ = (hashed_address, hashed_key, value);
+        Ok(())
+    }
+
+    /// Returns up to `limit` entries of the flat account snapshot in hashed
+    /// address order, starting at `start_hash`.
+    ///
+    /// Used to serve snap sync account range requests and to rebuild the
+    /// account trie from flat state.
+    ///
+    /// # Arguments
+    ///
+    /// * `start_hash` - The first hashed address of the range, inclusive.
+    /// * `limit` - The maximum number of entries to return.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(entries)` - Hashed addresses and RLP-encoded accounts; fewer
+    ///   than `limit` entries means the end of the snapshot was reached.
+    /// * `Err(error)` - An error occurred while reading the snapshot.
+    ///
+    /// # Note
+    ///
+    /// The default implementation returns no entries, like `get_account_snapshot`.
+    fn iter_account_snapshot(&self, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        let _ = (start_hash, limit);
+        Ok(Vec::new())
+    }
+
+    /// Returns up to `limit` storage slots of `hashed_address` from the flat
+    /// storage snapshot in hashed key order, starting at `start_hash`.
+    ///
+    /// # Arguments
+    ///
+    /// * `hashed_address` - The hashed address of the account.
+    /// * `start_hash` - The first hashed key of the range, inclusive.
+    /// * `limit` - The maximum number of slots to return.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(slots)` - Hashed keys and slot values; fewer than `limit` slots
+    ///   means the end of the account's storage was reached.
+    /// * `Err(error)` - An error occurred while reading the snapshot.
+    ///
+    /// # Note
+    ///
+    /// The default implementation returns no slots, like `get_storage_snapshot`.
+    fn iter_storage_snapshot(&self, hashed_address: B256, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        let _ = (hashed_address, start_hash, limit);
+        Ok(Vec::new())
+    }
+
+    /// Prepares the database for process shutdown.
+    ///
+    /// Flushes buffered writes and persists whatever the backend needs for a
+    /// fast restart. The database may still be read afterwards.
+    ///
+    /// # Returns
+    ///
+    /// * `Ok(())` - The database is ready to be dropped.
+    /// * `Err(error)` - An error occurred while flushing.
+    ///
+    /// # Note
+    ///
+    /// The default implementation does nothing, for backends that write
+    /// through synchronously.
+    fn shutdown(&self) -> Result<(), Self::Error> {
+        Ok(())
+    }
 }
+
+/// The error type of a [`TrieDatabase`] as the source of the errors of the
+/// tries built on it.
+///
+/// Lets the tries wrap the errors of any backend while keeping them typed,
+/// callers can downcast the source to the error type of the backend. The
+/// classification works without knowing the backend.
+pub trait TrieDBErrorSource: std::error::Error + Send + Sync + 'static {
+    /// Whether the failed operation may succeed when retried.
+    fn is_retryable(&self) -> bool {
+        false
+    }
+
+    /// Whether the database holds corrupted data.
+    fn is_corruption(&self) -> bool {
+        false
+    }
+}
+
+/// For backends that can't fail.
+impl TrieDBErrorSource for std::convert::Infallible {}
@@ -0,0 +1,62 @@
+//! Sampled access frequency tracking of trie node paths.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+
+use schnellru::{ByLength, LruMap};
+
+/// Approximate read counts per trie node path.
+///
+/// One in `sample_rate` reads is recorded and counts for `sample_rate`
+/// reads, so counts are estimates. Only the `capacity` most recently sampled
+/// paths are tracked; paths read too rarely to be sampled again before
+/// being evicted are cold by definition.
+pub(crate) struct AccessTracker {
+    sample_rate: u64,
+    reads: AtomicU64,
+    counts: Mutex<LruMap<Vec<u8>, u64, ByLength>>,
+}
+
+impl AccessTracker {
+    /// Create a tracker sampling one in `sample_rate` reads of up to `capacity` paths.
+    pub(crate) fn new(sample_rate: u32, capacity: u32) -> Self {
+        Self {
+            sample_rate: u64::from(sample_rate.max(1)),
+            reads: AtomicU64::new(0),
+            counts: Mutex::new(LruMap::new(ByLength::new(capacity.max(1)))),
+        }
+    }
+
+    /// Count a read of `key` if it is sampled.
+    pub(crate) fn record(&self, key: &[u8]) {
+        if self.reads.fetch_add(1, Ordering::Relaxed) % self.sample_rate != 0 {
+            return;
+        }
+        let mut counts = self.counts.lock().unwrap();
+        match counts.get(key) {
+            Some(count) => *count += self.sample_rate,
+            None => {
+                counts.insert(key.to_vec(), self.sample_rate);
+            }
+        }
+    }
+
+    /// Up to `limit` tracked paths with their estimated read counts, most read first.
+    pub(crate) fn hot_set(&self, limit: usize) -> Vec<(Vec<u8>, u64)> {
+        let counts = self.counts.lock().unwrap();
+        let mut hot_set: Vec<(Vec<u8>, u64)> = counts.iter().map(|(key, count)| (key.clone(), *count)).collect();
+        hot_set.sort_unstable_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+        hot_set.truncate(limit);
+        hot_set
+    }
+
+    /// Whether `key` is tracked, i.e. was sampled recently.
+    pub(crate) fn is_tracked(&self, key: &[u8]) -> bool {
+        self.counts.lock().unwrap().peek(key).is_some()
+    }
+
+    /// Forget all counts.
+    pub(crate) fn clear(&self) {
+        self.counts.lock().unwrap().clear();
+    }
+}
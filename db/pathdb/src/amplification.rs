@@ -0,0 +1,68 @@
+//! Write and space amplification estimates for PathDB.
+
+use std::collections::VecDeque;
+
+/// Write and space amplification estimates, see [`PathDB::amplification_report`](crate::PathDB::amplification_report).
+#[derive(Debug, Clone, Copy, Default, PartialEq)]
+pub struct AmplificationReport {
+    /// Bytes written to disk (WAL, flushes and compactions) per byte committed
+    /// over the sliding window, `None` until the window has two samples or
+    /// when RocksDB statistics are disabled
+    pub write_amplification: Option<f64>,
+    /// Total SST file size per byte of estimated live data, `None` on an empty database
+    pub space_amplification: Option<f64>,
+    /// Bytes committed through `commit_difflayer` within the window
+    pub window_commit_bytes: u64,
+    /// Bytes written to disk within the window
+    pub window_disk_write_bytes: u64,
+}
+
+/// Cumulative byte counters sampled after each commit.
+#[derive(Debug, Clone, Copy)]
+struct Sample {
+    commit_bytes: u64,
+    disk_write_bytes: u64,
+}
+
+/// Sliding window of commit samples used to estimate write amplification.
+#[derive(Debug)]
+pub(crate) struct AmplificationWindow {
+    capacity: usize,
+    commit_bytes: u64,
+    samples: VecDeque<Sample>,
+}
+
+impl AmplificationWindow {
+    /// Create a window keeping the last `capacity` commits, at least two.
+    pub(crate) fn new(capacity: usize) -> Self {
+        let capacity = capacity.max(2);
+        Self { capacity, commit_bytes: 0, samples: VecDeque::with_capacity(capacity) }
+    }
+
+    /// Record a commit of `commit_bytes`, with the cumulative disk write bytes after it.
+    pub(crate) fn record(&mut self, commit_bytes: u64, disk_write_bytes: u64) {
+        self.commit_bytes += commit_bytes;
+        if self.samples.len() == self.capacity {
+            self.samples.pop_front();
+        }
+        self.samples.push_back(Sample { commit_bytes: self.commit_bytes, disk_write_bytes });
+    }
+
+    /// Bytes committed and written to disk between the oldest and newest sample.
+    pub(crate) fn deltas(&self) -> (u64, u64) {
+        match (self.samples.front(), self.samples.back()) {
+            (Some(first), Some(last)) => (
+                last.commit_bytes - first.commit_bytes,
+                last.disk_write_bytes.saturating_sub(first.disk_write_bytes),
+            ),
+            _ => (0, 0),
+        }
+    }
+
+    /// Disk write bytes per committed byte over the window.
+    pub(crate) fn write_amplification(&self) -> Option<f64> {
+        let (commit_bytes, disk_write_bytes) = self.deltas();
+        (commit_bytes > 0 && disk_write_bytes > 0).then(|| disk_write_bytes as f64 / commit_bytes as f64)
+    }
+}
+
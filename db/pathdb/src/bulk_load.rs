@@ -0,0 +1,230 @@
+//! Bulk loading of trie nodes through external SST files.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use rocksdb::{IngestExternalFileOptions, SstFileWriter};
+use rust_eth_triedb_common::CancellationToken;
+use tracing::{info, trace, warn};
+
+use crate::pathdb::{hex_key, PathDB, DEFAULT_COLUMN_FAMILY_NAME, OVERFLOW_COLUMN_FAMILY_NAME};
+use crate::traits::*;
+
+/// Default size at which the loader starts a new SST file.
+pub const DEFAULT_BULK_LOAD_FILE_SIZE: u64 = 256 * 1024 * 1024;
+
+/// Check run on every `(key, value)` added to an [`SstBulkLoader`], returning
+/// why the node is rejected.
+pub type BulkLoadValidator = Box<dyn Fn(&[u8], &[u8]) -> Result<(), String> + Send + Sync>;
+
+/// Writes trie nodes into external SST files and ingests them into a
+/// [`PathDB`] at once.
+///
+/// Meant for initial sync and state import, where writing hundreds of
+/// millions of nodes through batches is dominated by memtable and
+/// compaction work. Nodes must be added in strictly increasing key order.
+/// Nothing is visible before [`finish`](Self::finish); dropping the loader
+/// leaves the written files in its directory.
+///
+/// Ingested nodes bypass the deferred deletion queue, so the loader is meant
+/// for empty databases or key ranges without queued deletions.
+///
+/// Once the token set with [`with_cancellation`](Self::with_cancellation) is
+/// cancelled, [`add`](Self::add) fails and [`finish`](Self::finish) ingests
+/// the nodes added before, a sorted prefix of the input.
+///
+/// External data should be checked with a validator set through
+/// [`with_validator`](Self::with_validator), so bad nodes are rejected before
+/// they reach an SST file; the state trie crate provides one decoding every
+/// node.
+pub struct SstBulkLoader<'a> {
+    /// Database the files are ingested into.
+    db: &'a PathDB,
+    /// Directory the SST files are written to.
+    dir: PathBuf,
+    /// Size at which the current file is finished and a new one started.
+    target_file_size: u64,
+    /// Writer of the current file, `None` before the first node of a file.
+    writer: Option<SstFileWriter<'a>>,
+    /// Finished files of the trie node column family.
+    files: Vec<PathBuf>,
+    /// Overflow chunks of oversized values. Chunk keys don't follow the order
+    /// of the node keys, so they are sorted before being written.
+    overflow_chunks: BTreeMap<Vec<u8>, Vec<u8>>,
+    /// Last added key, to reject unsorted input.
+    last_key: Option<Vec<u8>>,
+    /// Number of nodes added.
+    entries: u64,
+    /// Token stopping further adds once cancelled.
+    cancel: CancellationToken,
+    /// Check rejecting invalid nodes before they are written.
+    validator: Option<BulkLoadValidator>,
+}
+
+impl<'a> SstBulkLoader<'a> {
+    /// Create a loader writing its SST files to `dir`, which is created if missing.
+    pub fn new(db: &'a PathDB, dir: impl AsRef<Path>) -> PathProviderResult<Self> {
+        if db.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot bulk load into a read-only database".to_string()));
+        }
+        let dir = dir.as_ref().to_path_buf();
+        fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            db,
+            dir,
+            target_file_size: DEFAULT_BULK_LOAD_FILE_SIZE,
+            writer: None,
+            files: Vec::new(),
+            overflow_chunks: BTreeMap::new(),
+            last_key: None,
+            entries: 0,
+            cancel: CancellationToken::new(),
+            validator: None,
+        })
+    }
+
+    /// Set the size at which the loader starts a new SST file.
+    pub fn with_target_file_size(mut self, target_file_size: u64) -> Self {
+        self.target_file_size = target_file_size;
+        self
+    }
+
+    /// Stop accepting nodes once `cancel` is cancelled.
+    pub fn with_cancellation(mut self, cancel: CancellationToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Reject nodes for which `validator` fails, see [`BulkLoadValidator`].
+    pub fn with_validator(mut self, validator: impl Fn(&[u8], &[u8]) -> Result<(), String> + Send + Sync + 'static) -> Self {
+        self.validator = Some(Box::new(validator));
+        self
+    }
+
+    /// Add a trie node; `key` must be greater than every key added before.
+    pub fn add(&mut self, key: &[u8], value: &[u8]) -> PathProviderResult<()> {
+        if self.cancel.is_cancelled() {
+            return Err(PathProviderError::InvalidOperation(format!("Bulk load cancelled after {} nodes", self.entries)));
+        }
+        if self.last_key.as_deref().is_some_and(|last_key| key <= last_key) {
+            return Err(PathProviderError::InvalidOperation(format!(
+                "Bulk load keys must be strictly increasing, got 0x{} after 0x{}",
+                hex_key(key), hex_key(self.last_key.as_deref().unwrap_or_default())
+            )));
+        }
+        if let Some(validator) = &self.validator {
+            validator(key, value).map_err(|reason| PathProviderError::InvalidOperation(format!(
+                "Bulk load rejected invalid trie node 0x{}: {}", hex_key(key), reason
+            )))?;
+        }
+
+        let db_key = self.db.db_key(key).into_owned();
+        let pointer = match self.db.config().overflow_threshold {
+            Some(threshold) if value.len() > threshold => {
+                self.db.mark_overflow_key(&db_key);
+                let (pointer, chunks) = self.db.split_overflow(&db_key, value)?;
+                self.overflow_chunks.extend(chunks.into_iter().map(|(chunk_key, chunk)| (chunk_key, chunk.to_vec())));
+                Some(pointer)
+            }
+            _ => None,
+        };
+
+        if self.writer.is_none() {
+            let path = self.dir.join(format!("trie_nodes_{:06}.sst", self.files.len()));
+            let writer = SstFileWriter::create(self.db.sst_options(DEFAULT_COLUMN_FAMILY_NAME)?);
+            writer.open(&path).map_err(|e| sst_error("open", &path, e))?;
+            self.writer = Some(writer);
+            self.files.push(path);
+        }
+        let writer = self.writer.as_mut().expect("writer opened above");
+        writer.put(&db_key, pointer.as_deref().unwrap_or(value))
+            .map_err(|e| PathProviderError::rocksdb("SST put error", e))?;
+        if writer.file_size() >= self.target_file_size {
+            self.finish_file()?;
+        }
+
+        self.last_key = Some(key.to_vec());
+        self.entries += 1;
+        Ok(())
+    }
+
+    /// Number of nodes added so far.
+    pub fn entries(&self) -> u64 {
+        self.entries
+    }
+
+    /// Finish the written files and ingest them, returning the number of
+    /// nodes loaded.
+    ///
+    /// Files are moved into the database where possible. The LRU caches are
+    /// cleared since they may hold entries the ingested nodes replace.
+    pub fn finish(mut self) -> PathProviderResult<u64> {
+        self.finish_file()?;
+        if self.entries == 0 {
+            return Ok(0);
+        }
+
+        let mut ingest_options = IngestExternalFileOptions::default();
+        ingest_options.set_move_files(true);
+
+        // Chunks first, so no ingested pointer refers to missing chunks
+        if !self.overflow_chunks.is_empty() {
+            let path = self.dir.join("overflow_chunks.sst");
+            let mut writer = SstFileWriter::create(self.db.sst_options(OVERFLOW_COLUMN_FAMILY_NAME)?);
+            writer.open(&path).map_err(|e| sst_error("open", &path, e))?;
+            for (chunk_key, chunk) in &self.overflow_chunks {
+                writer.put(chunk_key, chunk)
+                    .map_err(|e| PathProviderError::rocksdb("SST put error", e))?;
+            }
+            writer.finish().map_err(|e| sst_error("finish", &path, e))?;
+            self.ingest(OVERFLOW_COLUMN_FAMILY_NAME, &ingest_options, vec![path])?;
+        }
+        let files = std::mem::take(&mut self.files);
+        let file_count = files.len();
+        self.ingest(DEFAULT_COLUMN_FAMILY_NAME, &ingest_options, files)?;
+
+        self.db.clear_cache();
+        info!(target: "pathdb::bulk_load", "Bulk loaded {} trie nodes from {} SST files", self.entries, file_count);
+        Ok(self.entries)
+    }
+
+    /// Finish the current file, if any.
+    fn finish_file(&mut self) -> PathProviderResult<()> {
+        if let Some(mut writer) = self.writer.take() {
+            let path = self.files.last().expect("open writer has a file");
+            writer.finish().map_err(|e| sst_error("finish", path, e))?;
+            trace!(target: "pathdb::bulk_load", "Finished SST file {}", path.display());
+        }
+        Ok(())
+    }
+
+    /// Ingest `files` into `cf_name` and remove whatever is left of them.
+    fn ingest(&self, cf_name: &str, ingest_options: &IngestExternalFileOptions, files: Vec<PathBuf>) -> PathProviderResult<()> {
+        let cf = self.db.raw_db().cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+        self.db.raw_db().ingest_external_file_cf_opts(&cf, ingest_options, files.clone())
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB ingest in CF '{}' error", cf_name), e))?;
+
+        for file in files {
+            if let Err(e) = fs::remove_file(&file) {
+                if e.kind() != std::io::ErrorKind::NotFound {
+                    warn!(target: "pathdb::bulk_load", "Failed to remove ingested SST file {}: {}", file.display(), e);
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Bulk loading
+impl PathDB {
+    /// Create an [`SstBulkLoader`] writing its SST files to `dir`.
+    pub fn sst_bulk_loader(&self, dir: impl AsRef<Path>) -> PathProviderResult<SstBulkLoader<'_>> {
+        SstBulkLoader::new(self, dir)
+    }
+}
+
+fn sst_error(operation: &str, path: &Path, e: rocksdb::Error) -> PathProviderError {
+    PathProviderError::rocksdb(format!("SST {} error for {}", operation, path.display()), e)
+}
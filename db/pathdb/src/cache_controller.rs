@@ -0,0 +1,210 @@
+//! Runtime resizing of PathDB LRU caches within a shared memory budget.
+
+use std::fmt::Debug;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::pathdb::PathDB;
+use crate::sharded_cache::ShardedCache;
+
+/// Default smallest capacity in entries a cache is shrunk to.
+pub const DEFAULT_MIN_CACHE_CAPACITY: u32 = 1024;
+
+/// Estimated bytes per cache entry on top of its key and value: the key and
+/// value headers plus the slot and links of the LRU map.
+const ENTRY_OVERHEAD: usize = 96;
+
+/// Key and value size assumed for caches without entries to sample.
+const DEFAULT_ENTRY_SIZE: usize = 128;
+
+/// Number of entries sampled per cache to estimate the entry size.
+const ENTRY_SIZE_SAMPLE: usize = 1024;
+
+/// Capacity of one cache after a rebalance, see [`CacheController::rebalance`].
+#[derive(Debug, Clone, PartialEq)]
+pub struct CacheAllocation {
+    /// Cache name, `<db index>/trie_node` or `<db index>/storage_root`
+    pub name: String,
+    /// Share of the lookups since the previous rebalance answered from the
+    /// cache, `None` without lookups
+    pub hit_rate: Option<f64>,
+    /// Estimated size in bytes of one entry, overhead included
+    pub entry_size: usize,
+    /// Capacity in entries before the rebalance
+    pub previous_capacity: u32,
+    /// Capacity in entries after the rebalance
+    pub capacity: u32,
+}
+
+/// A cache under control and its lookup counters at the previous rebalance.
+struct ControlledCache {
+    name: String,
+    cache: Arc<ShardedCache>,
+    lookups: (u64, u64),
+}
+
+/// Resizes the LRU caches of one or more PathDB instances, e.g. the shards
+/// of a [`ShardedPathDB`](crate::ShardedPathDB), to share one memory budget
+/// instead of static per-config sizes.
+///
+/// Each rebalance splits the budget left by idle caches between the caches
+/// looked up since the previous rebalance, in proportion to their misses, as
+/// misses are the reads a larger cache could have saved. Shares become
+/// capacities through the sampled entry size. Capacities move half way to
+/// their target per rebalance to damp oscillation and never drop below the
+/// minimum capacity; idle caches keep theirs.
+///
+/// Caches are shared by clones of a PathDB, so resizes apply to them as
+/// well. A namespaced view owns its caches and has to be added on its own.
+pub struct CacheController {
+    memory_budget: usize,
+    min_capacity: u32,
+    caches: Vec<ControlledCache>,
+}
+
+impl Debug for CacheController {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CacheController")
+            .field("memory_budget", &self.memory_budget)
+            .field("min_capacity", &self.min_capacity)
+            .field("caches", &self.caches.iter().map(|controlled| &controlled.name).collect::<Vec<_>>())
+            .finish()
+    }
+}
+
+impl CacheController {
+    /// Create a controller sharing `memory_budget` bytes between the caches
+    /// of the databases added with [`CacheController::with_db`].
+    pub fn new(memory_budget: usize) -> Self {
+        Self { memory_budget, min_capacity: DEFAULT_MIN_CACHE_CAPACITY, caches: Vec::new() }
+    }
+
+    /// Set the smallest capacity in entries a cache is shrunk to.
+    pub fn with_min_capacity(mut self, min_capacity: u32) -> Self {
+        self.min_capacity = min_capacity.max(1);
+        self
+    }
+
+    /// Add the trie node and storage root caches of `db`.
+    pub fn with_db(mut self, db: &PathDB) -> Self {
+        let index = self.caches.len() / 2;
+        for (kind, cache) in ["trie_node", "storage_root"].into_iter().zip(db.lru_caches()) {
+            let lookups = cache.lookups();
+            self.caches.push(ControlledCache { name: format!("{}/{}", index, kind), cache, lookups });
+        }
+        self
+    }
+
+    /// The memory budget in bytes shared by the caches.
+    pub fn memory_budget(&self) -> usize {
+        self.memory_budget
+    }
+
+    /// Resize the caches from their lookups since the previous rebalance,
+    /// returning the allocation of every cache in the order they were added.
+    pub fn rebalance(&mut self) -> Vec<CacheAllocation> {
+        let mut allocations = Vec::with_capacity(self.caches.len());
+        let mut misses = Vec::with_capacity(self.caches.len());
+        for controlled in self.caches.iter_mut() {
+            let lookups = controlled.cache.lookups();
+            let (hits, missed) = (lookups.0 - controlled.lookups.0, lookups.1 - controlled.lookups.1);
+            controlled.lookups = lookups;
+
+            let capacity = controlled.cache.capacity();
+            allocations.push(CacheAllocation {
+                name: controlled.name.clone(),
+                hit_rate: (hits + missed > 0).then(|| hits as f64 / (hits + missed) as f64),
+                entry_size: controlled.cache.average_entry_size(ENTRY_SIZE_SAMPLE).unwrap_or(DEFAULT_ENTRY_SIZE) + ENTRY_OVERHEAD,
+                previous_capacity: capacity,
+                capacity,
+            });
+            misses.push(missed);
+        }
+
+        let idle_bytes: usize = allocations
+            .iter()
+            .filter(|allocation| allocation.hit_rate.is_none())
+            .map(|allocation| allocation.capacity as usize * allocation.entry_size)
+            .sum();
+        let active_budget = self.memory_budget.saturating_sub(idle_bytes) as u128;
+        // One extra miss per active cache keeps a share for caches that only hit
+        let total_weight: u128 = allocations
+            .iter()
+            .zip(&misses)
+            .filter(|(allocation, _)| allocation.hit_rate.is_some())
+            .map(|(_, missed)| *missed as u128 + 1)
+            .sum();
+
+        for ((allocation, missed), controlled) in allocations.iter_mut().zip(misses).zip(&self.caches) {
+            if allocation.hit_rate.is_none() {
+                continue;
+            }
+            let target_bytes = active_budget * (missed as u128 + 1) / total_weight;
+            let target = (target_bytes / allocation.entry_size as u128).min(u32::MAX as u128) as u32;
+            let capacity = (allocation.previous_capacity / 2 + target / 2).max(self.min_capacity);
+            if capacity != allocation.previous_capacity {
+                controlled.cache.set_capacity(capacity);
+                allocation.capacity = capacity;
+            }
+        }
+        allocations
+    }
+}
+
+/// Handle of a background thread rebalancing a [`CacheController`] every
+/// `interval`. The thread stops when the handle is dropped.
+#[derive(Debug)]
+pub struct CacheControllerWorker {
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl CacheControllerWorker {
+    /// Spawn a worker rebalancing `controller` every `interval`.
+    pub fn spawn(mut controller: CacheController, interval: Duration) -> std::io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker_stop = stop.clone();
+        let handle = thread::Builder::new()
+            .name("pathdb-cache-controller".to_string())
+            .spawn(move || {
+                thread::park_timeout(interval);
+                while !worker_stop.load(Ordering::Acquire) {
+                    for allocation in controller.rebalance() {
+                        if allocation.capacity != allocation.previous_capacity {
+                            debug!(
+                                target: "pathdb::cache_controller",
+                                "Resized cache {} from {} to {} entries, hit rate {:?}",
+                                allocation.name, allocation.previous_capacity, allocation.capacity, allocation.hit_rate
+                            );
+                        }
+                    }
+                    thread::park_timeout(interval);
+                }
+            })?;
+
+        Ok(Self { stop, handle: Some(handle) })
+    }
+
+    /// Stop the worker and wait for the in-flight rebalance to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.stop.store(true, Ordering::Release);
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for CacheControllerWorker {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
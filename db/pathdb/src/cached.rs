@@ -0,0 +1,207 @@
+//! Read-through trie node cache in front of a trie database.
+
+use std::sync::{Arc, RwLock};
+
+use alloy_primitives::{Bytes, B256};
+use rust_eth_triedb_common::{AuditRecord, DiffLayer, TrieDatabase};
+
+use crate::pathdb::PathDB;
+use crate::sharded_cache::ShardedCache;
+
+/// Number of shards of the node cache of a [`CachedDB`].
+const CACHED_DB_SHARD_COUNT: usize = 16;
+
+/// A [`TrieDatabase`] keeping recently read trie nodes of a base database,
+/// by default a PathDB, in an in-memory LRU tier.
+///
+/// Meant as a larger alternative to the node cache of the PathDB, sized
+/// independently of it. Writes through the wrapper go to the base and drop
+/// the cached nodes they touch: inserted and removed nodes, the nodes of a
+/// committed difflayer and every cached node in its deleted ranges. Writes
+/// to the base bypassing the wrapper are not seen until
+/// [`clear_cache`](TrieDatabase::clear_cache). Clones share the cache.
+#[derive(Clone)]
+pub struct CachedDB<Base = PathDB> {
+    base: Base,
+    cache: Arc<ShardedCache>,
+    /// Held for reading while a miss is filled and for writing while the base
+    /// is written, so a fill can't cache a node a concurrent write replaced
+    writes: Arc<RwLock<()>>,
+}
+
+impl<Base: std::fmt::Debug> std::fmt::Debug for CachedDB<Base> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("CachedDB")
+            .field("base", &self.base)
+            .field("capacity", &self.cache.capacity())
+            .field("cached_nodes", &self.cache.len())
+            .finish()
+    }
+}
+
+impl<Base: TrieDatabase> CachedDB<Base> {
+    /// Cache up to `capacity` trie nodes of `base`.
+    pub fn new(base: Base, capacity: u32) -> Self {
+        Self {
+            base,
+            cache: Arc::new(ShardedCache::new(capacity, CACHED_DB_SHARD_COUNT)),
+            writes: Arc::new(RwLock::new(())),
+        }
+    }
+
+    /// The base database.
+    pub fn base(&self) -> &Base {
+        &self.base
+    }
+
+    /// Number of cached trie nodes, including cached absences.
+    pub fn cached_nodes(&self) -> usize {
+        self.cache.len()
+    }
+
+    /// Number of node lookups answered from the cache and from the base.
+    pub fn lookups(&self) -> (u64, u64) {
+        self.cache.lookups()
+    }
+
+    /// Drop the cached nodes `difflayer` changes.
+    fn invalidate_difflayer(&self, difflayer: &DiffLayer) {
+        let mut guard = self.cache.lock_all();
+        guard.remove_ranges(&difflayer.deleted_ranges);
+        for path in difflayer.diff_nodes.keys() {
+            guard.remove(path);
+        }
+    }
+}
+
+impl<Base: TrieDatabase> TrieDatabase for CachedDB<Base> {
+    type Error = Base::Error;
+
+    fn get_trie_node(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        if let Some(node) = self.cache.lookup(path) {
+            return Ok(node);
+        }
+        let _fill = self.writes.read().unwrap();
+        let node = self.base.get_trie_node(path)?;
+        self.cache.insert(path.to_vec(), node.clone());
+        Ok(node)
+    }
+
+    fn get_trie_nodes(&self, paths: &[Vec<u8>]) -> Result<Vec<Option<Bytes>>, Self::Error> {
+        let mut nodes: Vec<Option<Option<Bytes>>> = paths.iter().map(|path| self.cache.lookup(path)).collect();
+        let misses: Vec<Vec<u8>> = paths.iter().zip(&nodes).filter(|(_, node)| node.is_none()).map(|(path, _)| path.clone()).collect();
+        if !misses.is_empty() {
+            let _fill = self.writes.read().unwrap();
+            let base_nodes = self.base.get_trie_nodes(&misses)?;
+            let mut base_nodes = misses.into_iter().zip(base_nodes);
+            for node in nodes.iter_mut().filter(|node| node.is_none()) {
+                let (path, base_node) = base_nodes.next().expect("one base node per miss");
+                self.cache.insert(path, base_node.clone());
+                *node = Some(base_node);
+            }
+        }
+        Ok(nodes.into_iter().map(Option::flatten).collect())
+    }
+
+    fn get_trie_node_verified(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        // Verification is about the stored node, skip the cache
+        self.base.get_trie_node_verified(path)
+    }
+
+    fn insert_trie_node(&self, path: &[u8], data: Vec<u8>) -> Result<(), Self::Error> {
+        let _write = self.writes.write().unwrap();
+        self.cache.remove(path);
+        self.base.insert_trie_node(path, data)
+    }
+
+    fn contains_trie_node(&self, path: &[u8]) -> Result<bool, Self::Error> {
+        match self.cache.lookup(path) {
+            Some(node) => Ok(node.is_some()),
+            None => self.base.contains_trie_node(path),
+        }
+    }
+
+    fn remove_trie_node(&self, path: &[u8]) {
+        let _write = self.writes.write().unwrap();
+        self.cache.remove(path);
+        self.base.remove_trie_node(path)
+    }
+
+    fn iter_trie_node_range(&self, start: &[u8], end: Option<&[u8]>, limit: usize) -> Result<Vec<(Vec<u8>, Bytes)>, Self::Error> {
+        self.base.iter_trie_node_range(start, end, limit)
+    }
+
+    fn get_storage_root(&self, hased_address: B256) -> Result<Option<B256>, Self::Error> {
+        self.base.get_storage_root(hased_address)
+    }
+
+    fn commit_difflayer(&self, block_number: u64, state_root: B256, difflayer: &Option<Arc<DiffLayer>>) -> Result<(), Self::Error> {
+        let _write = self.writes.write().unwrap();
+        if let Some(difflayer) = difflayer {
+            // Dropped even if the commit fails, the base may have applied part of it
+            self.invalidate_difflayer(difflayer);
+        }
+        self.base.commit_difflayer(block_number, state_root, difflayer)
+    }
+
+    fn latest_persist_state(&self) -> Result<(u64, B256), Self::Error> {
+        self.base.latest_persist_state()
+    }
+
+    fn persist_generation(&self) -> Option<u64> {
+        self.base.persist_generation()
+    }
+
+    fn clear_cache(&self) {
+        self.cache.clear();
+        self.base.clear_cache();
+    }
+
+    fn put_audit_record(&self, record: &AuditRecord) -> Result<(), Self::Error> {
+        self.base.put_audit_record(record)
+    }
+
+    fn get_audit_records(&self, block_number: u64) -> Result<Vec<AuditRecord>, Self::Error> {
+        self.base.get_audit_records(block_number)
+    }
+
+    fn get_addresses_by_code_hash(&self, code_hash: B256) -> Result<Vec<B256>, Self::Error> {
+        self.base.get_addresses_by_code_hash(code_hash)
+    }
+
+    fn get_storage_slot_count(&self, hashed_address: B256) -> Result<Option<u64>, Self::Error> {
+        self.base.get_storage_slot_count(hashed_address)
+    }
+
+    fn get_storage_root_at(&self, hashed_address: B256, block_number: u64) -> Result<Option<B256>, Self::Error> {
+        self.base.get_storage_root_at(hashed_address, block_number)
+    }
+
+    fn get_account_snapshot(&self, hashed_address: B256) -> Result<Option<Bytes>, Self::Error> {
+        self.base.get_account_snapshot(hashed_address)
+    }
+
+    fn put_account_snapshot(&self, hashed_address: B256, account: Option<&[u8]>) -> Result<(), Self::Error> {
+        self.base.put_account_snapshot(hashed_address, account)
+    }
+
+    fn get_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<Bytes>, Self::Error> {
+        self.base.get_storage_snapshot(hashed_address, hashed_key)
+    }
+
+    fn put_storage_snapshot(&self, hashed_address: B256, hashed_key: B256, value: Option<&[u8]>) -> Result<(), Self::Error> {
+        self.base.put_storage_snapshot(hashed_address, hashed_key, value)
+    }
+
+    fn iter_account_snapshot(&self, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        self.base.iter_account_snapshot(start_hash, limit)
+    }
+
+    fn iter_storage_snapshot(&self, hashed_address: B256, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        self.base.iter_storage_snapshot(hashed_address, start_hash, limit)
+    }
+
+    fn shutdown(&self) -> Result<(), Self::Error> {
+        self.base.shutdown()
+    }
+}
@@ -0,0 +1,58 @@
+//! Background worker keeping a secondary PathDB instance up to date.
+
+use std::sync::atomic::Ordering;
+use std::thread;
+use std::time::Duration;
+
+use tracing::error;
+
+use crate::pathdb::PathDB;
+use crate::worker::WorkerThread;
+use crate::traits::PathProviderResult;
+
+/// Handle of a background thread catching a secondary instance up with its primary.
+///
+/// Every `interval` the worker calls [`PathDB::try_catch_up_with_primary`].
+/// The thread stops when the handle is dropped or the database is closed
+/// with [`PathDB::close_gracefully`].
+#[derive(Debug)]
+pub struct CatchUpWorker {
+    thread: WorkerThread,
+}
+
+impl CatchUpWorker {
+    /// Spawn a worker catching `db` up with its primary every `interval`.
+    ///
+    /// Fails if `db` wasn't opened with [`PathDB::open_as_secondary`].
+    pub fn spawn(db: PathDB, interval: Duration) -> PathProviderResult<Self> {
+        // Fail early instead of logging the same error forever
+        db.try_catch_up_with_primary()?;
+
+        let registry = db.clone();
+        let worker = WorkerThread::spawn("pathdb-catch-up", move |worker_stop| {
+            while !worker_stop.load(Ordering::Acquire) && !db.is_closed() {
+                thread::park_timeout(interval);
+                if worker_stop.load(Ordering::Acquire) || db.is_closed() {
+                    break;
+                }
+                if let Err(e) = db.try_catch_up_with_primary() {
+                    error!(target: "pathdb::secondary", "Failed to catch up with primary: {}", e);
+                }
+            }
+        })?;
+        registry.register_worker(worker.clone());
+
+        Ok(Self { thread: worker })
+    }
+
+    /// Stop the worker and wait for the in-flight catch up to finish.
+    pub fn stop(self) {
+        self.thread.stop();
+    }
+}
+
+impl Drop for CatchUpWorker {
+    fn drop(&mut self) {
+        self.thread.stop();
+    }
+}
@@ -0,0 +1,81 @@
+//! On-disk checkpoints of a PathDB.
+
+use std::fs;
+use std::path::Path;
+
+use alloy_primitives::B256;
+use rocksdb::checkpoint::Checkpoint;
+use tracing::info;
+
+use rust_eth_triedb_common::TrieDatabase;
+
+use crate::pathdb::{PathDB, COLUMN_FAMILY_NAMES};
+use crate::traits::*;
+
+/// Size and persisted state of a PathDB, see [`PathDB::backup_info`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BackupInfo {
+    /// Total size in bytes of the live SST files of all PathDB column families
+    pub size_bytes: u64,
+    /// Latest persisted block number
+    pub block_number: u64,
+    /// State root of the latest persisted block
+    pub state_root: B256,
+}
+
+/// Checkpoints
+impl PathDB {
+    /// Create a consistent on-disk checkpoint of the database at `path`.
+    ///
+    /// The checkpoint hard-links SST files where possible, so it is cheap and
+    /// can be taken while the node keeps writing. `path` must not exist yet.
+    /// Writes that are only in the memtables are flushed first.
+    pub fn create_checkpoint(&self, path: impl AsRef<Path>) -> PathProviderResult<()> {
+        let path = path.as_ref();
+        let checkpoint = Checkpoint::new(self.raw_db())
+            .map_err(|e| PathProviderError::rocksdb("Failed to create checkpoint object", e))?;
+        checkpoint.create_checkpoint(path)
+            .map_err(|e| PathProviderError::rocksdb(format!("Failed to create checkpoint at {}", path.display()), e))?;
+
+        info!(target: "pathdb::checkpoint", "Created checkpoint at {}", path.display());
+        Ok(())
+    }
+
+    /// Restore a checkpoint created by [`create_checkpoint`](Self::create_checkpoint)
+    /// into `db_path` and open it.
+    ///
+    /// `db_path` must not exist yet; the checkpoint itself is left untouched so
+    /// it can be restored again.
+    pub fn restore_from_checkpoint(checkpoint_path: impl AsRef<Path>, db_path: &str, config: PathProviderConfig) -> PathProviderResult<Self> {
+        let checkpoint_path = checkpoint_path.as_ref();
+        if Path::new(db_path).exists() {
+            return Err(PathProviderError::InvalidOperation(format!("Restore target {} already exists", db_path)));
+        }
+
+        fs::create_dir_all(db_path)?;
+        for entry in fs::read_dir(checkpoint_path)? {
+            let entry = entry?;
+            if entry.file_type()?.is_file() {
+                fs::copy(entry.path(), Path::new(db_path).join(entry.file_name()))?;
+            }
+        }
+
+        info!(target: "pathdb::checkpoint", "Restored checkpoint {} into {}", checkpoint_path.display(), db_path);
+        Self::new(db_path, config)
+    }
+
+    /// Get the on-disk size and the latest persisted state of the database,
+    /// e.g. to decide when to take the next checkpoint.
+    pub fn backup_info(&self) -> PathProviderResult<BackupInfo> {
+        let mut size_bytes = 0;
+        for cf_name in COLUMN_FAMILY_NAMES {
+            let cf = self.raw_db().cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+            let cf_size = self.raw_db().property_int_value_cf(&cf, "rocksdb.total-sst-files-size")
+                .map_err(|e| PathProviderError::rocksdb(format!("RocksDB property in CF '{}' error", cf_name), e))?;
+            size_bytes += cf_size.unwrap_or_default();
+        }
+
+        let (block_number, state_root) = self.latest_persist_state()?;
+        Ok(BackupInfo { size_bytes, block_number, state_root })
+    }
+}
@@ -0,0 +1,101 @@
+//! Sanity limits checked before a difflayer commit is written.
+
+use alloy_primitives::B256;
+use alloy_trie::EMPTY_ROOT_HASH;
+use rust_eth_triedb_common::{DiffLayer, TrieDatabase, TRIE_NODE_STORAGE_PREFIX};
+use tracing::warn;
+
+use crate::pathdb::PathDB;
+use crate::traits::*;
+
+/// Commit limits
+impl PathDB {
+    /// Check `difflayer` against the configured `CommitLimits`.
+    ///
+    /// With `CommitLimitAction::Reject` the first violation is returned as
+    /// `PathProviderError::CommitRejected`; with `CommitLimitAction::Warn`
+    /// every violation is logged and the commit goes ahead.
+    pub fn check_commit_limits(&self, block_number: u64, difflayer: &DiffLayer) -> PathProviderResult<()> {
+        check_commit_limits(&self.config().commit_limits, block_number, difflayer, |owner| self.get_storage_root(owner))
+    }
+}
+
+/// Check `difflayer` against `limits`, reading the current storage roots
+/// with `get_storage_root`, see [`PathDB::check_commit_limits`].
+pub(crate) fn check_commit_limits(
+    limits: &CommitLimits,
+    block_number: u64,
+    difflayer: &DiffLayer,
+    get_storage_root: impl Fn(B256) -> PathProviderResult<Option<B256>>,
+) -> PathProviderResult<()> {
+    if !limits.is_enabled() {
+        return Ok(());
+    }
+
+    for violation in commit_limit_violations(limits, difflayer, get_storage_root)? {
+        match limits.action {
+            CommitLimitAction::Reject => {
+                return Err(PathProviderError::CommitRejected(format!("block {}: {}", block_number, violation)));
+            }
+            CommitLimitAction::Warn => {
+                warn!(target: "pathdb::commit_limits", "Commit of block {} exceeds limit: {}", block_number, violation);
+            }
+        }
+    }
+    Ok(())
+}
+
+fn commit_limit_violations(
+    limits: &CommitLimits,
+    difflayer: &DiffLayer,
+    get_storage_root: impl Fn(B256) -> PathProviderResult<Option<B256>>,
+) -> PathProviderResult<Vec<String>> {
+    let mut violations = Vec::new();
+
+    if let Some(max_nodes) = limits.max_nodes {
+        if difflayer.diff_nodes.len() > max_nodes {
+            violations.push(format!("{} trie nodes, limit {}", difflayer.diff_nodes.len(), max_nodes));
+        }
+    }
+
+    if let Some(max_node_size) = limits.max_node_size {
+        let oversized = difflayer.diff_nodes
+            .iter()
+            .filter_map(|(key, node)| node.blob.as_ref().map(|blob| (key, blob.len())))
+            .filter(|(_, len)| *len > max_node_size)
+            .max_by_key(|(_, len)| *len);
+        if let Some((key, len)) = oversized {
+            violations.push(format!("trie node {:?} of {} bytes, limit {}", key, len, max_node_size));
+        }
+    }
+
+    if limits.check_storage_root_regressions {
+        for (owner, root) in difflayer.diff_storage_roots.iter() {
+            if *root != EMPTY_ROOT_HASH || storage_trie_dropped(difflayer, owner) {
+                continue;
+            }
+            if let Some(previous) = get_storage_root(*owner)? {
+                if previous != EMPTY_ROOT_HASH {
+                    violations.push(format!(
+                        "storage root of {:#x} regresses from {:#x} to empty without deleting its storage trie",
+                        owner, previous
+                    ));
+                }
+            }
+        }
+    }
+
+    Ok(violations)
+}
+
+/// Whether `difflayer` deletes the root node of the storage trie of `owner`,
+/// individually or through a deleted range.
+fn storage_trie_dropped(difflayer: &DiffLayer, owner: &B256) -> bool {
+    let mut root_key = Vec::with_capacity(TRIE_NODE_STORAGE_PREFIX.len() + B256::len_bytes());
+    root_key.extend_from_slice(TRIE_NODE_STORAGE_PREFIX);
+    root_key.extend_from_slice(owner.as_slice());
+    match difflayer.diff_nodes.get(&root_key) {
+        Some(node) => node.is_deleted(),
+        None => difflayer.is_range_deleted(&root_key),
+    }
+}
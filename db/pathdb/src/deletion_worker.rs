@@ -0,0 +1,44 @@
+//! Background worker draining the PathDB deletion queue.
+
+use tracing::{debug, error};
+
+use crate::pathdb::PathDB;
+use crate::worker::WorkerThread;
+
+/// Handle of a background thread processing queued trie node deletions.
+///
+/// Every `PathProviderConfig::deletion_interval` the worker deletes at most
+/// `PathProviderConfig::deletion_batch_size` queued nodes, which bounds the
+/// extra write load it puts on the database. The thread stops when the handle
+/// is dropped or the database is closed with [`PathDB::close_gracefully`].
+#[derive(Debug)]
+pub struct DeletionWorker {
+    thread: WorkerThread,
+}
+
+impl DeletionWorker {
+    /// Spawn a worker processing the deletion queue of `db`.
+    pub fn spawn(db: PathDB) -> std::io::Result<Self> {
+        let batch_size = db.config().deletion_batch_size;
+        let interval = db.config().deletion_interval;
+        let thread = WorkerThread::spawn_periodic("pathdb-deletion", db, interval, false, move |db| {
+            match db.process_deletion_queue(batch_size) {
+                Ok(0) => {}
+                Ok(deleted) => debug!(target: "pathdb::deletion", "Deleted {} queued trie nodes", deleted),
+                Err(e) => error!(target: "pathdb::deletion", "Failed to process deletion queue: {}", e),
+            }
+        })?;
+        Ok(Self { thread })
+    }
+
+    /// Stop the worker and wait for the in-flight batch to finish.
+    pub fn stop(self) {
+        self.thread.stop();
+    }
+}
+
+impl Drop for DeletionWorker {
+    fn drop(&mut self) {
+        self.thread.stop();
+    }
+}
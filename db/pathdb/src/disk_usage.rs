@@ -0,0 +1,138 @@
+//! Per column family key count and size estimates of a PathDB.
+
+use reth_metrics::{metrics::Gauge, Metrics};
+
+use crate::pathdb::PathDB;
+use crate::traits::*;
+
+/// Size estimates of one column family, see [`PathDB::disk_usage`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ColumnFamilyUsage {
+    /// Column family name
+    pub name: String,
+    /// Estimated number of keys
+    pub estimated_keys: u64,
+    /// Estimated size in bytes of the live data
+    pub live_data_bytes: u64,
+    /// Number of live SST files
+    pub sst_files: u64,
+    /// Total size in bytes of the live SST files
+    pub sst_files_bytes: u64,
+    /// Size in bytes of the active and unflushed memtables
+    pub memtable_bytes: u64,
+    /// Size in bytes of the block cache used by the column family. Column
+    /// families sharing `PathProviderConfig::block_cache_size` report the
+    /// usage of the whole shared cache
+    pub block_cache_bytes: u64,
+}
+
+/// Size estimates of all column families, see [`PathDB::disk_usage`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct DiskUsage {
+    /// Estimates per column family, sorted by name
+    pub column_families: Vec<ColumnFamilyUsage>,
+}
+
+impl DiskUsage {
+    /// Estimated number of keys over all column families.
+    pub fn estimated_keys(&self) -> u64 {
+        self.column_families.iter().map(|cf| cf.estimated_keys).sum()
+    }
+
+    /// Estimated size in bytes of the live data over all column families.
+    pub fn live_data_bytes(&self) -> u64 {
+        self.column_families.iter().map(|cf| cf.live_data_bytes).sum()
+    }
+
+    /// Total size in bytes of the live SST files over all column families.
+    pub fn sst_files_bytes(&self) -> u64 {
+        self.column_families.iter().map(|cf| cf.sst_files_bytes).sum()
+    }
+
+    /// Estimates of the column family `name`, if it exists.
+    pub fn column_family(&self, name: &str) -> Option<&ColumnFamilyUsage> {
+        self.column_families.iter().find(|cf| cf.name == name)
+    }
+}
+
+/// Disk usage gauges of one column family, see [`PathDB::report_disk_usage`].
+#[derive(Metrics, Clone)]
+#[metrics(scope = "rust.eth.triedb.pathdb.column_family")]
+struct ColumnFamilyMetrics {
+    /// Number of live SST files
+    sst_files: Gauge,
+    /// Total size in bytes of the live SST files
+    sst_files_bytes: Gauge,
+    /// Size in bytes of the active and unflushed memtables
+    memtable_bytes: Gauge,
+    /// Size in bytes of the block cache used by the column family
+    block_cache_bytes: Gauge,
+}
+
+/// Disk usage
+///
+/// Estimates come from RocksDB properties and cover the whole column family,
+/// including the keys of other namespaces sharing the database.
+impl PathDB {
+    /// Estimate the number of keys in the column family `cf_name`.
+    ///
+    /// Deletions and overwrites not yet compacted make the estimate drift,
+    /// it is meant for monitoring growth, not for exact counts.
+    pub fn estimate_num_keys(&self, cf_name: &str) -> PathProviderResult<u64> {
+        self.cf_int_property(cf_name, "rocksdb.estimate-num-keys")
+    }
+
+    /// Estimate the size in bytes of the live data in the column family `cf_name`.
+    pub fn estimate_live_data_size(&self, cf_name: &str) -> PathProviderResult<u64> {
+        self.cf_int_property(cf_name, "rocksdb.estimate-live-data-size")
+    }
+
+    /// Get the key count and size estimates of every column family, e.g. to
+    /// report trie database growth per column family.
+    pub fn disk_usage(&self) -> PathProviderResult<DiskUsage> {
+        let live_files = self.raw_db().live_files()
+            .map_err(|e| PathProviderError::rocksdb("Failed to list live files", e))?;
+        let column_families = self.column_families()
+            .into_iter()
+            .map(|name| {
+                Ok(ColumnFamilyUsage {
+                    estimated_keys: self.estimate_num_keys(&name)?,
+                    live_data_bytes: self.estimate_live_data_size(&name)?,
+                    sst_files: live_files.iter().filter(|file| file.column_family_name == name).count() as u64,
+                    sst_files_bytes: self.cf_int_property(&name, "rocksdb.total-sst-files-size")?,
+                    memtable_bytes: self.cf_int_property(&name, "rocksdb.size-all-mem-tables")?,
+                    block_cache_bytes: self.cf_int_property(&name, "rocksdb.block-cache-usage")?,
+                    name,
+                })
+            })
+            .collect::<PathProviderResult<Vec<_>>>()?;
+        Ok(DiskUsage { column_families })
+    }
+
+    /// Get the disk usage like [`PathDB::disk_usage`] and export it as gauges
+    /// labeled by metrics instance and column family, e.g. to chart the
+    /// growth of the trie node and storage root column families apart.
+    ///
+    /// Usually refreshed by a [`DiskUsageWorker`](crate::DiskUsageWorker).
+    pub fn report_disk_usage(&self) -> PathProviderResult<DiskUsage> {
+        let usage = self.disk_usage()?;
+        for cf in &usage.column_families {
+            let metrics = ColumnFamilyMetrics::new_with_labels(&[
+                ("instance", self.metrics_instance().to_string()),
+                ("column_family", cf.name.clone()),
+            ]);
+            metrics.sst_files.set(cf.sst_files as f64);
+            metrics.sst_files_bytes.set(cf.sst_files_bytes as f64);
+            metrics.memtable_bytes.set(cf.memtable_bytes as f64);
+            metrics.block_cache_bytes.set(cf.block_cache_bytes as f64);
+        }
+        Ok(usage)
+    }
+
+    pub(crate) fn cf_int_property(&self, cf_name: &str, property: &str) -> PathProviderResult<u64> {
+        let cf = self.raw_db().cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+        self.raw_db().property_int_value_cf(&cf, property)
+            .map(Option::unwrap_or_default)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB property in CF '{}' error", cf_name), e))
+    }
+}
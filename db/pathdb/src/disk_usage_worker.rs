@@ -0,0 +1,41 @@
+//! Background worker refreshing the PathDB disk usage gauges.
+
+use tracing::error;
+
+use crate::pathdb::PathDB;
+use crate::worker::WorkerThread;
+
+/// Handle of a background thread exporting the disk usage of a PathDB.
+///
+/// Every `PathProviderConfig::disk_usage_interval` the worker refreshes the
+/// per column family gauges with [`PathDB::report_disk_usage`]. The thread
+/// stops when the handle is dropped or the database is closed with
+/// [`PathDB::close_gracefully`].
+#[derive(Debug)]
+pub struct DiskUsageWorker {
+    thread: WorkerThread,
+}
+
+impl DiskUsageWorker {
+    /// Spawn a worker reporting the disk usage of `db`.
+    pub fn spawn(db: PathDB) -> std::io::Result<Self> {
+        let interval = db.config().disk_usage_interval;
+        let thread = WorkerThread::spawn_periodic("pathdb-disk-usage", db, interval, false, |db| {
+            if let Err(e) = db.report_disk_usage() {
+                error!(target: "pathdb::disk_usage", "Failed to report disk usage: {}", e);
+            }
+        })?;
+        Ok(Self { thread })
+    }
+
+    /// Stop the worker and wait for the in-flight refresh to finish.
+    pub fn stop(self) {
+        self.thread.stop();
+    }
+}
+
+impl Drop for DiskUsageWorker {
+    fn drop(&mut self) {
+        self.thread.stop();
+    }
+}
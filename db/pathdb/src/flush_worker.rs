@@ -0,0 +1,43 @@
+//! Background worker flushing the PathDB memtables periodically.
+
+use tracing::{error, trace};
+
+use crate::pathdb::PathDB;
+use crate::worker::WorkerThread;
+
+/// Handle of a background thread flushing the memtables of a PathDB.
+///
+/// Every `PathProviderConfig::flush_interval` the worker flushes the
+/// memtables of all column families with [`PathDB::flush_all`], so they are
+/// persisted while the node is idle and a crash with the WAL disabled loses
+/// at most one interval of writes. Flush
+/// durations are recorded like the ones of other flushes. The thread stops
+/// when the handle is dropped or the database is closed with
+/// [`PathDB::close_gracefully`].
+#[derive(Debug)]
+pub struct FlushWorker {
+    thread: WorkerThread,
+}
+
+impl FlushWorker {
+    /// Spawn a worker flushing `db`.
+    pub fn spawn(db: PathDB) -> std::io::Result<Self> {
+        let interval = db.config().flush_interval;
+        let thread = WorkerThread::spawn_periodic("pathdb-flush", db, interval, true, |db| match db.flush_all() {
+            Ok(()) => trace!(target: "pathdb::flush", "Flushed memtables"),
+            Err(e) => error!(target: "pathdb::flush", "Failed to flush memtables: {}", e),
+        })?;
+        Ok(Self { thread })
+    }
+
+    /// Stop the worker and wait for the in-flight flush to finish.
+    pub fn stop(self) {
+        self.thread.stop();
+    }
+}
+
+impl Drop for FlushWorker {
+    fn drop(&mut self) {
+        self.thread.stop();
+    }
+}
@@ -0,0 +1,43 @@
+//! Background worker dropping cache entries stale after external writes.
+
+use std::time::Duration;
+
+use tracing::debug;
+
+use crate::pathdb::PathDB;
+use crate::worker::WorkerThread;
+use crate::traits::PathProviderResult;
+
+/// Handle of a background thread keeping the caches of a PathDB coherent
+/// with writes made through the raw RocksDB handle.
+///
+/// Every `interval` the worker calls [`PathDB::invalidate_external_writes`].
+/// The thread stops when the handle is dropped or the database is closed
+/// with [`PathDB::close_gracefully`].
+#[derive(Debug)]
+pub struct InvalidationWorker {
+    thread: WorkerThread,
+}
+
+impl InvalidationWorker {
+    /// Spawn a worker checking `db` for external writes every `interval`.
+    pub fn spawn(db: PathDB, interval: Duration) -> PathProviderResult<Self> {
+        let thread = WorkerThread::spawn_periodic("pathdb-invalidation", db, interval, true, |db| {
+            if db.invalidate_external_writes() {
+                debug!(target: "pathdb::rocksdb", "Invalidated cache entries after external writes");
+            }
+        })?;
+        Ok(Self { thread })
+    }
+
+    /// Stop the worker and wait for the in-flight check to finish.
+    pub fn stop(self) {
+        self.thread.stop();
+    }
+}
+
+impl Drop for InvalidationWorker {
+    fn drop(&mut self) {
+        self.thread.stop();
+    }
+}
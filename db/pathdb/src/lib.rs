@@ -10,9 +10,60 @@
 
 pub mod pathdb;
 pub mod traits;
+pub mod deletion_worker;
+pub mod flush_worker;
+pub mod disk_usage_worker;
+pub mod catch_up_worker;
+pub mod invalidation_worker;
+pub mod checkpoint;
+pub mod disk_usage;
+pub mod sharded;
+pub mod snapshot_overlay;
+pub mod cache_controller;
+pub mod read_snapshot;
+pub mod commit_limits;
+pub mod repair;
+pub mod write_pressure;
+pub mod version_gc;
+pub mod migration;
+pub mod amplification;
+pub mod bulk_load;
+pub mod metrics_snapshot;
+pub mod snapshot_journal;
+pub mod snapshot_tree;
+pub mod storage_root_export;
+pub mod storage_root_history;
+pub mod cached;
+mod sharded_cache;
+mod negative_cache;
+mod overflow_filter;
+mod access_tracker;
+mod maintenance_limiter;
+mod worker;
 
 #[cfg(test)]
 pub mod tests;
 
-pub use pathdb::PathDB;
+pub use pathdb::{PathDB, PathDBWriteBatch, HealRequest, HealProgress};
+pub use deletion_worker::DeletionWorker;
+pub use flush_worker::FlushWorker;
+pub use disk_usage_worker::DiskUsageWorker;
+pub use catch_up_worker::CatchUpWorker;
+pub use invalidation_worker::InvalidationWorker;
+pub use checkpoint::BackupInfo;
+pub use disk_usage::{ColumnFamilyUsage, DiskUsage};
+pub use sharded::ShardedPathDB;
+pub use snapshot_overlay::SnapshotOverlay;
+pub use read_snapshot::PathDBSnapshot;
+pub use repair::{ChecksumReport, CorruptedRange};
+pub use write_pressure::WritePressure;
+pub use migration::{KeyMigration, CURRENT_SCHEMA_VERSION};
+pub use cache_controller::{CacheAllocation, CacheController, CacheControllerWorker, DEFAULT_MIN_CACHE_CAPACITY};
+pub use amplification::AmplificationReport;
+pub use bulk_load::{BulkLoadValidator, SstBulkLoader};
+pub use metrics_snapshot::PathDBMetricsSnapshot;
+pub use snapshot_journal::{SnapshotDiff, SnapshotRecovery};
+pub use snapshot_tree::{SnapshotLayer, SnapshotTree};
+pub use storage_root_export::StorageRootFile;
+pub use cached::CachedDB;
 pub use traits::*;
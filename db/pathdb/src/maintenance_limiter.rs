@@ -0,0 +1,39 @@
+//! Rate limit of background maintenance writes.
+
+use std::sync::Mutex;
+use std::thread;
+use std::time::{Duration, Instant};
+
+/// Spreads maintenance writes so they average at most a given number of
+/// bytes per second.
+///
+/// A write is charged after it lands and the next one waits until the
+/// charge is paid off, so callers can wait before taking any lock and a
+/// single large batch is never split or delayed itself.
+pub(crate) struct MaintenanceLimiter {
+    bytes_per_sec: u64,
+    next_write: Mutex<Instant>,
+}
+
+impl MaintenanceLimiter {
+    /// Create a limiter allowing `bytes_per_sec` bytes per second, at least one.
+    pub(crate) fn new(bytes_per_sec: u64) -> Self {
+        Self { bytes_per_sec: bytes_per_sec.max(1), next_write: Mutex::new(Instant::now()) }
+    }
+
+    /// Block until the writes charged so far are paid off.
+    pub(crate) fn wait(&self) {
+        let next_write = *self.next_write.lock().unwrap();
+        let now = Instant::now();
+        if next_write > now {
+            thread::sleep(next_write - now);
+        }
+    }
+
+    /// Charge a write of `bytes` bytes.
+    pub(crate) fn charge(&self, bytes: u64) {
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+        let mut next_write = self.next_write.lock().unwrap();
+        *next_write = (*next_write).max(Instant::now()) + cost;
+    }
+}
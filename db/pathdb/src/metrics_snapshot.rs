@@ -0,0 +1,141 @@
+//! Point-in-time snapshot of the PathDB metrics.
+
+use rust_eth_triedb_common::{CounterTotal, GaugeValue, HistogramSummary, HistogramTotal};
+
+/// Process-wide totals of the PathDB metrics, summed over all instances.
+pub(crate) struct PathDBMetricTotals {
+    pub(crate) trie_node_cache_hits: CounterTotal,
+    pub(crate) trie_node_cache_misses: CounterTotal,
+    pub(crate) negative_cache_hits: CounterTotal,
+    pub(crate) storage_root_cache_hits: CounterTotal,
+    pub(crate) storage_root_cache_misses: CounterTotal,
+    pub(crate) deletion_queue_backlog: GaugeValue,
+    pub(crate) deferred_deletions_processed: CounterTotal,
+    pub(crate) heal_healed_nodes: GaugeValue,
+    pub(crate) heal_pending_nodes: GaugeValue,
+    pub(crate) commit_bytes: CounterTotal,
+    pub(crate) write_amplification: GaugeValue,
+    pub(crate) space_amplification: GaugeValue,
+    pub(crate) trie_node_cache_entries: GaugeValue,
+    pub(crate) storage_root_cache_entries: GaugeValue,
+    pub(crate) compaction_duration: HistogramTotal,
+    pub(crate) flush_duration: HistogramTotal,
+    pub(crate) cache_bypassed_inserts: CounterTotal,
+    pub(crate) get_storage_root_duration: HistogramTotal,
+    pub(crate) storage_root_batch_size: HistogramTotal,
+    pub(crate) storage_root_found: CounterTotal,
+    pub(crate) storage_root_missing: CounterTotal,
+    pub(crate) storage_root_wiped: CounterTotal,
+    pub(crate) storage_root_corruptions: CounterTotal,
+}
+
+pub(crate) static PATHDB_METRIC_TOTALS: PathDBMetricTotals = PathDBMetricTotals {
+    trie_node_cache_hits: CounterTotal::new(),
+    trie_node_cache_misses: CounterTotal::new(),
+    negative_cache_hits: CounterTotal::new(),
+    storage_root_cache_hits: CounterTotal::new(),
+    storage_root_cache_misses: CounterTotal::new(),
+    deletion_queue_backlog: GaugeValue::new(),
+    deferred_deletions_processed: CounterTotal::new(),
+    heal_healed_nodes: GaugeValue::new(),
+    heal_pending_nodes: GaugeValue::new(),
+    commit_bytes: CounterTotal::new(),
+    write_amplification: GaugeValue::new(),
+    space_amplification: GaugeValue::new(),
+    trie_node_cache_entries: GaugeValue::new(),
+    storage_root_cache_entries: GaugeValue::new(),
+    compaction_duration: HistogramTotal::new(),
+    flush_duration: HistogramTotal::new(),
+    cache_bypassed_inserts: CounterTotal::new(),
+    get_storage_root_duration: HistogramTotal::new(),
+    storage_root_batch_size: HistogramTotal::new(),
+    storage_root_found: CounterTotal::new(),
+    storage_root_missing: CounterTotal::new(),
+    storage_root_wiped: CounterTotal::new(),
+    storage_root_corruptions: CounterTotal::new(),
+};
+
+/// Current values of the PathDB metrics, see [`snapshot`].
+///
+/// Counters and histograms are summed over all PathDB instances of the
+/// process, gauges hold the value last set by any of them.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct PathDBMetricsSnapshot {
+    /// Trie node cache hits
+    pub trie_node_cache_hits: u64,
+    /// Trie node cache misses
+    pub trie_node_cache_misses: u64,
+    /// Trie node reads answered by the negative cache
+    pub negative_cache_hits: u64,
+    /// Storage root cache hits
+    pub storage_root_cache_hits: u64,
+    /// Storage root cache misses
+    pub storage_root_cache_misses: u64,
+    /// Estimated number of trie node deletions waiting in the deletion queue
+    pub deletion_queue_backlog: f64,
+    /// Queued trie node deletions processed
+    pub deferred_deletions_processed: u64,
+    /// Number of nodes healed since the last heal reset
+    pub heal_healed_nodes: f64,
+    /// Number of outstanding heal requests
+    pub heal_pending_nodes: f64,
+    /// Bytes committed through `commit_difflayer`
+    pub commit_bytes: u64,
+    /// Write amplification over the sliding commit window
+    pub write_amplification: f64,
+    /// Space amplification of the SST files
+    pub space_amplification: f64,
+    /// Number of entries in the trie node cache
+    pub trie_node_cache_entries: f64,
+    /// Number of entries in the storage root cache
+    pub storage_root_cache_entries: f64,
+    /// Manual compaction durations (in seconds)
+    pub compaction_duration: HistogramSummary,
+    /// Flush durations (in seconds)
+    pub flush_duration: HistogramSummary,
+    /// Trie node cache inserts bypassed for exceeding the maximum value size
+    pub cache_bypassed_inserts: u64,
+    /// `get_storage_root` latencies (in seconds)
+    pub get_storage_root_duration: HistogramSummary,
+    /// Number of storage roots written per batch
+    pub storage_root_batch_size: HistogramSummary,
+    /// Storage root reads answered by the database
+    pub storage_root_found: u64,
+    /// Storage root reads returning `None`, left to the account trie
+    pub storage_root_missing: u64,
+    /// Accounts wiped from the flat state
+    pub storage_root_wiped: u64,
+    /// Storage roots of invalid length rejected on write or found on read
+    pub storage_root_corruptions: u64,
+}
+
+/// Take a snapshot of the PathDB metrics, e.g. to log them or assert on them
+/// in tests without scraping the metrics exporter.
+pub fn snapshot() -> PathDBMetricsSnapshot {
+    let totals = &PATHDB_METRIC_TOTALS;
+    PathDBMetricsSnapshot {
+        trie_node_cache_hits: totals.trie_node_cache_hits.get(),
+        trie_node_cache_misses: totals.trie_node_cache_misses.get(),
+        negative_cache_hits: totals.negative_cache_hits.get(),
+        storage_root_cache_hits: totals.storage_root_cache_hits.get(),
+        storage_root_cache_misses: totals.storage_root_cache_misses.get(),
+        deletion_queue_backlog: totals.deletion_queue_backlog.get(),
+        deferred_deletions_processed: totals.deferred_deletions_processed.get(),
+        heal_healed_nodes: totals.heal_healed_nodes.get(),
+        heal_pending_nodes: totals.heal_pending_nodes.get(),
+        commit_bytes: totals.commit_bytes.get(),
+        write_amplification: totals.write_amplification.get(),
+        space_amplification: totals.space_amplification.get(),
+        trie_node_cache_entries: totals.trie_node_cache_entries.get(),
+        storage_root_cache_entries: totals.storage_root_cache_entries.get(),
+        compaction_duration: totals.compaction_duration.get(),
+        flush_duration: totals.flush_duration.get(),
+        cache_bypassed_inserts: totals.cache_bypassed_inserts.get(),
+        get_storage_root_duration: totals.get_storage_root_duration.get(),
+        storage_root_batch_size: totals.storage_root_batch_size.get(),
+        storage_root_found: totals.storage_root_found.get(),
+        storage_root_missing: totals.storage_root_missing.get(),
+        storage_root_wiped: totals.storage_root_wiped.get(),
+        storage_root_corruptions: totals.storage_root_corruptions.get(),
+    }
+}
@@ -0,0 +1,173 @@
+//! Key layout versioning and migrations of a PathDB.
+
+use rocksdb::{IteratorMode, WriteBatch};
+use tracing::{info, warn};
+
+use crate::pathdb::{PathDB, DEFAULT_COLUMN_FAMILY_NAME};
+use crate::traits::*;
+
+/// Key layout version written by this version of PathDB.
+pub const CURRENT_SCHEMA_VERSION: u32 = 1;
+
+/// Schema version of databases created before the version was recorded.
+pub const BASE_SCHEMA_VERSION: u32 = 1;
+
+/// Key of the schema version in the trie node column family.
+pub const SCHEMA_VERSION_KEY: &[u8] = b"schema_version";
+
+/// Number of rewritten keys per write batch of a migration.
+const MIGRATION_BATCH_SIZE: usize = 10_000;
+
+/// A change of the key layout of the trie node column family, e.g. after
+/// the account or storage trie node key encoding changed, that upgrades a
+/// database from [`KeyMigration::from_version`] to the next version.
+pub trait KeyMigration: Send + Sync {
+    /// Schema version the migration applies to.
+    fn from_version(&self) -> u32;
+
+    /// New key of the entry stored under `key`, `None` to keep it.
+    ///
+    /// Every key of the trie node column family is passed, metadata such as
+    /// the persisted state included. Keys already in the new layout must be
+    /// kept so an interrupted migration can be run again, and new keys must
+    /// not collide with keys of the old layout.
+    fn migrate_key(&self, key: &[u8]) -> Option<Vec<u8>>;
+}
+
+/// Migrations to [`CURRENT_SCHEMA_VERSION`], oldest first.
+fn builtin_migrations() -> Vec<Box<dyn KeyMigration>> {
+    Vec::new()
+}
+
+/// Schema versioning and migrations
+impl PathDB {
+    /// Get the schema version of the key layout.
+    pub fn schema_version(&self) -> PathProviderResult<u32> {
+        match self.get_raw_meta_data(SCHEMA_VERSION_KEY)? {
+            Some(value) => {
+                let version: [u8; 4] = value.as_slice().try_into().map_err(|_| {
+                    PathProviderError::Deserialization(format!("Invalid schema version of {} bytes", value.len()))
+                })?;
+                Ok(u32::from_be_bytes(version))
+            }
+            None => Ok(BASE_SCHEMA_VERSION),
+        }
+    }
+
+    /// Upgrade the key layout to [`CURRENT_SCHEMA_VERSION`] with the
+    /// migrations shipped with this version, returning the resulting version.
+    pub fn migrate(&self) -> PathProviderResult<u32> {
+        let migrations = builtin_migrations();
+        let migrations: Vec<&dyn KeyMigration> = migrations.iter().map(Box::as_ref).collect();
+        self.apply_migrations(&migrations)
+    }
+
+    /// Apply `migrations` in turn from the current schema version, returning
+    /// the resulting version.
+    ///
+    /// Each migration rewrites the keys of the trie node column family in
+    /// batches, moving overflowed values with their chunks, and records the
+    /// next version with its last batch. The cold trie node column family
+    /// isn't migrated. Trie node writes are blocked for the duration and the
+    /// caches are cleared afterwards.
+    pub fn apply_migrations(&self, migrations: &[&dyn KeyMigration]) -> PathProviderResult<u32> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot migrate a read-only database".to_string()));
+        }
+
+        let mut version = self.schema_version()?;
+        while let Some(migration) = migrations.iter().find(|migration| migration.from_version() == version) {
+            let rewritten = self.run_key_migration(*migration)?;
+            version += 1;
+            info!(target: "pathdb::migration", "Migrated key layout to schema version {}, rewrote {} keys", version, rewritten);
+        }
+        Ok(version)
+    }
+
+    /// Check the schema version when opening.
+    ///
+    /// Databases at the base version carry no version key, new databases
+    /// are stamped with the current version once it differs.
+    pub(crate) fn check_schema_version(&self) -> PathProviderResult<()> {
+        let mut version = self.schema_version()?;
+        if version != CURRENT_SCHEMA_VERSION
+            && !self.is_read_only()
+            && self.get_raw_meta_data(SCHEMA_VERSION_KEY)?.is_none()
+            && self.iter_trie_nodes(&[])?.next().is_none()
+        {
+            self.put_schema_version(CURRENT_SCHEMA_VERSION)?;
+            version = CURRENT_SCHEMA_VERSION;
+        }
+
+        if version > CURRENT_SCHEMA_VERSION {
+            return Err(PathProviderError::InvalidOperation(format!(
+                "Database schema version {} is newer than the supported version {}",
+                version, CURRENT_SCHEMA_VERSION
+            )));
+        }
+        if version < CURRENT_SCHEMA_VERSION {
+            warn!(
+                target: "pathdb::migration",
+                "Database schema version {} is older than {}, run PathDB::migrate before use",
+                version, CURRENT_SCHEMA_VERSION
+            );
+        }
+        Ok(())
+    }
+
+    fn put_schema_version(&self, version: u32) -> PathProviderResult<()> {
+        let mut batch = WriteBatch::default();
+        self.batch_put_schema_version(&mut batch, version)?;
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb("Schema version write error", e))?;
+        self.invalidate_keys([SCHEMA_VERSION_KEY]);
+        Ok(())
+    }
+
+    fn batch_put_schema_version(&self, batch: &mut WriteBatch, version: u32) -> PathProviderResult<()> {
+        let cf = self.raw_db().cf_handle(DEFAULT_COLUMN_FAMILY_NAME)
+            .ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
+        batch.put_cf(&cf, self.db_key(SCHEMA_VERSION_KEY), version.to_be_bytes());
+        Ok(())
+    }
+
+    /// Rewrite the keys of the trie node column family with `migration` and
+    /// bump the schema version, returning the number of keys rewritten.
+    fn run_key_migration(&self, migration: &dyn KeyMigration) -> PathProviderResult<u64> {
+        let cf = self.raw_db().cf_handle(DEFAULT_COLUMN_FAMILY_NAME)
+            .ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
+
+        let _migration_guard = self.migration_guard();
+        let mut batch = WriteBatch::default();
+        let mut pending = 0;
+        let mut rewritten = 0u64;
+        // The iterator reads an implicit snapshot, so rewritten keys are not visited again
+        for item in self.raw_db().iterator_cf_opt(&cf, self.namespace_read_options(), IteratorMode::Start) {
+            let (db_key, value) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", DEFAULT_COLUMN_FAMILY_NAME), e)
+            })?;
+            let Some(key) = self.logical_key(&db_key) else { continue };
+            let Some(new_key) = migration.migrate_key(key) else { continue };
+            if new_key == key {
+                continue;
+            }
+
+            let value = self.resolve_overflow(&db_key, value.into_vec())?;
+            self.batch_delete_trie_node(&mut batch, &cf, &db_key)?;
+            self.batch_put_trie_node(&mut batch, &cf, &self.db_key(&new_key), &value)?;
+            pending += 1;
+            rewritten += 1;
+            if pending == MIGRATION_BATCH_SIZE {
+                self.write_raw_batch(std::mem::take(&mut batch))
+                    .map_err(|e| PathProviderError::rocksdb("Migration batch error", e))?;
+                pending = 0;
+            }
+        }
+
+        self.batch_put_schema_version(&mut batch, migration.from_version() + 1)?;
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb("Migration batch error", e))?;
+        self.clear_cache();
+        Ok(rewritten)
+    }
+}
@@ -0,0 +1,61 @@
+//! Bounded cache of trie node keys known to be absent from the database.
+
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use schnellru::{ByLength, LruMap};
+
+/// LRU cache of missing keys, each remembered for at most `ttl`.
+///
+/// Kept apart from the trie node cache so probes for absent paths can't
+/// evict cached nodes. The expiry bounds how long a write that bypassed the
+/// caches can stay hidden behind a negative entry.
+pub(crate) struct NegativeCache {
+    entries: Mutex<LruMap<Vec<u8>, Instant, ByLength>>,
+    ttl: Duration,
+}
+
+impl NegativeCache {
+    /// Create a cache of up to `capacity` keys remembered for `ttl` each.
+    pub(crate) fn new(capacity: u32, ttl: Duration) -> Self {
+        Self { entries: Mutex::new(LruMap::new(ByLength::new(capacity.max(1)))), ttl }
+    }
+
+    /// Whether `key` is known to be missing. Expired entries are dropped.
+    pub(crate) fn contains(&self, key: &[u8]) -> bool {
+        let mut entries = self.entries.lock().unwrap();
+        match entries.peek(key) {
+            Some(expiry) if *expiry > Instant::now() => true,
+            Some(_) => {
+                entries.remove(key);
+                false
+            }
+            None => false,
+        }
+    }
+
+    /// Remember that `key` is missing.
+    pub(crate) fn insert(&self, key: Vec<u8>) {
+        let expiry = Instant::now() + self.ttl;
+        self.entries.lock().unwrap().insert(key, expiry);
+    }
+
+    /// Forget `key`, e.g. because it was written.
+    pub(crate) fn remove(&self, key: &[u8]) {
+        self.entries.lock().unwrap().remove(key);
+    }
+
+    /// Forget the keys matching `predicate`.
+    pub(crate) fn remove_matching(&self, predicate: impl Fn(&[u8]) -> bool) {
+        let mut entries = self.entries.lock().unwrap();
+        let keys: Vec<Vec<u8>> = entries.iter().map(|(key, _)| key).filter(|key| predicate(key)).cloned().collect();
+        for key in &keys {
+            entries.remove(key);
+        }
+    }
+
+    /// Forget all keys.
+    pub(crate) fn clear(&self) {
+        self.entries.lock().unwrap().clear();
+    }
+}
@@ -0,0 +1,63 @@
+//! Filter of the trie node keys that may hold overflow chunks.
+
+use std::collections::hash_map::DefaultHasher;
+use std::hash::{Hash, Hasher};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+
+/// Number of bits of an [`OverflowKeyFilter`], 128 KiB.
+const OVERFLOW_FILTER_BITS: u64 = 1 << 20;
+
+/// Number of bits set per key.
+const OVERFLOW_FILTER_HASHES: u64 = 4;
+
+/// Bloom filter of the node keys stored as overflow pointers.
+///
+/// Overwriting or deleting a node drops the chunks of its previous value,
+/// which takes a point read of that value. Keys never stored as a pointer
+/// are rejected here, so only writes to oversized nodes and the rare false
+/// positive pay for the read. Keys are never removed, the filter is rebuilt
+/// from the overflow column family on open.
+pub(crate) struct OverflowKeyFilter {
+    bits: Box<[AtomicU64]>,
+    /// Whether any key was inserted, i.e. the overflow column family may
+    /// hold chunks.
+    in_use: AtomicBool,
+}
+
+impl OverflowKeyFilter {
+    /// Create an empty filter.
+    pub(crate) fn new() -> Self {
+        let bits = (0..OVERFLOW_FILTER_BITS / 64).map(|_| AtomicU64::new(0)).collect();
+        Self { bits, in_use: AtomicBool::new(false) }
+    }
+
+    /// Bit positions of `db_key`, by double hashing a single hash.
+    fn positions(db_key: &[u8]) -> impl Iterator<Item = u64> {
+        let mut hasher = DefaultHasher::new();
+        db_key.hash(&mut hasher);
+        let hash = hasher.finish();
+        let step = hash.rotate_left(32) | 1;
+        (0..OVERFLOW_FILTER_HASHES).map(move |index| hash.wrapping_add(index.wrapping_mul(step)) % OVERFLOW_FILTER_BITS)
+    }
+
+    /// Record that `db_key` is stored as an overflow pointer, before its
+    /// chunks are written.
+    pub(crate) fn insert(&self, db_key: &[u8]) {
+        for position in Self::positions(db_key) {
+            self.bits[(position / 64) as usize].fetch_or(1 << (position % 64), Ordering::Release);
+        }
+        self.in_use.store(true, Ordering::Release);
+    }
+
+    /// Whether `db_key` may be stored as an overflow pointer.
+    pub(crate) fn may_contain(&self, db_key: &[u8]) -> bool {
+        self.in_use()
+            && Self::positions(db_key)
+                .all(|position| self.bits[(position / 64) as usize].load(Ordering::Acquire) & (1 << (position % 64)) != 0)
+    }
+
+    /// Whether any key was inserted.
+    pub(crate) fn in_use(&self) -> bool {
+        self.in_use.load(Ordering::Acquire)
+    }
+}
@@ -1,21 +1,35 @@
 //! PathDB implementation for RocksDB integration.
 
-use std::collections::HashSet;
+use std::borrow::Cow;
+use std::collections::{BTreeMap, HashMap, HashSet};
 use std::fmt::Debug;
+use std::path::Path;
 use std::sync::Arc;
-use std::sync::Mutex;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Condvar, Mutex, MutexGuard, RwLock, RwLockReadGuard, RwLockWriteGuard};
+use std::time::Instant;
 
-use rocksdb::{ColumnFamilyDescriptor,DB, Options, ReadOptions, WriteBatch, WriteOptions};
-use schnellru::{ByLength, LruMap};
-use tracing::{error, trace, warn};
+use rocksdb::statistics::Ticker;
+use rocksdb::{AsColumnFamilyRef, BlockBasedOptions, BoundColumnFamily, Cache, ColumnFamilyDescriptor, DB, DBCompactionPri, DBCompactionStyle, DBCompressionType, FifoCompactOptions, FlushOptions, SliceTransform, Direction, IteratorMode, Options, ReadOptions, SstFileWriter, UniversalCompactOptions, WriteBatch, WriteBatchIterator, WriteBatchIteratorCf, WriteOptions};
+use tracing::{error, info, trace, warn};
 
-use alloy_primitives::B256;
+use alloy_primitives::{keccak256, Bytes, B256};
 use alloy_trie::EMPTY_ROOT_HASH;
+use crate::amplification::{AmplificationReport, AmplificationWindow};
+use crate::metrics_snapshot::PATHDB_METRIC_TOTALS;
+use crate::access_tracker::AccessTracker;
+use crate::maintenance_limiter::MaintenanceLimiter;
+use crate::migration::SCHEMA_VERSION_KEY;
+use crate::negative_cache::NegativeCache;
+use crate::overflow_filter::OverflowKeyFilter;
+use crate::sharded_cache::{ShardedCache, ShardedCacheGuard};
+use crate::version_gc::set_version_gc_filter;
+use crate::worker::WorkerThread;
 use crate::traits::*;
-use rust_eth_triedb_common::{TrieDatabase, DiffLayer, TRIE_STATE_ROOT_KEY, TRIE_STATE_BLOCK_NUMBER_KEY};
+use rust_eth_triedb_common::{TrieDatabase, AuditRecord, CancellationToken, DiffLayer, DiffLayers, SlotCountChange, TRIE_STATE_ROOT_KEY, TRIE_STATE_BLOCK_NUMBER_KEY};
 
 use reth_metrics::{
-    metrics::{Counter},
+    metrics::{Counter, Gauge, Histogram},
     Metrics,
 };
 
@@ -67,11 +81,272 @@ pub const META_COLUMN_FAMILY_NAME: &str = "meta_data";
 /// - **Value**: `B256` (32 bytes) - The root hash of the account's storage trie
 pub const STORAGE_ROOT_COLUMN_FAMILY_NAME: &str = "storage_root";
 
+/// The column family name used for storing oversized trie node values.
+///
+/// When `PathProviderConfig::overflow_threshold` is set, node blobs above the
+/// threshold are split into chunks stored here, and the primary column family
+/// only keeps a small overflow pointer. This keeps the value size distribution
+/// of the primary column family tight for better block cache behavior.
+///
+/// # Key-Value Format
+///
+/// - **Key**: `u16 BE len(key) || key || u32 BE chunk_index`
+/// - **Value**: Chunk bytes, at most `PathProviderConfig::overflow_chunk_size`
+pub const OVERFLOW_COLUMN_FAMILY_NAME: &str = "overflow";
+
+/// The column family name used for the append-only commit audit log.
+///
+/// # Key-Value Format
+///
+/// - **Key**: `u64 BE block_number || root_after`, so records iterate in block order
+///   and competing commits at the same height are all kept
+/// - **Value**: Encoded `AuditRecord`
+pub const AUDIT_LOG_COLUMN_FAMILY_NAME: &str = "audit_log";
+
+/// The column family name used for queued trie node deletions.
+///
+/// With `PathProviderConfig::deferred_deletion` enabled, stale nodes are
+/// recorded here on commit and deleted later in rate-limited batches, so
+/// reorg-heavy commits don't stall on large delete sets. Writing a node again
+/// cancels its pending deletion in the same batch.
+///
+/// # Key-Value Format
+///
+/// - **Key**: Trie node key as stored in the primary column family
+/// - **Value**: `u64 BE` number of the block whose commit queued the deletion
+pub const DELETION_QUEUE_COLUMN_FAMILY_NAME: &str = "deletion_queue";
+
+/// The column family name used for outstanding state heal requests.
+///
+/// A heal interrupted by a restart resumes from the requests still listed
+/// here, the healed and pending counts are kept in `META_COLUMN_FAMILY_NAME`.
+///
+/// # Key-Value Format
+///
+/// - **Key**: Trie node key of the missing node
+/// - **Value**: `B256` (32 bytes) - Expected hash of the missing node
+pub const HEAL_QUEUE_COLUMN_FAMILY_NAME: &str = "heal_queue";
+
+/// The column family name used for the code hash index.
+///
+/// Maps code hashes to the accounts deployed with that code, updated from the
+/// `code_hashes` of persisted diff layers. A reverse entry per account keeps
+/// its indexed code hash, so a changed or deleted account can be dropped from
+/// its old list. Accounts without code are not indexed.
+///
+/// # Key-Value Format
+///
+/// - **Key**: `b'c' || code_hash || hashed_address`, **Value**: empty
+/// - **Key**: `b'a' || hashed_address`, **Value**: `B256` (32 bytes) - Indexed code hash
+pub const CODE_HASH_INDEX_COLUMN_FAMILY_NAME: &str = "code_hash_index";
+
+/// The column family name used for the storage slot counts of accounts.
+///
+/// Counts are updated from the `slot_count_changes` of persisted diff layers
+/// and are approximate: slots written before tracking was enabled are not
+/// counted. Accounts without slots have no entry.
+///
+/// # Key-Value Format
+///
+/// - **Key**: `B256` (32 bytes) - The Keccak-256 hash of an account address
+/// - **Value**: `u64 BE` - Number of storage slots of the account
+pub const SLOT_COUNT_COLUMN_FAMILY_NAME: &str = "slot_count";
+
+/// The column family name used for the flat account snapshot.
+///
+/// Maps hashed addresses to their encoded accounts, so account reads can be
+/// served with one lookup instead of walking the account trie. Entries are
+/// written by the snapshot owner and are not updated by diff layer commits.
+///
+/// # Key-Value Format
+///
+/// - **Key**: `B256` (32 bytes) - The Keccak-256 hash of an account address
+/// - **Value**: RLP-encoded account, as stored in the account trie leaf
+pub const ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME: &str = "account_snap";
+
+/// The column family name used for the flat storage snapshot.
+///
+/// Maps the storage slots of accounts to their values, ordered by account
+/// then slot so the slots of one account can be walked as a range. Like the
+/// account snapshot, entries are written by the snapshot owner.
+///
+/// # Key-Value Format
+///
+/// - **Key**: `hashed_address || hashed_key` (64 bytes)
+/// - **Value**: Slot value as returned by `get_storage_with_hash_state`
+pub const STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME: &str = "storage_snap";
+
+/// The column family name used for the journal of flat snapshot changes.
+///
+/// Holds the snapshot changes of blocks not yet applied to the account and
+/// storage snapshots, see `PathDB::recover_snapshot`.
+///
+/// # Key-Value Format
+///
+/// - **Key**: `u64 BE` block number
+/// - **Value**: Encoded `SnapshotDiff`
+pub const SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME: &str = "snapshot_journal";
+
+/// Tag of the code hash to account entries in the code hash index.
+const CODE_HASH_INDEX_CODE_TAG: u8 = b'c';
+
+/// Tag of the account to code hash entries in the code hash index.
+const CODE_HASH_INDEX_ACCOUNT_TAG: u8 = b'a';
+
+/// Meta data key of the hot trie node keys persisted on graceful close.
+///
+/// The value is a sequence of `u16 BE` key length || key, most recently used first.
+const HOT_KEYS_KEY: &[u8] = b"hot_keys";
+
+/// Meta data key of the number of nodes healed since the last heal reset.
+const HEAL_HEALED_COUNT_KEY: &[u8] = b"heal_healed_count";
+
+/// Meta data key of the number of outstanding heal requests, kept next to
+/// the heal queue so progress reads don't scan it. The value is `u64 BE`.
+const HEAL_PENDING_COUNT_KEY: &[u8] = b"heal_pending_count";
+
+/// Meta data key of the block the storage root column family was marked
+/// complete at, see [`PathDB::mark_storage_roots_complete`]. The value is `u64 BE`.
+const STORAGE_ROOTS_COMPLETE_KEY: &[u8] = b"storage_roots_complete";
+
+/// Marker at the start of an overflow pointer stored in the primary column family.
+///
+/// RLP-encoded trie nodes never start with `0x00`, so pointers can't be
+/// confused with regular node blobs.
+const OVERFLOW_POINTER_MARKER: &[u8; 4] = b"\x00ovf";
+
+/// Length of an overflow pointer: marker || u64 BE total_len || u32 BE chunk_count.
+const OVERFLOW_POINTER_LEN: usize = OVERFLOW_POINTER_MARKER.len() + 8 + 4;
+
+/// Length of the longest trie node key before namespacing: the storage
+/// prefix, the account hash and a full path of one nibble per byte.
+const MAX_TRIE_NODE_KEY_LEN: usize = 1 + 32 + 64;
+
+/// Refill period of the rate limiter, the RocksDB default.
+const RATE_LIMIT_REFILL_PERIOD_US: i64 = 100_000;
+
+/// Fairness of the rate limiter between high and low priority requests, the RocksDB default.
+const RATE_LIMIT_FAIRNESS: i32 = 10;
+
+/// Kinds of values of the runtime options, checked before any option is set.
+#[derive(Debug, Clone, Copy)]
+enum OptionKind {
+    Int,
+    Bool,
+    Double,
+}
+
+impl OptionKind {
+    /// Whether RocksDB parses `value` as a value of this kind.
+    fn accepts(self, value: &str) -> bool {
+        match self {
+            Self::Int => value.parse::<i64>().is_ok() || value.parse::<u64>().is_ok(),
+            Self::Bool => matches!(value, "true" | "false" | "1" | "0"),
+            Self::Double => value.parse::<f64>().is_ok_and(f64::is_finite),
+        }
+    }
+}
+
+/// RocksDB options changed through `SetDBOptions` rather than per Column Family.
+const MUTABLE_DB_OPTIONS: &[(&str, OptionKind)] = &[
+    ("max_background_jobs", OptionKind::Int),
+    ("max_background_compactions", OptionKind::Int),
+    ("max_background_flushes", OptionKind::Int),
+    ("max_subcompactions", OptionKind::Int),
+    ("avoid_flush_during_shutdown", OptionKind::Bool),
+    ("writable_file_max_buffer_size", OptionKind::Int),
+    ("delayed_write_rate", OptionKind::Int),
+    ("max_total_wal_size", OptionKind::Int),
+    ("delete_obsolete_files_period_micros", OptionKind::Int),
+    ("stats_dump_period_sec", OptionKind::Int),
+    ("stats_persist_period_sec", OptionKind::Int),
+    ("stats_history_buffer_size", OptionKind::Int),
+    ("max_open_files", OptionKind::Int),
+    ("bytes_per_sync", OptionKind::Int),
+    ("wal_bytes_per_sync", OptionKind::Int),
+    ("strict_bytes_per_sync", OptionKind::Bool),
+    ("compaction_readahead_size", OptionKind::Int),
+];
+
+/// RocksDB options changed through `SetOptions` of a Column Family.
+const MUTABLE_CF_OPTIONS: &[(&str, OptionKind)] = &[
+    ("write_buffer_size", OptionKind::Int),
+    ("max_write_buffer_number", OptionKind::Int),
+    ("arena_block_size", OptionKind::Int),
+    ("memtable_prefix_bloom_size_ratio", OptionKind::Double),
+    ("memtable_huge_page_size", OptionKind::Int),
+    ("max_successive_merges", OptionKind::Int),
+    ("inplace_update_num_locks", OptionKind::Int),
+    ("disable_auto_compactions", OptionKind::Bool),
+    ("soft_pending_compaction_bytes_limit", OptionKind::Int),
+    ("hard_pending_compaction_bytes_limit", OptionKind::Int),
+    ("level0_file_num_compaction_trigger", OptionKind::Int),
+    ("level0_slowdown_writes_trigger", OptionKind::Int),
+    ("level0_stop_writes_trigger", OptionKind::Int),
+    ("max_compaction_bytes", OptionKind::Int),
+    ("target_file_size_base", OptionKind::Int),
+    ("target_file_size_multiplier", OptionKind::Int),
+    ("max_bytes_for_level_base", OptionKind::Int),
+    ("max_bytes_for_level_multiplier", OptionKind::Double),
+    ("ttl", OptionKind::Int),
+    ("periodic_compaction_seconds", OptionKind::Int),
+    ("paranoid_file_checks", OptionKind::Bool),
+    ("report_bg_io_stats", OptionKind::Bool),
+    ("enable_blob_files", OptionKind::Bool),
+    ("min_blob_size", OptionKind::Int),
+    ("blob_file_size", OptionKind::Int),
+    ("enable_blob_garbage_collection", OptionKind::Bool),
+    ("blob_garbage_collection_age_cutoff", OptionKind::Double),
+    ("blob_compaction_readahead_size", OptionKind::Int),
+];
+
+/// Check `options` against the runtime options of `allowed`.
+fn validate_options(options: &[(&str, &str)], allowed: &[(&str, OptionKind)]) -> PathProviderResult<()> {
+    for (key, value) in options {
+        let Some((_, kind)) = allowed.iter().find(|(name, _)| name == key) else {
+            return Err(PathProviderError::InvalidOperation(format!("Option '{}' can't be set at runtime", key)));
+        };
+        if !kind.accepts(value) {
+            return Err(PathProviderError::InvalidOperation(format!("Invalid value '{}' of option '{}'", value, key)));
+        }
+    }
+    Ok(())
+}
+
+/// The column family name used for rarely read trie nodes.
+///
+/// `PathDB::move_to_cold_storage` moves cold subtrees here from the primary
+/// column family, compressed with `PathProviderConfig::cold_compression`, as
+/// groundwork for keeping them on cheaper disks. With
+/// `PathProviderConfig::cold_storage` enabled, point reads missing the primary
+/// column family fall back to this one.
+///
+/// # Key-Value Format
+///
+/// Same as the primary column family; overflow pointers keep referencing
+/// their chunks in `OVERFLOW_COLUMN_FAMILY_NAME`.
+pub const COLD_TRIE_NODE_COLUMN_FAMILY_NAME: &str = "cold_trie_node";
+
+/// Number of trie nodes moved to cold storage per batch.
+const COLD_STORAGE_BATCH_SIZE: usize = 10_000;
+
+/// The column family name used for the storage roots of past blocks.
+///
+/// Written next to the storage root column family when
+/// `PathProviderConfig::storage_root_history` is enabled, with one entry per
+/// account and block its storage root changed at.
+///
+/// # Key-Value Format
+///
+/// - **Key**: `hashed_address || !u64 BE block number` (40 bytes), newest first, see `version_gc::versioned_key`
+/// - **Value**: `B256` (32 bytes) - The storage root as of that block
+pub const STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME: &str = "storage_root_history";
+
 /// An array containing all column family names used by PathDB.
 ///
 /// This array is used during database initialization to ensure all required
 /// column families are created if they don't already exist. The order of
-/// column families in this array is not significant, but all four must be
+/// column families in this array is not significant, but all of them must be
 /// present for PathDB to function correctly.
 ///
 /// # Column Families
@@ -80,7 +355,18 @@ pub const STORAGE_ROOT_COLUMN_FAMILY_NAME: &str = "storage_root";
 /// 2. `META_COLUMN_FAMILY_NAME` - Stores trie metadata (state root, block number)
 /// 3. `STORAGE_ROOT_COLUMN_FAMILY_NAME` - Stores storage trie roots
 /// 4. `TRIE_NODE_COLUMN_FAMILY_NAME` - Target destination for trie node data migration
-const COLUMN_FAMILY_NAMES: [&str; 4] = [DEFAULT_COLUMN_FAMILY_NAME, META_COLUMN_FAMILY_NAME, STORAGE_ROOT_COLUMN_FAMILY_NAME, TRIE_NODE_COLUMN_FAMILY_NAME];
+/// 5. `OVERFLOW_COLUMN_FAMILY_NAME` - Stores chunks of oversized trie node values
+/// 6. `AUDIT_LOG_COLUMN_FAMILY_NAME` - Stores the per-block commit audit log
+/// 7. `DELETION_QUEUE_COLUMN_FAMILY_NAME` - Stores trie node deletions pending background processing
+/// 8. `HEAL_QUEUE_COLUMN_FAMILY_NAME` - Stores outstanding state heal requests
+/// 9. `CODE_HASH_INDEX_COLUMN_FAMILY_NAME` - Stores the code hash to account index
+/// 10. `COLD_TRIE_NODE_COLUMN_FAMILY_NAME` - Stores rarely read trie nodes
+/// 11. `SLOT_COUNT_COLUMN_FAMILY_NAME` - Stores the storage slot counts of accounts
+/// 12. `ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME` - Stores the flat account snapshot
+/// 13. `STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME` - Stores the flat storage snapshot
+/// 14. `SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME` - Stores the journal of flat snapshot changes
+/// 15. `STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME` - Stores the storage roots of past blocks
+pub(crate) const COLUMN_FAMILY_NAMES: [&str; 15] = [DEFAULT_COLUMN_FAMILY_NAME, META_COLUMN_FAMILY_NAME, STORAGE_ROOT_COLUMN_FAMILY_NAME, TRIE_NODE_COLUMN_FAMILY_NAME, OVERFLOW_COLUMN_FAMILY_NAME, AUDIT_LOG_COLUMN_FAMILY_NAME, DELETION_QUEUE_COLUMN_FAMILY_NAME, HEAL_QUEUE_COLUMN_FAMILY_NAME, CODE_HASH_INDEX_COLUMN_FAMILY_NAME, COLD_TRIE_NODE_COLUMN_FAMILY_NAME, SLOT_COUNT_COLUMN_FAMILY_NAME, ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME, SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME, STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME];
 
 /// Metrics for the `PathDB`.
 #[derive(Metrics, Clone)]
@@ -90,28 +376,243 @@ pub(crate) struct PathDBMetrics {
     pub(crate) trie_node_cache_hits: Counter,
     /// Counter of cache misses
     pub(crate) trie_node_cache_misses: Counter,
+    /// Counter of trie node reads answered by the negative cache
+    pub(crate) negative_cache_hits: Counter,
     /// Counter of storage root cache hits
     pub(crate) storage_root_cache_hits: Counter,
     /// Counter of storage root cache misses
     pub(crate) storage_root_cache_misses: Counter,
+    /// Estimated number of trie node deletions waiting in the deletion queue
+    pub(crate) deletion_queue_backlog: Gauge,
+    /// Counter of queued trie node deletions processed
+    pub(crate) deferred_deletions_processed: Counter,
+    /// Number of nodes healed since the last heal reset
+    pub(crate) heal_healed_nodes: Gauge,
+    /// Number of outstanding heal requests
+    pub(crate) heal_pending_nodes: Gauge,
+    /// Counter of bytes committed through `commit_difflayer`
+    pub(crate) commit_bytes: Counter,
+    /// Write amplification over the sliding commit window
+    pub(crate) write_amplification: Gauge,
+    /// Space amplification of the SST files
+    pub(crate) space_amplification: Gauge,
+    /// Number of entries in the trie node cache
+    pub(crate) trie_node_cache_entries: Gauge,
+    /// Number of entries in the storage root cache
+    pub(crate) storage_root_cache_entries: Gauge,
+    /// Histogram of manual compaction durations (in seconds)
+    pub(crate) compaction_duration: Histogram,
+    /// Histogram of flush durations (in seconds)
+    pub(crate) flush_duration: Histogram,
+    /// Counter of trie node cache inserts bypassed for exceeding the maximum value size
+    pub(crate) cache_bypassed_inserts: Counter,
+    /// Histogram of `get_storage_root` latencies (in seconds)
+    pub(crate) get_storage_root_duration: Histogram,
+    /// Histogram of the number of storage roots written per batch
+    pub(crate) storage_root_batch_size: Histogram,
+    /// Counter of storage root reads answered by the database
+    pub(crate) storage_root_found: Counter,
+    /// Counter of storage root reads returning `None`, left to the trie
+    pub(crate) storage_root_missing: Counter,
+    /// Counter of accounts wiped from the flat state
+    pub(crate) storage_root_wiped: Counter,
+    /// Counter of storage roots of invalid length rejected on write or found on read
+    pub(crate) storage_root_corruptions: Counter,
+}
+
+/// Metric updates, mirrored into the process-wide totals read by
+/// [`metrics_snapshot::snapshot`](crate::metrics_snapshot::snapshot).
+impl PathDBMetrics {
+    pub(crate) fn increment_trie_node_cache_hits(&self, value: u64) {
+        self.trie_node_cache_hits.increment(value);
+        PATHDB_METRIC_TOTALS.trie_node_cache_hits.increment(value);
+    }
+
+    pub(crate) fn increment_negative_cache_hits(&self, value: u64) {
+        self.negative_cache_hits.increment(value);
+        PATHDB_METRIC_TOTALS.negative_cache_hits.increment(value);
+    }
+
+    pub(crate) fn increment_trie_node_cache_misses(&self, value: u64) {
+        self.trie_node_cache_misses.increment(value);
+        PATHDB_METRIC_TOTALS.trie_node_cache_misses.increment(value);
+    }
+
+    pub(crate) fn increment_storage_root_cache_hits(&self, value: u64) {
+        self.storage_root_cache_hits.increment(value);
+        PATHDB_METRIC_TOTALS.storage_root_cache_hits.increment(value);
+    }
+
+    pub(crate) fn increment_storage_root_cache_misses(&self, value: u64) {
+        self.storage_root_cache_misses.increment(value);
+        PATHDB_METRIC_TOTALS.storage_root_cache_misses.increment(value);
+    }
+
+    pub(crate) fn increment_deferred_deletions_processed(&self, value: u64) {
+        self.deferred_deletions_processed.increment(value);
+        PATHDB_METRIC_TOTALS.deferred_deletions_processed.increment(value);
+    }
+
+    pub(crate) fn increment_commit_bytes(&self, value: u64) {
+        self.commit_bytes.increment(value);
+        PATHDB_METRIC_TOTALS.commit_bytes.increment(value);
+    }
+
+    pub(crate) fn set_deletion_queue_backlog(&self, value: f64) {
+        self.deletion_queue_backlog.set(value);
+        PATHDB_METRIC_TOTALS.deletion_queue_backlog.set(value);
+    }
+
+    pub(crate) fn set_heal_healed_nodes(&self, value: f64) {
+        self.heal_healed_nodes.set(value);
+        PATHDB_METRIC_TOTALS.heal_healed_nodes.set(value);
+    }
+
+    pub(crate) fn set_heal_pending_nodes(&self, value: f64) {
+        self.heal_pending_nodes.set(value);
+        PATHDB_METRIC_TOTALS.heal_pending_nodes.set(value);
+    }
+
+    pub(crate) fn set_write_amplification(&self, value: f64) {
+        self.write_amplification.set(value);
+        PATHDB_METRIC_TOTALS.write_amplification.set(value);
+    }
+
+    pub(crate) fn set_space_amplification(&self, value: f64) {
+        self.space_amplification.set(value);
+        PATHDB_METRIC_TOTALS.space_amplification.set(value);
+    }
+
+    pub(crate) fn set_trie_node_cache_entries(&self, value: f64) {
+        self.trie_node_cache_entries.set(value);
+        PATHDB_METRIC_TOTALS.trie_node_cache_entries.set(value);
+    }
+
+    pub(crate) fn set_storage_root_cache_entries(&self, value: f64) {
+        self.storage_root_cache_entries.set(value);
+        PATHDB_METRIC_TOTALS.storage_root_cache_entries.set(value);
+    }
+
+    pub(crate) fn decrement_heal_pending_nodes(&self, value: f64) {
+        self.heal_pending_nodes.decrement(value);
+        PATHDB_METRIC_TOTALS.heal_pending_nodes.decrement(value);
+    }
+
+    pub(crate) fn record_compaction_duration(&self, duration: f64) {
+        self.compaction_duration.record(duration);
+        PATHDB_METRIC_TOTALS.compaction_duration.record(duration);
+    }
+
+    pub(crate) fn increment_cache_bypassed_inserts(&self, value: u64) {
+        self.cache_bypassed_inserts.increment(value);
+        PATHDB_METRIC_TOTALS.cache_bypassed_inserts.increment(value);
+    }
+
+    pub(crate) fn record_flush_duration(&self, duration: f64) {
+        self.flush_duration.record(duration);
+        PATHDB_METRIC_TOTALS.flush_duration.record(duration);
+    }
+
+    pub(crate) fn record_get_storage_root_duration(&self, duration: f64) {
+        self.get_storage_root_duration.record(duration);
+        PATHDB_METRIC_TOTALS.get_storage_root_duration.record(duration);
+    }
+
+    pub(crate) fn record_storage_root_batch_size(&self, size: usize) {
+        self.storage_root_batch_size.record(size as f64);
+        PATHDB_METRIC_TOTALS.storage_root_batch_size.record(size as f64);
+    }
+
+    pub(crate) fn increment_storage_root_found(&self, value: u64) {
+        self.storage_root_found.increment(value);
+        PATHDB_METRIC_TOTALS.storage_root_found.increment(value);
+    }
+
+    pub(crate) fn increment_storage_root_missing(&self, value: u64) {
+        self.storage_root_missing.increment(value);
+        PATHDB_METRIC_TOTALS.storage_root_missing.increment(value);
+    }
+
+    pub(crate) fn increment_storage_root_wiped(&self, value: u64) {
+        self.storage_root_wiped.increment(value);
+        PATHDB_METRIC_TOTALS.storage_root_wiped.increment(value);
+    }
+
+    pub(crate) fn increment_storage_root_corruptions(&self, value: u64) {
+        self.storage_root_corruptions.increment(value);
+        PATHDB_METRIC_TOTALS.storage_root_corruptions.increment(value);
+    }
 }
 
 /// PathDB implementation using RocksDB.
+///
+/// Fields are private so that every write goes through the caches; use
+/// [`PathDB::write_batch`] for atomic multi-key writes and [`PathDB::raw_db`]
+/// only when bypassing the caches is intended.
 pub struct PathDB {
     /// The underlying RocksDB instance.
-    pub db: Arc<DB>,
+    db: Arc<DB>,
     /// Set of Column Family names that exist in the database.
     column_family_names: Arc<Mutex<HashSet<String>>>,
     /// Configuration for the database.
-    pub config: PathProviderConfig,
+    config: PathProviderConfig,
     /// Write options for batch operations.
-    pub write_options: WriteOptions,
+    write_options: WriteOptions,
+    /// Write options for background maintenance writes.
+    maintenance_write_options: WriteOptions,
+    /// Rate limit of maintenance writes, if enabled, shared across clones.
+    maintenance_limiter: Option<Arc<MaintenanceLimiter>>,
     /// Read options for read operations.
-    pub read_options: ReadOptions,
-    /// LRU cache for key-value pairs.
-    pub trie_node_cache: Arc<Mutex<LruMap<Vec<u8>, Option<Vec<u8>>, ByLength>>>,
-    /// LRU cache for storage root key-value pairs.
-    pub storage_root_cache: Arc<Mutex<LruMap<Vec<u8>, Option<Vec<u8>>, ByLength>>>,
+    read_options: ReadOptions,
+    /// Read options of verified reads, which always verify checksums.
+    verified_read_options: ReadOptions,
+    /// Sharded LRU cache for key-value pairs.
+    trie_node_cache: Arc<ShardedCache>,
+    /// Sharded LRU cache for storage root key-value pairs.
+    storage_root_cache: Arc<ShardedCache>,
+    /// Trie node keys known to be missing, if enabled.
+    negative_cache: Option<Arc<NegativeCache>>,
+    /// Sampled read counts per trie node path, if enabled.
+    access_tracker: Option<Arc<AccessTracker>>,
+    /// Serializes trie node writes with deletion queue processing and cold
+    /// storage moves when either is enabled, shared across clones.
+    deletion_lock: Arc<Mutex<()>>,
+    /// Taken shared by trie node writes and exclusively by key migrations,
+    /// shared across clones.
+    migration_lock: Arc<RwLock<()>>,
+    /// Queued trie node keys whose deletion is being written outside the
+    /// deletion lock, shared across clones.
+    deletions_in_flight: Arc<DeletionsInFlight>,
+    /// Serializes updates of the heal queue and healed node count, shared across clones.
+    heal_lock: Arc<Mutex<()>>,
+    /// Options the database was opened with, kept to read RocksDB statistics.
+    db_options: Arc<Options>,
+    /// Options of the column families the SST bulk loader writes files for,
+    /// so the files match the column families they are ingested into.
+    sst_options: Arc<HashMap<&'static str, Options>>,
+    /// Sliding window of commit sizes for write amplification estimates, shared across clones.
+    amplification: Arc<Mutex<AmplificationWindow>>,
+    /// How the database was opened.
+    mode: OpenMode,
+    /// Sequence tracking for external write detection, shared across clones.
+    sequence: Arc<SequenceTracker>,
+    /// Set once the database is closed gracefully.
+    closed: Arc<AtomicBool>,
+    /// Background workers stopped by `close_gracefully`, shared across clones.
+    workers: Arc<Mutex<Vec<WorkerThread>>>,
+    /// Latest block committed by this process, which the version GC
+    /// compaction filter keeps the retention horizon behind; shared across clones.
+    version_gc_block: Arc<AtomicU64>,
+    /// Whether the storage root column family holds every account, so
+    /// missing owners have an empty storage root; shared across clones.
+    storage_roots_complete: Arc<AtomicBool>,
+    /// Bumped after every write of the persisted state, shared across clones.
+    persist_generation: Arc<AtomicU64>,
+    /// Node keys that may be stored as overflow pointers, so overwriting or
+    /// deleting them needs their chunks looked up; shared across clones.
+    overflow_keys: Arc<OverflowKeyFilter>,
+    /// Value of the `instance` label of the metrics.
+    metrics_instance: String,
     /// Metrics for the PathDB.
     metrics: PathDBMetrics,
 }
@@ -127,169 +628,714 @@ impl Debug for PathDB {
 
 impl Clone for PathDB {
     fn clone(&self) -> Self {
-        let write_options = WriteOptions::default();
-        let mut read_options = ReadOptions::default();
-        read_options.fill_cache(self.config.fill_cache);
-        read_options.set_readahead_size(self.config.readahead_size);
-        read_options.set_async_io(self.config.async_io);
-        read_options.set_verify_checksums(self.config.verify_checksums);
+        let write_options = write_options(self.config.write_durability());
+        let maintenance_write_options = maintenance_write_options(&self.config);
+        let read_options = point_read_options(&self.config);
+        let verified_read_options = verified_read_options(&self.config);
 
         Self {
             db: self.db.clone(),
             column_family_names: self.column_family_names.clone(),
             config: self.config.clone(),
             write_options,
+            maintenance_write_options,
+            maintenance_limiter: self.maintenance_limiter.clone(),
             read_options,
+            verified_read_options,
             trie_node_cache: self.trie_node_cache.clone(),
             storage_root_cache: self.storage_root_cache.clone(),
+            negative_cache: self.negative_cache.clone(),
+            access_tracker: self.access_tracker.clone(),
+            deletion_lock: self.deletion_lock.clone(),
+            migration_lock: self.migration_lock.clone(),
+            deletions_in_flight: self.deletions_in_flight.clone(),
+            heal_lock: self.heal_lock.clone(),
+            db_options: self.db_options.clone(),
+            sst_options: self.sst_options.clone(),
+            amplification: self.amplification.clone(),
+            mode: self.mode.clone(),
+            sequence: self.sequence.clone(),
+            closed: self.closed.clone(),
+            workers: self.workers.clone(),
+            version_gc_block: self.version_gc_block.clone(),
+            storage_roots_complete: self.storage_roots_complete.clone(),
+            persist_generation: self.persist_generation.clone(),
+            overflow_keys: self.overflow_keys.clone(),
+            metrics_instance: self.metrics_instance.clone(),
             metrics: self.metrics.clone(),
         }
     }
 }
 
+/// Sequence numbers accounted for by the writes of a PathDB instance and its clones.
+///
+/// Every key operation written to RocksDB consumes one sequence number, so
+/// the latest sequence number advancing further than the writes issued
+/// through PathDB since the last check reveals an external write.
+#[derive(Debug)]
+struct SequenceTracker {
+    /// Latest sequence number at the last check. Writes through PathDB hold
+    /// a read lock, checks the write lock, so no write is in flight while checking.
+    checked: RwLock<u64>,
+    /// Key operations written through PathDB since the last check.
+    own_writes: AtomicU64,
+}
+
+impl SequenceTracker {
+    fn new(latest: u64) -> Self {
+        Self { checked: RwLock::new(latest), own_writes: AtomicU64::new(0) }
+    }
+}
+
+/// Locks held while writing trie nodes, see [`PathDB::trie_write_guard`].
+pub(crate) struct TrieWriteGuard<'a> {
+    _deletion: Option<MutexGuard<'a, ()>>,
+    _migration: RwLockReadGuard<'a, ()>,
+}
+
+/// Trie node keys of a deletion queue batch written outside the deletion lock.
+///
+/// Writes that re-create one of the keys wait until the batch has landed, so
+/// the queued deletion can't clobber them.
+#[derive(Debug, Default)]
+struct DeletionsInFlight {
+    keys: Mutex<HashSet<Vec<u8>>>,
+    landed: Condvar,
+}
+
+impl DeletionsInFlight {
+    fn start(&self, db_keys: &[Box<[u8]>]) {
+        self.keys.lock().unwrap().extend(db_keys.iter().map(|db_key| db_key.to_vec()));
+    }
+
+    fn finish(&self, db_keys: &[Box<[u8]>]) {
+        let mut keys = self.keys.lock().unwrap();
+        for db_key in db_keys {
+            keys.remove(db_key.as_ref());
+        }
+        self.landed.notify_all();
+    }
+
+    fn wait(&self, db_key: &[u8]) {
+        let keys = self.keys.lock().unwrap();
+        let _keys = self.landed.wait_while(keys, |keys| keys.contains(db_key)).unwrap();
+    }
+}
+
+/// Collects the keys of the write batches replayed from the WAL.
+struct WrittenKeys(Vec<Vec<u8>>);
+
+impl WriteBatchIterator for WrittenKeys {
+    fn put(&mut self, key: &[u8], _value: &[u8]) {
+        self.0.push(key.to_vec());
+    }
+
+    fn delete(&mut self, key: &[u8]) {
+        self.0.push(key.to_vec());
+    }
+}
+
+impl WriteBatchIteratorCf for WrittenKeys {
+    fn put_cf(&mut self, _cf_id: u32, key: &[u8], _value: &[u8]) {
+        self.0.push(key.to_vec());
+    }
+
+    fn delete_cf(&mut self, _cf_id: u32, key: &[u8]) {
+        self.0.push(key.to_vec());
+    }
+
+    fn merge_cf(&mut self, _cf_id: u32, key: &[u8], _value: &[u8]) {
+        self.0.push(key.to_vec());
+    }
+}
+
+/// How a PathDB instance accesses the underlying RocksDB directory.
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum OpenMode {
+    /// Exclusive read-write access.
+    ReadWrite,
+    /// Read-only view of the database as of opening.
+    ReadOnly,
+    /// Secondary instance following a primary, keeping its own info logs
+    /// under the given path.
+    Secondary(String),
+}
+
 impl PathDB {
     /// Create a new PathDB instance.
     pub fn new(path: &str, config: PathProviderConfig) -> PathProviderResult<Self> {
-        let mut db_opts = Options::default();
-        db_opts.set_max_open_files(config.max_open_files);
-        db_opts.set_write_buffer_size(config.write_buffer_size);
-        db_opts.set_max_write_buffer_number(config.max_write_buffer_number);
-        db_opts.set_target_file_size_base(config.target_file_size_base);
-        db_opts.set_max_background_jobs(config.max_background_jobs);
-        db_opts.create_if_missing(config.create_if_missing);
-
-        // Ensure all required Column Families exist
-        ensure_column_families(path, &db_opts, &config)?;
-
-        // Now open database with all required Column Families
-        let mut cf_descriptors = Vec::new();
-        for cf_name in COLUMN_FAMILY_NAMES {
-            let mut cf_opts = Options::default();
-            cf_opts.set_max_write_buffer_number(config.max_write_buffer_number);
-            cf_opts.set_write_buffer_size(config.write_buffer_size);
-            cf_descriptors.push(ColumnFamilyDescriptor::new(cf_name, cf_opts));
+        Self::open(path, config, OpenMode::ReadWrite)
+    }
+
+    /// Open a read-only PathDB instance.
+    ///
+    /// The instance sees the database as of opening and never observes later
+    /// writes of the process owning it; every write fails. All required
+    /// Column Families must already exist.
+    pub fn open_read_only(path: &str, config: PathProviderConfig) -> PathProviderResult<Self> {
+        Self::open(path, config, OpenMode::ReadOnly)
+    }
+
+    /// Open a secondary PathDB instance following the primary at `path`.
+    ///
+    /// Lets another process, e.g. an RPC or analytics service, read trie
+    /// state while the main node writes to the database. The instance sees
+    /// new writes of the primary only after
+    /// [`PathDB::try_catch_up_with_primary`], which a [`CatchUpWorker`](crate::CatchUpWorker)
+    /// can call periodically. `secondary_path` holds the info logs of the
+    /// secondary instance. Every write fails.
+    pub fn open_as_secondary(path: &str, secondary_path: &str, config: PathProviderConfig) -> PathProviderResult<Self> {
+        Self::open(path, config, OpenMode::Secondary(secondary_path.to_string()))
+    }
+
+    fn open(path: &str, config: PathProviderConfig, mode: OpenMode) -> PathProviderResult<Self> {
+        if let Some(namespace) = &config.key_namespace {
+            validate_key_namespace(namespace)?;
+        }
+        if config.overflow_threshold.is_some() && config.overflow_chunk_size == 0 {
+            return Err(PathProviderError::InvalidOperation("Overflow chunk size must be greater than 0".to_string()));
+        }
+        if config.deferred_deletion && config.deletion_batch_size == 0 {
+            return Err(PathProviderError::InvalidOperation("Deletion batch size must be greater than 0".to_string()));
+        }
+        if config.prefix_extractor.prefix_len() == Some(0) {
+            return Err(PathProviderError::InvalidOperation("Prefix extractor length must be greater than 0".to_string()));
+        }
+        if config.max_subcompactions == 0 {
+            return Err(PathProviderError::InvalidOperation("Max subcompactions must be greater than 0".to_string()));
+        }
+        if config.rate_limit_bytes_per_sec.is_some_and(|rate| rate <= 0) {
+            return Err(PathProviderError::InvalidOperation("Rate limit must be greater than 0 bytes per second".to_string()));
+        }
+        if let Some(version_gc) = &config.version_gc {
+            let unversioned = |cf_name: &&String| COLUMN_FAMILY_NAMES.contains(&cf_name.as_str()) && cf_name.as_str() != STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME;
+            if let Some(cf_name) = version_gc.column_families.iter().find(unversioned) {
+                return Err(PathProviderError::InvalidOperation(format!("Column Family '{}' is not versioned", cf_name)));
+            }
+        }
+        if let Some(blob_files) = &config.blob_files {
+            if blob_files.blob_file_size == 0 {
+                return Err(PathProviderError::InvalidOperation("Blob file size must be greater than 0".to_string()));
+            }
+            if !(0.0..=1.0).contains(&blob_files.gc_age_cutoff) {
+                return Err(PathProviderError::InvalidOperation("Blob GC age cutoff must be between 0 and 1".to_string()));
+            }
+        }
+        if config.cache_max_value_size == Some(0) {
+            return Err(PathProviderError::InvalidOperation("Cache max value size must be greater than 0".to_string()));
+        }
+        if config.maintenance_rate_limit_bytes_per_sec == Some(0) {
+            return Err(PathProviderError::InvalidOperation("Maintenance rate limit must be greater than 0 bytes per second".to_string()));
+        }
+        if config.block_cache_size == Some(0) {
+            return Err(PathProviderError::InvalidOperation("Block cache size must be greater than 0".to_string()));
+        }
+        if config.bloom_filter_bits_per_key.is_some_and(|bits_per_key| bits_per_key <= 0.0) {
+            return Err(PathProviderError::InvalidOperation("Bloom filter bits per key must be greater than 0".to_string()));
+        }
+        for (cf_name, overrides) in &config.cf_overrides {
+            if overrides.bloom_filter_bits_per_key.is_some_and(|bits_per_key| bits_per_key <= 0.0) {
+                return Err(PathProviderError::InvalidOperation(format!("Bloom filter bits per key of Column Family '{}' must be greater than 0", cf_name)));
+            }
+            if overrides.write_buffer_size == Some(0) {
+                return Err(PathProviderError::InvalidOperation(format!("Write buffer size of Column Family '{}' must be greater than 0", cf_name)));
+            }
+            let lossy = cf_name == AUDIT_LOG_COLUMN_FAMILY_NAME || !COLUMN_FAMILY_NAMES.contains(&cf_name.as_str());
+            if matches!(overrides.compaction_style, Some(CompactionStyle::Fifo { .. })) && !lossy {
+                return Err(PathProviderError::InvalidOperation(format!("FIFO compaction would drop data of Column Family '{}'", cf_name)));
+            }
+        }
+        if matches!(config.compaction_style, CompactionStyle::Fifo { .. }) {
+            return Err(PathProviderError::InvalidOperation("FIFO compaction would drop trie nodes, set it per Column Family".to_string()));
         }
+        config.write_durability().validate()?;
+        if config.compression.zstd_max_dict_bytes > 0 && config.compression.bottommost != Some(CompressionType::Zstd) {
+            return Err(PathProviderError::InvalidOperation("Zstd dictionaries require zstd bottommost compression".to_string()));
+        }
+
+        let mut db_opts = database_options(&config);
 
-        let db = DB::open_cf_descriptors(&db_opts, path, cf_descriptors)
-            .map_err(|e| PathProviderError::Database(format!("Failed to open RocksDB: {}", e)))?;
+        let version_gc_block = Arc::new(AtomicU64::new(0));
+        let db = match &mode {
+            OpenMode::ReadWrite => {
+                // Missing Column Families are created on the live handle while opening
+                db_opts.create_missing_column_families(true);
+                let cf_descriptors = column_family_descriptors(path, &db_opts, &config, &version_gc_block)?;
+                DB::open_cf_descriptors(&db_opts, path, cf_descriptors)
+            }
+            OpenMode::ReadOnly => {
+                let cf_descriptors = existing_column_family_descriptors(path, &db_opts, &config, &version_gc_block)?;
+                DB::open_cf_descriptors_read_only(&db_opts, path, cf_descriptors, false)
+            }
+            OpenMode::Secondary(secondary_path) => {
+                // Secondary instances must keep all table files open to follow the primary
+                db_opts.set_max_open_files(-1);
+                let cf_descriptors = existing_column_family_descriptors(path, &db_opts, &config, &version_gc_block)?;
+                DB::open_cf_descriptors_as_secondary(&db_opts, path, secondary_path, cf_descriptors)
+            }
+        }
+        .map_err(|e| PathProviderError::rocksdb("Failed to open RocksDB", e))?;
 
         let cf_names_set: HashSet<String> = COLUMN_FAMILY_NAMES.iter().map(|s| s.to_string()).collect();
+        let sst_options = [DEFAULT_COLUMN_FAMILY_NAME, OVERFLOW_COLUMN_FAMILY_NAME]
+            .into_iter()
+            .map(|cf_name| (cf_name, cf_options_for(cf_name, &column_family_options(&config, None), &config, None, &version_gc_block)))
+            .collect();
 
-        let write_options = WriteOptions::default();
+        let write_options = write_options(config.write_durability());
+        let maintenance_write_options = maintenance_write_options(&config);
+        let maintenance_limiter = config.maintenance_rate_limit_bytes_per_sec.map(|rate| Arc::new(MaintenanceLimiter::new(rate)));
 
-        let mut read_options = ReadOptions::default();
-        read_options.fill_cache(config.fill_cache);
-        read_options.set_readahead_size(config.readahead_size);
-        read_options.set_async_io(config.async_io);
-        read_options.set_verify_checksums(config.verify_checksums);
+        let read_options = point_read_options(&config);
+        let verified_read_options = verified_read_options(&config);
 
-        let trie_node_cache_size = config.trie_node_cache_size;
-        let storage_root_cache_size = config.storage_root_cache_size;
+        let trie_node_cache_size = cf_cache_size(&config, DEFAULT_COLUMN_FAMILY_NAME, config.trie_node_cache_size);
+        let storage_root_cache_size = cf_cache_size(&config, STORAGE_ROOT_COLUMN_FAMILY_NAME, config.storage_root_cache_size);
+        let cache_shards = config.cache_shards;
+        let cache_max_value_size = config.cache_max_value_size;
+        let negative_cache = new_negative_cache(&config);
+        let access_tracker = new_access_tracker(&config);
+        let amplification_window = config.amplification_window;
+        let sequence = SequenceTracker::new(db.latest_sequence_number());
+        let metrics_instance = config.metrics_instance.clone().unwrap_or_else(|| path.to_string());
 
-        Ok(Self {
+        let path_db = Self {
             db: Arc::new(db),
             column_family_names: Arc::new(Mutex::new(cf_names_set)),
             config,
             write_options,
+            maintenance_write_options,
+            maintenance_limiter,
             read_options,
-            trie_node_cache: Arc::new(Mutex::new(LruMap::new(ByLength::new(trie_node_cache_size)))),
-            storage_root_cache: Arc::new(Mutex::new(LruMap::new(ByLength::new(storage_root_cache_size)))),
-            metrics: PathDBMetrics::new_with_labels(&[("instance", "default")]),
-        })
+            verified_read_options,
+            trie_node_cache: Arc::new(ShardedCache::new(trie_node_cache_size, cache_shards).with_max_value_size(cache_max_value_size)),
+            storage_root_cache: Arc::new(ShardedCache::new(storage_root_cache_size, cache_shards)),
+            negative_cache,
+            access_tracker,
+            deletion_lock: Arc::new(Mutex::new(())),
+            migration_lock: Arc::new(RwLock::new(())),
+            deletions_in_flight: Arc::new(DeletionsInFlight::default()),
+            heal_lock: Arc::new(Mutex::new(())),
+            db_options: Arc::new(db_opts),
+            sst_options: Arc::new(sst_options),
+            amplification: Arc::new(Mutex::new(AmplificationWindow::new(amplification_window))),
+            mode,
+            sequence: Arc::new(sequence),
+            closed: Arc::new(AtomicBool::new(false)),
+            workers: Arc::new(Mutex::new(Vec::new())),
+            version_gc_block,
+            storage_roots_complete: Arc::new(AtomicBool::new(false)),
+            persist_generation: Arc::new(AtomicU64::new(0)),
+            overflow_keys: Arc::new(OverflowKeyFilter::new()),
+            metrics: PathDBMetrics::new_with_labels(&[("instance", metrics_instance.clone())]),
+            metrics_instance,
+        };
+        path_db.check_schema_version()?;
+        path_db.load_storage_roots_complete()?;
+        path_db.load_overflow_keys()?;
+        Ok(path_db)
     }
 
     /// Get the underlying RocksDB instance.
-    pub fn inner(&self) -> &Arc<DB> {
+    ///
+    /// This is an escape hatch: reads and writes through the returned handle
+    /// bypass the LRU caches, key namespacing and overflow chunking. Writing
+    /// through it leaves the caches stale; call [`PathDB::invalidate_keys`]
+    /// or [`PathDB::invalidate_external_writes`] afterwards, or prefer
+    /// [`PathDB::write_batch`].
+    pub fn raw_db(&self) -> &Arc<DB> {
         &self.db
     }
 
+    /// Options the database was opened with.
+    pub(crate) fn db_options(&self) -> &Options {
+        &self.db_options
+    }
+
+    /// Get the options to write SST files for column family `cf_name` with,
+    /// for the trie node and overflow column families.
+    pub(crate) fn sst_options(&self, cf_name: &str) -> PathProviderResult<&Options> {
+        self.sst_options.get(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))
+    }
+
+    /// Get the underlying RocksDB instance.
+    #[deprecated(note = "use `raw_db`, which documents the cache-bypassing semantics")]
+    pub fn inner(&self) -> &Arc<DB> {
+        self.raw_db()
+    }
+
     /// Get the configuration.
     pub fn config(&self) -> &PathProviderConfig {
         &self.config
     }
 
+    /// Whether the instance was opened read-only or as a secondary.
+    pub fn is_read_only(&self) -> bool {
+        self.mode != OpenMode::ReadWrite
+    }
+
+    /// Catch up with the writes of the primary instance.
+    ///
+    /// Only valid on instances opened with [`PathDB::open_as_secondary`]. The
+    /// LRU caches are cleared afterwards since they may hold nodes the
+    /// primary has since overwritten or deleted.
+    pub fn try_catch_up_with_primary(&self) -> PathProviderResult<()> {
+        if !matches!(self.mode, OpenMode::Secondary(_)) {
+            return Err(PathProviderError::InvalidOperation("Catching up requires a secondary instance".to_string()));
+        }
+        self.db.try_catch_up_with_primary()
+            .map_err(|e| PathProviderError::rocksdb("RocksDB catch up with primary error", e))?;
+
+        self.trie_node_cache.clear();
+        self.storage_root_cache.clear();
+        self.forget_all_missing();
+        self.load_storage_roots_complete()
+    }
+
     /// Clear the LRU cache.
     pub fn clear_cache(&self) {
         warn!(target: "pathdb::rocksdb", "Clearing LRU cache");
-        self.trie_node_cache.lock().unwrap().clear();
-        self.storage_root_cache.lock().unwrap().clear();
+        self.trie_node_cache.clear();
+        self.storage_root_cache.clear();
+        self.forget_all_missing();
     }
 
     /// Get cache statistics.
     pub fn cache_stats(&self) -> (usize, usize) {
-        let trie_node_cache = self.trie_node_cache.lock().unwrap();
-        let storage_root_cache = self.storage_root_cache.lock().unwrap();
+        (self.trie_node_cache.len(), self.storage_root_cache.len())
+    }
 
-        (trie_node_cache.len(), storage_root_cache.len())
+    /// Add the trie node cache inserts bypassed since the previous report to
+    /// the bypassed insert counter. Reported after every commit and on close.
+    fn report_cache_bypassed_inserts(&self) {
+        let bypassed = self.trie_node_cache.take_bypassed_inserts();
+        if bypassed > 0 {
+            self.metrics.increment_cache_bypassed_inserts(bypassed);
+        }
     }
 
-    /// Create a new metrics instance for the PathDB.
-    pub fn with_new_metrics(&mut self, instance_name: &str) {
-        self.metrics = PathDBMetrics::new_with_labels(&[("instance", instance_name.to_string())]);
+    /// Get the capacities in entries of the trie node and storage root LRU
+    /// caches, which differ from the configured sizes once a
+    /// [`CacheController`](crate::cache_controller::CacheController) resized them.
+    pub fn cache_capacities(&self) -> (u32, u32) {
+        (self.trie_node_cache.capacity(), self.storage_root_cache.capacity())
     }
-}
 
-impl PathDB {
-    pub fn get_raw_trie_node(&self, key: &[u8]) -> PathProviderResult<Option<Vec<u8>>> {
-        trace!(target: "pathdb::rocksdb", "Getting key: {:?}", key);
+    /// The trie node and storage root LRU caches, shared with clones.
+    pub(crate) fn lru_caches(&self) -> [Arc<ShardedCache>; 2] {
+        [self.trie_node_cache.clone(), self.storage_root_cache.clone()]
+    }
 
-        // Check cache first
-        {
-            let cache = self.trie_node_cache.lock().unwrap();
-            if let Some(cached_value) = cache.peek(key) {
-                self.metrics.trie_node_cache_hits.increment(1);
-                trace!(target: "pathdb::rocksdb", "Found value in cache for key: {:?}", key);
-                return Ok(cached_value.clone());
-            } else {
-                self.metrics.trie_node_cache_misses.increment(1);
-            }
+    /// Latest block committed by this process, shared with clones and the
+    /// version GC compaction filters.
+    pub(crate) fn version_gc_block(&self) -> &AtomicU64 {
+        &self.version_gc_block
+    }
+
+    /// Value of the `instance` label of the metrics of this database.
+    pub(crate) fn metrics_instance(&self) -> &str {
+        &self.metrics_instance
+    }
+
+    /// Remove `keys` from the LRU caches, returning the number of entries removed.
+    ///
+    /// Use after writing the keys through [`PathDB::raw_db`] or from another
+    /// process, so later reads go to the database.
+    pub fn invalidate_keys<K: AsRef<[u8]>>(&self, keys: impl IntoIterator<Item = K>) -> usize {
+        let mut trie_node_cache = self.trie_node_cache.lock_all();
+        let mut storage_root_cache = self.storage_root_cache.lock_all();
+
+        let mut removed = 0;
+        for key in keys {
+            let key = key.as_ref();
+            removed += usize::from(trie_node_cache.remove(key));
+            removed += usize::from(storage_root_cache.remove(key));
+            self.forget_missing(key);
         }
+        removed
+    }
 
-        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| {
-            PathProviderError::Database(format!("Column Family '{}' handle not found", DEFAULT_COLUMN_FAMILY_NAME))
-        })?;
-        let key_hex = key.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    /// Remove every cached key starting with `prefix`, returning the number
+    /// of entries removed.
+    ///
+    /// Walks the whole negative cache, prefer [`PathDB::invalidate_keys`]
+    /// when the written keys are known.
+    pub fn invalidate_prefix(&self, prefix: &[u8]) -> usize {
+        let mut trie_node_cache = self.trie_node_cache.lock_all();
+        let mut storage_root_cache = self.storage_root_cache.lock_all();
 
-        // Cache miss, read from DB
-        match self.db.get_cf_opt(&cf, key, &self.read_options) {
-            Ok(Some(value)) => {
-                trace!(target: "pathdb::rocksdb", "Found value in CF '{}' for key: 0x{}", DEFAULT_COLUMN_FAMILY_NAME, key_hex);
-                self.trie_node_cache.lock().unwrap().insert(key.to_vec(), Some(value.to_vec()));
-                Ok(Some(value))
+        if let Some(negative_cache) = &self.negative_cache {
+            negative_cache.remove_matching(|key| key.starts_with(prefix));
+        }
+        trie_node_cache.remove_prefix(prefix) + storage_root_cache.remove_prefix(prefix)
+    }
+
+    /// Detect writes that bypassed this instance and its clones and drop the
+    /// keys they wrote from the LRU caches.
+    ///
+    /// Compares the latest RocksDB sequence number with the writes issued
+    /// through PathDB since the last check. On a mismatch the written keys
+    /// are read back from the WAL and invalidated; if the WAL no longer
+    /// covers them, e.g. because the writer disabled it, both caches are
+    /// cleared. Returns whether external writes were found.
+    ///
+    /// Keys written through PathDB in the same range are invalidated too,
+    /// which only costs cache misses.
+    pub fn invalidate_external_writes(&self) -> bool {
+        let (since, latest) = {
+            let mut checked = self.sequence.checked.write().unwrap();
+            let latest = self.db.latest_sequence_number();
+            let own_writes = self.sequence.own_writes.swap(0, Ordering::SeqCst);
+            let external = latest.saturating_sub(*checked) > own_writes;
+            let since = *checked + 1;
+            *checked = latest;
+            if !external {
+                return false;
             }
-            Ok(None) => {
-                trace!(target: "pathdb::rocksdb", "Key not found in CF '{}': 0x{}", DEFAULT_COLUMN_FAMILY_NAME, key_hex);
-                Ok(None)
+            // Released before touching the caches, writers lock them before the sequence
+            (since, latest)
+        };
+
+        match self.keys_written_since(since, latest) {
+            Some(db_keys) => {
+                let keys = db_keys.iter().filter_map(|db_key| self.logical_key(db_key));
+                let removed = self.invalidate_keys(keys);
+                trace!(target: "pathdb::rocksdb", "Invalidated {} cache entries after external writes up to sequence {}", removed, latest);
             }
-            Err(e) => {
-                error!(target: "pathdb::rocksdb", "Error getting in CF '{}' for key 0x{}: {}", DEFAULT_COLUMN_FAMILY_NAME, key_hex, e);
-                Err(PathProviderError::Database(format!("RocksDB get in CF '{}' for key 0x{} error: {}", DEFAULT_COLUMN_FAMILY_NAME, key_hex, e)))
+            None => {
+                warn!(target: "pathdb::rocksdb", "External writes up to sequence {} not covered by the WAL, clearing caches", latest);
+                self.trie_node_cache.clear();
+                self.storage_root_cache.clear();
+                self.forget_all_missing();
             }
         }
+        true
     }
 
-    pub fn put_raw_trie_node(&self, key: &[u8], value: &[u8]) -> PathProviderResult<()> {
-        trace!(target: "pathdb::rocksdb", "Putting key: {:?}, value_len: {}", key, value.len());
+    /// Keys of all writes with sequence numbers in `since..=latest` read from
+    /// the WAL, `None` if the WAL doesn't cover the whole range or holds
+    /// range deletions, whose keys aren't known.
+    fn keys_written_since(&self, since: u64, latest: u64) -> Option<Vec<Vec<u8>>> {
+        let updates = self.db.get_updates_since(since).ok()?;
 
-        // Update cache first
-        self.trie_node_cache.lock().unwrap().insert(key.to_vec(), Some(value.to_vec()));
-
-        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| {
-            PathProviderError::Database(format!("Column Family '{}' handle not found", DEFAULT_COLUMN_FAMILY_NAME))
-        })?;
+        let mut keys = WrittenKeys(Vec::new());
+        let mut next = since;
+        for update in updates {
+            let (sequence, batch) = update.ok()?;
+            if sequence > next {
+                // Gap in the WAL
+                return None;
+            }
+            let reported = keys.0.len();
+            batch.iterate_cf(&mut keys);
+            if keys.0.len() - reported != batch.len() {
+                // Range deletions are counted but not reported by the iterator
+                return None;
+            }
+            next = next.max(sequence + batch.len() as u64);
+        }
+        (next > latest).then_some(keys.0)
+    }
+
+    /// Map a key stored in RocksDB to the logical key of this instance,
+    /// `None` for keys of other namespaces.
+    pub(crate) fn logical_key<'a>(&self, db_key: &'a [u8]) -> Option<&'a [u8]> {
+        match &self.config.key_namespace {
+            Some(namespace) => {
+                let (&len, rest) = db_key.split_first()?;
+                (len as usize == namespace.len()).then_some(())?;
+                rest.strip_prefix(namespace.as_slice())
+            }
+            None => Some(db_key),
+        }
+    }
+
+    /// Write `batch` to RocksDB, accounting for the sequence numbers it
+    /// consumes so it isn't taken for an external write.
+    pub(crate) fn write_raw_batch(&self, batch: WriteBatch) -> Result<(), rocksdb::Error> {
+        self.write_raw_batch_opt(batch, &self.write_options)
+    }
+
+    /// Write a maintenance `batch` like `write_raw_batch`, with the
+    /// maintenance write options, and charge it to the maintenance rate limit.
+    ///
+    /// Callers wait for the rate limit with `wait_for_maintenance_budget`
+    /// before taking any lock.
+    fn write_maintenance_raw_batch(&self, batch: WriteBatch) -> Result<(), rocksdb::Error> {
+        let bytes = batch.size_in_bytes() as u64;
+        self.write_raw_batch_opt(batch, &self.maintenance_write_options)?;
+        if let Some(maintenance_limiter) = &self.maintenance_limiter {
+            maintenance_limiter.charge(bytes);
+        }
+        Ok(())
+    }
+
+    /// Block until the maintenance rate limit allows the next maintenance write.
+    fn wait_for_maintenance_budget(&self) {
+        if let Some(maintenance_limiter) = &self.maintenance_limiter {
+            maintenance_limiter.wait();
+        }
+    }
+
+    /// Like `write_raw_batch`, with explicit write options.
+    fn write_raw_batch_opt(&self, batch: WriteBatch, write_options: &WriteOptions) -> Result<(), rocksdb::Error> {
+        let _checked = self.sequence.checked.read().unwrap();
+        let ops = batch.len() as u64;
+        self.db.write_opt(batch, write_options)?;
+        self.sequence.own_writes.fetch_add(ops, Ordering::SeqCst);
+        Ok(())
+    }
+
+    /// Create a new metrics instance for the PathDB, replacing the `instance`
+    /// label set from `PathProviderConfig::metrics_instance` or the path.
+    pub fn with_new_metrics(&mut self, instance_name: &str) {
+        self.metrics = PathDBMetrics::new_with_labels(&[("instance", instance_name.to_string())]);
+        self.metrics_instance = instance_name.to_string();
+    }
+
+    /// Create a view of this database whose keys live under `namespace`.
+    ///
+    /// The returned instance shares the underlying RocksDB handle but owns fresh
+    /// caches, so independent tries (e.g. several test chains or shadow-fork
+    /// states) can be stored side by side without cross-contamination.
+    pub fn with_namespace(&self, namespace: &[u8]) -> PathProviderResult<Self> {
+        validate_key_namespace(namespace)?;
+
+        let mut config = self.config.clone();
+        config.key_namespace = Some(namespace.to_vec());
+
+        let mut db = self.clone();
+        let trie_node_cache_size = cf_cache_size(&config, DEFAULT_COLUMN_FAMILY_NAME, config.trie_node_cache_size);
+        let storage_root_cache_size = cf_cache_size(&config, STORAGE_ROOT_COLUMN_FAMILY_NAME, config.storage_root_cache_size);
+        db.trie_node_cache = Arc::new(ShardedCache::new(trie_node_cache_size, config.cache_shards).with_max_value_size(config.cache_max_value_size));
+        db.storage_root_cache = Arc::new(ShardedCache::new(storage_root_cache_size, config.cache_shards));
+        db.negative_cache = new_negative_cache(&config);
+        db.access_tracker = new_access_tracker(&config);
+        db.config = config;
+        Ok(db)
+    }
+
+    /// Get the key namespace, if any.
+    pub fn namespace(&self) -> Option<&[u8]> {
+        self.config.key_namespace.as_deref()
+    }
+
+    /// Map a logical key to the key stored in RocksDB.
+    ///
+    /// Namespaced keys are encoded as `len(namespace) || namespace || key`, the
+    /// length byte keeps namespaces that are prefixes of each other apart.
+    /// Caches are per instance and stay keyed by the logical key.
+    pub(crate) fn db_key<'a>(&self, key: &'a [u8]) -> Cow<'a, [u8]> {
+        match &self.config.key_namespace {
+            Some(namespace) => {
+                let mut db_key = Vec::with_capacity(1 + namespace.len() + key.len());
+                db_key.push(namespace.len() as u8);
+                db_key.extend_from_slice(namespace);
+                db_key.extend_from_slice(key);
+                Cow::Owned(db_key)
+            }
+            None => Cow::Borrowed(key),
+        }
+    }
+}
+
+impl PathDB {
+    pub fn get_raw_trie_node(&self, key: &[u8]) -> PathProviderResult<Option<Bytes>> {
+        self.get_raw_trie_node_with(key, &self.read_options, true)
+    }
+
+    /// Get a trie node like `get_raw_trie_node`, verifying the checksums of
+    /// every block read from disk whatever `verify_checksums` is set to.
+    ///
+    /// Meant for consensus-critical reads, e.g. the nodes resolved while
+    /// computing a state root, so a corrupted block fails the read instead of
+    /// yielding a wrong root. The LRU and negative caches are skipped, so the
+    /// node is always read from disk; the verified value is cached afterwards.
+    pub fn get_verified_raw_trie_node(&self, key: &[u8]) -> PathProviderResult<Option<Bytes>> {
+        self.get_raw_trie_node_with(key, &self.verified_read_options, false)
+    }
+
+    fn get_raw_trie_node_with(&self, key: &[u8], read_options: &ReadOptions, use_cache: bool) -> PathProviderResult<Option<Bytes>> {
+        trace!(target: "pathdb::rocksdb", "Getting key: {:?}", key);
+        self.record_access(key);
+
+        // Check cache first
+        if use_cache {
+            if let Some(cached_value) = self.trie_node_cache.lookup(key) {
+                self.metrics.increment_trie_node_cache_hits(1);
+                trace!(target: "pathdb::rocksdb", "Found value in cache for key: {:?}", key);
+                return Ok(cached_value);
+            } else {
+                self.metrics.increment_trie_node_cache_misses(1);
+            }
+            if self.is_known_missing(key) {
+                trace!(target: "pathdb::rocksdb", "Key known to be missing: {:?}", key);
+                return Ok(None);
+            }
+        }
+
+        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
+        let key_hex = key.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        // Cache miss, read from DB
+        let db_key = self.db_key(key);
+        if self.is_deletion_queued(&db_key)? {
+            self.remember_missing(key);
+            return Ok(None);
+        }
+        match self.db.get_cf_opt(&cf, &db_key, read_options) {
+            Ok(Some(value)) => {
+                trace!(target: "pathdb::rocksdb", "Found value in CF '{}' for key: 0x{}", DEFAULT_COLUMN_FAMILY_NAME, key_hex);
+                let value = Bytes::from(self.resolve_overflow_with(&db_key, value, read_options)?);
+                self.trie_node_cache.insert(key.to_vec(), Some(value.clone()));
+                Ok(Some(value))
+            }
+            Ok(None) => match self.read_cold_trie_node(&db_key, read_options)? {
+                Some(value) => {
+                    trace!(target: "pathdb::rocksdb", "Found value in CF '{}' for key: 0x{}", COLD_TRIE_NODE_COLUMN_FAMILY_NAME, key_hex);
+                    let value = Bytes::from(self.resolve_overflow_with(&db_key, value, read_options)?);
+                    self.trie_node_cache.insert(key.to_vec(), Some(value.clone()));
+                    Ok(Some(value))
+                }
+                None => {
+                    trace!(target: "pathdb::rocksdb", "Key not found in CF '{}': 0x{}", DEFAULT_COLUMN_FAMILY_NAME, key_hex);
+                    self.remember_missing(key);
+                    Ok(None)
+                }
+            },
+            Err(e) => {
+                error!(target: "pathdb::rocksdb", "Error getting in CF '{}' for key 0x{}: {}", DEFAULT_COLUMN_FAMILY_NAME, key_hex, e);
+                Err(PathProviderError::rocksdb(format!("RocksDB get in CF '{}' for key 0x{} error", DEFAULT_COLUMN_FAMILY_NAME, key_hex), e))
+            }
+        }
+    }
+
+    pub fn put_raw_trie_node(&self, key: &[u8], value: &[u8]) -> PathProviderResult<()> {
+        trace!(target: "pathdb::rocksdb", "Putting key: {:?}, value_len: {}", key, value.len());
+
+        // Drop the stale entry first, the new value is cached once it landed
+        // so the cache never runs ahead of the database
+        self.trie_node_cache.remove(key);
+        self.forget_missing(key);
+
+        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
 
         let key_hex = key.iter().map(|b| format!("{:02x}", b)).collect::<String>();
 
-        // Then write to DB
-        match self.db.put_cf_opt(&cf, key, value, &self.write_options) {
+        // Then write to DB, together with any overflow chunks and cancelled deletion
+        let _write_guard = self.trie_write_guard();
+        let mut batch = WriteBatch::default();
+        self.batch_put_trie_node(&mut batch, &cf, &self.db_key(key), value)?;
+        let result = self.write_raw_batch(batch);
+        match result {
             Ok(()) => {
                 trace!(target: "pathdb::rocksdb", "Successfully put in CF '{}' for key 0x{}", DEFAULT_COLUMN_FAMILY_NAME, key_hex);
+                self.trie_node_cache.insert(key.to_vec(), Some(Bytes::copy_from_slice(value)));
                 Ok(())
             }
             Err(e) => {
                 error!(target: "pathdb::rocksdb", "Error putting in CF '{}' for key 0x{}: {}", DEFAULT_COLUMN_FAMILY_NAME, key_hex, e);
-                self.trie_node_cache.lock().unwrap().remove(key);
-                Err(PathProviderError::Database(format!("RocksDB put in CF '{}' for key 0x{} error: {}", DEFAULT_COLUMN_FAMILY_NAME, key_hex, e)))
+                Err(PathProviderError::rocksdb(format!("RocksDB put in CF '{}' for key 0x{} error", DEFAULT_COLUMN_FAMILY_NAME, key_hex), e))
             }
         }
     }
@@ -298,23 +1344,25 @@ impl PathDB {
         trace!(target: "pathdb::rocksdb", "Deleting key: {:?}", key);
 
         // Remove from cache first
-        self.trie_node_cache.lock().unwrap().remove(key);
+        self.trie_node_cache.remove(key);
 
-        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| {
-            PathProviderError::Database(format!("Column Family '{}' handle not found", DEFAULT_COLUMN_FAMILY_NAME))
-        })?;
+        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
 
         let key_hex = key.iter().map(|b| format!("{:02x}", b)).collect::<String>();
 
-        // Then delete from DB
-        match self.db.delete_cf_opt(&cf, key, &self.write_options) {
+        // Then delete from DB, together with any overflow chunks and pending deletion
+        let _write_guard = self.trie_write_guard();
+        let mut batch = WriteBatch::default();
+        self.batch_delete_trie_node(&mut batch, &cf, &self.db_key(key))?;
+        let result = self.write_raw_batch(batch);
+        match result {
             Ok(()) => {
                 trace!(target: "pathdb::rocksdb", "Successfully deleted in CF '{}' for key 0x{}", DEFAULT_COLUMN_FAMILY_NAME, key_hex);
                 Ok(())
             }
             Err(e) => {
                 error!(target: "pathdb::rocksdb", "Error deleting in CF '{}' for key 0x{}: {}", DEFAULT_COLUMN_FAMILY_NAME, key_hex, e);
-                Err(PathProviderError::Database(format!("RocksDB delete in CF '{}' for key 0x{} error: {}", DEFAULT_COLUMN_FAMILY_NAME, key_hex, e)))
+                Err(PathProviderError::rocksdb(format!("RocksDB delete in CF '{}' for key 0x{} error", DEFAULT_COLUMN_FAMILY_NAME, key_hex), e))
             }
         }
     }
@@ -323,143 +1371,2083 @@ impl PathDB {
         trace!(target: "pathdb::rocksdb", "Checking existence of key: {:?}", key);
 
         // Check cache first
-        {
-            let cache = self.trie_node_cache.lock().unwrap();
-            if let Some(cached_value) = cache.peek(key) {
-                trace!(target: "pathdb::rocksdb", "Key exists in cache: {:?}", key);
-                self.metrics.trie_node_cache_hits.increment(1);
-                return Ok(cached_value.is_some());
-            } else {
-                self.metrics.trie_node_cache_misses.increment(1);
-            }
+        if let Some(cached_value) = self.trie_node_cache.lookup(key) {
+            trace!(target: "pathdb::rocksdb", "Key exists in cache: {:?}", key);
+            self.metrics.increment_trie_node_cache_hits(1);
+            return Ok(cached_value.is_some());
+        } else {
+            self.metrics.increment_trie_node_cache_misses(1);
+        }
+        if self.is_known_missing(key) {
+            return Ok(false);
         }
 
-        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| {
-            PathProviderError::Database(format!("Column Family '{}' handle not found", DEFAULT_COLUMN_FAMILY_NAME))
-        })?;
+        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
             
         let key_hex = key.iter().map(|b| format!("{:02x}", b)).collect::<String>();
 
         // Cache miss, check DB
-        match self.db.get_cf_opt(&cf, key, &self.read_options) {
+        let db_key = self.db_key(key);
+        if self.is_deletion_queued(&db_key)? {
+            self.remember_missing(key);
+            return Ok(false);
+        }
+        match self.db.get_cf_opt(&cf, &db_key, &self.read_options) {
             Ok(Some(_)) => {
                 trace!(target: "pathdb::rocksdb", "Key exists in CF '{}' for key 0x{}", DEFAULT_COLUMN_FAMILY_NAME, key_hex);
-                self.trie_node_cache.lock().unwrap().insert(key.to_vec(), Some(vec![]));
+                self.trie_node_cache.insert(key.to_vec(), Some(Bytes::new()));
+                Ok(true)
+            }
+            Ok(None) if self.read_cold_trie_node(&db_key, &self.read_options)?.is_some() => {
+                trace!(target: "pathdb::rocksdb", "Key exists in CF '{}' for key 0x{}", COLD_TRIE_NODE_COLUMN_FAMILY_NAME, key_hex);
                 Ok(true)
             }
             Ok(None) => {
                 trace!(target: "pathdb::rocksdb", "Key does not exist in CF '{}' for key 0x{}", DEFAULT_COLUMN_FAMILY_NAME, key_hex);
+                self.remember_missing(key);
                 Ok(false)
             }
             Err(e) => {
                 error!(target: "pathdb::rocksdb", "Error checking existence of key in CF '{}' for key 0x{}: {}", DEFAULT_COLUMN_FAMILY_NAME, key_hex, e);
-                Err(PathProviderError::Database(format!("RocksDB exists in CF '{}' for key 0x{} error: {}", DEFAULT_COLUMN_FAMILY_NAME, key_hex, e)))
+                Err(PathProviderError::rocksdb(format!("RocksDB exists in CF '{}' for key 0x{} error", DEFAULT_COLUMN_FAMILY_NAME, key_hex), e))
             }
         }
     }
 
-    pub fn get_raw_storage_root(&self, key: &[u8]) -> PathProviderResult<Option<Vec<u8>>> {
+    pub fn get_raw_storage_root(&self, key: &[u8]) -> PathProviderResult<Option<Bytes>> {
         trace!(target: "pathdb::rocksdb", "Getting key: {:?}", key);
 
         // Check cache first
-        {
-            let cache = self.storage_root_cache.lock().unwrap();
-            if let Some(cached_value) = cache.peek(key) {
-                self.metrics.storage_root_cache_hits.increment(1);
-                trace!(target: "pathdb::rocksdb", "Found value in cache for key: {:?}", key);
-                return Ok(cached_value.clone());
+        if let Some(cached_value) = self.storage_root_cache.lookup(key) {
+            self.metrics.increment_storage_root_cache_hits(1);
+            trace!(target: "pathdb::rocksdb", "Found value in cache for key: {:?}", key);
+            return Ok(cached_value);
+        } else {
+            self.metrics.increment_storage_root_cache_misses(1);
+        }
+
+        let cf = self.db.cf_handle(STORAGE_ROOT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(STORAGE_ROOT_COLUMN_FAMILY_NAME))?;
+
+        let key_hex = key.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+
+        // Cache miss, read from DB
+        match self.db.get_cf_opt(&cf, self.db_key(key), &self.read_options) {
+            Ok(Some(value)) => {
+                trace!(target: "pathdb::rocksdb", "Found value in CF '{}' for key 0x{}", STORAGE_ROOT_COLUMN_FAMILY_NAME, key_hex);
+                let value = Bytes::from(value);
+                self.storage_root_cache.insert(key.to_vec(), Some(value.clone()));
+                Ok(Some(value))
+            }
+            Ok(None) => {
+                trace!(target: "pathdb::rocksdb", "Key not found in CF '{}' for key 0x{}", STORAGE_ROOT_COLUMN_FAMILY_NAME, key_hex);
+                Ok(None)
+            }
+            Err(e) => {
+                error!(target: "pathdb::rocksdb", "Error getting in CF '{}' for key 0x{}: {}", STORAGE_ROOT_COLUMN_FAMILY_NAME, key_hex, e);
+                Err(PathProviderError::rocksdb(format!("RocksDB get in CF '{}' for key 0x{} error", STORAGE_ROOT_COLUMN_FAMILY_NAME, key_hex), e))
+            }
+        }
+    }
+
+    /// Decode the storage root of `hased_address`, see `TrieDatabase::get_storage_root`.
+    fn read_storage_root(&self, hased_address: B256) -> PathProviderResult<Option<B256>> {
+        let value = self.get_raw_storage_root(hased_address.as_slice())?;
+        if let Some(value) = value {
+            if value.len() == 32 {
+                Ok(Some(B256::from_slice(&value)))
             } else {
-                self.metrics.storage_root_cache_misses.increment(1);
+                let address_hex = format!("0x{:x}", hased_address);
+                let value_hex = value.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+                error!(target: "pathdb::rocksdb", "Storage root value length is not 32 for address: {}, value_len: {}, value: 0x{}", address_hex, value.len(), value_hex);
+                self.metrics.increment_storage_root_corruptions(1);
+                Ok(None)
+            }
+        } else if self.storage_roots_complete.load(Ordering::Acquire) {
+            // Every account has an entry, a missing owner has no storage
+            Ok(Some(EMPTY_ROOT_HASH))
+        } else {
+            Ok(None)
+        }
+    }
+
+    pub fn get_raw_meta_data(&self, key: &[u8]) -> PathProviderResult<Option<Vec<u8>>> {
+        // Check cache first
+        if let Some(cached_value) = self.trie_node_cache.peek(key) {
+            trace!(target: "pathdb::rocksdb", "Found value in cache for key: {:?}", key);
+            return Ok(cached_value.map(|value| value.to_vec()));
+        }
+
+        // TODO:: change to META_COLUMN_FAMILY_NAME from default CF in the future.
+        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
+
+        // Convert key to readable string: try UTF-8 first, fallback to hex if invalid
+        let key_string = String::from_utf8_lossy(key).to_string();
+        
+        match self.db.get_cf_opt(&cf, self.db_key(key), &self.read_options) {
+            Ok(Some(value)) => {
+                trace!(target: "pathdb::rocksdb", "Found value in CF '{}' for key: {}", DEFAULT_COLUMN_FAMILY_NAME, key_string);
+                self.trie_node_cache.insert(key.to_vec(), Some(Bytes::copy_from_slice(&value)));
+                Ok(Some(value))
+            }
+            Ok(None) => {
+                trace!(target: "pathdb::rocksdb", "Key not found in CF '{}' for key: {}", DEFAULT_COLUMN_FAMILY_NAME, key_string);
+                Ok(None)
+            }
+            Err(e) => {
+                error!(target: "pathdb::rocksdb", "Error getting in CF '{}' for key {}: {}", DEFAULT_COLUMN_FAMILY_NAME, key_string, e);
+                Err(PathProviderError::rocksdb(format!("RocksDB get in CF '{}' for key {} error", DEFAULT_COLUMN_FAMILY_NAME, key_string), e))
+            }
+        }
+    }
+}
+
+/// Linear walks over trie node key ranges.
+impl PathDB {
+    /// Build read options for a linear walk over the logical key range `[lower, upper)`.
+    ///
+    /// Unlike the point-read options, these set iterate bounds and a larger
+    /// readahead so walking a subtree is I/O-bound rather than latency-bound.
+    pub fn scan_read_options(&self, lower: Option<&[u8]>, upper: Option<&[u8]>) -> ReadOptions {
+        let mut read_options = ReadOptions::default();
+        read_options.fill_cache(self.config.scan_fill_cache);
+        read_options.set_readahead_size(self.config.scan_readahead_size);
+        read_options.set_async_io(self.config.async_io);
+        read_options.set_verify_checksums(self.config.verify_checksums);
+
+        // Walks may cross prefixes, which prefix seeks can't
+        read_options.set_total_order_seek(true);
+        read_options.set_iterate_lower_bound(self.db_key(lower.unwrap_or_default()).into_owned());
+        let upper = match upper {
+            Some(upper) => Some(self.db_key(upper).into_owned()),
+            None => self.config.key_namespace.as_ref().and_then(|_| prefix_upper_bound(&self.db_key(&[]))),
+        };
+        if let Some(upper) = upper {
+            read_options.set_iterate_upper_bound(upper);
+        }
+        read_options
+    }
+
+    /// Build read options for a linear walk over every key of this
+    /// instance, bounded to its namespace prefix if one is set.
+    pub(crate) fn namespace_read_options(&self) -> ReadOptions {
+        self.scan_read_options(None, None)
+    }
+
+    /// Iterate in key order over all trie nodes whose key starts with `prefix`.
+    ///
+    /// Yields `(key, value)` pairs with logical (un-namespaced) keys and
+    /// resolved overflow values. Reads bypass the trie node cache.
+    pub fn iter_trie_nodes(&self, prefix: &[u8]) -> PathProviderResult<impl Iterator<Item = PathProviderResult<(Vec<u8>, Vec<u8>)>> + '_> {
+        let upper = prefix_upper_bound(prefix);
+        self.iter_range(prefix, upper.as_deref())
+    }
+
+    /// Like `iter_trie_nodes`, overlaying the trie nodes of `difflayers`.
+    ///
+    /// Nodes in the difflayers take precedence over the database, the most
+    /// recent layer first, and nodes deleted in a difflayer are skipped, so
+    /// iterating at a root that is not flushed yet agrees with point reads
+    /// through the same difflayers.
+    pub fn iter_trie_nodes_with_difflayers(&self, prefix: &[u8], difflayers: &DiffLayers) -> PathProviderResult<impl Iterator<Item = PathProviderResult<(Vec<u8>, Vec<u8>)>> + '_> {
+        // Oldest layer first, so more recent layers overwrite it
+        let mut overlay: BTreeMap<Vec<u8>, Option<Vec<u8>>> = BTreeMap::new();
+        let mut deleted_ranges = Vec::new();
+        for difflayer in difflayers.diff_layers.iter().rev() {
+            if !difflayer.deleted_ranges.is_empty() {
+                overlay.retain(|key, _| !difflayer.is_range_deleted(key));
+                deleted_ranges.extend(difflayer.deleted_ranges.iter().cloned());
+            }
+            for (key, node) in difflayer.diff_nodes.iter().filter(|(key, _)| key.starts_with(prefix)) {
+                let blob = if node.is_deleted() { None } else { node.blob.as_ref().map(|blob| blob.to_vec()) };
+                overlay.insert(key.clone(), blob);
+            }
+        }
+
+        let mut disk = self.iter_trie_nodes(prefix)?
+            .filter(move |item| !matches!(item, Ok((key, _)) if deleted_ranges.iter().any(|(start, end)| start <= key && key < end)))
+            .peekable();
+        let mut overlay = overlay.into_iter().peekable();
+        Ok(std::iter::from_fn(move || loop {
+            let from_overlay = match (disk.peek(), overlay.peek()) {
+                (None, None) => return None,
+                // Errors are reported as soon as they are reached
+                (Some(Err(_)), _) | (Some(Ok(_)), None) => false,
+                (None, Some(_)) => true,
+                (Some(Ok((disk_key, _))), Some((overlay_key, _))) => overlay_key <= disk_key,
+            };
+            if !from_overlay {
+                return disk.next();
+            }
+
+            let (key, blob) = overlay.next().unwrap();
+            if matches!(disk.peek(), Some(Ok((disk_key, _))) if *disk_key == key) {
+                disk.next();
+            }
+            if let Some(blob) = blob {
+                return Some(Ok((key, blob)));
+            }
+        }))
+    }
+
+    /// Iterate in key order over the entries of column family `cf_name` whose
+    /// keys start with `prefix`, seeking with the prefix bloom filters of the
+    /// configured `PathProviderConfig::prefix_extractor`.
+    ///
+    /// The prefix extractor is set on the trie node column family, where
+    /// entries are yielded like with `iter_trie_nodes`. When `prefix` is at
+    /// least as long as the extracted prefix, SST files and memtables without
+    /// keys of that prefix are skipped, which makes walking a single storage
+    /// trie with `PrefixExtractor::Owner` cheap. Shorter prefixes and other
+    /// column families, whose raw values are yielded, walk in total order.
+    pub fn seek_prefix<'a>(&'a self, cf_name: &'a str, prefix: &[u8]) -> PathProviderResult<impl Iterator<Item = PathProviderResult<(Vec<u8>, Vec<u8>)>> + 'a> {
+        let upper = prefix_upper_bound(prefix);
+        let mut read_options = self.scan_read_options(Some(prefix), upper.as_deref());
+        if cf_name == DEFAULT_COLUMN_FAMILY_NAME && self.config.prefix_extractor.prefix_len().is_some_and(|len| prefix.len() >= len) {
+            read_options.set_total_order_seek(false);
+            read_options.set_prefix_same_as_start(true);
+        }
+        self.iter_cf_range_opt(cf_name, prefix, upper.as_deref(), read_options)
+    }
+
+    /// Iterate in key order over all trie nodes with keys in `[start, end)`,
+    /// `end` of `None` walks to the end of the key space.
+    ///
+    /// Uses the linear walk read options from `scan_read_options`, see
+    /// `iter_trie_nodes` for the yielded items.
+    pub fn iter_range(&self, start: &[u8], end: Option<&[u8]>) -> PathProviderResult<impl Iterator<Item = PathProviderResult<(Vec<u8>, Vec<u8>)>> + '_> {
+        self.iter_range_opt(start, end, self.scan_read_options(Some(start), end))
+    }
+
+    /// Like `iter_range`, with caller-provided read options.
+    ///
+    /// Iterate bounds set on `read_options` must match `[start, end)` encoded
+    /// with the key namespace, which `scan_read_options` takes care of.
+    pub fn iter_range_opt(&self, start: &[u8], end: Option<&[u8]>, read_options: ReadOptions) -> PathProviderResult<impl Iterator<Item = PathProviderResult<(Vec<u8>, Vec<u8>)>> + '_> {
+        self.iter_cf_range_opt(DEFAULT_COLUMN_FAMILY_NAME, start, end, read_options)
+    }
+
+    /// Like `iter_range_opt`, over column family `cf_name`. Only trie node
+    /// values are resolved from overflow chunks and only the trie node column
+    /// family skips the meta keys.
+    fn iter_cf_range_opt<'a>(&'a self, cf_name: &'a str, start: &[u8], end: Option<&[u8]>, read_options: ReadOptions) -> PathProviderResult<impl Iterator<Item = PathProviderResult<(Vec<u8>, Vec<u8>)>> + 'a> {
+        let cf = self.db.cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+        let trie_nodes = cf_name == DEFAULT_COLUMN_FAMILY_NAME;
+
+        let db_start = self.db_key(start).into_owned();
+        let db_end = end.map(|end| self.db_key(end).into_owned());
+        let namespace_len = db_start.len() - start.len();
+
+        let iter = self.db.iterator_cf_opt(&cf, read_options, IteratorMode::From(&db_start, Direction::Forward));
+        Ok(iter
+            .take_while(move |item| match (item, &db_end) {
+                (Ok((db_key, _)), Some(db_end)) => db_key.as_ref() < db_end.as_slice(),
+                _ => true,
+            })
+            // The persisted state and schema keys share the column family but are no trie nodes
+            .filter(move |item| !(trie_nodes && matches!(item, Ok((db_key, _)) if is_meta_key(&db_key[namespace_len..]))))
+            .map(move |item| {
+                let (db_key, value) = item.map_err(|e| {
+                    PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", cf_name), e)
+                })?;
+                let value = match trie_nodes {
+                    true => self.resolve_overflow(&db_key, value.into_vec())?,
+                    false => value.into_vec(),
+                };
+                Ok((db_key[namespace_len..].to_vec(), value))
+            }))
+    }
+
+    /// Get several trie nodes, in the order of `keys`.
+    ///
+    /// The cache is checked key by key and the misses are read with one
+    /// RocksDB multi-get, which batches the block reads.
+    /// Found nodes are inserted into the cache like with [`PathDB::get_raw_trie_node`].
+    pub fn get_multi_raw_trie_nodes<K: AsRef<[u8]>>(&self, keys: &[K]) -> PathProviderResult<Vec<Option<Bytes>>> {
+        let mut values = Vec::with_capacity(keys.len());
+        let mut misses = Vec::new();
+        for (index, key) in keys.iter().enumerate() {
+            self.record_access(key.as_ref());
+            match self.trie_node_cache.lookup(key.as_ref()) {
+                Some(cached_value) => values.push(cached_value),
+                None => {
+                    values.push(None);
+                    misses.push(index);
+                }
+            }
+        }
+        self.metrics.increment_trie_node_cache_hits((keys.len() - misses.len()) as u64);
+        self.metrics.increment_trie_node_cache_misses(misses.len() as u64);
+        misses.retain(|&index| !self.is_known_missing(keys[index].as_ref()));
+        if misses.is_empty() {
+            return Ok(values);
+        }
+
+        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
+        let db_keys: Vec<Vec<u8>> = misses.iter().map(|&index| self.db_key(keys[index].as_ref()).into_owned()).collect();
+        let results = self.db.multi_get_cf_opt(db_keys.iter().map(|db_key| (&cf, db_key)), &self.read_options);
+
+        let mut found = Vec::new();
+        for ((&index, db_key), result) in misses.iter().zip(&db_keys).zip(results) {
+            let value = result.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB multi get in CF '{}' error", DEFAULT_COLUMN_FAMILY_NAME), e)
+            })?;
+            let value = match value {
+                _ if self.is_deletion_queued(db_key)? => None,
+                Some(value) => Some(value),
+                None => self.read_cold_trie_node(db_key, &self.read_options)?,
+            };
+            match value {
+                Some(value) => {
+                    let value = Bytes::from(self.resolve_overflow(db_key, value)?);
+                    found.push((keys[index].as_ref().to_vec(), value.clone()));
+                    values[index] = Some(value);
+                }
+                None => self.remember_missing(keys[index].as_ref()),
+            }
+        }
+
+        trace!(target: "pathdb::rocksdb", "Multi get of {} keys, {} cache misses, {} found in CF '{}'", keys.len(), misses.len(), found.len(), DEFAULT_COLUMN_FAMILY_NAME);
+        for (key, value) in found {
+            self.trie_node_cache.insert(key, Some(value));
+        }
+        Ok(values)
+    }
+
+    /// Warm the trie node cache with the children of the node stored at `key`.
+    ///
+    /// Trie node keys end with the nibble path, so the 16 possible children are
+    /// `key || nibble`. Uncached children are fetched with a single multi-get.
+    /// Returns the number of child nodes loaded into the cache.
+    pub fn prefetch_child_nodes(&self, key: &[u8]) -> PathProviderResult<usize> {
+        let child_keys: Vec<Vec<u8>> = (0u8..16)
+            .map(|nibble| [key, &[nibble]].concat())
+            .filter(|child_key| !self.trie_node_cache.contains(child_key))
+            .collect();
+        if child_keys.is_empty() {
+            return Ok(0);
+        }
+
+        let loaded = self.get_multi_raw_trie_nodes(&child_keys)?.iter().filter(|value| value.is_some()).count();
+        trace!(target: "pathdb::rocksdb", "Prefetched {} child nodes", loaded);
+        Ok(loaded)
+    }
+}
+
+/// Column Family lifecycle.
+impl PathDB {
+    /// Get the names of the Column Families tracked by this database: the
+    /// required ones plus those created through [`PathDB::create_column_family`].
+    pub fn column_families(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.column_family_names.lock().unwrap().iter().cloned().collect();
+        names.sort();
+        names
+    }
+
+    /// Create the Column Family `name` with the shared Column Family options
+    /// and its `PathProviderConfig::cf_overrides`, e.g. for an experiment.
+    pub fn create_column_family(&self, name: &str) -> PathProviderResult<()> {
+        self.check_mutable_column_family(name)?;
+        if self.db.cf_handle(name).is_some() {
+            return Err(PathProviderError::InvalidOperation(format!("Column Family '{}' already exists", name)));
+        }
+
+        let cf_opts = cf_options_for(name, &column_family_options(&self.config, None), &self.config, None, &self.version_gc_block);
+        self.db.create_cf(name, &cf_opts)
+            .map_err(|e| PathProviderError::rocksdb(format!("Failed to create Column Family '{}'", name), e))?;
+        self.column_family_names.lock().unwrap().insert(name.to_string());
+        info!(target: "pathdb::rocksdb", "Created Column Family '{}'", name);
+        Ok(())
+    }
+
+    /// Drop the Column Family `name` with all its data.
+    ///
+    /// Required Column Families can't be dropped. The name is removed from
+    /// the tracked set; the LRU caches only hold required Column Families, so
+    /// no cached entry refers to the dropped one. Dropping affects every key
+    /// namespace sharing the database.
+    pub fn drop_column_family(&self, name: &str) -> PathProviderResult<()> {
+        self.check_mutable_column_family(name)?;
+        if self.db.cf_handle(name).is_none() {
+            return Err(PathProviderError::column_family_missing(name));
+        }
+
+        self.db.drop_cf(name)
+            .map_err(|e| PathProviderError::rocksdb(format!("Failed to drop Column Family '{}'", name), e))?;
+        self.column_family_names.lock().unwrap().remove(name);
+        info!(target: "pathdb::rocksdb", "Dropped Column Family '{}'", name);
+        Ok(())
+    }
+
+    /// Write all entries of the Column Family `name` into an SST file at
+    /// `dest_path`, then drop it, returning the number of entries archived.
+    ///
+    /// The file can be ingested again with RocksDB's external file ingestion.
+    /// No file is written for an empty Column Family. Keys are archived as
+    /// stored, including any key namespace.
+    pub fn archive_column_family(&self, name: &str, dest_path: impl AsRef<Path>) -> PathProviderResult<u64> {
+        let dest_path = dest_path.as_ref();
+        self.check_mutable_column_family(name)?;
+        if dest_path.exists() {
+            return Err(PathProviderError::InvalidOperation(format!("Archive target {} already exists", dest_path.display())));
+        }
+
+        let mut entries = 0u64;
+        {
+            let cf = self.db.cf_handle(name).ok_or_else(|| PathProviderError::column_family_missing(name))?;
+            let mut writer = SstFileWriter::create(self.db_options());
+            let mut read_options = ReadOptions::default();
+            read_options.fill_cache(self.config.scan_fill_cache);
+            read_options.set_readahead_size(self.config.scan_readahead_size);
+            read_options.set_total_order_seek(true);
+            for item in self.db.iterator_cf_opt(&cf, read_options, IteratorMode::Start) {
+                let (key, value) = item.map_err(|e| {
+                    PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", name), e)
+                })?;
+                if entries == 0 {
+                    writer.open(dest_path)
+                        .map_err(|e| PathProviderError::rocksdb(format!("Failed to open archive {}", dest_path.display()), e))?;
+                }
+                writer.put(&key, &value)
+                    .map_err(|e| PathProviderError::rocksdb(format!("Failed to write archive {}", dest_path.display()), e))?;
+                entries += 1;
+            }
+            if entries > 0 {
+                writer.finish()
+                    .map_err(|e| PathProviderError::rocksdb(format!("Failed to finish archive {}", dest_path.display()), e))?;
+            }
+        }
+
+        self.drop_column_family(name)?;
+        info!(target: "pathdb::rocksdb", "Archived {} entries of Column Family '{}' to {}", entries, name, dest_path.display());
+        Ok(entries)
+    }
+
+    /// Check that the Column Family `name` may be created or dropped.
+    fn check_mutable_column_family(&self, name: &str) -> PathProviderResult<()> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot change Column Families of a read-only database".to_string()));
+        }
+        if COLUMN_FAMILY_NAMES.contains(&name) {
+            return Err(PathProviderError::InvalidOperation(format!("Column Family '{}' is required by PathDB", name)));
+        }
+        Ok(())
+    }
+}
+
+/// Runtime reconfiguration.
+impl PathDB {
+    /// Change RocksDB options of the open database, e.g.
+    /// `[("write_buffer_size", "268435456"), ("max_background_jobs", "8")]`.
+    ///
+    /// Keys use the RocksDB option names. Database-wide options such as
+    /// `max_background_jobs` go to `SetDBOptions`, all others to `SetOptions`
+    /// of every Column Family. Only options RocksDB can change at runtime are
+    /// accepted, and all of them are checked before any is set, so an invalid
+    /// one leaves the options unchanged. Changes last until the database is
+    /// closed; the next open applies the `PathProviderConfig` again.
+    pub fn set_options(&self, options: &[(&str, &str)]) -> PathProviderResult<()> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot set options of a read-only database".to_string()));
+        }
+        let (db_options, cf_options): (Vec<(&str, &str)>, Vec<(&str, &str)>) =
+            options.iter().copied().partition(|(key, _)| MUTABLE_DB_OPTIONS.iter().any(|(name, _)| name == key));
+        validate_options(&cf_options, MUTABLE_CF_OPTIONS)?;
+        let cf_names = self.column_families();
+        let cfs = cf_names.iter()
+            .map(|cf_name| self.db.cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name)))
+            .collect::<PathProviderResult<Vec<_>>>()?;
+
+        if !db_options.is_empty() {
+            self.db.set_options(&db_options)
+                .map_err(|e| PathProviderError::InvalidOperation(format!("Failed to set DB options {:?}: {}", db_options, e)))?;
+        }
+        if !cf_options.is_empty() {
+            for (cf_name, cf) in cf_names.iter().zip(cfs) {
+                self.db.set_options_cf(&cf, &cf_options).map_err(|e| {
+                    PathProviderError::InvalidOperation(format!("Failed to set options {:?} of Column Family '{}': {}", cf_options, cf_name, e))
+                })?;
+            }
+        }
+        info!(target: "pathdb::rocksdb", "Set options {:?}", options);
+        Ok(())
+    }
+
+    /// Change RocksDB options of the Column Family `cf_name` only, e.g. to
+    /// give the storage root Column Family smaller write buffers.
+    pub fn set_cf_options(&self, cf_name: &str, options: &[(&str, &str)]) -> PathProviderResult<()> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot set options of a read-only database".to_string()));
+        }
+        validate_options(options, MUTABLE_CF_OPTIONS)?;
+        let cf = self.db.cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+        self.db.set_options_cf(&cf, options)
+            .map_err(|e| PathProviderError::InvalidOperation(format!("Failed to set options {:?} of Column Family '{}': {}", options, cf_name, e)))
+    }
+}
+
+/// Range deletions.
+impl PathDB {
+    /// Delete all trie nodes with keys in `start..end` from the trie node
+    /// column family.
+    ///
+    /// See [`PathDB::delete_range_cf`].
+    pub fn delete_range_raw(&self, start: &[u8], end: &[u8]) -> PathProviderResult<()> {
+        self.delete_range_cf(DEFAULT_COLUMN_FAMILY_NAME, start, end)
+    }
+
+    /// Delete all keys in `start..end` from the column family `cf_name`.
+    ///
+    /// Writes a single RocksDB range tombstone, so the cost doesn't depend on
+    /// the number of keys deleted; compaction drops the covered keys later.
+    /// Cached entries in the range are removed. Overflow chunks of deleted
+    /// trie nodes are range deleted as well, see
+    /// [`PathDB::batch_delete_overflow_range`].
+    pub fn delete_range_cf(&self, cf_name: &str, start: &[u8], end: &[u8]) -> PathProviderResult<()> {
+        if start > end {
+            return Err(PathProviderError::InvalidOperation(format!(
+                "Range start 0x{} is after its end 0x{}", hex_key(start), hex_key(end)
+            )));
+        }
+        let cf = self.db.cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+
+        let _write_guard = self.trie_write_guard();
+        let mut batch = WriteBatch::default();
+        batch.delete_range_cf(&cf, self.db_key(start), self.db_key(end));
+        if cf_name == DEFAULT_COLUMN_FAMILY_NAME {
+            self.batch_delete_cold_range(&mut batch, start, end)?;
+            self.batch_delete_overflow_range(&mut batch, start, end)?;
+        }
+
+        // Held while writing, so concurrent reads can't cache the deleted values again
+        let cache = match cf_name {
+            DEFAULT_COLUMN_FAMILY_NAME => Some(&self.trie_node_cache),
+            STORAGE_ROOT_COLUMN_FAMILY_NAME => Some(&self.storage_root_cache),
+            _ => None,
+        };
+        let mut cache = cache.map(|cache| cache.lock_all());
+        let removed = cache.as_mut().map_or(0, |cache| remove_cached_range(cache, start, end));
+
+        match self.write_raw_batch(batch) {
+            Ok(()) => {
+                trace!(target: "pathdb::rocksdb", "Deleted range 0x{}..0x{} in CF '{}', {} cache entries removed", hex_key(start), hex_key(end), cf_name, removed);
+                Ok(())
+            }
+            Err(e) => {
+                error!(target: "pathdb::rocksdb", "Error deleting range 0x{}..0x{} in CF '{}': {}", hex_key(start), hex_key(end), cf_name, e);
+                Err(PathProviderError::rocksdb(format!("RocksDB delete range in CF '{}' error", cf_name), e))
+            }
+        }
+    }
+}
+
+/// Multi-key deletions.
+impl PathDB {
+    /// Delete several trie nodes in a single RocksDB write.
+    ///
+    /// See [`PathDB::delete_multi_cf`].
+    pub fn delete_multi_raw_trie_nodes<K: AsRef<[u8]>>(&self, keys: &[K]) -> PathProviderResult<()> {
+        self.delete_multi_cf(DEFAULT_COLUMN_FAMILY_NAME, keys)
+    }
+
+    /// Delete `keys` from the column family `cf_name` in a single atomic write.
+    ///
+    /// Trie nodes are deleted together with their overflow chunks, cold
+    /// copies and pending deletions, like with [`PathDB::delete_raw_trie_node`].
+    /// The cache locks are held across the write, so concurrent reads can't
+    /// cache the deleted values again, and cached entries are only removed
+    /// once the write succeeds.
+    pub fn delete_multi_cf<K: AsRef<[u8]>>(&self, cf_name: &str, keys: &[K]) -> PathProviderResult<()> {
+        if keys.is_empty() {
+            return Ok(());
+        }
+        let cf = self.db.cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+
+        let _write_guard = self.trie_write_guard();
+        let cache = match cf_name {
+            DEFAULT_COLUMN_FAMILY_NAME => Some(&self.trie_node_cache),
+            STORAGE_ROOT_COLUMN_FAMILY_NAME => Some(&self.storage_root_cache),
+            _ => None,
+        };
+        let mut cache = cache.map(|cache| cache.lock_all());
+
+        let mut batch = WriteBatch::default();
+        let unique_keys: HashSet<&[u8]> = keys.iter().map(|key| key.as_ref()).collect();
+        for key in &unique_keys {
+            let db_key = self.db_key(key);
+            if cf_name == DEFAULT_COLUMN_FAMILY_NAME {
+                self.batch_delete_trie_node(&mut batch, &cf, &db_key)?;
+            } else {
+                batch.delete_cf(&cf, db_key);
+            }
+        }
+
+        match self.write_raw_batch(batch) {
+            Ok(()) => {
+                let removed = cache.as_mut().map_or(0, |cache| unique_keys.iter().filter(|key| cache.remove(key)).count());
+                trace!(target: "pathdb::rocksdb", "Deleted {} keys in CF '{}', {} cache entries removed", unique_keys.len(), cf_name, removed);
+                Ok(())
+            }
+            Err(e) => {
+                error!(target: "pathdb::rocksdb", "Error deleting {} keys in CF '{}': {}", unique_keys.len(), cf_name, e);
+                Err(PathProviderError::rocksdb(format!("RocksDB multi delete in CF '{}' error", cf_name), e))
+            }
+        }
+    }
+}
+
+/// Access frequency tracking and cold storage.
+impl PathDB {
+    /// Get up to `limit` of the most read trie node paths with their
+    /// estimated read counts, most read first.
+    ///
+    /// Counts are sampled, see `PathProviderConfig::access_sample_rate`, and
+    /// empty if sampling is disabled. Paths missing from the hot set are
+    /// candidates for [`PathDB::move_to_cold_storage`].
+    pub fn hot_set(&self, limit: usize) -> Vec<(Vec<u8>, u64)> {
+        self.access_tracker.as_ref().map_or_else(Vec::new, |access_tracker| access_tracker.hot_set(limit))
+    }
+
+    /// Forget the sampled read counts, e.g. to start a new observation window.
+    pub fn reset_access_stats(&self) {
+        if let Some(access_tracker) = &self.access_tracker {
+            access_tracker.clear();
+        }
+    }
+
+    /// Move the trie nodes whose key starts with `prefix` to the cold trie
+    /// node column family, returning how many were moved.
+    ///
+    /// Nodes in the hot set stay in the primary column family. Values move
+    /// as stored, overflow pointers included, and cached nodes stay valid.
+    /// The moves are written in batches of `COLD_STORAGE_BATCH_SIZE` nodes,
+    /// each holding the deletion lock only while it is read and written, so
+    /// commits can go ahead between batches.
+    /// Requires `PathProviderConfig::cold_storage`, otherwise moved nodes
+    /// wouldn't be read back. Linear walks such as
+    /// [`PathDB::iter_trie_nodes`] only cover the primary column family.
+    pub fn move_to_cold_storage(&self, prefix: &[u8]) -> PathProviderResult<usize> {
+        self.move_to_cold_storage_with_cancellation(prefix, &CancellationToken::new())
+    }
+
+    /// Move trie nodes to cold storage like [`PathDB::move_to_cold_storage`],
+    /// stopping the walk once `cancel` is cancelled.
+    ///
+    /// The nodes visited until then are still moved, the returned count
+    /// tells how many.
+    pub fn move_to_cold_storage_with_cancellation(&self, prefix: &[u8], cancel: &CancellationToken) -> PathProviderResult<usize> {
+        if !self.config.cold_storage {
+            return Err(PathProviderError::InvalidOperation("Cold storage is disabled".to_string()));
+        }
+        let upper = prefix_upper_bound(prefix);
+        let mut start = self.db_key(prefix).into_owned();
+        let mut moved = 0;
+        loop {
+            let (batch_moved, next) = self.move_cold_storage_batch(prefix, upper.as_deref(), &start, cancel)?;
+            moved += batch_moved;
+            match next {
+                Some(next) => start = next,
+                None => break,
+            }
+        }
+        if cancel.is_cancelled() {
+            info!(target: "pathdb::rocksdb", "Moving trie nodes under 0x{} to cold storage cancelled after {} nodes", hex_key(prefix), moved);
+        }
+        trace!(target: "pathdb::rocksdb", "Moved {} trie nodes under 0x{} to cold storage", moved, hex_key(prefix));
+        Ok(moved)
+    }
+
+    /// Move up to `COLD_STORAGE_BATCH_SIZE` trie nodes under `prefix` from
+    /// the database key `start` on, returning how many were moved and the
+    /// key to continue from, `None` once the walk is done or cancelled.
+    fn move_cold_storage_batch(&self, prefix: &[u8], upper: Option<&[u8]>, start: &[u8], cancel: &CancellationToken) -> PathProviderResult<(usize, Option<Vec<u8>>)> {
+        let default_cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
+        let cold_cf = self.cold_cf()?;
+
+        self.wait_for_maintenance_budget();
+        // Held while reading and writing, so no node is written in between
+        let _write_guard = self.trie_write_guard();
+        let read_options = self.scan_read_options(Some(prefix), upper);
+        let mut batch = WriteBatch::default();
+        let mut moved = 0;
+        let mut next = None;
+        for item in self.db.iterator_cf_opt(&default_cf, read_options, IteratorMode::From(start, Direction::Forward)) {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let (db_key, value) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", DEFAULT_COLUMN_FAMILY_NAME), e)
+            })?;
+            if moved == COLD_STORAGE_BATCH_SIZE {
+                next = Some(db_key.into_vec());
+                break;
+            }
+            let Some(key) = self.logical_key(&db_key) else { continue };
+            if is_meta_key(key) {
+                continue;
+            }
+            if self.access_tracker.as_ref().is_some_and(|access_tracker| access_tracker.is_tracked(key)) {
+                continue;
+            }
+            batch.put_cf(&cold_cf, &db_key, &value);
+            batch.delete_cf(&default_cf, &db_key);
+            moved += 1;
+        }
+
+        self.write_maintenance_raw_batch(batch).map_err(|e| {
+            error!(target: "pathdb::batch", "Error moving {} trie nodes to cold storage: {}", moved, e);
+            PathProviderError::rocksdb("Cold storage batch error", e)
+        })?;
+        Ok((moved, next))
+    }
+
+    fn record_access(&self, key: &[u8]) {
+        if let Some(access_tracker) = &self.access_tracker {
+            access_tracker.record(key);
+        }
+    }
+
+    /// Read the raw value stored under `db_key` in the cold trie node column
+    /// family, `None` if cold storage is disabled.
+    fn read_cold_trie_node(&self, db_key: &[u8], read_options: &ReadOptions) -> PathProviderResult<Option<Vec<u8>>> {
+        if !self.config.cold_storage {
+            return Ok(None);
+        }
+        self.db.get_cf_opt(&self.cold_cf()?, db_key, read_options)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB get in CF '{}' error", COLD_TRIE_NODE_COLUMN_FAMILY_NAME), e))
+    }
+
+    /// Add the delete of a cold copy of `db_key` to `batch`, including its
+    /// overflow chunks, so it can't resurface once the node is deleted.
+    fn batch_delete_cold_trie_node(&self, batch: &mut WriteBatch, db_key: &[u8]) -> PathProviderResult<()> {
+        if !self.config.cold_storage {
+            return Ok(());
+        }
+        let cold_cf = self.cold_cf()?;
+        self.batch_delete_overflow_chunks(batch, &cold_cf, db_key)?;
+        batch.delete_cf(&cold_cf, db_key);
+        Ok(())
+    }
+
+    /// Add the delete of the cold trie nodes in the logical key range `start..end` to `batch`.
+    fn batch_delete_cold_range(&self, batch: &mut WriteBatch, start: &[u8], end: &[u8]) -> PathProviderResult<()> {
+        if self.config.cold_storage {
+            batch.delete_range_cf(&self.cold_cf()?, self.db_key(start), self.db_key(end));
+        }
+        Ok(())
+    }
+
+    fn cold_cf(&self) -> PathProviderResult<Arc<BoundColumnFamily<'_>>> {
+        self.db.cf_handle(COLD_TRIE_NODE_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(COLD_TRIE_NODE_COLUMN_FAMILY_NAME))
+    }
+}
+
+/// Access tracker configured by `config`, `None` if sampling is disabled.
+fn new_access_tracker(config: &PathProviderConfig) -> Option<Arc<AccessTracker>> {
+    (config.access_sample_rate > 0).then(|| Arc::new(AccessTracker::new(config.access_sample_rate, config.access_tracker_size)))
+}
+
+/// Negative caching of missing trie nodes.
+impl PathDB {
+    /// Whether `key` is known to be missing from the trie node column family.
+    fn is_known_missing(&self, key: &[u8]) -> bool {
+        let missing = self.negative_cache.as_ref().is_some_and(|negative_cache| negative_cache.contains(key));
+        if missing {
+            self.metrics.increment_negative_cache_hits(1);
+        }
+        missing
+    }
+
+    /// Remember that `key` is missing from the trie node column family.
+    fn remember_missing(&self, key: &[u8]) {
+        if let Some(negative_cache) = &self.negative_cache {
+            negative_cache.insert(key.to_vec());
+        }
+    }
+
+    /// Forget that `key` was missing, called whenever it is written.
+    fn forget_missing(&self, key: &[u8]) {
+        if let Some(negative_cache) = &self.negative_cache {
+            negative_cache.remove(key);
+        }
+    }
+
+    fn forget_all_missing(&self) {
+        if let Some(negative_cache) = &self.negative_cache {
+            negative_cache.clear();
+        }
+    }
+}
+
+/// Negative cache configured by `config`, `None` if disabled.
+fn new_negative_cache(config: &PathProviderConfig) -> Option<Arc<NegativeCache>> {
+    config.negative_cache_size.map(|size| Arc::new(NegativeCache::new(size, config.negative_cache_ttl)))
+}
+
+/// Remove the cached entries with keys in `start..end`, returning how many were removed.
+fn remove_cached_range(cache: &mut ShardedCacheGuard<'_>, start: &[u8], end: &[u8]) -> usize {
+    cache.remove_ranges(&[(start.to_vec(), end.to_vec())])
+}
+
+pub(crate) fn hex_key(key: &[u8]) -> String {
+    key.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Commit audit log.
+impl PathDB {
+    /// Get all audit records of `block_number`, one per committed state root.
+    pub fn get_audit_records(&self, block_number: u64) -> PathProviderResult<Vec<AuditRecord>> {
+        let cf = self.db.cf_handle(AUDIT_LOG_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(AUDIT_LOG_COLUMN_FAMILY_NAME))?;
+
+        let prefix = block_number.to_be_bytes();
+        let upper = prefix_upper_bound(&prefix);
+        let read_options = self.scan_read_options(Some(&prefix), upper.as_deref());
+        let db_prefix = self.db_key(&prefix).into_owned();
+
+        let mut records = Vec::new();
+        for item in self.db.iterator_cf_opt(&cf, read_options, IteratorMode::From(&db_prefix, Direction::Forward)) {
+            let (_, value) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", AUDIT_LOG_COLUMN_FAMILY_NAME), e)
+            })?;
+            let record = AuditRecord::decode(&value).ok_or_else(|| {
+                PathProviderError::Deserialization(format!("Invalid audit record of {} bytes", value.len()))
+            })?;
+            records.push(record);
+        }
+        Ok(records)
+    }
+
+    /// Add the audit log entry of `record` to `batch`.
+    fn batch_put_audit_record(&self, batch: &mut WriteBatch, record: &AuditRecord) -> PathProviderResult<()> {
+        let cf = self.db.cf_handle(AUDIT_LOG_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(AUDIT_LOG_COLUMN_FAMILY_NAME))?;
+
+        let key = [record.block_number.to_be_bytes().as_slice(), record.root_after.as_slice()].concat();
+        batch.put_cf(&cf, self.db_key(&key), record.encode());
+        Ok(())
+    }
+}
+
+/// Code hash index.
+impl PathDB {
+    /// Get the hashed addresses of all indexed accounts with `code_hash`, in
+    /// hashed address order.
+    pub fn get_addresses_by_code_hash(&self, code_hash: B256) -> PathProviderResult<Vec<B256>> {
+        let cf = self.code_hash_index_cf()?;
+        let prefix = code_hash_index_code_key(code_hash, None);
+        let upper = prefix_upper_bound(&prefix);
+        let read_options = self.scan_read_options(Some(&prefix), upper.as_deref());
+        let db_prefix = self.db_key(&prefix).into_owned();
+
+        let mut addresses = Vec::new();
+        for item in self.db.iterator_cf_opt(&cf, read_options, IteratorMode::From(&db_prefix, Direction::Forward)) {
+            let (db_key, _) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", CODE_HASH_INDEX_COLUMN_FAMILY_NAME), e)
+            })?;
+            if db_key.len() != db_prefix.len() + 32 {
+                return Err(PathProviderError::Deserialization(format!("Invalid code hash index key of {} bytes", db_key.len())));
+            }
+            addresses.push(B256::from_slice(&db_key[db_prefix.len()..]));
+        }
+        Ok(addresses)
+    }
+
+    /// Add the index updates for the `code_hashes` of a diff layer to `batch`.
+    ///
+    /// Reads the currently indexed code hash of every account, so an account
+    /// moves from its old list to the new one.
+    fn batch_update_code_hash_index(&self, batch: &mut WriteBatch, code_hashes: &HashMap<B256, Option<B256>>) -> PathProviderResult<()> {
+        let cf = self.code_hash_index_cf()?;
+        let accounts: Vec<(&B256, &Option<B256>)> = code_hashes.iter().collect();
+        let account_keys: Vec<Vec<u8>> = accounts
+            .iter()
+            .map(|(hashed_address, _)| self.db_key(&code_hash_index_account_key(**hashed_address)).into_owned())
+            .collect();
+        let indexed = self.db.multi_get_cf_opt(account_keys.iter().map(|key| (&cf, key)), &self.read_options);
+
+        for (((hashed_address, code_hash), account_key), indexed) in accounts.into_iter().zip(&account_keys).zip(indexed) {
+            let indexed = indexed.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB multi get in CF '{}' error", CODE_HASH_INDEX_COLUMN_FAMILY_NAME), e)
+            })?;
+            let indexed = indexed.filter(|value| value.len() == 32).map(|value| B256::from_slice(&value));
+            if indexed == *code_hash {
+                continue;
+            }
+            if let Some(indexed) = indexed {
+                batch.delete_cf(&cf, self.db_key(&code_hash_index_code_key(indexed, Some(*hashed_address))));
+            }
+            match code_hash {
+                Some(code_hash) => {
+                    batch.put_cf(&cf, self.db_key(&code_hash_index_code_key(*code_hash, Some(*hashed_address))), []);
+                    batch.put_cf(&cf, account_key, code_hash.as_slice());
+                }
+                None => batch.delete_cf(&cf, account_key),
+            }
+        }
+        Ok(())
+    }
+
+    fn code_hash_index_cf(&self) -> PathProviderResult<Arc<BoundColumnFamily<'_>>> {
+        self.db.cf_handle(CODE_HASH_INDEX_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(CODE_HASH_INDEX_COLUMN_FAMILY_NAME))
+    }
+}
+
+/// Storage slot counts.
+impl PathDB {
+    /// Get the storage slot count of `hashed_address`, `None` if no slots are
+    /// counted for the account.
+    pub fn get_storage_slot_count(&self, hashed_address: B256) -> PathProviderResult<Option<u64>> {
+        let cf = self.slot_count_cf()?;
+        let value = self.db.get_cf_opt(&cf, self.db_key(hashed_address.as_slice()), &self.read_options).map_err(|e| {
+            PathProviderError::rocksdb(format!("RocksDB get in CF '{}' error", SLOT_COUNT_COLUMN_FAMILY_NAME), e)
+        })?;
+        value.map(|value| decode_slot_count(&value)).transpose()
+    }
+
+    /// Add the count updates for the `slot_count_changes` of a diff layer to `batch`.
+    ///
+    /// Deltas are applied to the stored counts and saturate at zero, so a
+    /// count started after the account already had slots never underflows.
+    fn batch_update_slot_counts(&self, batch: &mut WriteBatch, changes: &HashMap<B256, SlotCountChange>) -> PathProviderResult<()> {
+        let cf = self.slot_count_cf()?;
+        let changes: Vec<(&B256, &SlotCountChange)> = changes.iter().collect();
+        let keys: Vec<Vec<u8>> = changes.iter().map(|(hashed_address, _)| self.db_key(hashed_address.as_slice()).into_owned()).collect();
+        let stored = self.db.multi_get_cf_opt(keys.iter().map(|key| (&cf, key)), &self.read_options);
+
+        for (((_, change), key), stored) in changes.into_iter().zip(keys).zip(stored) {
+            let count = match change {
+                SlotCountChange::Reset(count) => *count,
+                SlotCountChange::Delta(delta) => {
+                    let stored = stored.map_err(|e| {
+                        PathProviderError::rocksdb(format!("RocksDB multi get in CF '{}' error", SLOT_COUNT_COLUMN_FAMILY_NAME), e)
+                    })?;
+                    let stored = stored.map(|value| decode_slot_count(&value)).transpose()?.unwrap_or_default();
+                    stored.saturating_add_signed(*delta)
+                }
+            };
+            if count == 0 {
+                batch.delete_cf(&cf, key);
+            } else {
+                batch.put_cf(&cf, key, count.to_be_bytes());
+            }
+        }
+        Ok(())
+    }
+
+    fn slot_count_cf(&self) -> PathProviderResult<Arc<BoundColumnFamily<'_>>> {
+        self.db.cf_handle(SLOT_COUNT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(SLOT_COUNT_COLUMN_FAMILY_NAME))
+    }
+}
+
+/// Flat state snapshot.
+impl PathDB {
+    /// Get the encoded account of `hashed_address` from the flat account
+    /// snapshot, `None` if the snapshot has no entry for it.
+    pub fn get_raw_account_snapshot(&self, hashed_address: B256) -> PathProviderResult<Option<Bytes>> {
+        self.get_snapshot_entry(ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, hashed_address.as_slice())
+    }
+
+    /// Store the encoded account of `hashed_address` in the flat account snapshot.
+    pub fn put_raw_account_snapshot(&self, hashed_address: B256, account: &[u8]) -> PathProviderResult<()> {
+        self.write_snapshot_entry(ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, hashed_address.as_slice(), Some(account))
+    }
+
+    /// Remove `hashed_address` from the flat account snapshot, e.g. once the
+    /// account is destructed.
+    pub fn delete_raw_account_snapshot(&self, hashed_address: B256) -> PathProviderResult<()> {
+        self.write_snapshot_entry(ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, hashed_address.as_slice(), None)
+    }
+
+    /// Get the value of the storage slot `hashed_key` of `hashed_address`
+    /// from the flat storage snapshot, `None` if the snapshot has no entry for it.
+    pub fn get_raw_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> PathProviderResult<Option<Bytes>> {
+        self.get_snapshot_entry(STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME, &storage_snapshot_key(hashed_address, hashed_key))
+    }
+
+    /// Store the value of the storage slot `hashed_key` of `hashed_address`
+    /// in the flat storage snapshot.
+    pub fn put_raw_storage_snapshot(&self, hashed_address: B256, hashed_key: B256, value: &[u8]) -> PathProviderResult<()> {
+        self.write_snapshot_entry(STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME, &storage_snapshot_key(hashed_address, hashed_key), Some(value))
+    }
+
+    /// Remove the storage slot `hashed_key` of `hashed_address` from the flat
+    /// storage snapshot.
+    pub fn delete_raw_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> PathProviderResult<()> {
+        self.write_snapshot_entry(STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME, &storage_snapshot_key(hashed_address, hashed_key), None)
+    }
+
+    /// Get up to `limit` entries of the flat account snapshot in hashed
+    /// address order, starting at `start_hash`.
+    ///
+    /// Serves snap sync account range requests and rebuilding the account
+    /// trie from flat state; continue after the last returned address to get
+    /// the next range.
+    pub fn iter_account_snapshot(&self, start_hash: B256, limit: usize) -> PathProviderResult<Vec<(B256, Bytes)>> {
+        self.snapshot_range(ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, &[], start_hash, limit)
+    }
+
+    /// Get up to `limit` storage slots of `hashed_address` from the flat
+    /// storage snapshot in hashed key order, starting at `start_hash`.
+    pub fn iter_storage_snapshot(&self, hashed_address: B256, start_hash: B256, limit: usize) -> PathProviderResult<Vec<(B256, Bytes)>> {
+        self.snapshot_range(STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME, hashed_address.as_slice(), start_hash, limit)
+    }
+
+    /// Remove the destructed account `hashed_address` from the flat state:
+    /// its storage root, its account snapshot entry and all of its flat
+    /// storage slots, in one batch.
+    pub fn wipe_account(&self, hashed_address: B256) -> PathProviderResult<()> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot wipe an account of a read-only database".to_string()));
+        }
+        let storage_root_cf = self.db.cf_handle(STORAGE_ROOT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(STORAGE_ROOT_COLUMN_FAMILY_NAME))?;
+        let account_cf = self.db.cf_handle(ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME))?;
+        let storage_cf = self.db.cf_handle(STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME))?;
+
+        let mut batch = WriteBatch::default();
+        batch.delete_cf(&storage_root_cf, self.db_key(hashed_address.as_slice()));
+        batch.delete_cf(&account_cf, self.db_key(hashed_address.as_slice()));
+        // Slot keys are fixed length, so the last slot key plus a byte bounds them all
+        let start = storage_snapshot_key(hashed_address, B256::ZERO);
+        let end = [storage_snapshot_key(hashed_address, B256::repeat_byte(0xff)).as_slice(), &[0]].concat();
+        batch.delete_range_cf(&storage_cf, self.db_key(&start), self.db_key(&end));
+
+        self.write_raw_batch(batch).map_err(|e| {
+            PathProviderError::rocksdb(format!("RocksDB wipe of account {:#x} error", hashed_address), e)
+        })?;
+        // Dropped once the wipe landed, so a concurrent read can't cache the old root again
+        self.storage_root_cache.remove(hashed_address.as_slice());
+        self.metrics.increment_storage_root_wiped(1);
+        Ok(())
+    }
+
+    fn get_snapshot_entry(&self, cf_name: &str, key: &[u8]) -> PathProviderResult<Option<Bytes>> {
+        let cf = self.db.cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+        let value = self.db.get_cf_opt(&cf, self.db_key(key), &self.read_options).map_err(|e| {
+            PathProviderError::rocksdb(format!("RocksDB get in CF '{}' error", cf_name), e)
+        })?;
+        Ok(value.map(Bytes::from))
+    }
+
+    /// Put `value` at `key` of the snapshot column family `cf_name`, or delete the key without a value.
+    fn write_snapshot_entry(&self, cf_name: &str, key: &[u8], value: Option<&[u8]>) -> PathProviderResult<()> {
+        let cf = self.db.cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+        let mut batch = WriteBatch::default();
+        match value {
+            Some(value) => batch.put_cf(&cf, self.db_key(key), value),
+            None => batch.delete_cf(&cf, self.db_key(key)),
+        }
+        self.write_raw_batch(batch).map_err(|e| {
+            PathProviderError::rocksdb(format!("RocksDB write in CF '{}' error", cf_name), e)
+        })
+    }
+
+    /// Walk the snapshot column family `cf_name` over the keys `prefix || hash`
+    /// from `prefix || start_hash`, returning up to `limit` hashes and values.
+    fn snapshot_range(&self, cf_name: &str, prefix: &[u8], start_hash: B256, limit: usize) -> PathProviderResult<Vec<(B256, Bytes)>> {
+        let cf = self.db.cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+        let lower = [prefix, start_hash.as_slice()].concat();
+        let upper = prefix_upper_bound(prefix);
+        let read_options = self.scan_read_options(Some(&lower), upper.as_deref());
+        let db_lower = self.db_key(&lower).into_owned();
+        let hash_offset = db_lower.len() - B256::len_bytes();
+
+        let mut entries = Vec::new();
+        for item in self.db.iterator_cf_opt(&cf, read_options, IteratorMode::From(&db_lower, Direction::Forward)).take(limit) {
+            let (db_key, value) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", cf_name), e)
+            })?;
+            if db_key.len() != db_lower.len() {
+                return Err(PathProviderError::Deserialization(format!("Invalid snapshot key of {} bytes in CF '{}'", db_key.len(), cf_name)));
+            }
+            entries.push((B256::from_slice(&db_key[hash_offset..]), Bytes::from(value.into_vec())));
+        }
+        Ok(entries)
+    }
+}
+
+/// Key of the storage slot `hashed_key` of `hashed_address` in the flat storage snapshot.
+pub(crate) fn storage_snapshot_key(hashed_address: B256, hashed_key: B256) -> [u8; 64] {
+    let mut key = [0u8; 64];
+    key[..32].copy_from_slice(hashed_address.as_slice());
+    key[32..].copy_from_slice(hashed_key.as_slice());
+    key
+}
+
+/// Decode a stored storage slot count.
+fn decode_slot_count(value: &[u8]) -> PathProviderResult<u64> {
+    let bytes: [u8; 8] = value.try_into().map_err(|_| {
+        PathProviderError::Deserialization(format!("Invalid slot count of {} bytes", value.len()))
+    })?;
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// Key of the entry of `hashed_address` in the account list of `code_hash`,
+/// or the prefix of the whole list without an address.
+fn code_hash_index_code_key(code_hash: B256, hashed_address: Option<B256>) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + 64);
+    key.push(CODE_HASH_INDEX_CODE_TAG);
+    key.extend_from_slice(code_hash.as_slice());
+    if let Some(hashed_address) = hashed_address {
+        key.extend_from_slice(hashed_address.as_slice());
+    }
+    key
+}
+
+/// Key of the indexed code hash of `hashed_address`.
+fn code_hash_index_account_key(hashed_address: B256) -> Vec<u8> {
+    let mut key = Vec::with_capacity(1 + 32);
+    key.push(CODE_HASH_INDEX_ACCOUNT_TAG);
+    key.extend_from_slice(hashed_address.as_slice());
+    key
+}
+
+/// A trie node missing from the database that a state heal has to fetch.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HealRequest {
+    /// Trie node key of the missing node
+    pub key: Vec<u8>,
+    /// Expected hash of the node blob
+    pub hash: B256,
+}
+
+/// Progress of a state heal.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct HealProgress {
+    /// Nodes healed since the last heal reset
+    pub healed: u64,
+    /// Outstanding heal requests
+    pub pending: u64,
+}
+
+/// Persisted state heal progress.
+impl PathDB {
+    /// Record missing trie nodes to heal, requests for the same key are replaced.
+    pub fn add_heal_requests(&self, requests: &[HealRequest]) -> PathProviderResult<()> {
+        let meta_cf = self.db.cf_handle(META_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(META_COLUMN_FAMILY_NAME))?;
+        let heal_queue_cf = self.heal_queue_cf()?;
+        let _heal_guard = self.heal_lock.lock().unwrap();
+
+        // Only requests for keys not queued yet add to the pending count
+        let mut pending = self.heal_pending_count()?;
+        let mut added = HashSet::new();
+        let mut batch = WriteBatch::default();
+        for request in requests {
+            let db_key = self.db_key(&request.key);
+            let queued = self.db.get_pinned_cf_opt(&heal_queue_cf, &db_key, &self.read_options)
+                .map_err(|e| PathProviderError::rocksdb(format!("RocksDB get in CF '{}' error", HEAL_QUEUE_COLUMN_FAMILY_NAME), e))?;
+            if queued.is_none() && added.insert(request.key.as_slice()) {
+                pending += 1;
+            }
+            batch.put_cf(&heal_queue_cf, &db_key, request.hash.as_slice());
+        }
+        batch.put_cf(&meta_cf, self.db_key(HEAL_PENDING_COUNT_KEY), pending.to_be_bytes());
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB write in CF '{}' error", HEAL_QUEUE_COLUMN_FAMILY_NAME), e))?;
+
+        self.update_heal_metrics()?;
+        Ok(())
+    }
+
+    /// Get up to `limit` outstanding heal requests in key order, e.g. to resume
+    /// an interrupted heal.
+    pub fn pending_heal_requests(&self, limit: usize) -> PathProviderResult<Vec<HealRequest>> {
+        let heal_queue_cf = self.heal_queue_cf()?;
+        let read_options = self.scan_read_options(None, None);
+        let lower = self.db_key(&[]).into_owned();
+        let namespace_len = lower.len();
+
+        let mut requests = Vec::new();
+        for item in self.db.iterator_cf_opt(&heal_queue_cf, read_options, IteratorMode::From(&lower, Direction::Forward)).take(limit) {
+            let (db_key, value) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", HEAL_QUEUE_COLUMN_FAMILY_NAME), e)
+            })?;
+            if value.len() != 32 {
+                return Err(PathProviderError::Deserialization(format!("Invalid heal request hash of {} bytes", value.len())));
+            }
+            requests.push(HealRequest { key: db_key[namespace_len..].to_vec(), hash: B256::from_slice(&value) });
+        }
+        Ok(requests)
+    }
+
+    /// Store a fetched node blob and mark its heal request as done.
+    ///
+    /// The blob must hash to the requested hash. The node write, the request
+    /// removal and the healed count update are committed in one batch, so
+    /// progress survives a crash at any point.
+    pub fn complete_heal_request(&self, key: &[u8], blob: &[u8]) -> PathProviderResult<()> {
+        let default_cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
+        let meta_cf = self.db.cf_handle(META_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(META_COLUMN_FAMILY_NAME))?;
+        let heal_queue_cf = self.heal_queue_cf()?;
+
+        let _heal_guard = self.heal_lock.lock().unwrap();
+        let db_key = self.db_key(key);
+        let expected = self.db.get_cf_opt(&heal_queue_cf, &db_key, &self.read_options)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB get in CF '{}' error", HEAL_QUEUE_COLUMN_FAMILY_NAME), e))?
+            .ok_or_else(|| PathProviderError::KeyNotFound(key.to_vec()))?;
+        let hash = keccak256(blob);
+        if expected.as_slice() != hash.as_slice() {
+            return Err(PathProviderError::InvalidOperation(format!(
+                "Heal blob hash mismatch: expected 0x{}, got {:#x}",
+                expected.iter().map(|b| format!("{:02x}", b)).collect::<String>(), hash
+            )));
+        }
+        let healed = self.heal_healed_count()? + 1;
+        let pending = self.heal_pending_count()?.saturating_sub(1);
+
+        let _write_guard = self.trie_write_guard();
+        let mut batch = WriteBatch::default();
+        self.batch_put_trie_node(&mut batch, &default_cf, &db_key, blob)?;
+        batch.delete_cf(&heal_queue_cf, &db_key);
+        batch.put_cf(&meta_cf, self.db_key(HEAL_HEALED_COUNT_KEY), healed.to_be_bytes());
+        batch.put_cf(&meta_cf, self.db_key(HEAL_PENDING_COUNT_KEY), pending.to_be_bytes());
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb("Heal batch commit error", e))?;
+        self.trie_node_cache.insert(key.to_vec(), Some(Bytes::copy_from_slice(blob)));
+        self.forget_missing(key);
+
+        self.metrics.set_heal_healed_nodes(healed as f64);
+        self.metrics.decrement_heal_pending_nodes(1.0);
+        Ok(())
+    }
+
+    /// Get the current heal progress.
+    pub fn heal_progress(&self) -> PathProviderResult<HealProgress> {
+        Ok(HealProgress { healed: self.heal_healed_count()?, pending: self.heal_pending_count()? })
+    }
+
+    /// Drop all outstanding heal requests and reset the healed count, e.g. when
+    /// healing towards a new target state.
+    pub fn reset_heal_progress(&self) -> PathProviderResult<()> {
+        let meta_cf = self.db.cf_handle(META_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(META_COLUMN_FAMILY_NAME))?;
+        let heal_queue_cf = self.heal_queue_cf()?;
+
+        let _heal_guard = self.heal_lock.lock().unwrap();
+        let mut batch = WriteBatch::default();
+        // The range ends right after the last request, which bounds it without a namespace
+        let lower = self.db_key(&[]).into_owned();
+        let read_options = self.scan_read_options(None, None);
+        if let Some(item) = self.db.iterator_cf_opt(&heal_queue_cf, read_options, IteratorMode::End).next() {
+            let (last, _) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", HEAL_QUEUE_COLUMN_FAMILY_NAME), e)
+            })?;
+            let mut upper = last.into_vec();
+            upper.push(0);
+            batch.delete_range_cf(&heal_queue_cf, lower, upper);
+        }
+        batch.delete_cf(&meta_cf, self.db_key(HEAL_HEALED_COUNT_KEY));
+        batch.put_cf(&meta_cf, self.db_key(HEAL_PENDING_COUNT_KEY), 0u64.to_be_bytes());
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb("Heal reset batch error", e))?;
+
+        self.update_heal_metrics()?;
+        Ok(())
+    }
+
+    fn heal_healed_count(&self) -> PathProviderResult<u64> {
+        Ok(self.heal_count(HEAL_HEALED_COUNT_KEY)?.unwrap_or_default())
+    }
+
+    /// Number of outstanding heal requests, counted once for queues written
+    /// before the count was kept.
+    fn heal_pending_count(&self) -> PathProviderResult<u64> {
+        if let Some(pending) = self.heal_count(HEAL_PENDING_COUNT_KEY)? {
+            return Ok(pending);
+        }
+        let heal_queue_cf = self.heal_queue_cf()?;
+        let read_options = self.scan_read_options(None, None);
+        let lower = self.db_key(&[]).into_owned();
+        let mut pending = 0;
+        for item in self.db.iterator_cf_opt(&heal_queue_cf, read_options, IteratorMode::From(&lower, Direction::Forward)) {
+            item.map_err(|e| PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", HEAL_QUEUE_COLUMN_FAMILY_NAME), e))?;
+            pending += 1;
+        }
+        Ok(pending)
+    }
+
+    fn heal_count(&self, key: &[u8]) -> PathProviderResult<Option<u64>> {
+        let meta_cf = self.db.cf_handle(META_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(META_COLUMN_FAMILY_NAME))?;
+        let value = self.db.get_cf_opt(&meta_cf, self.db_key(key), &self.read_options)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB get in CF '{}' error", META_COLUMN_FAMILY_NAME), e))?;
+        value.map(|value| {
+            value.as_slice().try_into().map(u64::from_be_bytes).map_err(|_| {
+                PathProviderError::Deserialization(format!("Invalid heal count of {} bytes", value.len()))
+            })
+        }).transpose()
+    }
+
+    fn update_heal_metrics(&self) -> PathProviderResult<()> {
+        let progress = self.heal_progress()?;
+        self.metrics.set_heal_healed_nodes(progress.healed as f64);
+        self.metrics.set_heal_pending_nodes(progress.pending as f64);
+        Ok(())
+    }
+
+    fn heal_queue_cf(&self) -> PathProviderResult<Arc<BoundColumnFamily<'_>>> {
+        self.db.cf_handle(HEAL_QUEUE_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(HEAL_QUEUE_COLUMN_FAMILY_NAME))
+    }
+}
+
+/// A set of trie node and storage root writes applied atomically by
+/// [`PathDB::write_batch`], optionally with the persisted state they belong to.
+///
+/// Keys are logical keys, the same ones passed to `put_raw_trie_node` and
+/// `get_raw_storage_root`; namespacing and overflow chunking are applied on write.
+#[derive(Debug, Clone, Default)]
+pub struct PathDBWriteBatch {
+    trie_nodes: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    storage_roots: Vec<(Vec<u8>, Option<Vec<u8>>)>,
+    persist_state: Option<(u64, B256)>,
+}
+
+impl PathDBWriteBatch {
+    /// Create an empty batch.
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Queue a trie node write.
+    pub fn put_trie_node(&mut self, key: &[u8], value: &[u8]) -> &mut Self {
+        self.trie_nodes.push((key.to_vec(), Some(value.to_vec())));
+        self
+    }
+
+    /// Queue a trie node delete.
+    pub fn delete_trie_node(&mut self, key: &[u8]) -> &mut Self {
+        self.trie_nodes.push((key.to_vec(), None));
+        self
+    }
+
+    /// Queue a storage root write.
+    pub fn put_storage_root(&mut self, hashed_address: B256, storage_root: B256) -> &mut Self {
+        self.storage_roots.push((hashed_address.to_vec(), Some(storage_root.to_vec())));
+        self
+    }
+
+    /// Queue a storage root write from raw bytes, e.g. copied from another
+    /// database. Key and value must be 32 bytes long, otherwise
+    /// [`PathDB::write_batch`] rejects the whole batch.
+    pub fn put_raw_storage_root(&mut self, key: &[u8], value: &[u8]) -> &mut Self {
+        self.storage_roots.push((key.to_vec(), Some(value.to_vec())));
+        self
+    }
+
+    /// Queue a storage root delete.
+    pub fn delete_storage_root(&mut self, hashed_address: B256) -> &mut Self {
+        self.storage_roots.push((hashed_address.to_vec(), None));
+        self
+    }
+
+    /// Record `block_number` and `state_root` as the persisted state in the
+    /// same write, so the state reported by `latest_persist_state` can't
+    /// diverge from the data of the batch.
+    pub fn set_persist_state(&mut self, block_number: u64, state_root: B256) -> &mut Self {
+        self.persist_state = Some((block_number, state_root));
+        self
+    }
+
+    /// Number of queued operations, the persisted state counting as one.
+    pub fn len(&self) -> usize {
+        self.trie_nodes.len() + self.storage_roots.len() + usize::from(self.persist_state.is_some())
+    }
+
+    /// Whether no operations are queued.
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+}
+
+/// Cache-coherent batch writes
+impl PathDB {
+    /// Apply `batch` in a single RocksDB write and update the caches to match.
+    ///
+    /// Operations are applied in insertion order, so a later write to the same
+    /// key wins. The cache locks are held across the write and the caches are
+    /// only updated once it succeeds, so a failed write leaves them untouched.
+    pub fn write_batch(&self, batch: PathDBWriteBatch) -> PathProviderResult<()> {
+        self.write_batch_with(batch, false)
+    }
+
+    /// Apply `batch` like [`PathDB::write_batch`] as a maintenance write,
+    /// e.g. for pruning or garbage collection.
+    ///
+    /// The write waits for the maintenance rate limit before taking the cache
+    /// locks and is issued at low priority if `maintenance_low_pri` is set,
+    /// so background cleanup doesn't compete with block commits.
+    pub fn write_maintenance_batch(&self, batch: PathDBWriteBatch) -> PathProviderResult<()> {
+        if !batch.is_empty() {
+            self.wait_for_maintenance_budget();
+        }
+        self.write_batch_with(batch, true)
+    }
+
+    fn write_batch_with(&self, batch: PathDBWriteBatch, maintenance: bool) -> PathProviderResult<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+
+        let default_cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
+        let storage_root_cf = self.db.cf_handle(STORAGE_ROOT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(STORAGE_ROOT_COLUMN_FAMILY_NAME))?;
+        let meta_cf = self.db.cf_handle(META_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(META_COLUMN_FAMILY_NAME))?;
+
+        // Rejected before anything is written, a malformed root would only
+        // surface when read back
+        for (key, value) in &batch.storage_roots {
+            let value_len = value.as_ref().map_or(B256::len_bytes(), Vec::len);
+            if key.len() != B256::len_bytes() || value_len != B256::len_bytes() {
+                self.metrics.increment_storage_root_corruptions(1);
+                error!(target: "pathdb::batch", "Rejected storage root write for key 0x{}: {} byte key, {} byte value", hex_key(key), key.len(), value_len);
+                return Err(PathProviderError::InvalidStorageRoot { key: key.clone(), key_len: key.len(), value_len });
+            }
+        }
+
+        let _write_guard = self.trie_write_guard();
+        let mut trie_node_cache = self.trie_node_cache.lock_all();
+        let mut storage_root_cache = self.storage_root_cache.lock_all();
+
+        // Only the last operation per trie node key is written, earlier ones
+        // would leave orphaned overflow chunks behind
+        let last_trie_node_ops: HashMap<&[u8], usize> = batch.trie_nodes.iter()
+            .enumerate()
+            .map(|(index, (key, _))| (key.as_slice(), index))
+            .collect();
+
+        let mut write_batch = WriteBatch::default();
+        for (index, (key, value)) in batch.trie_nodes.iter().enumerate() {
+            if last_trie_node_ops[key.as_slice()] != index {
+                continue;
+            }
+            match value {
+                Some(value) => self.batch_put_trie_node(&mut write_batch, &default_cf, &self.db_key(key), value)?,
+                None => self.batch_delete_trie_node(&mut write_batch, &default_cf, &self.db_key(key))?,
+            }
+        }
+        for (key, value) in &batch.storage_roots {
+            match value {
+                Some(value) => write_batch.put_cf(&storage_root_cf, self.db_key(key), value),
+                None => write_batch.delete_cf(&storage_root_cf, self.db_key(key)),
+            }
+        }
+        if let Some((block_number, state_root)) = batch.persist_state {
+            if self.config.storage_root_history {
+                // Removed roots are recorded as empty, so lookups past the removal don't see the old root
+                let roots = batch.storage_roots.iter().map(|(key, value)| {
+                    (B256::from_slice(key), value.as_deref().map_or(EMPTY_ROOT_HASH, B256::from_slice))
+                });
+                self.batch_record_storage_root_history(&mut write_batch, block_number, roots)?;
+            }
+            // Same keys as `commit_difflayer`, in both Column Families
+            for cf in [&default_cf, &meta_cf] {
+                write_batch.put_cf(cf, self.db_key(TRIE_STATE_ROOT_KEY), state_root.as_slice());
+                write_batch.put_cf(cf, self.db_key(TRIE_STATE_BLOCK_NUMBER_KEY), block_number.to_le_bytes());
+            }
+        }
+
+        let result = if maintenance {
+            self.write_maintenance_raw_batch(write_batch)
+        } else {
+            self.write_raw_batch(write_batch)
+        };
+        result.map_err(|e| {
+            error!(target: "pathdb::batch", "Error writing batch of {} operations: {}", batch.len(), e);
+            PathProviderError::rocksdb("Batch write error", e)
+        })?;
+
+        for (key, value) in batch.trie_nodes {
+            match value {
+                Some(value) => {
+                    self.forget_missing(&key);
+                    trie_node_cache.insert(key, Some(value.into()));
+                }
+                None => { trie_node_cache.remove(&key); }
+            }
+        }
+        for (key, value) in batch.storage_roots {
+            match value {
+                Some(value) => { storage_root_cache.insert(key, Some(value.into())); }
+                None => { storage_root_cache.remove(&key); }
+            }
+        }
+        if let Some((block_number, state_root)) = batch.persist_state {
+            self.persist_generation.fetch_add(1, Ordering::Release);
+            trie_node_cache.insert(TRIE_STATE_ROOT_KEY.to_vec(), Some(Bytes::copy_from_slice(state_root.as_slice())));
+            trie_node_cache.insert(TRIE_STATE_BLOCK_NUMBER_KEY.to_vec(), Some(Bytes::copy_from_slice(&block_number.to_le_bytes())));
+            self.forget_missing(TRIE_STATE_ROOT_KEY);
+            self.forget_missing(TRIE_STATE_BLOCK_NUMBER_KEY);
+        }
+        Ok(())
+    }
+
+    /// Write `storage_roots` together with `block_number` and `state_root` as
+    /// the persisted state, in a single RocksDB write.
+    pub fn batch_insert_storage_roots(
+        &self,
+        storage_roots: impl IntoIterator<Item = (B256, B256)>,
+        block_number: u64,
+        state_root: B256,
+    ) -> PathProviderResult<()> {
+        let mut batch = PathDBWriteBatch::new();
+        let mut batch_size = 0;
+        for (hashed_address, storage_root) in storage_roots {
+            batch.put_storage_root(hashed_address, storage_root);
+            batch_size += 1;
+        }
+        batch.set_persist_state(block_number, state_root);
+        self.write_batch(batch)?;
+        self.metrics.record_storage_root_batch_size(batch_size);
+        Ok(())
+    }
+
+    /// Record that the storage root column family holds the storage root of
+    /// every account as of `block_number`, e.g. once a backfill of the
+    /// persisted state finished.
+    ///
+    /// Commits keep the column family complete from then on, so
+    /// `TrieDatabase::get_storage_root` reports missing owners with
+    /// `EMPTY_ROOT_HASH` instead of `None` and readers can skip the account
+    /// trie. `block_number` must not be past the persisted block.
+    pub fn mark_storage_roots_complete(&self, block_number: u64) -> PathProviderResult<()> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot mark storage roots complete in a read-only database".to_string()));
+        }
+        let (persisted_block, _) = self.latest_persist_state()?;
+        if block_number > persisted_block {
+            return Err(PathProviderError::InvalidOperation(format!(
+                "Cannot mark storage roots complete at block {} past the persisted block {}", block_number, persisted_block
+            )));
+        }
+
+        let meta_cf = self.db.cf_handle(META_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(META_COLUMN_FAMILY_NAME))?;
+        let mut batch = WriteBatch::default();
+        batch.put_cf(&meta_cf, self.db_key(STORAGE_ROOTS_COMPLETE_KEY), block_number.to_be_bytes());
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB put in CF '{}' error", META_COLUMN_FAMILY_NAME), e))?;
+        self.storage_roots_complete.store(true, Ordering::Release);
+        Ok(())
+    }
+
+    /// Get the block the storage root column family was marked complete at.
+    pub fn storage_roots_complete_block(&self) -> PathProviderResult<Option<u64>> {
+        self.get_meta_cf_value(STORAGE_ROOTS_COMPLETE_KEY)?
+            .map(|value| {
+                let bytes: [u8; 8] = value.as_slice().try_into().map_err(|_| {
+                    PathProviderError::Deserialization(format!("Invalid storage roots complete block of {} bytes", value.len()))
+                })?;
+                Ok(u64::from_be_bytes(bytes))
+            })
+            .transpose()
+    }
+
+    fn load_storage_roots_complete(&self) -> PathProviderResult<()> {
+        let complete = self.storage_roots_complete_block()?.is_some();
+        self.storage_roots_complete.store(complete, Ordering::Release);
+        Ok(())
+    }
+}
+
+/// Graceful shutdown and cache warming
+impl PathDB {
+    /// Prepare the database for shutdown.
+    ///
+    /// Stops and joins the background workers of this instance and its
+    /// clones, persists the most recently used trie node keys for
+    /// [`PathDB::warm_cache`], flushes the memtables of every column family,
+    /// the snapshot journal included, and the WAL, and publishes final
+    /// metrics. The RocksDB handle itself closes when the last clone is
+    /// dropped; reads and writes keep working until then.
+    ///
+    /// The database counts as closed once the flush succeeded; a failed close
+    /// can be retried and closing again after a successful close is a no-op.
+    pub fn close_gracefully(&self) -> PathProviderResult<()> {
+        if self.is_closed() {
+            return Ok(());
+        }
+        trace!(target: "pathdb::rocksdb", "Closing database");
+
+        // No worker may flush, delete or catch up while the final state is written
+        self.stop_workers();
+        if !self.is_read_only() {
+            if self.config.hot_keys_limit > 0 {
+                self.persist_hot_keys()?;
+            }
+            self.flush_all()?;
+            self.db.flush_wal(true)
+                .map_err(|e| PathProviderError::rocksdb("WAL flush error", e))?;
+        }
+        if self.closed.swap(true, Ordering::AcqRel) {
+            return Ok(());
+        }
+
+        let (trie_node_entries, storage_root_entries) = self.cache_stats();
+        self.metrics.set_trie_node_cache_entries(trie_node_entries as f64);
+        self.report_cache_bypassed_inserts();
+        self.metrics.set_storage_root_cache_entries(storage_root_entries as f64);
+        if self.config.deferred_deletion {
+            self.update_deletion_queue_backlog();
+        }
+        if self.config.enable_statistics {
+            self.amplification_report()?;
+        }
+
+        trace!(target: "pathdb::rocksdb", "Closed database, trie node cache entries: {}, storage root cache entries: {}", trie_node_entries, storage_root_entries);
+        Ok(())
+    }
+
+    /// Whether [`PathDB::close_gracefully`] succeeded on this instance or a clone.
+    pub fn is_closed(&self) -> bool {
+        self.closed.load(Ordering::Acquire)
+    }
+
+    /// Flush the memtables of every column family at once, unlike
+    /// `PathProviderManager::flush` which only flushes the trie nodes.
+    pub fn flush_all(&self) -> PathProviderResult<()> {
+        let start = Instant::now();
+        let cfs: Vec<_> = self.column_families()
+            .iter()
+            .filter_map(|name| self.db.cf_handle(name))
+            .collect();
+        self.db.flush_cfs_opt(&cfs.iter().collect::<Vec<_>>(), &FlushOptions::default())
+            .map_err(|e| PathProviderError::rocksdb("Flush error", e))?;
+        let elapsed = start.elapsed();
+        self.metrics.record_flush_duration(elapsed.as_secs_f64());
+        trace!(target: "pathdb::rocksdb", "Flushed {} column families in {:?}", cfs.len(), elapsed);
+        Ok(())
+    }
+
+    /// Register a background worker for [`PathDB::close_gracefully`] to stop.
+    pub(crate) fn register_worker(&self, worker: WorkerThread) {
+        let mut workers = self.workers.lock().unwrap();
+        workers.retain(|worker| !worker.is_stopped());
+        workers.push(worker);
+    }
+
+    /// Stop and join the registered background workers.
+    fn stop_workers(&self) {
+        // Joined outside the lock, a stopping worker may register or close itself
+        let workers = std::mem::take(&mut *self.workers.lock().unwrap());
+        for worker in workers {
+            worker.stop();
+        }
+    }
+
+    /// Load the trie nodes whose keys were persisted by the last graceful
+    /// close into the cache, returning the number of nodes loaded.
+    ///
+    /// Avoids a cold cache after a restart. Keys no longer present are skipped.
+    pub fn warm_cache(&self) -> PathProviderResult<usize> {
+        let Some(hot_keys) = self.get_meta_cf_value(HOT_KEYS_KEY)? else {
+            return Ok(0);
+        };
+
+        let keys = decode_hot_keys(&hot_keys)
+            .ok_or_else(|| PathProviderError::Deserialization("Invalid hot key list".to_string()))?;
+        let mut loaded = 0;
+        // Least recently used first, so the LRU order survives the restart
+        for key in keys.iter().rev() {
+            if self.get_raw_trie_node(key)?.is_some() {
+                loaded += 1;
+            }
+        }
+        trace!(target: "pathdb::rocksdb", "Warmed trie node cache with {} of {} hot keys", loaded, keys.len());
+        Ok(loaded)
+    }
+
+    /// Persist the most recently used trie node keys in the meta Column Family.
+    fn persist_hot_keys(&self) -> PathProviderResult<()> {
+        let mut hot_keys = Vec::new();
+        {
+            let trie_node_cache = self.trie_node_cache.lock_all();
+            for (key, _) in trie_node_cache.most_recent(self.config.hot_keys_limit, |value| value.is_some()) {
+                let Ok(len) = u16::try_from(key.len()) else { continue };
+                hot_keys.extend_from_slice(&len.to_be_bytes());
+                hot_keys.extend_from_slice(key);
+            }
+        }
+
+        let meta_cf = self.db.cf_handle(META_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(META_COLUMN_FAMILY_NAME))?;
+        let mut batch = WriteBatch::default();
+        batch.put_cf(&meta_cf, self.db_key(HOT_KEYS_KEY), hot_keys);
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB put in CF '{}' error", META_COLUMN_FAMILY_NAME), e))
+    }
+
+    /// Read `key` from the meta Column Family, bypassing the caches.
+    pub(crate) fn get_meta_cf_value(&self, key: &[u8]) -> PathProviderResult<Option<Vec<u8>>> {
+        let meta_cf = self.db.cf_handle(META_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(META_COLUMN_FAMILY_NAME))?;
+        self.db.get_cf_opt(&meta_cf, self.db_key(key), &self.read_options)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB get in CF '{}' error", META_COLUMN_FAMILY_NAME), e))
+    }
+}
+
+/// Write and space amplification reporting.
+impl PathDB {
+    /// Estimate write and space amplification.
+    ///
+    /// Write amplification compares the bytes RocksDB wrote to disk (WAL,
+    /// flushes and compactions) with the bytes committed over the last
+    /// `PathProviderConfig::amplification_window` commits and needs
+    /// `PathProviderConfig::enable_statistics`. Space amplification compares
+    /// the total SST file size with RocksDB's estimate of live data.
+    pub fn amplification_report(&self) -> PathProviderResult<AmplificationReport> {
+        let (window_commit_bytes, window_disk_write_bytes, write_amplification) = {
+            let amplification = self.amplification.lock().unwrap();
+            let (commit_bytes, disk_write_bytes) = amplification.deltas();
+            (commit_bytes, disk_write_bytes, amplification.write_amplification())
+        };
+
+        let mut sst_bytes = 0;
+        let mut live_bytes = 0;
+        for cf_name in COLUMN_FAMILY_NAMES {
+            let cf = self.db.cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+            let property = |name: &str| {
+                self.db.property_int_value_cf(&cf, name)
+                    .map(Option::unwrap_or_default)
+                    .map_err(|e| PathProviderError::rocksdb(format!("RocksDB property in CF '{}' error", cf_name), e))
+            };
+            sst_bytes += property("rocksdb.total-sst-files-size")?;
+            live_bytes += property("rocksdb.estimate-live-data-size")?;
+        }
+        let space_amplification = (live_bytes > 0).then(|| sst_bytes as f64 / live_bytes as f64);
+
+        if let Some(write_amplification) = write_amplification {
+            self.metrics.set_write_amplification(write_amplification);
+        }
+        if let Some(space_amplification) = space_amplification {
+            self.metrics.set_space_amplification(space_amplification);
+        }
+        Ok(AmplificationReport { write_amplification, space_amplification, window_commit_bytes, window_disk_write_bytes })
+    }
+
+    /// Bytes RocksDB wrote to disk since open, zero without statistics.
+    fn disk_write_bytes(&self) -> u64 {
+        if !self.config.enable_statistics {
+            return 0;
+        }
+        [Ticker::WalFileBytes, Ticker::FlushWriteBytes, Ticker::CompactWriteBytes]
+            .into_iter()
+            .map(|ticker| self.db_options.get_ticker_count(ticker))
+            .sum()
+    }
+
+    fn record_commit_bytes(&self, commit_bytes: u64) {
+        self.metrics.increment_commit_bytes(commit_bytes);
+        let disk_write_bytes = self.disk_write_bytes();
+        let mut amplification = self.amplification.lock().unwrap();
+        amplification.record(commit_bytes, disk_write_bytes);
+        if let Some(write_amplification) = amplification.write_amplification() {
+            self.metrics.set_write_amplification(write_amplification);
+        }
+    }
+}
+
+/// Deferred deletion of stale trie nodes.
+impl PathDB {
+    /// Delete up to `max_deletions` queued trie nodes, returns how many were deleted.
+    ///
+    /// Each call writes a single batch. The batch is built under the deletion
+    /// lock, but written after releasing it, so a slow low priority write
+    /// never stalls block commits. Commits that re-create one of the queued
+    /// nodes meanwhile wait for the batch to land before queuing their write,
+    /// so they can't be clobbered. Only entries under this instance's
+    /// namespace are processed.
+    pub fn process_deletion_queue(&self, max_deletions: usize) -> PathProviderResult<usize> {
+        let default_cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
+        let deletion_queue_cf = self.deletion_queue_cf()?;
+
+        self.wait_for_maintenance_budget();
+        // Held until the batch landed, so a migration never sees it in flight
+        let _migration_guard = self.migration_lock.read().unwrap();
+        let deletion_guard = self.deletion_lock.lock().unwrap();
+        let read_options = self.scan_read_options(None, None);
+        let lower = self.db_key(&[]).into_owned();
+        let mut db_keys = Vec::new();
+        for item in self.db.iterator_cf_opt(&deletion_queue_cf, read_options, IteratorMode::From(&lower, Direction::Forward)).take(max_deletions) {
+            let (db_key, _) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", DELETION_QUEUE_COLUMN_FAMILY_NAME), e)
+            })?;
+            db_keys.push(db_key);
+        }
+        if db_keys.is_empty() {
+            return Ok(0);
+        }
+
+        let mut batch = WriteBatch::default();
+        for db_key in &db_keys {
+            self.batch_delete_trie_node(&mut batch, &default_cf, db_key)?;
+        }
+        self.deletions_in_flight.start(&db_keys);
+        drop(deletion_guard);
+
+        let result = self.write_maintenance_raw_batch(batch);
+        self.deletions_in_flight.finish(&db_keys);
+        result.map_err(|e| {
+            error!(target: "pathdb::batch", "Error processing {} queued deletions: {}", db_keys.len(), e);
+            PathProviderError::rocksdb("Deletion queue batch error", e)
+        })?;
+
+        self.metrics.increment_deferred_deletions_processed(db_keys.len() as u64);
+        self.update_deletion_queue_backlog();
+        trace!(target: "pathdb::batch", "Processed {} queued deletions", db_keys.len());
+        Ok(db_keys.len())
+    }
+
+    /// Estimated number of queued deletions across all namespaces.
+    pub fn deletion_queue_backlog(&self) -> PathProviderResult<u64> {
+        let deletion_queue_cf = self.deletion_queue_cf()?;
+        let backlog = self.db.property_int_value_cf(&deletion_queue_cf, "rocksdb.estimate-num-keys")
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB property in CF '{}' error", DELETION_QUEUE_COLUMN_FAMILY_NAME), e))?;
+        Ok(backlog.unwrap_or_default())
+    }
+
+    fn update_deletion_queue_backlog(&self) {
+        if let Ok(backlog) = self.deletion_queue_backlog() {
+            self.metrics.set_deletion_queue_backlog(backlog as f64);
+        }
+    }
+
+    /// Whether `db_key` waits in the deletion queue, so its blob on disk is stale.
+    fn is_deletion_queued(&self, db_key: &[u8]) -> PathProviderResult<bool> {
+        if !self.config.deferred_deletion {
+            return Ok(false);
+        }
+        let queued = self.db.get_pinned_cf_opt(&self.deletion_queue_cf()?, db_key, &self.read_options)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB get in CF '{}' error", DELETION_QUEUE_COLUMN_FAMILY_NAME), e))?;
+        Ok(queued.is_some())
+    }
+
+    /// Drop the pending deletion of `db_key`, if deferred deletion is enabled.
+    ///
+    /// Waits first if a deletion queue batch deleting `db_key` is still being
+    /// written, see [`PathDB::process_deletion_queue`].
+    fn batch_cancel_deletion(&self, batch: &mut WriteBatch, db_key: &[u8]) -> PathProviderResult<()> {
+        if self.config.deferred_deletion {
+            self.deletions_in_flight.wait(db_key);
+            batch.delete_cf(&self.deletion_queue_cf()?, db_key);
+        }
+        Ok(())
+    }
+
+    /// Hold the migration lock shared while writing trie nodes, and the
+    /// deletion lock if deferred deletion or cold storage is enabled.
+    ///
+    /// The migration lock is always taken first.
+    pub(crate) fn trie_write_guard(&self) -> TrieWriteGuard<'_> {
+        let migration = self.migration_lock.read().unwrap();
+        let deletion = (self.config.deferred_deletion || self.config.cold_storage).then(|| self.deletion_lock.lock().unwrap());
+        TrieWriteGuard { _deletion: deletion, _migration: migration }
+    }
+
+    /// Hold the migration lock exclusively, blocking all trie node writes.
+    pub(crate) fn migration_guard(&self) -> RwLockWriteGuard<'_, ()> {
+        self.migration_lock.write().unwrap()
+    }
+
+    fn deletion_queue_cf(&self) -> PathProviderResult<Arc<BoundColumnFamily<'_>>> {
+        self.db.cf_handle(DELETION_QUEUE_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DELETION_QUEUE_COLUMN_FAMILY_NAME))
+    }
+}
+
+/// Overflow storage for oversized trie node values.
+impl PathDB {
+    /// Add a trie node write to `batch`, splitting the value into overflow
+    /// chunks when it exceeds the configured threshold.
+    ///
+    /// Chunks of a previously stored oversized value under the same key are
+    /// deleted in the same batch, even if overflow storage has been disabled
+    /// since.
+    pub(crate) fn batch_put_trie_node(&self, batch: &mut WriteBatch, cf: &impl AsColumnFamilyRef, db_key: &[u8], value: &[u8]) -> PathProviderResult<()> {
+        self.batch_cancel_deletion(batch, db_key)?;
+        self.batch_delete_overflow_chunks(batch, cf, db_key)?;
+
+        match self.config.overflow_threshold {
+            Some(threshold) if value.len() > threshold => {}
+            _ => {
+                batch.put_cf(cf, db_key, value);
+                return Ok(());
             }
         }
 
-        let cf = self.db.cf_handle(STORAGE_ROOT_COLUMN_FAMILY_NAME).ok_or_else(|| {
-            PathProviderError::Database(format!("Column Family '{}' handle not found", STORAGE_ROOT_COLUMN_FAMILY_NAME))
+        let overflow_cf = self.overflow_cf()?;
+        self.mark_overflow_key(db_key);
+        let (pointer, chunks) = self.split_overflow(db_key, value)?;
+        let chunk_count = chunks.len();
+        for (chunk_key, chunk) in chunks {
+            batch.put_cf(&overflow_cf, chunk_key, chunk);
+        }
+        batch.put_cf(cf, db_key, pointer);
+
+        trace!(target: "pathdb::rocksdb", "Stored value of {} bytes in {} overflow chunks", value.len(), chunk_count);
+        Ok(())
+    }
+
+    /// Split `value` into overflow chunks, returning the pointer to store
+    /// under `db_key` and the `(chunk key, chunk)` entries of the overflow
+    /// column family.
+    pub(crate) fn split_overflow<'v>(&self, db_key: &[u8], value: &'v [u8]) -> PathProviderResult<(Vec<u8>, Vec<(Vec<u8>, &'v [u8])>)> {
+        let chunks = value.chunks(self.config.overflow_chunk_size);
+        let chunk_count = u32::try_from(chunks.len()).map_err(|_| {
+            PathProviderError::InvalidOperation(format!("Value of {} bytes needs too many overflow chunks", value.len()))
         })?;
+        let chunks = chunks
+            .enumerate()
+            .map(|(index, chunk)| (overflow_chunk_key(db_key, index as u32), chunk))
+            .collect();
+        Ok((encode_overflow_pointer(value.len(), chunk_count), chunks))
+    }
 
-        let key_hex = key.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    /// Add a trie node delete to `batch`, including its overflow chunks.
+    pub(crate) fn batch_delete_trie_node(&self, batch: &mut WriteBatch, cf: &impl AsColumnFamilyRef, db_key: &[u8]) -> PathProviderResult<()> {
+        self.batch_cancel_deletion(batch, db_key)?;
+        self.batch_delete_overflow_chunks(batch, cf, db_key)?;
+        batch.delete_cf(cf, db_key);
+        self.batch_delete_cold_trie_node(batch, db_key)
+    }
 
-        // Cache miss, read from DB
-        match self.db.get_cf_opt(&cf, key, &self.read_options) {
-            Ok(Some(value)) => {
-                trace!(target: "pathdb::rocksdb", "Found value in CF '{}' for key 0x{}", STORAGE_ROOT_COLUMN_FAMILY_NAME, key_hex);
-                self.storage_root_cache.lock().unwrap().insert(key.to_vec(), Some(value.to_vec()));
-                Ok(Some(value))
-            }
-            Ok(None) => {
-                trace!(target: "pathdb::rocksdb", "Key not found in CF '{}' for key 0x{}", STORAGE_ROOT_COLUMN_FAMILY_NAME, key_hex);
-                Ok(None)
-            }
-            Err(e) => {
-                error!(target: "pathdb::rocksdb", "Error getting in CF '{}' for key 0x{}: {}", STORAGE_ROOT_COLUMN_FAMILY_NAME, key_hex, e);
-                Err(PathProviderError::Database(format!("RocksDB get in CF '{}' for key 0x{} error: {}", STORAGE_ROOT_COLUMN_FAMILY_NAME, key_hex, e)))
+    /// Delete the overflow chunks referenced by the value currently stored under `db_key`.
+    ///
+    /// The stored value is only looked up if `db_key` may be stored as an
+    /// overflow pointer, see [`PathDB::mark_overflow_key`].
+    fn batch_delete_overflow_chunks(&self, batch: &mut WriteBatch, cf: &impl AsColumnFamilyRef, db_key: &[u8]) -> PathProviderResult<()> {
+        if !self.overflow_keys.may_contain(db_key) {
+            return Ok(());
+        }
+        let overflow_cf = self.overflow_cf()?;
+        let previous = self.db.get_pinned_cf_opt(cf, db_key, &self.read_options)
+            .map_err(|e| PathProviderError::rocksdb("RocksDB get for overflow pointer error", e))?;
+
+        if let Some((_, chunk_count)) = previous.as_deref().and_then(decode_overflow_pointer) {
+            for index in 0..chunk_count {
+                batch.delete_cf(&overflow_cf, overflow_chunk_key(db_key, index));
             }
         }
+        Ok(())
     }
 
-    pub fn get_raw_meta_data(&self, key: &[u8]) -> PathProviderResult<Option<Vec<u8>>> {
-        // Check cache first
-        {
-            let cache = self.trie_node_cache.lock().unwrap();
-            if let Some(cached_value) = cache.peek(key) {
-                trace!(target: "pathdb::rocksdb", "Found value in cache for key: {:?}", key);
-                return Ok(cached_value.clone());
-            }
+    /// Add the delete of the overflow chunks of the trie nodes in the logical
+    /// key range `start..end` to `batch`, hot and cold copies alike.
+    ///
+    /// Chunk keys start with the length of their node key, so this writes
+    /// one range tombstone per node key length the range can hold, see
+    /// [`overflow_chunk_ranges`]. Chunks written later in the same batch
+    /// are kept.
+    fn batch_delete_overflow_range(&self, batch: &mut WriteBatch, start: &[u8], end: &[u8]) -> PathProviderResult<()> {
+        if !self.overflow_keys.in_use() {
+            return Ok(());
+        }
+        let overflow_cf = self.overflow_cf()?;
+        let max_key_len = self.db_key(&[]).len() + MAX_TRIE_NODE_KEY_LEN;
+        for (chunk_start, chunk_end) in overflow_chunk_ranges(&self.db_key(start), &self.db_key(end), max_key_len) {
+            batch.delete_range_cf(&overflow_cf, chunk_start, chunk_end);
+        }
+        Ok(())
+    }
+
+    /// Reassemble a value from its overflow chunks if `value` is an overflow pointer.
+    pub(crate) fn resolve_overflow(&self, db_key: &[u8], value: Vec<u8>) -> PathProviderResult<Vec<u8>> {
+        self.resolve_overflow_with(db_key, value, &self.read_options)
+    }
+
+    fn resolve_overflow_with(&self, db_key: &[u8], value: Vec<u8>, read_options: &ReadOptions) -> PathProviderResult<Vec<u8>> {
+        let Some((total_len, chunk_count)) = decode_overflow_pointer(&value) else {
+            return Ok(value);
+        };
+
+        let overflow_cf = self.overflow_cf()?;
+        let mut resolved = Vec::with_capacity(total_len);
+        for index in 0..chunk_count {
+            let chunk = self.db.get_pinned_cf_opt(&overflow_cf, overflow_chunk_key(db_key, index), read_options)
+                .map_err(|e| PathProviderError::rocksdb(format!("RocksDB get in CF '{}' error", OVERFLOW_COLUMN_FAMILY_NAME), e))?
+                .ok_or_else(|| PathProviderError::Deserialization(format!("Missing overflow chunk {} of {}", index, chunk_count)))?;
+            resolved.extend_from_slice(&chunk);
         }
 
-        // TODO:: change to META_COLUMN_FAMILY_NAME from default CF in the future.
-        let cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| {
-            PathProviderError::Database(format!("Column Family '{}' handle not found", DEFAULT_COLUMN_FAMILY_NAME))
-        })?;
+        if resolved.len() != total_len {
+            return Err(PathProviderError::Deserialization(format!(
+                "Overflow value length mismatch: expected {}, got {}", total_len, resolved.len()
+            )));
+        }
+        Ok(resolved)
+    }
 
-        // Convert key to readable string: try UTF-8 first, fallback to hex if invalid
-        let key_string = String::from_utf8_lossy(key).to_string();
-        
-        match self.db.get_cf_opt(&cf, key, &self.read_options) {
-            Ok(Some(value)) => {
-                trace!(target: "pathdb::rocksdb", "Found value in CF '{}' for key: {}", DEFAULT_COLUMN_FAMILY_NAME, key_string);
-                self.trie_node_cache.lock().unwrap().insert(key.to_vec(), Some(value.clone()));
-                Ok(Some(value))
-            }
-            Ok(None) => {
-                trace!(target: "pathdb::rocksdb", "Key not found in CF '{}' for key: {}", DEFAULT_COLUMN_FAMILY_NAME, key_string);
-                Ok(None)
-            }
-            Err(e) => {
-                error!(target: "pathdb::rocksdb", "Error getting in CF '{}' for key {}: {}", DEFAULT_COLUMN_FAMILY_NAME, key_string, e);
-                Err(PathProviderError::Database(format!("RocksDB get in CF '{}' for key {} error: {}", DEFAULT_COLUMN_FAMILY_NAME, key_string, e)))
+    /// Record that `db_key` is stored as an overflow pointer, before writing its chunks.
+    pub(crate) fn mark_overflow_key(&self, db_key: &[u8]) {
+        self.overflow_keys.insert(db_key);
+    }
+
+    /// Fill the overflow key filter with the keys of the chunks written by
+    /// earlier runs, possibly with overflow storage enabled back then.
+    ///
+    /// Walks the overflow column family, which only holds the chunks of
+    /// oversized nodes.
+    fn load_overflow_keys(&self) -> PathProviderResult<()> {
+        let overflow_cf = self.overflow_cf()?;
+        for item in self.db.iterator_cf(&overflow_cf, IteratorMode::Start) {
+            let (chunk_key, _) = item.map_err(|e| PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", OVERFLOW_COLUMN_FAMILY_NAME), e))?;
+            if let Some(db_key) = overflow_chunk_db_key(&chunk_key) {
+                self.overflow_keys.insert(db_key);
             }
         }
+        Ok(())
+    }
+
+    fn overflow_cf(&self) -> PathProviderResult<Arc<BoundColumnFamily<'_>>> {
+        self.db.cf_handle(OVERFLOW_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(OVERFLOW_COLUMN_FAMILY_NAME))
     }
 }
 
 impl PathProviderManager for PathDB {
     fn close(&self) -> PathProviderResult<()> {
-        trace!(target: "pathdb::rocksdb", "Closing database");
-
-        // RocksDB automatically closes when the last Arc is dropped
-        Ok(())
+        self.close_gracefully()
     }
 
     fn flush(&self) -> PathProviderResult<()> {
         trace!(target: "pathdb::rocksdb", "Flushing database");
 
+        let start = Instant::now();
         match self.db.flush() {
             Ok(()) => {
-                trace!(target: "pathdb::rocksdb", "Successfully flushed database");
+                let elapsed = start.elapsed();
+                self.metrics.record_flush_duration(elapsed.as_secs_f64());
+                trace!(target: "pathdb::rocksdb", "Successfully flushed database in {:?}", elapsed);
                 Ok(())
             }
             Err(e) => {
                 error!(target: "pathdb::rocksdb", "Error flushing database: {}", e);
-                Err(PathProviderError::Database(format!("Flush error: {}", e)))
+                Err(PathProviderError::rocksdb("Flush error", e))
             }
         }
     }
 
-    fn compact(&self) -> PathProviderResult<()> {
+    fn compact(&self, range: Option<(&[u8], &[u8])>) -> PathProviderResult<()> {
         trace!(target: "pathdb::rocksdb", "Compacting database");
 
-        // Simplified compact implementation
+        let start = Instant::now();
+        for cf_name in COLUMN_FAMILY_NAMES {
+            self.compact_cf_range(cf_name, range)?;
+        }
+        trace!(target: "pathdb::rocksdb", "Successfully compacted database in {:?}", start.elapsed());
+        Ok(())
+    }
+
+    fn compact_cf(&self, cf_name: &str) -> PathProviderResult<()> {
+        self.compact_cf_range(cf_name, None)
+    }
+}
+
+/// Manual compaction.
+impl PathDB {
+    /// Compact the keys from `start` to `end` of `cf_name`, the whole column family without a range.
+    fn compact_cf_range(&self, cf_name: &str, range: Option<(&[u8], &[u8])>) -> PathProviderResult<()> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot compact a read-only database".to_string()));
+        }
+        let cf = self.db.cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+
+        let start = Instant::now();
+        match range {
+            Some((begin, end)) => self.db.compact_range_cf(&cf, Some(self.db_key(begin)), Some(self.db_key(end))),
+            None => self.db.compact_range_cf(&cf, None::<&[u8]>, None::<&[u8]>),
+        }
+        let elapsed = start.elapsed();
+        self.metrics.record_compaction_duration(elapsed.as_secs_f64());
+        trace!(target: "pathdb::rocksdb", "Compacted CF '{}' in {:?}", cf_name, elapsed);
         Ok(())
     }
 }
@@ -467,10 +3455,18 @@ impl PathProviderManager for PathDB {
 impl TrieDatabase for PathDB {
     type Error = PathProviderError;
 
-    fn get_trie_node(&self, path: &[u8]) -> Result<Option<Vec<u8>>, Self::Error> {
+    fn get_trie_node(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
         self.get_raw_trie_node(path)
     }
 
+    fn get_trie_nodes(&self, paths: &[Vec<u8>]) -> Result<Vec<Option<Bytes>>, Self::Error> {
+        self.get_multi_raw_trie_nodes(paths)
+    }
+
+    fn get_trie_node_verified(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        self.get_verified_raw_trie_node(path)
+    }
+
     fn insert_trie_node(&self, path: &[u8], data: Vec<u8>) -> Result<(), Self::Error> {
         self.put_raw_trie_node(path, &data)
     }
@@ -483,26 +3479,34 @@ impl TrieDatabase for PathDB {
         let _ = self.delete_raw_trie_node(path);
     }
 
+    fn iter_trie_node_range(&self, start: &[u8], end: Option<&[u8]>, limit: usize) -> Result<Vec<(Vec<u8>, Bytes)>, Self::Error> {
+        self.iter_range(start, end)?
+            .take(limit)
+            .map(|item| item.map(|(key, value)| (key, Bytes::from(value))))
+            .collect()
+    }
+
     fn get_storage_root(&self, hased_address: B256) -> Result<Option<B256>, Self::Error> {
-        let value = self.get_raw_storage_root(hased_address.as_slice())?;
-        if let Some(value) = value {
-            if value.len() == 32 {
-                Ok(Some(B256::from_slice(&value)))
-            } else {
-                let address_hex = format!("0x{:x}", hased_address);
-                let value_hex = value.iter().map(|b| format!("{:02x}", b)).collect::<String>();
-                error!(target: "pathdb::rocksdb", "Storage root value length is not 32 for address: {}, value_len: {}, value: 0x{}", address_hex, value.len(), value_hex);
-                Ok(None)
-            }
-        } else {
-            Ok(None)
+        let start = Instant::now();
+        let root = self.read_storage_root(hased_address)?;
+        self.metrics.record_get_storage_root_duration(start.elapsed().as_secs_f64());
+        // A root reported by a complete column family counts as found, only
+        // `None` sends the caller to the account trie
+        match root {
+            Some(_) => self.metrics.increment_storage_root_found(1),
+            None => self.metrics.increment_storage_root_missing(1),
         }
+        Ok(root)
     }
 
     fn clear_cache(&self) {
         self.clear_cache();
     }
 
+    fn persist_generation(&self) -> Option<u64> {
+        Some(self.persist_generation.load(Ordering::Acquire))
+    }
+
     fn latest_persist_state(&self) -> Result<(u64, B256), Self::Error> {
         let block_number_bytes = self.get_raw_meta_data(TRIE_STATE_BLOCK_NUMBER_KEY)?;
         let state_root_bytes = self.get_raw_meta_data(TRIE_STATE_ROOT_KEY)?;
@@ -516,153 +3520,636 @@ impl TrieDatabase for PathDB {
         }
     }
 
+    fn put_audit_record(&self, record: &AuditRecord) -> Result<(), Self::Error> {
+        let mut batch = WriteBatch::default();
+        self.batch_put_audit_record(&mut batch, record)?;
+        self.write_raw_batch(batch)
+            .map_err(|e| {
+                error!(target: "pathdb::rocksdb", "Error writing audit record for block {}: {}", record.block_number, e);
+                PathProviderError::rocksdb(format!("RocksDB put in CF '{}' error", AUDIT_LOG_COLUMN_FAMILY_NAME), e)
+            })
+    }
+
     fn commit_difflayer(&self, block_number: u64, state_root: B256, difflayer: &Option<Arc<DiffLayer>>) -> Result<(), Self::Error> {
+        self.commit_difflayer_with_durability(block_number, state_root, difflayer, self.config.write_durability())
+    }
+
+    fn get_audit_records(&self, block_number: u64) -> Result<Vec<AuditRecord>, Self::Error> {
+        PathDB::get_audit_records(self, block_number)
+    }
+
+    fn get_addresses_by_code_hash(&self, code_hash: B256) -> Result<Vec<B256>, Self::Error> {
+        PathDB::get_addresses_by_code_hash(self, code_hash)
+    }
+
+    fn get_storage_slot_count(&self, hashed_address: B256) -> Result<Option<u64>, Self::Error> {
+        PathDB::get_storage_slot_count(self, hashed_address)
+    }
+
+    fn get_storage_root_at(&self, hashed_address: B256, block_number: u64) -> Result<Option<B256>, Self::Error> {
+        PathDB::get_storage_root_at(self, hashed_address, block_number)
+    }
+
+    fn get_account_snapshot(&self, hashed_address: B256) -> Result<Option<Bytes>, Self::Error> {
+        self.get_raw_account_snapshot(hashed_address)
+    }
+
+    fn put_account_snapshot(&self, hashed_address: B256, account: Option<&[u8]>) -> Result<(), Self::Error> {
+        match account {
+            Some(account) => self.put_raw_account_snapshot(hashed_address, account),
+            None => self.delete_raw_account_snapshot(hashed_address),
+        }
+    }
+
+    fn get_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<Bytes>, Self::Error> {
+        self.get_raw_storage_snapshot(hashed_address, hashed_key)
+    }
+
+    fn put_storage_snapshot(&self, hashed_address: B256, hashed_key: B256, value: Option<&[u8]>) -> Result<(), Self::Error> {
+        match value {
+            Some(value) => self.put_raw_storage_snapshot(hashed_address, hashed_key, value),
+            None => self.delete_raw_storage_snapshot(hashed_address, hashed_key),
+        }
+    }
+
+    fn iter_account_snapshot(&self, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        PathDB::iter_account_snapshot(self, start_hash, limit)
+    }
+
+    fn iter_storage_snapshot(&self, hashed_address: B256, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        PathDB::iter_storage_snapshot(self, hashed_address, start_hash, limit)
+    }
+
+    fn shutdown(&self) -> Result<(), Self::Error> {
+        self.close_gracefully()
+    }
+}
+
+/// Difflayer commits
+impl PathDB {
+    /// Commit a difflayer like [`TrieDatabase::commit_difflayer`], with
+    /// `durability` instead of the configured one.
+    ///
+    /// Lets finalized blocks be synced to disk while intermediate blocks take
+    /// the cheaper unsynced WAL path. Fails without writing anything if the
+    /// difflayer is rejected by the configured `CommitLimits`.
+    pub fn commit_difflayer_with_durability(
+        &self,
+        block_number: u64,
+        state_root: B256,
+        difflayer: &Option<Arc<DiffLayer>>,
+        durability: WriteDurability,
+    ) -> PathProviderResult<()> {
+        durability.validate()?;
+        if let Some(difflayer) = difflayer {
+            self.check_commit_limits(block_number, difflayer)?;
+        }
+        self.write_difflayer(block_number, state_root, difflayer, durability)
+    }
+
+    /// Write a difflayer commit without checking the `CommitLimits`, for
+    /// callers that checked them already, e.g. on the unsplit layer of a
+    /// [`ShardedPathDB`](crate::ShardedPathDB).
+    pub(crate) fn write_difflayer(
+        &self,
+        block_number: u64,
+        state_root: B256,
+        difflayer: &Option<Arc<DiffLayer>>,
+        durability: WriteDurability,
+    ) -> PathProviderResult<()> {
+
         // Get Column Family handle for default CF
-        let default_cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| {
-            PathProviderError::Database(format!("Column Family '{}' handle not found", DEFAULT_COLUMN_FAMILY_NAME))
-        })?;
+        let default_cf = self.db.cf_handle(DEFAULT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(DEFAULT_COLUMN_FAMILY_NAME))?;
 
-        let meta_cf = self.db.cf_handle(META_COLUMN_FAMILY_NAME).ok_or_else(|| {
-            PathProviderError::Database(format!("Column Family '{}' handle not found", META_COLUMN_FAMILY_NAME))
-        })?;
+        let meta_cf = self.db.cf_handle(META_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(META_COLUMN_FAMILY_NAME))?;
 
-        let storage_root_cf = self.db.cf_handle(STORAGE_ROOT_COLUMN_FAMILY_NAME).ok_or_else(|| {
-            PathProviderError::Database(format!("Column Family '{}' handle not found", STORAGE_ROOT_COLUMN_FAMILY_NAME))
-        })?;
+        let storage_root_cf = self.db.cf_handle(STORAGE_ROOT_COLUMN_FAMILY_NAME).ok_or_else(|| PathProviderError::column_family_missing(STORAGE_ROOT_COLUMN_FAMILY_NAME))?;
+
+        let deletion_queue_cf = self.deletion_queue_cf()?;
 
         let mut diff_nodes_len = 0;
         let mut diff_storage_roots_len = 0;
 
+        let _write_guard = self.trie_write_guard();
         let mut batch = WriteBatch::default();
+        // Held until the batch lands, so cached entries never run ahead of
+        // the database, see `PathDB::read_snapshot`
+        let mut trie_node_cache = self.trie_node_cache.lock_all();
+        let mut storage_root_cache = self.storage_root_cache.lock_all();
         {
-            let mut trie_node_cache = self.trie_node_cache.lock().unwrap();
-            let mut storage_root_cache = self.storage_root_cache.lock().unwrap();
-
-            batch.put_cf(&default_cf, TRIE_STATE_ROOT_KEY, state_root.as_slice());
-            batch.put_cf(&default_cf, TRIE_STATE_BLOCK_NUMBER_KEY, &block_number.to_le_bytes());
+            batch.put_cf(&default_cf, self.db_key(TRIE_STATE_ROOT_KEY), state_root.as_slice());
+            batch.put_cf(&default_cf, self.db_key(TRIE_STATE_BLOCK_NUMBER_KEY), &block_number.to_le_bytes());
 
             // TODO:: double Write to meta CF using put_cf, will be delete default CF in the future.
-            batch.put_cf(&meta_cf, TRIE_STATE_ROOT_KEY, state_root.as_slice());
-            batch.put_cf(&meta_cf, TRIE_STATE_BLOCK_NUMBER_KEY, &block_number.to_le_bytes());
+            batch.put_cf(&meta_cf, self.db_key(TRIE_STATE_ROOT_KEY), state_root.as_slice());
+            batch.put_cf(&meta_cf, self.db_key(TRIE_STATE_BLOCK_NUMBER_KEY), &block_number.to_le_bytes());
 
-            trie_node_cache.insert(TRIE_STATE_ROOT_KEY.to_vec(), Some(state_root.as_slice().to_vec()));
-            trie_node_cache.insert(TRIE_STATE_BLOCK_NUMBER_KEY.to_vec(), Some(block_number.to_le_bytes().to_vec()));
+            trie_node_cache.insert(TRIE_STATE_ROOT_KEY.to_vec(), Some(Bytes::copy_from_slice(state_root.as_slice())));
+            trie_node_cache.insert(TRIE_STATE_BLOCK_NUMBER_KEY.to_vec(), Some(Bytes::copy_from_slice(&block_number.to_le_bytes())));
+            self.forget_missing(TRIE_STATE_ROOT_KEY);
+            self.forget_missing(TRIE_STATE_BLOCK_NUMBER_KEY);
         
             if let Some(difflayer) = difflayer {
                 diff_nodes_len = difflayer.diff_nodes.len();
                 diff_storage_roots_len = difflayer.diff_storage_roots.len();
 
+                // Ranges first, nodes written by the same block are kept
+                trie_node_cache.remove_ranges(&difflayer.deleted_ranges);
+                for (start, end) in difflayer.deleted_ranges.iter() {
+                    batch.delete_range_cf(&default_cf, self.db_key(start), self.db_key(end));
+                    self.batch_delete_cold_range(&mut batch, start, end)?;
+                    self.batch_delete_overflow_range(&mut batch, start, end)?;
+                }
+
                 for (key, node) in difflayer.diff_nodes.iter() {
-                    if node.is_deleted() {
+                    if node.is_deleted() && self.config.deferred_deletion {
+                        // The node stays on disk until the queue is processed, reads
+                        // missing the cached deletion check the queue instead
+                        trie_node_cache.insert(key.clone(), None);
+                        batch.put_cf(&deletion_queue_cf, self.db_key(key), block_number.to_be_bytes());
+                    } else if node.is_deleted() {
                         trie_node_cache.remove(key);
-                        batch.delete_cf(&default_cf, key);
+                        self.batch_delete_trie_node(&mut batch, &default_cf, &self.db_key(key))?;
                         
                     } else {
                         if let Some(blob) = &node.blob {
                             trie_node_cache.insert(key.clone(), Some(blob.clone()));
-                            batch.put_cf(&default_cf, key, blob);
+                            self.forget_missing(key);
+                            self.batch_put_trie_node(&mut batch, &default_cf, &self.db_key(key), blob)?;
                         }
                     }
                 }
 
                 for (key, value) in difflayer.diff_storage_roots.iter() {
-                    storage_root_cache.insert(key.as_slice().to_vec(), Some(value.as_slice().to_vec()));
-                    batch.put_cf(&storage_root_cf, key.as_slice(), value.as_slice());
+                    storage_root_cache.insert(key.as_slice().to_vec(), Some(Bytes::copy_from_slice(value.as_slice())));
+                    batch.put_cf(&storage_root_cf, self.db_key(key.as_slice()), value.as_slice());
+                }
+                if self.config.storage_root_history {
+                    self.batch_record_storage_root_history(&mut batch, block_number, difflayer.diff_storage_roots.iter().map(|(key, value)| (*key, *value)))?;
+                }
+
+                if !difflayer.code_hashes.is_empty() {
+                    self.batch_update_code_hash_index(&mut batch, &difflayer.code_hashes)?;
+                }
+
+                if !difflayer.slot_count_changes.is_empty() {
+                    self.batch_update_slot_counts(&mut batch, &difflayer.slot_count_changes)?;
+                }
+
+                if let Some(record) = &difflayer.audit_record {
+                    self.batch_put_audit_record(&mut batch, record)?;
                 }
             }
         }
 
-        match self.db.write_opt(batch, &self.write_options) {
+        let commit_bytes = batch.size_in_bytes() as u64;
+        let durability_options;
+        let commit_options = if durability == self.config.write_durability() {
+            &self.write_options
+        } else {
+            durability_options = write_options(durability);
+            &durability_options
+        };
+        let result = self.write_raw_batch_opt(batch, commit_options);
+        drop(trie_node_cache);
+        drop(storage_root_cache);
+        match result {
             Ok(()) => {
+                self.record_commit_bytes(commit_bytes);
+                if difflayer.is_some() {
+                    self.metrics.record_storage_root_batch_size(diff_storage_roots_len);
+                }
+                self.advance_version_gc(block_number);
+                self.persist_generation.fetch_add(1, Ordering::Release);
+                self.report_cache_bypassed_inserts();
+                if self.config.deferred_deletion {
+                    self.update_deletion_queue_backlog();
+                }
                 trace!(target: "pathdb::batch", "Successfully committed batch to database, block_number: {}, state_root: {:?}, diff_nodes_len: {}, diff_storage_roots_len: {}", block_number, state_root, diff_nodes_len, diff_storage_roots_len);
                 Ok(())
             }
             Err(e) => {
                 error!(target: "pathdb::batch", "Error committing batch: block_number: {}, state_root: {:?}, error: {}", block_number, state_root, e);
-                Err(PathProviderError::Database(format!("Batch commit error: {}", e)))
+                Err(PathProviderError::rocksdb("Batch commit error", e))
+            }
+
+        }
+    }
+}
+
+
+/// Encode the pointer stored in the primary column family for an overflowed value.
+fn encode_overflow_pointer(total_len: usize, chunk_count: u32) -> Vec<u8> {
+    let mut pointer = Vec::with_capacity(OVERFLOW_POINTER_LEN);
+    pointer.extend_from_slice(OVERFLOW_POINTER_MARKER);
+    pointer.extend_from_slice(&(total_len as u64).to_be_bytes());
+    pointer.extend_from_slice(&chunk_count.to_be_bytes());
+    pointer
+}
+
+/// Decode an overflow pointer into `(total_len, chunk_count)`, `None` for regular values.
+pub(crate) fn decode_overflow_pointer(value: &[u8]) -> Option<(usize, u32)> {
+    if value.len() != OVERFLOW_POINTER_LEN || !value.starts_with(OVERFLOW_POINTER_MARKER) {
+        return None;
+    }
+    let offset = OVERFLOW_POINTER_MARKER.len();
+    let total_len = u64::from_be_bytes(value[offset..offset + 8].try_into().ok()?);
+    let chunk_count = u32::from_be_bytes(value[offset + 8..].try_into().ok()?);
+    Some((total_len as usize, chunk_count))
+}
+
+/// Decode a hot key list written by `PathDB::persist_hot_keys`, `None` if malformed.
+fn decode_hot_keys(mut value: &[u8]) -> Option<Vec<Vec<u8>>> {
+    let mut keys = Vec::new();
+    while !value.is_empty() {
+        let len = u16::from_be_bytes(value.get(..2)?.try_into().ok()?) as usize;
+        keys.push(value.get(2..2 + len)?.to_vec());
+        value = &value[2 + len..];
+    }
+    Some(keys)
+}
+
+/// Key of one overflow chunk; the length prefix keeps chunk ranges of different keys apart.
+pub(crate) fn overflow_chunk_key(db_key: &[u8], index: u32) -> Vec<u8> {
+    let mut chunk_key = Vec::with_capacity(2 + db_key.len() + 4);
+    chunk_key.extend_from_slice(&(db_key.len() as u16).to_be_bytes());
+    chunk_key.extend_from_slice(db_key);
+    chunk_key.extend_from_slice(&index.to_be_bytes());
+    chunk_key
+}
+
+/// The node key of the overflow chunk key `chunk_key`, `None` if malformed.
+fn overflow_chunk_db_key(chunk_key: &[u8]) -> Option<&[u8]> {
+    let len = u16::from_be_bytes(chunk_key.get(..2)?.try_into().ok()?) as usize;
+    (chunk_key.len() == 2 + len + 4).then(|| &chunk_key[2..2 + len])
+}
+
+/// Overflow chunk key ranges `[start, end)` holding the chunks of every
+/// node key in `start..end` of at most `max_key_len` bytes.
+///
+/// Keys of one length in `start..end` form a contiguous run of chunk keys,
+/// so there is one range per key length, empty lengths are skipped.
+pub(crate) fn overflow_chunk_ranges(start: &[u8], end: &[u8], max_key_len: usize) -> Vec<(Vec<u8>, Vec<u8>)> {
+    let mut ranges = Vec::new();
+    for len in 1..=max_key_len.min(u16::MAX as usize) {
+        // Smallest key of `len` bytes not below `start`
+        let lowest = if len >= start.len() {
+            let mut lowest = start.to_vec();
+            lowest.resize(len, 0);
+            lowest
+        } else {
+            let mut lowest = start[..len].to_vec();
+            match lowest.iter().rposition(|byte| *byte != 0xff) {
+                Some(index) => {
+                    lowest[index] += 1;
+                    lowest[index + 1..].fill(0);
+                }
+                None => continue,
+            }
+            lowest
+        };
+        // Largest key of `len` bytes below `end`
+        let highest = if len < end.len() {
+            end[..len].to_vec()
+        } else {
+            let mut highest = end.to_vec();
+            match highest.iter().rposition(|byte| *byte != 0) {
+                Some(index) => {
+                    highest[index] -= 1;
+                    highest[index + 1..].fill(0xff);
+                }
+                None => continue,
             }
+            highest.resize(len, 0xff);
+            highest
+        };
+        if lowest <= highest {
+            let chunk_end = [overflow_chunk_key(&highest, u32::MAX).as_slice(), &[0]].concat();
+            ranges.push((overflow_chunk_key(&lowest, 0), chunk_end));
+        }
+    }
+    ranges
+}
+
+/// Whether `key` is one of the persisted state and schema keys kept next to the trie nodes.
+fn is_meta_key(key: &[u8]) -> bool {
+    key == TRIE_STATE_ROOT_KEY || key == TRIE_STATE_BLOCK_NUMBER_KEY || key == SCHEMA_VERSION_KEY
+}
 
+/// Smallest key greater than every key starting with `prefix`, `None` if unbounded.
+fn prefix_upper_bound(prefix: &[u8]) -> Option<Vec<u8>> {
+    let mut upper = prefix.to_vec();
+    while let Some(last) = upper.pop() {
+        if last < u8::MAX {
+            upper.push(last + 1);
+            return Some(upper);
         }
     }
+    None
 }
 
+/// Check that a key namespace is non-empty and fits in its one-byte length prefix.
+fn validate_key_namespace(namespace: &[u8]) -> PathProviderResult<()> {
+    if namespace.is_empty() || namespace.len() > MAX_KEY_NAMESPACE_LEN {
+        return Err(PathProviderError::InvalidOperation(format!(
+            "Key namespace length must be between 1 and {} bytes, got {}",
+            MAX_KEY_NAMESPACE_LEN, namespace.len()
+        )));
+    }
+    Ok(())
+}
 
-/// Ensure all required Column Families exist in the database.
-/// Creates missing Column Families if they don't exist.
+/// Build the Column Family descriptors to open an existing database without
+/// write access, which can't create Column Families.
 ///
 /// # Arguments
 /// * `path` - Path to the RocksDB database
 /// * `db_opts` - Database options
 /// * `config` - Path provider configuration
-///
-/// # Returns
-/// * `Ok(())` if all Column Families exist or were successfully created
-/// * `Err(PathProviderError)` if there was an error creating Column Families
-fn ensure_column_families(
+/// * `version_gc_block` - Block the version GC compaction filter follows
+fn existing_column_family_descriptors(
     path: &str,
     db_opts: &Options,
     config: &PathProviderConfig,
-) -> PathProviderResult<()> {
-    // List existing Column Families in the database
+    version_gc_block: &Arc<AtomicU64>,
+) -> PathProviderResult<Vec<ColumnFamilyDescriptor>> {
     let existing_cfs = DB::list_cf(db_opts, path)
-        .unwrap_or_else(|_| vec!["default".to_string()]);
-    let existing_cfs_set: HashSet<String> = existing_cfs.iter().cloned().collect();
+        .map_err(|e| PathProviderError::rocksdb("Failed to list Column Families", e))?;
+    if let Some(missing) = COLUMN_FAMILY_NAMES.iter().find(|&&cf_name| !existing_cfs.iter().any(|existing| existing == cf_name)) {
+        // Opening the database read-write once creates it
+        return Err(PathProviderError::column_family_missing(missing));
+    }
+
+    let block_cache = config.block_cache_size.map(Cache::new_lru_cache);
+    let cf_opts = column_family_options(config, block_cache.as_ref());
+    Ok(existing_cfs
+        .iter()
+        .map(|cf_name| ColumnFamilyDescriptor::new(cf_name, cf_options_for(cf_name, &cf_opts, config, block_cache.as_ref(), version_gc_block)))
+        .collect())
+}
+
+/// Build the Column Family descriptors to open the database with.
+///
+/// Covers every required Column Family plus any other Column Family already
+/// present on disk, since RocksDB refuses to open a database without all of
+/// its Column Families.
+///
+/// # Arguments
+/// * `path` - Path to the RocksDB database
+/// * `db_opts` - Database options
+/// * `config` - Path provider configuration
+/// * `version_gc_block` - Block the version GC compaction filter follows
+fn column_family_descriptors(
+    path: &str,
+    db_opts: &Options,
+    config: &PathProviderConfig,
+    version_gc_block: &Arc<AtomicU64>,
+) -> PathProviderResult<Vec<ColumnFamilyDescriptor>> {
+    // A missing database has no Column Families yet
+    let existing_cfs = if Path::new(path).join("CURRENT").exists() {
+        DB::list_cf(db_opts, path).map_err(|e| PathProviderError::rocksdb("Failed to list Column Families", e))?
+    } else {
+        Vec::new()
+    };
+
+    // One block cache shared by all Column Families
+    let block_cache = config.block_cache_size.map(Cache::new_lru_cache);
+    let cf_opts = column_family_options(config, block_cache.as_ref());
 
-    // Find missing Column Families
     let missing_cfs: Vec<&str> = COLUMN_FAMILY_NAMES
         .iter()
-        .filter(|&&cf_name| !existing_cfs_set.contains(cf_name))
+        .filter(|&&cf_name| !existing_cfs.iter().any(|existing| existing == cf_name))
         .copied()
         .collect();
-
-    // If no missing CFs, we're done
-    if missing_cfs.is_empty() {
-        trace!(
-            target: "pathdb::rocksdb",
-            "All required Column Families already exist"
-        );
-        return Ok(());
-    }
-
-    trace!(
-        target: "pathdb::rocksdb",
-        "Found {} missing Column Families: {:?}",
-        missing_cfs.len(),
-        missing_cfs
-    );
-
-    // Open database with existing CFs first
-    let mut existing_cf_descriptors = Vec::new();
-    for cf_name in &existing_cfs {
-        let mut cf_opts = Options::default();
-        cf_opts.set_max_write_buffer_number(config.max_write_buffer_number);
-        cf_opts.set_write_buffer_size(config.write_buffer_size);
-        existing_cf_descriptors.push(ColumnFamilyDescriptor::new(cf_name, cf_opts));
-    }
-
-    let temp_db = DB::open_cf_descriptors(db_opts, path, existing_cf_descriptors)
-        .map_err(|e| PathProviderError::Database(format!("Failed to open RocksDB: {}", e)))?;
-
-    // Create missing Column Families
-    for cf_name in missing_cfs {
-        let mut cf_opts = Options::default();
-        cf_opts.set_max_write_buffer_number(config.max_write_buffer_number);
-        cf_opts.set_write_buffer_size(config.write_buffer_size);
-        temp_db.create_cf(cf_name, &cf_opts).map_err(|e| {
-            PathProviderError::Database(format!(
-                "Failed to create Column Family '{}': {}",
-                cf_name, e
-            ))
-        })?;
+    if !missing_cfs.is_empty() {
         trace!(
             target: "pathdb::rocksdb",
-            "Created Column Family '{}'",
-            cf_name
+            "Creating {} missing Column Families: {:?}",
+            missing_cfs.len(),
+            missing_cfs
         );
     }
-    // Drop temp_db to close it before reopening with all CFs
-    drop(temp_db);
 
-    Ok(())
+    let extra_cfs = existing_cfs
+        .iter()
+        .filter(|existing| !COLUMN_FAMILY_NAMES.contains(&existing.as_str()))
+        .map(String::as_str);
+    Ok(COLUMN_FAMILY_NAMES
+        .iter()
+        .copied()
+        .chain(extra_cfs)
+        .map(|cf_name| ColumnFamilyDescriptor::new(cf_name, cf_options_for(cf_name, &cf_opts, config, block_cache.as_ref(), version_gc_block)))
+        .collect())
+}
+
+/// RocksDB write options for `durability`.
+fn write_options(durability: WriteDurability) -> WriteOptions {
+    let mut write_options = WriteOptions::default();
+    write_options.disable_wal(durability.disable_wal);
+    write_options.set_sync(durability.sync);
+    write_options
+}
+
+/// Read options of point reads.
+fn point_read_options(config: &PathProviderConfig) -> ReadOptions {
+    let mut read_options = ReadOptions::default();
+    read_options.fill_cache(config.fill_cache);
+    read_options.set_readahead_size(config.readahead_size);
+    read_options.set_async_io(config.async_io);
+    read_options.set_verify_checksums(config.verify_checksums);
+    read_options
+}
+
+/// Read options of verified reads: the point read options with checksum
+/// verification forced on and their own readahead.
+fn verified_read_options(config: &PathProviderConfig) -> ReadOptions {
+    let mut read_options = point_read_options(config);
+    read_options.set_readahead_size(config.verified_readahead_size);
+    read_options.set_verify_checksums(true);
+    read_options
+}
+
+/// Write options of maintenance writes: the configured durability, at low
+/// priority if enabled.
+fn maintenance_write_options(config: &PathProviderConfig) -> WriteOptions {
+    let mut write_options = write_options(config.write_durability());
+    write_options.set_low_pri(config.maintenance_low_pri);
+    write_options
+}
+
+/// Database-wide options of a PathDB opened, or repaired, with `config`.
+pub(crate) fn database_options(config: &PathProviderConfig) -> Options {
+    let mut db_opts = Options::default();
+    db_opts.set_max_open_files(config.max_open_files);
+    db_opts.set_write_buffer_size(config.write_buffer_size);
+    db_opts.set_max_write_buffer_number(config.max_write_buffer_number);
+    db_opts.set_target_file_size_base(config.target_file_size_base);
+    db_opts.set_max_background_jobs(config.max_background_jobs);
+    db_opts.set_max_subcompactions(config.max_subcompactions);
+    if let Some(rate_bytes_per_sec) = config.rate_limit_bytes_per_sec {
+        if config.rate_limit_auto_tuned {
+            db_opts.set_auto_tuned_ratelimiter(rate_bytes_per_sec, RATE_LIMIT_REFILL_PERIOD_US, RATE_LIMIT_FAIRNESS);
+        } else {
+            db_opts.set_ratelimiter(rate_bytes_per_sec, RATE_LIMIT_REFILL_PERIOD_US, RATE_LIMIT_FAIRNESS);
+        }
+    }
+    db_opts.create_if_missing(config.create_if_missing);
+    db_opts.set_paranoid_checks(config.paranoid_checks);
+    db_opts.set_use_direct_reads(config.use_direct_reads);
+    db_opts.set_use_direct_io_for_flush_and_compaction(config.use_direct_io_for_flush_and_compaction);
+    db_opts.set_compaction_readahead_size(config.compaction_readahead_size);
+    if config.enable_statistics {
+        db_opts.enable_statistics();
+    }
+    db_opts
+}
+
+/// Options shared by all Column Families.
+fn column_family_options(config: &PathProviderConfig, block_cache: Option<&Cache>) -> Options {
+    let mut cf_opts = Options::default();
+    cf_opts.set_max_write_buffer_number(config.max_write_buffer_number);
+    cf_opts.set_write_buffer_size(config.write_buffer_size);
+    cf_opts.set_block_based_table_factory(&block_based_options(config, config.bloom_filter_bits_per_key, block_cache));
+    cf_opts.set_compaction_pri(db_compaction_pri(config.compaction_priority));
+    set_compaction_style(&mut cf_opts, config.compaction_style);
+
+    let compression = &config.compression;
+    if !compression.per_level.is_empty() {
+        let per_level: Vec<DBCompressionType> = compression.per_level.iter().copied().map(db_compression_type).collect();
+        cf_opts.set_compression_per_level(&per_level);
+    }
+    if let Some(bottommost) = compression.bottommost {
+        cf_opts.set_bottommost_compression_type(db_compression_type(bottommost));
+    }
+    if compression.zstd_max_dict_bytes > 0 {
+        // Default window bits, level and strategy of RocksDB
+        cf_opts.set_bottommost_compression_options(-14, 32767, 0, compression.zstd_max_dict_bytes as i32, true);
+        cf_opts.set_bottommost_zstd_max_train_bytes(compression.zstd_max_train_bytes as i32, true);
+    }
+    cf_opts
+}
+
+fn set_compaction_style(cf_opts: &mut Options, style: CompactionStyle) {
+    match style {
+        CompactionStyle::Level => cf_opts.set_compaction_style(DBCompactionStyle::Level),
+        CompactionStyle::Universal { size_ratio, max_size_amplification_percent } => {
+            let mut universal = UniversalCompactOptions::default();
+            universal.set_size_ratio(size_ratio as i32);
+            universal.set_max_size_amplification_percent(max_size_amplification_percent as i32);
+            cf_opts.set_compaction_style(DBCompactionStyle::Universal);
+            cf_opts.set_universal_compaction_options(&universal);
+        }
+        CompactionStyle::Fifo { max_table_files_size } => {
+            let mut fifo = FifoCompactOptions::default();
+            fifo.set_max_table_files_size(max_table_files_size);
+            cf_opts.set_compaction_style(DBCompactionStyle::Fifo);
+            cf_opts.set_fifo_compaction_options(&fifo);
+        }
+    }
+}
+
+fn db_compaction_pri(priority: CompactionPriority) -> DBCompactionPri {
+    match priority {
+        CompactionPriority::ByCompensatedSize => DBCompactionPri::ByCompensatedSize,
+        CompactionPriority::OldestLargestSeqFirst => DBCompactionPri::OldestLargestSeqFirst,
+        CompactionPriority::OldestSmallestSeqFirst => DBCompactionPri::OldestSmallestSeqFirst,
+        CompactionPriority::MinOverlappingRatio => DBCompactionPri::MinOverlappingRatio,
+        CompactionPriority::RoundRobin => DBCompactionPri::RoundRobin,
+    }
+}
+
+fn db_compression_type(compression: CompressionType) -> DBCompressionType {
+    match compression {
+        CompressionType::None => DBCompressionType::None,
+        CompressionType::Lz4 => DBCompressionType::Lz4,
+        CompressionType::Zstd => DBCompressionType::Zstd,
+    }
+}
+
+/// Block-based table options of all Column Families, with bloom filters of
+/// `bloom_filter_bits_per_key`.
+fn block_based_options(config: &PathProviderConfig, bloom_filter_bits_per_key: Option<f64>, block_cache: Option<&Cache>) -> BlockBasedOptions {
+    let mut block_opts = BlockBasedOptions::default();
+    if let Some(block_cache) = block_cache {
+        block_opts.set_block_cache(block_cache);
+    }
+    if let Some(bits_per_key) = bloom_filter_bits_per_key {
+        block_opts.set_bloom_filter(bits_per_key, false);
+    }
+    block_opts.set_cache_index_and_filter_blocks(config.cache_index_and_filter_blocks);
+    block_opts.set_pin_l0_filter_and_index_blocks_in_cache(config.pin_l0_filter_and_index_blocks_in_cache);
+    block_opts
 }
 
+/// Options of Column Family `cf_name`, applying its overrides, adding blob
+/// files to the trie node Column Families, the prefix extractor and prefix
+/// bloom filters to the trie node Column Family and the version GC
+/// compaction filter to versioned Column Families.
+fn cf_options_for(
+    cf_name: &str,
+    cf_opts: &Options,
+    config: &PathProviderConfig,
+    block_cache: Option<&Cache>,
+    version_gc_block: &Arc<AtomicU64>,
+) -> Options {
+    let mut cf_opts = cf_opts.clone();
+    let overrides = config.cf_overrides.get(cf_name);
+    let bloom_filter_bits_per_key = overrides
+        .and_then(|overrides| overrides.bloom_filter_bits_per_key)
+        .or(config.bloom_filter_bits_per_key);
+    if let Some(overrides) = overrides {
+        if let Some(write_buffer_size) = overrides.write_buffer_size {
+            cf_opts.set_write_buffer_size(write_buffer_size);
+        }
+        if let Some(max_write_buffer_number) = overrides.max_write_buffer_number {
+            cf_opts.set_max_write_buffer_number(max_write_buffer_number);
+        }
+        if let Some(target_file_size_base) = overrides.target_file_size_base {
+            cf_opts.set_target_file_size_base(target_file_size_base);
+        }
+        if let Some(trigger) = overrides.level0_file_num_compaction_trigger {
+            cf_opts.set_level_zero_file_num_compaction_trigger(trigger);
+        }
+        if let Some(compaction_style) = overrides.compaction_style {
+            set_compaction_style(&mut cf_opts, compaction_style);
+        }
+        if overrides.bloom_filter_bits_per_key.is_some() {
+            cf_opts.set_block_based_table_factory(&block_based_options(config, bloom_filter_bits_per_key, block_cache));
+        }
+    }
+    if let Some(blob_files) = &config.blob_files {
+        if matches!(cf_name, DEFAULT_COLUMN_FAMILY_NAME | TRIE_NODE_COLUMN_FAMILY_NAME | COLD_TRIE_NODE_COLUMN_FAMILY_NAME) {
+            cf_opts.set_enable_blob_files(true);
+            cf_opts.set_min_blob_size(blob_files.min_blob_size);
+            cf_opts.set_blob_file_size(blob_files.blob_file_size);
+            cf_opts.set_blob_compression_type(db_compression_type(blob_files.compression));
+            cf_opts.set_enable_blob_gc(blob_files.enable_gc);
+            cf_opts.set_blob_gc_age_cutoff(blob_files.gc_age_cutoff);
+        }
+    }
+    if cf_name == COLD_TRIE_NODE_COLUMN_FAMILY_NAME {
+        let compression = db_compression_type(config.cold_compression);
+        cf_opts.set_compression_type(compression);
+        cf_opts.set_compression_per_level(&[]);
+        cf_opts.set_bottommost_compression_type(compression);
+    }
+    if let (DEFAULT_COLUMN_FAMILY_NAME, Some(prefix_len)) = (cf_name, config.prefix_extractor.prefix_len()) {
+        let namespace_len = config.key_namespace.as_ref().map_or(0, |namespace| 1 + namespace.len());
+        cf_opts.set_prefix_extractor(SliceTransform::create_fixed_prefix(namespace_len + prefix_len));
+        cf_opts.set_memtable_prefix_bloom_ratio(0.1);
+
+        // Prefix seeks need a bloom filter even if point lookups don't use one
+        let mut block_opts = block_based_options(config, bloom_filter_bits_per_key, block_cache);
+        block_opts.set_bloom_filter(bloom_filter_bits_per_key.unwrap_or(DEFAULT_PREFIX_BLOOM_BITS_PER_KEY), false);
+        block_opts.set_whole_key_filtering(true);
+        cf_opts.set_block_based_table_factory(&block_opts);
+    }
+    set_version_gc_filter(&mut cf_opts, cf_name, config, version_gc_block);
+    cf_opts
+}
+
+/// Size of the LRU cache in front of Column Family `cf_name`, `default`
+/// unless overridden.
+fn cf_cache_size(config: &PathProviderConfig, cf_name: &str, default: u32) -> u32 {
+    config.cf_overrides.get(cf_name).and_then(|overrides| overrides.cache_size).unwrap_or(default)
+}
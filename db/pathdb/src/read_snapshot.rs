@@ -0,0 +1,235 @@
+//! Reads of a PathDB pinned to a RocksDB snapshot.
+
+use std::sync::Arc;
+
+use alloy_primitives::{Bytes, B256};
+use alloy_trie::EMPTY_ROOT_HASH;
+use rocksdb::{ReadOptions, SnapshotWithThreadMode, DB};
+use rust_eth_triedb_common::{DiffLayer, TrieDatabase, TRIE_STATE_BLOCK_NUMBER_KEY, TRIE_STATE_ROOT_KEY};
+use tracing::warn;
+
+use crate::pathdb::{
+    decode_overflow_pointer, overflow_chunk_key, PathDB, ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, COLD_TRIE_NODE_COLUMN_FAMILY_NAME,
+    DEFAULT_COLUMN_FAMILY_NAME, DELETION_QUEUE_COLUMN_FAMILY_NAME, OVERFLOW_COLUMN_FAMILY_NAME, STORAGE_ROOT_COLUMN_FAMILY_NAME, STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME,
+};
+use crate::traits::*;
+
+/// Read-only view of a PathDB at the moment [`PathDB::read_snapshot`] was
+/// called.
+///
+/// Reads are isolated from later commits, so a TrieDB built on this handle
+/// serves RPC reads from one consistent state while `commit_difflayer` keeps
+/// writing to the same keys. The caches of the database follow the latest
+/// state, so they serve reads only while nothing was written since the
+/// snapshot was taken; afterwards reads go to the snapshot, where trie nodes
+/// waiting in the deletion queue read as deleted. Writes fail, and the
+/// snapshot pins the files and memtables it reads until every clone of the
+/// handle is dropped.
+#[derive(Clone)]
+pub struct PathDBSnapshot<'a> {
+    db: &'a PathDB,
+    snapshot: Arc<SnapshotWithThreadMode<'a, DB>>,
+    /// RocksDB sequence number when the snapshot was taken
+    sequence: u64,
+}
+
+impl std::fmt::Debug for PathDBSnapshot<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PathDBSnapshot").field("db", self.db).finish()
+    }
+}
+
+/// Read snapshots
+impl PathDB {
+    /// Take a snapshot of the database and return a read-only handle bound to it.
+    pub fn read_snapshot(&self) -> PathDBSnapshot<'_> {
+        // Taken first, so a write landing in between makes the snapshot stale rather than current
+        let sequence = self.raw_db().latest_sequence_number();
+        PathDBSnapshot { db: self, snapshot: Arc::new(self.raw_db().snapshot()), sequence }
+    }
+}
+
+impl<'a> PathDBSnapshot<'a> {
+    /// Get a trie node as of the snapshot.
+    pub fn get_raw_trie_node(&self, key: &[u8]) -> PathProviderResult<Option<Bytes>> {
+        if let Some(value) = self.read_current(|db| db.get_raw_trie_node(key))? {
+            return Ok(value);
+        }
+        let db_key = self.db.db_key(key);
+        if self.is_deletion_queued(&db_key)? {
+            return Ok(None);
+        }
+        let value = match self.get_cf(DEFAULT_COLUMN_FAMILY_NAME, &db_key)? {
+            Some(value) => Some(value),
+            None if self.db.config().cold_storage => self.get_cf(COLD_TRIE_NODE_COLUMN_FAMILY_NAME, &db_key)?,
+            None => None,
+        };
+        value.map(|value| self.resolve_overflow(&db_key, value).map(Bytes::from)).transpose()
+    }
+
+    /// Get a storage root as of the snapshot.
+    pub fn get_raw_storage_root(&self, key: &[u8]) -> PathProviderResult<Option<Bytes>> {
+        if let Some(value) = self.read_current(|db| db.get_raw_storage_root(key))? {
+            return Ok(value);
+        }
+        Ok(self.get_cf(STORAGE_ROOT_COLUMN_FAMILY_NAME, &self.db.db_key(key))?.map(Bytes::from))
+    }
+
+    /// Get a flat account snapshot entry as of the snapshot.
+    pub fn get_raw_account_snapshot(&self, hashed_address: B256) -> PathProviderResult<Option<Bytes>> {
+        Ok(self.get_cf(ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, &self.db.db_key(hashed_address.as_slice()))?.map(Bytes::from))
+    }
+
+    /// Get a flat storage snapshot entry as of the snapshot.
+    pub fn get_raw_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> PathProviderResult<Option<Bytes>> {
+        let key = [hashed_address.as_slice(), hashed_key.as_slice()].concat();
+        Ok(self.get_cf(STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME, &self.db.db_key(&key))?.map(Bytes::from))
+    }
+
+    /// Get a meta data value as of the snapshot.
+    pub fn get_raw_meta_data(&self, key: &[u8]) -> PathProviderResult<Option<Vec<u8>>> {
+        if let Some(value) = self.read_current(|db| db.get_raw_meta_data(key))? {
+            return Ok(value);
+        }
+        // Meta data still lives in the default column family, like `PathDB::get_raw_meta_data`
+        self.get_cf(DEFAULT_COLUMN_FAMILY_NAME, &self.db.db_key(key))
+    }
+
+    /// The underlying RocksDB snapshot, e.g. to iterate as of the snapshot.
+    pub(crate) fn raw_snapshot(&self) -> &SnapshotWithThreadMode<'a, DB> {
+        &self.snapshot
+    }
+
+    /// Whether nothing was written to the database since the snapshot was taken.
+    fn is_current(&self) -> bool {
+        self.db.raw_db().latest_sequence_number() == self.sequence
+    }
+
+    /// Read through the database and its caches while the snapshot is
+    /// current, `None` if a write landed before or during the read.
+    ///
+    /// Writes update the caches only once they landed, so a read finishing
+    /// before the sequence number moves saw the state of the snapshot.
+    fn read_current<T>(&self, read: impl FnOnce(&PathDB) -> PathProviderResult<T>) -> PathProviderResult<Option<T>> {
+        if !self.is_current() {
+            return Ok(None);
+        }
+        let value = read(self.db)?;
+        Ok(self.is_current().then_some(value))
+    }
+
+    /// Whether the trie node at `db_key` waited in the deletion queue as of
+    /// the snapshot, so its blob on disk is stale.
+    fn is_deletion_queued(&self, db_key: &[u8]) -> PathProviderResult<bool> {
+        if !self.db.config().deferred_deletion {
+            return Ok(false);
+        }
+        Ok(self.get_cf(DELETION_QUEUE_COLUMN_FAMILY_NAME, db_key)?.is_some())
+    }
+
+    fn get_cf(&self, cf_name: &str, db_key: &[u8]) -> PathProviderResult<Option<Vec<u8>>> {
+        let cf = self.db.raw_db().cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+        self.snapshot.get_cf_opt(&cf, db_key, self.read_options())
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB snapshot get in CF '{}' error", cf_name), e))
+    }
+
+    /// Reassemble a value from its overflow chunks as of the snapshot.
+    fn resolve_overflow(&self, db_key: &[u8], value: Vec<u8>) -> PathProviderResult<Vec<u8>> {
+        let Some((total_len, chunk_count)) = decode_overflow_pointer(&value) else {
+            return Ok(value);
+        };
+
+        let mut resolved = Vec::with_capacity(total_len);
+        for index in 0..chunk_count {
+            let chunk = self.get_cf(OVERFLOW_COLUMN_FAMILY_NAME, &overflow_chunk_key(db_key, index))?
+                .ok_or_else(|| PathProviderError::Deserialization(format!("Missing overflow chunk {} of {}", index, chunk_count)))?;
+            resolved.extend_from_slice(&chunk);
+        }
+
+        if resolved.len() != total_len {
+            return Err(PathProviderError::Deserialization(format!(
+                "Overflow value length mismatch: expected {}, got {}", total_len, resolved.len()
+            )));
+        }
+        Ok(resolved)
+    }
+
+    /// Point-read options matching the database configuration.
+    fn read_options(&self) -> ReadOptions {
+        let config = self.db.config();
+        let mut read_options = ReadOptions::default();
+        read_options.fill_cache(config.fill_cache);
+        read_options.set_readahead_size(config.readahead_size);
+        read_options.set_async_io(config.async_io);
+        read_options.set_verify_checksums(config.verify_checksums);
+        read_options
+    }
+
+    fn read_only_error() -> PathProviderError {
+        PathProviderError::InvalidOperation("Read snapshots are read-only".to_string())
+    }
+}
+
+impl TrieDatabase for PathDBSnapshot<'_> {
+    type Error = PathProviderError;
+
+    fn get_trie_node(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        self.get_raw_trie_node(path)
+    }
+
+    fn insert_trie_node(&self, _path: &[u8], _data: Vec<u8>) -> Result<(), Self::Error> {
+        Err(Self::read_only_error())
+    }
+
+    fn contains_trie_node(&self, path: &[u8]) -> Result<bool, Self::Error> {
+        Ok(self.get_raw_trie_node(path)?.is_some())
+    }
+
+    fn remove_trie_node(&self, path: &[u8]) {
+        warn!(target: "pathdb::rocksdb", "Ignoring removal of trie node {:?} from a read snapshot", path);
+    }
+
+    fn get_storage_root(&self, hased_address: B256) -> Result<Option<B256>, Self::Error> {
+        let value = self.get_raw_storage_root(hased_address.as_slice())?;
+        Ok(value.filter(|value| value.len() == 32).map(|value| B256::from_slice(&value)))
+    }
+
+    fn commit_difflayer(&self, _block_number: u64, _state_root: B256, _difflayer: &Option<Arc<DiffLayer>>) -> Result<(), Self::Error> {
+        Err(Self::read_only_error())
+    }
+
+    fn latest_persist_state(&self) -> Result<(u64, B256), Self::Error> {
+        let block_number = self.get_raw_meta_data(TRIE_STATE_BLOCK_NUMBER_KEY)?;
+        let state_root = self.get_raw_meta_data(TRIE_STATE_ROOT_KEY)?;
+        match (block_number, state_root) {
+            (Some(block_number), Some(state_root)) => {
+                let block_number: [u8; 8] = block_number.as_slice().try_into().map_err(|_| {
+                    PathProviderError::Deserialization(format!("Invalid persisted block number of {} bytes", block_number.len()))
+                })?;
+                if state_root.len() != B256::len_bytes() {
+                    return Err(PathProviderError::Deserialization(format!("Invalid persisted state root of {} bytes", state_root.len())));
+                }
+                Ok((u64::from_le_bytes(block_number), B256::from_slice(&state_root)))
+            }
+            _ => Ok((0, EMPTY_ROOT_HASH)),
+        }
+    }
+
+    fn clear_cache(&self) {}
+
+    fn get_account_snapshot(&self, hashed_address: B256) -> Result<Option<Bytes>, Self::Error> {
+        self.get_raw_account_snapshot(hashed_address)
+    }
+
+    fn put_account_snapshot(&self, _hashed_address: B256, _account: Option<&[u8]>) -> Result<(), Self::Error> {
+        Err(Self::read_only_error())
+    }
+
+    fn get_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<Bytes>, Self::Error> {
+        self.get_raw_storage_snapshot(hashed_address, hashed_key)
+    }
+
+    fn put_storage_snapshot(&self, _hashed_address: B256, _hashed_key: B256, _value: Option<&[u8]>) -> Result<(), Self::Error> {
+        Err(Self::read_only_error())
+    }
+}
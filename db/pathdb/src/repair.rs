@@ -0,0 +1,149 @@
+//! Corruption detection and recovery of a PathDB.
+
+use std::path::Path;
+
+use rocksdb::{ReadOptions, DB};
+use tracing::{info, warn};
+
+use crate::pathdb::{database_options, PathDB};
+use crate::traits::*;
+
+/// A key range of a column family that could not be read, see
+/// [`PathDB::verify_checksums_full`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CorruptedRange {
+    /// Column family name
+    pub column_family: String,
+    /// Last key read before the range, `None` from the start of the column family
+    pub after: Option<Vec<u8>>,
+    /// First key read after the range, `None` up to the end of the column family
+    pub before: Option<Vec<u8>>,
+    /// Error reported by the first failed read of the range
+    pub error: String,
+}
+
+/// Result of a full checksum scan, see [`PathDB::verify_checksums_full`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ChecksumReport {
+    /// Number of keys read with verified checksums
+    pub keys_verified: u64,
+    /// Unreadable key ranges, per column family in key order
+    pub corrupted_ranges: Vec<CorruptedRange>,
+}
+
+impl ChecksumReport {
+    /// Whether no corruption was found.
+    pub fn is_clean(&self) -> bool {
+        self.corrupted_ranges.is_empty()
+    }
+}
+
+/// Repair and verification
+impl PathDB {
+    /// Repair the database at `path` after a crash or power loss left it
+    /// unopenable, e.g. with a corrupted MANIFEST or SST file.
+    ///
+    /// `config` is the configuration the database is opened with, so the
+    /// repair runs with the same options as [`PathDB::new`]. The database
+    /// must not be open. RocksDB rebuilds the MANIFEST from the
+    /// table and WAL files it can still read, so corrupted data and possibly
+    /// the latest writes are lost: reopen the database with [`PathDB::new`],
+    /// check its persisted state and run [`PathDB::verify_checksums_full`]
+    /// before using it again.
+    pub fn repair(path: &str, config: &PathProviderConfig) -> PathProviderResult<()> {
+        if !Path::new(path).exists() {
+            return Err(PathProviderError::InvalidOperation(format!("Database {} does not exist", path)));
+        }
+
+        warn!(target: "pathdb::repair", "Repairing database at {}", path);
+        DB::repair(&database_options(config), path)
+            .map_err(|e| PathProviderError::rocksdb(format!("Failed to repair database at {}", path), e))?;
+        info!(target: "pathdb::repair", "Repaired database at {}", path);
+        Ok(())
+    }
+
+    /// Read every key of every column family with block checksum
+    /// verification and report the ranges that could not be read.
+    ///
+    /// A failed read is resumed at the next SST file boundary of the column
+    /// family, so one corrupted block doesn't hide the state of the rest of
+    /// the database. Keys are reported as stored, including any key
+    /// namespace, and the scan covers the keys of every namespace sharing the
+    /// database. Reads bypass the block cache.
+    pub fn verify_checksums_full(&self) -> PathProviderResult<ChecksumReport> {
+        let live_files = self.raw_db().live_files()
+            .map_err(|e| PathProviderError::rocksdb("Failed to list live files", e))?;
+
+        let mut report = ChecksumReport::default();
+        for cf_name in self.column_families() {
+            let mut boundaries: Vec<Vec<u8>> = live_files
+                .iter()
+                .filter(|file| file.column_family_name == cf_name)
+                .flat_map(|file| [file.start_key.clone(), file.end_key.clone()])
+                .flatten()
+                .collect();
+            boundaries.sort();
+            boundaries.dedup();
+            self.verify_column_family(&cf_name, &boundaries, &mut report)?;
+        }
+
+        if !report.is_clean() {
+            warn!(
+                target: "pathdb::repair",
+                "Checksum scan found {} corrupted ranges after verifying {} keys",
+                report.corrupted_ranges.len(), report.keys_verified
+            );
+        }
+        Ok(report)
+    }
+
+    fn verify_column_family(&self, cf_name: &str, boundaries: &[Vec<u8>], report: &mut ChecksumReport) -> PathProviderResult<()> {
+        let cf = self.raw_db().cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))?;
+        let mut read_options = ReadOptions::default();
+        read_options.set_verify_checksums(true);
+        read_options.fill_cache(false);
+        read_options.set_readahead_size(self.config().scan_readahead_size);
+        read_options.set_total_order_seek(true);
+
+        let mut iter = self.raw_db().raw_iterator_cf_opt(&cf, read_options);
+        iter.seek_to_first();
+        // Furthest key read or seeked to, resumes only move past it
+        let mut position: Option<Vec<u8>> = None;
+        let mut last_read: Option<Vec<u8>> = None;
+        let mut corrupted: Option<CorruptedRange> = None;
+        loop {
+            if let Some(key) = iter.key() {
+                let key = key.to_vec();
+                if let Some(mut range) = corrupted.take() {
+                    range.before = Some(key.clone());
+                    report.corrupted_ranges.push(range);
+                }
+                report.keys_verified += 1;
+                position = Some(key.clone());
+                last_read = Some(key);
+                iter.next();
+                continue;
+            }
+
+            let Err(e) = iter.status() else {
+                break;
+            };
+            corrupted.get_or_insert_with(|| CorruptedRange {
+                column_family: cf_name.to_string(),
+                after: last_read.clone(),
+                before: None,
+                error: e.to_string(),
+            });
+            let next = boundaries.iter().find(|boundary| position.as_ref().map_or(true, |position| *boundary > position));
+            match next {
+                Some(boundary) => {
+                    iter.seek(boundary);
+                    position = Some(boundary.clone());
+                }
+                None => break,
+            }
+        }
+        report.corrupted_ranges.extend(corrupted);
+        Ok(())
+    }
+}
@@ -0,0 +1,320 @@
+//! PathDB split across several RocksDB instances.
+
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::sync::Arc;
+
+use alloy_primitives::{Bytes, B256};
+use rust_eth_triedb_common::{AuditRecord, CancellationToken, DiffLayer, TrieDatabase, TRIE_NODE_STORAGE_PREFIX};
+use tracing::{info, warn};
+
+use crate::commit_limits::check_commit_limits;
+use crate::pathdb::PathDB;
+use crate::traits::*;
+
+/// Name of the file recording the shard count of a sharded database.
+const SHARD_COUNT_FILE: &str = "SHARDS";
+
+/// A PathDB whose keyspace is split by owner hash across several RocksDB
+/// instances.
+///
+/// Each shard is a full [`PathDB`] in its own directory with its own caches,
+/// memtables and compaction threads, so write throughput scales past the
+/// point where a single RocksDB instance saturates. Storage trie nodes,
+/// storage roots, storage slot counts and flat account snapshot entries live
+/// in the shard of their owner; the account trie, the persisted state, the
+/// audit log and the code hash index live in shard 0.
+///
+/// A difflayer commit writes one batch per shard, shard 0 last. The commit
+/// is atomic per shard only: after a crash mid-commit, shards other than 0
+/// may be ahead of the state reported by
+/// [`latest_persist_state`](TrieDatabase::latest_persist_state).
+#[derive(Debug, Clone)]
+pub struct ShardedPathDB {
+    shards: Arc<[PathDB]>,
+}
+
+impl ShardedPathDB {
+    /// Open or create a database of `shard_count` shards under `path`, each
+    /// opened with `config`.
+    ///
+    /// The shard count is recorded on creation; opening an existing database
+    /// with a different count fails, as keys would be routed to the wrong shards.
+    pub fn new(path: &str, shard_count: usize, config: PathProviderConfig) -> PathProviderResult<Self> {
+        if shard_count == 0 {
+            return Err(PathProviderError::InvalidOperation("Shard count must be at least 1".to_string()));
+        }
+
+        let count_file = Path::new(path).join(SHARD_COUNT_FILE);
+        if count_file.exists() {
+            let recorded = fs::read_to_string(&count_file)?;
+            if recorded.trim().parse::<usize>().ok() != Some(shard_count) {
+                return Err(PathProviderError::InvalidOperation(format!(
+                    "Database at {} has {} shards, not {}",
+                    path, recorded.trim(), shard_count
+                )));
+            }
+        } else {
+            fs::create_dir_all(path)?;
+            fs::write(&count_file, shard_count.to_string())?;
+        }
+
+        let shards = (0..shard_count)
+            .map(|index| {
+                let shard_path = Path::new(path).join(format!("shard-{}", index));
+                let mut config = config.clone();
+                // Shards default to their own path; keep an explicit label apart per shard
+                config.metrics_instance = config.metrics_instance.map(|instance| format!("{}/shard-{}", instance, index));
+                PathDB::new(&shard_path.to_string_lossy(), config)
+            })
+            .collect::<PathProviderResult<Vec<_>>>()?;
+
+        let sharded = Self { shards: shards.into() };
+        sharded.check_persisted_states()?;
+        info!(target: "pathdb::sharded", "Opened sharded PathDB at {} with {} shards", path, shard_count);
+        Ok(sharded)
+    }
+
+    /// The shards, shard 0 first.
+    pub fn shards(&self) -> &[PathDB] {
+        &self.shards
+    }
+
+    /// Prune the storage root history of every shard, returning the number
+    /// of entries deleted, see [`PathDB::prune_storage_root_history`].
+    pub fn prune_storage_root_history(&self, keep_from_block: u64) -> PathProviderResult<u64> {
+        self.prune_storage_root_history_with_cancellation(keep_from_block, &CancellationToken::new())
+    }
+
+    /// Prune the storage root history of every shard until `cancel` is
+    /// cancelled, see [`PathDB::prune_storage_root_history_with_cancellation`].
+    pub fn prune_storage_root_history_with_cancellation(&self, keep_from_block: u64, cancel: &CancellationToken) -> PathProviderResult<u64> {
+        let mut deleted = 0;
+        for shard in self.shards.iter() {
+            if cancel.is_cancelled() {
+                break;
+            }
+            deleted += shard.prune_storage_root_history_with_cancellation(keep_from_block, cancel)?;
+        }
+        Ok(deleted)
+    }
+
+    /// Mark the storage roots of every shard complete as of `block_number`,
+    /// see [`PathDB::mark_storage_roots_complete`].
+    pub fn mark_storage_roots_complete(&self, block_number: u64) -> PathProviderResult<()> {
+        for shard in self.shards.iter() {
+            shard.mark_storage_roots_complete(block_number)?;
+        }
+        Ok(())
+    }
+
+    /// Index of the shard holding the trie node at `key`.
+    pub fn shard_index(&self, key: &[u8]) -> usize {
+        match key.strip_prefix(TRIE_NODE_STORAGE_PREFIX) {
+            Some(rest) if rest.len() >= B256::len_bytes() => self.owner_shard_index(&rest[..B256::len_bytes()]),
+            _ => 0,
+        }
+    }
+
+    /// Index of the shard holding the storage trie and storage root of `owner`.
+    pub fn owner_shard_index(&self, owner: &[u8]) -> usize {
+        let mut prefix = [0u8; 8];
+        let len = owner.len().min(prefix.len());
+        prefix[..len].copy_from_slice(&owner[..len]);
+        (u64::from_be_bytes(prefix) % self.shards.len() as u64) as usize
+    }
+
+    fn shard(&self, key: &[u8]) -> &PathDB {
+        &self.shards[self.shard_index(key)]
+    }
+
+    /// Split `difflayer` into one layer per shard. Deleted ranges go to every
+    /// shard, as a range may cover the keys of several owners.
+    fn split_difflayer(&self, difflayer: &DiffLayer) -> Vec<DiffLayer> {
+        let mut layers: Vec<DiffLayer> = (0..self.shards.len())
+            .map(|_| DiffLayer::new(HashMap::new(), HashMap::new()).with_deleted_ranges(difflayer.deleted_ranges.clone()))
+            .collect();
+        for (key, node) in difflayer.diff_nodes.iter() {
+            layers[self.shard_index(key)].diff_nodes.insert(key.clone(), node.clone());
+        }
+        for (owner, root) in difflayer.diff_storage_roots.iter() {
+            layers[self.owner_shard_index(owner.as_slice())].diff_storage_roots.insert(*owner, *root);
+        }
+        for (owner, change) in difflayer.slot_count_changes.iter() {
+            layers[self.owner_shard_index(owner.as_slice())].slot_count_changes.insert(*owner, *change);
+        }
+        layers[0].code_hashes = difflayer.code_hashes.clone();
+        layers[0].audit_record = difflayer.audit_record;
+        layers
+    }
+
+    /// Warn about shards whose persisted state differs from shard 0, e.g.
+    /// after a crash during a commit.
+    fn check_persisted_states(&self) -> PathProviderResult<()> {
+        let expected = self.shards[0].latest_persist_state()?;
+        for (index, shard) in self.shards.iter().enumerate().skip(1) {
+            let state = shard.latest_persist_state()?;
+            if state != expected {
+                warn!(
+                    target: "pathdb::sharded",
+                    "Shard {} persisted block {} root {:?}, shard 0 persisted block {} root {:?}",
+                    index, state.0, state.1, expected.0, expected.1
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+impl TrieDatabase for ShardedPathDB {
+    type Error = PathProviderError;
+
+    fn get_trie_node(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        self.shard(path).get_raw_trie_node(path)
+    }
+
+    fn get_trie_nodes(&self, paths: &[Vec<u8>]) -> Result<Vec<Option<Bytes>>, Self::Error> {
+        let mut positions: Vec<Vec<usize>> = vec![Vec::new(); self.shards.len()];
+        for (position, path) in paths.iter().enumerate() {
+            positions[self.shard_index(path)].push(position);
+        }
+
+        let mut nodes = vec![None; paths.len()];
+        for (shard, positions) in self.shards.iter().zip(positions) {
+            if positions.is_empty() {
+                continue;
+            }
+            let keys: Vec<&[u8]> = positions.iter().map(|&position| paths[position].as_slice()).collect();
+            for (position, node) in positions.into_iter().zip(shard.get_multi_raw_trie_nodes(&keys)?) {
+                nodes[position] = node;
+            }
+        }
+        Ok(nodes)
+    }
+
+    fn get_trie_node_verified(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        self.shard(path).get_verified_raw_trie_node(path)
+    }
+
+    fn insert_trie_node(&self, path: &[u8], data: Vec<u8>) -> Result<(), Self::Error> {
+        self.shard(path).put_raw_trie_node(path, &data)
+    }
+
+    fn contains_trie_node(&self, path: &[u8]) -> Result<bool, Self::Error> {
+        self.shard(path).exists_raw_trie_node(path)
+    }
+
+    fn remove_trie_node(&self, path: &[u8]) {
+        let _ = self.shard(path).delete_raw_trie_node(path);
+    }
+
+    fn iter_trie_node_range(&self, start: &[u8], end: Option<&[u8]>, limit: usize) -> Result<Vec<(Vec<u8>, Bytes)>, Self::Error> {
+        // The first `limit` nodes of the range are among the first `limit` of their shard
+        let mut nodes = Vec::new();
+        for shard in &self.shards {
+            nodes.extend(TrieDatabase::iter_trie_node_range(shard, start, end, limit)?);
+        }
+        nodes.sort_unstable_by(|(a, _), (b, _)| a.cmp(b));
+        nodes.truncate(limit);
+        Ok(nodes)
+    }
+
+    fn get_storage_root(&self, hased_address: B256) -> Result<Option<B256>, Self::Error> {
+        self.shards[self.owner_shard_index(hased_address.as_slice())].get_storage_root(hased_address)
+    }
+
+    fn commit_difflayer(&self, block_number: u64, state_root: B256, difflayer: &Option<Arc<DiffLayer>>) -> Result<(), Self::Error> {
+        // Checked once on the whole layer before any shard writes, so a
+        // rejected commit writes nothing
+        if let Some(difflayer) = difflayer {
+            check_commit_limits(&self.shards[0].config().commit_limits, block_number, difflayer, |owner| self.get_storage_root(owner))?;
+        }
+        let layers = match difflayer {
+            Some(difflayer) => self.split_difflayer(difflayer).into_iter().map(|layer| Some(Arc::new(layer))).collect(),
+            None => vec![None; self.shards.len()],
+        };
+        // Shard 0 reports the persisted state, commit it last. The limits were
+        // checked on the whole layer, so the shards don't check their parts
+        for (shard, layer) in self.shards.iter().zip(layers).rev() {
+            shard.write_difflayer(block_number, state_root, &layer, shard.config().write_durability())?;
+        }
+        Ok(())
+    }
+
+    fn latest_persist_state(&self) -> Result<(u64, B256), Self::Error> {
+        self.shards[0].latest_persist_state()
+    }
+
+    fn persist_generation(&self) -> Option<u64> {
+        self.shards[0].persist_generation()
+    }
+
+    fn clear_cache(&self) {
+        for shard in self.shards.iter() {
+            shard.clear_cache();
+        }
+    }
+
+    fn put_audit_record(&self, record: &AuditRecord) -> Result<(), Self::Error> {
+        self.shards[0].put_audit_record(record)
+    }
+
+    fn get_audit_records(&self, block_number: u64) -> Result<Vec<AuditRecord>, Self::Error> {
+        self.shards[0].get_audit_records(block_number)
+    }
+
+    fn get_addresses_by_code_hash(&self, code_hash: B256) -> Result<Vec<B256>, Self::Error> {
+        self.shards[0].get_addresses_by_code_hash(code_hash)
+    }
+
+    fn get_storage_slot_count(&self, hashed_address: B256) -> Result<Option<u64>, Self::Error> {
+        self.shards[self.owner_shard_index(hashed_address.as_slice())].get_storage_slot_count(hashed_address)
+    }
+
+    fn get_storage_root_at(&self, hashed_address: B256, block_number: u64) -> Result<Option<B256>, Self::Error> {
+        self.shards[self.owner_shard_index(hashed_address.as_slice())].get_storage_root_at(hashed_address, block_number)
+    }
+
+    fn get_account_snapshot(&self, hashed_address: B256) -> Result<Option<Bytes>, Self::Error> {
+        self.shards[self.owner_shard_index(hashed_address.as_slice())].get_raw_account_snapshot(hashed_address)
+    }
+
+    fn put_account_snapshot(&self, hashed_address: B256, account: Option<&[u8]>) -> Result<(), Self::Error> {
+        TrieDatabase::put_account_snapshot(&self.shards[self.owner_shard_index(hashed_address.as_slice())], hashed_address, account)
+    }
+
+    fn get_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<Bytes>, Self::Error> {
+        self.shards[self.owner_shard_index(hashed_address.as_slice())].get_raw_storage_snapshot(hashed_address, hashed_key)
+    }
+
+    fn put_storage_snapshot(&self, hashed_address: B256, hashed_key: B256, value: Option<&[u8]>) -> Result<(), Self::Error> {
+        TrieDatabase::put_storage_snapshot(&self.shards[self.owner_shard_index(hashed_address.as_slice())], hashed_address, hashed_key, value)
+    }
+
+    fn iter_account_snapshot(&self, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        // Accounts are spread over all shards, merge the first `limit` of each
+        let mut entries = Vec::new();
+        for shard in self.shards.iter() {
+            entries.extend(shard.iter_account_snapshot(start_hash, limit)?);
+        }
+        entries.sort_unstable_by(|a, b| a.0.cmp(&b.0));
+        entries.truncate(limit);
+        Ok(entries)
+    }
+
+    fn iter_storage_snapshot(&self, hashed_address: B256, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        self.shards[self.owner_shard_index(hashed_address.as_slice())].iter_storage_snapshot(hashed_address, start_hash, limit)
+    }
+
+    fn shutdown(&self) -> Result<(), Self::Error> {
+        // Shut down every shard even if one fails
+        let mut result = Ok(());
+        for shard in self.shards.iter() {
+            if let Err(e) = TrieDatabase::shutdown(shard) {
+                result = result.and(Err(e));
+            }
+        }
+        result
+    }
+}
@@ -0,0 +1,336 @@
+//! Sharded LRU cache for trie nodes and storage roots.
+
+use std::collections::hash_map::DefaultHasher;
+use std::collections::BTreeSet;
+use std::hash::{Hash, Hasher};
+use std::ops::Bound;
+use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+use std::sync::{Mutex, MutexGuard};
+
+use alloy_primitives::Bytes;
+use schnellru::{ByLength, LruMap};
+
+/// One shard of a [`ShardedCache`]. Values are shared with readers, `None`
+/// caches a known absence.
+///
+/// Keeps an ordered index of its keys next to the LRU map, so removing a
+/// key range only visits the keys in it. Evictions go through the shard to
+/// keep the index in sync, the map never evicts on its own.
+pub(crate) struct CacheShard {
+    entries: LruMap<Vec<u8>, Option<Bytes>, ByLength>,
+    keys: BTreeSet<Vec<u8>>,
+    capacity: u32,
+}
+
+impl CacheShard {
+    fn new(capacity: u32) -> Self {
+        Self { entries: LruMap::new(ByLength::new(capacity)), keys: BTreeSet::new(), capacity }
+    }
+
+    fn peek(&self, key: &[u8]) -> Option<&Option<Bytes>> {
+        self.entries.peek(key)
+    }
+
+    fn len(&self) -> usize {
+        self.entries.len()
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &Option<Bytes>)> + '_ {
+        self.entries.iter()
+    }
+
+    fn insert(&mut self, key: Vec<u8>, value: Option<Bytes>) {
+        if self.entries.peek(&key).is_none() {
+            while self.entries.len() >= self.capacity as usize {
+                self.pop_oldest();
+            }
+            self.keys.insert(key.clone());
+        }
+        if !self.entries.insert(key.clone(), value) {
+            self.keys.remove(&key);
+        }
+    }
+
+    fn remove(&mut self, key: &[u8]) -> bool {
+        self.keys.remove(key);
+        self.entries.remove(key).is_some()
+    }
+
+    fn pop_oldest(&mut self) {
+        if let Some((key, _)) = self.entries.pop_oldest() {
+            self.keys.remove(&key);
+        }
+    }
+
+    fn set_capacity(&mut self, capacity: u32) {
+        self.capacity = capacity;
+        *self.entries.limiter_mut() = ByLength::new(capacity);
+        while self.entries.len() > capacity as usize {
+            self.pop_oldest();
+        }
+    }
+
+    fn clear(&mut self) {
+        self.entries.clear();
+        self.keys.clear();
+    }
+
+    /// Cached keys within `bounds`, in key order.
+    fn range<'a>(&'a self, bounds: (Bound<&'a [u8]>, Bound<&'a [u8]>)) -> impl Iterator<Item = &'a Vec<u8>> + 'a {
+        self.keys.range::<[u8], _>(bounds)
+    }
+
+    /// Remove the entries of `keys`, returning how many were removed.
+    fn remove_all(&mut self, keys: &[Vec<u8>]) -> usize {
+        keys.iter().filter(|key| self.remove(key)).count()
+    }
+}
+
+/// LRU cache split into independently locked shards keyed by key hash.
+///
+/// Point operations lock a single shard, so concurrent readers of different
+/// keys rarely contend. Each shard evicts on its own, the cache as a whole is
+/// only approximately LRU. Operations that must be atomic with respect to the
+/// whole cache take [`ShardedCache::lock_all`].
+///
+/// Values larger than the maximum value size are not admitted: inserting one
+/// drops the cached entry of its key instead, so a single huge node can't
+/// evict many small hot ones.
+pub(crate) struct ShardedCache {
+    shards: Box<[Mutex<CacheShard>]>,
+    /// Total capacity in entries, changed by [`ShardedCache::set_capacity`].
+    capacity: AtomicU32,
+    /// Size in bytes of the largest value admitted, `None` admits all values.
+    max_value_size: Option<usize>,
+    /// Inserts not admitted since the last [`ShardedCache::take_bypassed_inserts`].
+    bypassed_inserts: AtomicU64,
+    /// Lookups answered from the cache, see [`ShardedCache::lookup`].
+    hits: AtomicU64,
+    /// Lookups not answered from the cache.
+    misses: AtomicU64,
+}
+
+impl ShardedCache {
+    /// Create a cache of `capacity` entries split over `shard_count` shards.
+    pub(crate) fn new(capacity: u32, shard_count: usize) -> Self {
+        let shard_count = shard_count.max(1);
+        let shard_capacity = capacity.div_ceil(shard_count as u32).max(1);
+        let shards = (0..shard_count)
+            .map(|_| Mutex::new(CacheShard::new(shard_capacity)))
+            .collect();
+        Self {
+            shards,
+            capacity: AtomicU32::new(capacity),
+            max_value_size: None,
+            bypassed_inserts: AtomicU64::new(0),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    /// Stop admitting values larger than `max_value_size` bytes.
+    pub(crate) fn with_max_value_size(mut self, max_value_size: Option<usize>) -> Self {
+        self.max_value_size = max_value_size;
+        self
+    }
+
+    /// Whether `value` fits the maximum value size, counting it otherwise.
+    fn admits(&self, value: &Option<Bytes>) -> bool {
+        let admitted = match (self.max_value_size, value) {
+            (Some(max_value_size), Some(value)) => value.len() <= max_value_size,
+            _ => true,
+        };
+        if !admitted {
+            self.bypassed_inserts.fetch_add(1, Ordering::Relaxed);
+        }
+        admitted
+    }
+
+    /// Number of inserts not admitted since the previous call.
+    pub(crate) fn take_bypassed_inserts(&self) -> u64 {
+        self.bypassed_inserts.swap(0, Ordering::Relaxed)
+    }
+
+    fn shard(&self, key: &[u8]) -> MutexGuard<'_, CacheShard> {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        self.shards[(hasher.finish() % self.shards.len() as u64) as usize].lock().unwrap()
+    }
+
+    /// Get a cached entry without updating its recency.
+    pub(crate) fn peek(&self, key: &[u8]) -> Option<Option<Bytes>> {
+        self.shard(key).peek(key).cloned()
+    }
+
+    /// Get a cached entry without updating its recency, counting the lookup
+    /// as a hit or miss.
+    pub(crate) fn lookup(&self, key: &[u8]) -> Option<Option<Bytes>> {
+        let value = self.peek(key);
+        let counter = if value.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        value
+    }
+
+    /// Number of hits and misses of [`ShardedCache::lookup`] since creation.
+    pub(crate) fn lookups(&self) -> (u64, u64) {
+        (self.hits.load(Ordering::Relaxed), self.misses.load(Ordering::Relaxed))
+    }
+
+    /// Total capacity in entries.
+    pub(crate) fn capacity(&self) -> u32 {
+        self.capacity.load(Ordering::Relaxed)
+    }
+
+    /// Change the total capacity to `capacity` entries, evicting the least
+    /// recently used entries of shards over their new capacity.
+    pub(crate) fn set_capacity(&self, capacity: u32) {
+        let shard_capacity = capacity.div_ceil(self.shards.len() as u32).max(1);
+        for shard in self.shards.iter() {
+            shard.lock().unwrap().set_capacity(shard_capacity);
+        }
+        self.capacity.store(capacity, Ordering::Relaxed);
+    }
+
+    /// Average size in bytes of the keys and values of up to `sample_size`
+    /// of the most recently used entries, `None` if the cache is empty.
+    pub(crate) fn average_entry_size(&self, sample_size: usize) -> Option<usize> {
+        let per_shard = sample_size.div_ceil(self.shards.len()).max(1);
+        let (mut entries, mut bytes) = (0, 0);
+        for shard in self.shards.iter() {
+            for (key, value) in shard.lock().unwrap().iter().take(per_shard) {
+                entries += 1;
+                bytes += key.len() + value.as_ref().map_or(0, |value| value.len());
+            }
+        }
+        (entries > 0).then(|| bytes / entries)
+    }
+
+    /// Whether `key` is cached.
+    pub(crate) fn contains(&self, key: &[u8]) -> bool {
+        self.shard(key).peek(key).is_some()
+    }
+
+    /// Insert an entry, evicting the least recently used entry of its shard
+    /// if full. A value over the maximum value size drops the entry instead.
+    pub(crate) fn insert(&self, key: Vec<u8>, value: Option<Bytes>) {
+        if self.admits(&value) {
+            self.shard(&key).insert(key, value);
+        } else {
+            self.shard(&key).remove(&key);
+        }
+    }
+
+    /// Remove an entry, returning whether it was cached.
+    pub(crate) fn remove(&self, key: &[u8]) -> bool {
+        self.shard(key).remove(key)
+    }
+
+    /// Remove all entries.
+    pub(crate) fn clear(&self) {
+        for shard in self.shards.iter() {
+            shard.lock().unwrap().clear();
+        }
+    }
+
+    /// Number of cached entries.
+    pub(crate) fn len(&self) -> usize {
+        self.shards.iter().map(|shard| shard.lock().unwrap().len()).sum()
+    }
+
+    /// Lock all shards, in shard order, until the guard is dropped.
+    pub(crate) fn lock_all(&self) -> ShardedCacheGuard<'_> {
+        ShardedCacheGuard { cache: self, shards: self.shards.iter().map(|shard| shard.lock().unwrap()).collect() }
+    }
+}
+
+/// Exclusive access to all shards of a [`ShardedCache`].
+pub(crate) struct ShardedCacheGuard<'a> {
+    cache: &'a ShardedCache,
+    shards: Vec<MutexGuard<'a, CacheShard>>,
+}
+
+impl ShardedCacheGuard<'_> {
+    fn shard_mut(&mut self, key: &[u8]) -> &mut CacheShard {
+        let mut hasher = DefaultHasher::new();
+        key.hash(&mut hasher);
+        let index = (hasher.finish() % self.shards.len() as u64) as usize;
+        &mut self.shards[index]
+    }
+
+    /// Insert an entry like [`ShardedCache::insert`].
+    pub(crate) fn insert(&mut self, key: Vec<u8>, value: Option<Bytes>) {
+        if self.cache.admits(&value) {
+            self.shard_mut(&key).insert(key, value);
+        } else {
+            self.shard_mut(&key).remove(&key);
+        }
+    }
+
+    /// Remove an entry, returning whether it was cached.
+    pub(crate) fn remove(&mut self, key: &[u8]) -> bool {
+        self.shard_mut(key).remove(key)
+    }
+
+    /// Iterate over all entries, most recently used first within each shard.
+    pub(crate) fn iter(&self) -> impl Iterator<Item = (&Vec<u8>, &Option<Bytes>)> + '_ {
+        self.shards.iter().flat_map(|shard| shard.iter())
+    }
+
+    /// Iterate over up to `limit` of the most recently used entries whose
+    /// values match `predicate`, taking an equal share from each shard.
+    pub(crate) fn most_recent(&self, limit: usize, predicate: impl Fn(&Option<Bytes>) -> bool + Copy + 'static) -> impl Iterator<Item = (&Vec<u8>, &Option<Bytes>)> + '_ {
+        let per_shard = limit.div_ceil(self.shards.len());
+        self.shards
+            .iter()
+            .flat_map(move |shard| shard.iter().filter(move |(_, value)| predicate(value)).take(per_shard))
+            .take(limit)
+    }
+
+    /// Remove the entries with keys in any of the `[start, end)` ranges,
+    /// returning how many were removed.
+    ///
+    /// Looks the merged ranges up in the key index of each shard, so only
+    /// the removed keys are visited however large the cache is.
+    pub(crate) fn remove_ranges(&mut self, ranges: &[(Vec<u8>, Vec<u8>)]) -> usize {
+        let mut sorted: Vec<(&[u8], &[u8])> = ranges
+            .iter()
+            .filter(|(start, end)| start < end)
+            .map(|(start, end)| (start.as_slice(), end.as_slice()))
+            .collect();
+        if sorted.is_empty() {
+            return 0;
+        }
+        sorted.sort_unstable();
+        let mut merged: Vec<(&[u8], &[u8])> = Vec::with_capacity(sorted.len());
+        for (start, end) in sorted {
+            match merged.last_mut() {
+                Some((_, last_end)) if start <= *last_end => *last_end = (*last_end).max(end),
+                _ => merged.push((start, end)),
+            }
+        }
+        let mut removed = 0;
+        for shard in self.shards.iter_mut() {
+            for (start, end) in &merged {
+                let keys: Vec<Vec<u8>> = shard.range((Bound::Included(*start), Bound::Excluded(*end))).cloned().collect();
+                removed += shard.remove_all(&keys);
+            }
+        }
+        removed
+    }
+
+    /// Remove the entries whose keys start with `prefix`, returning how many
+    /// were removed. Like [`ShardedCacheGuard::remove_ranges`], only the
+    /// removed keys are visited.
+    pub(crate) fn remove_prefix(&mut self, prefix: &[u8]) -> usize {
+        let mut removed = 0;
+        for shard in self.shards.iter_mut() {
+            let keys: Vec<Vec<u8>> = shard
+                .range((Bound::Included(prefix), Bound::Unbounded))
+                .take_while(|key| key.starts_with(prefix))
+                .cloned()
+                .collect();
+            removed += shard.remove_all(&keys);
+        }
+        removed
+    }
+}
@@ -0,0 +1,330 @@
+//! Per-block journal of flat state snapshot changes and its crash recovery.
+
+use std::collections::BTreeMap;
+use std::sync::Arc;
+
+use alloy_primitives::{Bytes, B256};
+use rocksdb::{BoundColumnFamily, IteratorMode, WriteBatch};
+use rust_eth_triedb_common::{CancellationToken, TrieDatabase};
+use tracing::{error, info};
+
+use crate::pathdb::{
+    storage_snapshot_key, PathDB, ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, META_COLUMN_FAMILY_NAME,
+    SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME, STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME,
+};
+use crate::traits::*;
+
+/// Meta data key of the block of the last journal entry applied to the snapshot.
+const SNAPSHOT_BLOCK_KEY: &[u8] = b"snapshot_block";
+
+/// Changes of the flat account and storage snapshots made by one block.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotDiff {
+    /// Encoded accounts by hashed address, `None` to remove the entry
+    pub accounts: BTreeMap<B256, Option<Bytes>>,
+    /// Slot values by hashed address and hashed key, `None` to remove the entry
+    pub storages: BTreeMap<(B256, B256), Option<Bytes>>,
+}
+
+impl SnapshotDiff {
+    /// Whether the diff changes nothing.
+    pub fn is_empty(&self) -> bool {
+        self.accounts.is_empty() && self.storages.is_empty()
+    }
+
+    /// Encode the diff as `u32 BE count || entries` for the accounts, then
+    /// the storages, each entry `key || u8 present || [u32 BE len || value]`.
+    fn encode(&self) -> Vec<u8> {
+        let mut encoded = Vec::new();
+        encoded.extend_from_slice(&(self.accounts.len() as u32).to_be_bytes());
+        for (hashed_address, value) in &self.accounts {
+            encoded.extend_from_slice(hashed_address.as_slice());
+            encode_value(&mut encoded, value.as_deref());
+        }
+        encoded.extend_from_slice(&(self.storages.len() as u32).to_be_bytes());
+        for ((hashed_address, hashed_key), value) in &self.storages {
+            encoded.extend_from_slice(hashed_address.as_slice());
+            encoded.extend_from_slice(hashed_key.as_slice());
+            encode_value(&mut encoded, value.as_deref());
+        }
+        encoded
+    }
+
+    fn decode(mut data: &[u8]) -> PathProviderResult<Self> {
+        let data = &mut data;
+        let mut diff = Self::default();
+        for _ in 0..read_u32(data)? {
+            let hashed_address = read_hash(data)?;
+            diff.accounts.insert(hashed_address, read_value(data)?);
+        }
+        for _ in 0..read_u32(data)? {
+            let hashed_address = read_hash(data)?;
+            let hashed_key = read_hash(data)?;
+            diff.storages.insert((hashed_address, hashed_key), read_value(data)?);
+        }
+        if !data.is_empty() {
+            return Err(PathProviderError::Deserialization(format!("{} trailing bytes in snapshot diff", data.len())));
+        }
+        Ok(diff)
+    }
+}
+
+fn encode_value(encoded: &mut Vec<u8>, value: Option<&[u8]>) {
+    match value {
+        Some(value) => {
+            encoded.push(1);
+            encoded.extend_from_slice(&(value.len() as u32).to_be_bytes());
+            encoded.extend_from_slice(value);
+        }
+        None => encoded.push(0),
+    }
+}
+
+fn read_bytes<'a>(data: &mut &'a [u8], len: usize) -> PathProviderResult<&'a [u8]> {
+    if data.len() < len {
+        return Err(PathProviderError::Deserialization("Truncated snapshot diff".to_string()));
+    }
+    let (bytes, rest) = data.split_at(len);
+    *data = rest;
+    Ok(bytes)
+}
+
+fn read_u32(data: &mut &[u8]) -> PathProviderResult<u32> {
+    Ok(u32::from_be_bytes(read_bytes(data, 4)?.try_into().unwrap()))
+}
+
+fn read_hash(data: &mut &[u8]) -> PathProviderResult<B256> {
+    Ok(B256::from_slice(read_bytes(data, B256::len_bytes())?))
+}
+
+fn read_value(data: &mut &[u8]) -> PathProviderResult<Option<Bytes>> {
+    match read_bytes(data, 1)?[0] {
+        0 => Ok(None),
+        1 => {
+            let len = read_u32(data)? as usize;
+            Ok(Some(Bytes::copy_from_slice(read_bytes(data, len)?)))
+        }
+        flag => Err(PathProviderError::Deserialization(format!("Invalid snapshot diff value flag {}", flag))),
+    }
+}
+
+/// Outcome of [`PathDB::recover_snapshot`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SnapshotRecovery {
+    /// Block the snapshot is at after the recovery, `None` if no journal
+    /// entry was ever applied
+    pub snapshot_block: Option<u64>,
+    /// Journal entries applied to the snapshot
+    pub replayed: usize,
+    /// Journal entries dropped, being ahead of the persisted trie state
+    pub discarded: usize,
+}
+
+/// Snapshot journal
+///
+/// Snapshot changes are journaled per block before the block's diff layer is
+/// committed and applied to the snapshot once it is. The journal entry of a
+/// block is removed in the same batch that applies it, so after a crash the
+/// journal holds exactly the blocks whose changes are missing from the
+/// snapshot, and [`PathDB::recover_snapshot`] brings the snapshot back in
+/// line with the persisted trie state.
+impl PathDB {
+    /// Journal the snapshot changes of `block_number`, replacing any entry
+    /// journaled for that block before, e.g. by a reorged block.
+    pub fn journal_snapshot_diff(&self, block_number: u64, diff: &SnapshotDiff) -> PathProviderResult<()> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot journal to a read-only database".to_string()));
+        }
+        let cf = self.snapshot_cf(SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME)?;
+        let mut batch = WriteBatch::default();
+        batch.put_cf(&cf, self.db_key(&block_number.to_be_bytes()), diff.encode());
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB put in CF '{}' error", SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME), e))
+    }
+
+    /// Apply the journal entries up to `block_number` to the snapshot in
+    /// block order, one batch per entry, returning the number of entries applied.
+    ///
+    /// Called once the diff layer of `block_number` is committed. Unreadable
+    /// entries are an error, see [`PathDB::recover_snapshot`] to drop them.
+    pub fn apply_snapshot_journal(&self, block_number: u64) -> PathProviderResult<usize> {
+        self.apply_snapshot_journal_with_cancellation(block_number, &CancellationToken::new())
+    }
+
+    /// Apply the journal entries up to `block_number` like
+    /// [`PathDB::apply_snapshot_journal`], stopping before the next entry
+    /// once `cancel` is cancelled.
+    ///
+    /// Each entry is applied in its own batch, so the snapshot stays at the
+    /// last applied block and the rest of the journal is kept for later.
+    pub fn apply_snapshot_journal_with_cancellation(&self, block_number: u64, cancel: &CancellationToken) -> PathProviderResult<usize> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot apply the snapshot journal of a read-only database".to_string()));
+        }
+        let mut applied = 0;
+        for (entry_block, encoded) in self.snapshot_journal_entries(block_number.checked_add(1))? {
+            if cancel.is_cancelled() {
+                break;
+            }
+            self.apply_snapshot_journal_entry(entry_block, SnapshotDiff::decode(&encoded)?)?;
+            applied += 1;
+        }
+        Ok(applied)
+    }
+
+    /// Drop the journal entries of blocks below `block_number` without
+    /// applying them, returning the number of entries dropped.
+    ///
+    /// Reclaims old snapshot generations, e.g. after the snapshot was
+    /// regenerated at `block_number` or the entries were abandoned.
+    pub fn prune_below(&self, block_number: u64) -> PathProviderResult<usize> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot prune the snapshot journal of a read-only database".to_string()));
+        }
+        let pruned = self.snapshot_journal_entries(Some(block_number))?.len();
+        if pruned == 0 {
+            return Ok(0);
+        }
+
+        let cf = self.snapshot_cf(SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME)?;
+        let mut batch = WriteBatch::default();
+        batch.delete_range_cf(&cf, self.db_key(&0u64.to_be_bytes()), self.db_key(&block_number.to_be_bytes()));
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB delete range in CF '{}' error", SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME), e))?;
+        Ok(pruned)
+    }
+
+    /// Get the block of the last journal entry applied to the snapshot.
+    pub fn snapshot_block(&self) -> PathProviderResult<Option<u64>> {
+        self.get_meta_cf_value(SNAPSHOT_BLOCK_KEY)?
+            .map(|value| {
+                let bytes: [u8; 8] = value.as_slice().try_into().map_err(|_| {
+                    PathProviderError::Deserialization(format!("Invalid snapshot block of {} bytes", value.len()))
+                })?;
+                Ok(u64::from_be_bytes(bytes))
+            })
+            .transpose()
+    }
+
+    /// Bring the snapshot in line with the persisted trie state after an
+    /// unclean shutdown. Run at startup, before serving snapshot reads.
+    ///
+    /// Journal entries up to the persisted block are replayed. Entries past
+    /// it belong to blocks whose commit never landed and are discarded. An
+    /// entry up to the persisted block that can't be decoded fails the
+    /// recovery with a [`PathProviderError::Deserialization`] error, as the
+    /// snapshot can't catch up without it and must be regenerated; the
+    /// entries before it are replayed and it is kept in the journal.
+    pub fn recover_snapshot(&self) -> PathProviderResult<SnapshotRecovery> {
+        self.recover_snapshot_with_cancellation(&CancellationToken::new())
+    }
+
+    /// Recover the snapshot like [`PathDB::recover_snapshot`], stopping
+    /// before the next journal entry once `cancel` is cancelled.
+    ///
+    /// The entries not visited stay in the journal, so running the recovery
+    /// again picks up where it stopped.
+    pub fn recover_snapshot_with_cancellation(&self, cancel: &CancellationToken) -> PathProviderResult<SnapshotRecovery> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot recover the snapshot of a read-only database".to_string()));
+        }
+        let (persisted_block, _) = self.latest_persist_state()?;
+        let cf = self.snapshot_cf(SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME)?;
+
+        let mut recovery = SnapshotRecovery::default();
+        let mut discard = WriteBatch::default();
+        for (entry_block, encoded) in self.snapshot_journal_entries(None)? {
+            if cancel.is_cancelled() {
+                info!(target: "pathdb::snapshot", "Snapshot recovery cancelled before journal entry of block {}", entry_block);
+                break;
+            }
+            if entry_block > persisted_block {
+                discard.delete_cf(&cf, self.db_key(&entry_block.to_be_bytes()));
+                recovery.discarded += 1;
+                continue;
+            }
+            let diff = SnapshotDiff::decode(&encoded).map_err(|e| {
+                error!(target: "pathdb::snapshot", "Unreadable snapshot journal entry of block {}, the snapshot must be regenerated: {}", entry_block, e);
+                e
+            })?;
+            self.apply_snapshot_journal_entry(entry_block, diff)?;
+            recovery.replayed += 1;
+        }
+        if !discard.is_empty() {
+            self.write_raw_batch(discard)
+                .map_err(|e| PathProviderError::rocksdb(format!("RocksDB delete in CF '{}' error", SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME), e))?;
+        }
+
+        recovery.snapshot_block = self.snapshot_block()?;
+        if recovery.replayed > 0 || recovery.discarded > 0 {
+            info!(
+                target: "pathdb::snapshot",
+                "Recovered snapshot at block {:?}: replayed {} and discarded {} journal entries, trie persisted at block {}",
+                recovery.snapshot_block, recovery.replayed, recovery.discarded, persisted_block
+            );
+        }
+        Ok(recovery)
+    }
+
+    /// Apply `diff` journaled for `block_number` and drop its journal entry in one batch.
+    fn apply_snapshot_journal_entry(&self, block_number: u64, diff: SnapshotDiff) -> PathProviderResult<()> {
+        let journal_cf = self.snapshot_cf(SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME)?;
+        let meta_cf = self.snapshot_cf(META_COLUMN_FAMILY_NAME)?;
+
+        let mut batch = WriteBatch::default();
+        self.batch_apply_snapshot_diff(&mut batch, &diff)?;
+        batch.delete_cf(&journal_cf, self.db_key(&block_number.to_be_bytes()));
+        batch.put_cf(&meta_cf, self.db_key(SNAPSHOT_BLOCK_KEY), block_number.to_be_bytes());
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB write of snapshot journal entry {} error", block_number), e))
+    }
+
+    /// Add the account and storage snapshot writes of `diff` to `batch`.
+    pub(crate) fn batch_apply_snapshot_diff(&self, batch: &mut WriteBatch, diff: &SnapshotDiff) -> PathProviderResult<()> {
+        let account_cf = self.snapshot_cf(ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME)?;
+        let storage_cf = self.snapshot_cf(STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME)?;
+        for (hashed_address, value) in &diff.accounts {
+            match value {
+                Some(value) => batch.put_cf(&account_cf, self.db_key(hashed_address.as_slice()), value),
+                None => batch.delete_cf(&account_cf, self.db_key(hashed_address.as_slice())),
+            }
+        }
+        for ((hashed_address, hashed_key), value) in &diff.storages {
+            let key = storage_snapshot_key(*hashed_address, *hashed_key);
+            match value {
+                Some(value) => batch.put_cf(&storage_cf, self.db_key(&key), value),
+                None => batch.delete_cf(&storage_cf, self.db_key(&key)),
+            }
+        }
+        Ok(())
+    }
+
+    /// Journal entries of the blocks below `before`, all without it, in
+    /// block order, as block number and encoded diff.
+    ///
+    /// Only the entries of this instance's namespace are read.
+    fn snapshot_journal_entries(&self, before: Option<u64>) -> PathProviderResult<Vec<(u64, Vec<u8>)>> {
+        let cf = self.snapshot_cf(SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME)?;
+        let read_options = match before {
+            Some(before) => self.scan_read_options(None, Some(&before.to_be_bytes())),
+            None => self.namespace_read_options(),
+        };
+        let db_start = self.db_key(&[]).into_owned();
+
+        let mut entries = Vec::new();
+        for item in self.raw_db().iterator_cf_opt(&cf, read_options, IteratorMode::Start) {
+            let (db_key, value) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME), e)
+            })?;
+            let block_number: [u8; 8] = db_key[db_start.len()..].try_into().map_err(|_| {
+                PathProviderError::Deserialization(format!("Invalid snapshot journal key of {} bytes", db_key.len()))
+            })?;
+            entries.push((u64::from_be_bytes(block_number), value.into_vec()));
+        }
+        Ok(entries)
+    }
+
+    fn snapshot_cf(&self, cf_name: &str) -> PathProviderResult<Arc<BoundColumnFamily<'_>>> {
+        self.raw_db().cf_handle(cf_name).ok_or_else(|| PathProviderError::column_family_missing(cf_name))
+    }
+}
@@ -0,0 +1,176 @@
+//! A trie database split between a trie node PathDB and a snapshot PathDB.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use alloy_primitives::{Bytes, B256};
+use rust_eth_triedb_common::{AuditRecord, DiffLayer, TrieDatabase};
+
+use crate::pathdb::PathDB;
+use crate::traits::*;
+
+/// A [`TrieDatabase`] answering trie node reads from one PathDB and storage
+/// roots and flat state from another, the snapshot database.
+///
+/// Lets TrieDB run on one handle while the snapshot lives in its own
+/// database, e.g. on a separate disk or seeded with
+/// [`PathDB::import_storage_roots`]. Trie nodes, the persisted state, the
+/// audit log and the code hash index live in the trie database; storage
+/// roots, their history, slot counts and the flat snapshots live in the
+/// snapshot database.
+///
+/// A difflayer commit writes the snapshot database first, so after a crash
+/// mid-commit it may be ahead of the state reported by
+/// [`latest_persist_state`](TrieDatabase::latest_persist_state), like the
+/// shards of a [`ShardedPathDB`](crate::ShardedPathDB).
+#[derive(Debug, Clone)]
+pub struct SnapshotOverlay {
+    trie: PathDB,
+    snapshot: PathDB,
+}
+
+impl SnapshotOverlay {
+    /// Combine the trie node database `trie` with the snapshot database `snapshot`.
+    pub fn new(trie: PathDB, snapshot: PathDB) -> Self {
+        Self { trie, snapshot }
+    }
+
+    /// The trie node database.
+    pub fn trie(&self) -> &PathDB {
+        &self.trie
+    }
+
+    /// The snapshot database.
+    pub fn snapshot(&self) -> &PathDB {
+        &self.snapshot
+    }
+
+    /// Split `difflayer` into the layers of the trie and snapshot databases.
+    fn split_difflayer(difflayer: &DiffLayer) -> (DiffLayer, DiffLayer) {
+        let mut trie_layer = DiffLayer::new(difflayer.diff_nodes.clone(), HashMap::new())
+            .with_deleted_ranges(difflayer.deleted_ranges.clone())
+            .with_code_hashes(difflayer.code_hashes.clone());
+        // The audit log lives in the trie database, see `put_audit_record`
+        trie_layer.audit_record = difflayer.audit_record;
+        let snapshot_layer = DiffLayer::new(HashMap::new(), difflayer.diff_storage_roots.clone())
+            .with_slot_count_changes(difflayer.slot_count_changes.clone());
+        (trie_layer, snapshot_layer)
+    }
+}
+
+impl TrieDatabase for SnapshotOverlay {
+    type Error = PathProviderError;
+
+    fn get_trie_node(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        TrieDatabase::get_trie_node(&self.trie, path)
+    }
+
+    fn get_trie_nodes(&self, paths: &[Vec<u8>]) -> Result<Vec<Option<Bytes>>, Self::Error> {
+        TrieDatabase::get_trie_nodes(&self.trie, paths)
+    }
+
+    fn get_trie_node_verified(&self, path: &[u8]) -> Result<Option<Bytes>, Self::Error> {
+        TrieDatabase::get_trie_node_verified(&self.trie, path)
+    }
+
+    fn insert_trie_node(&self, path: &[u8], data: Vec<u8>) -> Result<(), Self::Error> {
+        TrieDatabase::insert_trie_node(&self.trie, path, data)
+    }
+
+    fn contains_trie_node(&self, path: &[u8]) -> Result<bool, Self::Error> {
+        TrieDatabase::contains_trie_node(&self.trie, path)
+    }
+
+    fn remove_trie_node(&self, path: &[u8]) {
+        TrieDatabase::remove_trie_node(&self.trie, path)
+    }
+
+    fn iter_trie_node_range(&self, start: &[u8], end: Option<&[u8]>, limit: usize) -> Result<Vec<(Vec<u8>, Bytes)>, Self::Error> {
+        TrieDatabase::iter_trie_node_range(&self.trie, start, end, limit)
+    }
+
+    fn get_storage_root(&self, hased_address: B256) -> Result<Option<B256>, Self::Error> {
+        TrieDatabase::get_storage_root(&self.snapshot, hased_address)
+    }
+
+    fn commit_difflayer(&self, block_number: u64, state_root: B256, difflayer: &Option<Arc<DiffLayer>>) -> Result<(), Self::Error> {
+        let (trie_layer, snapshot_layer) = match difflayer {
+            Some(difflayer) => {
+                let (trie_layer, snapshot_layer) = Self::split_difflayer(difflayer);
+                (Some(Arc::new(trie_layer)), Some(Arc::new(snapshot_layer)))
+            }
+            None => (None, None),
+        };
+        // Check both before writing either, so a rejected commit writes nothing
+        if let (Some(trie_layer), Some(snapshot_layer)) = (&trie_layer, &snapshot_layer) {
+            self.trie.check_commit_limits(block_number, trie_layer)?;
+            self.snapshot.check_commit_limits(block_number, snapshot_layer)?;
+        }
+        // The trie database reports the persisted state, commit it last
+        TrieDatabase::commit_difflayer(&self.snapshot, block_number, state_root, &snapshot_layer)?;
+        TrieDatabase::commit_difflayer(&self.trie, block_number, state_root, &trie_layer)
+    }
+
+    fn latest_persist_state(&self) -> Result<(u64, B256), Self::Error> {
+        TrieDatabase::latest_persist_state(&self.trie)
+    }
+
+    fn persist_generation(&self) -> Option<u64> {
+        TrieDatabase::persist_generation(&self.trie)
+    }
+
+    fn clear_cache(&self) {
+        self.trie.clear_cache();
+        self.snapshot.clear_cache();
+    }
+
+    fn put_audit_record(&self, record: &AuditRecord) -> Result<(), Self::Error> {
+        TrieDatabase::put_audit_record(&self.trie, record)
+    }
+
+    fn get_audit_records(&self, block_number: u64) -> Result<Vec<AuditRecord>, Self::Error> {
+        TrieDatabase::get_audit_records(&self.trie, block_number)
+    }
+
+    fn get_addresses_by_code_hash(&self, code_hash: B256) -> Result<Vec<B256>, Self::Error> {
+        TrieDatabase::get_addresses_by_code_hash(&self.trie, code_hash)
+    }
+
+    fn get_storage_slot_count(&self, hashed_address: B256) -> Result<Option<u64>, Self::Error> {
+        TrieDatabase::get_storage_slot_count(&self.snapshot, hashed_address)
+    }
+
+    fn get_storage_root_at(&self, hashed_address: B256, block_number: u64) -> Result<Option<B256>, Self::Error> {
+        TrieDatabase::get_storage_root_at(&self.snapshot, hashed_address, block_number)
+    }
+
+    fn get_account_snapshot(&self, hashed_address: B256) -> Result<Option<Bytes>, Self::Error> {
+        TrieDatabase::get_account_snapshot(&self.snapshot, hashed_address)
+    }
+
+    fn put_account_snapshot(&self, hashed_address: B256, account: Option<&[u8]>) -> Result<(), Self::Error> {
+        TrieDatabase::put_account_snapshot(&self.snapshot, hashed_address, account)
+    }
+
+    fn get_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<Bytes>, Self::Error> {
+        TrieDatabase::get_storage_snapshot(&self.snapshot, hashed_address, hashed_key)
+    }
+
+    fn put_storage_snapshot(&self, hashed_address: B256, hashed_key: B256, value: Option<&[u8]>) -> Result<(), Self::Error> {
+        TrieDatabase::put_storage_snapshot(&self.snapshot, hashed_address, hashed_key, value)
+    }
+
+    fn iter_account_snapshot(&self, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        TrieDatabase::iter_account_snapshot(&self.snapshot, start_hash, limit)
+    }
+
+    fn iter_storage_snapshot(&self, hashed_address: B256, start_hash: B256, limit: usize) -> Result<Vec<(B256, Bytes)>, Self::Error> {
+        TrieDatabase::iter_storage_snapshot(&self.snapshot, hashed_address, start_hash, limit)
+    }
+
+    fn shutdown(&self) -> Result<(), Self::Error> {
+        // Shut down both even if one fails
+        let snapshot = TrieDatabase::shutdown(&self.snapshot);
+        TrieDatabase::shutdown(&self.trie).and(snapshot)
+    }
+}
@@ -0,0 +1,182 @@
+//! In-memory diff layers of the flat state snapshot on top of its disk layer.
+
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+
+use alloy_primitives::{Bytes, B256};
+use rocksdb::WriteBatch;
+use tracing::debug;
+
+use crate::pathdb::PathDB;
+use crate::snapshot_journal::SnapshotDiff;
+use crate::traits::*;
+
+/// Snapshot changes of one unfinalized block, see [`SnapshotTree::update`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotLayer {
+    block_hash: B256,
+    parent: B256,
+    diff: SnapshotDiff,
+}
+
+impl SnapshotLayer {
+    /// Hash of the block the layer belongs to.
+    pub fn block_hash(&self) -> B256 {
+        self.block_hash
+    }
+
+    /// Hash of the parent block, a layer or the disk layer.
+    pub fn parent(&self) -> B256 {
+        self.parent
+    }
+
+    /// Snapshot changes of the block.
+    pub fn diff(&self) -> &SnapshotDiff {
+        &self.diff
+    }
+}
+
+/// A tree of snapshot diff layers, one per unfinalized block, on top of the
+/// flat account and storage snapshots of a PathDB.
+///
+/// Mirrors the trie-side `DiffLayers` for flat state: a read at a block walks
+/// the layers from that block to the disk layer, so reads at blocks of
+/// competing forks see their own fork's changes. [`SnapshotTree::flatten`]
+/// writes the layers up to a finalized block to disk and drops the forks that
+/// don't build on it.
+///
+/// Like `DiffLayers` the tree is not synchronized; share it behind a lock.
+#[derive(Debug)]
+pub struct SnapshotTree {
+    db: PathDB,
+    disk_block_hash: B256,
+    layers: HashMap<B256, Arc<SnapshotLayer>>,
+}
+
+impl SnapshotTree {
+    /// Create a tree without diff layers over the snapshot of `db`, which is
+    /// at the block `disk_block_hash`.
+    pub fn new(db: PathDB, disk_block_hash: B256) -> Self {
+        Self { db, disk_block_hash, layers: HashMap::new() }
+    }
+
+    /// Hash of the block the disk layer is at.
+    pub fn disk_block_hash(&self) -> B256 {
+        self.disk_block_hash
+    }
+
+    /// Number of diff layers in the tree.
+    pub fn len(&self) -> usize {
+        self.layers.len()
+    }
+
+    /// Whether the tree has no diff layers.
+    pub fn is_empty(&self) -> bool {
+        self.layers.is_empty()
+    }
+
+    /// Add the snapshot changes of `block_hash` on top of `parent`, the disk
+    /// layer or another diff layer. `None` values remove an entry.
+    pub fn update(
+        &mut self,
+        block_hash: B256,
+        parent: B256,
+        accounts: BTreeMap<B256, Option<Bytes>>,
+        storage: BTreeMap<(B256, B256), Option<Bytes>>,
+    ) -> PathProviderResult<()> {
+        if block_hash == self.disk_block_hash || self.layers.contains_key(&block_hash) {
+            return Err(PathProviderError::InvalidOperation(format!("Snapshot layer {:#x} already exists", block_hash)));
+        }
+        if parent != self.disk_block_hash && !self.layers.contains_key(&parent) {
+            return Err(Self::unknown_layer(parent));
+        }
+        let diff = SnapshotDiff { accounts, storages: storage };
+        self.layers.insert(block_hash, Arc::new(SnapshotLayer { block_hash, parent, diff }));
+        Ok(())
+    }
+
+    /// Get the diff layers from `block_hash` down to the disk layer, the
+    /// most recent first like `DiffLayers`, empty at the disk layer.
+    pub fn layers(&self, block_hash: B256) -> PathProviderResult<Vec<Arc<SnapshotLayer>>> {
+        let mut layers = Vec::new();
+        let mut current = block_hash;
+        while current != self.disk_block_hash {
+            let layer = self.layers.get(&current).ok_or_else(|| Self::unknown_layer(current))?;
+            current = layer.parent;
+            layers.push(layer.clone());
+        }
+        Ok(layers)
+    }
+
+    /// Get the encoded account of `hashed_address` as of `block_hash`.
+    pub fn account(&self, block_hash: B256, hashed_address: B256) -> PathProviderResult<Option<Bytes>> {
+        let mut current = block_hash;
+        while current != self.disk_block_hash {
+            let layer = self.layers.get(&current).ok_or_else(|| Self::unknown_layer(current))?;
+            if let Some(value) = layer.diff.accounts.get(&hashed_address) {
+                return Ok(value.clone());
+            }
+            current = layer.parent;
+        }
+        self.db.get_raw_account_snapshot(hashed_address)
+    }
+
+    /// Get the storage slot `hashed_key` of `hashed_address` as of `block_hash`.
+    pub fn storage(&self, block_hash: B256, hashed_address: B256, hashed_key: B256) -> PathProviderResult<Option<Bytes>> {
+        let mut current = block_hash;
+        while current != self.disk_block_hash {
+            let layer = self.layers.get(&current).ok_or_else(|| Self::unknown_layer(current))?;
+            if let Some(value) = layer.diff.storages.get(&(hashed_address, hashed_key)) {
+                return Ok(value.clone());
+            }
+            current = layer.parent;
+        }
+        self.db.get_raw_storage_snapshot(hashed_address, hashed_key)
+    }
+
+    /// Write the diff layers from the disk layer up to `block_hash` to disk
+    /// in one batch and make `block_hash` the disk layer, returning the
+    /// number of layers written.
+    ///
+    /// Layers that don't build on `block_hash` belong to abandoned forks and
+    /// are dropped; its descendants stay in the tree.
+    pub fn flatten(&mut self, block_hash: B256) -> PathProviderResult<usize> {
+        let layers = self.layers(block_hash)?;
+        if layers.is_empty() {
+            return Ok(0);
+        }
+        if self.db.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot flatten snapshot layers into a read-only database".to_string()));
+        }
+
+        // Oldest layer first, so more recent layers overwrite it
+        let mut merged = SnapshotDiff::default();
+        for layer in layers.iter().rev() {
+            merged.accounts.extend(layer.diff.accounts.iter().map(|(key, value)| (*key, value.clone())));
+            merged.storages.extend(layer.diff.storages.iter().map(|(key, value)| (*key, value.clone())));
+        }
+        let mut batch = WriteBatch::default();
+        self.db.batch_apply_snapshot_diff(&mut batch, &merged)?;
+        self.db.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB write of snapshot layers up to {:#x} error", block_hash), e))?;
+
+        for layer in &layers {
+            self.layers.remove(&layer.block_hash);
+        }
+        self.disk_block_hash = block_hash;
+        let abandoned: Vec<B256> = self.layers.keys().filter(|hash| self.layers(**hash).is_err()).copied().collect();
+        for hash in &abandoned {
+            self.layers.remove(hash);
+        }
+        debug!(
+            target: "pathdb::snapshot",
+            "Flattened {} snapshot layers up to {:#x}, dropped {} abandoned layers",
+            layers.len(), block_hash, abandoned.len()
+        );
+        Ok(layers.len())
+    }
+
+    fn unknown_layer(block_hash: B256) -> PathProviderError {
+        PathProviderError::InvalidOperation(format!("Unknown snapshot layer {:#x}", block_hash))
+    }
+}
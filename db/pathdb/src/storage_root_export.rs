@@ -0,0 +1,189 @@
+//! Export and import of the storage root column family as a flat file.
+
+use std::fs::File;
+use std::io::{BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::path::{Path, PathBuf};
+
+use alloy_primitives::B256;
+use rocksdb::{Direction, IteratorMode, WriteBatch};
+use rust_eth_triedb_common::TrieDatabase;
+use tracing::info;
+
+use crate::pathdb::{PathDB, STORAGE_ROOT_COLUMN_FAMILY_NAME};
+use crate::traits::*;
+
+/// Magic bytes at the start of a storage root file.
+const STORAGE_ROOT_FILE_MAGIC: &[u8; 8] = b"TRIEDBSR";
+
+/// Version of the storage root file format.
+const STORAGE_ROOT_FILE_VERSION: u8 = 1;
+
+/// Size of the file header: magic, version, block number, state root and entry count.
+const STORAGE_ROOT_FILE_HEADER_LEN: usize = 8 + 1 + 8 + 32 + 8;
+
+/// Number of storage roots written per batch on import.
+const IMPORT_BATCH_SIZE: usize = 10_000;
+
+/// Header of a storage root file, see [`PathDB::export_storage_roots`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StorageRootFile {
+    /// Persisted block of the exporting database at the time of the export
+    pub block_number: u64,
+    /// Persisted state root of the exporting database at the time of the export
+    pub state_root: B256,
+    /// Number of storage roots in the file
+    pub entries: u64,
+}
+
+/// Storage root export and import
+impl PathDB {
+    /// Write every storage root of the database to a new file at `path`,
+    /// e.g. to seed the storage roots of a new node without copying the
+    /// whole database.
+    ///
+    /// Roots are streamed from one RocksDB snapshot, so the file matches the
+    /// persisted state recorded in its header even while commits continue.
+    /// Only the roots of this instance's namespace are exported, with keys
+    /// written without the namespace. The file is written next to `path`
+    /// with a `.tmp` suffix and renamed once complete, so `path` never holds
+    /// a partial export.
+    ///
+    /// # File Format
+    ///
+    /// - **Header**: `b"TRIEDBSR"` || `u8` version || `u64 BE` block number
+    ///   || `B256` state root || `u64 BE` entry count
+    /// - **Entries**: `hashed_address || storage_root` (64 bytes each), in
+    ///   hashed address order
+    pub fn export_storage_roots(&self, path: impl AsRef<Path>) -> PathProviderResult<StorageRootFile> {
+        let path = path.as_ref();
+        if path.exists() {
+            return Err(PathProviderError::InvalidOperation(format!("Export target {} already exists", path.display())));
+        }
+
+        let mut tmp_path = path.as_os_str().to_owned();
+        tmp_path.push(".tmp");
+        let tmp_path = PathBuf::from(tmp_path);
+        let exported = self.write_storage_root_file(&tmp_path).and_then(|exported| {
+            std::fs::rename(&tmp_path, path)?;
+            Ok(exported)
+        });
+        let exported = match exported {
+            Ok(exported) => exported,
+            Err(e) => {
+                let _ = std::fs::remove_file(&tmp_path);
+                return Err(e);
+            }
+        };
+
+        info!(target: "pathdb::rocksdb", "Exported {} storage roots at block {} to {}", exported.entries, exported.block_number, path.display());
+        Ok(exported)
+    }
+
+    /// Write the storage roots of this instance's namespace to a new file at `path`.
+    fn write_storage_root_file(&self, path: &Path) -> PathProviderResult<StorageRootFile> {
+        let snapshot = self.read_snapshot();
+        let (block_number, state_root) = snapshot.latest_persist_state()?;
+        let cf = self.raw_db().cf_handle(STORAGE_ROOT_COLUMN_FAMILY_NAME)
+            .ok_or_else(|| PathProviderError::column_family_missing(STORAGE_ROOT_COLUMN_FAMILY_NAME))?;
+        let prefix = self.db_key(&[]).into_owned();
+        let mut read_options = self.namespace_read_options();
+        read_options.set_snapshot(snapshot.raw_snapshot());
+
+        // The entry count is patched in once all entries are written
+        let mut writer = BufWriter::new(File::create(path)?);
+        write_header(&mut writer, &StorageRootFile { block_number, state_root, entries: 0 })?;
+        let mut entries = 0u64;
+        for item in self.raw_db().iterator_cf_opt(&cf, read_options, IteratorMode::From(&prefix, Direction::Forward)) {
+            let (db_key, value) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", STORAGE_ROOT_COLUMN_FAMILY_NAME), e)
+            })?;
+            if !db_key.starts_with(&prefix) {
+                break;
+            }
+            if db_key.len() != prefix.len() + B256::len_bytes() || value.len() != B256::len_bytes() {
+                return Err(PathProviderError::Deserialization(format!(
+                    "Invalid storage root entry with a {} byte key and a {} byte value", db_key.len(), value.len()
+                )));
+            }
+            writer.write_all(&db_key[prefix.len()..])?;
+            writer.write_all(&value)?;
+            entries += 1;
+        }
+
+        let mut file = writer.into_inner().map_err(|e| e.into_error())?;
+        file.seek(SeekFrom::Start((STORAGE_ROOT_FILE_HEADER_LEN - 8) as u64))?;
+        file.write_all(&entries.to_be_bytes())?;
+        file.sync_all()?;
+        Ok(StorageRootFile { block_number, state_root, entries })
+    }
+
+    /// Write the storage roots of a file made by [`PathDB::export_storage_roots`]
+    /// into the database, returning the file header.
+    ///
+    /// Roots are written in batches and overwrite existing entries; an
+    /// interrupted import can be restarted. The persisted state is left
+    /// unchanged, the caller sets it once the matching trie is in place.
+    /// The LRU caches are cleared afterwards.
+    pub fn import_storage_roots(&self, path: impl AsRef<Path>) -> PathProviderResult<StorageRootFile> {
+        let path = path.as_ref();
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot import storage roots into a read-only database".to_string()));
+        }
+
+        let mut reader = BufReader::new(File::open(path)?);
+        let header = read_header(&mut reader)?;
+        let cf = self.raw_db().cf_handle(STORAGE_ROOT_COLUMN_FAMILY_NAME)
+            .ok_or_else(|| PathProviderError::column_family_missing(STORAGE_ROOT_COLUMN_FAMILY_NAME))?;
+
+        let mut batch = WriteBatch::default();
+        let mut entry = [0u8; 64];
+        for index in 0..header.entries {
+            reader.read_exact(&mut entry).map_err(|e| {
+                PathProviderError::Deserialization(format!("Storage root file truncated at entry {} of {}: {}", index, header.entries, e))
+            })?;
+            batch.put_cf(&cf, self.db_key(&entry[..32]), &entry[32..]);
+            if batch.len() >= IMPORT_BATCH_SIZE {
+                self.write_import_batch(std::mem::take(&mut batch))?;
+            }
+        }
+        if reader.read(&mut [0u8; 1])? != 0 {
+            return Err(PathProviderError::Deserialization(format!("Storage root file has data past its {} entries", header.entries)));
+        }
+        self.write_import_batch(batch)?;
+        self.clear_cache();
+
+        info!(target: "pathdb::rocksdb", "Imported {} storage roots at block {} from {}", header.entries, header.block_number, path.display());
+        Ok(header)
+    }
+
+    fn write_import_batch(&self, batch: WriteBatch) -> PathProviderResult<()> {
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB write in CF '{}' error", STORAGE_ROOT_COLUMN_FAMILY_NAME), e))
+    }
+}
+
+fn write_header(writer: &mut impl Write, header: &StorageRootFile) -> PathProviderResult<()> {
+    writer.write_all(STORAGE_ROOT_FILE_MAGIC)?;
+    writer.write_all(&[STORAGE_ROOT_FILE_VERSION])?;
+    writer.write_all(&header.block_number.to_be_bytes())?;
+    writer.write_all(header.state_root.as_slice())?;
+    writer.write_all(&header.entries.to_be_bytes())?;
+    Ok(())
+}
+
+fn read_header(reader: &mut impl Read) -> PathProviderResult<StorageRootFile> {
+    let mut header = [0u8; STORAGE_ROOT_FILE_HEADER_LEN];
+    reader.read_exact(&mut header)
+        .map_err(|e| PathProviderError::Deserialization(format!("Storage root file header unreadable: {}", e)))?;
+    if &header[..8] != STORAGE_ROOT_FILE_MAGIC {
+        return Err(PathProviderError::Deserialization("Not a storage root file".to_string()));
+    }
+    if header[8] != STORAGE_ROOT_FILE_VERSION {
+        return Err(PathProviderError::Deserialization(format!("Unsupported storage root file version {}", header[8])));
+    }
+    Ok(StorageRootFile {
+        block_number: u64::from_be_bytes(header[9..17].try_into().expect("8 bytes")),
+        state_root: B256::from_slice(&header[17..49]),
+        entries: u64::from_be_bytes(header[49..57].try_into().expect("8 bytes")),
+    })
+}
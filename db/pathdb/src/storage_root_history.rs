@@ -0,0 +1,143 @@
+//! Storage roots of past blocks for historical lookups.
+
+use alloy_primitives::B256;
+use rocksdb::{Direction, IteratorMode, WriteBatch};
+use rust_eth_triedb_common::CancellationToken;
+use tracing::info;
+
+use crate::pathdb::{PathDB, STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME};
+use crate::traits::*;
+use crate::version_gc::{key_version, versioned_key, VERSION_SUFFIX_LEN};
+
+/// Number of deletes written per batch when pruning.
+const PRUNE_BATCH_SIZE: usize = 10_000;
+
+/// Storage root history
+///
+/// With `PathProviderConfig::storage_root_history` enabled, every persisted
+/// storage root is also written under `hashed_address || block_number`, so
+/// the root of an account can be looked up as of any block since, e.g. to
+/// serve `eth_getProof` against old state roots on archive nodes.
+impl PathDB {
+    /// Get the storage root of `hashed_address` as of `block_number`: the
+    /// root recorded at the latest block up to `block_number`.
+    ///
+    /// Returns `None` if no root was recorded for the account up to that
+    /// block, e.g. before history was enabled or below the pruned range.
+    /// Accounts whose storage was removed report `EMPTY_ROOT_HASH`.
+    pub fn get_storage_root_at(&self, hashed_address: B256, block_number: u64) -> PathProviderResult<Option<B256>> {
+        let cf = self.raw_db().cf_handle(STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME)
+            .ok_or_else(|| PathProviderError::column_family_missing(STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME))?;
+        // Versions sort newest first, so the first one from `block_number` on is the answer
+        let lower = versioned_key(hashed_address.as_slice(), block_number);
+        // The oldest version of the account is the largest of its keys
+        let upper: Vec<u8> = versioned_key(hashed_address.as_slice(), 0).into_iter().chain([0]).collect();
+        let (db_lower, db_upper) = (self.db_key(&lower).into_owned(), self.db_key(&upper).into_owned());
+        let mut read_options = self.namespace_read_options();
+        read_options.set_iterate_lower_bound(db_lower);
+        read_options.set_iterate_upper_bound(db_upper);
+
+        let Some(item) = self.raw_db().iterator_cf_opt(&cf, read_options, IteratorMode::Start).next() else {
+            return Ok(None);
+        };
+        let (_, value) = item.map_err(|e| {
+            PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME), e)
+        })?;
+        if value.len() != B256::len_bytes() {
+            return Err(PathProviderError::Deserialization(format!(
+                "Invalid historical storage root of {} bytes for {:#x}", value.len(), hashed_address
+            )));
+        }
+        Ok(Some(B256::from_slice(&value)))
+    }
+
+    /// Delete the historical storage roots no lookup from `keep_from_block`
+    /// on can return, returning the number of entries deleted. Only the
+    /// entries of this instance's namespace are visited.
+    ///
+    /// The latest root of each account up to `keep_from_block` is kept, as
+    /// it still answers lookups at later blocks. Listing the history column
+    /// family in `PathProviderConfig::version_gc` prunes the same way during
+    /// compactions.
+    pub fn prune_storage_root_history(&self, keep_from_block: u64) -> PathProviderResult<u64> {
+        self.prune_storage_root_history_with_cancellation(keep_from_block, &CancellationToken::new())
+    }
+
+    /// Prune the storage root history like
+    /// [`PathDB::prune_storage_root_history`], stopping the walk once
+    /// `cancel` is cancelled.
+    ///
+    /// The entries found until then are still deleted, the returned count
+    /// tells how many.
+    pub fn prune_storage_root_history_with_cancellation(&self, keep_from_block: u64, cancel: &CancellationToken) -> PathProviderResult<u64> {
+        if self.is_read_only() {
+            return Err(PathProviderError::InvalidOperation("Cannot prune the storage root history of a read-only database".to_string()));
+        }
+        let cf = self.raw_db().cf_handle(STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME)
+            .ok_or_else(|| PathProviderError::column_family_missing(STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME))?;
+        let prefix = self.db_key(&[]).into_owned();
+        let key_len = prefix.len() + B256::len_bytes() + VERSION_SUFFIX_LEN;
+
+        let mut deleted = 0u64;
+        let mut batch = WriteBatch::default();
+        // Account whose newest version up to `keep_from_block` was kept;
+        // versions sort newest first, so its following versions are superseded
+        let mut covered: Option<Box<[u8]>> = None;
+        for item in self.raw_db().iterator_cf_opt(&cf, self.namespace_read_options(), IteratorMode::From(&prefix, Direction::Forward)) {
+            if cancel.is_cancelled() {
+                break;
+            }
+            let (db_key, _) = item.map_err(|e| {
+                PathProviderError::rocksdb(format!("RocksDB iterate in CF '{}' error", STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME), e)
+            })?;
+            if !db_key.starts_with(&prefix) {
+                break;
+            }
+            if db_key.len() != key_len {
+                return Err(PathProviderError::Deserialization(format!("Invalid storage root history key of {} bytes", db_key.len())));
+            }
+            let version = key_version(&db_key).expect("length checked above");
+            let owner = &db_key[..key_len - VERSION_SUFFIX_LEN];
+            if covered.as_deref() == Some(owner) {
+                batch.delete_cf(&cf, &db_key);
+                deleted += 1;
+            } else if version <= keep_from_block {
+                covered = Some(owner.into());
+            }
+            if batch.len() >= PRUNE_BATCH_SIZE {
+                self.write_prune_batch(std::mem::take(&mut batch))?;
+            }
+        }
+        self.write_prune_batch(batch)?;
+
+        if cancel.is_cancelled() {
+            info!(target: "pathdb::rocksdb", "Pruning historical storage roots before block {} cancelled after {} entries", keep_from_block, deleted);
+            return Ok(deleted);
+        }
+        info!(target: "pathdb::rocksdb", "Pruned {} historical storage roots before block {}", deleted, keep_from_block);
+        Ok(deleted)
+    }
+
+    /// Add the history entries of `storage_roots` persisted at `block_number` to `batch`.
+    pub(crate) fn batch_record_storage_root_history(
+        &self,
+        batch: &mut WriteBatch,
+        block_number: u64,
+        storage_roots: impl IntoIterator<Item = (B256, B256)>,
+    ) -> PathProviderResult<()> {
+        let cf = self.raw_db().cf_handle(STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME)
+            .ok_or_else(|| PathProviderError::column_family_missing(STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME))?;
+        for (hashed_address, storage_root) in storage_roots {
+            batch.put_cf(&cf, self.db_key(&versioned_key(hashed_address.as_slice(), block_number)), storage_root.as_slice());
+        }
+        Ok(())
+    }
+
+    fn write_prune_batch(&self, batch: WriteBatch) -> PathProviderResult<()> {
+        if batch.is_empty() {
+            return Ok(());
+        }
+        self.write_raw_batch(batch)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB write in CF '{}' error", STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME), e))
+    }
+}
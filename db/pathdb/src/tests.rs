@@ -16,7 +16,7 @@ fn test_basic_operations() {
     db.put_raw_trie_node(key, value).unwrap();
     
     let retrieved = db.get_raw_trie_node(key).unwrap();
-    assert_eq!(retrieved, Some(value.to_vec()));
+    assert_eq!(retrieved, Some(value.to_vec().into()));
 
     // Test exists
     assert!(db.exists_raw_trie_node(key).unwrap());
@@ -114,6 +114,2748 @@ fn test_concurrent_access() {
         let key = format!("thread_key_{}", i).into_bytes();
         let expected_value = format!("thread_value_{}", i).into_bytes();
         let retrieved = db.get_raw_trie_node(&key).unwrap();
-        assert_eq!(retrieved, Some(expected_value));
+        assert_eq!(retrieved, Some(expected_value.into()));
     }
-}
\ No newline at end of file
+}
+
+#[test]
+fn test_sharded_cache() {
+    use std::sync::Arc;
+    use std::thread;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path();
+    let mut config = PathProviderConfig::default();
+    config.trie_node_cache_size = 64;
+    config.cache_shards = 4;
+    let db = Arc::new(PathDB::new(db_path.to_str().unwrap(), config).unwrap());
+
+    // Concurrent writers and readers over disjoint keys
+    let handles: Vec<_> = (0..4).map(|t| {
+        let db = db.clone();
+        thread::spawn(move || {
+            for i in 0..200 {
+                let key = format!("shard_key_{}_{}", t, i).into_bytes();
+                let value = format!("shard_value_{}_{}", t, i).into_bytes();
+                db.put_raw_trie_node(&key, &value).unwrap();
+                assert_eq!(db.get_raw_trie_node(&key).unwrap(), Some(value.into()));
+            }
+        })
+    }).collect();
+    for handle in handles {
+        handle.join().unwrap();
+    }
+
+    // Every shard evicts on its own, so the cache stays within its capacity
+    let (cache_len, _) = db.cache_stats();
+    assert!(cache_len > 0 && cache_len <= 64);
+    for t in 0..4 {
+        for i in 0..200 {
+            let key = format!("shard_key_{}_{}", t, i).into_bytes();
+            let expected_value = format!("shard_value_{}_{}", t, i).into_bytes();
+            assert_eq!(db.get_raw_trie_node(&key).unwrap(), Some(expected_value.into()));
+        }
+    }
+
+    db.clear_cache();
+    assert_eq!(db.cache_stats().0, 0);
+}
+
+#[test]
+fn test_sharded_cache_remove_ranges() {
+    use crate::sharded_cache::ShardedCache;
+
+    let cache = ShardedCache::new(1024, 4);
+    for key in [b"A1", b"B1", b"B5", b"C1", b"D1", b"E1", b"F1"] {
+        cache.insert(key.to_vec(), None);
+    }
+
+    // Unsorted, overlapping and empty ranges, removed in one pass
+    let ranges = vec![
+        (b"D".to_vec(), b"E".to_vec()),
+        (b"B".to_vec(), b"B5".to_vec()),
+        (b"B2".to_vec(), b"C2".to_vec()),
+        (b"F".to_vec(), b"F".to_vec()),
+    ];
+    assert_eq!(cache.lock_all().remove_ranges(&ranges), 4);
+    for key in [b"A1", b"E1", b"F1"] {
+        assert!(cache.contains(key));
+    }
+    for key in [b"B1", b"B5", b"C1", b"D1"] {
+        assert!(!cache.contains(key));
+    }
+    assert_eq!(cache.lock_all().remove_ranges(&[]), 0);
+}
+
+#[test]
+fn test_sharded_cache_key_index() {
+    use crate::sharded_cache::ShardedCache;
+
+    // Evicted keys leave the index, range removals only count cached keys
+    let cache = ShardedCache::new(2, 1);
+    for key in [b"A1", b"A2", b"A3"] {
+        cache.insert(key.to_vec(), None);
+    }
+    assert!(!cache.contains(b"A1"));
+    assert_eq!(cache.lock_all().remove_ranges(&[(b"A".to_vec(), b"B".to_vec())]), 2);
+    assert_eq!(cache.len(), 0);
+
+    let cache = ShardedCache::new(1024, 4);
+    for key in [b"A1", b"B1", b"B2", b"C1"] {
+        cache.insert(key.to_vec(), None);
+    }
+    cache.remove(b"B2");
+    assert_eq!(cache.lock_all().remove_prefix(b"B"), 1);
+    assert!(cache.contains(b"A1") && cache.contains(b"C1"));
+    assert_eq!(cache.lock_all().remove_prefix(b""), 2);
+}
+
+#[test]
+fn test_negative_cache() {
+    use std::time::Duration;
+    use crate::pathdb::DEFAULT_COLUMN_FAMILY_NAME;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path();
+    let mut config = PathProviderConfig::default();
+    config.negative_cache_size = Some(16);
+    config.negative_cache_ttl = Duration::from_millis(200);
+    let db = PathDB::new(db_path.to_str().unwrap(), config).unwrap();
+
+    let key = b"negative_key";
+    assert_eq!(db.get_raw_trie_node(key).unwrap(), None);
+    let before = crate::metrics_snapshot::snapshot();
+    assert_eq!(db.get_multi_raw_trie_nodes(&[key]).unwrap(), vec![None]);
+    assert!(!db.exists_raw_trie_node(key).unwrap());
+    assert!(crate::metrics_snapshot::snapshot().negative_cache_hits >= before.negative_cache_hits + 2);
+
+    // Writes through PathDB forget the key
+    db.put_raw_trie_node(key, b"value").unwrap();
+    assert_eq!(db.get_raw_trie_node(key).unwrap(), Some(b"value".to_vec().into()));
+
+    // Writes bypassing PathDB stay hidden until invalidated
+    let other_key = b"negative_other_key";
+    assert_eq!(db.get_raw_trie_node(other_key).unwrap(), None);
+    let default_cf = db.raw_db().cf_handle(DEFAULT_COLUMN_FAMILY_NAME).unwrap();
+    db.raw_db().put_cf(&default_cf, other_key, b"external").unwrap();
+    assert_eq!(db.get_raw_trie_node(other_key).unwrap(), None);
+    db.invalidate_keys([other_key]);
+    assert_eq!(db.get_raw_trie_node(other_key).unwrap(), Some(b"external".to_vec().into()));
+
+    // ... or until their entry expires
+    let expiring_key = b"negative_expiring_key";
+    assert!(!db.exists_raw_trie_node(expiring_key).unwrap());
+    db.raw_db().put_cf(&default_cf, expiring_key, b"late").unwrap();
+    assert!(!db.exists_raw_trie_node(expiring_key).unwrap());
+    std::thread::sleep(Duration::from_millis(300));
+    assert_eq!(db.get_raw_trie_node(expiring_key).unwrap(), Some(b"late".to_vec().into()));
+}
+#[test]
+fn test_key_namespace_isolation() {
+    use alloy_primitives::B256;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path();
+
+    let mut config = PathProviderConfig::default();
+    config.key_namespace = Some(b"chain_a".to_vec());
+    let chain_a = PathDB::new(db_path.to_str().unwrap(), config).unwrap();
+    let chain_b = chain_a.with_namespace(b"chain_b").unwrap();
+    assert_eq!(chain_a.namespace(), Some(&b"chain_a"[..]));
+    assert_eq!(chain_b.namespace(), Some(&b"chain_b"[..]));
+
+    // Same logical key, different values per namespace
+    let key = b"shared_key";
+    chain_a.put_raw_trie_node(key, b"value_a").unwrap();
+    chain_b.put_raw_trie_node(key, b"value_b").unwrap();
+
+    chain_a.clear_cache();
+    chain_b.clear_cache();
+    assert_eq!(chain_a.get_raw_trie_node(key).unwrap(), Some(b"value_a".to_vec().into()));
+    assert_eq!(chain_b.get_raw_trie_node(key).unwrap(), Some(b"value_b".to_vec().into()));
+
+    chain_b.delete_raw_trie_node(key).unwrap();
+    assert!(!chain_b.exists_raw_trie_node(key).unwrap());
+    assert!(chain_a.exists_raw_trie_node(key).unwrap());
+
+    // Persisted state is tracked per namespace
+    let state_root = B256::repeat_byte(0xab);
+    chain_a.commit_difflayer(42, state_root, &None).unwrap();
+    chain_a.clear_cache();
+    assert_eq!(chain_a.latest_persist_state().unwrap(), (42, state_root));
+    assert_eq!(chain_b.latest_persist_state().unwrap(), (0, alloy_trie::EMPTY_ROOT_HASH));
+
+    // Invalid namespaces are rejected
+    assert!(chain_a.with_namespace(b"").is_err());
+    assert!(chain_a.with_namespace(&[0u8; 256]).is_err());
+}
+
+#[test]
+fn test_overflow_storage() {
+    use crate::pathdb::{DEFAULT_COLUMN_FAMILY_NAME, OVERFLOW_COLUMN_FAMILY_NAME};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path();
+
+    let mut config = PathProviderConfig::default();
+    config.overflow_threshold = Some(16);
+    config.overflow_chunk_size = 8;
+    let db = PathDB::new(db_path.to_str().unwrap(), config).unwrap();
+
+    let key = b"large_node";
+    let large_value: Vec<u8> = (0..50u8).collect();
+    db.put_raw_trie_node(key, &large_value).unwrap();
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(key).unwrap(), Some(large_value.into()));
+
+    // Primary CF only holds a small pointer, chunks live in the overflow CF
+    let default_cf = db.raw_db().cf_handle(DEFAULT_COLUMN_FAMILY_NAME).unwrap();
+    let overflow_cf = db.raw_db().cf_handle(OVERFLOW_COLUMN_FAMILY_NAME).unwrap();
+    let pointer = db.raw_db().get_cf(&default_cf, key).unwrap().unwrap();
+    assert!(pointer.len() < 50);
+    let chunk_count = db.raw_db().iterator_cf(&overflow_cf, rocksdb::IteratorMode::Start).count();
+    assert_eq!(chunk_count, 7);
+
+    // Overwriting with a small value drops the old chunks
+    db.put_raw_trie_node(key, b"small").unwrap();
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(key).unwrap(), Some(b"small".to_vec().into()));
+    assert_eq!(db.raw_db().iterator_cf(&overflow_cf, rocksdb::IteratorMode::Start).count(), 0);
+
+    // Deleting removes the chunks as well
+    db.put_raw_trie_node(key, &[0xaa; 40]).unwrap();
+    db.delete_raw_trie_node(key).unwrap();
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(key).unwrap(), None);
+    assert_eq!(db.raw_db().iterator_cf(&overflow_cf, rocksdb::IteratorMode::Start).count(), 0);
+}
+
+#[test]
+fn test_overflow_chunks_removed_after_disabling() {
+    use crate::pathdb::OVERFLOW_COLUMN_FAMILY_NAME;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().to_str().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.overflow_threshold = Some(16);
+    config.overflow_chunk_size = 8;
+    let db = PathDB::new(db_path, config).unwrap();
+    db.put_raw_trie_node(b"replaced", &[0xaa; 40]).unwrap();
+    db.put_raw_trie_node(b"deleted", &[0xbb; 40]).unwrap();
+    drop(db);
+
+    // Chunks written with overflow enabled are still cleaned up once disabled
+    let db = PathDB::new(db_path, PathProviderConfig::default()).unwrap();
+    db.put_raw_trie_node(b"replaced", b"small").unwrap();
+    db.delete_raw_trie_node(b"deleted").unwrap();
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(b"replaced").unwrap(), Some(b"small".to_vec().into()));
+    assert_eq!(db.get_raw_trie_node(b"deleted").unwrap(), None);
+    let overflow_cf = db.raw_db().cf_handle(OVERFLOW_COLUMN_FAMILY_NAME).unwrap();
+    assert_eq!(db.raw_db().iterator_cf(&overflow_cf, rocksdb::IteratorMode::Start).count(), 0);
+}
+
+#[test]
+fn test_overflow_key_filter() {
+    use crate::overflow_filter::OverflowKeyFilter;
+
+    let filter = OverflowKeyFilter::new();
+    assert!(!filter.in_use());
+    assert!(!filter.may_contain(b"large_node"));
+
+    // Inserted keys always match, most others don't
+    filter.insert(b"large_node");
+    assert!(filter.in_use());
+    assert!(filter.may_contain(b"large_node"));
+    let false_positives = (0..1000u32).filter(|i| filter.may_contain(&i.to_be_bytes())).count();
+    assert!(false_positives < 10);
+}
+
+#[test]
+fn test_overflow_chunks_range_deleted() {
+    use crate::pathdb::OVERFLOW_COLUMN_FAMILY_NAME;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().to_str().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.overflow_threshold = Some(16);
+    config.overflow_chunk_size = 8;
+    let db = PathDB::new(db_path, config).unwrap();
+    let keys: [&[u8]; 6] = [b"O", b"O1", b"O1a", b"O1abcd", b"O2", b"O2x"];
+    for key in keys {
+        db.put_raw_trie_node(key, &[0xaa; 40]).unwrap();
+    }
+
+    // Chunks of the nodes in the range go with them, whatever their key length
+    db.delete_range_raw(b"O1", b"O2").unwrap();
+    db.clear_cache();
+    let overflow_cf = db.raw_db().cf_handle(OVERFLOW_COLUMN_FAMILY_NAME).unwrap();
+    assert_eq!(db.raw_db().iterator_cf(&overflow_cf, rocksdb::IteratorMode::Start).count(), 3 * 5);
+    for key in [b"O".as_slice(), b"O2", b"O2x"] {
+        assert_eq!(db.get_raw_trie_node(key).unwrap(), Some(vec![0xaa; 40].into()));
+    }
+}
+
+#[test]
+fn test_iter_trie_nodes_and_prefetch() {
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path();
+
+    let mut config = PathProviderConfig::default();
+    config.key_namespace = Some(b"walk".to_vec());
+    let db = PathDB::new(db_path.to_str().unwrap(), config).unwrap();
+    let other = db.with_namespace(b"other").unwrap();
+
+    for nibble in [3u8, 0, 15] {
+        db.put_raw_trie_node(&[b'A', 1, nibble], &[nibble]).unwrap();
+    }
+    db.put_raw_trie_node(&[b'A', 2], b"outside").unwrap();
+    other.put_raw_trie_node(&[b'A', 1, 7], b"other namespace").unwrap();
+
+    let nodes: Vec<(Vec<u8>, Vec<u8>)> = db.iter_trie_nodes(&[b'A', 1]).unwrap().map(|item| item.unwrap()).collect();
+    assert_eq!(nodes, vec![
+        (vec![b'A', 1, 0], vec![0]),
+        (vec![b'A', 1, 3], vec![3]),
+        (vec![b'A', 1, 15], vec![15]),
+    ]);
+    assert_eq!(db.iter_trie_nodes(&[]).unwrap().count(), 4);
+
+    db.clear_cache();
+    assert_eq!(db.prefetch_child_nodes(&[b'A', 1]).unwrap(), 3);
+    assert_eq!(db.cache_stats().0, 3);
+    assert_eq!(db.prefetch_child_nodes(&[b'A', 1]).unwrap(), 0);
+}
+
+#[test]
+fn test_iter_trie_nodes_skips_meta_keys() {
+    use alloy_primitives::B256;
+    use crate::migration::SCHEMA_VERSION_KEY;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    db.put_raw_trie_node(&[b'A', 1], b"node").unwrap();
+    let mut batch = crate::PathDBWriteBatch::new();
+    batch.set_persist_state(3, B256::repeat_byte(0x33));
+    db.write_batch(batch).unwrap();
+    let default_cf = db.raw_db().cf_handle(crate::pathdb::DEFAULT_COLUMN_FAMILY_NAME).unwrap();
+    db.raw_db().put_cf(&default_cf, SCHEMA_VERSION_KEY, 1u32.to_be_bytes()).unwrap();
+
+    let nodes: Vec<(Vec<u8>, Vec<u8>)> = db.iter_trie_nodes(&[]).unwrap().map(|item| item.unwrap()).collect();
+    assert_eq!(nodes, vec![(vec![b'A', 1], b"node".to_vec())]);
+    assert_eq!(db.iter_range(&[], None).unwrap().count(), 1);
+    assert_eq!(db.seek_prefix(crate::pathdb::DEFAULT_COLUMN_FAMILY_NAME, SCHEMA_VERSION_KEY).unwrap().count(), 0);
+    assert_eq!(db.latest_persist_state().unwrap(), (3, B256::repeat_byte(0x33)));
+}
+
+#[test]
+fn test_iter_trie_node_range_pages() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    for i in 0..5u8 {
+        db.put_raw_trie_node(&[b'A', i], &[i]).unwrap();
+    }
+    db.put_raw_trie_node(&[b'B', 0], b"other").unwrap();
+
+    let first = TrieDatabase::iter_trie_node_range(&db, &[b'A'], Some(&[b'B']), 3).unwrap();
+    assert_eq!(first.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), vec![vec![b'A', 0], vec![b'A', 1], vec![b'A', 2]]);
+
+    let next = TrieDatabase::iter_trie_node_range(&db, &[b'A', 3], Some(&[b'B']), 3).unwrap();
+    assert_eq!(next, vec![(vec![b'A', 3], vec![3u8].into()), (vec![b'A', 4], vec![4u8].into())]);
+    assert!(TrieDatabase::iter_trie_node_range(&db, &[b'A'], None, 0).unwrap().is_empty());
+}
+
+#[test]
+fn test_write_batch_cache_coherence() {
+    use alloy_primitives::B256;
+    use crate::PathDBWriteBatch;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path();
+    let db = PathDB::new(db_path.to_str().unwrap(), PathProviderConfig::default()).unwrap();
+
+    db.put_raw_trie_node(b"node_a", b"old_a").unwrap();
+    db.put_raw_trie_node(b"node_b", b"old_b").unwrap();
+    assert_eq!(db.get_raw_trie_node(b"node_a").unwrap(), Some(b"old_a".to_vec().into()));
+
+    let hashed_address = B256::repeat_byte(0x11);
+    let storage_root = B256::repeat_byte(0x22);
+    let mut batch = PathDBWriteBatch::new();
+    batch
+        .put_trie_node(b"node_a", b"first_a")
+        .put_trie_node(b"node_a", b"new_a")
+        .delete_trie_node(b"node_b")
+        .put_storage_root(hashed_address, storage_root);
+    assert_eq!(batch.len(), 4);
+    db.write_batch(batch).unwrap();
+
+    // Cached reads observe the batch without a cache clear
+    assert_eq!(db.get_raw_trie_node(b"node_a").unwrap(), Some(b"new_a".to_vec().into()));
+    assert_eq!(db.get_raw_trie_node(b"node_b").unwrap(), None);
+    assert_eq!(db.get_storage_root(hashed_address).unwrap(), Some(storage_root));
+
+    // And so does the database
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(b"node_a").unwrap(), Some(b"new_a".to_vec().into()));
+    assert_eq!(db.get_raw_trie_node(b"node_b").unwrap(), None);
+    assert_eq!(db.get_storage_root(hashed_address).unwrap(), Some(storage_root));
+
+    let mut batch = PathDBWriteBatch::new();
+    batch.delete_storage_root(hashed_address);
+    db.write_batch(batch).unwrap();
+    assert_eq!(db.get_storage_root(hashed_address).unwrap(), None);
+}
+
+#[test]
+fn test_deferred_deletion_queue() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use std::time::{Duration, Instant};
+    use alloy_primitives::B256;
+    use rust_eth_triedb_common::{DiffLayer, TrieNode};
+    use crate::DeletionWorker;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path();
+
+    let mut config = PathProviderConfig::default();
+    assert!(!config.maintenance_low_pri);
+    config.deferred_deletion = true;
+    config.deletion_batch_size = 1;
+    config.deletion_interval = Duration::from_millis(10);
+    let db = PathDB::new(db_path.to_str().unwrap(), config).unwrap();
+
+    let commit = |block_number: u64, nodes: Vec<(&[u8], Option<&[u8]>)>| {
+        let diff_nodes: HashMap<Vec<u8>, Arc<TrieNode>> = nodes.into_iter()
+            .map(|(key, blob)| (key.to_vec(), Arc::new(TrieNode::new(None, blob.map(|b| b.to_vec().into())))))
+            .collect();
+        let difflayer = Arc::new(DiffLayer::new(diff_nodes, HashMap::new()));
+        db.commit_difflayer(block_number, B256::ZERO, &Some(difflayer)).unwrap();
+    };
+
+    commit(1, vec![(b"A1", Some(b"node_1")), (b"A2", Some(b"node_2")), (b"A3", Some(b"node_3"))]);
+    commit(2, vec![(b"A1", None), (b"A2", None)]);
+
+    // Deleted nodes read as missing but stay on disk until processed
+    assert_eq!(db.get_raw_trie_node(b"A1").unwrap(), None);
+    assert_eq!(db.raw_db().get(b"A1").unwrap(), Some(b"node_1".to_vec()));
+
+    // Also once the cached deletion is evicted
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(b"A1").unwrap(), None);
+    assert!(!db.exists_raw_trie_node(b"A2").unwrap());
+    db.clear_cache();
+    assert_eq!(db.get_multi_raw_trie_nodes(&[b"A1", b"A3"]).unwrap(), vec![None, Some(b"node_3".to_vec().into())]);
+
+    // Re-creating a node cancels its pending deletion
+    commit(3, vec![(b"A2", Some(b"node_2_new"))]);
+
+    assert_eq!(db.process_deletion_queue(10).unwrap(), 1);
+    assert_eq!(db.process_deletion_queue(10).unwrap(), 0);
+    assert_eq!(db.raw_db().get(b"A1").unwrap(), None);
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(b"A2").unwrap(), Some(b"node_2_new".to_vec().into()));
+    assert_eq!(db.get_raw_trie_node(b"A3").unwrap(), Some(b"node_3".to_vec().into()));
+
+    // The background worker drains the queue in rate-limited batches
+    commit(4, vec![(b"A2", None), (b"A3", None)]);
+    let worker = DeletionWorker::spawn(db.clone()).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while db.raw_db().get(b"A3").unwrap().is_some() || db.raw_db().get(b"A2").unwrap().is_some() {
+        assert!(Instant::now() < deadline, "deletion worker didn't drain the queue");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    worker.stop();
+}
+
+#[test]
+fn test_flush_worker() {
+    use std::time::{Duration, Instant};
+    use crate::pathdb::{DEFAULT_COLUMN_FAMILY_NAME, STORAGE_ROOT_COLUMN_FAMILY_NAME};
+    use crate::FlushWorker;
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.flush_interval = Duration::from_millis(10);
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    let before = crate::metrics_snapshot::snapshot();
+    db.put_raw_trie_node(b"A1", b"node_1").unwrap();
+    assert_eq!(db.cf_int_property(DEFAULT_COLUMN_FAMILY_NAME, "rocksdb.num-entries-active-mem-table").unwrap(), 1);
+
+    let storage_root_cf = db.raw_db().cf_handle(STORAGE_ROOT_COLUMN_FAMILY_NAME).unwrap();
+    db.raw_db().put_cf(&storage_root_cf, [0x11; 32], [0x22; 32]).unwrap();
+    drop(storage_root_cf);
+
+    // The worker persists the memtables of all column families without any further writes
+    let worker = FlushWorker::spawn(db.clone()).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while db.cf_int_property(DEFAULT_COLUMN_FAMILY_NAME, "rocksdb.num-entries-active-mem-table").unwrap() > 0
+        || db.cf_int_property(STORAGE_ROOT_COLUMN_FAMILY_NAME, "rocksdb.num-entries-active-mem-table").unwrap() > 0
+    {
+        assert!(Instant::now() < deadline, "flush worker didn't flush the memtables");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    worker.stop();
+
+    assert!(crate::metrics_snapshot::snapshot().flush_duration.count > before.flush_duration.count);
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(b"A1").unwrap(), Some(b"node_1".to_vec().into()));
+}
+
+#[test]
+fn test_heal_progress_resume() {
+    use alloy_primitives::keccak256;
+    use crate::{HealProgress, HealRequest};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().to_str().unwrap().to_string();
+
+    let blobs: Vec<(Vec<u8>, Vec<u8>)> = (0u8..3)
+        .map(|i| (vec![b'A', i], vec![0xc0 + i; 40]))
+        .collect();
+    {
+        let db = PathDB::new(&db_path, PathProviderConfig::default()).unwrap();
+        let requests: Vec<HealRequest> = blobs.iter()
+            .map(|(key, blob)| HealRequest { key: key.clone(), hash: keccak256(blob) })
+            .collect();
+        db.add_heal_requests(&requests).unwrap();
+        assert_eq!(db.heal_progress().unwrap(), HealProgress { healed: 0, pending: 3 });
+
+        // Requests for queued keys are replaced, not counted again
+        db.add_heal_requests(&requests[..2]).unwrap();
+        assert_eq!(db.heal_progress().unwrap(), HealProgress { healed: 0, pending: 3 });
+
+        // A blob that doesn't match the requested hash is rejected
+        assert!(db.complete_heal_request(&blobs[0].0, b"bogus").is_err());
+        db.complete_heal_request(&blobs[0].0, &blobs[0].1).unwrap();
+        assert_eq!(db.heal_progress().unwrap(), HealProgress { healed: 1, pending: 2 });
+    }
+
+    // Reopening resumes from the outstanding requests
+    let db = PathDB::new(&db_path, PathProviderConfig::default()).unwrap();
+    let pending = db.pending_heal_requests(10).unwrap();
+    assert_eq!(pending.iter().map(|r| r.key.clone()).collect::<Vec<_>>(), vec![blobs[1].0.clone(), blobs[2].0.clone()]);
+    for request in pending {
+        let blob = &blobs.iter().find(|(key, _)| *key == request.key).unwrap().1;
+        db.complete_heal_request(&request.key, blob).unwrap();
+    }
+    assert_eq!(db.heal_progress().unwrap(), HealProgress { healed: 3, pending: 0 });
+    assert_eq!(db.get_raw_trie_node(&blobs[2].0).unwrap(), Some(blobs[2].1.clone().into()));
+
+    db.add_heal_requests(&[
+        HealRequest { key: vec![b'A', 8], hash: keccak256(b"x") },
+        HealRequest { key: vec![b'A', 9], hash: keccak256(b"y") },
+    ]).unwrap();
+    db.reset_heal_progress().unwrap();
+    assert_eq!(db.heal_progress().unwrap(), HealProgress::default());
+    assert!(db.pending_heal_requests(10).unwrap().is_empty());
+}
+
+#[test]
+fn test_open_creates_missing_and_keeps_extra_column_families() {
+    use crate::pathdb::{AUDIT_LOG_COLUMN_FAMILY_NAME, HEAL_QUEUE_COLUMN_FAMILY_NAME};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().to_str().unwrap();
+
+    // A database from an older layout with an unrelated Column Family
+    {
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let legacy = rocksdb::DB::open_cf(&opts, db_path, ["default", "legacy"]).unwrap();
+        let legacy_cf = legacy.cf_handle("legacy").unwrap();
+        legacy.put_cf(&legacy_cf, b"key", b"value").unwrap();
+    }
+
+    let db = PathDB::new(db_path, PathProviderConfig::default()).unwrap();
+    assert!(db.raw_db().cf_handle(AUDIT_LOG_COLUMN_FAMILY_NAME).is_some());
+    assert!(db.raw_db().cf_handle(HEAL_QUEUE_COLUMN_FAMILY_NAME).is_some());
+    let legacy_cf = db.raw_db().cf_handle("legacy").unwrap();
+    assert_eq!(db.raw_db().get_cf(&legacy_cf, b"key").unwrap(), Some(b"value".to_vec()));
+
+    db.put_raw_trie_node(b"node", b"blob").unwrap();
+    drop(legacy_cf);
+    drop(db);
+
+    // Reopening an up-to-date database works as well
+    let db = PathDB::new(db_path, PathProviderConfig::default()).unwrap();
+    assert_eq!(db.get_raw_trie_node(b"node").unwrap(), Some(b"blob".to_vec().into()));
+}
+
+#[test]
+fn test_iter_range() {
+    use alloy_primitives::B256;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path();
+    let db = PathDB::new(db_path.to_str().unwrap(), PathProviderConfig::default()).unwrap();
+
+    // Storage trie nodes of two owners
+    let (owner_a, owner_b) = (B256::repeat_byte(0xaa), B256::repeat_byte(0xbb));
+    let storage_key = |owner: B256, path: &[u8]| [b"O".as_slice(), owner.as_slice(), path].concat();
+    for path in [&[][..], &[1], &[1, 2]] {
+        db.put_raw_trie_node(&storage_key(owner_a, path), b"a").unwrap();
+        db.put_raw_trie_node(&storage_key(owner_b, path), b"b").unwrap();
+    }
+
+    // All nodes of one owner
+    let start = storage_key(owner_a, &[]);
+    let end = storage_key(owner_b, &[]);
+    let nodes: Vec<_> = db.iter_range(&start, Some(&end)).unwrap().map(|item| item.unwrap()).collect();
+    assert_eq!(nodes.len(), 3);
+    assert!(nodes.iter().all(|(key, value)| key.starts_with(&start) && value == b"a"));
+
+    // Open ended range
+    assert_eq!(db.iter_range(&end, None).unwrap().count(), 3);
+
+    // Caller-provided read options
+    let read_options = db.scan_read_options(Some(&start), Some(&end));
+    assert_eq!(db.iter_range_opt(&storage_key(owner_a, &[1]), Some(&end), read_options).unwrap().count(), 2);
+}
+
+#[test]
+fn test_checkpoint_and_restore() {
+    use std::sync::Arc;
+    use alloy_primitives::B256;
+    use rust_eth_triedb_common::DiffLayer;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let checkpoint_path = temp_dir.path().join("checkpoint");
+    let restore_path = temp_dir.path().join("restored");
+
+    let db = PathDB::new(db_path.to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    db.put_raw_trie_node(b"node", b"blob").unwrap();
+    let state_root = B256::repeat_byte(0x42);
+    db.commit_difflayer(9, state_root, &Some(Arc::new(DiffLayer::default()))).unwrap();
+
+    db.create_checkpoint(&checkpoint_path).unwrap();
+    assert!(db.create_checkpoint(&checkpoint_path).is_err());
+
+    // Writes after the checkpoint are not part of it
+    db.put_raw_trie_node(b"later", b"blob").unwrap();
+
+    let restored = PathDB::restore_from_checkpoint(&checkpoint_path, restore_path.to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    assert_eq!(restored.get_raw_trie_node(b"node").unwrap(), Some(b"blob".to_vec().into()));
+    assert_eq!(restored.get_raw_trie_node(b"later").unwrap(), None);
+
+    let info = restored.backup_info().unwrap();
+    assert_eq!((info.block_number, info.state_root), (9, state_root));
+    assert!(info.size_bytes > 0);
+
+    assert!(PathDB::restore_from_checkpoint(&checkpoint_path, restore_path.to_str().unwrap(), PathProviderConfig::default()).is_err());
+}
+
+#[test]
+fn test_amplification_window() {
+    use crate::amplification::AmplificationWindow;
+
+    let mut window = AmplificationWindow::new(3);
+    assert_eq!(window.write_amplification(), None);
+
+    window.record(100, 1000);
+    assert_eq!(window.write_amplification(), None);
+    window.record(100, 1300);
+    assert_eq!(window.write_amplification(), Some(3.0));
+    window.record(100, 1700);
+
+    // The oldest sample slides out of the window
+    window.record(100, 2500);
+    assert_eq!(window.deltas(), (200, 1200));
+    assert_eq!(window.write_amplification(), Some(6.0));
+}
+
+#[test]
+fn test_amplification_report() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::B256;
+    use rust_eth_triedb_common::{DiffLayer, TrieNode};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.enable_statistics = true;
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    for block_number in 1..=4u64 {
+        let key = format!("A{}", block_number).into_bytes();
+        let diff_nodes = HashMap::from([(key, Arc::new(TrieNode::new(None, Some(vec![block_number as u8; 256].into()))))]);
+        let difflayer = Arc::new(DiffLayer::new(diff_nodes, HashMap::new()));
+        db.commit_difflayer(block_number, B256::ZERO, &Some(difflayer)).unwrap();
+    }
+
+    let report = db.amplification_report().unwrap();
+    assert!(report.window_commit_bytes > 0);
+    assert!(report.window_disk_write_bytes > 0);
+    assert!(report.write_amplification.unwrap() > 0.0);
+}
+
+#[test]
+fn test_read_only_and_secondary() {
+    use std::time::{Duration, Instant};
+    use crate::{CatchUpWorker, PathProviderError, PathProviderManager};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().join("db");
+    let db_path = db_path.to_str().unwrap();
+    let secondary_path = temp_dir.path().join("secondary");
+    let secondary_path = secondary_path.to_str().unwrap();
+
+    // Without write access, missing Column Families can't be created
+    assert!(PathDB::open_read_only(db_path, PathProviderConfig::default()).is_err());
+
+    let primary = PathDB::new(db_path, PathProviderConfig::default()).unwrap();
+    primary.put_raw_trie_node(b"A1", b"node_1").unwrap();
+    primary.flush().unwrap();
+
+    let read_only = PathDB::open_read_only(db_path, PathProviderConfig::default()).unwrap();
+    assert!(read_only.is_read_only());
+    assert_eq!(read_only.get_raw_trie_node(b"A1").unwrap(), Some(b"node_1".to_vec().into()));
+    assert!(read_only.put_raw_trie_node(b"A2", b"node_2").is_err());
+    assert!(matches!(read_only.wipe_account(alloy_primitives::B256::ZERO), Err(PathProviderError::InvalidOperation(_))));
+    assert!(read_only.try_catch_up_with_primary().is_err());
+
+    let secondary = PathDB::open_as_secondary(db_path, secondary_path, PathProviderConfig::default()).unwrap();
+    assert_eq!(secondary.get_raw_trie_node(b"A1").unwrap(), Some(b"node_1".to_vec().into()));
+    assert_eq!(secondary.get_raw_trie_node(b"A2").unwrap(), None);
+
+    primary.put_raw_trie_node(b"A2", b"node_2").unwrap();
+    // The cached miss is dropped on catch up
+    secondary.try_catch_up_with_primary().unwrap();
+    assert_eq!(secondary.get_raw_trie_node(b"A2").unwrap(), Some(b"node_2".to_vec().into()));
+
+    primary.put_raw_trie_node(b"A3", b"node_3").unwrap();
+    let worker = CatchUpWorker::spawn(secondary.clone(), Duration::from_millis(10)).unwrap();
+    let deadline = Instant::now() + Duration::from_secs(5);
+    while secondary.get_raw_trie_node(b"A3").unwrap().is_none() {
+        assert!(Instant::now() < deadline, "secondary did not catch up");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    worker.stop();
+
+    assert!(CatchUpWorker::spawn(primary.clone(), Duration::from_millis(10)).is_err());
+}
+
+#[test]
+fn test_seek_prefix_with_owner_extractor() {
+    use alloy_primitives::B256;
+    use crate::{PathProviderManager, PrefixExtractor};
+    use crate::pathdb::{DEFAULT_COLUMN_FAMILY_NAME, STORAGE_ROOT_COLUMN_FAMILY_NAME};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.prefix_extractor = PrefixExtractor::Owner;
+    config.key_namespace = Some(b"ns".to_vec());
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    let storage_key = |owner: u8, path: &[u8]| [b"O".as_slice(), &[owner; 32], path].concat();
+    for owner in [1u8, 2, 3] {
+        for nibble in 0u8..4 {
+            db.put_raw_trie_node(&storage_key(owner, &[nibble]), &[owner, nibble]).unwrap();
+        }
+    }
+    db.put_raw_trie_node(b"A", b"root").unwrap();
+    db.put_raw_trie_node(b"A\x01", b"child").unwrap();
+    db.flush().unwrap();
+
+    // A full owner prefix stays within the storage trie of that owner
+    let owner_prefix = storage_key(2, &[]);
+    let nodes: Vec<_> = db.seek_prefix(DEFAULT_COLUMN_FAMILY_NAME, &owner_prefix).unwrap().map(Result::unwrap).collect();
+    assert_eq!(nodes.len(), 4);
+    assert!(nodes.iter().all(|(key, value)| key.starts_with(&owner_prefix) && value[0] == 2));
+
+    let nodes: Vec<_> = db.seek_prefix(DEFAULT_COLUMN_FAMILY_NAME, &storage_key(2, &[3])).unwrap().map(Result::unwrap).collect();
+    assert_eq!(nodes, vec![(storage_key(2, &[3]), vec![2, 3])]);
+    assert_eq!(db.seek_prefix(DEFAULT_COLUMN_FAMILY_NAME, &storage_key(4, &[])).unwrap().count(), 0);
+
+    // Prefixes shorter than the extracted prefix walk in total order
+    assert_eq!(db.seek_prefix(DEFAULT_COLUMN_FAMILY_NAME, b"O").unwrap().count(), 12);
+    assert_eq!(db.seek_prefix(DEFAULT_COLUMN_FAMILY_NAME, b"A").unwrap().count(), 2);
+    assert_eq!(db.iter_trie_nodes(b"").unwrap().count(), 14);
+
+    // Other column families yield their raw entries
+    let mut batch = crate::PathDBWriteBatch::new();
+    batch.put_storage_root(B256::repeat_byte(0x11), B256::repeat_byte(0xaa));
+    batch.put_storage_root(B256::repeat_byte(0x22), B256::repeat_byte(0xbb));
+    db.write_batch(batch).unwrap();
+    let roots: Vec<_> = db.seek_prefix(STORAGE_ROOT_COLUMN_FAMILY_NAME, &[0x22]).unwrap().map(Result::unwrap).collect();
+    assert_eq!(roots, vec![(vec![0x22; 32], vec![0xbb; 32])]);
+    assert!(matches!(db.seek_prefix("missing", b"").map(|iter| iter.count()), Err(crate::PathProviderError::ColumnFamilyMissing { .. })));
+
+    // Bulk loaded files are written with the prefix extractor of the column family
+    let sst_dir = TempDir::new().unwrap();
+    let mut loader = db.sst_bulk_loader(sst_dir.path()).unwrap();
+    for nibble in 0u8..3 {
+        loader.add(&storage_key(5, &[nibble]), &[5, nibble]).unwrap();
+    }
+    assert_eq!(loader.finish().unwrap(), 3);
+    assert_eq!(db.seek_prefix(DEFAULT_COLUMN_FAMILY_NAME, &storage_key(5, &[])).unwrap().count(), 3);
+}
+
+#[test]
+fn test_compression_config() {
+    use crate::{CompressionConfig, CompressionType, PathProviderManager};
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.compression = CompressionConfig { zstd_max_dict_bytes: 1024, ..Default::default() };
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+
+    let mut config = PathProviderConfig::default();
+    config.compression = CompressionConfig::recommended();
+    assert_eq!(config.compression.bottommost, Some(CompressionType::Zstd));
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    let blob = [0x5a; 512];
+    for i in 0u16..1000 {
+        db.put_raw_trie_node(&[b"A".as_slice(), &i.to_be_bytes()].concat(), &blob).unwrap();
+    }
+    db.flush().unwrap();
+    // Compaction moves the data to the bottommost level and trains the dictionary
+    db.raw_db().compact_range(None::<&[u8]>, None::<&[u8]>);
+    db.clear_cache();
+
+    for i in 0u16..1000 {
+        assert_eq!(db.get_raw_trie_node(&[b"A".as_slice(), &i.to_be_bytes()].concat()).unwrap(), Some(blob.to_vec().into()));
+    }
+}
+
+#[test]
+fn test_verified_reads() {
+    use crate::PathProviderManager;
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.paranoid_checks = true;
+    config.verify_checksums = false;
+    config.verified_readahead_size = 256 * 1024;
+    config.overflow_threshold = Some(64);
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    let large = [0x33; 200];
+    db.put_raw_trie_node(b"small", b"value").unwrap();
+    db.put_raw_trie_node(b"large", &large).unwrap();
+    db.flush().unwrap();
+    db.clear_cache();
+
+    // Verified reads serve the same nodes, overflow chunks included
+    assert_eq!(db.get_verified_raw_trie_node(b"small").unwrap(), Some(b"value".to_vec().into()));
+    assert_eq!(db.get_verified_raw_trie_node(b"large").unwrap(), Some(large.to_vec().into()));
+    assert_eq!(db.get_verified_raw_trie_node(b"missing").unwrap(), None);
+    assert_eq!(db.get_trie_node_verified(b"large").unwrap(), db.get_trie_node(b"large").unwrap());
+
+    // Verified reads go to disk even when the node is cached
+    assert_eq!(db.get_raw_trie_node(b"small").unwrap(), Some(b"value".to_vec().into()));
+    db.raw_db().put(b"small", b"rewritten").unwrap();
+    assert_eq!(db.get_raw_trie_node(b"small").unwrap(), Some(b"value".to_vec().into()));
+    assert_eq!(db.get_verified_raw_trie_node(b"small").unwrap(), Some(b"rewritten".to_vec().into()));
+}
+
+#[test]
+fn test_blob_files() {
+    use crate::pathdb::DEFAULT_COLUMN_FAMILY_NAME;
+    use crate::{BlobConfig, PathProviderManager};
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.blob_files = Some(BlobConfig { gc_age_cutoff: 1.5, ..Default::default() });
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+
+    let mut config = PathProviderConfig::default();
+    config.blob_files = Some(BlobConfig { min_blob_size: 256, ..Default::default() });
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    let small = [0x11; 64];
+    let large = [0x22; 1024];
+    for i in 0u16..100 {
+        db.put_raw_trie_node(&[b"A".as_slice(), &i.to_be_bytes()].concat(), &small).unwrap();
+        db.put_raw_trie_node(&[b"B".as_slice(), &i.to_be_bytes()].concat(), &large).unwrap();
+    }
+    db.flush().unwrap();
+    db.clear_cache();
+
+    // Large nodes went to blob files, small ones stayed inline
+    assert!(db.cf_int_property(DEFAULT_COLUMN_FAMILY_NAME, "rocksdb.num-blob-files").unwrap() > 0);
+    assert!(db.cf_int_property(DEFAULT_COLUMN_FAMILY_NAME, "rocksdb.total-blob-file-size").unwrap() >= 100 * large.len() as u64);
+    for i in 0u16..100 {
+        assert_eq!(db.get_raw_trie_node(&[b"A".as_slice(), &i.to_be_bytes()].concat()).unwrap(), Some(small.to_vec().into()));
+        assert_eq!(db.get_raw_trie_node(&[b"B".as_slice(), &i.to_be_bytes()].concat()).unwrap(), Some(large.to_vec().into()));
+    }
+}
+
+#[test]
+fn test_direct_io() {
+    use crate::PathProviderManager;
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.use_direct_reads = true;
+    config.use_direct_io_for_flush_and_compaction = true;
+    config.compaction_readahead_size = 4 * 1024 * 1024;
+    // Some file systems, e.g. tmpfs, do not support O_DIRECT
+    let Ok(db) = PathDB::new(temp_dir.path().to_str().unwrap(), config) else {
+        return;
+    };
+
+    for i in 0u16..100 {
+        db.put_raw_trie_node(&i.to_be_bytes(), &[0x33; 256]).unwrap();
+    }
+    db.flush().unwrap();
+    db.raw_db().compact_range(None::<&[u8]>, None::<&[u8]>);
+    db.clear_cache();
+
+    for i in 0u16..100 {
+        assert_eq!(db.get_raw_trie_node(&i.to_be_bytes()).unwrap(), Some(vec![0x33; 256].into()));
+    }
+}
+
+#[test]
+fn test_block_based_table_config() {
+    use crate::{PathProviderManager, PrefixExtractor};
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.block_cache_size = Some(0);
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+    let mut config = PathProviderConfig::default();
+    config.bloom_filter_bits_per_key = Some(0.0);
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+
+    let mut config = PathProviderConfig::default();
+    config.block_cache_size = Some(8 * 1024 * 1024);
+    config.bloom_filter_bits_per_key = Some(10.0);
+    config.cache_index_and_filter_blocks = true;
+    config.pin_l0_filter_and_index_blocks_in_cache = true;
+    config.prefix_extractor = PrefixExtractor::Owner;
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    for i in 0u16..1000 {
+        db.put_raw_trie_node(&[b"A".as_slice(), &i.to_be_bytes()].concat(), &i.to_le_bytes()).unwrap();
+    }
+    db.flush().unwrap();
+    db.clear_cache();
+
+    for i in 0u16..1000 {
+        assert_eq!(db.get_raw_trie_node(&[b"A".as_slice(), &i.to_be_bytes()].concat()).unwrap(), Some(i.to_le_bytes().to_vec().into()));
+    }
+    assert_eq!(db.get_raw_trie_node(&[b"A".as_slice(), &1000u16.to_be_bytes()].concat()).unwrap(), None);
+}
+
+#[test]
+fn test_cf_overrides() {
+    use alloy_primitives::B256;
+    use crate::pathdb::{DEFAULT_COLUMN_FAMILY_NAME, STORAGE_ROOT_COLUMN_FAMILY_NAME};
+    use crate::{CfConfig, PathDBWriteBatch};
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.cf_overrides.insert(STORAGE_ROOT_COLUMN_FAMILY_NAME.to_string(), CfConfig {
+        bloom_filter_bits_per_key: Some(0.0),
+        ..Default::default()
+    });
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+
+    let mut config = PathProviderConfig::default();
+    config.cache_shards = 1;
+    config.cf_overrides.insert(DEFAULT_COLUMN_FAMILY_NAME.to_string(), CfConfig {
+        write_buffer_size: Some(64 * 1024 * 1024),
+        bloom_filter_bits_per_key: Some(10.0),
+        cache_size: Some(32),
+        ..Default::default()
+    });
+    config.cf_overrides.insert(STORAGE_ROOT_COLUMN_FAMILY_NAME.to_string(), CfConfig {
+        write_buffer_size: Some(4 * 1024 * 1024),
+        max_write_buffer_number: Some(2),
+        target_file_size_base: Some(16 * 1024 * 1024),
+        level0_file_num_compaction_trigger: Some(8),
+        cache_size: Some(4),
+        ..Default::default()
+    });
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    let mut batch = PathDBWriteBatch::new();
+    for i in 0u8..64 {
+        batch.put_trie_node(&[b"A".as_slice(), &[i]].concat(), &[i]);
+        batch.put_storage_root(B256::with_last_byte(i), B256::repeat_byte(i));
+    }
+    db.write_batch(batch).unwrap();
+
+    // Each cache is bounded by its Column Family's size
+    assert_eq!(db.cache_stats(), (32, 4));
+    for i in 0u8..64 {
+        assert_eq!(db.get_raw_trie_node(&[b"A".as_slice(), &[i]].concat()).unwrap(), Some(vec![i].into()));
+        assert_eq!(db.get_raw_storage_root(B256::with_last_byte(i).as_slice()).unwrap(), Some(B256::repeat_byte(i).to_vec().into()));
+    }
+}
+
+#[test]
+fn test_compaction_style() {
+    use crate::pathdb::{AUDIT_LOG_COLUMN_FAMILY_NAME, DEFAULT_COLUMN_FAMILY_NAME};
+    use crate::{CfConfig, CompactionStyle, PathProviderManager};
+
+    let temp_dir = TempDir::new().unwrap();
+
+    // FIFO compaction is refused wherever it would drop trie data
+    let mut config = PathProviderConfig::default();
+    config.compaction_style = CompactionStyle::fifo();
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+    let mut config = PathProviderConfig::default();
+    config.cf_overrides.insert(DEFAULT_COLUMN_FAMILY_NAME.to_string(), CfConfig {
+        compaction_style: Some(CompactionStyle::fifo()),
+        ..Default::default()
+    });
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+
+    let mut config = PathProviderConfig::default();
+    config.compaction_style = CompactionStyle::universal();
+    config.cf_overrides.insert(AUDIT_LOG_COLUMN_FAMILY_NAME.to_string(), CfConfig {
+        compaction_style: Some(CompactionStyle::fifo()),
+        ..Default::default()
+    });
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    for round in 0u8..4 {
+        for i in 0u16..100 {
+            db.put_raw_trie_node(&[b"A".as_slice(), &i.to_be_bytes()].concat(), &[round; 32]).unwrap();
+        }
+        db.flush().unwrap();
+    }
+    db.compact(None).unwrap();
+    db.clear_cache();
+
+    for i in 0u16..100 {
+        assert_eq!(db.get_raw_trie_node(&[b"A".as_slice(), &i.to_be_bytes()].concat()).unwrap(), Some(vec![3; 32].into()));
+    }
+}
+
+#[test]
+fn test_cache_max_value_size() {
+    use alloy_primitives::B256;
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.cache_max_value_size = Some(0);
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+
+    let mut config = PathProviderConfig::default();
+    config.cache_max_value_size = Some(100);
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    let before = crate::metrics_snapshot::snapshot();
+    let large = [0x44; 101];
+    db.put_raw_trie_node(b"A1", &[0x11; 100]).unwrap();
+    db.put_raw_trie_node(b"A2", &large).unwrap();
+    assert_eq!(db.cache_stats().0, 1);
+
+    // A large value replacing a cached one drops the stale entry
+    db.put_raw_trie_node(b"A1", &large).unwrap();
+    assert_eq!(db.cache_stats().0, 0);
+    assert_eq!(db.get_raw_trie_node(b"A1").unwrap(), Some(large.to_vec().into()));
+    assert_eq!(db.get_raw_trie_node(b"A2").unwrap(), Some(large.to_vec().into()));
+    assert_eq!(db.cache_stats().0, 0);
+
+    // Bypassed inserts are reported with the next commit
+    db.commit_difflayer(1, B256::ZERO, &None).unwrap();
+    assert!(crate::metrics_snapshot::snapshot().cache_bypassed_inserts >= before.cache_bypassed_inserts + 4);
+}
+
+#[test]
+fn test_hot_set_and_cold_storage() {
+    use crate::pathdb::DEFAULT_COLUMN_FAMILY_NAME;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    assert!(db.hot_set(10).is_empty());
+    assert!(db.move_to_cold_storage(b"B").is_err());
+    drop(db);
+
+    let mut config = PathProviderConfig::default();
+    config.access_sample_rate = 1;
+    config.cold_storage = true;
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    for i in 0u8..16 {
+        db.put_raw_trie_node(&[b'A', i], &[i]).unwrap();
+        db.put_raw_trie_node(&[b'B', i], &[i]).unwrap();
+    }
+    for _ in 0..3 {
+        for i in 0u8..4 {
+            db.get_raw_trie_node(&[b'A', i]).unwrap();
+        }
+    }
+    db.get_raw_trie_node(&[b'A', 4]).unwrap();
+
+    let hot_set = db.hot_set(5);
+    assert_eq!(hot_set.len(), 5);
+    assert!(hot_set[..4].iter().all(|(key, count)| key[0] == b'A' && key[1] < 4 && *count == 3));
+    assert_eq!(hot_set[4], (vec![b'A', 4], 1));
+
+    // Tracked paths stay in the primary column family
+    assert_eq!(db.move_to_cold_storage(b"A").unwrap(), 11);
+    assert_eq!(db.move_to_cold_storage(b"B").unwrap(), 16);
+    let default_cf = db.raw_db().cf_handle(DEFAULT_COLUMN_FAMILY_NAME).unwrap();
+    assert!(db.raw_db().get_cf(&default_cf, [b'A', 0]).unwrap().is_some());
+    assert!(db.raw_db().get_cf(&default_cf, [b'B', 0]).unwrap().is_none());
+
+    // Cold nodes are read back, and deleting them doesn't leave a cold copy behind
+    db.clear_cache();
+    for i in 0u8..16 {
+        assert_eq!(db.get_raw_trie_node(&[b'B', i]).unwrap(), Some(vec![i].into()));
+        assert!(db.exists_raw_trie_node(&[b'A', i]).unwrap());
+    }
+    db.delete_raw_trie_node(&[b'B', 0]).unwrap();
+    db.delete_range_raw(&[b'B', 8], &[b'B', 16]).unwrap();
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(&[b'B', 0]).unwrap(), None);
+    assert_eq!(db.get_raw_trie_node(&[b'B', 1]).unwrap(), Some(vec![1].into()));
+    assert_eq!(db.get_multi_raw_trie_nodes(&[[b'B', 7], [b'B', 8]]).unwrap(), vec![Some(vec![7].into()), None]);
+
+    db.reset_access_stats();
+    assert!(db.hot_set(10).is_empty());
+}
+
+#[test]
+fn test_drop_and_archive_column_family() {
+    use crate::pathdb::STORAGE_ROOT_COLUMN_FAMILY_NAME;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().join("db").to_str().unwrap(), PathProviderConfig::default()).unwrap();
+
+    assert!(db.drop_column_family(STORAGE_ROOT_COLUMN_FAMILY_NAME).is_err());
+    assert!(db.drop_column_family("experiment").is_err());
+
+    db.create_column_family("experiment").unwrap();
+    assert!(db.create_column_family("experiment").is_err());
+    assert!(db.column_families().contains(&"experiment".to_string()));
+    let cf = db.raw_db().cf_handle("experiment").unwrap();
+    for i in 0u8..10 {
+        db.raw_db().put_cf(&cf, [i], [i; 4]).unwrap();
+    }
+    drop(cf);
+
+    let archive_path = temp_dir.path().join("experiment.sst");
+    assert_eq!(db.archive_column_family("experiment", &archive_path).unwrap(), 10);
+    assert!(archive_path.exists());
+    assert!(db.raw_db().cf_handle("experiment").is_none());
+    assert!(!db.column_families().contains(&"experiment".to_string()));
+
+    // The archive can be ingested again
+    db.create_column_family("experiment").unwrap();
+    let cf = db.raw_db().cf_handle("experiment").unwrap();
+    db.raw_db().ingest_external_file_cf(&cf, vec![&archive_path]).unwrap();
+    assert_eq!(db.raw_db().get_cf(&cf, [3u8]).unwrap(), Some(vec![3; 4]));
+    drop(cf);
+
+    db.drop_column_family("experiment").unwrap();
+    db.create_column_family("experiment").unwrap();
+    assert_eq!(db.archive_column_family("experiment", temp_dir.path().join("empty.sst")).unwrap(), 0);
+    assert!(!temp_dir.path().join("empty.sst").exists());
+}
+
+#[test]
+fn test_cache_invalidation() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+
+    db.put_raw_trie_node(b"A1", b"node_1").unwrap();
+    db.put_raw_trie_node(b"A2", b"node_2").unwrap();
+    db.put_raw_trie_node(b"B1", b"node_3").unwrap();
+    assert_eq!(db.cache_stats().0, 3);
+    // Writes through PathDB are not external
+    assert!(!db.invalidate_external_writes());
+
+    assert_eq!(db.invalidate_keys([b"B1"]), 1);
+    assert_eq!(db.invalidate_prefix(b"A"), 2);
+    assert_eq!(db.cache_stats().0, 0);
+    assert_eq!(db.get_raw_trie_node(b"A1").unwrap(), Some(b"node_1".to_vec().into()));
+    assert_eq!(db.get_raw_trie_node(b"A2").unwrap(), Some(b"node_2".to_vec().into()));
+
+    // Cached values go stale on writes through the raw handle
+    db.raw_db().put(b"A1", b"external").unwrap();
+    db.raw_db().delete(b"A2").unwrap();
+    db.put_raw_trie_node(b"B1", b"node_4").unwrap();
+    assert_eq!(db.get_raw_trie_node(b"A1").unwrap(), Some(b"node_1".to_vec().into()));
+
+    assert!(db.invalidate_external_writes());
+    assert!(!db.invalidate_external_writes());
+    assert_eq!(db.get_raw_trie_node(b"A1").unwrap(), Some(b"external".to_vec().into()));
+    assert_eq!(db.get_raw_trie_node(b"A2").unwrap(), None);
+    assert_eq!(db.get_raw_trie_node(b"B1").unwrap(), Some(b"node_4".to_vec().into()));
+}
+
+#[test]
+fn test_write_durability() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::B256;
+    use rust_eth_triedb_common::{DiffLayer, TrieNode};
+    use crate::WriteDurability;
+
+    let temp_dir = TempDir::new().unwrap();
+
+    let mut config = PathProviderConfig::default();
+    config.disable_wal = true;
+    config.sync_writes = true;
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+
+    let mut config = PathProviderConfig::default();
+    config.disable_wal = true;
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+    db.put_raw_trie_node(b"A1", b"node_1").unwrap();
+
+    let commit = |block_number: u64, durability: WriteDurability| {
+        let node = Arc::new(TrieNode::new(None, Some(vec![block_number as u8].into())));
+        let difflayer = Arc::new(DiffLayer::new(HashMap::from([(b"A2".to_vec(), node)]), HashMap::new()));
+        db.commit_difflayer_with_durability(block_number, B256::with_last_byte(block_number as u8), &Some(difflayer), durability)
+    };
+    assert!(commit(1, WriteDurability { disable_wal: true, sync: true }).is_err());
+    commit(2, WriteDurability::synced()).unwrap();
+    commit(3, db.config().write_durability()).unwrap();
+
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(b"A1").unwrap(), Some(b"node_1".to_vec().into()));
+    assert_eq!(db.get_raw_trie_node(b"A2").unwrap(), Some(vec![3].into()));
+    assert_eq!(db.latest_persist_state().unwrap(), (3, B256::with_last_byte(3)));
+}
+
+#[test]
+fn test_close_gracefully_and_warm_cache() {
+    use crate::PathProviderManager;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().to_str().unwrap();
+
+    let db = PathDB::new(db_path, PathProviderConfig::default()).unwrap();
+    // Nothing persisted yet
+    assert_eq!(db.warm_cache().unwrap(), 0);
+    for i in 0u8..10 {
+        db.put_raw_trie_node(&[b'A', i], &[i]).unwrap();
+    }
+    db.delete_raw_trie_node(&[b'A', 9]).unwrap();
+    let mut batch = crate::PathDBWriteBatch::new();
+    batch.put_storage_root(alloy_primitives::B256::repeat_byte(1), alloy_primitives::B256::repeat_byte(2));
+    db.write_batch(batch).unwrap();
+    let worker = crate::DiskUsageWorker::spawn(db.clone()).unwrap();
+    assert!(!db.is_closed());
+    db.close().unwrap();
+    assert!(db.is_closed());
+    // The worker was joined by the close, and every column family flushed
+    worker.stop();
+    assert_eq!(db.cf_int_property(crate::pathdb::STORAGE_ROOT_COLUMN_FAMILY_NAME, "rocksdb.num-entries-active-mem-table").unwrap(), 0);
+    // Closing is idempotent and reads keep working
+    db.close_gracefully().unwrap();
+    assert_eq!(db.get_raw_trie_node(&[b'A', 0]).unwrap(), Some(vec![0].into()));
+    drop(db);
+
+    let db = PathDB::new(db_path, PathProviderConfig::default()).unwrap();
+    assert_eq!(db.cache_stats().0, 0);
+    assert_eq!(db.warm_cache().unwrap(), 9);
+    assert_eq!(db.cache_stats().0, 9);
+    for i in 0u8..9 {
+        assert_eq!(db.get_raw_trie_node(&[b'A', i]).unwrap(), Some(vec![i].into()));
+    }
+}
+
+#[test]
+fn test_get_multi_raw_trie_nodes() {
+    use alloy_primitives::Bytes;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.overflow_threshold = Some(16);
+    config.overflow_chunk_size = 8;
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    db.put_raw_trie_node(b"A1", b"node_1").unwrap();
+    db.put_raw_trie_node(b"A2", &[0x42; 40]).unwrap();
+    db.put_raw_trie_node(b"A3", b"node_3").unwrap();
+    db.clear_cache();
+    // One cache hit, the rest is read from the database
+    assert_eq!(db.get_raw_trie_node(b"A3").unwrap(), Some(b"node_3".to_vec().into()));
+
+    let keys: Vec<&[u8]> = vec![b"A1", b"A4", b"A2", b"A3"];
+    let expected: Vec<Option<Bytes>> = vec![Some(Bytes::from_static(b"node_1")), None, Some(vec![0x42; 40].into()), Some(Bytes::from_static(b"node_3"))];
+    assert_eq!(db.get_multi_raw_trie_nodes(&keys).unwrap(), expected);
+    assert_eq!(db.cache_stats().0, 3);
+    assert_eq!(db.get_trie_nodes(&keys.iter().map(|key| key.to_vec()).collect::<Vec<_>>()).unwrap(), expected);
+}
+
+#[test]
+fn test_delete_range() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::B256;
+    use rust_eth_triedb_common::{DiffLayer, TrieNode};
+    use crate::pathdb::{DEFAULT_COLUMN_FAMILY_NAME, STORAGE_ROOT_COLUMN_FAMILY_NAME};
+    use crate::{PathDBWriteBatch, PathProviderError};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.key_namespace = Some(b"range".to_vec());
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+    let other = db.with_namespace(b"other").unwrap();
+
+    for key in [b"O1a".as_slice(), b"O1b", b"O2a", b"O3a"] {
+        db.put_raw_trie_node(key, key).unwrap();
+        other.put_raw_trie_node(key, key).unwrap();
+    }
+    assert_eq!(db.get_raw_trie_node(b"O2a").unwrap(), Some(b"O2a".to_vec().into()));
+
+    // Cached and stored entries in the range are gone, other namespaces are untouched
+    db.delete_range_raw(b"O1", b"O3").unwrap();
+    assert_eq!(db.get_raw_trie_node(b"O2a").unwrap(), None);
+    let keys: Vec<Vec<u8>> = db.iter_trie_nodes(b"O").unwrap().map(|item| item.unwrap().0).collect();
+    assert_eq!(keys, vec![b"O3a".to_vec()]);
+    assert_eq!(other.iter_trie_nodes(b"O").unwrap().count(), 4);
+    assert!(matches!(db.delete_range_raw(b"O3", b"O1"), Err(PathProviderError::InvalidOperation(_))));
+
+    let (hashed_address, storage_root) = (B256::repeat_byte(0x11), B256::repeat_byte(0x22));
+    let mut batch = PathDBWriteBatch::new();
+    batch.put_storage_root(hashed_address, storage_root);
+    db.write_batch(batch).unwrap();
+    db.delete_range_cf(STORAGE_ROOT_COLUMN_FAMILY_NAME, hashed_address.as_slice(), B256::repeat_byte(0x12).as_slice()).unwrap();
+    assert_eq!(db.get_storage_root(hashed_address).unwrap(), None);
+
+    // A committed difflayer wipes its ranges before writing its nodes
+    let difflayer = DiffLayer::new(
+        HashMap::from([(b"O3b".to_vec(), Arc::new(TrieNode::new(Some(B256::ZERO), Some(b"new".to_vec().into()))))]),
+        HashMap::new(),
+    ).with_deleted_ranges(vec![(b"O3".to_vec(), b"O4".to_vec())]);
+    db.commit_difflayer(1, B256::ZERO, &Some(Arc::new(difflayer))).unwrap();
+    let nodes: Vec<(Vec<u8>, Vec<u8>)> = db.iter_trie_nodes(b"O").unwrap().map(|item| item.unwrap()).collect();
+    assert_eq!(nodes, vec![(b"O3b".to_vec(), b"new".to_vec())]);
+    assert_eq!(db.get_raw_trie_node(b"O3a").unwrap(), None);
+
+    // Range deletions are invisible in the WAL, so external ones clear the caches
+    db.get_raw_trie_node(b"O3b").unwrap();
+    let cf = db.raw_db().cf_handle(DEFAULT_COLUMN_FAMILY_NAME).unwrap();
+    db.raw_db().delete_range_cf(&cf, b"\x05rangeO3".as_slice(), b"\x05rangeO4".as_slice()).unwrap();
+    assert!(db.invalidate_external_writes());
+    assert_eq!(db.cache_stats(), (0, 0));
+    assert_eq!(db.get_raw_trie_node(b"O3b").unwrap(), None);
+}
+
+#[test]
+fn test_compact() {
+    use crate::pathdb::{DEFAULT_COLUMN_FAMILY_NAME, META_COLUMN_FAMILY_NAME};
+    use crate::{PathProviderError, PathProviderManager};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().to_str().unwrap();
+    let db = PathDB::new(db_path, PathProviderConfig::default()).unwrap();
+    let sst_size = |db: &PathDB| {
+        let cf = db.raw_db().cf_handle(DEFAULT_COLUMN_FAMILY_NAME).unwrap();
+        db.raw_db().property_int_value_cf(&cf, "rocksdb.total-sst-files-size").unwrap().unwrap()
+    };
+
+    for i in 0u16..1000 {
+        db.put_raw_trie_node(&[b"A".as_slice(), &i.to_be_bytes()].concat(), &[0x42; 1024]).unwrap();
+    }
+    db.put_raw_trie_node(b"B", b"kept").unwrap();
+    db.flush().unwrap();
+    let before = sst_size(&db);
+
+    // Compaction reclaims the space of the deleted range and keeps the rest
+    db.delete_range_raw(b"A", b"B").unwrap();
+    db.compact(Some((b"A".as_slice(), b"B".as_slice()))).unwrap();
+    assert!(sst_size(&db) < before / 2);
+    db.compact(None).unwrap();
+    db.compact_cf(META_COLUMN_FAMILY_NAME).unwrap();
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(b"B").unwrap(), Some(b"kept".to_vec().into()));
+    assert_eq!(db.iter_trie_nodes(b"A").unwrap().count(), 0);
+    assert!(matches!(db.compact_cf("missing"), Err(PathProviderError::ColumnFamilyMissing { .. })));
+
+    drop(db);
+    let read_only = PathDB::open_read_only(db_path, PathProviderConfig::default()).unwrap();
+    assert!(matches!(read_only.compact(None), Err(PathProviderError::InvalidOperation(_))));
+}
+
+#[test]
+fn test_sst_bulk_loader() {
+    use crate::PathProviderError;
+
+    let temp_dir = TempDir::new().unwrap();
+    let sst_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.overflow_threshold = Some(64);
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap().with_namespace(b"bulk").unwrap();
+    db.put_raw_trie_node(b"A0005", b"stale").unwrap();
+    db.get_raw_trie_node(b"A0005").unwrap();
+
+    // Small target size to spread the nodes over several files
+    let mut loader = db.sst_bulk_loader(sst_dir.path()).unwrap().with_target_file_size(1024);
+    for i in 0u16..200 {
+        loader.add(format!("A{:04}", i).as_bytes(), &[i as u8; 32]).unwrap();
+    }
+    loader.add(b"B", &[0x42; 200]).unwrap();
+    assert!(matches!(loader.add(b"A0001", b"late"), Err(PathProviderError::InvalidOperation(_))));
+    assert_eq!(loader.finish().unwrap(), 201);
+
+    // Ingested nodes replace cached and stored ones, oversized values are chunked
+    assert_eq!(db.get_raw_trie_node(b"A0005").unwrap(), Some(vec![5; 32].into()));
+    assert_eq!(db.get_raw_trie_node(b"B").unwrap(), Some(vec![0x42; 200].into()));
+    assert_eq!(db.iter_trie_nodes(b"A").unwrap().count(), 200);
+    assert_eq!(std::fs::read_dir(sst_dir.path()).unwrap().count(), 0);
+
+    assert_eq!(db.sst_bulk_loader(sst_dir.path()).unwrap().finish().unwrap(), 0);
+}
+
+#[test]
+fn test_metrics_snapshot() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    db.put_raw_trie_node(b"key", b"value").unwrap();
+
+    // Totals are process-wide and other tests run concurrently
+    let before = crate::metrics_snapshot::snapshot();
+    db.get_raw_trie_node(b"key").unwrap();
+    db.get_raw_trie_node(b"missing").unwrap();
+    let after = crate::metrics_snapshot::snapshot();
+    assert!(after.trie_node_cache_hits > before.trie_node_cache_hits);
+    assert!(after.trie_node_cache_misses > before.trie_node_cache_misses);
+}
+
+#[test]
+fn test_storage_root_metrics() {
+    use alloy_primitives::B256;
+    use rust_eth_triedb_common::TrieDatabase;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let stored = B256::repeat_byte(0x01);
+
+    // Totals are process-wide and other tests run concurrently
+    let before = crate::metrics_snapshot::snapshot();
+    db.batch_insert_storage_roots([(stored, B256::repeat_byte(0x11)), (B256::repeat_byte(0x02), B256::repeat_byte(0x22))], 1, B256::repeat_byte(0xaa)).unwrap();
+    TrieDatabase::get_storage_root(&db, stored).unwrap();
+    TrieDatabase::get_storage_root(&db, B256::repeat_byte(0x03)).unwrap();
+    db.wipe_account(stored).unwrap();
+    let after = crate::metrics_snapshot::snapshot();
+    assert_eq!(TrieDatabase::get_storage_root(&db, stored).unwrap(), None);
+
+    assert!(after.storage_root_batch_size.count > before.storage_root_batch_size.count);
+    assert!(after.get_storage_root_duration.count >= before.get_storage_root_duration.count + 2);
+    assert!(after.storage_root_found > before.storage_root_found);
+    assert!(after.storage_root_missing > before.storage_root_missing);
+    assert!(after.storage_root_wiped > before.storage_root_wiped);
+}
+
+#[test]
+fn test_cached_blobs_are_shared() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::{Bytes, B256};
+    use rust_eth_triedb_common::{DiffLayer, TrieNode};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+
+    let blob = Bytes::from(vec![0x42; 64]);
+    let node = Arc::new(TrieNode::new(None, Some(blob.clone())));
+    let difflayer = Arc::new(DiffLayer::new(HashMap::from([(b"A1".to_vec(), node)]), HashMap::new()));
+    db.commit_difflayer(1, B256::ZERO, &Some(difflayer)).unwrap();
+
+    // Committed blobs are cached and read back without copying
+    let first = db.get_trie_node(b"A1").unwrap().unwrap();
+    let second = db.get_raw_trie_node(b"A1").unwrap().unwrap();
+    assert_eq!(first, blob);
+    assert_eq!(first.as_ptr(), blob.as_ptr());
+    assert_eq!(second.as_ptr(), blob.as_ptr());
+}
+
+#[test]
+fn test_disk_usage() {
+    use crate::pathdb::{COLUMN_FAMILY_NAMES, DEFAULT_COLUMN_FAMILY_NAME};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    for i in 0u32..1000 {
+        db.put_raw_trie_node(&i.to_be_bytes(), &[0xab; 64]).unwrap();
+    }
+    db.flush().unwrap();
+
+    assert!(db.estimate_num_keys(DEFAULT_COLUMN_FAMILY_NAME).unwrap() > 0);
+    assert!(db.estimate_live_data_size(DEFAULT_COLUMN_FAMILY_NAME).unwrap() > 0);
+    assert!(db.estimate_num_keys("missing").is_err());
+
+    let usage = db.disk_usage().unwrap();
+    assert_eq!(usage.column_families.len(), COLUMN_FAMILY_NAMES.len());
+    let default_usage = usage.column_family(DEFAULT_COLUMN_FAMILY_NAME).unwrap();
+    assert!(default_usage.sst_files_bytes > 0);
+    assert_eq!(default_usage.sst_files, 1);
+    assert_eq!(usage.estimated_keys(), usage.column_families.iter().map(|cf| cf.estimated_keys).sum::<u64>());
+    assert!(usage.sst_files_bytes() >= default_usage.sst_files_bytes);
+
+    // Reporting exports the same usage as gauges, also from the background worker
+    let reported = db.report_disk_usage().unwrap();
+    assert_eq!(reported.column_family(DEFAULT_COLUMN_FAMILY_NAME).unwrap().sst_files, 1);
+    crate::DiskUsageWorker::spawn(db.clone()).unwrap().stop();
+}
+
+#[test]
+fn test_sharded_path_db() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::{Bytes, B256};
+    use rust_eth_triedb_common::{DiffLayer, TrieNode};
+    use crate::ShardedPathDB;
+
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("sharded");
+    let path = path.to_str().unwrap();
+    let db = ShardedPathDB::new(path, 4, PathProviderConfig::default()).unwrap();
+
+    let owners: Vec<B256> = (0u8..16).map(|i| B256::repeat_byte(i.wrapping_mul(37))).collect();
+    let storage_key = |owner: &B256| [b"O".as_slice(), owner.as_slice(), &[0x01]].concat();
+    let mut diff_nodes = HashMap::new();
+    let mut diff_storage_roots = HashMap::new();
+    for owner in &owners {
+        diff_nodes.insert(storage_key(owner), Arc::new(TrieNode::new(Some(*owner), Some(Bytes::copy_from_slice(owner.as_slice())))));
+        diff_storage_roots.insert(*owner, *owner);
+    }
+    diff_nodes.insert(b"A\x01".to_vec(), Arc::new(TrieNode::new(None, Some(Bytes::from_static(b"account")))));
+    let state_root = B256::repeat_byte(0xee);
+    db.commit_difflayer(3, state_root, &Some(Arc::new(DiffLayer::new(diff_nodes, diff_storage_roots)))).unwrap();
+
+    // Every key is readable through the wrapper and stored only in its owner's shard
+    for owner in &owners {
+        let key = storage_key(owner);
+        let shard = db.shard_index(&key);
+        assert_eq!(shard, db.owner_shard_index(owner.as_slice()));
+        assert_eq!(db.get_trie_node(&key).unwrap().unwrap().as_ref(), owner.as_slice());
+        assert_eq!(db.get_storage_root(*owner).unwrap(), Some(*owner));
+        for (index, other) in db.shards().iter().enumerate() {
+            assert_eq!(other.get_raw_trie_node(&key).unwrap().is_some(), index == shard);
+        }
+    }
+    assert_eq!(db.shard_index(b"A\x01"), 0);
+    assert!(db.shards()[0].get_raw_trie_node(b"A\x01").unwrap().is_some());
+    assert!(db.shards().iter().skip(1).any(|shard| shard.get_raw_trie_node(&storage_key(&owners[1])).unwrap().is_some()));
+
+    let keys: Vec<Vec<u8>> = owners.iter().map(storage_key).chain([b"A\x01".to_vec(), b"A\x02".to_vec()]).collect();
+    let nodes = db.get_trie_nodes(&keys).unwrap();
+    assert_eq!(nodes.len(), keys.len());
+    assert_eq!(nodes[owners.len()].as_deref(), Some(b"account".as_slice()));
+    assert!(nodes[owners.len() + 1].is_none());
+    assert_eq!(db.latest_persist_state().unwrap(), (3, state_root));
+    drop(db);
+
+    // The shard count can't change once recorded
+    assert!(ShardedPathDB::new(path, 2, PathProviderConfig::default()).is_err());
+    assert!(ShardedPathDB::new(path, 0, PathProviderConfig::default()).is_err());
+    let db = ShardedPathDB::new(path, 4, PathProviderConfig::default()).unwrap();
+    assert_eq!(db.latest_persist_state().unwrap(), (3, state_root));
+}
+
+#[test]
+fn test_background_job_tuning() {
+    use crate::CompactionPriority;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().to_str().unwrap();
+    let invalid_configs = [
+        PathProviderConfig { max_subcompactions: 0, ..Default::default() },
+        PathProviderConfig { rate_limit_bytes_per_sec: Some(0), ..Default::default() },
+    ];
+    for config in invalid_configs {
+        assert!(PathDB::new(db_path, config).is_err());
+    }
+
+    let config = PathProviderConfig {
+        max_subcompactions: 4,
+        rate_limit_bytes_per_sec: Some(64 * 1024 * 1024),
+        rate_limit_auto_tuned: true,
+        compaction_priority: CompactionPriority::OldestSmallestSeqFirst,
+        ..Default::default()
+    };
+    let db = PathDB::new(db_path, config).unwrap();
+    for i in 0u16..1000 {
+        db.put_raw_trie_node(&i.to_be_bytes(), &[0x42; 256]).unwrap();
+    }
+    db.flush().unwrap();
+    assert_eq!(db.get_raw_trie_node(&7u16.to_be_bytes()).unwrap().as_deref(), Some([0x42; 256].as_slice()));
+}
+
+#[test]
+fn test_cancellation() {
+    use rust_eth_triedb_common::CancellationToken;
+
+    let temp_dir = TempDir::new().unwrap();
+    let sst_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.cold_storage = true;
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+
+    // A cancelled bulk load still ingests the nodes added before
+    let cancel = CancellationToken::new();
+    let mut loader = db.sst_bulk_loader(sst_dir.path()).unwrap().with_cancellation(cancel.clone());
+    for i in 0u16..10 {
+        loader.add(format!("A{:04}", i).as_bytes(), &[i as u8; 32]).unwrap();
+    }
+    cancel.cancel();
+    assert!(loader.add(b"A0010", &[10; 32]).is_err());
+    assert_eq!(loader.finish().unwrap(), 10);
+    assert_eq!(db.iter_trie_nodes(b"A").unwrap().count(), 10);
+
+    // A cancelled move stops before the next node and leaves the rest in place
+    assert_eq!(db.move_to_cold_storage_with_cancellation(b"A", &cancel).unwrap(), 0);
+    assert_eq!(db.iter_trie_nodes(b"A").unwrap().count(), 10);
+    assert_eq!(db.move_to_cold_storage_with_cancellation(b"A", &CancellationToken::new()).unwrap(), 10);
+    assert_eq!(db.get_raw_trie_node(b"A0003").unwrap(), Some(vec![3; 32].into()));
+}
+
+#[test]
+fn test_set_options() {
+    use crate::pathdb::STORAGE_ROOT_COLUMN_FAMILY_NAME;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    db.put_raw_trie_node(b"key", b"value").unwrap();
+
+    db.set_options(&[
+        ("write_buffer_size", "16777216"),
+        ("level0_file_num_compaction_trigger", "8"),
+        ("max_background_jobs", "2"),
+    ]).unwrap();
+    db.set_cf_options(STORAGE_ROOT_COLUMN_FAMILY_NAME, &[("max_write_buffer_number", "2")]).unwrap();
+    assert_eq!(db.get_raw_trie_node(b"key").unwrap(), Some(b"value".to_vec().into()));
+
+    // Unknown or immutable options and Column Families are rejected
+    assert!(db.set_options(&[("no_such_option", "1")]).is_err());
+    assert!(db.set_options(&[("max_background_jobs", "many")]).is_err());
+    assert!(db.set_cf_options("missing", &[("write_buffer_size", "16777216")]).is_err());
+
+    // Nothing is set if any option is invalid
+    assert!(db.set_options(&[("disable_auto_compactions", "true"), ("level0_stop_writes_trigger", "many")]).is_err());
+    assert!(db.set_options(&[("max_background_jobs", "4"), ("no_such_option", "1")]).is_err());
+}
+
+#[test]
+fn test_cache_controller() {
+    use crate::CacheController;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.trie_node_cache_size = 4096;
+    config.storage_root_cache_size = 4096;
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+    for i in 0u32..4096 {
+        db.put_raw_trie_node(&i.to_be_bytes(), &[0xab; 64]).unwrap();
+    }
+    db.clear_cache();
+
+    let mut controller = CacheController::new(1 << 20).with_min_capacity(256).with_db(&db);
+    assert_eq!(controller.memory_budget(), 1 << 20);
+
+    // Only the trie node cache misses, it takes most of the budget
+    for i in 0u32..4096 {
+        db.get_raw_trie_node(&i.to_be_bytes()).unwrap();
+    }
+    db.get_raw_storage_root(&[0x01; 32]).unwrap();
+    let allocations = controller.rebalance();
+    assert_eq!(allocations.len(), 2);
+    assert_eq!(allocations[0].hit_rate, Some(0.0));
+    let (trie_node_capacity, storage_root_capacity) = db.cache_capacities();
+    assert_eq!(trie_node_capacity, allocations[0].capacity);
+    assert!(trie_node_capacity > storage_root_capacity);
+    assert!(storage_root_capacity < 4096);
+
+    // A cache that only hits shrinks, evicting down to its new capacity
+    for round in 0u8..16 {
+        db.get_raw_trie_node(&0u32.to_be_bytes()).unwrap();
+        for i in 0u8..64 {
+            db.get_raw_storage_root(&[round, i].repeat(16)).unwrap();
+        }
+        controller.rebalance();
+    }
+    let (trie_node_capacity, storage_root_capacity) = db.cache_capacities();
+    assert_eq!(trie_node_capacity, 256);
+    assert!(storage_root_capacity > trie_node_capacity);
+    assert!(db.cache_stats().0 <= trie_node_capacity as usize);
+
+    // Idle caches keep their capacity
+    let allocations = CacheController::new(1 << 20).with_db(&db).rebalance();
+    assert!(allocations.iter().all(|allocation| allocation.hit_rate.is_none()));
+    assert_eq!(db.cache_capacities(), (trie_node_capacity, storage_root_capacity));
+}
+
+#[test]
+fn test_read_snapshot() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::{Bytes, B256};
+    use rust_eth_triedb_common::{DiffLayer, TrieNode};
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let hashed_address = B256::repeat_byte(0x11);
+    db.put_raw_trie_node(b"node", b"before").unwrap();
+    db.commit_difflayer(1, B256::repeat_byte(0x01), &None).unwrap();
+
+    let snapshot = db.read_snapshot();
+    let mut diff_nodes = HashMap::new();
+    diff_nodes.insert(b"node".to_vec(), Arc::new(TrieNode::new(None, Some(Bytes::from_static(b"after")))));
+    diff_nodes.insert(b"fresh".to_vec(), Arc::new(TrieNode::new(None, Some(Bytes::from_static(b"fresh")))));
+    let difflayer = DiffLayer::new(diff_nodes, HashMap::from([(hashed_address, B256::repeat_byte(0x22))]));
+    db.commit_difflayer(2, B256::repeat_byte(0x02), &Some(Arc::new(difflayer))).unwrap();
+
+    // The database sees the commit, the snapshot doesn't
+    assert_eq!(db.get_raw_trie_node(b"node").unwrap(), Some(Bytes::from_static(b"after")));
+    assert_eq!(snapshot.get_raw_trie_node(b"node").unwrap(), Some(Bytes::from_static(b"before")));
+    assert_eq!(snapshot.get_trie_node(b"fresh").unwrap(), None);
+    assert_eq!(snapshot.get_storage_root(hashed_address).unwrap(), None);
+    assert_eq!(snapshot.latest_persist_state().unwrap(), (1, B256::repeat_byte(0x01)));
+    assert_eq!(db.read_snapshot().latest_persist_state().unwrap(), (2, B256::repeat_byte(0x02)));
+
+    // Snapshots are read-only
+    assert!(snapshot.insert_trie_node(b"node", b"write".to_vec()).is_err());
+    assert!(snapshot.commit_difflayer(3, B256::ZERO, &None).is_err());
+}
+
+#[test]
+fn test_read_snapshot_deletion_queue() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::{Bytes, B256};
+    use rust_eth_triedb_common::{DiffLayer, TrieNode, TRIE_STATE_ROOT_KEY};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.deferred_deletion = true;
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+    db.put_raw_trie_node(b"node", b"blob").unwrap();
+    let diff_nodes = HashMap::from([(b"node".to_vec(), Arc::new(TrieNode::new(None, None)))]);
+    db.commit_difflayer(1, B256::repeat_byte(0x01), &Some(Arc::new(DiffLayer::new(diff_nodes, HashMap::new())))).unwrap();
+
+    // Served by the caches while current, then from the snapshot
+    let snapshot = db.read_snapshot();
+    assert_eq!(snapshot.get_raw_trie_node(b"node").unwrap(), None);
+    db.put_raw_trie_node(b"other", b"blob").unwrap();
+    db.clear_cache();
+    assert_eq!(snapshot.get_raw_trie_node(b"node").unwrap(), None);
+    assert_eq!(snapshot.get_raw_trie_node(b"other").unwrap(), None);
+    assert_eq!(db.read_snapshot().get_raw_trie_node(b"other").unwrap(), Some(Bytes::from_static(b"blob")));
+
+    // A corrupted persisted root is an error, not a panic
+    let default_cf = db.raw_db().cf_handle(crate::pathdb::DEFAULT_COLUMN_FAMILY_NAME).unwrap();
+    db.raw_db().put_cf(&default_cf, TRIE_STATE_ROOT_KEY, [0u8; 3]).unwrap();
+    db.clear_cache();
+    assert!(matches!(db.read_snapshot().latest_persist_state(), Err(crate::PathProviderError::Deserialization(_))));
+}
+
+#[test]
+fn test_delete_multi() {
+    use alloy_primitives::B256;
+    use crate::pathdb::STORAGE_ROOT_COLUMN_FAMILY_NAME;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.overflow_threshold = Some(64);
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+    for i in 0u8..8 {
+        db.put_raw_trie_node(&[i], &[i; 256]).unwrap();
+    }
+    let hashed_address = B256::repeat_byte(0x11);
+    db.write_batch({
+        let mut batch = crate::PathDBWriteBatch::new();
+        batch.put_storage_root(hashed_address, B256::repeat_byte(0x22));
+        batch
+    }).unwrap();
+    assert!(db.get_storage_root(hashed_address).unwrap().is_some());
+
+    // Cached and overflowing nodes are deleted in one write, duplicates are fine
+    db.delete_multi_raw_trie_nodes(&[[0u8], [2], [4], [2]]).unwrap();
+    for i in 0u8..8 {
+        assert_eq!(db.get_raw_trie_node(&[i]).unwrap().is_some(), i % 2 == 1 || i > 4);
+    }
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(&[2]).unwrap(), None);
+    assert_eq!(db.get_raw_trie_node(&[3]).unwrap().unwrap().len(), 256);
+
+    db.delete_multi_cf(STORAGE_ROOT_COLUMN_FAMILY_NAME, &[hashed_address]).unwrap();
+    assert_eq!(db.get_storage_root(hashed_address).unwrap(), None);
+    assert!(db.delete_multi_cf("missing", &[[0u8]]).is_err());
+    db.delete_multi_raw_trie_nodes::<Vec<u8>>(&[]).unwrap();
+}
+
+#[test]
+fn test_commit_limits() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::{Bytes, B256};
+    use alloy_trie::EMPTY_ROOT_HASH;
+    use rust_eth_triedb_common::{DiffLayer, TrieNode};
+    use crate::{CommitLimitAction, CommitLimits, PathProviderError};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.commit_limits = CommitLimits {
+        max_nodes: Some(2),
+        max_node_size: Some(64),
+        check_storage_root_regressions: true,
+        action: CommitLimitAction::Reject,
+    };
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config.clone()).unwrap();
+    let commit = |nodes: Vec<(Vec<u8>, Option<Bytes>)>, roots: Vec<(B256, B256)>| {
+        let nodes = nodes.into_iter().map(|(key, blob)| (key, Arc::new(TrieNode::new(None, blob)))).collect();
+        let layer = Some(Arc::new(DiffLayer::new(nodes, roots.into_iter().collect::<HashMap<_, _>>())));
+        db.commit_difflayer(1, B256::repeat_byte(0x01), &layer)
+    };
+
+    let owner = B256::repeat_byte(0x11);
+    let mut root_key = b"O".to_vec();
+    root_key.extend_from_slice(owner.as_slice());
+    commit(vec![(root_key.clone(), Some(Bytes::from(vec![1u8; 32])))], vec![(owner, B256::repeat_byte(0x22))]).unwrap();
+
+    // Too many nodes and oversized blobs are rejected before anything is written
+    let nodes = (0u8..3).map(|i| (vec![i], Some(Bytes::from(vec![i; 8])))).collect();
+    assert!(matches!(commit(nodes, vec![]), Err(PathProviderError::CommitRejected(_))));
+    assert_eq!(db.get_raw_trie_node(&[0]).unwrap(), None);
+    let oversized = vec![(vec![0u8], Some(Bytes::from(vec![0u8; 65])))];
+    assert!(matches!(commit(oversized, vec![]), Err(PathProviderError::CommitRejected(_))));
+
+    // Emptying a storage root requires deleting its storage trie
+    assert!(matches!(commit(vec![], vec![(owner, EMPTY_ROOT_HASH)]), Err(PathProviderError::CommitRejected(_))));
+    assert_eq!(db.get_storage_root(owner).unwrap(), Some(B256::repeat_byte(0x22)));
+    commit(vec![(root_key, None)], vec![(owner, EMPTY_ROOT_HASH)]).unwrap();
+    assert_eq!(db.get_storage_root(owner).unwrap(), Some(EMPTY_ROOT_HASH));
+    drop(db);
+
+    // Warnings let the commit through
+    config.commit_limits.action = CommitLimitAction::Warn;
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+    let nodes = (0u8..3).map(|i| (vec![i], Arc::new(TrieNode::new(None, Some(Bytes::from(vec![i; 8])))))).collect();
+    db.commit_difflayer(2, B256::repeat_byte(0x02), &Some(Arc::new(DiffLayer::new(nodes, HashMap::new())))).unwrap();
+    assert!(db.get_raw_trie_node(&[2]).unwrap().is_some());
+    drop(db);
+
+    // Sharded commits are checked as a whole, not per shard
+    config.commit_limits.action = CommitLimitAction::Reject;
+    let sharded_dir = TempDir::new().unwrap();
+    let sharded = crate::ShardedPathDB::new(sharded_dir.path().join("sharded").to_str().unwrap(), 2, config).unwrap();
+    let nodes = (0u8..3)
+        .map(|i| {
+            let mut key = b"O".to_vec();
+            key.extend_from_slice(B256::repeat_byte(i).as_slice());
+            (key, Arc::new(TrieNode::new(None, Some(Bytes::from(vec![i; 8])))))
+        })
+        .collect();
+    let layer = Some(Arc::new(DiffLayer::new(nodes, HashMap::new())));
+    assert!(matches!(sharded.commit_difflayer(1, B256::repeat_byte(0x01), &layer), Err(PathProviderError::CommitRejected(_))));
+    assert_eq!(sharded.latest_persist_state().unwrap(), (0, EMPTY_ROOT_HASH));
+}
+
+#[test]
+fn test_repair_and_verify_checksums() {
+    use crate::PathProviderManager;
+
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().join("db");
+    let path = path.to_str().unwrap();
+    assert!(PathDB::repair(path, &PathProviderConfig::default()).is_err());
+
+    let db = PathDB::new(path, PathProviderConfig::default()).unwrap();
+    for i in 0u8..16 {
+        db.put_raw_trie_node(&[i], &[i; 64]).unwrap();
+    }
+    db.flush().unwrap();
+    db.put_raw_trie_node(&[0xff], &[0xff; 64]).unwrap();
+
+    let report = db.verify_checksums_full().unwrap();
+    assert!(report.is_clean());
+    assert!(report.keys_verified >= 17);
+    drop(db);
+
+    // Repair keeps readable data, including the unflushed write from the WAL
+    PathDB::repair(path, &PathProviderConfig::default()).unwrap();
+    let db = PathDB::new(path, PathProviderConfig::default()).unwrap();
+    assert_eq!(db.get_raw_trie_node(&[3]).unwrap().unwrap().as_ref(), &[3u8; 64]);
+    assert_eq!(db.get_raw_trie_node(&[0xff]).unwrap().unwrap().as_ref(), &[0xffu8; 64]);
+    assert!(db.verify_checksums_full().unwrap().is_clean());
+}
+
+#[test]
+fn test_write_pressure() {
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    for i in 0u8..16 {
+        db.put_raw_trie_node(&[i], &[i; 64]).unwrap();
+    }
+
+    let pressure = db.write_pressure().unwrap();
+    assert!(!pressure.is_stalled());
+    assert!(pressure.memtables >= db.column_families().len() as u64);
+    assert!(pressure.immutable_memtables >= pressure.max_cf_immutable_memtables);
+}
+
+#[test]
+fn test_structured_errors() {
+    use crate::PathProviderError;
+
+    let temp_dir = TempDir::new().unwrap();
+    let missing_path = temp_dir.path().join("missing");
+    let err = PathDB::open_read_only(missing_path.to_str().unwrap(), PathProviderConfig::default()).unwrap_err();
+    assert!(err.rocksdb_error().is_some());
+    assert!(!err.is_retryable());
+    assert!(!err.is_corruption());
+
+    let db = PathDB::new(temp_dir.path().join("db").to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let err = db.estimate_num_keys("missing").unwrap_err();
+    assert!(matches!(&err, PathProviderError::ColumnFamilyMissing { name } if name == "missing"));
+    assert!(err.rocksdb_error().is_none());
+}
+
+#[test]
+fn test_maintenance_writes() {
+    use std::time::{Duration, Instant};
+    use crate::PathDBWriteBatch;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.maintenance_rate_limit_bytes_per_sec = Some(64 * 1024);
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config.clone()).unwrap();
+
+    // The first write is charged after it lands, the next one waits it off
+    let write = |value: u8| {
+        let mut batch = PathDBWriteBatch::new();
+        batch.put_trie_node(&[value], &[value; 32 * 1024]);
+        db.write_maintenance_batch(batch).unwrap();
+    };
+    let start = Instant::now();
+    write(1);
+    write(2);
+    assert!(start.elapsed() >= Duration::from_millis(400));
+    assert_eq!(db.get_raw_trie_node(&[2]).unwrap().unwrap().len(), 32 * 1024);
+    db.clear_cache();
+    assert_eq!(db.get_raw_trie_node(&[1]).unwrap().unwrap().len(), 32 * 1024);
+    drop(db);
+
+    config.maintenance_rate_limit_bytes_per_sec = Some(0);
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+}
+
+#[test]
+fn test_version_gc() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::B256;
+    use rust_eth_triedb_common::DiffLayer;
+    use crate::pathdb::STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME;
+    use crate::version_gc::{key_version, versioned_key};
+    use crate::{PathProviderManager, VersionGcConfig};
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.version_gc = Some(VersionGcConfig { column_families: vec!["history".to_string()], retention_blocks: 10 });
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config.clone()).unwrap();
+    db.create_column_family("history").unwrap();
+    assert_eq!(db.version_horizon(), Some(0));
+
+    let cf = db.raw_db().cf_handle("history").unwrap();
+    for block_number in [1u64, 50, 95] {
+        db.raw_db().put_cf(&cf, versioned_key(b"node", block_number), [block_number as u8]).unwrap();
+    }
+    db.raw_db().put_cf(&cf, versioned_key(b"lone", 1), [1]).unwrap();
+    assert_eq!(key_version(&versioned_key(b"node", 95)), Some(95));
+    assert!(versioned_key(b"node", 95) < versioned_key(b"node", 50));
+    assert_eq!(key_version(b"short"), None);
+
+    // Versions superseded at or below the horizon are dropped by the next
+    // compaction, the newest one below it still answers lookups at the horizon
+    db.commit_difflayer(100, B256::repeat_byte(0x01), &None).unwrap();
+    assert_eq!(db.version_horizon(), Some(90));
+    db.compact_cf("history").unwrap();
+    assert!(db.raw_db().get_cf(&cf, versioned_key(b"node", 1)).unwrap().is_none());
+    assert!(db.raw_db().get_cf(&cf, versioned_key(b"node", 50)).unwrap().is_some());
+    assert!(db.raw_db().get_cf(&cf, versioned_key(b"node", 95)).unwrap().is_some());
+    assert!(db.raw_db().get_cf(&cf, versioned_key(b"lone", 1)).unwrap().is_some());
+    drop(cf);
+    drop(db);
+
+    // The storage root history is versioned and can be garbage collected
+    let history_dir = TempDir::new().unwrap();
+    let mut history_config = config.clone();
+    history_config.storage_root_history = true;
+    history_config.version_gc = Some(VersionGcConfig { column_families: vec![STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME.to_string()], retention_blocks: 10 });
+    let db = PathDB::new(history_dir.path().to_str().unwrap(), history_config).unwrap();
+    let account = B256::repeat_byte(0xab);
+    for block_number in [10u64, 20, 30] {
+        let storage_roots = HashMap::from([(account, B256::repeat_byte(block_number as u8))]);
+        let difflayer = Arc::new(DiffLayer::new(HashMap::new(), storage_roots));
+        db.commit_difflayer(block_number, B256::repeat_byte(0x01), &Some(difflayer)).unwrap();
+    }
+    db.compact_cf(STORAGE_ROOT_HISTORY_COLUMN_FAMILY_NAME).unwrap();
+    assert_eq!(db.get_storage_root_at(account, 15).unwrap(), None);
+    assert_eq!(db.get_storage_root_at(account, 20).unwrap(), Some(B256::repeat_byte(20)));
+    assert_eq!(db.get_storage_root_at(account, 30).unwrap(), Some(B256::repeat_byte(30)));
+    drop(db);
+
+    // Path keyed Column Families can't be garbage collected by version
+    config.version_gc = Some(VersionGcConfig { column_families: vec!["default".to_string()], retention_blocks: 10 });
+    assert!(PathDB::new(temp_dir.path().to_str().unwrap(), config).is_err());
+}
+
+#[test]
+fn test_key_migration() {
+    use crate::{KeyMigration, PathProviderError, CURRENT_SCHEMA_VERSION};
+
+    /// Moves the nodes under `X` to `Y`.
+    struct RenamePrefix;
+    impl KeyMigration for RenamePrefix {
+        fn from_version(&self) -> u32 {
+            CURRENT_SCHEMA_VERSION
+        }
+
+        fn migrate_key(&self, key: &[u8]) -> Option<Vec<u8>> {
+            key.strip_prefix(b"X").map(|rest| [b"Y".as_slice(), rest].concat())
+        }
+    }
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.overflow_threshold = Some(64);
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config.clone()).unwrap();
+    assert_eq!(db.schema_version().unwrap(), CURRENT_SCHEMA_VERSION);
+    assert_eq!(db.migrate().unwrap(), CURRENT_SCHEMA_VERSION);
+
+    db.put_raw_trie_node(b"X1", b"small").unwrap();
+    db.put_raw_trie_node(b"X2", &[2u8; 256]).unwrap();
+    db.put_raw_trie_node(b"A1", b"kept").unwrap();
+
+    // Migrating a namespace leaves the keys of other namespaces alone
+    let other = db.with_namespace(b"other").unwrap();
+    other.put_raw_trie_node(b"X1", b"other").unwrap();
+    let namespaced = db.with_namespace(b"chain").unwrap();
+    namespaced.put_raw_trie_node(b"X1", b"namespaced").unwrap();
+    assert_eq!(namespaced.apply_migrations(&[&RenamePrefix]).unwrap(), CURRENT_SCHEMA_VERSION + 1);
+    assert_eq!(namespaced.get_raw_trie_node(b"Y1").unwrap().unwrap().as_ref(), b"namespaced");
+    assert_eq!(other.get_raw_trie_node(b"X1").unwrap().unwrap().as_ref(), b"other");
+    assert_eq!(other.get_raw_trie_node(b"Y1").unwrap(), None);
+    other.delete_raw_trie_node(b"X1").unwrap();
+    namespaced.delete_raw_trie_node(b"Y1").unwrap();
+
+    assert_eq!(db.apply_migrations(&[&RenamePrefix]).unwrap(), CURRENT_SCHEMA_VERSION + 1);
+    assert_eq!(db.schema_version().unwrap(), CURRENT_SCHEMA_VERSION + 1);
+    assert_eq!(db.get_raw_trie_node(b"X1").unwrap(), None);
+    assert_eq!(db.get_raw_trie_node(b"Y1").unwrap().unwrap().as_ref(), b"small");
+    assert_eq!(db.get_raw_trie_node(b"Y2").unwrap().unwrap().as_ref(), &[2u8; 256]);
+    assert_eq!(db.get_raw_trie_node(b"A1").unwrap().unwrap().as_ref(), b"kept");
+
+    // Nothing applies past the recorded version
+    assert_eq!(db.apply_migrations(&[&RenamePrefix]).unwrap(), CURRENT_SCHEMA_VERSION + 1);
+    drop(db);
+
+    // Layouts newer than this version can't be opened
+    let err = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap_err();
+    assert!(matches!(err, PathProviderError::InvalidOperation(_)));
+}
+
+#[test]
+fn test_snapshot_ranges() {
+    use alloy_primitives::B256;
+    use crate::ShardedPathDB;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().join("db").to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let other = db.with_namespace(b"other").unwrap();
+
+    let hashes: Vec<B256> = (0u8..10).map(|i| B256::repeat_byte(i * 16 + 1)).collect();
+    let owner = B256::repeat_byte(0x42);
+    for hash in &hashes {
+        db.put_raw_account_snapshot(*hash, hash.as_slice()).unwrap();
+        db.put_raw_storage_snapshot(owner, *hash, &hash[..1]).unwrap();
+        // Slots of the neighbouring accounts stay out of the range
+        db.put_raw_storage_snapshot(B256::repeat_byte(0x41), *hash, b"before").unwrap();
+        db.put_raw_storage_snapshot(B256::repeat_byte(0x43), *hash, b"after").unwrap();
+    }
+    other.put_raw_account_snapshot(B256::ZERO, b"other").unwrap();
+
+    let accounts = db.iter_account_snapshot(B256::ZERO, 100).unwrap();
+    assert_eq!(accounts.iter().map(|(hash, _)| *hash).collect::<Vec<_>>(), hashes);
+    assert!(accounts.iter().all(|(hash, value)| value.as_ref() == hash.as_slice()));
+
+    // Ranges start at the given hash and stop at the limit
+    let page = db.iter_account_snapshot(hashes[3], 4).unwrap();
+    assert_eq!(page.iter().map(|(hash, _)| *hash).collect::<Vec<_>>(), hashes[3..7]);
+    let page = db.iter_account_snapshot(B256::repeat_byte(0x22), 2).unwrap();
+    assert_eq!(page.iter().map(|(hash, _)| *hash).collect::<Vec<_>>(), hashes[3..5]);
+    assert!(db.iter_account_snapshot(B256::repeat_byte(0xff), 10).unwrap().is_empty());
+    assert_eq!(other.iter_account_snapshot(B256::ZERO, 10).unwrap().len(), 1);
+
+    let slots = db.iter_storage_snapshot(owner, hashes[8], 10).unwrap();
+    assert_eq!(slots, vec![(hashes[8], vec![hashes[8][0]].into()), (hashes[9], vec![hashes[9][0]].into())]);
+    assert_eq!(db.iter_storage_snapshot(owner, B256::ZERO, 100).unwrap().len(), hashes.len());
+    assert!(db.iter_storage_snapshot(B256::repeat_byte(0x44), B256::ZERO, 100).unwrap().is_empty());
+    db.delete_raw_storage_snapshot(owner, hashes[0]).unwrap();
+    assert_eq!(db.get_raw_storage_snapshot(owner, hashes[0]).unwrap(), None);
+    assert_eq!(db.iter_storage_snapshot(owner, B256::ZERO, 1).unwrap()[0].0, hashes[1]);
+
+    // Account ranges of a sharded database are merged across shards
+    let sharded = ShardedPathDB::new(temp_dir.path().join("sharded").to_str().unwrap(), 4, PathProviderConfig::default()).unwrap();
+    for hash in &hashes {
+        sharded.put_account_snapshot(*hash, Some(hash.as_slice())).unwrap();
+    }
+    let page = sharded.iter_account_snapshot(hashes[2], 5).unwrap();
+    assert_eq!(page.iter().map(|(hash, _)| *hash).collect::<Vec<_>>(), hashes[2..7]);
+}
+
+#[test]
+fn test_snapshot_journal_recovery() {
+    use alloy_primitives::{Bytes, B256};
+    use crate::pathdb::SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME;
+    use crate::SnapshotDiff;
+
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().to_str().unwrap();
+    let db = PathDB::new(path, PathProviderConfig::default()).unwrap();
+
+    let account = |i: u8| B256::repeat_byte(i);
+    let diff = |i: u8| {
+        let mut diff = SnapshotDiff::default();
+        diff.accounts.insert(account(i), Some(Bytes::from(vec![i])));
+        diff.accounts.insert(account(i - 1), None);
+        diff.storages.insert((account(i), B256::ZERO), Some(Bytes::from(vec![i; 3])));
+        diff
+    };
+    for block in 1..=4 {
+        db.journal_snapshot_diff(block, &diff(block as u8)).unwrap();
+    }
+
+    // Applied in block order up to the committed block
+    db.commit_difflayer(1, B256::repeat_byte(1), &None).unwrap();
+    let cancel = rust_eth_triedb_common::CancellationToken::new();
+    cancel.cancel();
+    assert_eq!(db.apply_snapshot_journal_with_cancellation(1, &cancel).unwrap(), 0);
+    assert_eq!(db.recover_snapshot_with_cancellation(&cancel).unwrap(), crate::SnapshotRecovery::default());
+    assert_eq!(db.snapshot_block().unwrap(), None);
+    assert_eq!(db.apply_snapshot_journal(1).unwrap(), 1);
+    assert_eq!(db.snapshot_block().unwrap(), Some(1));
+    assert_eq!(db.get_raw_account_snapshot(account(1)).unwrap(), Some(Bytes::from(vec![1])));
+
+    // Crash after the trie commit of block 3, before the snapshot caught up
+    db.commit_difflayer(3, B256::repeat_byte(3), &None).unwrap();
+    let cf = db.raw_db().cf_handle(SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME).unwrap();
+    db.raw_db().put_cf(&cf, 9u64.to_be_bytes(), b"partial").unwrap();
+    drop(cf);
+    drop(db);
+
+    let db = PathDB::new(path, PathProviderConfig::default()).unwrap();
+    let recovery = db.recover_snapshot().unwrap();
+    assert_eq!((recovery.snapshot_block, recovery.replayed, recovery.discarded), (Some(3), 2, 2));
+    assert_eq!(db.get_raw_account_snapshot(account(2)).unwrap(), None);
+    assert_eq!(db.get_raw_account_snapshot(account(3)).unwrap(), Some(Bytes::from(vec![3])));
+    assert_eq!(db.get_raw_account_snapshot(account(4)).unwrap(), None);
+    assert_eq!(db.get_raw_storage_snapshot(account(3), B256::ZERO).unwrap(), Some(Bytes::from(vec![3; 3])));
+
+    // Nothing is left to recover
+    assert_eq!(db.recover_snapshot().unwrap(), crate::SnapshotRecovery { snapshot_block: Some(3), replayed: 0, discarded: 0 });
+    assert_eq!(db.apply_snapshot_journal(u64::MAX).unwrap(), 0);
+
+    // An unreadable entry the persisted trie state depends on fails the recovery and is kept
+    db.commit_difflayer(4, B256::repeat_byte(4), &None).unwrap();
+    let cf = db.raw_db().cf_handle(SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME).unwrap();
+    db.raw_db().put_cf(&cf, 4u64.to_be_bytes(), b"corrupt").unwrap();
+    db.raw_db().put_cf(&cf, 6u64.to_be_bytes(), b"corrupt").unwrap();
+    drop(cf);
+    assert!(matches!(db.recover_snapshot(), Err(crate::PathProviderError::Deserialization(_))));
+    assert_eq!(db.snapshot_block().unwrap(), Some(3));
+
+    // Applying stops at the requested block without touching later entries
+    db.journal_snapshot_diff(4, &diff(4)).unwrap();
+    assert_eq!(db.apply_snapshot_journal(5).unwrap(), 1);
+    assert_eq!(db.snapshot_block().unwrap(), Some(4));
+    assert_eq!(db.prune_below(6).unwrap(), 0);
+    assert_eq!(db.prune_below(7).unwrap(), 1);
+}
+
+#[test]
+fn test_snapshot_tree() {
+    use std::collections::BTreeMap;
+    use alloy_primitives::{Bytes, B256};
+    use crate::SnapshotTree;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let address = B256::repeat_byte(0xaa);
+    let slot = B256::repeat_byte(0x01);
+    db.put_raw_account_snapshot(address, b"disk").unwrap();
+
+    let block = |i: u8| B256::repeat_byte(i);
+    let accounts = |value: Option<&'static [u8]>| BTreeMap::from([(address, value.map(Bytes::from_static))]);
+    let storage = |value: &'static [u8]| BTreeMap::from([((address, slot), Some(Bytes::from_static(value)))]);
+
+    // disk (0) <- 1 <- 2a
+    //               <- 2b <- 3b
+    let mut tree = SnapshotTree::new(db.clone(), block(0));
+    tree.update(block(1), block(0), accounts(Some(b"one")), storage(b"s1")).unwrap();
+    tree.update(block(0x2a), block(1), accounts(None), BTreeMap::new()).unwrap();
+    tree.update(block(0x2b), block(1), accounts(Some(b"two-b")), storage(b"s2b")).unwrap();
+    tree.update(block(0x3b), block(0x2b), BTreeMap::new(), BTreeMap::new()).unwrap();
+    assert!(tree.update(block(1), block(0), BTreeMap::new(), BTreeMap::new()).is_err());
+    assert!(tree.update(block(9), block(8), BTreeMap::new(), BTreeMap::new()).is_err());
+
+    // Every block sees its own fork
+    assert_eq!(tree.account(block(0), address).unwrap().as_deref(), Some(b"disk".as_slice()));
+    assert_eq!(tree.account(block(1), address).unwrap().as_deref(), Some(b"one".as_slice()));
+    assert_eq!(tree.account(block(0x2a), address).unwrap(), None);
+    assert_eq!(tree.account(block(0x3b), address).unwrap().as_deref(), Some(b"two-b".as_slice()));
+    assert_eq!(tree.storage(block(0x2a), address, slot).unwrap().as_deref(), Some(b"s1".as_slice()));
+    assert_eq!(tree.storage(block(0x3b), address, slot).unwrap().as_deref(), Some(b"s2b".as_slice()));
+    assert_eq!(tree.layers(block(0x3b)).unwrap().iter().map(|layer| layer.block_hash()).collect::<Vec<_>>(), vec![block(0x3b), block(0x2b), block(1)]);
+    assert!(tree.account(block(9), address).is_err());
+
+    // Finalizing 2b writes 1 and 2b to disk and drops the 2a fork
+    assert_eq!(tree.flatten(block(0x2b)).unwrap(), 2);
+    assert_eq!(tree.disk_block_hash(), block(0x2b));
+    assert_eq!(tree.len(), 1);
+    assert!(tree.account(block(0x2a), address).is_err());
+    assert_eq!(db.get_raw_account_snapshot(address).unwrap().as_deref(), Some(b"two-b".as_slice()));
+    assert_eq!(db.get_raw_storage_snapshot(address, slot).unwrap().as_deref(), Some(b"s2b".as_slice()));
+    assert_eq!(tree.account(block(0x3b), address).unwrap().as_deref(), Some(b"two-b".as_slice()));
+    assert_eq!(tree.flatten(block(0x2b)).unwrap(), 0);
+}
+
+#[test]
+fn test_snapshot_wipe_and_prune() {
+    use alloy_primitives::{Bytes, B256};
+    use crate::SnapshotDiff;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+
+    // Wiping removes the storage root, the account and every slot of the account only
+    for address in [B256::repeat_byte(0x10), B256::repeat_byte(0xff), B256::repeat_byte(0x11)] {
+        db.put_raw_account_snapshot(address, b"account").unwrap();
+        for slot in [B256::ZERO, B256::repeat_byte(0x80), B256::repeat_byte(0xff)] {
+            db.put_raw_storage_snapshot(address, slot, b"slot").unwrap();
+        }
+        let mut batch = crate::PathDBWriteBatch::new();
+        batch.put_storage_root(address, B256::repeat_byte(0x01));
+        db.write_batch(batch).unwrap();
+    }
+    for wiped in [B256::repeat_byte(0x10), B256::repeat_byte(0xff)] {
+        assert!(db.get_storage_root(wiped).unwrap().is_some());
+        db.wipe_account(wiped).unwrap();
+        assert_eq!(db.get_storage_root(wiped).unwrap(), None);
+        assert_eq!(db.get_raw_account_snapshot(wiped).unwrap(), None);
+        assert!(db.iter_storage_snapshot(wiped, B256::ZERO, 10).unwrap().is_empty());
+    }
+    let kept = B256::repeat_byte(0x11);
+    assert_eq!(db.get_storage_root(kept).unwrap(), Some(B256::repeat_byte(0x01)));
+    assert_eq!(db.get_raw_account_snapshot(kept).unwrap().as_deref(), Some(b"account".as_slice()));
+    assert_eq!(db.iter_storage_snapshot(kept, B256::ZERO, 10).unwrap().len(), 3);
+
+    // Pruning drops old journal entries without applying them
+    let mut diff = SnapshotDiff::default();
+    diff.accounts.insert(kept, Some(Bytes::from_static(b"journaled")));
+    for block in [3, 5, 8] {
+        db.journal_snapshot_diff(block, &diff).unwrap();
+    }
+    assert_eq!(db.prune_below(2).unwrap(), 0);
+    assert_eq!(db.prune_below(6).unwrap(), 2);
+    assert_eq!(db.apply_snapshot_journal(u64::MAX).unwrap(), 1);
+    assert_eq!(db.snapshot_block().unwrap(), Some(8));
+}
+
+#[test]
+fn test_batch_insert_storage_roots() {
+    use alloy_primitives::B256;
+    use alloy_trie::EMPTY_ROOT_HASH;
+
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().to_str().unwrap();
+    let db = PathDB::new(path, PathProviderConfig::default()).unwrap();
+    assert_eq!(db.latest_persist_state().unwrap(), (0, EMPTY_ROOT_HASH));
+
+    let roots = [(B256::repeat_byte(1), B256::repeat_byte(0x11)), (B256::repeat_byte(2), B256::repeat_byte(0x22))];
+    db.batch_insert_storage_roots(roots, 7, B256::repeat_byte(0x77)).unwrap();
+    assert_eq!(db.latest_persist_state().unwrap(), (7, B256::repeat_byte(0x77)));
+    for (hashed_address, storage_root) in roots {
+        assert_eq!(db.get_storage_root(hashed_address).unwrap(), Some(storage_root));
+    }
+
+    // A batch with only the persisted state is not empty
+    let mut batch = crate::PathDBWriteBatch::new();
+    batch.set_persist_state(8, B256::repeat_byte(0x88));
+    assert_eq!(batch.len(), 1);
+    db.write_batch(batch).unwrap();
+    drop(db);
+
+    let db = PathDB::new(path, PathProviderConfig::default()).unwrap();
+    assert_eq!(db.latest_persist_state().unwrap(), (8, B256::repeat_byte(0x88)));
+    assert_eq!(db.get_storage_root(roots[0].0).unwrap(), Some(roots[0].1));
+}
+
+#[test]
+fn test_write_batch_rejects_invalid_storage_roots() {
+    use alloy_primitives::B256;
+    use crate::PathProviderError;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let hashed_address = B256::repeat_byte(0x01);
+
+    let mut batch = crate::PathDBWriteBatch::new();
+    batch.put_raw_storage_root(hashed_address.as_slice(), B256::repeat_byte(0x11).as_slice());
+    db.write_batch(batch).unwrap();
+    assert_eq!(db.get_storage_root(hashed_address).unwrap(), Some(B256::repeat_byte(0x11)));
+
+    // Totals are process-wide and other tests run concurrently
+    let before = crate::metrics_snapshot::snapshot();
+    let mut batch = crate::PathDBWriteBatch::new();
+    batch.put_storage_root(B256::repeat_byte(0x02), B256::repeat_byte(0x22));
+    batch.put_raw_storage_root(hashed_address.as_slice(), &[0x33; 31]);
+    assert!(matches!(
+        db.write_batch(batch),
+        Err(PathProviderError::InvalidStorageRoot { key_len: 32, value_len: 31, .. })
+    ));
+    let mut batch = crate::PathDBWriteBatch::new();
+    batch.put_raw_storage_root(&[0x01; 20], B256::repeat_byte(0x33).as_slice());
+    assert!(matches!(
+        db.write_batch(batch),
+        Err(PathProviderError::InvalidStorageRoot { key_len: 20, value_len: 32, .. })
+    ));
+    assert!(crate::metrics_snapshot::snapshot().storage_root_corruptions >= before.storage_root_corruptions + 2);
+
+    // A rejected batch writes nothing
+    assert_eq!(db.get_storage_root(hashed_address).unwrap(), Some(B256::repeat_byte(0x11)));
+    assert_eq!(db.get_storage_root(B256::repeat_byte(0x02)).unwrap(), None);
+}
+
+#[test]
+fn test_storage_roots_complete_marker() {
+    use alloy_primitives::B256;
+    use alloy_trie::EMPTY_ROOT_HASH;
+
+    let temp_dir = TempDir::new().unwrap();
+    let path = temp_dir.path().to_str().unwrap();
+    let db = PathDB::new(path, PathProviderConfig::default()).unwrap();
+    let (stored, missing) = (B256::repeat_byte(1), B256::repeat_byte(2));
+    db.batch_insert_storage_roots([(stored, B256::repeat_byte(0x11))], 5, B256::repeat_byte(0x55)).unwrap();
+
+    assert_eq!(db.storage_roots_complete_block().unwrap(), None);
+    assert_eq!(db.get_storage_root(missing).unwrap(), None);
+    // The marker can't be ahead of the persisted state
+    assert!(db.mark_storage_roots_complete(6).is_err());
+
+    db.mark_storage_roots_complete(5).unwrap();
+    assert_eq!(db.storage_roots_complete_block().unwrap(), Some(5));
+    assert_eq!(db.get_storage_root(missing).unwrap(), Some(EMPTY_ROOT_HASH));
+    assert_eq!(db.get_storage_root(stored).unwrap(), Some(B256::repeat_byte(0x11)));
+    drop(db);
+
+    let db = PathDB::new(path, PathProviderConfig::default()).unwrap();
+    assert_eq!(db.storage_roots_complete_block().unwrap(), Some(5));
+    assert_eq!(db.get_storage_root(missing).unwrap(), Some(EMPTY_ROOT_HASH));
+}
+
+#[test]
+fn test_open_adds_snapshot_column_families() {
+    use alloy_primitives::{Bytes, B256};
+    use crate::pathdb::{
+        ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, META_COLUMN_FAMILY_NAME, SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME,
+        STORAGE_ROOT_COLUMN_FAMILY_NAME, STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME,
+    };
+    use crate::traits::CfConfig;
+
+    let temp_dir = TempDir::new().unwrap();
+    let db_path = temp_dir.path().to_str().unwrap();
+    let hashed_address = B256::repeat_byte(1);
+
+    // A database from before the snapshot tables
+    {
+        let mut opts = rocksdb::Options::default();
+        opts.create_if_missing(true);
+        opts.create_missing_column_families(true);
+        let legacy = rocksdb::DB::open_cf(&opts, db_path, ["default", META_COLUMN_FAMILY_NAME, STORAGE_ROOT_COLUMN_FAMILY_NAME]).unwrap();
+        let storage_root_cf = legacy.cf_handle(STORAGE_ROOT_COLUMN_FAMILY_NAME).unwrap();
+        legacy.put_cf(&storage_root_cf, hashed_address, B256::repeat_byte(0x11)).unwrap();
+    }
+
+    // The snapshot tables are tuned apart from the trie tables
+    let mut config = PathProviderConfig::default();
+    for name in [ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME] {
+        config.cf_overrides.insert(name.to_string(), CfConfig { write_buffer_size: Some(8 << 20), ..Default::default() });
+    }
+    let db = PathDB::new(db_path, config).unwrap();
+    for name in [ACCOUNT_SNAPSHOT_COLUMN_FAMILY_NAME, STORAGE_SNAPSHOT_COLUMN_FAMILY_NAME, SNAPSHOT_JOURNAL_COLUMN_FAMILY_NAME] {
+        assert!(db.column_families().contains(&name.to_string()));
+    }
+    assert_eq!(db.get_storage_root(hashed_address).unwrap(), Some(B256::repeat_byte(0x11)));
+    for name in ["storage_root", "account_snap", "storage_snap", "meta_data"] {
+        assert!(db.column_families().contains(&name.to_string()));
+    }
+
+    // Same key, separate tables
+    db.put_raw_account_snapshot(hashed_address, b"account").unwrap();
+    assert_eq!(db.get_raw_account_snapshot(hashed_address).unwrap(), Some(Bytes::from_static(b"account")));
+    assert_eq!(db.get_storage_root(hashed_address).unwrap(), Some(B256::repeat_byte(0x11)));
+    assert_eq!(db.iter_storage_snapshot(hashed_address, B256::ZERO, 10).unwrap(), vec![]);
+}
+
+#[test]
+fn test_export_import_storage_roots() {
+    use alloy_primitives::B256;
+
+    let source_dir = TempDir::new().unwrap();
+    let source = PathDB::new(source_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let roots: Vec<(B256, B256)> = (1u8..=5).map(|i| (B256::repeat_byte(i), B256::repeat_byte(0x10 + i))).collect();
+    source.batch_insert_storage_roots(roots.clone(), 9, B256::repeat_byte(0x99)).unwrap();
+
+    let export_dir = TempDir::new().unwrap();
+    let file = export_dir.path().join("storage_roots.bin");
+    let exported = source.export_storage_roots(&file).unwrap();
+    assert_eq!((exported.block_number, exported.state_root, exported.entries), (9, B256::repeat_byte(0x99), 5));
+    assert_eq!(std::fs::metadata(&file).unwrap().len(), 57 + 5 * 64);
+    assert!(!export_dir.path().join("storage_roots.bin.tmp").exists());
+    // An existing file is never overwritten
+    assert!(source.export_storage_roots(&file).is_err());
+
+    // A namespace exports its own roots only
+    let namespaced = source.with_namespace(b"chain").unwrap();
+    namespaced.batch_insert_storage_roots([(B256::repeat_byte(0x42), B256::repeat_byte(0x43))], 3, B256::repeat_byte(0x33)).unwrap();
+    let namespaced_file = export_dir.path().join("namespaced.bin");
+    assert_eq!(namespaced.export_storage_roots(&namespaced_file).unwrap().entries, 1);
+
+    // Keys are exported without the namespace of the source
+    let target_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.key_namespace = Some(b"chain".to_vec());
+    let target = PathDB::new(target_dir.path().to_str().unwrap(), config).unwrap();
+    assert_eq!(target.import_storage_roots(&file).unwrap(), exported);
+    for (hashed_address, storage_root) in &roots {
+        assert_eq!(target.get_storage_root(*hashed_address).unwrap(), Some(*storage_root));
+    }
+    // The persisted state stays with the caller
+    assert_eq!(target.latest_persist_state().unwrap().0, 0);
+
+    // Truncated files are rejected
+    let bytes = std::fs::read(&file).unwrap();
+    let truncated = export_dir.path().join("truncated.bin");
+    std::fs::write(&truncated, &bytes[..bytes.len() - 1]).unwrap();
+    assert!(target.import_storage_roots(&truncated).is_err());
+}
+
+#[test]
+fn test_storage_root_history() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::B256;
+    use alloy_trie::EMPTY_ROOT_HASH;
+    use rust_eth_triedb_common::DiffLayer;
+
+    let temp_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.storage_root_history = true;
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), config).unwrap();
+    let (account, other) = (B256::repeat_byte(1), B256::repeat_byte(2));
+
+    let commit = |block_number: u64, roots: Vec<(B256, B256)>| {
+        let difflayer = DiffLayer::new(HashMap::new(), roots.into_iter().collect());
+        db.commit_difflayer(block_number, B256::with_last_byte(block_number as u8), &Some(Arc::new(difflayer))).unwrap();
+    };
+    commit(10, vec![(account, B256::repeat_byte(0x10)), (other, B256::repeat_byte(0xaa))]);
+    commit(20, vec![(account, B256::repeat_byte(0x20))]);
+    commit(30, vec![(account, EMPTY_ROOT_HASH)]);
+    // Batched writes with a persisted state are recorded as well
+    db.batch_insert_storage_roots([(account, B256::repeat_byte(0x40))], 40, B256::with_last_byte(40)).unwrap();
+
+    assert_eq!(db.get_storage_root_at(account, 9).unwrap(), None);
+    assert_eq!(db.get_storage_root_at(account, 10).unwrap(), Some(B256::repeat_byte(0x10)));
+    assert_eq!(db.get_storage_root_at(account, 19).unwrap(), Some(B256::repeat_byte(0x10)));
+    assert_eq!(db.get_storage_root_at(account, 25).unwrap(), Some(B256::repeat_byte(0x20)));
+    assert_eq!(db.get_storage_root_at(account, 30).unwrap(), Some(EMPTY_ROOT_HASH));
+    assert_eq!(db.get_storage_root_at(account, u64::MAX).unwrap(), Some(B256::repeat_byte(0x40)));
+    assert_eq!(db.get_storage_root_at(other, 35).unwrap(), Some(B256::repeat_byte(0xaa)));
+    assert_eq!(db.get_storage_root(account).unwrap(), Some(B256::repeat_byte(0x40)));
+
+    // A cancelled prune deletes nothing
+    let cancel = rust_eth_triedb_common::CancellationToken::new();
+    cancel.cancel();
+    assert_eq!(db.prune_storage_root_history_with_cancellation(25, &cancel).unwrap(), 0);
+    assert_eq!(db.get_storage_root_at(account, 19).unwrap(), Some(B256::repeat_byte(0x10)));
+
+    // Pruning keeps the roots still answering lookups from block 25 on
+    assert_eq!(db.prune_storage_root_history(25).unwrap(), 1);
+    assert_eq!(db.get_storage_root_at(account, 19).unwrap(), None);
+    assert_eq!(db.get_storage_root_at(account, 25).unwrap(), Some(B256::repeat_byte(0x20)));
+    assert_eq!(db.get_storage_root_at(other, 25).unwrap(), Some(B256::repeat_byte(0xaa)));
+    assert_eq!(db.prune_storage_root_history(25).unwrap(), 0);
+
+    // Namespaces sharing a database keep separate histories
+    let shared_dir = TempDir::new().unwrap();
+    let mut config = PathProviderConfig::default();
+    config.storage_root_history = true;
+    config.key_namespace = Some(b"a".to_vec());
+    let first = PathDB::new(shared_dir.path().to_str().unwrap(), config).unwrap();
+    let second = first.with_namespace(b"b").unwrap();
+    for (block_number, root) in [(10u64, 0x10u8), (20, 0x20)] {
+        first.batch_insert_storage_roots([(account, B256::repeat_byte(root))], block_number, B256::with_last_byte(root)).unwrap();
+    }
+    second.batch_insert_storage_roots([(account, B256::repeat_byte(0xbb))], 15, B256::with_last_byte(0xbb)).unwrap();
+    assert_eq!(first.get_storage_root_at(account, 15).unwrap(), Some(B256::repeat_byte(0x10)));
+    assert_eq!(second.get_storage_root_at(account, 12).unwrap(), None);
+    assert_eq!(second.get_storage_root_at(account, 25).unwrap(), Some(B256::repeat_byte(0xbb)));
+    assert_eq!(second.prune_storage_root_history(25).unwrap(), 0);
+    assert_eq!(first.prune_storage_root_history(25).unwrap(), 1);
+    assert_eq!(second.get_storage_root_at(account, 15).unwrap(), Some(B256::repeat_byte(0xbb)));
+}
+
+#[test]
+fn test_overlay_db() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::{Bytes, B256};
+    use alloy_trie::EMPTY_ROOT_HASH;
+    use rust_eth_triedb_common::{DiffLayer, OverlayDB, TrieNode};
+
+    let temp_dir = TempDir::new().unwrap();
+    let base = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    base.put_raw_trie_node(b"A1", b"base").unwrap();
+    base.put_raw_trie_node(b"A2", b"removed").unwrap();
+    base.put_raw_account_snapshot(B256::repeat_byte(0x01), b"base").unwrap();
+    base.put_raw_account_snapshot(B256::repeat_byte(0x02), b"removed").unwrap();
+    let overlay = OverlayDB::new(base.clone());
+
+    let storage_root = B256::repeat_byte(0x11);
+    let diff_nodes = HashMap::from([
+        (b"A3".to_vec(), Arc::new(TrieNode::new(None, Some(Bytes::from_static(b"committed"))))),
+        (b"A2".to_vec(), Arc::new(TrieNode::new(None, None))),
+    ]);
+    let difflayer = DiffLayer::new(diff_nodes, HashMap::from([(B256::repeat_byte(0xaa), storage_root)]));
+    overlay.commit_difflayer(1, B256::repeat_byte(0x01), &Some(Arc::new(difflayer))).unwrap();
+    overlay.insert_trie_node(b"A4", b"inserted".to_vec()).unwrap();
+    overlay.put_account_snapshot(B256::repeat_byte(0x02), None).unwrap();
+    overlay.put_account_snapshot(B256::repeat_byte(0x03), Some(b"overlay")).unwrap();
+    assert_eq!(overlay.pending_writes(), 4);
+
+    // Reads see the overlay first, the base is untouched
+    assert_eq!(overlay.get_trie_node(b"A1").unwrap().as_deref(), Some(b"base".as_slice()));
+    assert_eq!(overlay.get_trie_node(b"A2").unwrap(), None);
+    assert_eq!(overlay.get_trie_nodes(&[b"A3".to_vec(), b"A1".to_vec(), b"A4".to_vec()]).unwrap().iter().map(|node| node.is_some()).collect::<Vec<_>>(), [true, true, true]);
+    assert_eq!(TrieDatabase::get_storage_root(&overlay, B256::repeat_byte(0xaa)).unwrap(), Some(storage_root));
+    assert_eq!(overlay.latest_persist_state().unwrap(), (1, B256::repeat_byte(0x01)));
+    let accounts = overlay.iter_account_snapshot(B256::ZERO, 10).unwrap();
+    assert_eq!(accounts.iter().map(|(hashed_address, _)| *hashed_address).collect::<Vec<_>>(), [B256::repeat_byte(0x01), B256::repeat_byte(0x03)]);
+    assert_eq!(base.get_raw_trie_node(b"A3").unwrap(), None);
+    assert_eq!(TrieDatabase::latest_persist_state(&base).unwrap(), (0, EMPTY_ROOT_HASH));
+
+    overlay.discard();
+    assert_eq!(overlay.pending_writes(), 0);
+    assert_eq!(overlay.get_trie_node(b"A2").unwrap().as_deref(), Some(b"removed".as_slice()));
+
+    overlay.insert_trie_node(b"A4", b"inserted".to_vec()).unwrap();
+    overlay.put_account_snapshot(B256::repeat_byte(0x02), None).unwrap();
+    overlay.flatten_into_base().unwrap();
+    assert_eq!(overlay.pending_writes(), 0);
+    assert_eq!(base.get_raw_trie_node(b"A4").unwrap().as_deref(), Some(b"inserted".as_slice()));
+    assert_eq!(TrieDatabase::get_account_snapshot(&base, B256::repeat_byte(0x02)).unwrap(), None);
+}
+
+#[test]
+fn test_overlay_db_memory_limit() {
+    use alloy_primitives::B256;
+    use rust_eth_triedb_common::OverlayDB;
+    use crate::PathProviderError;
+
+    let temp_dir = TempDir::new().unwrap();
+    let base = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let overlay = OverlayDB::new(base).with_memory_limit(100);
+
+    overlay.insert_trie_node(b"A1", vec![0x01; 40]).unwrap();
+    overlay.put_account_snapshot(B256::repeat_byte(0x01), Some(&[0x02; 8])).unwrap();
+    assert_eq!(overlay.memory_usage(), 2 + 40 + 32 + 8);
+
+    // A rejected write is not buffered
+    let result = overlay.insert_trie_node(b"A2", vec![0x03; 40]);
+    assert!(matches!(result, Err(PathProviderError::MemoryLimitExceeded(e)) if e.usage == 82 && e.write == 42 && e.limit == 100));
+    assert_eq!(overlay.get_trie_node(b"A2").unwrap(), None);
+    assert_eq!(overlay.pending_writes(), 2);
+
+    overlay.flatten_into_base().unwrap();
+    assert_eq!(overlay.memory_usage(), 0);
+    overlay.insert_trie_node(b"A2", vec![0x03; 40]).unwrap();
+    assert_eq!(overlay.memory_usage(), 42);
+    overlay.discard();
+    assert_eq!(overlay.memory_usage(), 0);
+}
+
+#[test]
+fn test_overlay_db_concurrent_writes() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use alloy_primitives::{Bytes, B256};
+    use rust_eth_triedb_common::{DiffLayer, OverlayDB, TrieNode};
+
+    let temp_dir = TempDir::new().unwrap();
+    let base = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let overlay = OverlayDB::new(base.clone());
+
+    std::thread::scope(|scope| {
+        for thread in 0..8u8 {
+            let overlay = overlay.clone();
+            scope.spawn(move || {
+                for i in 0..100u8 {
+                    overlay.insert_trie_node(&[b'S', thread, i], vec![thread, i]).unwrap();
+                }
+            });
+        }
+    });
+    assert_eq!(overlay.pending_writes(), 800);
+
+    // A later range deletion wipes nodes of every shard, nodes of the same layer are kept
+    let kept = Arc::new(TrieNode::new(None, Some(Bytes::from_static(b"kept"))));
+    let difflayer = DiffLayer::new(HashMap::from([(vec![b'S', 3, 3], kept)]), HashMap::new())
+        .with_deleted_ranges(vec![(vec![b'S', 2], vec![b'S', 4])]);
+    overlay.commit_difflayer(1, B256::repeat_byte(0x01), &Some(Arc::new(difflayer))).unwrap();
+    overlay.insert_trie_node(&[b'S', 2, 7], b"after".to_vec()).unwrap();
+    assert_eq!(overlay.get_trie_node(&[b'S', 2, 5]).unwrap(), None);
+
+    overlay.flatten_into_base().unwrap();
+    assert_eq!(base.get_raw_trie_node(&[b'S', 1, 99]).unwrap().as_deref(), Some([1u8, 99].as_slice()));
+    assert_eq!(base.get_raw_trie_node(&[b'S', 2, 5]).unwrap(), None);
+    assert_eq!(base.get_raw_trie_node(&[b'S', 2, 7]).unwrap().as_deref(), Some(b"after".as_slice()));
+    assert_eq!(base.get_raw_trie_node(&[b'S', 3, 3]).unwrap().as_deref(), Some(b"kept".as_slice()));
+    assert_eq!(base.get_raw_trie_node(&[b'S', 4, 0]).unwrap().as_deref(), Some([4u8, 0].as_slice()));
+}
+
+#[test]
+fn test_overlay_db_child() {
+    use rust_eth_triedb_common::OverlayDB;
+
+    let temp_dir = TempDir::new().unwrap();
+    let base = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    base.put_raw_trie_node(b"A1", b"base").unwrap();
+    let parent = OverlayDB::new(base.clone());
+    parent.insert_trie_node(b"A2", b"parent".to_vec()).unwrap();
+
+    let left = parent.child();
+    let right = parent.child();
+    left.insert_trie_node(b"A1", b"left".to_vec()).unwrap();
+    right.remove_trie_node(b"A2");
+
+    // Children read through the parent and don't see each other's writes
+    assert_eq!(left.get_trie_node(b"A1").unwrap().as_deref(), Some(b"left".as_slice()));
+    assert_eq!(left.get_trie_node(b"A2").unwrap().as_deref(), Some(b"parent".as_slice()));
+    assert_eq!(right.get_trie_node(b"A1").unwrap().as_deref(), Some(b"base".as_slice()));
+    assert_eq!(right.get_trie_node(b"A2").unwrap(), None);
+    assert_eq!(parent.get_trie_node(b"A1").unwrap().as_deref(), Some(b"base".as_slice()));
+    assert_eq!(parent.pending_writes(), 1);
+
+    // Flattening a child merges its writes into the parent only
+    left.flatten_into_base().unwrap();
+    assert_eq!(parent.get_trie_node(b"A1").unwrap().as_deref(), Some(b"left".as_slice()));
+    assert_eq!(base.get_raw_trie_node(b"A1").unwrap().as_deref(), Some(b"base".as_slice()));
+    parent.flatten_into_base().unwrap();
+    assert_eq!(base.get_raw_trie_node(b"A1").unwrap().as_deref(), Some(b"left".as_slice()));
+}
+
+#[test]
+fn test_overlay_db_rollback() {
+    use rust_eth_triedb_common::{DiffLayer, OverlayDB};
+    use alloy_primitives::B256;
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    let temp_dir = TempDir::new().unwrap();
+    let base = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let overlay = OverlayDB::new(base.clone());
+    let address = B256::repeat_byte(0x01);
+
+    overlay.insert_trie_node(b"A1", b"block1".to_vec()).unwrap();
+    let difflayer = DiffLayer::new(HashMap::new(), HashMap::from([(address, B256::repeat_byte(0x11))]));
+    overlay.commit_difflayer(1, B256::repeat_byte(0xa1), &Some(Arc::new(difflayer))).unwrap();
+    let savepoint = overlay.savepoint();
+    let usage = overlay.memory_usage();
+
+    // Block 2 of the abandoned fork
+    overlay.insert_trie_node(b"A1", b"block2".to_vec()).unwrap();
+    overlay.insert_trie_node(b"A2", b"block2".to_vec()).unwrap();
+    let difflayer = DiffLayer::new(HashMap::new(), HashMap::from([(address, B256::repeat_byte(0x22))]));
+    overlay.commit_difflayer(2, B256::repeat_byte(0xa2), &Some(Arc::new(difflayer))).unwrap();
+    assert_eq!(overlay.get_storage_root(address).unwrap(), Some(B256::repeat_byte(0x22)));
+
+    assert_eq!(overlay.rollback_to(savepoint), 3);
+    assert_eq!(overlay.pending_writes(), 2);
+    assert_eq!(overlay.memory_usage(), usage);
+    assert_eq!(overlay.get_trie_node(b"A1").unwrap().as_deref(), Some(b"block1".as_slice()));
+    assert_eq!(overlay.get_trie_node(b"A2").unwrap(), None);
+    assert_eq!(overlay.get_storage_root(address).unwrap(), Some(B256::repeat_byte(0x11)));
+    assert_eq!(overlay.latest_persist_state().unwrap(), (1, B256::repeat_byte(0xa1)));
+
+    // Nothing left to undo
+    assert_eq!(overlay.rollback_to(savepoint), 0);
+    overlay.flatten_into_base().unwrap();
+    assert_eq!(base.get_raw_trie_node(b"A1").unwrap().as_deref(), Some(b"block1".as_slice()));
+}
+
+#[test]
+fn test_cached_db() {
+    use crate::CachedDB;
+    use rust_eth_triedb_common::{DiffLayer, TrieNode};
+    use alloy_primitives::{Bytes, B256};
+    use std::collections::HashMap;
+    use std::sync::Arc;
+
+    let temp_dir = TempDir::new().unwrap();
+    let base = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    base.put_raw_trie_node(b"A1", b"old").unwrap();
+    base.put_raw_trie_node(b"B1", b"old").unwrap();
+    let cached = CachedDB::new(base.clone(), 1024);
+
+    assert_eq!(cached.get_trie_node(b"A1").unwrap().as_deref(), Some(b"old".as_slice()));
+    let nodes = cached.get_trie_nodes(&[b"A1".to_vec(), b"B1".to_vec(), b"C1".to_vec()]).unwrap();
+    assert_eq!(nodes[1].as_deref(), Some(b"old".as_slice()));
+    assert_eq!(nodes[2], None);
+    assert_eq!(cached.cached_nodes(), 3);
+    assert_eq!(cached.lookups(), (1, 3));
+
+    // Writes bypassing the wrapper are not seen until the cache is cleared
+    base.put_raw_trie_node(b"B1", b"bypass").unwrap();
+    assert_eq!(cached.get_trie_node(b"B1").unwrap().as_deref(), Some(b"old".as_slice()));
+
+    // Writes through the wrapper drop the nodes they touch
+    cached.insert_trie_node(b"A1", b"new".to_vec()).unwrap();
+    assert_eq!(cached.get_trie_node(b"A1").unwrap().as_deref(), Some(b"new".as_slice()));
+    let node = Arc::new(TrieNode::new(None, Some(Bytes::from_static(b"committed"))));
+    let difflayer = DiffLayer::new(HashMap::from([(b"C1".to_vec(), node)]), HashMap::new())
+        .with_deleted_ranges(vec![(b"B".to_vec(), b"C".to_vec())]);
+    cached.commit_difflayer(1, B256::repeat_byte(0x01), &Some(Arc::new(difflayer))).unwrap();
+    assert_eq!(cached.get_trie_node(b"B1").unwrap(), None);
+    assert_eq!(cached.get_trie_node(b"C1").unwrap().as_deref(), Some(b"committed".as_slice()));
+
+    cached.clear_cache();
+    assert_eq!(cached.cached_nodes(), 0);
+}
+
+#[test]
+fn test_overlay_db_buffered_trie_nodes() {
+    use rust_eth_triedb_common::OverlayDB;
+
+    let temp_dir = TempDir::new().unwrap();
+    let base = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).unwrap();
+    let overlay = OverlayDB::new(base);
+    let paths: Vec<Vec<u8>> = (0..64u8).rev().map(|i| vec![b'A', i]).collect();
+    for path in &paths {
+        overlay.insert_trie_node(path, path.clone()).unwrap();
+    }
+    overlay.remove_trie_node(&paths[0]);
+
+    let nodes: Vec<_> = overlay.buffered_trie_nodes().into_iter().collect();
+    assert_eq!(nodes.len(), 64);
+    assert!(nodes.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    assert_eq!(nodes[63], (paths[0].clone(), None));
+    assert_eq!(nodes[0].1.as_deref(), Some(paths[63].as_slice()));
+}
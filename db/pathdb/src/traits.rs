@@ -1,6 +1,10 @@
 //! PathProvider trait definitions for key-value database operations.
 
+use std::collections::HashMap;
 use std::fmt::Debug;
+use std::time::Duration;
+
+use rust_eth_triedb_common::{MemoryLimitExceeded, TrieDBErrorSource};
 
 // Default configuration constants
 pub const DEFAULT_MAX_OPEN_FILES: i32 = 10000000;
@@ -8,15 +12,103 @@ pub const DEFAULT_WRITE_BUFFER_SIZE: usize = 4 * 1024 * 1024 * 1024; // 4GB
 pub const DEFAULT_MAX_WRITE_BUFFER_NUMBER: i32 = 4;
 pub const DEFAULT_TARGET_FILE_SIZE_BASE: u64 = 64 * 1024 * 1024; // 64MB
 pub const DEFAULT_MAX_BACKGROUND_JOBS: i32 = 4;
+pub const DEFAULT_MAX_SUBCOMPACTIONS: u32 = 1;
+pub const DEFAULT_RATE_LIMIT_BYTES_PER_SEC: Option<i64> = None; // disabled
+pub const DEFAULT_RATE_LIMIT_AUTO_TUNED: bool = false;
+pub const DEFAULT_COMPACTION_PRIORITY: CompactionPriority = CompactionPriority::MinOverlappingRatio;
+pub const DEFAULT_COMPACTION_STYLE: CompactionStyle = CompactionStyle::Level;
 pub const DEFAULT_CREATE_IF_MISSING: bool = true;
+pub const DEFAULT_PARANOID_CHECKS: bool = true;
 pub const DEFAULT_TRIE_NODECACHE_SIZE: u32 = 20_000_000; // 2KW entries
 pub const DEFAULT_STORAGE_ROOT_CACHE_SIZE: u32 = 200_000_000; // 20KW entries
+pub const DEFAULT_CACHE_SHARDS: usize = 16;
+pub const DEFAULT_CACHE_MAX_VALUE_SIZE: Option<usize> = None; // admit all values
+pub const DEFAULT_NEGATIVE_CACHE_SIZE: Option<u32> = None; // disabled
+pub const DEFAULT_NEGATIVE_CACHE_TTL: Duration = Duration::from_secs(30);
+
+// Direct I/O configuration constants
+pub const DEFAULT_USE_DIRECT_READS: bool = false;
+pub const DEFAULT_USE_DIRECT_IO_FOR_FLUSH_AND_COMPACTION: bool = false;
+pub const DEFAULT_COMPACTION_READAHEAD_SIZE: usize = 2 * 1024 * 1024; // 2MB
 
 // ReadOptions configuration constants
 pub const DEFAULT_FILL_CACHE: bool = true;
 pub const DEFAULT_READAHEAD_SIZE: usize = 128 * 1024; // 128KB
 pub const DEFAULT_ASYNC_IO: bool = true;
 pub const DEFAULT_VERIFY_CHECKSUMS: bool = false;
+pub const DEFAULT_VERIFIED_READAHEAD_SIZE: usize = 0; // RocksDB's adaptive readahead
+
+// Linear walk ReadOptions configuration constants
+pub const DEFAULT_SCAN_READAHEAD_SIZE: usize = 4 * 1024 * 1024; // 4MB
+pub const DEFAULT_SCAN_FILL_CACHE: bool = false;
+
+// Overflow storage configuration constants
+pub const DEFAULT_OVERFLOW_THRESHOLD: Option<usize> = None; // disabled
+pub const DEFAULT_OVERFLOW_CHUNK_SIZE: usize = 64 * 1024; // 64KB
+
+// Deferred deletion configuration constants
+pub const DEFAULT_DEFERRED_DELETION: bool = false;
+pub const DEFAULT_DELETION_BATCH_SIZE: usize = 10_000;
+pub const DEFAULT_DELETION_INTERVAL: Duration = Duration::from_millis(500);
+
+// Periodic flush configuration constants
+pub const DEFAULT_FLUSH_INTERVAL: Duration = Duration::from_secs(10);
+
+// Disk usage reporting configuration constants
+pub const DEFAULT_DISK_USAGE_INTERVAL: Duration = Duration::from_secs(60);
+
+// Amplification reporting configuration constants
+pub const DEFAULT_ENABLE_STATISTICS: bool = false;
+pub const DEFAULT_AMPLIFICATION_WINDOW: usize = 128; // commits
+
+// Prefix extractor configuration constants
+pub const DEFAULT_PREFIX_EXTRACTOR: PrefixExtractor = PrefixExtractor::None;
+pub const OWNER_PREFIX_LEN: usize = 1 + 32; // storage trie marker + owner hash
+
+// Compaction style configuration constants
+pub const DEFAULT_UNIVERSAL_SIZE_RATIO: u32 = 1; // percent
+pub const DEFAULT_UNIVERSAL_MAX_SIZE_AMPLIFICATION_PERCENT: u32 = 200;
+pub const DEFAULT_FIFO_MAX_TABLE_FILES_SIZE: u64 = 1024 * 1024 * 1024; // 1GB
+
+// Compression configuration constants
+pub const RECOMMENDED_ZSTD_MAX_DICT_BYTES: u32 = 16 * 1024; // 16KB
+pub const RECOMMENDED_ZSTD_MAX_TRAIN_BYTES: u32 = 100 * RECOMMENDED_ZSTD_MAX_DICT_BYTES;
+
+// Blob file configuration constants
+pub const DEFAULT_BLOB_FILES: Option<BlobConfig> = None; // disabled
+pub const DEFAULT_MIN_BLOB_SIZE: u64 = 512; // bytes, about a branch node with 16 children
+pub const DEFAULT_BLOB_FILE_SIZE: u64 = 256 * 1024 * 1024; // 256MB
+pub const DEFAULT_BLOB_GC_AGE_CUTOFF: f64 = 0.25;
+
+// Block-based table configuration constants
+pub const DEFAULT_BLOCK_CACHE_SIZE: Option<usize> = None; // RocksDB default cache per Column Family
+pub const DEFAULT_BLOOM_FILTER_BITS_PER_KEY: Option<f64> = None; // disabled
+pub const DEFAULT_PREFIX_BLOOM_BITS_PER_KEY: f64 = 10.0;
+pub const DEFAULT_CACHE_INDEX_AND_FILTER_BLOCKS: bool = false;
+pub const DEFAULT_PIN_L0_FILTER_AND_INDEX_BLOCKS_IN_CACHE: bool = false;
+
+// Access tracking and cold storage configuration constants
+pub const DEFAULT_ACCESS_SAMPLE_RATE: u32 = 0; // disabled
+pub const DEFAULT_ACCESS_TRACKER_SIZE: u32 = 1_000_000; // paths
+pub const DEFAULT_COLD_STORAGE: bool = false;
+pub const DEFAULT_COLD_COMPRESSION: CompressionType = CompressionType::Zstd;
+
+// Storage root history configuration constants
+pub const DEFAULT_STORAGE_ROOT_HISTORY: bool = false;
+
+// Graceful close configuration constants
+pub const DEFAULT_HOT_KEYS_LIMIT: usize = 100_000;
+
+// Write durability configuration constants
+pub const DEFAULT_DISABLE_WAL: bool = false;
+pub const DEFAULT_SYNC_WRITES: bool = false;
+
+// Maintenance write configuration constants
+pub const DEFAULT_MAINTENANCE_LOW_PRI: bool = false;
+pub const DEFAULT_MAINTENANCE_RATE_LIMIT_BYTES_PER_SEC: Option<u64> = None; // unlimited
+
+/// Maximum length in bytes of a key namespace.
+pub const MAX_KEY_NAMESPACE_LEN: usize = u8::MAX as usize;
 
 /// Result type for PathProvider operations.
 pub type PathProviderResult<T> = Result<T, PathProviderError>;
@@ -24,8 +116,26 @@ pub type PathProviderResult<T> = Result<T, PathProviderError>;
 /// Error type for PathProvider operations.
 #[derive(Debug, thiserror::Error)]
 pub enum PathProviderError {
-    #[error("Database error: {0}")]
-    Database(String),
+    /// RocksDB reported a missing file or entry.
+    #[error("Not found: {context}: {source}")]
+    NotFound { context: String, source: rocksdb::Error },
+    /// RocksDB found corrupted data, e.g. a block checksum mismatch; retrying
+    /// won't help, see `PathDB::repair`.
+    #[error("Corruption: {context}: {source}")]
+    Corruption { context: String, source: rocksdb::Error },
+    /// RocksDB couldn't complete the operation now, e.g. a busy lock or a
+    /// timed out write; the operation may succeed when retried.
+    #[error("Busy: {context}: {source}")]
+    Busy { context: String, source: rocksdb::Error },
+    /// RocksDB failed to read or write its files.
+    #[error("IO error: {context}: {source}")]
+    IOError { context: String, source: rocksdb::Error },
+    /// A Column Family the operation needs doesn't exist.
+    #[error("Column Family '{name}' not found")]
+    ColumnFamilyMissing { name: String },
+    /// Any other RocksDB error.
+    #[error("Database error: {context}: {source}")]
+    Database { context: String, source: rocksdb::Error },
     #[error("IO error: {0}")]
     Io(#[from] std::io::Error),
     #[error("Serialization error: {0}")]
@@ -36,18 +146,364 @@ pub enum PathProviderError {
     KeyNotFound(Vec<u8>),
     #[error("Invalid operation: {0}")]
     InvalidOperation(String),
+    #[error("Commit rejected: {0}")]
+    CommitRejected(String),
+    /// A storage root write with a key or value that isn't 32 bytes long.
+    #[error("Invalid storage root write for key {key:?}: {key_len} byte key, {value_len} byte value")]
+    InvalidStorageRoot { key: Vec<u8>, key_len: usize, value_len: usize },
+    /// A write to an `OverlayDB` over the database exceeded its memory limit.
+    #[error(transparent)]
+    MemoryLimitExceeded(#[from] MemoryLimitExceeded),
+}
+
+impl PathProviderError {
+    /// Wrap a RocksDB error, classified by its kind, with the operation that
+    /// failed.
+    pub fn rocksdb(context: impl Into<String>, source: rocksdb::Error) -> Self {
+        let context = context.into();
+        match source.kind() {
+            rocksdb::ErrorKind::NotFound => Self::NotFound { context, source },
+            rocksdb::ErrorKind::Corruption => Self::Corruption { context, source },
+            rocksdb::ErrorKind::Busy
+            | rocksdb::ErrorKind::TryAgain
+            | rocksdb::ErrorKind::TimedOut
+            | rocksdb::ErrorKind::Incomplete => Self::Busy { context, source },
+            rocksdb::ErrorKind::IOError => Self::IOError { context, source },
+            _ => Self::Database { context, source },
+        }
+    }
+
+    /// Error for the missing Column Family `name`.
+    pub fn column_family_missing(name: &str) -> Self {
+        Self::ColumnFamilyMissing { name: name.to_string() }
+    }
+
+    /// Whether the operation may succeed when retried.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Busy { .. })
+    }
+
+    /// Whether the database holds corrupted data.
+    pub fn is_corruption(&self) -> bool {
+        matches!(self, Self::Corruption { .. })
+    }
+
+    /// The underlying RocksDB error, if any.
+    pub fn rocksdb_error(&self) -> Option<&rocksdb::Error> {
+        match self {
+            Self::NotFound { source, .. }
+            | Self::Corruption { source, .. }
+            | Self::Busy { source, .. }
+            | Self::IOError { source, .. }
+            | Self::Database { source, .. } => Some(source),
+            _ => None,
+        }
+    }
+}
+
+impl TrieDBErrorSource for PathProviderError {
+    fn is_retryable(&self) -> bool {
+        PathProviderError::is_retryable(self)
+    }
+
+    fn is_corruption(&self) -> bool {
+        PathProviderError::is_corruption(self)
+    }
 }
 
 /// Trait for database management operations.
 pub trait PathProviderManager: Send + Sync + Debug {
-    /// Close the database.
+    /// Close the database, persisting whatever is needed for a clean restart.
     fn close(&self) -> PathProviderResult<()>;
 
     /// Flush all pending writes to disk.
     fn flush(&self) -> PathProviderResult<()>;
 
-    /// Compact the database.
-    fn compact(&self) -> PathProviderResult<()>;
+    /// Compact all column families, optionally only the keys from `start` to `end`.
+    ///
+    /// Rewrites the SST files covering the range, which drops deleted and
+    /// overwritten values, e.g. to reclaim space after a large prune. Blocks
+    /// until the compaction is done.
+    fn compact(&self, range: Option<(&[u8], &[u8])>) -> PathProviderResult<()>;
+
+    /// Compact the whole column family `cf_name`.
+    fn compact_cf(&self, cf_name: &str) -> PathProviderResult<()>;
+}
+
+/// Prefix extractor of the trie node column family.
+///
+/// Lets RocksDB build prefix bloom filters, so seeks to a prefix skip SST
+/// files and memtables that hold no key with it. Prefixes are taken from the
+/// logical key, the key namespace is accounted for.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum PrefixExtractor {
+    /// No prefix extractor.
+    #[default]
+    None,
+    /// The first given number of bytes of the key; shorter keys get no prefix.
+    FixedLength(usize),
+    /// The storage trie owner: the storage marker and the 32-byte owner hash.
+    ///
+    /// Speeds up scans over the storage trie of an account. Account trie keys
+    /// get a prefix too when long enough, which is harmless.
+    Owner,
+}
+
+impl PrefixExtractor {
+    /// Length of the extracted prefix of a logical key, `None` if disabled.
+    pub fn prefix_len(&self) -> Option<usize> {
+        match self {
+            Self::None => None,
+            Self::FixedLength(len) => Some(*len),
+            Self::Owner => Some(OWNER_PREFIX_LEN),
+        }
+    }
+}
+
+/// Order in which RocksDB picks files of a level to compact.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactionPriority {
+    /// Largest files first, after compensating for deletions.
+    ByCompensatedSize,
+    /// Files whose newest data is oldest first, for keys updated at random.
+    OldestLargestSeqFirst,
+    /// Files covering the oldest key ranges first, for keys updated in order.
+    OldestSmallestSeqFirst,
+    /// Files overlapping the least data in the next level first, which
+    /// minimizes write amplification.
+    MinOverlappingRatio,
+    /// Files in turn, cycling through the key range.
+    RoundRobin,
+}
+
+/// How RocksDB compacts the SST files of a column family.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompactionStyle {
+    /// Leveled compaction, with the lowest space amplification. Files are
+    /// picked by `PathProviderConfig::compaction_priority`.
+    Level,
+    /// Universal compaction, which merges whole sorted runs of similar size.
+    /// Rewrites data less often than leveled compaction, which suits
+    /// write-heavy workloads such as an initial sync, at the expense of
+    /// temporarily using more space.
+    Universal {
+        /// Percentage by which a sorted run may be larger than the next ones
+        /// and still be merged with them.
+        size_ratio: u32,
+        /// Extra space in percent of the oldest sorted run after which all
+        /// sorted runs are merged.
+        max_size_amplification_percent: u32,
+    },
+    /// FIFO compaction, which deletes the oldest SST files once their total
+    /// size exceeds `max_table_files_size`. It drops data, so it is only
+    /// accepted for column families whose entries may be lost, i.e. the audit
+    /// log and column families created with `PathDB::create_column_family`.
+    Fifo {
+        /// Total size in bytes of the SST files above which the oldest are deleted.
+        max_table_files_size: u64,
+    },
+}
+
+impl CompactionStyle {
+    /// Universal compaction with the default size ratio and space amplification.
+    pub fn universal() -> Self {
+        Self::Universal {
+            size_ratio: DEFAULT_UNIVERSAL_SIZE_RATIO,
+            max_size_amplification_percent: DEFAULT_UNIVERSAL_MAX_SIZE_AMPLIFICATION_PERCENT,
+        }
+    }
+
+    /// FIFO compaction keeping the default total SST file size.
+    pub fn fifo() -> Self {
+        Self::Fifo { max_table_files_size: DEFAULT_FIFO_MAX_TABLE_FILES_SIZE }
+    }
+}
+
+/// Compression algorithm of SST files.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CompressionType {
+    /// No compression.
+    None,
+    /// LZ4, cheap to compress and decompress.
+    Lz4,
+    /// Zstandard, slower but with a better ratio.
+    Zstd,
+}
+
+/// Compression settings of all Column Families.
+///
+/// The default keeps the RocksDB defaults.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct CompressionConfig {
+    /// Compression per LSM level starting at level 0; levels past the end of
+    /// the list use its last entry. Empty keeps the RocksDB default.
+    pub per_level: Vec<CompressionType>,
+    /// Compression of the bottommost level, which holds most of the data.
+    /// `None` uses the per-level setting.
+    pub bottommost: Option<CompressionType>,
+    /// Maximum size in bytes of the zstd dictionary of the bottommost level,
+    /// 0 disables dictionaries. Requires zstd bottommost compression.
+    pub zstd_max_dict_bytes: u32,
+    /// Bytes of samples the bottommost zstd dictionary is trained on, 0 uses
+    /// the sampled data as dictionary without training.
+    pub zstd_max_train_bytes: u32,
+}
+
+impl CompressionConfig {
+    /// No compression on levels 0 and 1, which are rewritten soon, LZ4 on the
+    /// middle levels and zstd with a trained dictionary on the bottommost level.
+    ///
+    /// Trie node blobs share a lot of structure, so dictionary compression of
+    /// the bottommost level saves most of the disk space.
+    pub fn recommended() -> Self {
+        Self {
+            per_level: vec![CompressionType::None, CompressionType::None, CompressionType::Lz4],
+            bottommost: Some(CompressionType::Zstd),
+            zstd_max_dict_bytes: RECOMMENDED_ZSTD_MAX_DICT_BYTES,
+            zstd_max_train_bytes: RECOMMENDED_ZSTD_MAX_TRAIN_BYTES,
+        }
+    }
+}
+
+/// Separation of large trie node values into blob files, see
+/// `PathProviderConfig::blob_files`.
+///
+/// Values of at least `min_blob_size` bytes are written to RocksDB's
+/// integrated blob files and the LSM tree only keeps a reference to them, so
+/// compactions rewrite small references instead of the full nodes. Smaller
+/// nodes, e.g. short branch and leaf nodes, stay inline.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct BlobConfig {
+    /// Smallest value size in bytes written to blob files.
+    pub min_blob_size: u64,
+    /// Target size in bytes of a blob file.
+    pub blob_file_size: u64,
+    /// Compression of blob files. Trie nodes are mostly hashes, which don't
+    /// compress.
+    pub compression: CompressionType,
+    /// Whether compactions relocate the live values of the oldest blob
+    /// files, so files holding mostly stale values are dropped.
+    pub enable_gc: bool,
+    /// Share of the oldest blob files relocated by blob garbage collection,
+    /// between 0 and 1.
+    pub gc_age_cutoff: f64,
+}
+
+impl Default for BlobConfig {
+    fn default() -> Self {
+        Self {
+            min_blob_size: DEFAULT_MIN_BLOB_SIZE,
+            blob_file_size: DEFAULT_BLOB_FILE_SIZE,
+            compression: CompressionType::None,
+            enable_gc: true,
+            gc_age_cutoff: DEFAULT_BLOB_GC_AGE_CUTOFF,
+        }
+    }
+}
+
+/// Overrides of the options shared by all Column Families for a single
+/// Column Family, see `PathProviderConfig::cf_overrides`.
+///
+/// `None` keeps the shared setting.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct CfConfig {
+    /// Write buffer size in bytes.
+    pub write_buffer_size: Option<usize>,
+    /// Maximum write buffer number.
+    pub max_write_buffer_number: Option<i32>,
+    /// Target file size for compaction.
+    pub target_file_size_base: Option<u64>,
+    /// Number of level 0 files that triggers a compaction.
+    pub level0_file_num_compaction_trigger: Option<i32>,
+    /// Bits per key of the bloom filters of SST files.
+    pub bloom_filter_bits_per_key: Option<f64>,
+    /// Size of the PathDB LRU cache in front of the Column Family, in number
+    /// of entries. Only the trie node and storage root Column Families are cached.
+    pub cache_size: Option<u32>,
+    /// Compaction style of the Column Family.
+    pub compaction_style: Option<CompactionStyle>,
+}
+
+/// Durability of a write to the database.
+///
+/// The default writes to the WAL without syncing it, so a write survives a
+/// process crash but may be lost on a power failure.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WriteDurability {
+    /// Skip the WAL, the write is lost on a crash until its memtable is flushed.
+    pub disable_wal: bool,
+    /// Sync the WAL to disk before the write returns. Requires the WAL.
+    pub sync: bool,
+}
+
+impl WriteDurability {
+    /// Synced WAL writes, e.g. for finalized blocks.
+    pub fn synced() -> Self {
+        Self { disable_wal: false, sync: true }
+    }
+
+    /// Check that the settings can be combined.
+    pub fn validate(&self) -> PathProviderResult<()> {
+        if self.disable_wal && self.sync {
+            return Err(PathProviderError::InvalidOperation("Synced writes require the WAL".to_string()));
+        }
+        Ok(())
+    }
+}
+
+/// What a commit exceeding a `CommitLimits` guardrail does.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CommitLimitAction {
+    /// Fail the commit with `PathProviderError::CommitRejected` before
+    /// anything is written.
+    #[default]
+    Reject,
+    /// Log a warning and write the commit anyway.
+    Warn,
+}
+
+/// Sanity limits checked by `PathDB::commit_difflayer` before a difflayer is
+/// written, so a corrupted upstream post-state can't silently poison the
+/// database.
+///
+/// The default disables every check.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CommitLimits {
+    /// Maximum number of trie nodes written or deleted by one commit.
+    pub max_nodes: Option<usize>,
+    /// Maximum size in bytes of a single trie node blob.
+    pub max_node_size: Option<usize>,
+    /// Whether a storage root going from a persisted non-empty root to the
+    /// empty root is checked to come with the deletion of the storage trie
+    /// root node. Costs a storage root read per emptied account.
+    pub check_storage_root_regressions: bool,
+    /// What a commit exceeding a limit does.
+    pub action: CommitLimitAction,
+}
+
+impl CommitLimits {
+    /// Whether any check is enabled.
+    pub fn is_enabled(&self) -> bool {
+        self.max_nodes.is_some() || self.max_node_size.is_some() || self.check_storage_root_regressions
+    }
+}
+
+/// Garbage collection of versioned entries during compaction, see
+/// `PathProviderConfig::version_gc`.
+///
+/// A versioned entry is one whose key ends in the 8-byte big-endian
+/// complement of the block number it was written at, see
+/// `version_gc::versioned_key`.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VersionGcConfig {
+    /// Column Families holding versioned entries: the storage root history
+    /// column family or Column Families created with
+    /// `PathDB::create_column_family`. The other required Column Families
+    /// are keyed by path and can't be listed.
+    pub column_families: Vec<String>,
+    /// Number of most recently committed blocks lookups stay answerable at;
+    /// compactions drop the versions superseded before that.
+    pub retention_blocks: u64,
 }
 
 /// Configuration for PathProvider.
@@ -63,12 +519,68 @@ pub struct PathProviderConfig {
     pub target_file_size_base: u64,
     /// Maximum background jobs.
     pub max_background_jobs: i32,
+    /// Maximum number of threads a single compaction job is split into.
+    pub max_subcompactions: u32,
+    /// Maximum bytes per second written by flushes and compactions (`None`
+    /// disables the rate limiter).
+    ///
+    /// Keeps background compactions from saturating slow disks, e.g.
+    /// spinning ones, at the expense of block commit latency. Foreground
+    /// writes are not limited.
+    pub rate_limit_bytes_per_sec: Option<i64>,
+    /// Whether `rate_limit_bytes_per_sec` is only an upper bound and RocksDB
+    /// lowers the limit while little background work is pending.
+    pub rate_limit_auto_tuned: bool,
+    /// Order in which files are picked for compaction in all column families.
+    pub compaction_priority: CompactionPriority,
+    /// Compaction style of all column families, `CompactionStyle::Fifo`
+    /// is only accepted through `cf_overrides`.
+    ///
+    /// Switching an existing database from leveled to universal compaction
+    /// requires its data to be compacted into a single level first.
+    pub compaction_style: CompactionStyle,
     /// Whether to create the database if it doesn't exist.
     pub create_if_missing: bool,
+    /// Whether RocksDB checks the database aggressively, e.g. verifying the
+    /// files listed in the MANIFEST on open and stopping writes once a
+    /// background error is detected.
+    pub paranoid_checks: bool,
+    /// Whether user reads and compaction inputs bypass the OS page cache.
+    ///
+    /// Avoids caching the trie store twice, in the page cache and the block
+    /// cache, e.g. on NVMe disks with little memory to spare. Size the block
+    /// cache accordingly, it becomes the only read cache. Requires a file
+    /// system supporting `O_DIRECT`.
+    pub use_direct_reads: bool,
+    /// Whether flushes and compactions write without going through the OS
+    /// page cache. Requires a file system supporting `O_DIRECT`.
+    pub use_direct_io_for_flush_and_compaction: bool,
+    /// Readahead size in bytes of compaction inputs. Compactions read large
+    /// sequential ranges, which direct reads no longer prefetch through the
+    /// page cache.
+    pub compaction_readahead_size: usize,
     /// LRU cache size in number of entries (default: 1M entries).
     pub trie_node_cache_size: u32,
     /// LRU cache size in number of entries (default: 1M entries).
     pub storage_root_cache_size: u32,
+    /// Number of independently locked shards each LRU cache is split into.
+    /// The cache sizes are divided evenly over the shards, which evict on
+    /// their own.
+    pub cache_shards: usize,
+    /// Size in bytes of the largest trie node admitted to the LRU cache
+    /// (`None` admits all nodes).
+    ///
+    /// Larger nodes, e.g. huge branch nodes, are read from RocksDB every time
+    /// instead of evicting many small hot entries.
+    pub cache_max_value_size: Option<usize>,
+    /// Number of missing trie node keys remembered, so repeated reads of
+    /// absent paths skip RocksDB (`None` disables the negative cache).
+    ///
+    /// Writes through PathDB forget the written keys; writes that bypass
+    /// it stay hidden for at most `negative_cache_ttl` unless invalidated.
+    pub negative_cache_size: Option<u32>,
+    /// How long a missing trie node key is remembered.
+    pub negative_cache_ttl: Duration,
     /// Whether to fill cache on reads.
     pub fill_cache: bool,
     /// Readahead size in bytes for sequential reads.
@@ -76,7 +588,130 @@ pub struct PathProviderConfig {
     /// Whether to enable async IO for reads.
     pub async_io: bool,
     /// Whether to verify checksums on reads.
+    ///
+    /// Applies to every point read except verified reads, see
+    /// `PathDB::get_verified_raw_trie_node`, which always verify checksums.
     pub verify_checksums: bool,
+    /// Readahead size in bytes of verified reads.
+    pub verified_readahead_size: usize,
+    /// Readahead size in bytes for linear walks over a key range.
+    pub scan_readahead_size: usize,
+    /// Whether linear walks fill the block cache.
+    pub scan_fill_cache: bool,
+    /// Optional namespace prepended to every key written to or read from the database.
+    ///
+    /// Lets several independent tries share one RocksDB instance. Instances with
+    /// different namespaces never observe each other's keys; mixing namespaced
+    /// and un-namespaced instances on the same database is not supported.
+    pub key_namespace: Option<Vec<u8>>,
+    /// Trie node values larger than this many bytes are moved to the overflow
+    /// column family in chunks (`None` disables overflow storage).
+    pub overflow_threshold: Option<usize>,
+    /// Chunk size in bytes for values stored in the overflow column family.
+    pub overflow_chunk_size: usize,
+    /// Queue stale trie nodes deleted by `commit_difflayer` in the deletion
+    /// queue column family instead of deleting them inline.
+    ///
+    /// Queued nodes are removed by `PathDB::process_deletion_queue`, usually
+    /// driven by a `DeletionWorker`. Until then the stale blobs stay on disk,
+    /// point reads missing the trie node cache check the queue and report them
+    /// as missing, while key range walks still yield them.
+    pub deferred_deletion: bool,
+    /// Maximum number of queued deletions processed per worker tick.
+    pub deletion_batch_size: usize,
+    /// Interval between deletion worker ticks.
+    pub deletion_interval: Duration,
+    /// Interval between the memtable flushes of a `FlushWorker`.
+    pub flush_interval: Duration,
+    /// Interval between the disk usage gauge refreshes of a `DiskUsageWorker`.
+    pub disk_usage_interval: Duration,
+    /// Whether to collect RocksDB statistics, required for write amplification estimates.
+    pub enable_statistics: bool,
+    /// Number of most recent commits the write amplification estimate covers.
+    pub amplification_window: usize,
+    /// Prefix extractor of the trie node column family, see `PathDB::seek_prefix`.
+    pub prefix_extractor: PrefixExtractor,
+    /// Compression settings of all column families.
+    pub compression: CompressionConfig,
+    /// Size in bytes of the LRU block cache shared by all column families
+    /// (`None` keeps a RocksDB default cache per column family).
+    pub block_cache_size: Option<usize>,
+    /// Per Column Family overrides, keyed by Column Family name, e.g. to give
+    /// the storage root Column Family smaller write buffers or another bloom
+    /// filter than the trie node Column Family.
+    pub cf_overrides: HashMap<String, CfConfig>,
+    /// Bits per key of the bloom filters of SST files (`None` disables them).
+    ///
+    /// Most trie node reads are point lookups of paths absent from most SST
+    /// files, so a bloom filter saves a block read per file checked. Also
+    /// used for the prefix bloom filters of the prefix extractor.
+    pub bloom_filter_bits_per_key: Option<f64>,
+    /// Whether index and filter blocks are kept in the block cache, bounding
+    /// their memory by the cache size, instead of being held outside of it.
+    pub cache_index_and_filter_blocks: bool,
+    /// Whether index and filter blocks of level 0 files stay pinned in the
+    /// block cache. Only applies with `cache_index_and_filter_blocks`.
+    pub pin_l0_filter_and_index_blocks_in_cache: bool,
+    /// Whether writes skip the WAL, see `WriteDurability`.
+    pub disable_wal: bool,
+    /// Whether writes sync the WAL to disk before returning, see `WriteDurability`.
+    ///
+    /// `PathDB::commit_difflayer_with_durability` overrides this per commit.
+    pub sync_writes: bool,
+    /// Sample one in this many trie node reads to estimate read counts per
+    /// path, see `PathDB::hot_set` (0 disables sampling).
+    pub access_sample_rate: u32,
+    /// Maximum number of paths whose read counts are tracked; the least
+    /// recently sampled paths are dropped first.
+    pub access_tracker_size: u32,
+    /// Whether trie node point reads that miss the primary column family fall
+    /// back to the cold trie node column family, see `PathDB::move_to_cold_storage`.
+    ///
+    /// Costs an extra lookup per missing node, and trie node writes take the
+    /// deletion lock to serialize with moves.
+    pub cold_storage: bool,
+    /// Compression of all levels of the cold trie node column family.
+    pub cold_compression: CompressionType,
+    /// Separation of large values of the trie node column families into
+    /// blob files (`None` keeps every value in the LSM tree).
+    ///
+    /// Values over `overflow_threshold` are chunked into the overflow column
+    /// family first, so only values between the two sizes reach blob files.
+    pub blob_files: Option<BlobConfig>,
+    /// Maximum number of most recently used trie node keys persisted by
+    /// `PathDB::close_gracefully` for `PathDB::warm_cache` (0 disables).
+    pub hot_keys_limit: usize,
+    /// Sanity limits checked before a difflayer commit is written.
+    pub commit_limits: CommitLimits,
+    /// Whether maintenance writes, e.g. deletion queue processing and cold
+    /// storage moves, are low priority writes that RocksDB slows down first
+    /// when compactions fall behind, instead of block commits. Disabled by
+    /// default, as a stalled low priority write lets the backlog grow.
+    pub maintenance_low_pri: bool,
+    /// Maximum average bytes per second of maintenance writes (`None`
+    /// disables the limit). Maintenance writes wait for their budget before
+    /// taking any lock, so the wait never delays block commits.
+    pub maintenance_rate_limit_bytes_per_sec: Option<u64>,
+    /// Value of the `instance` label of the PathDB metrics (`None` uses the
+    /// database path), so several databases in one process report apart.
+    pub metrics_instance: Option<String>,
+    /// Compaction-time garbage collection of versioned entries (`None`
+    /// disables it).
+    pub version_gc: Option<VersionGcConfig>,
+    /// Whether persisted storage roots are also kept per block in the
+    /// storage root history column family, see `PathDB::get_storage_root_at`.
+    ///
+    /// Meant for archive nodes; the history grows with every changed storage
+    /// root until pruned with `PathDB::prune_storage_root_history`, or by
+    /// compactions when `version_gc` lists `storage_root_history`.
+    pub storage_root_history: bool,
+}
+
+impl PathProviderConfig {
+    /// Durability of writes issued with this configuration.
+    pub fn write_durability(&self) -> WriteDurability {
+        WriteDurability { disable_wal: self.disable_wal, sync: self.sync_writes }
+    }
 }
 
 impl Default for PathProviderConfig {
@@ -87,13 +722,60 @@ impl Default for PathProviderConfig {
             max_write_buffer_number: DEFAULT_MAX_WRITE_BUFFER_NUMBER,
             target_file_size_base: DEFAULT_TARGET_FILE_SIZE_BASE,
             max_background_jobs: DEFAULT_MAX_BACKGROUND_JOBS,
+            max_subcompactions: DEFAULT_MAX_SUBCOMPACTIONS,
+            rate_limit_bytes_per_sec: DEFAULT_RATE_LIMIT_BYTES_PER_SEC,
+            rate_limit_auto_tuned: DEFAULT_RATE_LIMIT_AUTO_TUNED,
+            compaction_priority: DEFAULT_COMPACTION_PRIORITY,
+            compaction_style: DEFAULT_COMPACTION_STYLE,
             create_if_missing: DEFAULT_CREATE_IF_MISSING,
+            paranoid_checks: DEFAULT_PARANOID_CHECKS,
+            use_direct_reads: DEFAULT_USE_DIRECT_READS,
+            use_direct_io_for_flush_and_compaction: DEFAULT_USE_DIRECT_IO_FOR_FLUSH_AND_COMPACTION,
+            compaction_readahead_size: DEFAULT_COMPACTION_READAHEAD_SIZE,
             trie_node_cache_size: DEFAULT_TRIE_NODECACHE_SIZE,
             storage_root_cache_size: DEFAULT_STORAGE_ROOT_CACHE_SIZE,
+            cache_shards: DEFAULT_CACHE_SHARDS,
+            cache_max_value_size: DEFAULT_CACHE_MAX_VALUE_SIZE,
+            negative_cache_size: DEFAULT_NEGATIVE_CACHE_SIZE,
+            negative_cache_ttl: DEFAULT_NEGATIVE_CACHE_TTL,
             fill_cache: DEFAULT_FILL_CACHE,
             readahead_size: DEFAULT_READAHEAD_SIZE,
             async_io: DEFAULT_ASYNC_IO,
             verify_checksums: DEFAULT_VERIFY_CHECKSUMS,
+            verified_readahead_size: DEFAULT_VERIFIED_READAHEAD_SIZE,
+            scan_readahead_size: DEFAULT_SCAN_READAHEAD_SIZE,
+            scan_fill_cache: DEFAULT_SCAN_FILL_CACHE,
+            key_namespace: None,
+            overflow_threshold: DEFAULT_OVERFLOW_THRESHOLD,
+            overflow_chunk_size: DEFAULT_OVERFLOW_CHUNK_SIZE,
+            deferred_deletion: DEFAULT_DEFERRED_DELETION,
+            deletion_batch_size: DEFAULT_DELETION_BATCH_SIZE,
+            deletion_interval: DEFAULT_DELETION_INTERVAL,
+            flush_interval: DEFAULT_FLUSH_INTERVAL,
+            disk_usage_interval: DEFAULT_DISK_USAGE_INTERVAL,
+            enable_statistics: DEFAULT_ENABLE_STATISTICS,
+            amplification_window: DEFAULT_AMPLIFICATION_WINDOW,
+            prefix_extractor: DEFAULT_PREFIX_EXTRACTOR,
+            compression: CompressionConfig::default(),
+            block_cache_size: DEFAULT_BLOCK_CACHE_SIZE,
+            cf_overrides: HashMap::new(),
+            bloom_filter_bits_per_key: DEFAULT_BLOOM_FILTER_BITS_PER_KEY,
+            cache_index_and_filter_blocks: DEFAULT_CACHE_INDEX_AND_FILTER_BLOCKS,
+            pin_l0_filter_and_index_blocks_in_cache: DEFAULT_PIN_L0_FILTER_AND_INDEX_BLOCKS_IN_CACHE,
+            disable_wal: DEFAULT_DISABLE_WAL,
+            sync_writes: DEFAULT_SYNC_WRITES,
+            access_sample_rate: DEFAULT_ACCESS_SAMPLE_RATE,
+            access_tracker_size: DEFAULT_ACCESS_TRACKER_SIZE,
+            cold_storage: DEFAULT_COLD_STORAGE,
+            cold_compression: DEFAULT_COLD_COMPRESSION,
+            blob_files: DEFAULT_BLOB_FILES,
+            hot_keys_limit: DEFAULT_HOT_KEYS_LIMIT,
+            commit_limits: CommitLimits::default(),
+            maintenance_low_pri: DEFAULT_MAINTENANCE_LOW_PRI,
+            maintenance_rate_limit_bytes_per_sec: DEFAULT_MAINTENANCE_RATE_LIMIT_BYTES_PER_SEC,
+            metrics_instance: None,
+            version_gc: None,
+            storage_root_history: DEFAULT_STORAGE_ROOT_HISTORY,
         }
     }
 }
@@ -0,0 +1,135 @@
+//! Garbage collection of versioned entries during compaction.
+//!
+//! Versioned entries keep one value per block for the same logical key, e.g.
+//! the storage root history. Their keys end in the 8-byte big-endian
+//! complement of the block number they were written at, so the versions of a
+//! key sort newest first. Compactions drop a version once a newer version at
+//! or below the retention horizon supersedes it, without explicit delete
+//! batches. The newest version at or below the horizon is always kept, as it
+//! still answers lookups at later blocks.
+
+use std::ffi::CStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+
+use rocksdb::compaction_filter::{CompactionFilter, Decision};
+use rocksdb::compaction_filter_factory::{CompactionFilterContext, CompactionFilterFactory};
+use rocksdb::Options;
+
+use crate::pathdb::PathDB;
+use crate::traits::*;
+
+/// Name of the version GC compaction filter.
+const VERSION_GC_FILTER_NAME: &CStr = match CStr::from_bytes_with_nul(b"pathdb_version_gc\0") {
+    Ok(name) => name,
+    Err(_) => panic!("invalid compaction filter name"),
+};
+
+/// Length in bytes of the block number suffix of a versioned key.
+pub const VERSION_SUFFIX_LEN: usize = 8;
+
+/// Key of the version of `key` written at `block_number`.
+///
+/// The suffix is the complement of the block number, so newer versions sort
+/// before older ones.
+pub fn versioned_key(key: &[u8], block_number: u64) -> Vec<u8> {
+    let mut versioned = Vec::with_capacity(key.len() + VERSION_SUFFIX_LEN);
+    versioned.extend_from_slice(key);
+    versioned.extend_from_slice(&(!block_number).to_be_bytes());
+    versioned
+}
+
+/// Block number suffix of a versioned key, `None` if the key is too short.
+pub fn key_version(key: &[u8]) -> Option<u64> {
+    let suffix = key.len().checked_sub(VERSION_SUFFIX_LEN).map(|start| &key[start..])?;
+    Some(!u64::from_be_bytes(suffix.try_into().ok()?))
+}
+
+/// Install the version GC compaction filter on `cf_opts` if `cf_name` is a
+/// versioned Column Family.
+///
+/// The horizon is read from `latest_block` when a compaction starts, so
+/// commits move it forward without reopening the database.
+pub(crate) fn set_version_gc_filter(cf_opts: &mut Options, cf_name: &str, config: &PathProviderConfig, latest_block: &Arc<AtomicU64>) {
+    let Some(version_gc) = &config.version_gc else {
+        return;
+    };
+    if !version_gc.column_families.iter().any(|name| name == cf_name) {
+        return;
+    }
+
+    cf_opts.set_compaction_filter_factory(VersionGcFilterFactory {
+        retention_blocks: version_gc.retention_blocks,
+        latest_block: latest_block.clone(),
+    });
+}
+
+/// Creates a [`VersionGcFilter`] per compaction.
+struct VersionGcFilterFactory {
+    retention_blocks: u64,
+    latest_block: Arc<AtomicU64>,
+}
+
+impl CompactionFilterFactory for VersionGcFilterFactory {
+    type Filter = VersionGcFilter;
+
+    fn create(&mut self, _context: CompactionFilterContext) -> Self::Filter {
+        let horizon = self.latest_block.load(Ordering::Relaxed).saturating_sub(self.retention_blocks);
+        VersionGcFilter { horizon, covered: None }
+    }
+
+    fn name(&self) -> &CStr {
+        VERSION_GC_FILTER_NAME
+    }
+}
+
+/// Drops the versions superseded below the horizon within one compaction.
+///
+/// A compaction visits keys in order, so the versions of a key come newest
+/// first. Versions older than one this compaction already kept at or below
+/// the horizon are dropped. Versions whose superseding version lives in
+/// another file are kept until a compaction sees both.
+struct VersionGcFilter {
+    horizon: u64,
+    /// Logical key whose newest version at or below the horizon was kept.
+    covered: Option<Vec<u8>>,
+}
+
+impl CompactionFilter for VersionGcFilter {
+    fn filter(&mut self, _level: u32, key: &[u8], _value: &[u8]) -> Decision {
+        let Some(version) = key_version(key) else {
+            return Decision::Keep;
+        };
+        let logical_key = &key[..key.len() - VERSION_SUFFIX_LEN];
+        if self.covered.as_deref() == Some(logical_key) {
+            return Decision::Remove;
+        }
+        if version <= self.horizon {
+            self.covered = Some(logical_key.to_vec());
+        }
+        Decision::Keep
+    }
+
+    fn name(&self) -> &CStr {
+        VERSION_GC_FILTER_NAME
+    }
+}
+
+/// Versioned entry garbage collection
+impl PathDB {
+    /// The oldest block lookups of versioned entries stay answerable at,
+    /// `None` without version GC. Compactions only drop versions superseded
+    /// by a newer version at or below it.
+    ///
+    /// The horizon follows the blocks committed by this process and starts at
+    /// 0 after opening, so nothing is dropped until the first commit.
+    pub fn version_horizon(&self) -> Option<u64> {
+        let version_gc = self.config().version_gc.as_ref()?;
+        Some(self.version_gc_block().load(Ordering::Relaxed).saturating_sub(version_gc.retention_blocks))
+    }
+
+    /// Move the version GC horizon forward after committing `block_number`.
+    pub(crate) fn advance_version_gc(&self, block_number: u64) {
+        self.version_gc_block().fetch_max(block_number, Ordering::Relaxed);
+    }
+}
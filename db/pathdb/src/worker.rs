@@ -0,0 +1,78 @@
+//! Threads of the PathDB background workers.
+
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use crate::pathdb::PathDB;
+
+/// Stop flag and thread of a background worker.
+///
+/// Shared between the handle of the worker and the database it works on, so
+/// the thread is stopped and joined by whichever comes first: dropping the
+/// handle or [`PathDB::close_gracefully`](crate::PathDB::close_gracefully).
+#[derive(Debug, Clone)]
+pub(crate) struct WorkerThread {
+    stop: Arc<AtomicBool>,
+    handle: Arc<Mutex<Option<JoinHandle<()>>>>,
+}
+
+impl WorkerThread {
+    /// Spawn thread `name` running `work`, which must return soon after the
+    /// flag it's given is set; the thread is unparked when it is.
+    pub(crate) fn spawn(name: &str, work: impl FnOnce(Arc<AtomicBool>) + Send + 'static) -> std::io::Result<Self> {
+        let stop = Arc::new(AtomicBool::new(false));
+        let worker_stop = stop.clone();
+        let handle = thread::Builder::new()
+            .name(name.to_string())
+            .spawn(move || work(worker_stop))?;
+
+        Ok(Self { stop, handle: Arc::new(Mutex::new(Some(handle))) })
+    }
+
+    /// Spawn thread `name` running `tick` on `db` every `interval` until the
+    /// worker is stopped or `db` is closed, and register it with `db` for
+    /// [`PathDB::close_gracefully`](crate::PathDB::close_gracefully) to stop.
+    ///
+    /// The first tick runs right away, or after one interval with `delay_first`.
+    pub(crate) fn spawn_periodic(
+        name: &str,
+        db: PathDB,
+        interval: Duration,
+        delay_first: bool,
+        mut tick: impl FnMut(&PathDB) + Send + 'static,
+    ) -> std::io::Result<Self> {
+        let registry = db.clone();
+        let worker = Self::spawn(name, move |worker_stop| {
+            if delay_first {
+                thread::park_timeout(interval);
+            }
+            while !worker_stop.load(Ordering::Acquire) && !db.is_closed() {
+                tick(&db);
+                thread::park_timeout(interval);
+            }
+        })?;
+        registry.register_worker(worker.clone());
+        Ok(worker)
+    }
+
+    /// Whether the worker was stopped.
+    pub(crate) fn is_stopped(&self) -> bool {
+        self.stop.load(Ordering::Acquire)
+    }
+
+    /// Stop the worker and wait for its in-flight work to finish. Stopping
+    /// again is a no-op.
+    pub(crate) fn stop(&self) {
+        self.stop.store(true, Ordering::Release);
+        let handle = self.handle.lock().unwrap().take();
+        if let Some(handle) = handle {
+            handle.thread().unpark();
+            // A worker stopping itself, e.g. by closing the database, can't join itself
+            if handle.thread().id() != thread::current().id() {
+                let _ = handle.join();
+            }
+        }
+    }
+}
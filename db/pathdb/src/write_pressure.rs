@@ -0,0 +1,66 @@
+//! Write stall and compaction pressure of a PathDB.
+
+use crate::pathdb::PathDB;
+use crate::traits::*;
+
+/// Backlog of flushes and compactions, see [`PathDB::write_pressure`].
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WritePressure {
+    /// Memtables over all column families, active ones included
+    pub memtables: u64,
+    /// Immutable memtables waiting to be flushed over all column families
+    pub immutable_memtables: u64,
+    /// Most immutable memtables of a single column family, which RocksDB
+    /// compares against `max_write_buffer_number` to stall writes
+    pub max_cf_immutable_memtables: u64,
+    /// Estimated bytes compactions have to rewrite to bring every level
+    /// under its target size, over all column families
+    pub pending_compaction_bytes: u64,
+    /// Number of compactions running
+    pub running_compactions: u64,
+    /// Rate in bytes per second writes are delayed to, 0 when writes run at
+    /// full speed
+    pub delayed_write_rate: u64,
+    /// Whether writes are stopped until flushes or compactions catch up
+    pub writes_stopped: bool,
+}
+
+impl WritePressure {
+    /// Whether RocksDB currently delays or stops writes, in which case the
+    /// next `commit_difflayer` blocks until the backlog shrinks.
+    pub fn is_stalled(&self) -> bool {
+        self.writes_stopped || self.delayed_write_rate > 0
+    }
+}
+
+/// Write pressure
+impl PathDB {
+    /// Get the flush and compaction backlog and the write stall state, so
+    /// block import can be throttled before `commit_difflayer` blocks inside
+    /// RocksDB.
+    ///
+    /// Values are read from RocksDB properties and cover the whole database,
+    /// including other namespaces sharing it.
+    pub fn write_pressure(&self) -> PathProviderResult<WritePressure> {
+        let mut pressure = WritePressure {
+            running_compactions: self.db_int_property("rocksdb.num-running-compactions")?,
+            delayed_write_rate: self.db_int_property("rocksdb.actual-delayed-write-rate")?,
+            writes_stopped: self.db_int_property("rocksdb.is-write-stopped")? != 0,
+            ..Default::default()
+        };
+        for cf_name in self.column_families() {
+            let immutable_memtables = self.cf_int_property(&cf_name, "rocksdb.num-immutable-mem-table")?;
+            pressure.memtables += immutable_memtables + 1;
+            pressure.immutable_memtables += immutable_memtables;
+            pressure.max_cf_immutable_memtables = pressure.max_cf_immutable_memtables.max(immutable_memtables);
+            pressure.pending_compaction_bytes += self.cf_int_property(&cf_name, "rocksdb.estimate-pending-compaction-bytes")?;
+        }
+        Ok(pressure)
+    }
+
+    fn db_int_property(&self, property: &str) -> PathProviderResult<u64> {
+        self.raw_db().property_int_value(property)
+            .map(Option::unwrap_or_default)
+            .map_err(|e| PathProviderError::rocksdb(format!("RocksDB property '{}' error", property), e))
+    }
+}
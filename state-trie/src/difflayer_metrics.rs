@@ -0,0 +1,101 @@
+//! Metrics for trie node lookups in difflayers.
+
+use std::sync::{Arc, OnceLock};
+
+use reth_metrics::{
+    metrics::{Counter, Histogram},
+    Metrics,
+};
+use rust_eth_triedb_common::{CounterTotal, DiffLayerLookup, DiffLayers, HistogramSummary, HistogramTotal, TrieNode};
+
+/// Metrics for the trie node lookups in difflayers.
+#[derive(Metrics, Clone)]
+#[metrics(scope = "rust.eth.triedb.difflayer")]
+pub(crate) struct DiffLayerMetrics {
+    /// Counter of lookups served by the newest difflayer
+    pub(crate) newest_layer_hit_counter: Counter,
+    /// Counter of lookups served by an older difflayer
+    pub(crate) older_layer_hit_counter: Counter,
+    /// Counter of lookups not found in any difflayer
+    pub(crate) miss_counter: Counter,
+    /// Histogram of difflayers probed per lookup
+    pub(crate) layers_probed_histogram: Histogram,
+}
+
+impl DiffLayerMetrics {
+    pub(crate) fn record_lookup(&self, lookup: DiffLayerLookup, layers_probed: usize) {
+        let totals = &DIFFLAYER_METRIC_TOTALS;
+        match lookup {
+            DiffLayerLookup::NewestLayer => {
+                self.newest_layer_hit_counter.increment(1);
+                totals.newest_layer_hit_counter.increment(1);
+            }
+            DiffLayerLookup::OlderLayer => {
+                self.older_layer_hit_counter.increment(1);
+                totals.older_layer_hit_counter.increment(1);
+            }
+            DiffLayerLookup::Miss => {
+                self.miss_counter.increment(1);
+                totals.miss_counter.increment(1);
+            }
+        }
+        self.layers_probed_histogram.record(layers_probed as f64);
+        totals.layers_probed_histogram.record(layers_probed as f64);
+    }
+}
+
+fn difflayer_metrics() -> &'static DiffLayerMetrics {
+    static METRICS: OnceLock<DiffLayerMetrics> = OnceLock::new();
+    METRICS.get_or_init(DiffLayerMetrics::default)
+}
+
+/// Looks up the trie node at `key` in `difflayers` and records the outcome.
+///
+/// Lookups without any difflayer are not recorded.
+pub(crate) fn lookup_trie_node(difflayers: &DiffLayers, key: &[u8]) -> Option<Arc<TrieNode>> {
+    if difflayers.is_empty() {
+        return None;
+    }
+    let (node, lookup, layers_probed) = difflayers.lookup_trie_node(key);
+    difflayer_metrics().record_lookup(lookup, layers_probed);
+    node
+}
+
+/// Process-wide totals of the difflayer lookup metrics.
+struct DiffLayerMetricTotals {
+    newest_layer_hit_counter: CounterTotal,
+    older_layer_hit_counter: CounterTotal,
+    miss_counter: CounterTotal,
+    layers_probed_histogram: HistogramTotal,
+}
+
+static DIFFLAYER_METRIC_TOTALS: DiffLayerMetricTotals = DiffLayerMetricTotals {
+    newest_layer_hit_counter: CounterTotal::new(),
+    older_layer_hit_counter: CounterTotal::new(),
+    miss_counter: CounterTotal::new(),
+    layers_probed_histogram: HistogramTotal::new(),
+};
+
+/// Current values of the difflayer lookup metrics, see [`snapshot`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct DiffLayerMetricsSnapshot {
+    /// Lookups served by the newest difflayer
+    pub newest_layer_hit_counter: u64,
+    /// Lookups served by an older difflayer
+    pub older_layer_hit_counter: u64,
+    /// Lookups not found in any difflayer
+    pub miss_counter: u64,
+    /// Difflayers probed per lookup
+    pub layers_probed_histogram: HistogramSummary,
+}
+
+/// Take a snapshot of the difflayer lookup metrics, summed over the process.
+pub fn snapshot() -> DiffLayerMetricsSnapshot {
+    let totals = &DIFFLAYER_METRIC_TOTALS;
+    DiffLayerMetricsSnapshot {
+        newest_layer_hit_counter: totals.newest_layer_hit_counter.get(),
+        older_layer_hit_counter: totals.older_layer_hit_counter.get(),
+        miss_counter: totals.miss_counter.get(),
+        layers_probed_histogram: totals.layers_probed_histogram.get(),
+    }
+}
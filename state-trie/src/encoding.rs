@@ -158,9 +158,8 @@ fn decode_nibbles(nibbles: &[u8], buf: &mut [u8]) {
     }
 }
 
-// Trie node storage prefixes
-pub const TRIE_NODE_STORAGE_PREFIX: &[u8] = b"O";
-pub const TRIE_NODE_ACCOUNT_PREFIX: &[u8] = b"A";
+// Trie node storage prefixes, shared with the databases
+pub use rust_eth_triedb_common::{TRIE_NODE_ACCOUNT_PREFIX, TRIE_NODE_STORAGE_PREFIX};
 
 /// Generate storage trie node key: TrieNodeStoragePrefix + accountHash + path
 /// Equivalent to BSC's storageTrieNodeKey function
@@ -188,3 +187,19 @@ pub fn account_trie_node_key(path: &[u8]) -> Vec<u8> {
 }
 
 
+
+/// Generate the key range `[start, end)` holding all storage trie nodes of
+/// an account: every key starting with TrieNodeStoragePrefix + accountHash
+pub fn storage_trie_node_range(account_hash: &[u8]) -> (Vec<u8>, Vec<u8>) {
+    let start = storage_trie_node_key(account_hash, &[]);
+
+    // Smallest key greater than every key with the prefix, the storage
+    // prefix itself is never 0xff so it always exists
+    let mut end = start.clone();
+    while end.last() == Some(&0xff) {
+        end.pop();
+    }
+    *end.last_mut().unwrap() += 1;
+
+    (start, end)
+}
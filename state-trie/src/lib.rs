@@ -25,8 +25,20 @@ pub mod state_trie;
 pub mod trie_hasher;
 /// Trie change tracer (Geth-compatible semantics)
 pub mod trie_tracer;
+/// Recording of resolved trie nodes
+pub mod trie_recorder;
 /// Trie committer (collects dirty nodes during commit)
 pub mod trie_committer;
+/// Trie node format validation
+pub mod node_validator;
+/// Ordered trie leaf iteration
+pub mod trie_iterator;
+/// Ordered trie node iteration
+pub mod node_iterator;
+/// Trie built from sorted keys
+pub mod stack_trie;
+/// Difflayer lookup metrics
+pub mod difflayer_metrics;
 
 #[cfg(test)]
 mod trie_test;
@@ -37,4 +49,10 @@ pub use traits::SecureTrieTrait;
 pub use node::NodeSet;
 // Re-export TrieNode, DiffLayer, DiffLayers from common crate
 pub use secure_trie::{SecureTrieId, SecureTrieBuilder, SecureTrieError};
+pub use trie_iterator::TrieIterator;
+pub use trie_recorder::NodeReadRecorder;
+pub use node_iterator::{LeafIterator, NodeIterator};
+pub use stack_trie::StackTrie;
+pub use difflayer_metrics::DiffLayerMetricsSnapshot;
+pub use node_validator::{validate_blob, validate_node_entry, validating_bulk_loader, validate_trie_nodes, validate_trie_nodes_with_cancellation, BlobValidationError, ValidationReport};
 pub use rust_eth_triedb_common::{TrieNode, DiffLayer, DiffLayers};
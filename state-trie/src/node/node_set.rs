@@ -4,12 +4,15 @@
 //! have been modified during trie operations, enabling efficient batch commits.
 
 use std::sync::Arc;
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 
 use alloy_primitives::B256;
-use rust_eth_triedb_common::{Leaf, TrieNode};
+use rust_eth_triedb_common::{DiffLayer, Leaf, TrieNode};
 use crate::encoding;
 
+/// Version of the node set signature scheme returned by `signature()`.
+pub const NODE_SET_SIGNATURE_VERSION: u8 = 2;
+
 /// NodeSet contains a set of nodes collected during the commit operation.
 /// Each node is keyed by path. It's not thread-safe to use.
 #[derive(Clone)]
@@ -104,7 +107,33 @@ impl NodeSet {
     }
 
     /// Calculates a deterministic hash of the entire `NodeSet` contents.
+    ///
+    /// This is the signature of the current scheme version
+    /// (`NODE_SET_SIGNATURE_VERSION`), currently [`signature_v1`](Self::signature_v1):
+    /// version 2 only changed the signature of `MergedNodeSet`.
     pub fn signature(&self) -> B256 {
+        self.signature_v1()
+    }
+
+    /// Calculates the version 1 signature of the `NodeSet` contents.
+    ///
+    /// The signature is stable across releases and matches the BSC/geth
+    /// implementation, so it can be used to compare commits across clients.
+    ///
+    /// # Byte Layout
+    ///
+    /// The signature is `keccak256` over the concatenation of:
+    ///
+    /// 1. `owner` - 32 bytes.
+    /// 2. For every leaf, sorted by `(parent, blob)`: `parent` (32 bytes) followed
+    ///    by the raw `blob` bytes.
+    /// 3. For every node, sorted by path: the raw path bytes (one nibble per
+    ///    byte, no length prefix), then `0x01 || hash` (33 bytes) if the node has
+    ///    a hash, then `0x01 || blob` if the node has a blob. Absent fields
+    ///    contribute no bytes.
+    /// 4. `updates` as a big-endian u64.
+    /// 5. `deletes` as a big-endian u64.
+    pub fn signature_v1(&self) -> B256 {
         use alloy_primitives::{keccak256};
 
         let mut buf: Vec<u8> = Vec::new();
@@ -211,13 +240,16 @@ impl std::fmt::Debug for NodeSet {
 #[allow(dead_code)]
 pub struct MergedNodeSet {
     pub sets: HashMap<B256, Arc<NodeSet>>,
+    /// Owners whose storage trie is dropped as a whole before `sets` apply,
+    /// i.e. self-destructed accounts
+    pub wiped_owners: HashSet<B256>,
 }
 
 impl MergedNodeSet {
     /// Create a new merged node set
     #[allow(dead_code)]
     pub fn new() -> Self {
-        Self { sets: HashMap::new() }
+        Self { sets: HashMap::new(), wiped_owners: HashSet::new() }
     }
 
     /// Merge a node set into the merged set
@@ -230,6 +262,70 @@ impl MergedNodeSet {
         Ok(())
     }
 
+    /// Calculates the aggregate signature of all node sets.
+    ///
+    /// This is the signature of the current scheme version
+    /// (`NODE_SET_SIGNATURE_VERSION`), currently [`signature_v2`](Self::signature_v2).
+    pub fn signature(&self) -> B256 {
+        self.signature_v2()
+    }
+
+    /// Calculates the version 1 aggregate signature of all node sets.
+    ///
+    /// Doesn't cover `wiped_owners`, two commits differing only in the
+    /// storage tries they wipe sign the same. Use
+    /// [`signature_v2`](Self::signature_v2) for new records.
+    ///
+    /// # Byte Layout
+    ///
+    /// The signature is `keccak256` over the concatenation, for every node set
+    /// sorted by owner, of `owner` (32 bytes) followed by the set's
+    /// [`NodeSet::signature_v1`] (32 bytes). An empty merged set hashes the
+    /// empty byte string.
+    pub fn signature_v1(&self) -> B256 {
+        use alloy_primitives::{keccak256};
+
+        let mut sets_sorted: Vec<(&B256, &Arc<NodeSet>)> = self.sets.iter().collect();
+        sets_sorted.sort_by_key(|(owner, _)| **owner);
+
+        let mut buf: Vec<u8> = Vec::with_capacity(sets_sorted.len() * 64);
+        for (owner, set) in sets_sorted {
+            buf.extend_from_slice(owner.as_slice());
+            buf.extend_from_slice(set.signature_v1().as_slice());
+        }
+
+        keccak256(&buf)
+    }
+
+    /// Calculates the version 2 aggregate signature of all node sets and
+    /// wiped owners.
+    ///
+    /// # Byte Layout
+    ///
+    /// Without wiped owners, this is [`signature_v1`](Self::signature_v1),
+    /// so records of commits that wipe nothing keep their signature.
+    /// Otherwise the signature is `keccak256` over the version 1 signature
+    /// (32 bytes) followed by every wiped owner, sorted (32 bytes each).
+    pub fn signature_v2(&self) -> B256 {
+        use alloy_primitives::{keccak256};
+
+        let signature = self.signature_v1();
+        if self.wiped_owners.is_empty() {
+            return signature;
+        }
+
+        let mut owners: Vec<&B256> = self.wiped_owners.iter().collect();
+        owners.sort();
+
+        let mut buf: Vec<u8> = Vec::with_capacity((owners.len() + 1) * 32);
+        buf.extend_from_slice(signature.as_slice());
+        for owner in owners {
+            buf.extend_from_slice(owner.as_slice());
+        }
+
+        keccak256(&buf)
+    }
+
     /// Convert the merged node set to a difflayer
     pub fn to_diff_nodes(&self) -> Arc<HashMap<Vec<u8>, Arc<TrieNode>>> {
         let mut difflayer = HashMap::new();
@@ -246,6 +342,19 @@ impl MergedNodeSet {
         }
         Arc::new(difflayer)
     }
+
+    /// Convert the wiped owners to the trie node key ranges deleted by a difflayer
+    pub fn to_deleted_ranges(&self) -> Vec<(Vec<u8>, Vec<u8>)> {
+        let mut owners: Vec<&B256> = self.wiped_owners.iter().collect();
+        owners.sort();
+        owners.into_iter().map(|owner| encoding::storage_trie_node_range(owner.as_slice())).collect()
+    }
+
+    /// Convert the merged node set to a difflayer with its trie nodes, deleted ranges and `storage_roots`
+    pub fn to_difflayer(&self, storage_roots: HashMap<B256, B256>) -> DiffLayer {
+        DiffLayer::new((*self.to_diff_nodes()).clone(), storage_roots)
+            .with_deleted_ranges(self.to_deleted_ranges())
+    }
 }
 
 #[cfg(test)]
@@ -257,7 +366,7 @@ mod tests {
     }
 
     fn make_node(hash_byte: u8, blob_bytes: &[u8]) -> Arc<TrieNode> {
-       Arc::new(TrieNode::new(Some(b256(hash_byte)), Some(blob_bytes.to_vec())))
+       Arc::new(TrieNode::new(Some(b256(hash_byte)), Some(blob_bytes.to_vec().into())))
     }
 
     #[test]
@@ -266,8 +375,84 @@ mod tests {
         assert_eq!(set.size(), (0, 0));
 
         set.add_node(b"abc", make_node(1, b"v1"));
-        set.add_node(b"def", Arc::new(TrieNode::new(Some(B256::ZERO), Some(Vec::new())))); // deleted
+        set.add_node(b"def", Arc::new(TrieNode::new(Some(B256::ZERO), Some(Vec::new().into())))); // deleted
         assert_eq!(set.size(), (1, 1));
         assert_eq!(set.nodes().len(), 2);
     }
+
+    #[test]
+    fn nodeset_signature_v1_layout() {
+        use alloy_primitives::keccak256;
+
+        let mut set = NodeSet::new(b256(7));
+        set.add_node(&[1, 2], make_node(1, b"v1"));
+        set.add_leaf(b256(2), b"leaf".to_vec());
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(b256(7).as_slice());
+        expected.extend_from_slice(b256(2).as_slice());
+        expected.extend_from_slice(b"leaf");
+        expected.extend_from_slice(&[1, 2]);
+        expected.push(1u8);
+        expected.extend_from_slice(b256(1).as_slice());
+        expected.push(1u8);
+        expected.extend_from_slice(b"v1");
+        expected.extend_from_slice(&1u64.to_be_bytes());
+        expected.extend_from_slice(&0u64.to_be_bytes());
+
+        assert_eq!(set.signature_v1(), keccak256(&expected));
+        assert_eq!(set.signature(), set.signature_v1());
+        assert_eq!(NODE_SET_SIGNATURE_VERSION, 2);
+    }
+
+    #[test]
+    fn merged_nodeset_signature_v1() {
+        use alloy_primitives::keccak256;
+
+        let mut account_set = NodeSet::new(B256::ZERO);
+        account_set.add_node(&[1], make_node(1, b"account"));
+        let mut storage_set = NodeSet::new(b256(9));
+        storage_set.add_node(&[2], make_node(2, b"storage"));
+
+        let mut merged = MergedNodeSet::new();
+        merged.merge(Arc::new(storage_set.clone())).unwrap();
+        merged.merge(Arc::new(account_set.clone())).unwrap();
+
+        let mut expected = Vec::new();
+        expected.extend_from_slice(B256::ZERO.as_slice());
+        expected.extend_from_slice(account_set.signature_v1().as_slice());
+        expected.extend_from_slice(b256(9).as_slice());
+        expected.extend_from_slice(storage_set.signature_v1().as_slice());
+
+        assert_eq!(merged.signature_v1(), keccak256(&expected));
+        assert_eq!(MergedNodeSet::new().signature_v1(), keccak256([]));
+    }
+
+    #[test]
+    fn merged_nodeset_signature_v2() {
+        use alloy_primitives::keccak256;
+
+        let mut storage_set = NodeSet::new(b256(9));
+        storage_set.add_node(&[2], make_node(2, b"storage"));
+        let mut merged = MergedNodeSet::new();
+        merged.merge(Arc::new(storage_set)).unwrap();
+
+        // Nothing wiped, same as version 1
+        assert_eq!(merged.signature_v2(), merged.signature_v1());
+
+        merged.wiped_owners.insert(b256(5));
+        merged.wiped_owners.insert(b256(3));
+        let mut expected = Vec::new();
+        expected.extend_from_slice(merged.signature_v1().as_slice());
+        expected.extend_from_slice(b256(3).as_slice());
+        expected.extend_from_slice(b256(5).as_slice());
+
+        assert_eq!(merged.signature_v2(), keccak256(&expected));
+        assert_eq!(merged.signature(), merged.signature_v2());
+
+        // Wiping another owner changes the signature
+        let mut other = merged.clone();
+        other.wiped_owners.insert(b256(4));
+        assert_ne!(other.signature_v2(), merged.signature_v2());
+    }
 }
@@ -0,0 +1,237 @@
+//! Ordered iteration over the stored nodes and the leaves of a trie.
+
+use std::sync::Arc;
+
+use alloy_primitives::{Bytes, B256};
+use rust_eth_triedb_common::TrieDatabase;
+
+use crate::encoding::{hex_to_keybytes, key_to_nibbles};
+use crate::node::{DiffLayers, Node};
+use crate::secure_trie::SecureTrieError;
+use crate::trie::load_node_blob;
+
+/// Iterator over the stored nodes of a trie in path order, yielding
+/// `(path, hash, blob)`.
+///
+/// Paths are nibble paths from the root, the root itself has the empty path.
+/// Only nodes stored on their own are yielded, i.e. those referenced by hash;
+/// nodes embedded in their parent are walked but not yielded. Nodes are
+/// resolved through the difflayers before the database like point reads, so
+/// the iterator sees the persisted root of the trie, not its uncommitted
+/// changes.
+///
+/// [`skip_children`](Self::skip_children) skips the subtree below the node
+/// last yielded, e.g. when a node with the same hash was seen before, and
+/// [`seek`](Self::seek) restarts the walk at a key.
+#[derive(Debug)]
+pub struct NodeIterator<DB> {
+    database: DB,
+    difflayers: Option<DiffLayers>,
+    owner: B256,
+    /// Root hash, `None` for an empty trie
+    root: Option<B256>,
+    /// Nodes left to visit with their nibble paths, the next one on top.
+    stack: Vec<(Arc<Node>, Vec<u8>)>,
+    /// The node last yielded, its children are visited next unless skipped
+    last: Option<(Arc<Node>, Vec<u8>)>,
+    /// Nibble path nodes before which are skipped, set by `seek`
+    seek: Option<Vec<u8>>,
+}
+
+/// A step of the walk of a [`NodeIterator`].
+enum Step {
+    /// A stored node with its path, hash and blob
+    Node(Vec<u8>, B256, Bytes),
+    /// A leaf with its path, terminator included, and value
+    Leaf(Vec<u8>, Vec<u8>),
+}
+
+impl<DB> NodeIterator<DB>
+where
+    DB: TrieDatabase,
+    DB::Error: std::fmt::Debug,
+{
+    /// Creates an iterator over the trie of `owner` with root hash `root`.
+    pub(crate) fn new(root: Option<B256>, owner: B256, database: DB, difflayers: Option<DiffLayers>) -> Self {
+        let mut iter = Self { database, difflayers, owner, root, stack: Vec::new(), last: None, seek: None };
+        iter.restart();
+        iter
+    }
+
+    /// Don't visit the subtree below the node last yielded.
+    pub fn skip_children(&mut self) {
+        self.last = None;
+    }
+
+    /// Restart the walk at `key`: the next node yielded is the first one
+    /// whose path is not before the nibble path of `key`.
+    ///
+    /// Subtrees entirely before `key` are skipped without being read. Keys
+    /// are raw trie keys, i.e. hashed keys for secure tries, and resuming a
+    /// dump from its last leaf key therefore starts at that leaf.
+    pub fn seek(&mut self, key: &[u8]) {
+        let mut nibbles = key_to_nibbles(key);
+        // Drop the terminator, node paths never carry one
+        nibbles.pop();
+        self.restart();
+        self.seek = Some(nibbles);
+    }
+
+    fn restart(&mut self) {
+        self.stack.clear();
+        self.stack.extend(self.root.map(|root| (Arc::new(Node::Hash(root)), Vec::new())));
+        self.last = None;
+        self.seek = None;
+    }
+
+    /// Push the children of `node` at `path`, the lowest one on top.
+    fn push_children(&mut self, node: &Node, path: &[u8]) {
+        match node {
+            Node::Short(short) => {
+                let mut child_path = path.to_vec();
+                child_path.extend_from_slice(&short.key);
+                self.stack.push((short.val.clone(), child_path));
+            }
+            Node::Full(full) => {
+                for nibble in (0..16u8).rev() {
+                    if matches!(full.children[nibble as usize].as_ref(), Node::Empty) {
+                        continue;
+                    }
+                    let mut child_path = path.to_vec();
+                    child_path.push(nibble);
+                    self.stack.push((full.children[nibble as usize].clone(), child_path));
+                }
+                // The value sorts before the children, with the terminator as its last nibble
+                if !matches!(full.children[16].as_ref(), Node::Empty) {
+                    let mut value_path = path.to_vec();
+                    value_path.push(16);
+                    self.stack.push((full.children[16].clone(), value_path));
+                }
+            }
+            Node::Empty | Node::Value(_) | Node::Hash(_) => {}
+        }
+    }
+}
+
+impl<DB> NodeIterator<DB>
+where
+    DB: TrieDatabase,
+    DB::Error: std::fmt::Debug,
+{
+    /// Take the next step of the walk: the next stored node or leaf.
+    fn step(&mut self) -> Option<Result<Step, SecureTrieError>> {
+        loop {
+            if let Some((node, path)) = self.last.take() {
+                self.push_children(&node, &path);
+            }
+            let (node, path) = self.stack.pop()?;
+
+            // Paths are visited in order, once one reaches the seek target all later ones do
+            let mut before_seek = false;
+            if let Some(target) = &self.seek {
+                if path >= *target {
+                    self.seek = None;
+                } else if target.starts_with(&path) {
+                    before_seek = true;
+                } else {
+                    continue;
+                }
+            }
+
+            let hash = match node.as_ref() {
+                Node::Hash(hash) => *hash,
+                Node::Value(value) if !before_seek => return Some(Ok(Step::Leaf(path, value.clone()))),
+                _ => {
+                    self.push_children(&node, &path);
+                    continue;
+                }
+            };
+            let resolved = load_node_blob(&self.database, self.difflayers.as_ref(), self.owner, &path, false)
+                .and_then(|(blob, _)| Ok((Node::decode_node(Some(hash), &blob)?, blob)));
+            match resolved {
+                Ok((resolved, blob)) => {
+                    self.last = Some((resolved, path.clone()));
+                    if !before_seek {
+                        return Some(Ok(Step::Node(path, hash, blob)));
+                    }
+                }
+                Err(e) => {
+                    // Stop after reporting the error
+                    self.stack.clear();
+                    return Some(Err(e));
+                }
+            }
+        }
+    }
+}
+
+impl<DB> Iterator for NodeIterator<DB>
+where
+    DB: TrieDatabase,
+    DB::Error: std::fmt::Debug,
+{
+    type Item = Result<(Vec<u8>, B256, Bytes), SecureTrieError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.step()? {
+                Ok(Step::Node(path, hash, blob)) => return Some(Ok((path, hash, blob))),
+                Ok(Step::Leaf(..)) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
+
+/// Iterator over the leaves of a secure trie in hashed key order, yielding
+/// `(hashed_key, value)` with values decoded to `T`.
+///
+/// Walks the trie with a [`NodeIterator`], so it sees the persisted root of
+/// the trie like it does. [`start_at`](Self::start_at) skips the leaves
+/// before a hashed key without reading their subtrees, to page through
+/// large tries, e.g. for RPC range queries.
+#[derive(Debug)]
+pub struct LeafIterator<DB, T> {
+    nodes: NodeIterator<DB>,
+    decode: fn(&[u8]) -> Result<T, SecureTrieError>,
+}
+
+impl<DB, T> LeafIterator<DB, T>
+where
+    DB: TrieDatabase,
+    DB::Error: std::fmt::Debug,
+{
+    /// Creates an iterator over the leaves walked by `nodes`, decoding their values with `decode`.
+    pub(crate) fn new(nodes: NodeIterator<DB>, decode: fn(&[u8]) -> Result<T, SecureTrieError>) -> Self {
+        Self { nodes, decode }
+    }
+
+    /// Start at the first leaf whose hashed key is not before `start`.
+    pub fn start_at(mut self, start: B256) -> Self {
+        self.nodes.seek(start.as_slice());
+        self
+    }
+}
+
+impl<DB, T> Iterator for LeafIterator<DB, T>
+where
+    DB: TrieDatabase,
+    DB::Error: std::fmt::Debug,
+{
+    type Item = Result<(B256, T), SecureTrieError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            match self.nodes.step()? {
+                Ok(Step::Leaf(path, value)) => {
+                    let item = B256::try_from(hex_to_keybytes(&path).as_slice())
+                        .map_err(|_| SecureTrieError::InvalidNode)
+                        .and_then(|key| Ok((key, (self.decode)(&value)?)));
+                    return Some(item);
+                }
+                Ok(Step::Node(..)) => {}
+                Err(e) => return Some(Err(e)),
+            }
+        }
+    }
+}
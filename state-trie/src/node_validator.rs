@@ -0,0 +1,288 @@
+//! Trie node format validation.
+//!
+//! Checks stored trie node blobs for structural problems: blobs that don't
+//! decode, hashes that don't match the reference held by the parent node and
+//! compact keys that are malformed or inconsistent with the node's path. Used
+//! to check a database offline and to reject bad external data on import
+//! before it reaches the database.
+//!
+//! The checks assume a secure trie, where every key is a 32-byte hash.
+
+use std::path::Path;
+
+use alloy_primitives::{keccak256, B256};
+use rust_eth_triedb_common::{CancellationToken, TrieDatabase};
+use rust_eth_triedb_pathdb::{PathDB, PathProviderError, SstBulkLoader};
+use thiserror::Error;
+
+use crate::encoding::{account_trie_node_key, storage_trie_node_key, TRIE_NODE_ACCOUNT_PREFIX, TRIE_NODE_STORAGE_PREFIX};
+use crate::node::rlp_raw::{count_values, split, split_list, split_string, Kind};
+use crate::node::{init_empty_root_node, Node};
+
+/// Length in nibbles of a full trie key.
+const KEY_NIBBLES: usize = 64;
+
+/// Problems found in a trie node blob.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+pub enum BlobValidationError {
+    #[error("node does not decode: {0}")]
+    Decode(String),
+    #[error("node hash mismatch: expected {expected:#x}, got {actual:#x}")]
+    HashMismatch { expected: B256, actual: B256 },
+    #[error("invalid path nibble {0}")]
+    InvalidPath(u8),
+    #[error("invalid compact key: {0}")]
+    InvalidCompactKey(&'static str),
+    #[error("key of {len} nibbles at path of {path_len} nibbles: {reason}")]
+    InconsistentKeyLength { path_len: usize, len: usize, reason: &'static str },
+    #[error("invalid trie node key")]
+    InvalidNodeKey,
+    #[error("missing child node at path {0:?}")]
+    MissingChild(Vec<u8>),
+}
+
+/// Reference from a node to a child stored under its own path.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChildRef {
+    /// Nibble path of the child
+    pub path: Vec<u8>,
+    /// Hash the parent holds for the child
+    pub hash: B256,
+}
+
+/// A node that failed validation in a bulk scan.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BlobIssue {
+    /// Database key of the node
+    pub key: Vec<u8>,
+    /// What is wrong with it
+    pub error: BlobValidationError,
+}
+
+/// Result of a bulk scan.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ValidationReport {
+    /// Number of nodes scanned
+    pub scanned: u64,
+    /// Nodes that failed validation
+    pub issues: Vec<BlobIssue>,
+    /// Whether the scan was cancelled before covering all nodes
+    pub cancelled: bool,
+}
+
+impl ValidationReport {
+    /// Whether the scan completed without finding issues.
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty() && !self.cancelled
+    }
+}
+
+/// Validate a trie node blob stored at nibble `path`.
+///
+/// Checks that the blob decodes, that its hash matches `expected_hash`, the
+/// reference held by the parent node, if given, and that the compact keys of
+/// the node and its embedded children are well-formed and consistent with
+/// `path`. Returns the hash references to children stored separately, which
+/// the caller can check in turn.
+pub fn validate_blob(path: &[u8], blob: &[u8], expected_hash: Option<B256>) -> Result<Vec<ChildRef>, BlobValidationError> {
+    if let Some(&nibble) = path.iter().find(|&&nibble| nibble >= 16) {
+        return Err(BlobValidationError::InvalidPath(nibble));
+    }
+    if let Some(expected) = expected_hash {
+        let actual = keccak256(blob);
+        if actual != expected {
+            return Err(BlobValidationError::HashMismatch { expected, actual });
+        }
+    }
+
+    // Decoding resolves empty children to the shared empty root node
+    init_empty_root_node();
+    Node::decode_node(None, blob).map_err(|e| BlobValidationError::Decode(e.to_string()))?;
+
+    let mut children = Vec::new();
+    let (elements, _) = split_list(blob).map_err(decode_error)?;
+    check_node(&mut path.to_vec(), elements, &mut children)?;
+    Ok(children)
+}
+
+/// Validate a trie node blob stored under database `key`, with the path
+/// taken from the key, see [`validate_blob`].
+pub fn validate_node_entry(key: &[u8], blob: &[u8]) -> Result<Vec<ChildRef>, BlobValidationError> {
+    let (_, path) = split_node_key(key).ok_or(BlobValidationError::InvalidNodeKey)?;
+    validate_blob(path, blob, None)
+}
+
+/// Create an [`SstBulkLoader`] for `db` that checks every added node with
+/// [`validate_node_entry`], so a bad node of an import fails the add instead
+/// of being ingested.
+///
+/// Hash references are not checked, children may be added after their parent.
+pub fn validating_bulk_loader<'a>(db: &'a PathDB, dir: impl AsRef<Path>) -> Result<SstBulkLoader<'a>, PathProviderError> {
+    Ok(db.sst_bulk_loader(dir)?.with_validator(|key, blob| {
+        validate_node_entry(key, blob).map(|_| ()).map_err(|e| e.to_string())
+    }))
+}
+
+/// Validate every trie node whose database key starts with `prefix`.
+///
+/// Each node is checked with [`validate_blob`] at the path taken from its
+/// key, and each hash reference it holds is checked against the child blob
+/// stored in `db`, so a node whose hash doesn't match is reported under its
+/// parent's key.
+pub fn validate_trie_nodes(db: &PathDB, prefix: &[u8]) -> Result<ValidationReport, PathProviderError> {
+    validate_trie_nodes_with_cancellation(db, prefix, &CancellationToken::new())
+}
+
+/// Validate trie nodes like [`validate_trie_nodes`], stopping once `cancel`
+/// is cancelled.
+///
+/// The report of a cancelled scan covers the nodes scanned until then and
+/// has `cancelled` set.
+pub fn validate_trie_nodes_with_cancellation(db: &PathDB, prefix: &[u8], cancel: &CancellationToken) -> Result<ValidationReport, PathProviderError> {
+    let mut report = ValidationReport::default();
+    for item in db.iter_trie_nodes(prefix)? {
+        if cancel.is_cancelled() {
+            report.cancelled = true;
+            break;
+        }
+        let (key, blob) = item?;
+        report.scanned += 1;
+
+        let mut report_issue = |error| report.issues.push(BlobIssue { key: key.clone(), error });
+        let Some((owner, path)) = split_node_key(&key) else {
+            report_issue(BlobValidationError::InvalidNodeKey);
+            continue;
+        };
+        let children = match validate_blob(path, &blob, None) {
+            Ok(children) => children,
+            Err(error) => {
+                report_issue(error);
+                continue;
+            }
+        };
+
+        for child in children {
+            let child_key = match owner {
+                Some(owner) => storage_trie_node_key(owner, &child.path),
+                None => account_trie_node_key(&child.path),
+            };
+            match db.get_trie_node(&child_key)? {
+                Some(child_blob) => {
+                    let actual = keccak256(&child_blob);
+                    if actual != child.hash {
+                        report_issue(BlobValidationError::HashMismatch { expected: child.hash, actual });
+                    }
+                }
+                None => report_issue(BlobValidationError::MissingChild(child.path)),
+            }
+        }
+    }
+    Ok(report)
+}
+
+/// Split a trie node key into the storage trie owner, `None` for the account
+/// trie, and the nibble path.
+fn split_node_key(key: &[u8]) -> Option<(Option<&[u8]>, &[u8])> {
+    if let Some(path) = key.strip_prefix(TRIE_NODE_ACCOUNT_PREFIX) {
+        return Some((None, path));
+    }
+    let rest = key.strip_prefix(TRIE_NODE_STORAGE_PREFIX)?;
+    if rest.len() < B256::len_bytes() {
+        return None;
+    }
+    let (owner, path) = rest.split_at(B256::len_bytes());
+    Some((Some(owner), path))
+}
+
+fn decode_error(e: impl std::fmt::Debug) -> BlobValidationError {
+    BlobValidationError::Decode(format!("{:?}", e))
+}
+
+/// Check the RLP list payload of a node at `path`, collecting hash references.
+fn check_node(path: &mut Vec<u8>, elements: &[u8], children: &mut Vec<ChildRef>) -> Result<(), BlobValidationError> {
+    match count_values(elements).map_err(decode_error)? {
+        2 => {
+            let (compact, rest) = split_string(elements).map_err(decode_error)?;
+            let (key, leaf) = compact_key_nibbles(compact)?;
+            let len = path.len() + key.len();
+            if leaf {
+                if len != KEY_NIBBLES {
+                    return Err(BlobValidationError::InconsistentKeyLength {
+                        path_len: path.len(),
+                        len: key.len(),
+                        reason: "leaf does not end at the full key length",
+                    });
+                }
+                return Ok(());
+            }
+            if key.is_empty() || len >= KEY_NIBBLES {
+                return Err(BlobValidationError::InconsistentKeyLength {
+                    path_len: path.len(),
+                    len: key.len(),
+                    reason: "extension must be non-empty and end before the full key length",
+                });
+            }
+            let depth = path.len();
+            path.extend_from_slice(&key);
+            check_ref(path, rest, children)?;
+            path.truncate(depth);
+        }
+        17 => {
+            if path.len() >= KEY_NIBBLES {
+                return Err(BlobValidationError::InconsistentKeyLength {
+                    path_len: path.len(),
+                    len: 0,
+                    reason: "branch at or below the full key length",
+                });
+            }
+            let mut rest = elements;
+            for nibble in 0..16u8 {
+                path.push(nibble);
+                rest = check_ref(path, rest, children)?;
+                path.pop();
+            }
+        }
+        _ => return Err(BlobValidationError::Decode("invalid number of list elements".to_string())),
+    }
+    Ok(())
+}
+
+/// Check a child reference at `path`, returning the remaining list payload.
+fn check_ref<'a>(path: &mut Vec<u8>, buf: &'a [u8], children: &mut Vec<ChildRef>) -> Result<&'a [u8], BlobValidationError> {
+    let (kind, val, rest) = split(buf).map_err(decode_error)?;
+    match kind {
+        Kind::List => check_node(path, val, children)?,
+        Kind::String if val.len() == B256::len_bytes() => {
+            children.push(ChildRef { path: path.clone(), hash: B256::from_slice(val) });
+        }
+        _ => {}
+    }
+    Ok(rest)
+}
+
+/// Decode a compact key into nibbles, returning whether it is a leaf key.
+///
+/// Unlike `compact_to_hex`, malformed flag nibbles and padding are rejected.
+fn compact_key_nibbles(compact: &[u8]) -> Result<(Vec<u8>, bool), BlobValidationError> {
+    let Some(&first) = compact.first() else {
+        return Err(BlobValidationError::InvalidCompactKey("empty key"));
+    };
+    let flag = first >> 4;
+    if flag > 3 {
+        return Err(BlobValidationError::InvalidCompactKey("invalid flag nibble"));
+    }
+    let odd = flag & 1 == 1;
+    if !odd && first & 0x0f != 0 {
+        return Err(BlobValidationError::InvalidCompactKey("non-zero padding nibble"));
+    }
+
+    let mut nibbles = Vec::with_capacity(compact.len() * 2);
+    if odd {
+        nibbles.push(first & 0x0f);
+    }
+    for &b in &compact[1..] {
+        nibbles.push(b >> 4);
+        nibbles.push(b & 0x0f);
+    }
+    Ok((nibbles, flag >= 2))
+}
@@ -1,11 +1,13 @@
 //! Secure trie identifier and builder implementation.
 
 use alloy_primitives::B256;
-use rust_eth_triedb_common::TrieDatabase;
+use std::sync::Arc;
+use rust_eth_triedb_common::{TrieDatabase, TrieHooks};
 use thiserror::Error;
 use alloy_trie::EMPTY_ROOT_HASH;
 use super::state_trie::StateTrie;
 use super::node::DiffLayers;
+use super::trie_recorder::NodeReadRecorder;
 
 // use super::state_trie::StateTrie;
 
@@ -33,6 +35,25 @@ pub enum SecureTrieError {
     /// Invalid storage data
     #[error("Invalid storage data")]
     InvalidStorage,
+    /// Trie has changes that are not hashed yet
+    #[error("Trie has unhashed changes")]
+    Unhashed,
+    /// Stack trie key not greater than the previous one, or extending it
+    #[error("Stack trie keys must be strictly increasing and not prefixes of each other")]
+    UnorderedKey,
+    /// Stack trie insert of an empty value
+    #[error("Stack trie values can't be empty")]
+    EmptyValue,
+    /// Update of one key of a batch failed
+    #[error("Update of key {key} failed: {source}")]
+    KeyUpdate { key: alloy_primitives::Bytes, source: Box<SecureTrieError> },
+}
+
+impl SecureTrieError {
+    /// Wraps the error of the update of `key` within a batch.
+    pub(crate) fn for_key(self, key: &[u8]) -> Self {
+        Self::KeyUpdate { key: key.to_vec().into(), source: Box::new(self) }
+    }
 }
 
 /// A unique identifier for a secure trie instance.
@@ -116,6 +137,9 @@ pub struct SecureTrieBuilder<DB> {
     #[allow(dead_code)]
     database: DB,
     id: Option<SecureTrieId>,
+    hooks: Option<Arc<dyn TrieHooks>>,
+    verified_reads: bool,
+    recorder: Option<NodeReadRecorder>,
 }
 
 impl<DB> SecureTrieBuilder<DB>
@@ -128,6 +152,9 @@ where
         Self {
             database,
             id: None,
+            hooks: None,
+            verified_reads: false,
+            recorder: None,
         }
     }
 
@@ -137,9 +164,27 @@ where
         self
     }
 
+    /// Sets the instrumentation hooks
+    pub fn with_hooks(mut self, hooks: Option<Arc<dyn TrieHooks>>) -> Self {
+        self.hooks = hooks;
+        self
+    }
+
+    /// Sets whether nodes are resolved through verified database reads
+    pub fn with_verified_reads(mut self, verified_reads: bool) -> Self {
+        self.verified_reads = verified_reads;
+        self
+    }
+
+    /// Sets the recorder of the nodes the trie resolves
+    pub fn with_recorder(mut self, recorder: Option<NodeReadRecorder>) -> Self {
+        self.recorder = recorder;
+        self
+    }
+
     /// Builds the secure trie with difflayer
     pub fn build_with_difflayer(self, difflayer: Option<&DiffLayers>) -> Result<StateTrie<DB>, SecureTrieError> {
         let id = self.id.unwrap_or_else(|| SecureTrieId::default());
-        StateTrie::new(id, self.database, difflayer)
+        StateTrie::new_with_recorder(id, self.database, difflayer, self.hooks, self.verified_reads, self.recorder)
     }
 }
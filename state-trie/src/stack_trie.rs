@@ -0,0 +1,215 @@
+//! Trie built from keys inserted in order.
+
+use std::sync::Arc;
+
+use alloy_primitives::{keccak256, B256};
+use alloy_trie::EMPTY_ROOT_HASH;
+
+use crate::encoding::{common_prefix_length, hex_to_compact, key_to_nibbles};
+use crate::node::{FullNode, Node, NodeFlag, NodeSet, ShortNode, TrieNode};
+use crate::secure_trie::SecureTrieError;
+
+/// A trie built from keys inserted in strictly increasing order, e.g. from
+/// the sorted account stream of snap sync or a genesis import.
+///
+/// Never reads the database: once a key is inserted, every subtree left of
+/// it is complete and is collapsed to its hash right away, so memory stays
+/// bounded by the depth of the trie. The root matches the one of a
+/// [`Trie`](crate::trie::Trie) holding the same keys and values, and with
+/// [`with_node_set`](Self::with_node_set) the collapsed nodes are collected
+/// into the same [`NodeSet`] its commit returns.
+///
+/// Keys are raw trie keys, i.e. hashed keys for secure tries. Values can't
+/// be empty, and no key can be a prefix of another.
+#[derive(Debug)]
+pub struct StackTrie {
+    owner: B256,
+    root: StackNode,
+    /// Collected nodes, `None` if not collecting
+    nodes: Option<NodeSet>,
+    /// The key inserted last
+    last_key: Option<Vec<u8>>,
+}
+
+/// A node of a [`StackTrie`], keys are in nibbles.
+#[derive(Debug)]
+enum StackNode {
+    Empty,
+    Leaf { key: Vec<u8>, value: Vec<u8> },
+    Extension { key: Vec<u8>, child: Box<StackNode> },
+    Branch(Box<[StackNode; 16]>),
+    /// A complete subtree, collapsed to a hash node or an embedded node
+    Collapsed(Arc<Node>),
+}
+
+impl StackTrie {
+    /// Creates an empty stack trie for the trie of `owner`, zero for the account trie.
+    pub fn new(owner: B256) -> Self {
+        Self { owner, root: StackNode::Empty, nodes: None, last_key: None }
+    }
+
+    /// Collect the nodes of the trie into a [`NodeSet`] returned by `commit`,
+    /// to persist them.
+    pub fn with_node_set(mut self, collect: bool) -> Self {
+        self.nodes = collect.then(|| NodeSet::new(self.owner));
+        self
+    }
+
+    /// Inserts `value` at `key`, which must be greater than all keys inserted before.
+    pub fn update(&mut self, key: &[u8], value: &[u8]) -> Result<(), SecureTrieError> {
+        if value.is_empty() {
+            return Err(SecureTrieError::EmptyValue);
+        }
+        if self.last_key.as_ref().is_some_and(|last_key| key <= last_key.as_slice()) {
+            return Err(SecureTrieError::UnorderedKey);
+        }
+        let mut nibbles = key_to_nibbles(key);
+        // The terminator is added back when leaves are encoded
+        nibbles.pop();
+        self.root.insert(&nibbles, value.to_vec(), &[], &mut self.nodes)?;
+        self.last_key = Some(key.to_vec());
+        Ok(())
+    }
+
+    /// Collapses the remaining nodes and returns the root hash, with the
+    /// nodes of the trie if collected.
+    pub fn commit(mut self) -> (B256, Option<Arc<NodeSet>>) {
+        if matches!(self.root, StackNode::Empty) {
+            return (EMPTY_ROOT_HASH, None);
+        }
+        // The root is stored by hash even if its encoding is short
+        self.root.collapse(&[], true, &mut self.nodes);
+        let StackNode::Collapsed(root) = &self.root else {
+            unreachable!("collapsed above");
+        };
+        let Node::Hash(root_hash) = root.as_ref() else {
+            unreachable!("the root is collapsed to its hash");
+        };
+        (*root_hash, self.nodes.map(Arc::new))
+    }
+}
+
+/// `path` extended by `nibbles`.
+fn child_path(path: &[u8], nibbles: &[u8]) -> Vec<u8> {
+    [path, nibbles].concat()
+}
+
+fn new_branch() -> Box<[StackNode; 16]> {
+    Box::new(std::array::from_fn(|_| StackNode::Empty))
+}
+
+impl StackNode {
+    /// Inserts `value` at the nibble key `key` below this node at `path`.
+    fn insert(&mut self, key: &[u8], value: Vec<u8>, path: &[u8], nodes: &mut Option<NodeSet>) -> Result<(), SecureTrieError> {
+        match self {
+            StackNode::Empty => *self = StackNode::Leaf { key: key.to_vec(), value },
+            StackNode::Branch(children) => {
+                let index = key[0] as usize;
+                // Keys are sorted, so the left siblings are complete
+                if let Some(sibling) = (0..index).rev().find(|&i| !matches!(children[i], StackNode::Empty)) {
+                    children[sibling].collapse(&child_path(path, &[sibling as u8]), false, nodes);
+                }
+                children[index].insert(&key[1..], value, &child_path(path, &key[..1]), nodes)?;
+            }
+            StackNode::Extension { key: ext_key, child } => {
+                let diff = common_prefix_length(ext_key, key);
+                if diff == ext_key.len() {
+                    let path = child_path(path, ext_key);
+                    return child.insert(&key[diff..], value, &path, nodes);
+                }
+                let StackNode::Extension { key: ext_key, child } = std::mem::replace(self, StackNode::Empty) else {
+                    unreachable!("matched above");
+                };
+                // The part after the split point is complete
+                let mut rest = if diff + 1 < ext_key.len() {
+                    StackNode::Extension { key: ext_key[diff + 1..].to_vec(), child }
+                } else {
+                    *child
+                };
+                rest.collapse(&child_path(path, &ext_key[..diff + 1]), false, nodes);
+                *self = Self::split(&ext_key, diff, rest, key, value);
+            }
+            StackNode::Leaf { key: leaf_key, .. } => {
+                let diff = common_prefix_length(leaf_key, key);
+                if diff == leaf_key.len() {
+                    // The previous key is a prefix of this one
+                    return Err(SecureTrieError::UnorderedKey);
+                }
+                let StackNode::Leaf { key: leaf_key, value: leaf_value } = std::mem::replace(self, StackNode::Empty) else {
+                    unreachable!("matched above");
+                };
+                let mut rest = StackNode::Leaf { key: leaf_key[diff + 1..].to_vec(), value: leaf_value };
+                rest.collapse(&child_path(path, &leaf_key[..diff + 1]), false, nodes);
+                *self = Self::split(&leaf_key, diff, rest, key, value);
+            }
+            StackNode::Collapsed(_) => unreachable!("keys are sorted, collapsed subtrees are never entered again"),
+        }
+        Ok(())
+    }
+
+    /// The node replacing one with key `old_key` split at nibble `diff` by
+    /// the insert of `value` at `key`, `rest` holding what followed the split.
+    fn split(old_key: &[u8], diff: usize, rest: StackNode, key: &[u8], value: Vec<u8>) -> StackNode {
+        let mut branch = new_branch();
+        branch[old_key[diff] as usize] = rest;
+        branch[key[diff] as usize] = StackNode::Leaf { key: key[diff + 1..].to_vec(), value };
+        match diff {
+            0 => StackNode::Branch(branch),
+            _ => StackNode::Extension { key: old_key[..diff].to_vec(), child: Box::new(StackNode::Branch(branch)) },
+        }
+    }
+
+    /// Collapses this complete subtree at `path` to its hash node, or to the
+    /// node itself if it encodes to less than 32 bytes and not `force`.
+    fn collapse(&mut self, path: &[u8], force: bool, nodes: &mut Option<NodeSet>) {
+        let node = match std::mem::replace(self, StackNode::Empty) {
+            StackNode::Empty => return,
+            StackNode::Collapsed(node) => {
+                *self = StackNode::Collapsed(node);
+                return;
+            }
+            StackNode::Leaf { mut key, value } => {
+                key.push(16);
+                Node::Short(Arc::new(ShortNode {
+                    key: hex_to_compact(&key),
+                    val: Arc::new(Node::Value(value)),
+                    flags: NodeFlag::default(),
+                }))
+            }
+            StackNode::Extension { key, mut child } => {
+                child.collapse(&child_path(path, &key), false, nodes);
+                Node::Short(Arc::new(ShortNode { key: hex_to_compact(&key), val: child.into_node(), flags: NodeFlag::default() }))
+            }
+            StackNode::Branch(children) => {
+                let mut full = FullNode::new();
+                let children: [StackNode; 16] = *children;
+                for (nibble, mut child) in children.into_iter().enumerate() {
+                    child.collapse(&child_path(path, &[nibble as u8]), false, nodes);
+                    full.children[nibble] = child.into_node();
+                }
+                Node::Full(Arc::new(full))
+            }
+        };
+
+        let node = Arc::new(node);
+        let blob = Node::node_to_bytes(node.clone());
+        if blob.len() < 32 && !force {
+            *self = StackNode::Collapsed(node);
+            return;
+        }
+        let hash = keccak256(&blob);
+        if let Some(nodes) = nodes {
+            nodes.add_node(path, Arc::new(TrieNode::new(Some(hash), Some(blob.into()))));
+        }
+        *self = StackNode::Collapsed(Arc::new(Node::Hash(hash)));
+    }
+
+    /// The trie node of a collapsed or empty node.
+    fn into_node(self) -> Arc<Node> {
+        match self {
+            StackNode::Empty => Node::empty_root(),
+            StackNode::Collapsed(node) => node,
+            _ => unreachable!("children are collapsed before their parent"),
+        }
+    }
+}
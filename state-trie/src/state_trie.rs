@@ -4,12 +4,15 @@ use alloy_primitives::{Address, B256, U256, keccak256};
 use std::{sync::Arc};
 
 use alloy_rlp::{Encodable, Decodable};
-use rust_eth_triedb_common::TrieDatabase;
+use alloy_trie::EMPTY_ROOT_HASH;
+use rust_eth_triedb_common::{TrieDatabase, TrieHooks};
 
 use super::account::StateAccount;
 use super::secure_trie::{SecureTrieId, SecureTrieError};
 use super::traits::SecureTrieTrait;
 use super::trie::Trie;
+use super::trie_recorder::NodeReadRecorder;
+use super::node_iterator::{LeafIterator, NodeIterator};
 use super::node::{NodeSet, DiffLayers};
 use super::node::rlp_raw;
 
@@ -88,10 +91,54 @@ where
 {
     /// Creates a new state trie with the given identifier and database
     pub fn new(id: SecureTrieId, database: DB, difflayer: Option<&DiffLayers>) -> Result<Self, SecureTrieError> {
-        let trie = Trie::new(&id, database, difflayer)?;
+        Self::new_with_hooks(id, database, difflayer, None)
+    }
+
+    /// Creates a new state trie that reports node reads to `hooks`
+    pub fn new_with_hooks(id: SecureTrieId, database: DB, difflayer: Option<&DiffLayers>, hooks: Option<Arc<dyn TrieHooks>>) -> Result<Self, SecureTrieError> {
+        Self::new_with_options(id, database, difflayer, hooks, false)
+    }
+
+    /// Creates a new state trie that reports node reads to `hooks` and
+    /// resolves nodes through verified reads if `verified_reads`, see
+    /// `Trie::new_with_options`
+    pub fn new_with_options(
+        id: SecureTrieId,
+        database: DB,
+        difflayer: Option<&DiffLayers>,
+        hooks: Option<Arc<dyn TrieHooks>>,
+        verified_reads: bool,
+    ) -> Result<Self, SecureTrieError> {
+        Self::new_with_recorder(id, database, difflayer, hooks, verified_reads, None)
+    }
+
+    /// Creates a new state trie like `new_with_options` that records the
+    /// nodes it resolves to `recorder`, see `Trie::new_with_recorder`
+    pub fn new_with_recorder(
+        id: SecureTrieId,
+        database: DB,
+        difflayer: Option<&DiffLayers>,
+        hooks: Option<Arc<dyn TrieHooks>>,
+        verified_reads: bool,
+        recorder: Option<NodeReadRecorder>,
+    ) -> Result<Self, SecureTrieError> {
+        let trie = Trie::new_with_recorder(&id, database, difflayer, hooks, verified_reads, recorder)?;
         Ok(Self { trie, id })
     }
 
+    /// Re-targets this state trie at `id` on top of `difflayer`, reusing its
+    /// allocations, see `Trie::reset`.
+    pub fn reset(&mut self, id: SecureTrieId, difflayer: Option<&DiffLayers>) -> Result<(), SecureTrieError> {
+        self.trie.reset(&id, difflayer)?;
+        self.id = id;
+        Ok(())
+    }
+
+    /// Drops the state held by this state trie ahead of a later `reset`.
+    pub fn release(&mut self) {
+        self.trie.release();
+    }
+
     /// Returns the identifier of this state trie
     pub fn id(&self) -> &SecureTrieId {
         &self.id
@@ -119,6 +166,91 @@ where
     pub fn hash_key(&self, key: &[u8]) -> B256 {
         keccak256(key)
     }
+
+    /// Applies a batch of account updates keyed by hashed address, `None`
+    /// deleting the account.
+    ///
+    /// Large batches update the root subtries in parallel, see `Trie::update_batch`.
+    pub fn update_accounts_with_hash_state(&mut self, accounts: Vec<(B256, Option<StateAccount>)>) -> Result<(), SecureTrieError> {
+        let updates = accounts
+            .into_iter()
+            .map(|(hashed_address, account)| {
+                let encoded_account = account.map(alloy_rlp::encode).unwrap_or_default();
+                (hashed_address.to_vec(), encoded_account)
+            })
+            .collect();
+        self.trie.update_batch(updates)
+    }
+
+    /// Applies a batch of storage updates keyed by hashed slot, `None`
+    /// deleting the slot, with values encoded as in
+    /// `update_storage_u256_with_hash_state`.
+    ///
+    /// Goes through the bulk path of `Trie::update_sorted`, meant for
+    /// accounts with many changed slots.
+    pub fn update_storages_with_hash_state(&mut self, slots: Vec<(B256, Option<U256>)>) -> Result<(), SecureTrieError> {
+        let updates = slots
+            .into_iter()
+            .map(|(hashed_key, value)| {
+                let encoded_value = value.map(alloy_rlp::encode).unwrap_or_default();
+                (hashed_key.to_vec(), encoded_value)
+            })
+            .collect();
+        self.trie.update_sorted(updates)
+    }
+
+    /// Iterates over all accounts in hashed address order, see `Trie::iter`.
+    pub fn iter_accounts_with_hash_state(&self) -> impl Iterator<Item = Result<(B256, StateAccount), SecureTrieError>> {
+        self.trie.iter().map(|item| {
+            let (key, data) = item?;
+            let account = StateAccount::decode(&mut &data[..])
+                .map_err(|_| SecureTrieError::InvalidAccount)?;
+            Ok((B256::from_slice(&key), account))
+        })
+    }
+
+    /// Iterates over all storage slots in hashed key order, with values in
+    /// the format of `get_storage_with_hash_state`, see `Trie::iter`.
+    pub fn iter_storage_with_hash_state(&self) -> impl Iterator<Item = Result<(B256, Vec<u8>), SecureTrieError>> {
+        self.trie.iter().map(|item| {
+            let (key, enc) = item?;
+            let (_, value, _) = rlp_raw::split(&enc).map_err(|_| SecureTrieError::InvalidStorage)?;
+            Ok((B256::from_slice(&key), value.to_vec()))
+        })
+    }
+
+    /// Iterates over the stored nodes of the trie in path order, see `Trie::node_iter`.
+    pub fn node_iter(&self) -> Result<NodeIterator<DB>, SecureTrieError> {
+        self.trie.node_iter()
+    }
+
+    /// Iterates over the accounts of the persisted root of this account trie
+    /// in hashed address order, see `LeafIterator`.
+    ///
+    /// Fails with `Unhashed` if the trie has changes that are not hashed yet.
+    pub fn iter_accounts(&self) -> Result<LeafIterator<DB, StateAccount>, SecureTrieError> {
+        let decode = |data: &[u8]| StateAccount::decode(&mut &data[..]).map_err(|_| SecureTrieError::InvalidAccount);
+        Ok(LeafIterator::new(self.trie.node_iter()?, decode))
+    }
+
+    /// Iterates over the storage slots of the account `hashed_address` in
+    /// hashed slot order, see `LeafIterator`.
+    ///
+    /// The storage trie is read at the storage root of the account in this
+    /// account trie, which must be persisted. Yields nothing for accounts
+    /// that don't exist or have no storage.
+    pub fn iter_storage(&mut self, hashed_address: B256) -> Result<LeafIterator<DB, U256>, SecureTrieError> {
+        let storage_root = self.get_account_with_hash_state(hashed_address)?
+            .map(|account| account.storage_root)
+            .filter(|storage_root| *storage_root != EMPTY_ROOT_HASH);
+        let decode = |enc: &[u8]| U256::decode(&mut &enc[..]).map_err(|_| SecureTrieError::InvalidStorage);
+        Ok(LeafIterator::new(self.trie.node_iter_at(hashed_address, storage_root), decode))
+    }
+
+    /// Builds a Merkle proof for an already hashed account address or storage key
+    pub fn prove_with_hash_state(&self, hashed_key: B256) -> Result<Vec<Vec<u8>>, SecureTrieError> {
+        self.trie.prove(hashed_key.as_slice())
+    }
 }
 
 impl<DB> SecureTrieTrait for StateTrie<DB>
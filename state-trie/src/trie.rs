@@ -1,16 +1,121 @@
 //! Core trie implementation for secure trie operations.
 
+use std::collections::HashMap;
 use std::sync::{Arc, Mutex};
 
-use alloy_primitives::{B256};
+use alloy_primitives::{Bytes, B256};
 use alloy_trie::EMPTY_ROOT_HASH;
-use rust_eth_triedb_common::TrieDatabase;
+use rust_eth_triedb_common::{NodeReadSource, TrieDatabase, TrieHooks};
+use rayon::prelude::*;
+use crate::difflayer_metrics::lookup_trie_node;
 use crate::trie_committer::Committer;
 use super::encoding::{common_prefix_length, key_to_nibbles, account_trie_node_key, storage_trie_node_key};
 use super::node::{Node, NodeFlag, FullNode, ShortNode, NodeSet, TrieNode, DiffLayers};
 use super::secure_trie::{SecureTrieId, SecureTrieError};
 use super::trie_hasher::Hasher;
 use super::trie_tracer::TrieTracer;
+use super::trie_recorder::NodeReadRecorder;
+use super::trie_iterator::TrieIterator;
+use super::node_iterator::NodeIterator;
+
+/// Reads the blob of the node at `prefix` of the trie of `owner`, from the
+/// difflayers first and the database otherwise, through a verified read if
+/// `verified`.
+pub(crate) fn load_node_blob<DB>(
+    database: &DB,
+    difflayers: Option<&DiffLayers>,
+    owner: B256,
+    prefix: &[u8],
+    verified: bool,
+) -> Result<(Bytes, NodeReadSource), SecureTrieError>
+where
+    DB: TrieDatabase,
+    DB::Error: std::fmt::Debug,
+{
+    let key = trie_node_key(owner, prefix);
+
+    // 1. Check if the hash is in the difflayer, a deleted node masks the database
+    let diff_node = difflayers.and_then(|difflayers| lookup_trie_node(difflayers, &key));
+    match diff_node {
+        Some(node) if !node.is_deleted() => return Ok((node.blob.clone().unwrap(), NodeReadSource::DiffLayer)),
+        Some(_) => {}
+        None => {
+            // 2. Check if the hash is in the database
+            let node_blob = match verified {
+                true => database.get_trie_node_verified(&key),
+                false => database.get_trie_node(&key),
+            };
+            if let Some(node_blob) = node_blob.map_err(|e| SecureTrieError::Database(format!("{:?}", e)))? {
+                return Ok((node_blob, NodeReadSource::Database));
+            }
+        }
+    }
+
+    Err(missing_trie_node(owner, prefix, &key))
+}
+
+/// Reads the blobs of the nodes at `prefixes` of the trie of `owner` like
+/// `load_node_blob`, with a single database lookup for all nodes not found
+/// in the difflayers.
+pub(crate) fn load_node_blobs<DB>(
+    database: &DB,
+    difflayers: Option<&DiffLayers>,
+    owner: B256,
+    prefixes: &[Vec<u8>],
+) -> Result<Vec<(Bytes, NodeReadSource)>, SecureTrieError>
+where
+    DB: TrieDatabase,
+    DB::Error: std::fmt::Debug,
+{
+    let keys: Vec<Vec<u8>> = prefixes.iter().map(|prefix| trie_node_key(owner, prefix)).collect();
+
+    let mut blobs = vec![None; keys.len()];
+    let mut database_lookups = Vec::new();
+    for (index, key) in keys.iter().enumerate() {
+        match difflayers.and_then(|difflayers| lookup_trie_node(difflayers, key)) {
+            Some(node) if !node.is_deleted() => blobs[index] = Some((node.blob.clone().unwrap(), NodeReadSource::DiffLayer)),
+            // A deleted node masks the database
+            Some(_) => {}
+            None => database_lookups.push(index),
+        }
+    }
+
+    if !database_lookups.is_empty() {
+        let database_keys: Vec<Vec<u8>> = database_lookups.iter().map(|&index| keys[index].clone()).collect();
+        let nodes = database.get_trie_nodes(&database_keys).map_err(|e| SecureTrieError::Database(format!("{:?}", e)))?;
+        for (index, node) in database_lookups.into_iter().zip(nodes) {
+            blobs[index] = node.map(|node_blob| (node_blob, NodeReadSource::Database));
+        }
+    }
+
+    blobs.into_iter()
+        .zip(prefixes.iter().zip(&keys))
+        .map(|(blob, (prefix, key))| blob.ok_or_else(|| missing_trie_node(owner, prefix, key)))
+        .collect()
+}
+
+/// Database key of the node at `prefix` of the trie of `owner`.
+fn trie_node_key(owner: B256, prefix: &[u8]) -> Vec<u8> {
+    if owner == B256::ZERO {
+        account_trie_node_key(prefix)
+    } else {
+        storage_trie_node_key(owner.as_slice(), prefix)
+    }
+}
+
+fn missing_trie_node(owner: B256, prefix: &[u8], key: &[u8]) -> SecureTrieError {
+    let owner_hex = format!("0x{:x}", owner);
+    let prefix_hex = prefix.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    let key_hex = key.iter().map(|b| format!("{:02x}", b)).collect::<String>();
+    SecureTrieError::Database(format!("missing trie node: owner: {}, prefix: 0x{}, key: 0x{}", owner_hex, prefix_hex, key_hex))
+}
+
+/// Minimum batch size for `Trie::update_batch` to update root subtries in parallel.
+const PARALLEL_UPDATE_THRESHOLD: usize = 64;
+
+/// Number of unhashed changes above which the root subtries are hashed and
+/// committed in parallel.
+const PARALLEL_HASH_THRESHOLD: usize = 100;
 
 /// Core trie implementation
 #[derive(Clone, Debug)]
@@ -23,6 +128,9 @@ pub struct Trie<DB> {
     pub tracer: TrieTracer,
     database: DB,
     difflayers: Option<DiffLayers>,
+    hooks: Option<Arc<dyn TrieHooks>>,
+    verified_reads: bool,
+    recorder: Option<NodeReadRecorder>,
 }
 
 /// Basic Trie operations
@@ -33,6 +141,39 @@ where
 {
     /// Creates a new trie with the given identifier and database
     pub fn new(id: &SecureTrieId, database: DB, difflayer: Option<&DiffLayers>) -> Result<Self, SecureTrieError> {
+        Self::new_with_hooks(id, database, difflayer, None)
+    }
+
+    /// Creates a new trie that reports node reads to `hooks`
+    pub fn new_with_hooks(id: &SecureTrieId, database: DB, difflayer: Option<&DiffLayers>, hooks: Option<Arc<dyn TrieHooks>>) -> Result<Self, SecureTrieError> {
+        Self::new_with_options(id, database, difflayer, hooks, false)
+    }
+
+    /// Creates a new trie that reports node reads to `hooks` and resolves
+    /// nodes through verified database reads if `verified_reads`, see
+    /// `TrieDatabase::get_trie_node_verified`.
+    ///
+    /// `prefetch_paths` keeps the regular reads either way.
+    pub fn new_with_options(
+        id: &SecureTrieId,
+        database: DB,
+        difflayer: Option<&DiffLayers>,
+        hooks: Option<Arc<dyn TrieHooks>>,
+        verified_reads: bool,
+    ) -> Result<Self, SecureTrieError> {
+        Self::new_with_recorder(id, database, difflayer, hooks, verified_reads, None)
+    }
+
+    /// Creates a trie like `new_with_options` that records the nodes it
+    /// resolves, the root included, to `recorder`.
+    pub fn new_with_recorder(
+        id: &SecureTrieId,
+        database: DB,
+        difflayer: Option<&DiffLayers>,
+        hooks: Option<Arc<dyn TrieHooks>>,
+        verified_reads: bool,
+        recorder: Option<NodeReadRecorder>,
+    ) -> Result<Self, SecureTrieError> {
         let mut tr = Self {
             root: Node::empty_root(),
             owner: id.owner,
@@ -42,6 +183,9 @@ where
             tracer: TrieTracer::new(),
             database,
             difflayers: difflayer.map(|d| d.clone()),
+            hooks,
+            verified_reads,
+            recorder,
         };
 
         // Check if this is an empty trie (root is EmptyRootHash)
@@ -59,6 +203,38 @@ where
         Ok(tr)
     }
 
+    /// Re-targets the trie at `id` on top of `difflayer`, keeping its
+    /// database, hooks, recorder and the allocations of its tracer.
+    ///
+    /// Equivalent to building a new trie with `new_with_hooks`, without
+    /// allocating a fresh one for every storage trie of every block.
+    pub fn reset(&mut self, id: &SecureTrieId, difflayer: Option<&DiffLayers>) -> Result<(), SecureTrieError> {
+        self.release();
+        self.owner = id.owner;
+        self.difflayers = difflayer.map(|d| d.clone());
+        if id.state_root != alloy_trie::EMPTY_ROOT_HASH && id.state_root != B256::ZERO {
+            self.root = self.resolve_and_track(&id.state_root, &[])?;
+        }
+        Ok(())
+    }
+
+    /// Sets the recorder of the nodes resolved from now on, `None` to stop
+    /// recording.
+    pub fn set_recorder(&mut self, recorder: Option<NodeReadRecorder>) {
+        self.recorder = recorder;
+    }
+
+    /// Drops the nodes, difflayers and tracked paths of the trie, keeping
+    /// the allocations of its tracer for a later `reset`.
+    pub fn release(&mut self) {
+        self.root = Node::empty_root();
+        self.committed = false;
+        self.unhashed = 0;
+        self.uncommitted = 0;
+        self.tracer.reset();
+        self.difflayers = None;
+    }
+
     /// Creates a new flag for the trie
     pub fn new_flag(&self) -> NodeFlag {
         NodeFlag::default()
@@ -74,7 +250,7 @@ where
         if self.root == Node::empty_root() {
             return EMPTY_ROOT_HASH;
         }
-        let hasher = Hasher::new(self.unhashed > 100);
+        let hasher = Hasher::new(self.unhashed > PARALLEL_HASH_THRESHOLD);
         let(hashed, cached) = hasher.hash(self.root.clone(), true);
         
         self.root = cached;
@@ -122,7 +298,7 @@ where
             self.root = Committer::new(nodes.clone(), &self.tracer, collect_leaf)
                 .commit(
                     self.root.clone(), 
-                    self.unhashed > 100
+                    self.unhashed > PARALLEL_HASH_THRESHOLD
                 );
         }
 
@@ -171,6 +347,53 @@ where
         Ok(value)
     }
 
+    /// Builds a Merkle proof for `key` against the persisted root of the trie.
+    ///
+    /// Returns the encoded nodes on the path from the root towards `key`, root
+    /// first. The proof proves absence if the key isn't in the trie. Nodes
+    /// are read from the difflayers and the database, so uncommitted changes
+    /// made through this instance are not reflected.
+    pub fn prove(&self, key: &[u8]) -> Result<Vec<Vec<u8>>, SecureTrieError> {
+        let (root_hash, dirty) = self.root.cache();
+        let root_hash = match (root_hash, dirty) {
+            (Some(root_hash), false) => root_hash,
+            _ if matches!(self.root.as_ref(), Node::Empty) => return Ok(Vec::new()),
+            _ => return Err(SecureTrieError::Unhashed),
+        };
+
+        let nibbles_key = key_to_nibbles(key);
+        let mut proof = Vec::new();
+        let mut node = Arc::new(Node::Hash(root_hash));
+        let mut pos = 0;
+        loop {
+            node = match node.as_ref() {
+                Node::Hash(hash) => {
+                    let (node_blob, _) = self.read_node_blob(&nibbles_key[..pos])?;
+                    if let Some(recorder) = &self.recorder {
+                        recorder.record(&node_blob);
+                    }
+                    let resolved = Node::must_decode_node(Some(*hash), &node_blob);
+                    proof.push(node_blob.to_vec());
+                    resolved
+                }
+                Node::Short(short) => {
+                    if !nibbles_key[pos..].starts_with(&short.key) {
+                        break;
+                    }
+                    pos += short.key.len();
+                    short.val.clone()
+                }
+                Node::Full(full) => {
+                    let child = full.get_child(nibbles_key[pos] as usize);
+                    pos += 1;
+                    child
+                }
+                Node::Empty | Node::Value(_) => break,
+            };
+        }
+        Ok(proof)
+    }
+
     /// Updates a value in the trie by key
     pub fn update(&mut self, key: &[u8], value: &[u8]) -> Result<(), SecureTrieError> {
         // Check if trie is already committed
@@ -243,6 +466,202 @@ where
         self.root = new_root;
         Ok(())
     }
+
+    /// Applies a batch of updates, an empty value deleting the key.
+    ///
+    /// When the root is a full node, updates are partitioned by the first
+    /// nibble of their key and the 16 root subtries are updated in parallel,
+    /// each with its own tracer, before the root is reassembled. Otherwise,
+    /// or for small batches, updates are applied serially. Updates to the same
+    /// key are applied in batch order. On error the trie must be discarded.
+    pub fn update_batch(&mut self, updates: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), SecureTrieError> {
+        if self.committed {
+            return Err(SecureTrieError::AlreadyCommitted);
+        }
+
+        // Empty keys end at the root and can't be partitioned
+        let parallel = updates.len() >= PARALLEL_UPDATE_THRESHOLD && updates.iter().all(|(key, _)| !key.is_empty());
+        let full = match self.root.as_ref() {
+            Node::Full(full) if parallel => full.clone(),
+            _ => {
+                for (key, value) in updates {
+                    self.update(&key, &value).map_err(|e| e.for_key(&key))?;
+                }
+                return Ok(());
+            }
+        };
+
+        // Partition updates by the root child they go to
+        let mut partitions: Vec<Vec<(Vec<u8>, Vec<u8>, Vec<u8>)>> = vec![Vec::new(); 16];
+        let count = updates.len();
+        for (key, value) in updates {
+            let mut nibbles_key = key_to_nibbles(&key);
+            let nibble = nibbles_key.remove(0);
+            partitions[nibble as usize].push((key, nibbles_key, value));
+        }
+
+        let shards: Vec<_> = partitions
+            .into_iter()
+            .enumerate()
+            .filter(|(_, partition)| !partition.is_empty())
+            .map(|(nibble, partition)| (nibble, self.shard(full.get_child(nibble), nibble as u8), partition))
+            .collect();
+        let shards = shards
+            .into_par_iter()
+            .map(|(nibble, mut shard, partition)| {
+                let mut root = shard.root.clone();
+                let mut shard_dirty = false;
+                for (key, nibbles_key, value) in partition {
+                    let (dirty, new_root) = if value.is_empty() {
+                        shard.delete_internal(root, vec![nibble as u8], nibbles_key)
+                    } else {
+                        shard.insert_internal(root, vec![nibble as u8], nibbles_key, Arc::new(Node::Value(value)))
+                    }.map_err(|e| e.for_key(&key))?;
+                    shard_dirty |= dirty;
+                    root = new_root;
+                }
+                shard.root = root;
+                Ok((nibble, shard_dirty, shard))
+            })
+            .collect::<Result<Vec<_>, SecureTrieError>>()?;
+
+        self.unhashed += count;
+        self.uncommitted += count;
+        self.root = self.join_shards(&full, shards)?;
+        Ok(())
+    }
+
+    /// Applies a large batch of updates in key order, an empty value deleting
+    /// the key.
+    ///
+    /// Meant for batches covering a large part of a trie, like the storage of
+    /// a contract with thousands of slots changed in one block. The paths of
+    /// all keys are prefetched, then the trie is walked once in key order:
+    /// subtries that don't exist yet are built bottom-up from the sorted
+    /// leaves, like a stack trie, instead of one insert at a time, and the
+    /// subtries of a full root are updated in parallel. The resulting trie is
+    /// the same as after applying the updates one by one, so for repeated keys
+    /// the last update wins. On error the trie must be discarded.
+    pub fn update_sorted(&mut self, updates: Vec<(Vec<u8>, Vec<u8>)>) -> Result<(), SecureTrieError> {
+        if self.committed {
+            return Err(SecureTrieError::AlreadyCommitted);
+        }
+        self.prefetch_paths(&updates.iter().map(|(key, _)| key).collect::<Vec<_>>())?;
+
+        let count = updates.len();
+        let mut sorted: Vec<(Vec<u8>, Vec<u8>)> = updates
+            .into_iter()
+            .map(|(key, value)| (key_to_nibbles(&key), value))
+            .collect();
+        // The sort is stable, so the last update of a key is kept
+        sorted.sort_by(|a, b| a.0.cmp(&b.0));
+        let mut entries: Vec<(Vec<u8>, Vec<u8>)> = Vec::with_capacity(sorted.len());
+        for entry in sorted {
+            match entries.last_mut() {
+                Some(last) if last.0 == entry.0 => *last = entry,
+                _ => entries.push(entry),
+            }
+        }
+        self.unhashed += count;
+        self.uncommitted += count;
+
+        // Empty keys end at the root and can't be partitioned
+        let parallel = entries.len() >= PARALLEL_UPDATE_THRESHOLD && entries.iter().all(|(key, _)| key[0] < 16);
+        let full = match self.root.as_ref() {
+            Node::Full(full) if parallel => full.clone(),
+            _ => {
+                let (_, new_root) = self.bulk_internal(self.root.clone(), 0, &entries)?;
+                self.root = new_root;
+                return Ok(());
+            }
+        };
+
+        let mut shards = Vec::new();
+        let mut rest = entries.as_slice();
+        while let Some((key, _)) = rest.first() {
+            let nibble = key[0];
+            let (partition, tail) = rest.split_at(rest.partition_point(|(key, _)| key[0] == nibble));
+            shards.push((nibble as usize, self.shard(full.get_child(nibble as usize), nibble), partition));
+            rest = tail;
+        }
+        let shards = shards
+            .into_par_iter()
+            .map(|(nibble, mut shard, partition)| {
+                let (dirty, new_root) = shard.bulk_internal(shard.root.clone(), 1, partition)?;
+                shard.root = new_root;
+                Ok((nibble, dirty, shard))
+            })
+            .collect::<Result<Vec<_>, SecureTrieError>>()?;
+
+        self.root = self.join_shards(&full, shards)?;
+        Ok(())
+    }
+
+    /// Returns an iterator over the leaves of the trie in key order.
+    ///
+    /// Nodes are resolved through the difflayers of this trie before the
+    /// database, see `TrieIterator`.
+    pub fn iter(&self) -> TrieIterator<DB> {
+        TrieIterator::new(self.root.clone(), self.owner, self.database.clone(), self.difflayers.clone())
+    }
+
+    /// Returns an iterator over the stored nodes of the persisted root of the
+    /// trie in path order, see `NodeIterator`.
+    ///
+    /// Fails with `Unhashed` if the trie has changes that are not hashed yet.
+    pub fn node_iter(&self) -> Result<NodeIterator<DB>, SecureTrieError> {
+        let root = match self.root.as_ref() {
+            Node::Empty => None,
+            Node::Hash(hash) => Some(*hash),
+            node => match node.cache() {
+                (Some(hash), false) => Some(hash),
+                _ => return Err(SecureTrieError::Unhashed),
+            },
+        };
+        Ok(self.node_iter_at(self.owner, root))
+    }
+
+    /// Returns an iterator over the stored nodes of the trie of `owner` with
+    /// root hash `root`, `None` for an empty trie, resolved through the
+    /// database and difflayers of this trie.
+    pub(crate) fn node_iter_at(&self, owner: B256, root: Option<B256>) -> NodeIterator<DB> {
+        NodeIterator::new(root, owner, self.database.clone(), self.difflayers.clone())
+    }
+
+    /// Pre-resolves all nodes along the paths of `keys`, one trie level at a time.
+    ///
+    /// Keys are sorted so that every shared path prefix is resolved exactly once,
+    /// and resolved nodes are kept in the trie, so subsequent `get`/`update`
+    /// calls on these keys don't hit the database for them again. The nodes
+    /// of each level are read from the database in a single lookup.
+    /// Returns the number of nodes resolved.
+    pub fn prefetch_paths<K: AsRef<[u8]>>(&mut self, keys: &[K]) -> Result<usize, SecureTrieError> {
+        if self.committed {
+            return Err(SecureTrieError::AlreadyCommitted);
+        }
+
+        let mut nibbles_keys: Vec<Vec<u8>> = keys.iter().map(|key| key_to_nibbles(key.as_ref())).collect();
+        nibbles_keys.sort_unstable();
+        nibbles_keys.dedup();
+
+        // Every pass resolves the nodes loaded by the previous one and
+        // collects the unresolved nodes below them
+        let mut blobs = HashMap::new();
+        let mut total_resolved = 0;
+        loop {
+            let mut frontier = Vec::new();
+            let (new_root, resolved) = self.prefetch_internal(self.root.clone(), &nibbles_keys, 0, &mut blobs, &mut frontier)?;
+            if resolved > 0 {
+                self.root = new_root;
+                total_resolved += resolved;
+            }
+            if frontier.is_empty() {
+                return Ok(total_resolved);
+            }
+            let loaded = load_node_blobs(&self.database, self.difflayers.as_ref(), self.owner, &frontier)?;
+            blobs = frontier.into_iter().zip(loaded).collect();
+        }
+    }
 }
 
 /// Trie internal implementation
@@ -251,6 +670,274 @@ where
     DB: TrieDatabase + Clone + Send + Sync,
     DB::Error: std::fmt::Debug,
 {
+    /// Creates a trie rooted at the root child `nibble`, taking over the
+    /// tracer entries below it so its updates are traced as they would be
+    /// on this trie.
+    fn shard(&mut self, root: Arc<Node>, nibble: u8) -> Self {
+        Self {
+            root,
+            owner: self.owner,
+            committed: false,
+            unhashed: 0,
+            uncommitted: 0,
+            tracer: self.tracer.split_off_prefix(&[nibble]),
+            database: self.database.clone(),
+            difflayers: self.difflayers.clone(),
+            hooks: self.hooks.clone(),
+            verified_reads: self.verified_reads,
+            recorder: self.recorder.clone(),
+        }
+    }
+
+    /// Reassembles the full root from the shards updated in parallel,
+    /// taking back their tracer entries.
+    ///
+    /// The root is kept as is, hash included, when no shard changed.
+    fn join_shards(&mut self, full: &FullNode, shards: Vec<(usize, bool, Self)>) -> Result<Arc<Node>, SecureTrieError> {
+        let dirty = shards.iter().any(|(_, dirty, _)| *dirty);
+        let mut new_full = full.to_mutable_copy_with_cow();
+        new_full.flags = self.new_flag();
+        for (nibble, _, shard) in shards {
+            new_full.set_child(nibble, &shard.root);
+            self.tracer.absorb(shard.tracer);
+        }
+        if !dirty {
+            return Ok(self.root.clone());
+        }
+
+        if new_full.children.iter().all(|child| matches!(child.as_ref(), Node::Empty)) {
+            self.tracer.on_delete(b"");
+            Ok(Node::empty_root())
+        } else {
+            self.reduce_full_node(new_full, &[])
+        }
+    }
+
+    /// Applies the sorted, deduplicated `entries` to the subtrie `node` at
+    /// depth `pos`, see `update_sorted`.
+    ///
+    /// Keys are full nibble keys sharing the path of `node`, so the subtrie
+    /// of a full node gets a contiguous run of entries.
+    /// Returns: (dirty, new_node)
+    fn bulk_internal(
+        &mut self,
+        node: Arc<Node>,
+        pos: usize,
+        entries: &[(Vec<u8>, Vec<u8>)],
+    ) -> Result<(bool, Arc<Node>), SecureTrieError> {
+        let Some((first, _)) = entries.first() else {
+            return Ok((false, node));
+        };
+        let prefix = &first[..pos];
+
+        match node.as_ref() {
+            Node::Empty => {
+                // Deleting a missing key is a no-op
+                let inserts: Vec<_> = entries.iter().filter(|(_, value)| !value.is_empty()).collect();
+                if inserts.is_empty() {
+                    return Ok((false, node));
+                }
+                Ok((true, self.build_sorted(pos, &inserts)))
+            }
+
+            Node::Full(full) => {
+                let mut new_full = None;
+                let mut rest = entries;
+                while let Some((key, _)) = rest.first() {
+                    let nibble = key[pos];
+                    let (group, tail) = rest.split_at(rest.partition_point(|(key, _)| key[pos] == nibble));
+                    rest = tail;
+
+                    let (dirty, new_child) = self.bulk_internal(full.get_child(nibble as usize), pos + 1, group)?;
+                    if dirty {
+                        new_full
+                            .get_or_insert_with(|| full.to_mutable_copy_with_cow())
+                            .set_child(nibble as usize, &new_child);
+                    }
+                }
+
+                let Some(mut new_full) = new_full else {
+                    return Ok((false, node));
+                };
+                new_full.flags = self.new_flag();
+                if new_full.children.iter().all(|child| matches!(child.as_ref(), Node::Empty)) {
+                    self.tracer.on_delete(prefix);
+                    return Ok((true, Node::empty_root()));
+                }
+                Ok((true, self.reduce_full_node(new_full, prefix)?))
+            }
+
+            Node::Short(short) => {
+                let end = pos + short.key.len();
+                let below = |key: &[u8]| key.len() >= end && key[pos..end] == short.key[..];
+
+                // Inserting a key that leaves the short node splits it, the
+                // split node then takes all entries again
+                if let Some((key, value)) = entries.iter().find(|(key, value)| !value.is_empty() && !below(key)) {
+                    let (_, split) = self.update_at(node.clone(), pos, key, value)?;
+                    let (_, new_node) = self.bulk_internal(split, pos, entries)?;
+                    return Ok((true, new_node));
+                }
+
+                // Keys below the short node are contiguous, the others are
+                // missing deletes
+                let Some(start) = entries.iter().position(|(key, _)| below(key)) else {
+                    return Ok((false, node));
+                };
+                let len = entries[start..].iter().take_while(|(key, _)| below(key)).count();
+                let (dirty, new_child) = self.bulk_internal(short.val.clone(), end, &entries[start..start + len])?;
+                if !dirty {
+                    return Ok((false, node));
+                }
+
+                let new_short = match new_child.as_ref() {
+                    Node::Empty => {
+                        self.tracer.on_delete(prefix);
+                        return Ok((true, Node::empty_root()));
+                    }
+                    Node::Short(child_short) => {
+                        // Merge keys when the child is a short node too. `first`
+                        // may be a missing delete outside of the short node
+                        self.tracer.on_delete(&entries[start].0[..end]);
+                        let mut merged_key = short.key.clone();
+                        merged_key.extend(&child_short.key);
+                        ShortNode { key: merged_key, val: child_short.val.clone(), flags: self.new_flag() }
+                    }
+                    _ => ShortNode { key: short.key.clone(), val: new_child, flags: self.new_flag() },
+                };
+                Ok((true, Arc::new(Node::Short(Arc::new(new_short)))))
+            }
+
+            Node::Hash(hash) => {
+                let resolved_node = self.resolve_and_track(hash, prefix)?;
+                self.bulk_internal(resolved_node, pos, entries)
+            }
+
+            // A value is the end of a single key
+            Node::Value(_) => {
+                let (key, value) = &entries[0];
+                self.update_at(node.clone(), pos, key, value)
+            }
+        }
+    }
+
+    /// Builds the subtrie at depth `pos` holding the sorted `entries`,
+    /// bottom-up like a stack trie, tracing every node as inserted.
+    fn build_sorted(&mut self, pos: usize, entries: &[&(Vec<u8>, Vec<u8>)]) -> Arc<Node> {
+        let (first, value) = entries[0];
+        if entries.len() == 1 {
+            let value_node = Arc::new(Node::Value(value.clone()));
+            if first.len() == pos {
+                return value_node;
+            }
+            self.tracer.on_insert(&first[..pos]);
+            return Arc::new(Node::Short(Arc::new(ShortNode {
+                key: first[pos..].to_vec(),
+                val: value_node,
+                flags: self.new_flag(),
+            })));
+        }
+
+        // The first and last of the sorted keys share the prefix of all of them
+        let last = &entries[entries.len() - 1].0;
+        let end = pos + common_prefix_length(&first[pos..], &last[pos..]);
+        let mut branch = FullNode::new();
+        let mut rest = entries;
+        while let Some((key, _)) = rest.first() {
+            let nibble = key[end];
+            let (group, tail) = rest.split_at(rest.partition_point(|(key, _)| key[end] == nibble));
+            rest = tail;
+            let child = self.build_sorted(end + 1, group);
+            branch.set_child(nibble as usize, &child);
+        }
+        branch.flags = self.new_flag();
+        self.tracer.on_insert(&first[..end]);
+
+        let branch = Arc::new(Node::Full(Arc::new(branch)));
+        if end == pos {
+            return branch;
+        }
+        self.tracer.on_insert(&first[..pos]);
+        Arc::new(Node::Short(Arc::new(ShortNode {
+            key: first[pos..end].to_vec(),
+            val: branch,
+            flags: self.new_flag(),
+        })))
+    }
+
+    /// Applies a single update of the full nibble `key` to the subtrie
+    /// `node` at depth `pos`, an empty value deleting the key.
+    fn update_at(&mut self, node: Arc<Node>, pos: usize, key: &[u8], value: &[u8]) -> Result<(bool, Arc<Node>), SecureTrieError> {
+        if value.is_empty() {
+            self.delete_internal(node, key[..pos].to_vec(), key[pos..].to_vec())
+        } else {
+            self.insert_internal(node, key[..pos].to_vec(), key[pos..].to_vec(), Arc::new(Node::Value(value.to_vec())))
+        }
+    }
+
+    /// Collapses a full node at `prefix` left with a single child into a
+    /// short node, merging it with a short node child.
+    fn reduce_full_node(&mut self, full: FullNode, prefix: &[u8]) -> Result<Arc<Node>, SecureTrieError> {
+        let mut non_empty_pos = -1i32;
+        let mut non_empty_count = 0;
+
+        // Count non-empty children and find their position
+        for (i, child) in full.children.iter().enumerate() {
+            if !matches!(&**child, Node::Empty) {
+                non_empty_count += 1;
+                if non_empty_pos == -1 {
+                    non_empty_pos = i as i32;
+                } else {
+                    non_empty_pos = -2; // Multiple children
+                    break;
+                }
+            }
+        }
+
+        if non_empty_pos >= 0 && non_empty_count == 1 {
+            // Only one non-empty child - collapse to ShortNode
+            let pos_nibbles = vec![non_empty_pos as u8];
+
+            if non_empty_pos != 16 {
+                // Non-value child - try to merge with ShortNode
+                let mut child_prefix = prefix.to_vec();
+                child_prefix.extend(&pos_nibbles);
+
+                let resolved_child = self.resolve(
+                    full.get_child(non_empty_pos as usize),
+                    &child_prefix
+                )?;
+
+                if let Node::Short(child_short) = &*resolved_child {
+                    // Trace the delete operation
+                    self.tracer.on_delete(child_prefix);
+
+                    // Merge with child ShortNode
+                    let mut merged_key = vec![non_empty_pos as u8];
+                    merged_key.extend(&child_short.key);
+
+                    let new_short_arc = Arc::new(Node::Short(Arc::new(ShortNode {
+                        key: merged_key,
+                        val: child_short.val.clone(),
+                        flags: self.new_flag(),
+                    })));
+                    return Ok(new_short_arc);
+                }
+            }
+
+            // Create ShortNode with single child
+            let new_short_arc = Arc::new(Node::Short(Arc::new(ShortNode {
+                key: pos_nibbles,
+                val: full.get_child(non_empty_pos as usize),
+                flags: self.new_flag(),
+            })));
+            Ok(new_short_arc)
+        } else {
+            // Multiple children remain - keep as FullNode
+            Ok(Arc::new(Node::Full(Arc::new(full))))
+        }
+    }
+
     /// Gets a value from the trie by key
     /// Internal function to get a value from the trie
     /// Returns: (value, new_node, resolved)
@@ -329,6 +1016,94 @@ where
         }
     }
 
+    /// Internal function to resolve the paths of sorted `nibbles_keys` with
+    /// the node blobs in `blobs`, keyed by path. Hash nodes without a blob
+    /// are left in place and their paths added to `frontier`.
+    /// Returns: (new_node, resolved)
+    /// - new_node: The potentially updated node (for CoW)
+    /// - resolved: Number of nodes resolved from hash below this node
+    fn prefetch_internal(
+        &mut self, node: Arc<Node>,
+        nibbles_keys: &[Vec<u8>],
+        pos: usize,
+        blobs: &mut HashMap<Vec<u8>, (Vec<u8>, NodeReadSource)>,
+        frontier: &mut Vec<Vec<u8>>
+    ) -> Result<(Arc<Node>, usize), SecureTrieError> {
+        match &*node {
+            Node::Empty | Node::Value(_) => Ok((node, 0)),
+
+            // Short node - continue with the keys passing through it
+            Node::Short(short) => {
+                let matching: Vec<Vec<u8>> = nibbles_keys.iter()
+                    .filter(|key| key[pos..].starts_with(&short.key))
+                    .cloned()
+                    .collect();
+                if matching.is_empty() {
+                    return Ok((node, 0));
+                }
+
+                let (new_child, resolved) = self.prefetch_internal(
+                    short.val.clone(),
+                    &matching,
+                    pos + short.key.len(),
+                    blobs,
+                    frontier
+                )?;
+                if resolved > 0 {
+                    let mut new_short = short.to_mutable_copy_with_cow();
+                    new_short.set_value(&new_child);
+                    Ok((Arc::new(Node::Short(Arc::new(new_short))), resolved))
+                } else {
+                    Ok((node, 0))
+                }
+            }
+
+            // Full node - keys are sorted, so each child gets a contiguous range
+            Node::Full(full) => {
+                let mut new_full = None;
+                let mut total_resolved = 0;
+                let mut start = 0;
+                while start < nibbles_keys.len() {
+                    let nibble = nibbles_keys[start][pos] as usize;
+                    let end = start + nibbles_keys[start..].iter()
+                        .take_while(|key| key[pos] as usize == nibble)
+                        .count();
+
+                    let (new_child, resolved) = self.prefetch_internal(
+                        full.get_child(nibble),
+                        &nibbles_keys[start..end],
+                        pos + 1,
+                        blobs,
+                        frontier
+                    )?;
+                    if resolved > 0 {
+                        new_full.get_or_insert_with(|| full.to_mutable_copy_with_cow())
+                            .set_child(nibble, &new_child);
+                        total_resolved += resolved;
+                    }
+                    start = end;
+                }
+
+                match new_full {
+                    Some(new_full) => Ok((Arc::new(Node::Full(Arc::new(new_full))), total_resolved)),
+                    None => Ok((node, 0)),
+                }
+            }
+
+            // Hash node - resolve once for all keys below it, if loaded
+            Node::Hash(hash) => {
+                let prefix = &nibbles_keys[0][..pos];
+                let Some((node_blob, source)) = blobs.remove(prefix) else {
+                    frontier.push(prefix.to_vec());
+                    return Ok((node, 0));
+                };
+                let resolved_node = self.track_resolved(hash, prefix, node_blob, source);
+                let (new_node, resolved) = self.prefetch_internal(resolved_node, nibbles_keys, pos, blobs, frontier)?;
+                Ok((new_node, resolved + 1))
+            }
+        }
+    }
+
     /// Internal function to insert a value into the trie
     /// Returns: (dirty, new_node)
     /// - dirty: Whether the node was modified
@@ -597,67 +1372,7 @@ where
                 match &*new_child {
                     Node::Empty => {
                         // Child became empty - check if we can collapse the FullNode
-                        let mut non_empty_pos = -1i32;
-                        let mut non_empty_count = 0;
-
-                        // Count non-empty children and find their position
-                        for (i, child) in full_copy.children.iter().enumerate() {
-                            if !matches!(&**child, Node::Empty) {
-                                non_empty_count += 1;
-                                if non_empty_pos == -1 {
-                                    non_empty_pos = i as i32;
-                                } else {
-                                    non_empty_pos = -2; // Multiple children
-                                    break;
-                                }
-                            }
-                        }
-
-                        if non_empty_pos >= 0 && non_empty_count == 1 {
-                            // Only one non-empty child - collapse to ShortNode
-                            let pos_nibbles = vec![non_empty_pos as u8];
-
-                            if non_empty_pos != 16 {
-                                // Non-value child - try to merge with ShortNode
-                                let mut child_prefix = prefix.clone();
-                                child_prefix.extend(&pos_nibbles);
-
-                                let resolved_child = self.resolve(
-                                    full_copy.get_child(non_empty_pos as usize),
-                                    &child_prefix.to_vec()
-                                )?;
-
-                                if let Node::Short(child_short) = &*resolved_child {
-                                    // Trace the delete operation
-                                    let mut trace_path = prefix.clone();
-                                    trace_path.extend(&pos_nibbles);
-                                    self.tracer.on_delete(trace_path);
-
-                                    // Merge with child ShortNode
-                                    let mut merged_key = vec![non_empty_pos as u8];
-                                    merged_key.extend(&child_short.key);
-
-                                    let new_short_arc = Arc::new(Node::Short(Arc::new(ShortNode {
-                                        key: merged_key,
-                                        val: child_short.val.clone(),
-                                        flags: self.new_flag(),
-                                    })));
-                                    return Ok((true, new_short_arc));
-                                }
-                            }
-
-                            // Create ShortNode with single child
-                            let new_short_arc =Arc::new(Node::Short(Arc::new(ShortNode {
-                                key: pos_nibbles,
-                                val: full_copy.get_child(non_empty_pos as usize),
-                                flags: self.new_flag(),
-                            })));
-                            Ok((true, new_short_arc))
-                        } else {
-                            // Multiple children remain - keep as FullNode
-                            let new_full_arc = Arc::new(Node::Full(Arc::new(full_copy)));
-                            Ok((true, new_full_arc))
-                        }
+                        Ok((true, self.reduce_full_node(full_copy, &prefix)?))
                     }
                     _ => {
                         // Child is not empty - keep as FullNode
@@ -719,30 +1434,26 @@ where
 
     /// Resolves a hash and tracks it in the difflayer
     fn resolve_and_track(&mut self, hash: &B256, prefix: &[u8]) -> Result<Arc<Node>, SecureTrieError> {
-        let key = if self.owner == B256::ZERO {
-            account_trie_node_key(prefix)
-        } else {
-            storage_trie_node_key(self.owner.as_slice(), prefix)
-        };
-        
-        // 1. Check if the hash is in the difflayer
-        if let Some(difflayers) = &self.difflayers {
-            if let Some(node) = difflayers.get_trie_nodes(key.clone()) {
-                self.tracer.on_read(prefix, node.blob.clone().unwrap());              
-                return Ok(Node::must_decode_node(Some(*hash), &node.blob.clone().unwrap()));
-            }           
-        }
+        let (node_blob, source) = self.read_node_blob(prefix)?;
+        Ok(self.track_resolved(hash, prefix, node_blob, source))
+    }
 
-        // 2. Check if the hash is in the database
-        if let Some(node_blob) = self.database.get_trie_node(&key).map_err(|e| SecureTrieError::Database(format!("{:?}", e)))? {
-            self.tracer.on_read(prefix, node_blob.clone());
-            return Ok(Node::must_decode_node(Some(*hash), &node_blob));
+    /// Decodes the blob of a resolved hash node and records the read.
+    fn track_resolved(&mut self, hash: &B256, prefix: &[u8], node_blob: Bytes, source: NodeReadSource) -> Arc<Node> {
+        if let Some(hooks) = &self.hooks {
+            hooks.on_node_read(self.owner, prefix, source, node_blob.len());
+        }
+        if let Some(recorder) = &self.recorder {
+            recorder.record(&node_blob);
         }
+        self.tracer.on_read(prefix, node_blob.clone());
+        Node::must_decode_node(Some(*hash), &node_blob)
+    }
 
-        let owner_hex = format!("0x{:x}", self.owner);
-        let prefix_hex = prefix.iter().map(|b| format!("{:02x}", b)).collect::<String>();
-        let key_hex = key.iter().map(|b| format!("{:02x}", b)).collect::<String>();
-        return Err(SecureTrieError::Database(format!("missing trie node: owner: {}, prefix: 0x{}, key: 0x{}", owner_hex, prefix_hex, key_hex)));
+    /// Reads the blob of the node at `prefix`, from the difflayers first and
+    /// the database second.
+    fn read_node_blob(&self, prefix: &[u8]) -> Result<(Bytes, NodeReadSource), SecureTrieError> {
+        load_node_blob(&self.database, self.difflayers.as_ref(), self.owner, prefix, self.verified_reads)
     }
 
 }
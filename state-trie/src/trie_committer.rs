@@ -208,7 +208,7 @@ impl<'a> Committer<'a> {
         {
             let node_bytes = Node::node_to_bytes(node.clone());
             let mut nodeset = self.nodes.lock().unwrap();
-            nodeset.add_node(path.as_slice(), Arc::new(TrieNode::new(hash, Some(node_bytes))));
+            nodeset.add_node(path.as_slice(), Arc::new(TrieNode::new(hash, Some(node_bytes.into()))));
         }
 
         if self.collect_leaf {
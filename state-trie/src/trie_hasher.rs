@@ -110,28 +110,29 @@ impl Hasher {
         let mut cached = full.to_mutable_copy_with_cow();
 
         if self.parallel {
-            let child_results: Vec<(Arc<Node>, Arc<Node>)> = (0..16)
+            // Only dirty children are worth a task, clean ones return their cached hash
+            let dirty: Vec<usize> = (0..16)
+                .filter(|&i| !matches!(&*full.children[i], Node::Empty) && full.children[i].cache().0.is_none())
+                .collect();
+            let child_results: Vec<(usize, (Arc<Node>, Arc<Node>))> = dirty
                 .into_par_iter()
                 .map(|i| {
-                    match &*full.children[i] {
-                        Node::Empty => {
-                            (Node::empty_root(), Node::empty_root())
-                        }
-                        _ => {
-                            // Initialize a new hasher for each parallel task
-                            let hasher = Hasher::new(false);
-                            hasher.hash(full.children[i].clone(), false)
-                        }
-                    }
+                    // Initialize a new hasher for each parallel task
+                    let hasher = Hasher::new(false);
+                    (i, hasher.hash(full.children[i].clone(), false))
                 })
                 .collect();
 
             // Write results to collapsed and cached children
-            for i in 0..16 {
-                let (child_collapsed, child_cached) = child_results[i].clone();
+            for (i, (child_collapsed, child_cached)) in child_results {
                 collapsed.set_child(i, &*child_collapsed);
                 cached.set_child(i, &*child_cached);
             }
+            for i in 0..16 {
+                if let (Some(hash), _) = full.children[i].cache() {
+                    collapsed.set_child(i, &Node::Hash(hash));
+                }
+            }
         } else {
             for i in 0..16 {
                 match &*full.children[i] {
@@ -280,6 +281,23 @@ mod tests {
         println!("   - Test data: {} keys, {} deletions", test_data.len(), 2000);
     }
 
+    #[test]
+    fn test_parallel_hasher_partially_hashed_trie() {
+        let test_data = generate_test_data();
+        let operations: Vec<_> = test_data.iter().map(|(key, value)| (key.clone(), Some(value.clone()))).collect();
+        let mut trie = create_test_trie(&operations);
+        trie.hash();
+
+        // Only the root children touched by the updates are dirty
+        for (key, _) in test_data.iter().take(5) {
+            trie.update(key, b"updated").expect("Failed to update trie");
+        }
+        let (parallel_result, parallel_cached) = Hasher::new(true).hash(trie.root().clone(), true);
+        let (serial_result, serial_cached) = Hasher::new(false).hash(trie.root().clone(), true);
+        assert_eq!(parallel_result, serial_result);
+        assert_eq!(parallel_cached.cache(), serial_cached.cache());
+    }
+
     #[test]
     fn test_hasher_performance_comparison() {
         println!("🚀 Testing hasher performance comparison...");
@@ -0,0 +1,85 @@
+//! Ordered iteration over the leaves of a trie.
+
+use std::sync::Arc;
+
+use alloy_primitives::B256;
+use rust_eth_triedb_common::TrieDatabase;
+
+use crate::encoding::hex_to_keybytes;
+use crate::node::{DiffLayers, Node};
+use crate::secure_trie::SecureTrieError;
+use crate::trie::load_node_blob;
+
+/// Iterator over the leaves of a trie in key order, yielding `(key, value)`.
+///
+/// Walks the in-memory nodes of the trie it was created from, including
+/// uncommitted changes, and resolves hash nodes through the difflayers before
+/// the database, the same way point reads do. Iterating a trie at a root
+/// that is not flushed yet therefore sees the same state as point reads, and
+/// nodes deleted in a difflayer are never read from the database.
+///
+/// Keys are the raw trie keys, i.e. hashed keys for secure tries. Values are
+/// the stored, still encoded leaf values.
+#[derive(Debug)]
+pub struct TrieIterator<DB> {
+    database: DB,
+    difflayers: Option<DiffLayers>,
+    owner: B256,
+    /// Nodes left to visit with their nibble paths, the next one on top.
+    stack: Vec<(Arc<Node>, Vec<u8>)>,
+}
+
+impl<DB> TrieIterator<DB>
+where
+    DB: TrieDatabase,
+    DB::Error: std::fmt::Debug,
+{
+    /// Creates an iterator over the trie rooted at `root` of `owner`.
+    pub(crate) fn new(root: Arc<Node>, owner: B256, database: DB, difflayers: Option<DiffLayers>) -> Self {
+        Self { database, difflayers, owner, stack: vec![(root, Vec::new())] }
+    }
+}
+
+impl<DB> Iterator for TrieIterator<DB>
+where
+    DB: TrieDatabase,
+    DB::Error: std::fmt::Debug,
+{
+    type Item = Result<(Vec<u8>, Vec<u8>), SecureTrieError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((node, path)) = self.stack.pop() {
+            match node.as_ref() {
+                Node::Empty => {}
+                Node::Value(value) => return Some(Ok((hex_to_keybytes(&path), value.clone()))),
+                Node::Short(short) => {
+                    let mut child_path = path;
+                    child_path.extend_from_slice(&short.key);
+                    self.stack.push((short.val.clone(), child_path));
+                }
+                Node::Full(full) => {
+                    // Pushed in reverse so the value and the lowest nibble are visited first
+                    for nibble in (0..16u8).rev() {
+                        let mut child_path = path.clone();
+                        child_path.push(nibble);
+                        self.stack.push((full.children[nibble as usize].clone(), child_path));
+                    }
+                    self.stack.push((full.children[16].clone(), path));
+                }
+                Node::Hash(hash) => {
+                    let resolved = load_node_blob(&self.database, self.difflayers.as_ref(), self.owner, &path, false)
+                        .and_then(|(blob, _)| Node::decode_node(Some(*hash), &blob).map_err(SecureTrieError::from));
+                    match resolved {
+                        Ok(resolved) => self.stack.push((resolved, path)),
+                        Err(e) => {
+                            // Stop after reporting the error
+                            self.stack.clear();
+                            return Some(Err(e));
+                        }
+                    }
+                }
+            }
+        }
+        None
+    }
+}
@@ -0,0 +1,47 @@
+//! Recording of the trie nodes resolved by tries, e.g. for witnesses.
+
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::{keccak256, Bytes, B256};
+
+/// Records the encoded trie nodes resolved by the tries it is attached to.
+///
+/// Every node a trie reads from its difflayers or database is recorded:
+/// on lookups, updates, prefetches and proofs. Clones share the recorded
+/// nodes, so one recorder attached to the account trie and the storage
+/// tries of a block, and through them to the clones made for reads and
+/// parallel updates, collects every node the block read.
+#[derive(Debug, Clone, Default)]
+pub struct NodeReadRecorder {
+    /// Recorded nodes by hash
+    nodes: Arc<Mutex<BTreeMap<B256, Bytes>>>,
+}
+
+impl NodeReadRecorder {
+    /// Creates an empty recorder
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Records the encoded node `blob`
+    pub fn record(&self, blob: &Bytes) {
+        let hash = keccak256(blob);
+        self.nodes.lock().unwrap().entry(hash).or_insert_with(|| blob.clone());
+    }
+
+    /// Returns the number of distinct nodes recorded
+    pub fn len(&self) -> usize {
+        self.nodes.lock().unwrap().len()
+    }
+
+    /// Returns whether no node was recorded
+    pub fn is_empty(&self) -> bool {
+        self.nodes.lock().unwrap().is_empty()
+    }
+
+    /// Takes the recorded nodes ordered by hash, leaving the recorder empty
+    pub fn take(&self) -> Vec<Bytes> {
+        std::mem::take(&mut *self.nodes.lock().unwrap()).into_values().collect()
+    }
+}
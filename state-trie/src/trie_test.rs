@@ -2231,3 +2231,682 @@ fn test_trie_empty_root_with_10_keys() {
     println!("✅ Empty root verification passed!");
     println!("=== Empty Root Test Completed Successfully ===");
 }
+
+#[test]
+fn test_trie_prefetch_paths() {
+    use std::sync::Arc;
+    use rust_eth_triedb_common::{DiffLayer, TrieDatabase};
+    use crate::node::MergedNodeSet;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default())
+        .expect("Failed to create PathDB");
+
+    // Build and persist a trie
+    let mut state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(B256::ZERO))
+        .build_with_difflayer(None)
+        .expect("Failed to create trie");
+    let keys: Vec<B256> = (0..500u64).map(|i| keccak256(i.to_be_bytes())).collect();
+    for key in &keys {
+        state_trie.trie_mut().update(key.as_slice(), key.as_slice()).unwrap();
+    }
+    let (root, nodes) = state_trie.trie_mut().commit(false).unwrap();
+    let mut merged = MergedNodeSet::new();
+    merged.merge(nodes.unwrap()).unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*merged.to_diff_nodes()).clone(), Default::default()));
+    db.commit_difflayer(1, root, &Some(difflayer)).unwrap();
+
+    // Reopen and prefetch a subset of the keys
+    let mut state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(root))
+        .build_with_difflayer(None)
+        .expect("Failed to reopen trie");
+    let trie = state_trie.trie_mut();
+    let resolved = trie.prefetch_paths(&keys[..100]).unwrap();
+    assert!(resolved > 0);
+    assert_eq!(trie.prefetch_paths(&keys[..100]).unwrap(), 0, "Already resolved paths must not be resolved again");
+
+    for key in &keys {
+        assert_eq!(trie.get(key.as_slice()).unwrap(), Some(key.to_vec()));
+    }
+    assert_eq!(trie.hash(), root);
+}
+
+#[test]
+fn test_memory_db_dump_and_load() {
+    use std::sync::Arc;
+    use rust_eth_triedb_common::{DiffLayer, MemoryDB, TrieDatabase};
+    use crate::node::MergedNodeSet;
+
+    let db = MemoryDB::new();
+    assert_eq!(db.latest_persist_state().unwrap(), (0, EMPTY_ROOT_HASH));
+
+    // Build and persist a trie with a storage root
+    let mut state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(B256::ZERO))
+        .build_with_difflayer(None)
+        .expect("Failed to create trie");
+    let keys: Vec<B256> = (0..200u64).map(|i| keccak256(i.to_be_bytes())).collect();
+    for key in &keys {
+        state_trie.trie_mut().update(key.as_slice(), key.as_slice()).unwrap();
+    }
+    let (root, nodes) = state_trie.trie_mut().commit(false).unwrap();
+    let mut merged = MergedNodeSet::new();
+    merged.merge(nodes.unwrap()).unwrap();
+    let storage_roots = [(keys[0], keys[1])].into_iter().collect();
+    let difflayer = Arc::new(DiffLayer::new((*merged.to_diff_nodes()).clone(), storage_roots));
+    db.commit_difflayer(7, root, &Some(difflayer)).unwrap();
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let path = temp_dir.path().join("memory.db");
+    db.dump_to_file(&path).unwrap();
+    let loaded = MemoryDB::load_from_file(&path).unwrap();
+
+    assert_eq!(loaded.trie_node_count(), db.trie_node_count());
+    assert_eq!(loaded.latest_persist_state().unwrap(), (7, root));
+    assert_eq!(loaded.get_storage_root(keys[0]).unwrap(), Some(keys[1]));
+
+    let mut state_trie = SecureTrieBuilder::new(loaded)
+        .with_id(SecureTrieId::new(root))
+        .build_with_difflayer(None)
+        .expect("Failed to reopen trie");
+    for key in &keys {
+        assert_eq!(state_trie.trie_mut().get(key.as_slice()).unwrap(), Some(key.to_vec()));
+    }
+
+    // Truncated and foreign files are rejected
+    let bytes = std::fs::read(&path).unwrap();
+    std::fs::write(&path, &bytes[..bytes.len() - 1]).unwrap();
+    assert_eq!(MemoryDB::load_from_file(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+    std::fs::write(&path, b"not a dump").unwrap();
+    assert_eq!(MemoryDB::load_from_file(&path).unwrap_err().kind(), std::io::ErrorKind::InvalidData);
+}
+
+#[test]
+fn test_memory_db_fork() {
+    use rust_eth_triedb_common::{MemoryDB, TrieDatabase};
+
+    let parent = MemoryDB::new();
+    parent.insert_trie_node(b"A1", b"parent".to_vec()).unwrap();
+    parent.insert_trie_node(b"A2", b"parent".to_vec()).unwrap();
+
+    let left = parent.fork();
+    let right = parent.fork();
+    left.insert_trie_node(b"A1", b"left".to_vec()).unwrap();
+    right.remove_trie_node(b"A2");
+    parent.insert_trie_node(b"A3", b"parent".to_vec()).unwrap();
+
+    // Forks hold the state at fork time and don't see each other's writes
+    assert_eq!(left.get_trie_node(b"A1").unwrap().as_deref(), Some(b"left".as_slice()));
+    assert_eq!(left.get_trie_node(b"A2").unwrap().as_deref(), Some(b"parent".as_slice()));
+    assert_eq!(left.get_trie_node(b"A3").unwrap(), None);
+    assert_eq!(right.get_trie_node(b"A1").unwrap().as_deref(), Some(b"parent".as_slice()));
+    assert_eq!(right.get_trie_node(b"A2").unwrap(), None);
+    assert_eq!(parent.get_trie_node(b"A1").unwrap().as_deref(), Some(b"parent".as_slice()));
+    assert_eq!(parent.get_trie_node(b"A2").unwrap().as_deref(), Some(b"parent".as_slice()));
+    assert_eq!(parent.trie_node_count(), 3);
+}
+
+#[test]
+fn test_memory_db_sharded_range() {
+    use std::sync::Arc;
+    use rust_eth_triedb_common::{DiffLayer, MemoryDB, TrieDatabase};
+
+    let db = MemoryDB::new();
+    let paths: Vec<Vec<u8>> = (0..=255u8).map(|i| vec![0x01, i]).collect();
+    for path in &paths {
+        db.insert_trie_node(path, path.clone()).unwrap();
+    }
+
+    // Ranges merge the shards in path order
+    let nodes = db.iter_trie_node_range(&[0x01, 0x10], Some(&[0x01, 0x20]), 100).unwrap();
+    assert_eq!(nodes.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), paths[0x10..0x20].to_vec());
+    let nodes = db.iter_trie_node_range(&[0x01], None, 5).unwrap();
+    assert_eq!(nodes.iter().map(|(path, _)| path.clone()).collect::<Vec<_>>(), paths[..5].to_vec());
+
+    // Deleted ranges span all shards
+    let difflayer = DiffLayer::new(Default::default(), Default::default()).with_deleted_ranges(vec![(vec![0x01, 0x80], vec![0x02])]);
+    db.commit_difflayer(1, B256::repeat_byte(1), &Some(Arc::new(difflayer))).unwrap();
+    assert_eq!(db.trie_node_count(), 0x80);
+    assert_eq!(db.get_trie_node(&[0x01, 0x7f]).unwrap().as_deref(), Some([0x01, 0x7f].as_slice()));
+    assert_eq!(db.get_trie_node(&[0x01, 0x80]).unwrap(), None);
+}
+
+#[test]
+fn test_memory_db_savepoint() {
+    use std::sync::Arc;
+    use rust_eth_triedb_common::{DiffLayer, MemoryDB, TrieDatabase};
+
+    let db = MemoryDB::new();
+    db.insert_trie_node(b"A1", b"first".to_vec()).unwrap();
+    let savepoint = db.savepoint();
+    let usage = db.memory_usage();
+
+    db.insert_trie_node(b"A1", b"second".to_vec()).unwrap();
+    db.insert_trie_node(b"A2", b"second".to_vec()).unwrap();
+    let storage_roots = [(B256::repeat_byte(2), B256::repeat_byte(3))].into_iter().collect();
+    db.commit_difflayer(5, B256::repeat_byte(5), &Some(Arc::new(DiffLayer::new(Default::default(), storage_roots)))).unwrap();
+    let later = db.savepoint();
+    db.remove_trie_node(b"A1");
+
+    assert_eq!(db.rollback_to(later), 1);
+    assert_eq!(db.get_trie_node(b"A1").unwrap().as_deref(), Some(b"second".as_slice()));
+
+    assert_eq!(db.rollback_to(savepoint), 3);
+    assert_eq!(db.get_trie_node(b"A1").unwrap().as_deref(), Some(b"first".as_slice()));
+    assert_eq!(db.get_trie_node(b"A2").unwrap(), None);
+    assert_eq!(db.get_storage_root(B256::repeat_byte(2)).unwrap(), None);
+    assert_eq!(db.latest_persist_state().unwrap(), (0, EMPTY_ROOT_HASH));
+    assert_eq!(db.memory_usage(), usage);
+
+    // The savepoint stays usable after a rollback
+    db.insert_trie_node(b"A3", b"third".to_vec()).unwrap();
+    assert_eq!(db.rollback_to(savepoint), 1);
+    assert_eq!(db.trie_node_count(), 1);
+}
+
+#[test]
+fn test_memory_db_memory_limit() {
+    use std::sync::Arc;
+    use rust_eth_triedb_common::{DiffLayer, MemoryDB, MemoryLimitExceeded, TrieDatabase, TrieNode};
+
+    let db = MemoryDB::new().with_memory_limit(16);
+    db.insert_trie_node(b"A1", vec![0x11; 6]).unwrap();
+    assert_eq!(db.memory_usage(), 8);
+
+    // Overwrites are charged the difference, shrinking writes always pass
+    let err = db.insert_trie_node(b"A1", vec![0x22; 15]).unwrap_err();
+    assert_eq!(err, MemoryLimitExceeded { usage: 8, write: 17, limit: 16 });
+    db.insert_trie_node(b"A1", vec![0x22; 14]).unwrap();
+    assert_eq!(db.memory_usage(), 16);
+    db.insert_trie_node(b"A1", vec![0x33; 2]).unwrap();
+    assert_eq!(db.memory_usage(), 4);
+
+    // A rejected difflayer leaves the nodes and the persisted state untouched
+    let diff_nodes = [(b"A2".to_vec(), Arc::new(TrieNode::new(Some(B256::ZERO), Some(vec![0x44; 14].into()))))].into_iter().collect();
+    let difflayer = Arc::new(DiffLayer::new(diff_nodes, Default::default()));
+    assert!(db.commit_difflayer(1, B256::repeat_byte(1), &Some(difflayer)).is_err());
+    assert_eq!(db.get_trie_node(b"A2").unwrap(), None);
+    assert_eq!(db.latest_persist_state().unwrap().0, 0);
+    assert_eq!(db.memory_usage(), 4);
+
+    db.remove_trie_node(b"A1");
+    assert_eq!(db.memory_usage(), 0);
+    let fork = db.fork();
+    fork.insert_trie_node(b"A1", vec![0x55; 14]).unwrap();
+    assert!(fork.insert_trie_node(b"A2", vec![0x55; 1]).is_err());
+    assert_eq!(db.memory_usage(), 0);
+}
+
+#[test]
+fn test_validate_trie_nodes() {
+    use std::sync::Arc;
+    use rust_eth_triedb_common::{DiffLayer, TrieDatabase};
+    use crate::node::MergedNodeSet;
+    use crate::node_validator::{validate_blob, validate_node_entry, validating_bulk_loader, validate_trie_nodes, validate_trie_nodes_with_cancellation, BlobValidationError};
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default())
+        .expect("Failed to create PathDB");
+
+    let mut state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(B256::ZERO))
+        .build_with_difflayer(None)
+        .expect("Failed to create trie");
+    for i in 0..200u64 {
+        let key = keccak256(i.to_be_bytes());
+        state_trie.trie_mut().update(key.as_slice(), &[0xab; 40]).unwrap();
+    }
+    let (root, nodes) = state_trie.trie_mut().commit(false).unwrap();
+    let mut merged = MergedNodeSet::new();
+    merged.merge(nodes.unwrap()).unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*merged.to_diff_nodes()).clone(), Default::default()));
+    db.commit_difflayer(1, root, &Some(difflayer)).unwrap();
+
+    let report = validate_trie_nodes(&db, b"A").unwrap();
+    assert!(report.scanned > 1);
+    assert!(report.is_ok(), "unexpected issues: {:?}", report.issues);
+
+    // A cancelled scan reports partial progress and is not ok
+    let cancel = rust_eth_triedb_common::CancellationToken::new();
+    cancel.cancel();
+    let cancelled = validate_trie_nodes_with_cancellation(&db, b"A", &cancel).unwrap();
+    assert!(cancelled.cancelled);
+    assert_eq!(cancelled.scanned, 0);
+    assert!(!cancelled.is_ok());
+
+    // The root blob hashes to the state root and references its children
+    let root_blob = db.get_raw_trie_node(b"A").unwrap().unwrap();
+    assert_eq!(validate_blob(&[], &root_blob, Some(root)).unwrap().len(), 16);
+    assert!(matches!(
+        validate_blob(&[], &root_blob, Some(B256::ZERO)),
+        Err(BlobValidationError::HashMismatch { .. })
+    ));
+    assert!(matches!(validate_blob(&[], &[0xc0, 0x01], None), Err(BlobValidationError::Decode(_))));
+    assert_eq!(validate_blob(&[16], &root_blob, None), Err(BlobValidationError::InvalidPath(16)));
+    // A branch node can't sit at the full key length
+    assert!(matches!(
+        validate_blob(&[0; 64], &root_blob, None),
+        Err(BlobValidationError::InconsistentKeyLength { .. })
+    ));
+
+    // Replacing a child blob is reported under its parent
+    let (child_key, _) = db.iter_trie_nodes(b"A").unwrap().nth(1).unwrap().unwrap();
+    db.put_raw_trie_node(&child_key, &root_blob).unwrap();
+    let report = validate_trie_nodes(&db, b"A").unwrap();
+    assert!(report.issues.iter().any(|issue| matches!(issue.error, BlobValidationError::HashMismatch { .. })));
+
+    // A leaf whose key doesn't end at the full key length
+    let leaf = [0xc4, 0x82, 0x20, 0x01, 0x01];
+    assert!(matches!(
+        validate_blob(&[], &leaf, None),
+        Err(BlobValidationError::InconsistentKeyLength { .. })
+    ));
+    // Invalid flag nibble
+    let bad_flag = [0xc4, 0x82, 0x40, 0x01, 0x01];
+    assert_eq!(validate_blob(&[], &bad_flag, None), Err(BlobValidationError::InvalidCompactKey("invalid flag nibble")));
+
+    // Imports reject bad nodes before they are ingested
+    assert_eq!(validate_node_entry(b"A", &root_blob).unwrap().len(), 16);
+    assert_eq!(validate_node_entry(b"X", &root_blob), Err(BlobValidationError::InvalidNodeKey));
+    let import_dir = tempfile::TempDir::new().unwrap();
+    let import_db_dir = tempfile::TempDir::new().unwrap();
+    let import_db = PathDB::new(import_db_dir.path().to_str().unwrap(), PathProviderConfig::default())
+        .expect("Failed to create PathDB");
+    let mut loader = validating_bulk_loader(&import_db, import_dir.path()).unwrap();
+    loader.add(b"A", &root_blob).unwrap();
+    assert!(loader.add(&[b'A', 1], &bad_flag).is_err());
+    assert_eq!(loader.finish().unwrap(), 1);
+    assert_eq!(import_db.get_raw_trie_node(b"A").unwrap(), Some(root_blob.clone()));
+    assert!(import_db.get_raw_trie_node(&[b'A', 1]).unwrap().is_none());
+}
+
+#[test]
+fn test_trie_update_batch_matches_serial() {
+    use std::sync::Arc;
+    use rust_eth_triedb_common::{DiffLayer, TrieDatabase};
+    use crate::node::MergedNodeSet;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default())
+        .expect("Failed to create PathDB");
+
+    let key = |i: u64| keccak256(i.to_be_bytes()).to_vec();
+    let mut state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(B256::ZERO))
+        .build_with_difflayer(None)
+        .expect("Failed to create trie");
+    for i in 0..300u64 {
+        state_trie.trie_mut().update(&key(i), &[1; 40]).unwrap();
+    }
+    let (root, nodes) = state_trie.trie_mut().commit(false).unwrap();
+    let mut merged = MergedNodeSet::new();
+    merged.merge(nodes.unwrap()).unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*merged.to_diff_nodes()).clone(), Default::default()));
+    db.commit_difflayer(1, root, &Some(difflayer)).unwrap();
+
+    let apply = |updates: Vec<(Vec<u8>, Vec<u8>)>, batch: bool| {
+        let mut state_trie = SecureTrieBuilder::new(db.clone())
+            .with_id(SecureTrieId::new(root))
+            .build_with_difflayer(None)
+            .expect("Failed to reopen trie");
+        let trie = state_trie.trie_mut();
+        if batch {
+            trie.update_batch(updates).unwrap();
+        } else {
+            for (key, value) in updates {
+                trie.update(&key, &value).unwrap();
+            }
+        }
+        let (root, nodes) = trie.commit(false).unwrap();
+        (root, nodes.map(|nodes| nodes.signature_v1()))
+    };
+
+    // Deletes, updates, inserts and a key deleted and reinserted
+    let mut updates: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+    updates.extend((0..150).map(|i| (key(i), Vec::new())));
+    updates.extend((150..250).map(|i| (key(i), vec![2; 40])));
+    updates.extend((300..400).map(|i| (key(i), vec![3; 40])));
+    updates.push((key(7), vec![4; 40]));
+    assert_eq!(apply(updates.clone(), true), apply(updates, false));
+
+    // Deleting all but one key collapses the root
+    let updates: Vec<(Vec<u8>, Vec<u8>)> = (1..300).map(|i| (key(i), Vec::new())).collect();
+    assert_eq!(apply(updates.clone(), true), apply(updates, false));
+
+    // Deleting missing keys leaves the root clean, nothing to commit
+    let updates: Vec<(Vec<u8>, Vec<u8>)> = (1000..1100).map(|i| (key(i), Vec::new())).collect();
+    assert_eq!(apply(updates.clone(), true), (root, None));
+    assert_eq!(apply(updates.clone(), true), apply(updates, false));
+
+    // Deleting every key empties the trie
+    let updates: Vec<(Vec<u8>, Vec<u8>)> = (0..300).map(|i| (key(i), Vec::new())).collect();
+    let (batch_root, _) = apply(updates.clone(), true);
+    assert_eq!(batch_root, EMPTY_ROOT_HASH);
+    assert_eq!(apply(updates.clone(), true), apply(updates, false));
+}
+
+#[test]
+fn test_trie_update_sorted_matches_serial() {
+    use std::sync::Arc;
+    use rust_eth_triedb_common::{DiffLayer, TrieDatabase};
+    use crate::node::{DiffLayers, MergedNodeSet, NodeSet};
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default())
+        .expect("Failed to create PathDB");
+
+    let key = |i: u64| keccak256(i.to_be_bytes()).to_vec();
+    let to_difflayer = |nodes: Option<Arc<NodeSet>>| {
+        let mut merged = MergedNodeSet::new();
+        if let Some(nodes) = nodes {
+            merged.merge(nodes).unwrap();
+        }
+        Arc::new(DiffLayer::new((*merged.to_diff_nodes()).clone(), Default::default()))
+    };
+    let mut state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(B256::ZERO))
+        .build_with_difflayer(None)
+        .expect("Failed to create trie");
+    for i in 0..300u64 {
+        state_trie.trie_mut().update(&key(i), &[1; 40]).unwrap();
+    }
+    let (root, nodes) = state_trie.trie_mut().commit(false).unwrap();
+    db.commit_difflayer(1, root, &Some(to_difflayer(nodes))).unwrap();
+
+    // Returns the new root, the deleted paths and the leaves read back
+    // through the committed nodes only
+    let apply = |root: B256, updates: Vec<(Vec<u8>, Vec<u8>)>, sorted: bool| {
+        let mut state_trie = SecureTrieBuilder::new(db.clone())
+            .with_id(SecureTrieId::new(root))
+            .build_with_difflayer(None)
+            .expect("Failed to reopen trie");
+        let trie = state_trie.trie_mut();
+        if sorted {
+            trie.update_sorted(updates).unwrap();
+        } else {
+            for (key, value) in updates {
+                trie.update(&key, &value).unwrap();
+            }
+        }
+        let (new_root, nodes) = trie.commit(false).unwrap();
+        let mut deleted: Vec<String> = nodes
+            .iter()
+            .flat_map(|nodes| nodes.nodes.iter().filter(|(_, node)| node.is_deleted()).map(|(path, _)| path.clone()))
+            .collect();
+        deleted.sort();
+
+        let mut difflayers = DiffLayers::default();
+        difflayers.insert_difflayer(to_difflayer(nodes));
+        let mut reopened = SecureTrieBuilder::new(db.clone())
+            .with_id(SecureTrieId::new(new_root))
+            .build_with_difflayer(Some(&difflayers))
+            .expect("Failed to reopen updated trie");
+        let leaves: Vec<_> = reopened.trie_mut().iter().map(Result::unwrap).collect();
+        (new_root, deleted, leaves)
+    };
+    // Serial updates may also rewrite unchanged nodes, e.g. when a delete
+    // collapses a branch that a later insert splits again, so the written
+    // nodes are compared by what they read back
+    let check = |root: B256, updates: Vec<(Vec<u8>, Vec<u8>)>| {
+        assert_eq!(apply(root, updates.clone(), true), apply(root, updates, false));
+    };
+
+    // Deletes, updates, inserts, a key deleted and reinserted and one
+    // inserted and deleted again
+    let mut updates: Vec<(Vec<u8>, Vec<u8>)> = Vec::new();
+    updates.extend((0..150).map(|i| (key(i), Vec::new())));
+    updates.extend((150..250).map(|i| (key(i), vec![2; 40])));
+    updates.extend((300..2000).map(|i| (key(i), vec![3; 40])));
+    updates.push((key(7), vec![4; 40]));
+    updates.push((key(1000), Vec::new()));
+    check(root, updates);
+
+    // Small batches are applied without sharding the root
+    check(root, (290..310).map(|i| (key(i), vec![(i % 2) as u8; (i % 2) as usize * 40])).collect());
+
+    // A new trie is built from scratch, keys of any length included
+    let mut updates: Vec<(Vec<u8>, Vec<u8>)> = (0..500).map(|i| (key(i), vec![5; 40])).collect();
+    updates.extend([&b"do"[..], b"dog", b"doge", b"horse"].map(|key| (key.to_vec(), vec![6; 40])));
+    check(B256::ZERO, updates);
+
+    // Deleting all but one key collapses the root
+    check(root, (1..300).map(|i| (key(i), Vec::new())).collect());
+
+    // Deleting every key empties the trie
+    let updates: Vec<(Vec<u8>, Vec<u8>)> = (0..300).map(|i| (key(i), Vec::new())).collect();
+    assert_eq!(apply(root, updates.clone(), true).0, EMPTY_ROOT_HASH);
+    check(root, updates);
+
+    // A leading delete of a missing key doesn't shift the path of the child
+    // a short node merges with
+    let mut state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(B256::ZERO))
+        .build_with_difflayer(None)
+        .expect("Failed to create trie");
+    for key in [[0x12, 0x34, 0x00], [0x12, 0x34, 0x10], [0x12, 0x35, 0x00]] {
+        state_trie.trie_mut().update(&key, &[7; 40]).unwrap();
+    }
+    let (short_root, nodes) = state_trie.trie_mut().commit(false).unwrap();
+    db.commit_difflayer(2, short_root, &Some(to_difflayer(nodes))).unwrap();
+    let updates = vec![(vec![0x00, 0x00, 0x00], Vec::new()), (vec![0x12, 0x35, 0x00], Vec::new())];
+    assert!(!apply(short_root, updates.clone(), true).1.is_empty());
+    check(short_root, updates);
+}
+
+#[test]
+fn test_difflayer_lookup_metrics() {
+    use std::collections::HashMap;
+    use std::sync::Arc;
+    use rust_eth_triedb_common::{DiffLayer, DiffLayerLookup, DiffLayers, TrieNode};
+    use crate::difflayer_metrics::{lookup_trie_node, snapshot};
+
+    let layer = |key: &[u8]| {
+        let node = Arc::new(TrieNode::new(None, Some(key.to_vec().into())));
+        Arc::new(DiffLayer::new(HashMap::from([(key.to_vec(), node)]), HashMap::new()))
+    };
+    let mut difflayers = DiffLayers::default();
+    difflayers.diff_layers = vec![layer(b"new"), layer(b"mid"), layer(b"old")];
+
+    assert_eq!(difflayers.lookup_trie_node(b"new").1, DiffLayerLookup::NewestLayer);
+    let (node, lookup, layers_probed) = difflayers.lookup_trie_node(b"old");
+    assert_eq!(node.unwrap().blob.as_deref().map(|blob| &blob[..]), Some(&b"old"[..]));
+    assert_eq!((lookup, layers_probed), (DiffLayerLookup::OlderLayer, 3));
+    assert_eq!(difflayers.lookup_trie_node(b"none"), (None, DiffLayerLookup::Miss, 3));
+
+    // Totals are process-wide, so only compare deltas
+    let before = snapshot();
+    assert!(lookup_trie_node(&difflayers, b"new").is_some());
+    assert!(lookup_trie_node(&difflayers, b"mid").is_some());
+    assert!(lookup_trie_node(&difflayers, b"none").is_none());
+    assert!(lookup_trie_node(&DiffLayers::default(), b"new").is_none());
+    let after = snapshot();
+    assert!(after.newest_layer_hit_counter > before.newest_layer_hit_counter);
+    assert!(after.older_layer_hit_counter > before.older_layer_hit_counter);
+    assert!(after.miss_counter > before.miss_counter);
+    assert!(after.layers_probed_histogram.count >= before.layers_probed_histogram.count + 3);
+}
+
+#[test]
+fn test_node_iterator() {
+    use std::sync::Arc;
+    use rust_eth_triedb_common::{DiffLayer, TrieDatabase};
+    use crate::encoding::{account_trie_node_key, key_to_nibbles};
+    use crate::node::MergedNodeSet;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default())
+        .expect("Failed to create PathDB");
+
+    let mut state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(B256::ZERO))
+        .build_with_difflayer(None)
+        .expect("Failed to create trie");
+    assert_eq!(state_trie.node_iter().unwrap().count(), 0);
+    let mut keys = Vec::new();
+    for i in 0..200u64 {
+        let key = keccak256(i.to_be_bytes());
+        state_trie.trie_mut().update(key.as_slice(), &[0xab; 40]).unwrap();
+        keys.push(key);
+    }
+    keys.sort();
+    assert!(matches!(state_trie.node_iter(), Err(crate::SecureTrieError::Unhashed)));
+    let (root, nodes) = state_trie.trie_mut().commit(false).unwrap();
+    let mut merged = MergedNodeSet::new();
+    merged.merge(nodes.unwrap()).unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*merged.to_diff_nodes()).clone(), Default::default()));
+    db.commit_difflayer(1, root, &Some(difflayer)).unwrap();
+
+    let state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(root))
+        .build_with_difflayer(None)
+        .expect("Failed to open trie");
+    let all: Vec<_> = state_trie.node_iter().unwrap().map(Result::unwrap).collect();
+
+    // Every stored node exactly once, in path order, root first
+    assert_eq!(all.len(), db.iter_trie_nodes(b"A").unwrap().count());
+    assert_eq!((all[0].0.as_slice(), all[0].1), (&[][..], root));
+    assert!(all.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    for (path, hash, blob) in &all {
+        assert_eq!(keccak256(blob), *hash);
+        assert_eq!(db.get_raw_trie_node(&account_trie_node_key(path)).unwrap().as_deref(), Some(blob.as_ref()));
+    }
+
+    // Seeking resumes at the first node not before the key
+    let mut iter = state_trie.node_iter().unwrap();
+    iter.seek(keys[100].as_slice());
+    let mut target = key_to_nibbles(keys[100].as_slice());
+    target.pop();
+    let resumed: Vec<_> = iter.map(Result::unwrap).collect();
+    let expected: Vec<_> = all.iter().filter(|(path, _, _)| *path >= target).cloned().collect();
+    assert!(!resumed.is_empty());
+    assert_eq!(resumed, expected);
+
+    // Skipping the children of the root ends the walk
+    let mut iter = state_trie.node_iter().unwrap();
+    assert!(iter.next().is_some());
+    iter.skip_children();
+    assert!(iter.next().is_none());
+}
+
+#[test]
+fn test_leaf_iterator() {
+    use std::sync::Arc;
+    use alloy_primitives::U256;
+    use rust_eth_triedb_common::{DiffLayer, TrieDatabase};
+    use crate::node::MergedNodeSet;
+    use crate::state_trie::StateTrie;
+    use crate::StateAccount;
+
+    let temp_dir = tempfile::TempDir::new().unwrap();
+    let db = PathDB::new(temp_dir.path().to_str().unwrap(), PathProviderConfig::default())
+        .expect("Failed to create PathDB");
+    let commit = |trie: &mut StateTrie<PathDB>, block_number: u64| {
+        let (root, nodes) = trie.trie_mut().commit(false).unwrap();
+        let mut merged = MergedNodeSet::new();
+        merged.merge(nodes.unwrap()).unwrap();
+        let difflayer = Arc::new(DiffLayer::new((*merged.to_diff_nodes()).clone(), Default::default()));
+        db.commit_difflayer(block_number, root, &Some(difflayer)).unwrap();
+        root
+    };
+
+    // Storage of the first account
+    let owner = keccak256(0u64.to_be_bytes());
+    let mut storage_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(EMPTY_ROOT_HASH).with_owner(owner))
+        .build_with_difflayer(None)
+        .expect("Failed to create storage trie");
+    let mut slots: Vec<(B256, U256)> = (1..=50u64).map(|i| (keccak256(i.to_be_bytes()), U256::from(i))).collect();
+    storage_trie.update_storages_with_hash_state(slots.iter().map(|(slot, value)| (*slot, Some(*value))).collect()).unwrap();
+    let storage_root = commit(&mut storage_trie, 1);
+    slots.sort();
+
+    let mut accounts: Vec<(B256, StateAccount)> = (0..20u64)
+        .map(|i| (keccak256(i.to_be_bytes()), StateAccount::default().with_nonce(i)))
+        .collect();
+    accounts[0].1 = accounts[0].1.with_storage_root(storage_root);
+    let mut state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(B256::ZERO))
+        .build_with_difflayer(None)
+        .expect("Failed to create trie");
+    state_trie.update_accounts_with_hash_state(accounts.iter().map(|(address, account)| (*address, Some(*account))).collect()).unwrap();
+    let root = commit(&mut state_trie, 2);
+    accounts.sort_by_key(|(address, _)| *address);
+
+    let mut state_trie = SecureTrieBuilder::new(db.clone())
+        .with_id(SecureTrieId::new(root))
+        .build_with_difflayer(None)
+        .expect("Failed to open trie");
+    let all: Vec<_> = state_trie.iter_accounts().unwrap().map(Result::unwrap).collect();
+    assert_eq!(all, accounts);
+
+    // Pages start at the given hash, which doesn't have to be a key
+    let page: Vec<_> = state_trie.iter_accounts().unwrap().start_at(accounts[10].0).take(5).map(Result::unwrap).collect();
+    assert_eq!(page, accounts[10..15]);
+    let start = B256::repeat_byte(0x80);
+    let next = state_trie.iter_accounts().unwrap().start_at(start).next().unwrap().unwrap();
+    assert_eq!(Some(&next), accounts.iter().find(|(address, _)| *address >= start));
+
+    let storage: Vec<_> = state_trie.iter_storage(owner).unwrap().map(Result::unwrap).collect();
+    assert_eq!(storage, slots);
+    let page: Vec<_> = state_trie.iter_storage(owner).unwrap().start_at(slots[25].0).map(Result::unwrap).collect();
+    assert_eq!(page, slots[25..]);
+    assert_eq!(state_trie.iter_storage(accounts[1].0).unwrap().count(), 0);
+    assert_eq!(state_trie.iter_storage(B256::repeat_byte(0x42)).unwrap().count(), 0);
+}
+
+#[test]
+fn test_stack_trie_matches_trie() {
+    use crate::StackTrie;
+
+    let db_dir = tempfile::TempDir::new().unwrap();
+    let db = PathDB::new(db_dir.path().to_str().unwrap(), PathProviderConfig::default())
+        .expect("Failed to create PathDB");
+
+    // Hashed keys, and short keys whose nodes are embedded in their parents
+    let hashed: Vec<(Vec<u8>, Vec<u8>)> = (0..500u64)
+        .map(|i| (keccak256(i.to_be_bytes()).to_vec(), vec![0xab; 1 + (i % 70) as usize]))
+        .collect();
+    let short: Vec<(Vec<u8>, Vec<u8>)> = (0..300u16).map(|i| ((i * 7).to_be_bytes().to_vec(), vec![i as u8 | 1])).collect();
+
+    for (owner, mut entries) in [(B256::ZERO, hashed), (keccak256(b"owner"), short)] {
+        let mut trie = SecureTrieBuilder::new(db.clone())
+            .with_id(SecureTrieId::new(EMPTY_ROOT_HASH).with_owner(owner))
+            .build_with_difflayer(None)
+            .expect("Failed to create trie");
+        for (key, value) in &entries {
+            trie.trie_mut().update(key, value).unwrap();
+        }
+        let (root, nodes) = trie.trie_mut().commit(false).unwrap();
+
+        entries.sort();
+        let mut stack_trie = StackTrie::new(owner).with_node_set(true);
+        for (key, value) in &entries {
+            stack_trie.update(key, value).unwrap();
+        }
+        let (stack_root, stack_nodes) = stack_trie.commit();
+        assert_eq!(stack_root, root);
+        let (nodes, stack_nodes) = (nodes.unwrap(), stack_nodes.unwrap());
+        assert_eq!(stack_nodes.owner, owner);
+        assert_eq!(stack_nodes.nodes(), nodes.nodes());
+    }
+
+    // Without a node set only the root is computed
+    let mut stack_trie = StackTrie::new(B256::ZERO);
+    stack_trie.update(&[0x12], &[0x01]).unwrap();
+    let (root, nodes) = stack_trie.commit();
+    assert_ne!(root, EMPTY_ROOT_HASH);
+    assert!(nodes.is_none());
+    let (root, nodes) = StackTrie::new(B256::ZERO).with_node_set(true).commit();
+    assert_eq!(root, EMPTY_ROOT_HASH);
+    assert!(nodes.is_none());
+
+    let mut stack_trie = StackTrie::new(B256::ZERO);
+    stack_trie.update(&[0x12, 0x34], &[0x01]).unwrap();
+    assert!(matches!(stack_trie.update(&[0x12, 0x34], &[0x02]), Err(crate::SecureTrieError::UnorderedKey)));
+    assert!(matches!(stack_trie.update(&[0x12], &[0x02]), Err(crate::SecureTrieError::UnorderedKey)));
+    assert!(matches!(stack_trie.update(&[0x12, 0x34, 0x56], &[0x02]), Err(crate::SecureTrieError::UnorderedKey)));
+    assert!(matches!(stack_trie.update(&[0x56], &[]), Err(crate::SecureTrieError::EmptyValue)));
+    stack_trie.update(&[0x56], &[0x02]).unwrap();
+}
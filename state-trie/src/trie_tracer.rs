@@ -1,5 +1,7 @@
 use std::collections::{HashMap, HashSet};
 
+use alloy_primitives::Bytes;
+
 /// TrieTracer tracks inserted, deleted and accessed trie nodes by their path.
 ///
 /// Semantics mirror geth's tracer in `bsc/trie/tracer.go`:
@@ -13,7 +15,7 @@ use std::collections::{HashMap, HashSet};
 pub struct TrieTracer {
     inserts: HashSet<Vec<u8>>,      // set of node paths inserted
     deletes: HashSet<Vec<u8>>,      // set of node paths deleted
-    access_list: HashMap<Vec<u8>, Vec<u8>>, // path -> rlp-encoded blob as loaded from DB
+    access_list: HashMap<Vec<u8>, Bytes>, // path -> rlp-encoded blob as loaded from DB
 }
 
 impl TrieTracer {
@@ -23,8 +25,8 @@ impl TrieTracer {
     }
 
     /// Tracks a newly loaded trie node and caches its RLP-encoded blob.
-    /// The provided `val` is stored as-is without additional cloning.
-    pub fn on_read(&mut self, path: impl AsRef<[u8]>, val: Vec<u8>) {
+    /// The provided `val` shares its buffer with the loaded node.
+    pub fn on_read(&mut self, path: impl AsRef<[u8]>, val: Bytes) {
         self.access_list.insert(path.as_ref().to_vec(), val);
     }
 
@@ -68,6 +70,25 @@ impl TrieTracer {
         paths
     }
 
+    /// Removes and returns the entries of all paths starting with `prefix`.
+    pub fn split_off_prefix(&mut self, prefix: &[u8]) -> Self {
+        let (inserts, rest) = std::mem::take(&mut self.inserts).into_iter().partition(|path| path.starts_with(prefix));
+        self.inserts = rest;
+        let (deletes, rest) = std::mem::take(&mut self.deletes).into_iter().partition(|path| path.starts_with(prefix));
+        self.deletes = rest;
+        let (access_list, rest) = std::mem::take(&mut self.access_list).into_iter().partition(|(path, _)| path.starts_with(prefix));
+        self.access_list = rest;
+        Self { inserts, deletes, access_list }
+    }
+
+    /// Takes over the entries of `other`, which must track paths disjoint
+    /// from this tracer's, e.g. one returned by `split_off_prefix`.
+    pub fn absorb(&mut self, other: Self) {
+        self.inserts.extend(other.inserts);
+        self.deletes.extend(other.deletes);
+        self.access_list.extend(other.access_list);
+    }
+
     /// Returns a deep-copied snapshot of the tracer.
     pub fn copy(&self) -> Self {
         self.clone()
@@ -76,6 +97,6 @@ impl TrieTracer {
     /// Returns references to the internal tracking collections.
     pub fn inserts(&self) -> &HashSet<Vec<u8>> { &self.inserts }
     pub fn deletes(&self) -> &HashSet<Vec<u8>> { &self.deletes }
-    pub fn access_list(&self) -> &HashMap<Vec<u8>, Vec<u8>> { &self.access_list }
+    pub fn access_list(&self) -> &HashMap<Vec<u8>, Bytes> { &self.access_list }
 }
 
@@ -9,6 +9,21 @@ pub mod triedb_manager;
 pub mod triedb_metrics;
 pub mod triedb_disk;
 pub mod triedb_reth;
+pub mod triedb_audit;
+pub mod triedb_flat;
+pub mod triedb_override;
+pub mod triedb_read_set;
+pub mod triedb_iter;
+pub mod triedb_replay;
+pub mod triedb_code_index;
+pub mod triedb_slot_count;
+pub mod triedb_root_audit;
+pub mod triedb_trie_pool;
+pub mod triedb_vectors;
+pub mod triedb_snapshot_verify;
+pub mod triedb_witness;
+#[cfg(feature = "debug-http")]
+pub mod triedb_debug_http;
 
 #[cfg(test)]
 mod triedb_test;
@@ -16,5 +31,17 @@ mod triedb_test;
 // Re-export main types
 pub use triedb::TrieDB;
 pub use triedb::TrieDBError;
+pub use triedb::{CommitConfig, DEFAULT_BULK_STORAGE_THRESHOLD};
 pub use triedb_reth::TrieDBHashedPostState;
-pub use triedb_manager::{init_global_triedb_manager, get_global_triedb, disable_triedb};
+pub use triedb_trie_pool::DEFAULT_STORAGE_TRIE_POOL_SIZE;
+pub use triedb_flat::{FlatStorageReader, FlatReadMode, StorageSnapshotReader};
+pub use triedb_override::{AccountOverride, StateOverrides};
+pub use triedb_replay::{ReplayReport, RootMismatch};
+pub use triedb_vectors::{TestVector, VectorBlock, VectorMismatch, VectorReport};
+pub use triedb_snapshot_verify::{SnapshotMismatch, SnapshotVerifyReport};
+pub use triedb_witness::{BytecodeReader, ExecutionWitness};
+pub use triedb_metrics::{MetricsSnapshot, TrieDBMetricsSnapshot};
+pub use triedb_read_set::{ReadSet, ReadResults, AccountRead, SlotRead};
+#[cfg(feature = "debug-http")]
+pub use triedb_debug_http::{DebugHandler, DebugHttpServer, DebugResponse};
+pub use triedb_manager::{init_global_triedb_manager, get_global_triedb, disable_triedb, global_cancellation_token, cancel_global_operations, shutdown_global_triedb};
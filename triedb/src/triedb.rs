@@ -1,17 +1,24 @@
 //! Trie database implementation.
 
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
 
 use alloy_primitives::B256;
 use alloy_trie::EMPTY_ROOT_HASH;
 
-use rust_eth_triedb_common::TrieDatabase;
+use rust_eth_triedb_common::{SlotCountChange, TrieDatabase, TrieDBErrorSource, TrieHooks, TriePhase};
 use rust_eth_triedb_state_trie::node::DiffLayers;
 use rust_eth_triedb_state_trie::state_trie::StateTrie;
 use rust_eth_triedb_state_trie::account::StateAccount;
 use rust_eth_triedb_state_trie::{SecureTrieId, SecureTrieBuilder};
+use rust_eth_triedb_pathdb::PathProviderError;
 
+use crate::triedb_disk::PersistStateTracker;
+use crate::triedb_flat::{FlatReadMode, FlatStorageReader};
 use crate::triedb_metrics::TrieDBMetrics;
+use crate::triedb_trie_pool::DEFAULT_STORAGE_TRIE_POOL_SIZE;
+use crate::triedb_witness::{BytecodeReader, ExecutionWitness, WitnessRecorder};
 
 /// Error type for trie database operations
 #[derive(Debug, thiserror::Error)]
@@ -27,6 +34,76 @@ pub enum TrieDBError {
     
     #[error("State trie error: {0}")]
     StateTrie(#[from] rust_eth_triedb_state_trie::secure_trie::SecureTrieError),
+
+    #[error("{context}: {source}")]
+    Provider {
+        context: String,
+        #[source]
+        source: Box<dyn std::error::Error + Send + Sync>,
+        /// Whether the operation may succeed when retried
+        retryable: bool,
+        /// Whether the database holds corrupted data
+        corruption: bool,
+    },
+
+    #[error("State root divergence: committed {committed:#x}, account {hashed_address:#x}: {reason}")]
+    RootDivergence { committed: B256, hashed_address: B256, reason: String },
+}
+
+impl TrieDBError {
+    /// Wrap a database error with the operation that failed, keeping the
+    /// typed error for callers to inspect.
+    pub(crate) fn provider(context: impl Into<String>, source: impl TrieDBErrorSource) -> Self {
+        Self::Provider {
+            context: context.into(),
+            retryable: source.is_retryable(),
+            corruption: source.is_corruption(),
+            source: Box::new(source),
+        }
+    }
+
+    /// The PathDB error behind this error, if any.
+    pub fn provider_error(&self) -> Option<&PathProviderError> {
+        match self {
+            Self::Provider { source, .. } => source.downcast_ref(),
+            _ => None,
+        }
+    }
+
+    /// Whether the operation may succeed when retried, see `TrieDBErrorSource::is_retryable`.
+    pub fn is_retryable(&self) -> bool {
+        matches!(self, Self::Provider { retryable: true, .. })
+    }
+
+    /// Whether the database holds corrupted data, see `TrieDBErrorSource::is_corruption`.
+    pub fn is_corruption(&self) -> bool {
+        matches!(self, Self::Provider { corruption: true, .. })
+    }
+}
+
+/// Controls which tries collect leaves into their node sets on commit.
+///
+/// Collected leaves are needed to update snapshots or build witnesses, but
+/// cost memory when unused.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CommitConfig {
+    /// Collect leaves of the account trie, defaults to `true`
+    pub collect_account_leaves: bool,
+    /// Collect leaves of storage tries, defaults to `false`
+    pub collect_storage_leaves: bool,
+}
+
+/// Default number of changed slots from which an account's storage trie is
+/// updated through the bulk path, see `TrieDB::with_bulk_storage_threshold`.
+pub const DEFAULT_BULK_STORAGE_THRESHOLD: usize = 1024;
+
+impl Default for CommitConfig {
+    fn default() -> Self {
+        Self {
+            collect_account_leaves: true,
+            collect_storage_leaves: false,
+        }
+    }
 }
 
 /// Ethereum-compatible trie database implementation for managing state and storage tries.
@@ -151,6 +228,13 @@ where
     /// This map tracks these changes so that the account's `storage_root` field
     /// can be updated in the account trie during commit operations.
     pub(crate) updated_storage_roots: HashMap<B256, B256>,
+
+    /// Accounts whose whole storage trie is dropped by the pending commit.
+    ///
+    /// Self-destructed and rebuilt accounts are recorded here, so the
+    /// committed node set wipes their storage key range on flush instead of
+    /// leaving the old nodes behind.
+    pub(crate) wiped_storages: HashSet<B256>,
     
     /// Uncommitted diff layers for tracking state changes.
     ///
@@ -163,6 +247,59 @@ where
     ///
     /// This database provides the persistent storage backend for all trie operations.
     pub(crate) path_db: DB,
+
+    /// Cached latest persisted state, shared across clones.
+    ///
+    /// Invalidated on every flush and used to notify subscribers of
+    /// persisted state changes.
+    pub(crate) persist_state: Arc<PersistStateTracker>,
+
+    /// Optional flat storage reader used as a fast path for storage reads.
+    pub(crate) flat_storage: Option<Arc<dyn FlatStorageReader>>,
+
+    /// How storage reads use `flat_storage`.
+    pub(crate) flat_read_mode: FlatReadMode,
+
+    /// Optional instrumentation hooks for external profilers.
+    pub(crate) hooks: Option<Arc<dyn TrieHooks>>,
+
+    /// Leaf collection policy applied by `commit`.
+    pub(crate) commit_config: CommitConfig,
+
+    /// Number of changed slots from which a storage trie is updated in bulk.
+    pub(crate) bulk_storage_threshold: usize,
+
+    /// Whether the tries updated by this instance resolve nodes through
+    /// verified database reads.
+    pub(crate) verified_reads: bool,
+
+    /// Whether committed diff layers carry code hashes for the code hash index.
+    pub(crate) code_hash_index: bool,
+
+    /// Whether committed diff layers carry storage slot count changes.
+    pub(crate) slot_count_tracking: bool,
+
+    /// Slot count changes of the last `batch_update_and_commit`, taken by
+    /// the diff layer built from it.
+    pub(crate) slot_count_changes: HashMap<B256, SlotCountChange>,
+
+    /// Whether every commit re-computes the state root through `HashBuilder`.
+    pub(crate) root_audit: bool,
+
+    /// Recorder of the reads for execution witnesses, set while recording.
+    pub(crate) witness_recorder: Option<WitnessRecorder>,
+
+    /// Optional bytecode source filling the `codes` of execution witnesses.
+    pub(crate) bytecode_reader: Option<Arc<dyn BytecodeReader>>,
+
+    /// Execution witness of the last `batch_update_and_commit`, if recorded.
+    pub(crate) last_execution_witness: Option<ExecutionWitness>,
+
+    /// Released storage tries kept for reuse by later blocks.
+    pub(crate) storage_trie_pool: Vec<StateTrie<DB>>,
+
+    /// Maximum number of storage tries kept in `storage_trie_pool`.
+    pub(crate) storage_trie_pool_size: usize,
     
     /// Metrics for monitoring trie database operations and performance.
     pub(crate) metrics: TrieDBMetrics,
@@ -172,7 +309,7 @@ where
 impl<DB> TrieDB<DB>
 where
     DB: TrieDatabase + Clone + Send + Sync,
-    DB::Error: std::fmt::Debug,
+    DB::Error: TrieDBErrorSource,
 {
     /// Creates a new trie database
     pub fn new(path_db: DB) -> Self {
@@ -182,8 +319,25 @@ where
             storage_tries: HashMap::new(),
             accounts_with_storage_trie: HashMap::new(),
             updated_storage_roots: HashMap::new(),
+            wiped_storages: HashSet::new(),
             difflayer: None,
             path_db: path_db.clone(),
+            persist_state: Arc::new(PersistStateTracker::new()),
+            flat_storage: None,
+            flat_read_mode: FlatReadMode::Disabled,
+            hooks: None,
+            commit_config: CommitConfig::default(),
+            bulk_storage_threshold: DEFAULT_BULK_STORAGE_THRESHOLD,
+            verified_reads: false,
+            code_hash_index: false,
+            slot_count_tracking: false,
+            slot_count_changes: HashMap::new(),
+            root_audit: false,
+            witness_recorder: None,
+            bytecode_reader: None,
+            last_execution_witness: None,
+            storage_trie_pool: Vec::new(),
+            storage_trie_pool_size: DEFAULT_STORAGE_TRIE_POOL_SIZE,
             metrics: TrieDBMetrics::new_with_labels(&[("instance", "default")]),
         }
     }
@@ -194,16 +348,84 @@ where
         self.account_trie = Some(
             SecureTrieBuilder::new(self.path_db.clone())
             .with_id(id)
+            .with_hooks(self.hooks.clone())
+            .with_verified_reads(self.verified_reads)
+            .with_recorder(self.node_recorder())
             .build_with_difflayer(difflayer)?
         );
         self.root_hash = root_hash;
         self.updated_storage_roots.clear();
+        self.wiped_storages.clear();
         self.difflayer = difflayer.map(|d| d.clone());
-        self.storage_tries.clear();
+        self.release_storage_tries();
         self.accounts_with_storage_trie.clear();
         Ok(())
     }
 
+    /// Sets the leaf collection policy used by `commit`.
+    pub fn with_commit_config(mut self, commit_config: CommitConfig) -> Self {
+        self.commit_config = commit_config;
+        self
+    }
+
+    /// Returns the leaf collection policy used by `commit`.
+    pub fn commit_config(&self) -> CommitConfig {
+        self.commit_config
+    }
+
+    /// Sets the number of changed slots from which an account's storage trie
+    /// is updated through the bulk path instead of slot by slot.
+    ///
+    /// The bulk path sorts the slots and builds new subtries directly, which
+    /// pays off for contracts with thousands of changed slots in a block but
+    /// costs a sort for small updates. `usize::MAX` disables it.
+    pub fn with_bulk_storage_threshold(mut self, threshold: usize) -> Self {
+        self.bulk_storage_threshold = threshold;
+        self
+    }
+
+    /// Returns the number of changed slots from which storage tries are
+    /// updated in bulk.
+    pub fn bulk_storage_threshold(&self) -> usize {
+        self.bulk_storage_threshold
+    }
+
+    /// Enables or disables verified reads for the consensus-critical paths.
+    ///
+    /// When enabled, the account and storage tries updated by
+    /// `commit_hashed_post_state` and its helpers resolve nodes with
+    /// `TrieDatabase::get_trie_node_verified`, so a corrupted node fails the
+    /// block instead of producing a wrong state root. Prefetching the account
+    /// paths and reads served to RPC, e.g. `read_set`, keep the fast reads.
+    pub fn with_verified_reads(mut self, enabled: bool) -> Self {
+        self.verified_reads = enabled;
+        // Pooled storage tries keep the read mode they were built with
+        self.storage_trie_pool.clear();
+        self
+    }
+
+    /// Returns whether consensus-critical paths use verified reads.
+    pub fn verified_reads(&self) -> bool {
+        self.verified_reads
+    }
+
+    /// Registers instrumentation hooks for phase timings and node reads.
+    ///
+    /// Takes effect for tries built by the next `state_at` call. Pooled
+    /// storage tries still report to the previous hooks, so they are dropped.
+    pub fn set_hooks(&mut self, hooks: Arc<dyn TrieHooks>) {
+        self.hooks = Some(hooks);
+        self.storage_trie_pool.clear();
+    }
+
+    /// Starts timing `phase` and reports its start to the hooks.
+    pub(crate) fn hook_phase(&self, phase: TriePhase) -> PhaseGuard {
+        if let Some(hooks) = &self.hooks {
+            hooks.on_phase_start(phase);
+        }
+        PhaseGuard { hooks: self.hooks.clone(), phase, start: Instant::now() }
+    }
+
     /// Gets a mutable reference to the database
     pub fn get_mut_path_db_ref(&mut self) -> &mut DB {
         &mut self.path_db
@@ -213,13 +435,47 @@ where
     pub fn clean(&mut self) {
         self.root_hash = EMPTY_ROOT_HASH;
         self.account_trie = None;
-        self.storage_tries.clear();
+        self.release_storage_tries();
         self.accounts_with_storage_trie.clear();
         self.updated_storage_roots.clear();
+        self.wiped_storages.clear();
         self.difflayer = None;
     }
 }
 
+/// A phase started by `TrieDB::hook_phase`.
+///
+/// The end of the phase is reported to the hooks by `finish`, or on drop if
+/// the phase is cut short, e.g. by an error returned with `?`, so every
+/// reported start has a matching end.
+pub(crate) struct PhaseGuard {
+    hooks: Option<Arc<dyn TrieHooks>>,
+    phase: TriePhase,
+    start: Instant,
+}
+
+impl PhaseGuard {
+    /// Ends the phase, returning its duration.
+    pub(crate) fn finish(mut self) -> Duration {
+        let elapsed = self.start.elapsed();
+        self.end(elapsed);
+        elapsed
+    }
+
+    fn end(&mut self, elapsed: Duration) {
+        if let Some(hooks) = self.hooks.take() {
+            hooks.on_phase_end(self.phase, elapsed);
+        }
+    }
+}
+
+impl Drop for PhaseGuard {
+    fn drop(&mut self) {
+        let elapsed = self.start.elapsed();
+        self.end(elapsed);
+    }
+}
+
 impl<DB> Clone for TrieDB<DB>
 where
     DB: TrieDatabase + Clone + Send + Sync,
@@ -232,8 +488,26 @@ where
             storage_tries: HashMap::new(),
             accounts_with_storage_trie: HashMap::new(),
             updated_storage_roots: HashMap::new(),
+            wiped_storages: HashSet::new(),
             difflayer: None,
             path_db: self.path_db.clone(),
+            persist_state: self.persist_state.clone(),
+            flat_storage: self.flat_storage.clone(),
+            flat_read_mode: self.flat_read_mode,
+            hooks: self.hooks.clone(),
+            commit_config: self.commit_config,
+            bulk_storage_threshold: self.bulk_storage_threshold,
+            verified_reads: self.verified_reads,
+            code_hash_index: self.code_hash_index,
+            slot_count_tracking: self.slot_count_tracking,
+            slot_count_changes: HashMap::new(),
+            root_audit: self.root_audit,
+            // Clones record their own witnesses
+            witness_recorder: self.witness_recorder.as_ref().map(|_| WitnessRecorder::default()),
+            bytecode_reader: self.bytecode_reader.clone(),
+            last_execution_witness: None,
+            storage_trie_pool: Vec::new(),
+            storage_trie_pool_size: self.storage_trie_pool_size,
             metrics: self.metrics.clone()
         }
     }
@@ -251,8 +525,20 @@ where
             .field("storage_tries_count", &self.storage_tries.len())
             .field("accounts_with_storage_trie_count", &self.accounts_with_storage_trie.len())
             .field("updated_storage_roots_count", &self.updated_storage_roots.len())
+            .field("wiped_storages_count", &self.wiped_storages.len())
             .field("difflayer", &self.difflayer.as_ref().map(|_| "<Difflayer>"))
             .field("db", &format!("<{}>", std::any::type_name::<DB>()))
+            .field("flat_read_mode", &self.flat_read_mode)
+            .field("hooks", &self.hooks)
+            .field("commit_config", &self.commit_config)
+            .field("bulk_storage_threshold", &self.bulk_storage_threshold)
+            .field("verified_reads", &self.verified_reads)
+            .field("code_hash_index", &self.code_hash_index)
+            .field("slot_count_tracking", &self.slot_count_tracking)
+            .field("root_audit", &self.root_audit)
+            .field("execution_witness", &self.witness_recorder.is_some())
+            .field("bytecode_reader", &self.bytecode_reader)
+            .field("storage_trie_pool_count", &self.storage_trie_pool.len())
             .finish()
     }
 }
@@ -0,0 +1,58 @@
+//! Commit audit log for TrieDB.
+
+use std::sync::Arc;
+
+use alloy_primitives::B256;
+use rust_eth_triedb_common::{AuditRecord, TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::node::{DiffLayer, DiffLayers, MergedNodeSet};
+
+use crate::triedb::{TrieDB, TrieDBError};
+use crate::triedb_reth::TrieDBHashedPostState;
+
+/// Audited commits
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Same as [`commit_hashed_post_state`](Self::commit_hashed_post_state), and
+    /// additionally attaches an `AuditRecord` for `block_number` to the returned
+    /// difflayer, appended to the database's audit log in the same write that
+    /// persists the layer.
+    pub fn commit_hashed_post_state_with_audit(
+        &mut self,
+        block_number: u64,
+        root_hash: B256,
+        difflayer: Option<&DiffLayers>,
+        hashed_post_state: &TrieDBHashedPostState) ->
+        Result<(B256, Option<Arc<DiffLayer>>), TrieDBError> {
+        self.commit_hashed_post_state_inner(root_hash, difflayer, hashed_post_state, Some(block_number))
+    }
+}
+
+/// Summarizes a committed node set into an audit record.
+pub(crate) fn build_audit_record(
+    block_number: u64,
+    root_before: B256,
+    root_after: B256,
+    node_set: &MergedNodeSet,
+    storage_roots: usize) -> AuditRecord {
+
+    let (mut updates, mut deletes, mut leaves) = (0, 0, 0);
+    for set in node_set.sets.values() {
+        updates += set.updates as u64;
+        deletes += set.deletes as u64;
+        leaves += set.leaf_count() as u64;
+    }
+
+    AuditRecord {
+        block_number,
+        root_before,
+        root_after,
+        updates,
+        deletes,
+        leaves,
+        storage_roots: storage_roots as u64,
+        signature: node_set.signature_v2(),
+    }
+}
@@ -3,11 +3,10 @@
 use std::sync::Arc;
 use std::collections::HashMap;
 use rayon::prelude::*;
-use std::time::Instant;
 
 use alloy_primitives::{keccak256, Address, B256};
 use alloy_trie::EMPTY_ROOT_HASH;
-use rust_eth_triedb_common::TrieDatabase;
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource, TriePhase};
 use rust_eth_triedb_state_trie::node::{MergedNodeSet, NodeSet};
 use rust_eth_triedb_state_trie::state_trie::StateTrie;
 use rust_eth_triedb_state_trie::account::StateAccount;
@@ -55,10 +54,12 @@ use crate::triedb::{TrieDB, TrieDBError};
 impl<DB> TrieDB<DB>
 where
     DB: TrieDatabase + Clone + Send + Sync,
-    DB::Error: std::fmt::Debug,
+    DB::Error: TrieDBErrorSource,
 {
     pub fn get_account(&mut self, address: Address) -> Result<Option<StateAccount>, TrieDBError> {
-        Ok(self.account_trie.as_mut().unwrap().get_account(address)?)
+        let account = self.account_trie.as_mut().unwrap().get_account(address)?;
+        self.record_account_read(account.as_ref());
+        Ok(account)
     }
 
     pub fn update_account(&mut self, address: Address, account: &StateAccount) -> Result<(), TrieDBError> {
@@ -70,7 +71,9 @@ where
     }
 
     pub fn get_storage(&mut self, address: Address, key: &[u8]) -> Result<Option<Vec<u8>>, TrieDBError> {
-        let mut storage_trie = self.get_storage_trie(address)?;
+        let Some(mut storage_trie) = self.get_readable_storage_trie(keccak256(address.as_slice()))? else {
+            return Ok(None);
+        };
         Ok(storage_trie.get_storage(address, key)?)
     }
 
@@ -91,10 +94,10 @@ where
 impl<DB> TrieDB<DB>
 where
     DB: TrieDatabase + Clone + Send + Sync,
-    DB::Error: std::fmt::Debug,
+    DB::Error: TrieDBErrorSource,
 {
     pub fn calculate_hash(&mut self) -> Result<B256, TrieDBError> {
-        let hash_start = Instant::now();
+        let phase = self.hook_phase(TriePhase::Hash);
 
         let (storage_hashes, storage_tries): (HashMap<B256, B256>, HashMap<B256, StateTrie<DB>>) = self.storage_tries
         .par_iter()
@@ -120,24 +123,30 @@ where
         self.storage_tries.extend(storage_tries);
 
         let hash = self.account_trie.as_mut().unwrap().hash();
-        self.metrics.record_hash_duration(hash_start.elapsed().as_secs_f64());
+        let hash_elapsed = phase.finish();
+        self.metrics.record_hash_duration(hash_elapsed.as_secs_f64());
         Ok(hash)
     }
 
-    pub fn commit(&mut self, _collect_leaf: bool) -> Result<(B256, Arc<MergedNodeSet>), TrieDBError> {
+    /// Hashes and commits all tries into a merged node set.
+    ///
+    /// Leaf collection follows the configured `CommitConfig`.
+    pub fn commit(&mut self) -> Result<(B256, Arc<MergedNodeSet>), TrieDBError> {
         let root_hash = self.calculate_hash()?;
 
-        let commit_start = Instant::now();
+        let phase = self.hook_phase(TriePhase::Commit);
         let mut merged_node_set = MergedNodeSet::new();
+        merged_node_set.wiped_owners = self.wiped_storages.clone();
 
         // Start both tasks in parallel using rayon
+        let commit_config = self.commit_config;
         let mut account_trie_clone = self.account_trie.as_mut().unwrap().clone();
         let (account_commit_result, storage_commit_results): (Result<(B256, Option<Arc<NodeSet>>), _>, Vec<(B256, Option<Arc<NodeSet>>)>) = rayon::join(
-            || account_trie_clone.commit(true),
+            || account_trie_clone.commit(commit_config.collect_account_leaves),
             || self.storage_tries
                 .par_iter()
                 .map(|(hashed_address, trie)| {
-                    let (_, node_set) = trie.clone().commit(false).unwrap();
+                    let (_, node_set) = trie.clone().commit(commit_config.collect_storage_leaves).unwrap();
                     (*hashed_address, node_set)
                 })
                 .collect()
@@ -158,7 +167,8 @@ where
             }
         }
 
-        self.metrics.record_commit_duration(commit_start.elapsed().as_secs_f64());
+        let commit_elapsed = phase.finish();
+        self.metrics.record_commit_duration(commit_elapsed.as_secs_f64());
         Ok((root_hash, Arc::new(merged_node_set)))
     }
 }
@@ -167,7 +177,7 @@ where
 impl<DB> TrieDB<DB>
 where
     DB: TrieDatabase + Clone + Send + Sync,
-    DB::Error: std::fmt::Debug,
+    DB::Error: TrieDBErrorSource,
 {
     /// Gets the storage trie for an account
     fn get_storage_trie(&mut self, address: Address) -> Result<StateTrie<DB>, TrieDBError> {
@@ -182,10 +192,36 @@ where
         }
 
         let storage_root = self.get_storage_root_with_hash_state(hashed_address)?;
+        self.build_storage_trie(hashed_address, storage_root)
+    }
+
+    /// Gets the storage trie for an hash address to read from, `None` if the
+    /// account's storage root is empty.
+    ///
+    /// An empty storage trie has nothing to read or prove, so no trie is
+    /// built or cached and neither the diff layers nor the database are probed.
+    pub(crate) fn get_readable_storage_trie(&mut self, hashed_address: B256) -> Result<Option<StateTrie<DB>>, TrieDBError> {
+        if let Some(storage_trie) = self.storage_tries.get(&hashed_address) {
+            return Ok(Some(storage_trie.clone()));
+        }
+
+        let storage_root = self.get_storage_root_with_hash_state(hashed_address)?;
+        if storage_root == EMPTY_ROOT_HASH {
+            self.metrics.increment_empty_storage_root_counter();
+            return Ok(None);
+        }
+        self.build_storage_trie(hashed_address, storage_root).map(Some)
+    }
+
+    /// Builds and caches the storage trie of `hashed_address` at `storage_root`.
+    pub(crate) fn build_storage_trie(&mut self, hashed_address: B256, storage_root: B256) -> Result<StateTrie<DB>, TrieDBError> {
         let id = SecureTrieId::new(storage_root)
             .with_owner(hashed_address);
         let storage_trie = SecureTrieBuilder::new(self.path_db.clone())
             .with_id(id)
+            .with_hooks(self.hooks.clone())
+            .with_verified_reads(self.verified_reads)
+            .with_recorder(self.node_recorder())
             .build_with_difflayer(self.difflayer.as_ref())?;
 
         self.storage_tries.insert(hashed_address, storage_trie.clone());
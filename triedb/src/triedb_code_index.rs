@@ -0,0 +1,57 @@
+//! Code hash index maintenance and queries for TrieDB.
+
+use std::collections::HashMap;
+
+use alloy_primitives::B256;
+use alloy_trie::KECCAK_EMPTY;
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::account::StateAccount;
+
+use crate::triedb::{TrieDB, TrieDBError};
+
+/// Code hash index configuration and queries
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Enables or disables maintaining the code hash index.
+    ///
+    /// When enabled, diff layers returned by `commit_hashed_post_state` carry
+    /// the code hashes of the changed accounts, which the database indexes
+    /// once the layer is flushed. Accounts not changed since the index was
+    /// enabled are not indexed.
+    pub fn with_code_hash_index(mut self, enabled: bool) -> Self {
+        self.code_hash_index = enabled;
+        self
+    }
+
+    /// Returns whether the code hash index is maintained.
+    pub fn code_hash_index(&self) -> bool {
+        self.code_hash_index
+    }
+
+    /// Returns the hashed addresses of all persisted accounts deployed with
+    /// `code_hash`, e.g. every contract sharing a bytecode.
+    ///
+    /// Only flushed state is covered, see `with_code_hash_index`.
+    pub fn contracts_with_code_hash(&self, code_hash: B256) -> Result<Vec<B256>, TrieDBError> {
+        self.path_db.get_addresses_by_code_hash(code_hash)
+            .map_err(|e| TrieDBError::provider(format!("Failed to read code hash index for {:#x}", code_hash), e))
+    }
+
+    /// Collects the code hashes of the accounts changed by a block for its
+    /// diff layer, empty if the index is disabled.
+    pub(crate) fn code_hash_changes(&self, states: &HashMap<B256, Option<StateAccount>>) -> HashMap<B256, Option<B256>> {
+        if !self.code_hash_index {
+            return HashMap::new();
+        }
+        states
+            .iter()
+            .map(|(hashed_address, account)| {
+                let code_hash = account.as_ref().map(|account| account.code_hash).filter(|code_hash| *code_hash != KECCAK_EMPTY);
+                (*hashed_address, code_hash)
+            })
+            .collect()
+    }
+}
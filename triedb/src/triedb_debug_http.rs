@@ -0,0 +1,261 @@
+//! Read-only HTTP debug endpoint for operators of a live node.
+//!
+//! [`DebugHandler`] maps a request method and path to a JSON response and
+//! doesn't depend on any HTTP framework, so it can be mounted into an
+//! embedder's router. [`DebugHttpServer`] serves it on its own for nodes
+//! without one.
+//!
+//! | Path              | Response                                              |
+//! |-------------------|-------------------------------------------------------|
+//! | `/state`          | Latest persisted block number and state root          |
+//! | `/cache`          | PathDB cache sizes, hits and misses                   |
+//! | `/stats`          | TrieDB update, hash, commit and flush statistics      |
+//! | `/node/0x<path>`  | Blob of the trie node stored at the hex-encoded path  |
+
+use std::fmt::Write as _;
+use std::io::{self, Read, Write};
+use std::net::{SocketAddr, TcpListener, TcpStream};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::Duration;
+
+use alloy_primitives::hex;
+use rust_eth_triedb_common::{HistogramSummary, TrieDatabase, TrieDBErrorSource};
+use tracing::{debug, warn};
+
+use crate::triedb_metrics;
+
+/// Interval at which the server checks for new connections and shutdown.
+const ACCEPT_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Maximum size of a request head read by the server.
+const MAX_REQUEST_HEAD: usize = 8 * 1024;
+
+/// Response of the [`DebugHandler`].
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DebugResponse {
+    /// HTTP status code
+    pub status: u16,
+    /// JSON body
+    pub body: String,
+}
+
+impl DebugResponse {
+    fn ok(body: String) -> Self {
+        Self { status: 200, body }
+    }
+
+    fn error(status: u16, message: &str) -> Self {
+        Self { status, body: format!("{{\"error\":\"{}\"}}", message.replace('\\', "\\\\").replace('"', "\\\"")) }
+    }
+}
+
+/// Router-agnostic handler of the read-only debug requests.
+///
+/// Holds a clone of the database the TrieDB is backed by, reads never
+/// change state and don't need the TrieDB itself.
+#[derive(Debug, Clone)]
+pub struct DebugHandler<DB> {
+    db: DB,
+}
+
+impl<DB> DebugHandler<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Create a handler answering from `db`.
+    pub fn new(db: DB) -> Self {
+        Self { db }
+    }
+
+    /// Handle a request for `path`, which may carry a query string. Only
+    /// `GET` is allowed.
+    pub fn handle(&self, method: &str, path: &str) -> DebugResponse {
+        if method != "GET" {
+            return DebugResponse::error(405, "Method not allowed");
+        }
+        let path = path.split_once('?').map_or(path, |(path, _)| path);
+        match path.trim_end_matches('/') {
+            "/state" => self.state(),
+            "/cache" => cache(),
+            "/stats" => stats(),
+            path => match path.strip_prefix("/node/") {
+                Some(node_path) => self.node(node_path),
+                None => DebugResponse::error(404, "Not found"),
+            },
+        }
+    }
+
+    fn state(&self) -> DebugResponse {
+        match self.db.latest_persist_state() {
+            Ok((block_number, state_root)) => {
+                DebugResponse::ok(format!("{{\"block_number\":{},\"state_root\":\"{:#x}\"}}", block_number, state_root))
+            }
+            Err(e) => DebugResponse::error(500, &format!("{:?}", e)),
+        }
+    }
+
+    fn node(&self, node_path: &str) -> DebugResponse {
+        let Ok(path) = hex::decode(node_path) else {
+            return DebugResponse::error(400, "Invalid hex path");
+        };
+        match self.db.get_trie_node(&path) {
+            Ok(Some(blob)) => DebugResponse::ok(format!(
+                "{{\"path\":\"{}\",\"blob\":\"{}\"}}", hex::encode_prefixed(&path), hex::encode_prefixed(&blob)
+            )),
+            Ok(None) => DebugResponse::error(404, "Trie node not found"),
+            Err(e) => DebugResponse::error(500, &format!("{:?}", e)),
+        }
+    }
+}
+
+fn cache() -> DebugResponse {
+    let pathdb = triedb_metrics::snapshot().pathdb;
+    DebugResponse::ok(format!(
+        "{{\"trie_node_cache_entries\":{},\"storage_root_cache_entries\":{},\"trie_node_cache_hits\":{},\"trie_node_cache_misses\":{},\"storage_root_cache_hits\":{},\"storage_root_cache_misses\":{},\"negative_cache_hits\":{}}}",
+        pathdb.trie_node_cache_entries,
+        pathdb.storage_root_cache_entries,
+        pathdb.trie_node_cache_hits,
+        pathdb.trie_node_cache_misses,
+        pathdb.storage_root_cache_hits,
+        pathdb.storage_root_cache_misses,
+        pathdb.negative_cache_hits,
+    ))
+}
+
+fn stats() -> DebugResponse {
+    let triedb = triedb_metrics::snapshot().triedb;
+    let mut body = String::from("{");
+    for (name, histogram) in [
+        ("update", &triedb.update_histogram),
+        ("hash", &triedb.hash_histogram),
+        ("commit", &triedb.commit_histogram),
+        ("flush", &triedb.flush_histogram),
+    ] {
+        write_histogram(&mut body, name, histogram);
+        body.push(',');
+    }
+    let _ = write!(
+        body,
+        "\"bulk_storage_updates\":{},\"storage_trie_reuses\":{},\"root_audit_mismatches\":{}}}",
+        triedb.bulk_storage_update_counter, triedb.storage_trie_reuse_counter, triedb.root_audit_mismatch_counter,
+    );
+    DebugResponse::ok(body)
+}
+
+fn write_histogram(body: &mut String, name: &str, histogram: &HistogramSummary) {
+    let _ = write!(body, "\"{}\":{{\"count\":{},\"sum_seconds\":{}}}", name, histogram.count, histogram.sum);
+}
+
+/// Handle of a thread serving a [`DebugHandler`] over plain HTTP/1.1.
+///
+/// Requests are served one at a time and every connection is closed after
+/// its response, which is plenty for operators poking a node with `curl`.
+/// Bind it to a loopback or otherwise private address. The thread stops
+/// when the handle is dropped.
+#[derive(Debug)]
+pub struct DebugHttpServer {
+    local_addr: SocketAddr,
+    stop: Arc<AtomicBool>,
+    handle: Option<JoinHandle<()>>,
+}
+
+impl DebugHttpServer {
+    /// Spawn a server answering requests on `addr` with `handler`.
+    pub fn spawn<DB>(handler: DebugHandler<DB>, addr: impl Into<SocketAddr>) -> io::Result<Self>
+    where
+        DB: TrieDatabase + Clone + Send + Sync + 'static,
+        DB::Error: TrieDBErrorSource,
+    {
+        let listener = TcpListener::bind(addr.into())?;
+        listener.set_nonblocking(true)?;
+        let local_addr = listener.local_addr()?;
+
+        let stop = Arc::new(AtomicBool::new(false));
+        let server_stop = stop.clone();
+        let handle = thread::Builder::new()
+            .name("triedb-debug-http".to_string())
+            .spawn(move || {
+                while !server_stop.load(Ordering::Acquire) {
+                    match listener.accept() {
+                        Ok((stream, _)) => {
+                            if let Err(e) = serve_connection(&handler, stream) {
+                                debug!(target: "triedb::debug_http", "Debug request failed: {}", e);
+                            }
+                        }
+                        Err(e) if e.kind() == io::ErrorKind::WouldBlock => thread::park_timeout(ACCEPT_INTERVAL),
+                        Err(e) => warn!(target: "triedb::debug_http", "Debug server accept error: {}", e),
+                    }
+                }
+            })?;
+
+        Ok(Self { local_addr, stop, handle: Some(handle) })
+    }
+
+    /// Address the server listens on, e.g. to find the port after binding port 0.
+    pub fn local_addr(&self) -> SocketAddr {
+        self.local_addr
+    }
+
+    /// Stop the server and wait for the in-flight request to finish.
+    pub fn stop(mut self) {
+        self.shutdown();
+    }
+
+    fn shutdown(&mut self) {
+        if let Some(handle) = self.handle.take() {
+            self.stop.store(true, Ordering::Release);
+            handle.thread().unpark();
+            let _ = handle.join();
+        }
+    }
+}
+
+impl Drop for DebugHttpServer {
+    fn drop(&mut self) {
+        self.shutdown();
+    }
+}
+
+/// Read a request head from `stream` and write the handler's response.
+fn serve_connection<DB>(handler: &DebugHandler<DB>, mut stream: TcpStream) -> io::Result<()>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    stream.set_nonblocking(false)?;
+    stream.set_read_timeout(Some(Duration::from_secs(5)))?;
+
+    let mut head = Vec::new();
+    let mut buf = [0u8; 1024];
+    while !head.windows(4).any(|window| window == b"\r\n\r\n") && head.len() < MAX_REQUEST_HEAD {
+        let read = stream.read(&mut buf)?;
+        if read == 0 {
+            break;
+        }
+        head.extend_from_slice(&buf[..read]);
+    }
+
+    let head = String::from_utf8_lossy(&head);
+    let mut request_line = head.lines().next().unwrap_or_default().split_whitespace();
+    let response = match (request_line.next(), request_line.next()) {
+        (Some(method), Some(path)) => handler.handle(method, path),
+        _ => DebugResponse::error(400, "Malformed request"),
+    };
+
+    let reason = match response.status {
+        200 => "OK",
+        400 => "Bad Request",
+        404 => "Not Found",
+        405 => "Method Not Allowed",
+        _ => "Internal Server Error",
+    };
+    write!(
+        stream,
+        "HTTP/1.1 {} {}\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+        response.status, reason, response.body.len(), response.body
+    )?;
+    stream.flush()
+}
@@ -1,19 +1,65 @@
 //! PathDB operations for TrieDB.
 
-use std::sync::Arc;
-use std::time::Instant;
+use std::sync::{Arc, RwLock};
+use tokio::sync::watch;
 use tracing::debug;
 
 use alloy_primitives::B256;
-use rust_eth_triedb_common::{TrieDatabase, DiffLayer};
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource, DiffLayer, TriePhase};
 
 use crate::triedb::{TrieDB, TrieDBError};
 
+/// In-memory view of the latest persisted `(block_number, state_root)`.
+///
+/// The tracker is shared by all clones of a `TrieDB`, so hot-path readers
+/// only hit the database once after each persisted state change. The cached
+/// value is tagged with the database's `persist_generation` observed before
+/// reading it and only served while the generation is unchanged, so writes
+/// through other handles of the database, or racing a read, are never
+/// masked. Every successful flush publishes the new state on a watch
+/// channel for subscribers.
+#[derive(Debug)]
+pub(crate) struct PersistStateTracker {
+    /// Cached latest persisted state and the generation it was read at.
+    cached: RwLock<Option<(u64, (u64, B256))>>,
+    /// Sender side of the persisted state change notification channel.
+    sender: watch::Sender<Option<(u64, B256)>>,
+}
+
+impl PersistStateTracker {
+    pub(crate) fn new() -> Self {
+        let (sender, _) = watch::channel(None);
+        Self {
+            cached: RwLock::new(None),
+            sender,
+        }
+    }
+
+    fn get(&self, generation: u64) -> Option<(u64, B256)> {
+        match *self.cached.read().unwrap() {
+            Some((cached_generation, state)) if cached_generation == generation => Some(state),
+            _ => None,
+        }
+    }
+
+    fn set(&self, generation: u64, state: (u64, B256)) {
+        *self.cached.write().unwrap() = Some((generation, state));
+    }
+
+    fn notify(&self, state: (u64, B256)) {
+        self.sender.send_replace(Some(state));
+    }
+
+    fn subscribe(&self) -> watch::Receiver<Option<(u64, B256)>> {
+        self.sender.subscribe()
+    }
+}
+
 /// Flush trienodes to PathDB, after commit
 impl<DB> TrieDB<DB>
 where
     DB: TrieDatabase + Clone + Send + Sync,
-    DB::Error: std::fmt::Debug,
+    DB::Error: TrieDBErrorSource,
 {
     pub fn get_storage_root(&mut self, hased_address: B256) -> Result<Option<B256>, TrieDBError> {
         if let Some(dl) = self.difflayer.as_ref() {
@@ -23,12 +69,15 @@ where
             }
         }
 
-        // TODO: query storage root from flat kv, instead of trie
-        // if let Some(root) = self.path_db.get_storage_root(hased_address)
-        //     .map_err(|e| TrieDBError::Database(format!("Failed to get storage root: {:?}", e)))? {
-        //     self.metrics.increment_get_storage_root_from_flat_counter();
-        //     return Ok(Some(root));
-        // }
+        // The flat storage roots follow the persisted state, which the diff
+        // layers build on; an older state has to go through its trie
+        if self.difflayer.is_some() || self.root_hash == self.latest_persist_state()?.1 {
+            if let Some(root) = self.path_db.get_storage_root(hased_address)
+                .map_err(|e| TrieDBError::provider("Failed to get storage root", e))? {
+                self.metrics.increment_get_storage_root_from_flat_counter();
+                return Ok(Some(root));
+            }
+        }
         if let Some(account) = self.get_account_with_hash_state(hased_address)? {
             self.metrics.increment_get_storage_root_from_trie_counter();
             return Ok(Some(account.storage_root));
@@ -37,24 +86,62 @@ where
         Ok(None)
     }
 
+    /// Returns the latest persisted `(block_number, state_root)`.
+    ///
+    /// The value is served from memory while the database's
+    /// `persist_generation` is unchanged, and always read from the database
+    /// if it doesn't report one.
     pub fn latest_persist_state(&self) -> Result<(u64, B256), TrieDBError> {
-        self.path_db.latest_persist_state()
-            .map_err(|e| TrieDBError::Database(format!("Failed to get latest persist state: {:?}", e)))
+        // Observed before the read, so a state persisted meanwhile fails the next check
+        let generation = self.path_db.persist_generation();
+        if let Some(state) = generation.and_then(|generation| self.persist_state.get(generation)) {
+            return Ok(state);
+        }
+
+        let state = self.path_db.latest_persist_state()
+            .map_err(|e| TrieDBError::provider("Failed to get latest persist state", e))?;
+        if let Some(generation) = generation {
+            self.persist_state.set(generation, state);
+        }
+        Ok(state)
+    }
+
+    /// Subscribes to latest persisted state changes.
+    ///
+    /// The receiver observes `Some((block_number, state_root))` after every
+    /// successful flush; it holds `None` until the first flush of this process.
+    pub fn subscribe_persist_state(&self) -> watch::Receiver<Option<(u64, B256)>> {
+        self.persist_state.subscribe()
     }
 
     pub fn flush(&mut self, block_number: u64, state_root: B256, difflayer: &Option<Arc<DiffLayer>>) -> Result<(), TrieDBError> {
-        let flush_start = Instant::now();
+        let phase = self.hook_phase(TriePhase::Flush);
 
         self.path_db.commit_difflayer(block_number, state_root, difflayer)
-            .map_err(|e| TrieDBError::Database(format!("Failed to commit difflayer: {:?}", e)))?;
+            .map_err(|e| TrieDBError::provider("Failed to commit difflayer", e))?;
+        self.persist_state.notify((block_number, state_root));
         
-        self.metrics.record_flush_duration(flush_start.elapsed().as_secs_f64());
-        debug!(target: "triedb::flush", "Persisted block number: {}, state root: {:?}, duration: {:?}", block_number, state_root, flush_start.elapsed());
+        let flush_elapsed = phase.finish();
+        self.metrics.record_flush_duration(flush_elapsed.as_secs_f64());
+        debug!(target: "triedb::flush", "Persisted block number: {}, state root: {:?}, duration: {:?}", block_number, state_root, flush_elapsed);
         Ok(())
     }
 
     pub fn clear_cache(&mut self) {
         self.path_db.clear_cache();
     }
+
+    /// Closes the database gracefully before process exit.
+    ///
+    /// Flushes the database and persists its warm-up state, see
+    /// `TrieDatabase::shutdown`. Changes not flushed yet are lost.
+    pub fn close(&mut self) -> Result<(), TrieDBError> {
+        self.path_db.shutdown()
+            .map_err(|e| TrieDBError::provider("Failed to close database", e))?;
+
+        let persist_state = *self.persist_state.subscribe().borrow();
+        debug!(target: "triedb::close", "Closed database, latest persisted state: {:?}", persist_state);
+        Ok(())
+    }
 }
 
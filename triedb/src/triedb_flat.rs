@@ -0,0 +1,219 @@
+//! Flat storage fast path for TrieDB reads.
+
+use std::sync::Arc;
+use tracing::warn;
+
+use alloy_primitives::{B256, U256};
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::account::StateAccount;
+
+use crate::triedb::{TrieDB, TrieDBError};
+
+/// Source of flat (non-trie) storage slot values, e.g. a snapshot database.
+///
+/// The reader must serve the persisted state. Reads are only routed to it
+/// when TrieDB is positioned at the persisted state root and no uncommitted
+/// diff layers are in use.
+pub trait FlatStorageReader: Send + Sync + std::fmt::Debug {
+    /// Returns the value of a storage slot in the same format as
+    /// `get_storage_with_hash_state`, or `None` if the slot isn't covered by the
+    /// flat table, in which case the read falls back to the trie.
+    fn get_flat_storage(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<Vec<u8>>, TrieDBError>;
+}
+
+/// A [`FlatStorageReader`] over the flat storage snapshot of a database,
+/// e.g. a PathDB, as written by `TrieDB::put_storage_snapshot`.
+///
+/// The snapshot drops zero values, so slots it has no entry for are read
+/// through the trie.
+#[derive(Debug, Clone)]
+pub struct StorageSnapshotReader<DB> {
+    db: DB,
+}
+
+impl<DB> StorageSnapshotReader<DB> {
+    /// Creates a reader over the storage snapshot of `db`.
+    pub fn new(db: DB) -> Self {
+        Self { db }
+    }
+}
+
+impl<DB> FlatStorageReader for StorageSnapshotReader<DB>
+where
+    DB: TrieDatabase + Send + Sync + std::fmt::Debug,
+    DB::Error: TrieDBErrorSource,
+{
+    fn get_flat_storage(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<Vec<u8>>, TrieDBError> {
+        let value = self.db.get_storage_snapshot(hashed_address, hashed_key)
+            .map_err(|e| TrieDBError::provider(format!("Failed to read storage snapshot for {:#x}, hashed_key {:#x}", hashed_address, hashed_key), e))?;
+        // Re-trimmed, so values written with leading zeros match the trie's
+        value
+            .map(|value| decode_storage_snapshot(hashed_address, hashed_key, &value).map(|value| value.to_be_bytes_trimmed_vec()))
+            .transpose()
+    }
+}
+
+/// How storage reads use the flat storage reader.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum FlatReadMode {
+    /// Always read through the trie.
+    #[default]
+    Disabled,
+    /// Read from the flat table first and fall back to the trie on a miss.
+    Enabled,
+    /// Read both, return the trie value and count mismatches; used during rollout.
+    CrossCheck,
+}
+
+/// Flat storage configuration and reads
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Sets the flat storage reader and how storage reads use it.
+    pub fn with_flat_storage(mut self, reader: Arc<dyn FlatStorageReader>, mode: FlatReadMode) -> Self {
+        self.flat_storage = Some(reader);
+        self.flat_read_mode = mode;
+        self
+    }
+
+    /// Changes how storage reads use the flat storage reader.
+    pub fn set_flat_read_mode(&mut self, mode: FlatReadMode) {
+        self.flat_read_mode = mode;
+    }
+
+    /// Returns the current flat read mode.
+    pub fn flat_read_mode(&self) -> FlatReadMode {
+        self.flat_read_mode
+    }
+
+    /// Serves a storage read through the flat storage reader.
+    ///
+    /// Returns `None` if the read was not served and must go through the trie.
+    pub(crate) fn get_storage_with_flat_state(&mut self, hashed_address: B256, hashed_key: B256) -> Result<Option<Option<Vec<u8>>>, TrieDBError> {
+        let Some(reader) = self.flat_storage.clone() else {
+            return Ok(None);
+        };
+        // Flat reads yield no trie nodes for the execution witness
+        if self.flat_read_mode == FlatReadMode::Disabled || self.difflayer.is_some() || self.witness_recorder.is_some() {
+            return Ok(None);
+        }
+        // Like the flat storage roots, the flat table only holds the persisted state
+        if self.root_hash != self.latest_persist_state()?.1 {
+            return Ok(None);
+        }
+
+        let flat_value = reader.get_flat_storage(hashed_address, hashed_key)?;
+        if flat_value.is_some() {
+            self.metrics.increment_flat_storage_hit_counter();
+        } else {
+            self.metrics.increment_flat_storage_miss_counter();
+        }
+
+        match self.flat_read_mode {
+            FlatReadMode::Enabled => Ok(flat_value.map(Some)),
+            FlatReadMode::CrossCheck => {
+                let trie_value = self.get_storage_from_trie(hashed_address, hashed_key)?;
+                if flat_value.is_some() && flat_value != trie_value {
+                    self.metrics.increment_flat_storage_mismatch_counter();
+                    warn!(target: "triedb::flat", "Flat storage mismatch for hashed_address: {:#x}, hashed_key: {:#x}, flat: {:?}, trie: {:?}", hashed_address, hashed_key, flat_value, trie_value);
+                }
+                Ok(Some(trie_value))
+            }
+            FlatReadMode::Disabled => Ok(None),
+        }
+    }
+}
+
+/// Flat state snapshot
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Stores `account` in the flat account snapshot of the database.
+    ///
+    /// The snapshot is not updated by commits; its owner keeps it in sync
+    /// with the persisted state, e.g. from the account leaves of each block.
+    pub fn put_account_snapshot(&self, hashed_address: B256, account: StateAccount) -> Result<(), TrieDBError> {
+        self.path_db.put_account_snapshot(hashed_address, Some(&account.to_rlp()))
+            .map_err(|e| TrieDBError::provider(format!("Failed to write account snapshot for {:#x}", hashed_address), e))
+    }
+
+    /// Removes `hashed_address` from the flat account snapshot of the database.
+    pub fn delete_account_snapshot(&self, hashed_address: B256) -> Result<(), TrieDBError> {
+        self.path_db.put_account_snapshot(hashed_address, None)
+            .map_err(|e| TrieDBError::provider(format!("Failed to delete account snapshot for {:#x}", hashed_address), e))
+    }
+
+    /// Returns the account of `hashed_address` from the flat account
+    /// snapshot with a single lookup, without walking the account trie.
+    ///
+    /// `None` means the snapshot has no entry, not that the account doesn't
+    /// exist; fall back to `get_account_with_hash_state` in that case.
+    pub fn get_account_snapshot(&self, hashed_address: B256) -> Result<Option<StateAccount>, TrieDBError> {
+        let value = self.path_db.get_account_snapshot(hashed_address)
+            .map_err(|e| TrieDBError::provider(format!("Failed to read account snapshot for {:#x}", hashed_address), e))?;
+        value.map(|value| decode_account_snapshot(hashed_address, &value)).transpose()
+    }
+
+    /// Returns up to `limit` accounts of the flat account snapshot in hashed
+    /// address order, starting at `start_hash`.
+    ///
+    /// Serves snap sync account ranges and rebuilding the account trie from
+    /// flat state; fewer than `limit` accounts means the range is complete.
+    pub fn iter_account_snapshot(&self, start_hash: B256, limit: usize) -> Result<Vec<(B256, StateAccount)>, TrieDBError> {
+        let entries = self.path_db.iter_account_snapshot(start_hash, limit)
+            .map_err(|e| TrieDBError::provider(format!("Failed to iterate account snapshot from {:#x}", start_hash), e))?;
+        entries
+            .into_iter()
+            .map(|(hashed_address, value)| Ok((hashed_address, decode_account_snapshot(hashed_address, &value)?)))
+            .collect()
+    }
+
+    /// Stores `value` as the storage slot `hashed_key` of `hashed_address`
+    /// in the flat storage snapshot of the database. Zero values are
+    /// removed, as they are from the storage trie.
+    pub fn put_storage_snapshot(&self, hashed_address: B256, hashed_key: B256, value: U256) -> Result<(), TrieDBError> {
+        let value = (!value.is_zero()).then(|| value.to_be_bytes_trimmed_vec());
+        self.path_db.put_storage_snapshot(hashed_address, hashed_key, value.as_deref())
+            .map_err(|e| TrieDBError::provider(format!("Failed to write storage snapshot for {:#x}, hashed_key {:#x}", hashed_address, hashed_key), e))
+    }
+
+    /// Removes the storage slot `hashed_key` of `hashed_address` from the
+    /// flat storage snapshot of the database.
+    pub fn delete_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> Result<(), TrieDBError> {
+        self.put_storage_snapshot(hashed_address, hashed_key, U256::ZERO)
+    }
+
+    /// Returns the storage slot `hashed_key` of `hashed_address` from the
+    /// flat storage snapshot, `None` if the snapshot has no entry for it.
+    pub fn get_storage_snapshot(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<U256>, TrieDBError> {
+        let value = self.path_db.get_storage_snapshot(hashed_address, hashed_key)
+            .map_err(|e| TrieDBError::provider(format!("Failed to read storage snapshot for {:#x}, hashed_key {:#x}", hashed_address, hashed_key), e))?;
+        value.map(|value| decode_storage_snapshot(hashed_address, hashed_key, &value)).transpose()
+    }
+
+    /// Returns up to `limit` storage slots of `hashed_address` from the flat
+    /// storage snapshot in hashed key order, starting at `start_hash`.
+    pub fn iter_storage_snapshot(&self, hashed_address: B256, start_hash: B256, limit: usize) -> Result<Vec<(B256, U256)>, TrieDBError> {
+        let entries = self.path_db.iter_storage_snapshot(hashed_address, start_hash, limit)
+            .map_err(|e| TrieDBError::provider(format!("Failed to iterate storage snapshot for {:#x} from {:#x}", hashed_address, start_hash), e))?;
+        entries
+            .into_iter()
+            .map(|(hashed_key, value)| Ok((hashed_key, decode_storage_snapshot(hashed_address, hashed_key, &value)?)))
+            .collect()
+    }
+}
+
+fn decode_account_snapshot(hashed_address: B256, value: &[u8]) -> Result<StateAccount, TrieDBError> {
+    StateAccount::from_rlp(value)
+        .map_err(|e| TrieDBError::InvalidData(format!("Invalid account snapshot for {:#x}: {}", hashed_address, e)))
+}
+
+fn decode_storage_snapshot(hashed_address: B256, hashed_key: B256, value: &[u8]) -> Result<U256, TrieDBError> {
+    U256::try_from_be_slice(value).ok_or_else(|| {
+        TrieDBError::InvalidData(format!("Invalid storage snapshot for {:#x}, hashed_key {:#x}: {} bytes", hashed_address, hashed_key, value.len()))
+    })
+}
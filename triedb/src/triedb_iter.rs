@@ -0,0 +1,38 @@
+//! Ordered iteration over accounts and storage slots.
+
+use alloy_primitives::B256;
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::account::StateAccount;
+
+use crate::triedb::{TrieDB, TrieDBError};
+
+/// Account and storage iteration
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Iterates over all accounts of the current state in hashed address order.
+    ///
+    /// Nodes are read through the diff layers the state was opened with, so
+    /// iteration at a root that is not flushed yet sees the same accounts as
+    /// point reads. Accounts changed in place but not committed are included.
+    pub fn iter_accounts(&self) -> Result<impl Iterator<Item = Result<(B256, StateAccount), TrieDBError>>, TrieDBError> {
+        let account_trie = self.account_trie.as_ref()
+            .ok_or_else(|| TrieDBError::Database("Account trie is not initialized".to_string()))?;
+        Ok(account_trie.iter_accounts_with_hash_state().map(|item| Ok(item?)))
+    }
+
+    /// Iterates over all storage slots of an account in hashed key order.
+    ///
+    /// Values are in the format of `get_storage_with_hash_state`. Like
+    /// `iter_accounts`, reads go through the diff layers.
+    pub fn iter_storage(&mut self, hashed_address: B256) -> Result<impl Iterator<Item = Result<(B256, Vec<u8>), TrieDBError>>, TrieDBError> {
+        let storage_trie = self.get_readable_storage_trie(hashed_address)?;
+        Ok(storage_trie
+            .map(|storage_trie| storage_trie.iter_storage_with_hash_state())
+            .into_iter()
+            .flatten()
+            .map(|item| Ok(item?)))
+    }
+}
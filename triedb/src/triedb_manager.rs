@@ -3,10 +3,10 @@
 //! This module provides a singleton manager for TrieDB instances,
 //! allowing global access to a shared TrieDB across the application.
 
-use std::sync::{OnceLock};
+use std::sync::{Mutex, OnceLock};
+use rust_eth_triedb_common::CancellationToken;
 use rust_eth_triedb_pathdb::{PathDB, PathProviderConfig};
-// use rust_eth_triedb_snapshotdb::{SnapshotDB, PathProviderConfig as SnapshotPathProviderConfig};
-use super::TrieDB;
+use super::{TrieDB, TrieDBError};
 use rust_eth_triedb_state_trie::node::init_empty_root_node;
 use tracing::info;
 
@@ -25,8 +25,11 @@ pub fn enable_triedb() {
     ACTIVE_TRIEDB.get_or_init(|| true);
 }
 
-// Disable the active_triedb flag
+// Disable the active_triedb flag, cancelling the long-running operations on the global TrieDB
 pub fn disable_triedb() {
+    if let Some(manager) = MANAGER_INSTANCE.get() {
+        manager.cancel_operations();
+    }
     if let Some(&current_value) = ACTIVE_TRIEDB.get() {
         if current_value {
             panic!("TrieDB is already enabled. Cannot disable it after it has been enabled.");
@@ -48,6 +51,7 @@ pub fn is_triedb_active() -> bool {
 /// accessible throughout the application lifecycle.
 pub struct TrieDBManager {
     triedb: TrieDB<PathDB>,
+    cancellation: Mutex<CancellationToken>,
 }
 
 // Global singleton instance - automatically initialized on first access
@@ -101,6 +105,41 @@ pub fn get_global_triedb() -> TrieDB<PathDB> {
     get_manager().get_triedb()
 }
 
+/// Get the token long-running operations on the global TrieDB should run
+/// with, so [`cancel_global_operations`] can stop them.
+///
+/// # Panics
+///
+/// This function will panic if `init_global_manager()` has not been called first.
+pub fn global_cancellation_token() -> CancellationToken {
+    get_manager().cancellation_token()
+}
+
+/// Cancel the long-running operations started with the current global
+/// token, e.g. on shutdown or reorg. Operations started afterwards get a
+/// fresh token.
+///
+/// # Panics
+///
+/// This function will panic if `init_global_manager()` has not been called first.
+pub fn cancel_global_operations() {
+    get_manager().cancel_operations();
+}
+
+/// Shut the global TrieDB down before process exit.
+///
+/// Cancels the long-running operations started with the global token, then
+/// closes the database, see [`TrieDB::close`].
+///
+/// # Panics
+///
+/// This function will panic if `init_global_manager()` has not been called first.
+pub fn shutdown_global_triedb() -> Result<(), TrieDBError> {
+    let manager = get_manager();
+    manager.cancel_operations();
+    manager.get_triedb().close()
+}
+
 impl TrieDBManager {
     /// Create a new TrieDBManager with the given database path
     /// 
@@ -113,6 +152,7 @@ impl TrieDBManager {
         let triedb = TrieDB::new(pathdb);
         Self {
             triedb,
+            cancellation: Mutex::new(CancellationToken::new()),
         }
     }
 
@@ -120,5 +160,17 @@ impl TrieDBManager {
     pub fn get_triedb(&self) -> TrieDB<PathDB> {
         self.triedb.clone()
     }
+
+    /// Get the token for long-running operations on the managed TrieDB
+    pub fn cancellation_token(&self) -> CancellationToken {
+        self.cancellation.lock().unwrap().clone()
+    }
+
+    /// Cancel the operations holding the current token and replace it
+    pub fn cancel_operations(&self) {
+        let cancelled = std::mem::take(&mut *self.cancellation.lock().unwrap());
+        cancelled.cancel();
+        info!(target: "reth::cli", "Cancelled long-running TrieDB operations");
+    }
 }
 
@@ -4,6 +4,9 @@ use reth_metrics::{
     metrics::{Histogram, Counter},
     Metrics,
 };
+use rust_eth_triedb_common::{CounterTotal, HistogramSummary, HistogramTotal};
+use rust_eth_triedb_pathdb::PathDBMetricsSnapshot;
+use rust_eth_triedb_state_trie::DiffLayerMetricsSnapshot;
 
 /// Metrics for the `TrieDB`.
 #[derive(Metrics, Clone)]
@@ -13,6 +16,10 @@ pub(crate) struct TrieDBMetrics {
     pub(crate) update_prepare_histogram: Histogram,
     /// Histogram of update and commit durations (in seconds)
     pub(crate) update_histogram: Histogram,
+    /// Histogram of account path prefetch durations (in seconds)
+    pub(crate) account_prefetch_histogram: Histogram,
+    /// Histogram of account trie nodes resolved per prefetch
+    pub(crate) account_prefetch_nodes_histogram: Histogram,
 
     /// Histogram of hash durations (in seconds)
     pub(crate) hash_histogram: Histogram,
@@ -25,35 +32,220 @@ pub(crate) struct TrieDBMetrics {
     pub(crate) get_storage_root_from_flat_counter: Counter,
     /// Counter of get storage root from trie database
     pub(crate) get_storage_root_from_trie_counter: Counter,
+    /// Counter of storage tries updated through the bulk path
+    pub(crate) bulk_storage_update_counter: Counter,
+    /// Counter of pooled storage tries reused instead of built
+    pub(crate) storage_trie_reuse_counter: Counter,
+    /// Counter of storage reads and proofs short-circuited on an empty storage root
+    pub(crate) empty_storage_root_counter: Counter,
+
+    /// Counter of storage reads served by the flat storage reader
+    pub(crate) flat_storage_hit_counter: Counter,
+    /// Counter of storage reads not covered by the flat storage reader
+    pub(crate) flat_storage_miss_counter: Counter,
+    /// Counter of flat storage values differing from the trie in cross-check mode
+    pub(crate) flat_storage_mismatch_counter: Counter,
+
+    /// Counter of commits whose re-computed root differed in root audit mode
+    pub(crate) root_audit_mismatch_counter: Counter,
 }
 
 impl TrieDBMetrics {
     pub(crate) fn record_hash_duration(&self, duration: f64) {
         self.hash_histogram.record(duration);
+        TRIEDB_METRIC_TOTALS.hash_histogram.record(duration);
     }
 
     pub(crate) fn record_commit_duration(&self, duration: f64) {
         self.commit_histogram.record(duration);
+        TRIEDB_METRIC_TOTALS.commit_histogram.record(duration);
     }
 
     pub(crate) fn record_flush_duration(&self, duration: f64) {
         self.flush_histogram.record(duration);
+        TRIEDB_METRIC_TOTALS.flush_histogram.record(duration);
     }
 
     pub(crate) fn record_update_prepare_duration(&self, duration: f64) {
         self.update_prepare_histogram.record(duration);
+        TRIEDB_METRIC_TOTALS.update_prepare_histogram.record(duration);
     }
 
     pub(crate) fn record_update_duration(&self, duration: f64) {
         self.update_histogram.record(duration);
+        TRIEDB_METRIC_TOTALS.update_histogram.record(duration);
+    }
+
+    pub(crate) fn record_account_prefetch(&self, duration: f64, resolved_nodes: usize) {
+        self.account_prefetch_histogram.record(duration);
+        TRIEDB_METRIC_TOTALS.account_prefetch_histogram.record(duration);
+        self.account_prefetch_nodes_histogram.record(resolved_nodes as f64);
+        TRIEDB_METRIC_TOTALS.account_prefetch_nodes_histogram.record(resolved_nodes as f64);
     }
 
     pub(crate) fn increment_get_storage_root_from_flat_counter(&self) {
         self.get_storage_root_from_flat_counter.increment(1);
+        TRIEDB_METRIC_TOTALS.get_storage_root_from_flat_counter.increment(1);
     }
 
     pub(crate) fn increment_get_storage_root_from_trie_counter(&self) {
         self.get_storage_root_from_trie_counter.increment(1);
+        TRIEDB_METRIC_TOTALS.get_storage_root_from_trie_counter.increment(1);
+    }
+
+    pub(crate) fn increment_bulk_storage_update_counter(&self) {
+        self.bulk_storage_update_counter.increment(1);
+        TRIEDB_METRIC_TOTALS.bulk_storage_update_counter.increment(1);
+    }
+
+    pub(crate) fn increment_storage_trie_reuse_counter(&self) {
+        self.storage_trie_reuse_counter.increment(1);
+        TRIEDB_METRIC_TOTALS.storage_trie_reuse_counter.increment(1);
+    }
+
+    pub(crate) fn increment_empty_storage_root_counter(&self) {
+        self.empty_storage_root_counter.increment(1);
+        TRIEDB_METRIC_TOTALS.empty_storage_root_counter.increment(1);
+    }
+
+    pub(crate) fn increment_flat_storage_hit_counter(&self) {
+        self.flat_storage_hit_counter.increment(1);
+        TRIEDB_METRIC_TOTALS.flat_storage_hit_counter.increment(1);
+    }
+
+    pub(crate) fn increment_flat_storage_miss_counter(&self) {
+        self.flat_storage_miss_counter.increment(1);
+        TRIEDB_METRIC_TOTALS.flat_storage_miss_counter.increment(1);
     }
+
+    pub(crate) fn increment_flat_storage_mismatch_counter(&self) {
+        self.flat_storage_mismatch_counter.increment(1);
+        TRIEDB_METRIC_TOTALS.flat_storage_mismatch_counter.increment(1);
+    }
+
+    pub(crate) fn increment_root_audit_mismatch_counter(&self) {
+        self.root_audit_mismatch_counter.increment(1);
+        TRIEDB_METRIC_TOTALS.root_audit_mismatch_counter.increment(1);
+    }
+}
+
+/// Process-wide totals of the TrieDB metrics, summed over all instances.
+struct TrieDBMetricTotals {
+    update_prepare_histogram: HistogramTotal,
+    update_histogram: HistogramTotal,
+    account_prefetch_histogram: HistogramTotal,
+    account_prefetch_nodes_histogram: HistogramTotal,
+    hash_histogram: HistogramTotal,
+    commit_histogram: HistogramTotal,
+    flush_histogram: HistogramTotal,
+    get_storage_root_from_flat_counter: CounterTotal,
+    get_storage_root_from_trie_counter: CounterTotal,
+    bulk_storage_update_counter: CounterTotal,
+    storage_trie_reuse_counter: CounterTotal,
+    empty_storage_root_counter: CounterTotal,
+    flat_storage_hit_counter: CounterTotal,
+    flat_storage_miss_counter: CounterTotal,
+    flat_storage_mismatch_counter: CounterTotal,
+    root_audit_mismatch_counter: CounterTotal,
+}
+
+static TRIEDB_METRIC_TOTALS: TrieDBMetricTotals = TrieDBMetricTotals {
+    update_prepare_histogram: HistogramTotal::new(),
+    update_histogram: HistogramTotal::new(),
+    account_prefetch_histogram: HistogramTotal::new(),
+    account_prefetch_nodes_histogram: HistogramTotal::new(),
+    hash_histogram: HistogramTotal::new(),
+    commit_histogram: HistogramTotal::new(),
+    flush_histogram: HistogramTotal::new(),
+    get_storage_root_from_flat_counter: CounterTotal::new(),
+    get_storage_root_from_trie_counter: CounterTotal::new(),
+    bulk_storage_update_counter: CounterTotal::new(),
+    storage_trie_reuse_counter: CounterTotal::new(),
+    empty_storage_root_counter: CounterTotal::new(),
+    flat_storage_hit_counter: CounterTotal::new(),
+    flat_storage_miss_counter: CounterTotal::new(),
+    flat_storage_mismatch_counter: CounterTotal::new(),
+    root_audit_mismatch_counter: CounterTotal::new(),
+};
+
+/// Current values of the TrieDB metrics, summed over all TrieDB instances of
+/// the process.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct TrieDBMetricsSnapshot {
+    /// Update and commit prepare durations (in seconds)
+    pub update_prepare_histogram: HistogramSummary,
+    /// Update and commit durations (in seconds)
+    pub update_histogram: HistogramSummary,
+    /// Account path prefetch durations (in seconds)
+    pub account_prefetch_histogram: HistogramSummary,
+    /// Account trie nodes resolved per prefetch
+    pub account_prefetch_nodes_histogram: HistogramSummary,
+    /// Hash durations (in seconds)
+    pub hash_histogram: HistogramSummary,
+    /// Commit durations (in seconds)
+    pub commit_histogram: HistogramSummary,
+    /// Flush durations (in seconds)
+    pub flush_histogram: HistogramSummary,
+    /// Storage roots read from the flat database
+    pub get_storage_root_from_flat_counter: u64,
+    /// Storage roots read from the trie database
+    pub get_storage_root_from_trie_counter: u64,
+    /// Storage tries updated through the bulk path
+    pub bulk_storage_update_counter: u64,
+    /// Pooled storage tries reused instead of built
+    pub storage_trie_reuse_counter: u64,
+    /// Storage reads and proofs short-circuited on an empty storage root
+    pub empty_storage_root_counter: u64,
+    /// Storage reads served by the flat storage reader
+    pub flat_storage_hit_counter: u64,
+    /// Storage reads not covered by the flat storage reader
+    pub flat_storage_miss_counter: u64,
+    /// Flat storage values differing from the trie in cross-check mode
+    pub flat_storage_mismatch_counter: u64,
+    /// Commits whose re-computed root differed in root audit mode
+    pub root_audit_mismatch_counter: u64,
 }
 
+/// Current values of all trie metrics, see [`snapshot`].
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct MetricsSnapshot {
+    /// TrieDB metrics
+    pub triedb: TrieDBMetricsSnapshot,
+    /// PathDB metrics
+    pub pathdb: PathDBMetricsSnapshot,
+    /// Difflayer lookup metrics
+    pub difflayer: DiffLayerMetricsSnapshot,
+}
+
+/// Take a snapshot of all trie metrics.
+///
+/// For embedders that log or serialize the metrics directly instead of
+/// scraping the metrics exporter, and for assertions on metrics in tests.
+/// Values accumulate over the whole process, so tests should compare two
+/// snapshots rather than absolute values.
+pub fn snapshot() -> MetricsSnapshot {
+    let totals = &TRIEDB_METRIC_TOTALS;
+    let triedb = TrieDBMetricsSnapshot {
+        update_prepare_histogram: totals.update_prepare_histogram.get(),
+        update_histogram: totals.update_histogram.get(),
+        account_prefetch_histogram: totals.account_prefetch_histogram.get(),
+        account_prefetch_nodes_histogram: totals.account_prefetch_nodes_histogram.get(),
+        hash_histogram: totals.hash_histogram.get(),
+        commit_histogram: totals.commit_histogram.get(),
+        flush_histogram: totals.flush_histogram.get(),
+        get_storage_root_from_flat_counter: totals.get_storage_root_from_flat_counter.get(),
+        get_storage_root_from_trie_counter: totals.get_storage_root_from_trie_counter.get(),
+        bulk_storage_update_counter: totals.bulk_storage_update_counter.get(),
+        storage_trie_reuse_counter: totals.storage_trie_reuse_counter.get(),
+        empty_storage_root_counter: totals.empty_storage_root_counter.get(),
+        flat_storage_hit_counter: totals.flat_storage_hit_counter.get(),
+        flat_storage_miss_counter: totals.flat_storage_miss_counter.get(),
+        flat_storage_mismatch_counter: totals.flat_storage_mismatch_counter.get(),
+        root_audit_mismatch_counter: totals.root_audit_mismatch_counter.get(),
+    };
+    MetricsSnapshot {
+        triedb,
+        pathdb: rust_eth_triedb_pathdb::metrics_snapshot::snapshot(),
+        difflayer: rust_eth_triedb_state_trie::difflayer_metrics::snapshot(),
+    }
+}
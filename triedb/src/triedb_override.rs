@@ -0,0 +1,95 @@
+//! Ad-hoc state overrides layered on top of a state root.
+
+use std::collections::{BTreeMap, HashMap};
+
+use alloy_primitives::{B256, U256};
+use alloy_trie::EMPTY_ROOT_HASH;
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::node::DiffLayers;
+
+use crate::triedb::{TrieDB, TrieDBError};
+
+/// Override of a single account, keyed by hashed address in `StateOverrides`.
+///
+/// Mirrors `eth_call` state overrides, fields left as `None` keep their value
+/// at the chosen root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountOverride {
+    /// Replacement nonce
+    pub nonce: Option<u64>,
+    /// Replacement balance
+    pub balance: Option<U256>,
+    /// Replacement code hash
+    pub code_hash: Option<B256>,
+    /// Replaces the whole storage with these slots, keyed by hashed slot
+    pub state: Option<HashMap<B256, U256>>,
+    /// Patches individual slots on top of the existing storage, keyed by hashed slot
+    pub state_diff: HashMap<B256, U256>,
+}
+
+/// Account overrides keyed by hashed address.
+pub type StateOverrides = HashMap<B256, AccountOverride>;
+
+/// State overrides
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Returns a trie db positioned at `root_hash` on top of `difflayer`, with
+    /// `overrides` applied.
+    ///
+    /// The overrides are applied in memory to the account and storage tries of
+    /// a clone, so reads go through the normal trie read path while `self`
+    /// stays where it was. Nothing is hashed, committed or written to the
+    /// database; `calculate_hash` on the returned trie db gives the root of the
+    /// overridden state when needed.
+    pub fn state_at_with_overrides(
+        &self,
+        root_hash: B256,
+        difflayer: Option<&DiffLayers>,
+        overrides: &StateOverrides) -> Result<Self, TrieDBError> {
+
+        let mut overridden = self.clone();
+        // The flat table holds the slots at the persisted root, not the overridden ones
+        overridden.flat_storage = None;
+        overridden.state_at(root_hash, difflayer)?;
+
+        for (hashed_address, account_override) in overrides {
+            let mut account = overridden.get_account_with_hash_state(*hashed_address)?.unwrap_or_default();
+            if let Some(nonce) = account_override.nonce {
+                account.nonce = nonce;
+            }
+            if let Some(balance) = account_override.balance {
+                account.balance = balance;
+            }
+            if let Some(code_hash) = account_override.code_hash {
+                account.code_hash = code_hash;
+            }
+
+            let storage_trie = if account_override.state.is_some() {
+                account.storage_root = EMPTY_ROOT_HASH;
+                Some(overridden.build_storage_trie(*hashed_address, EMPTY_ROOT_HASH)?)
+            } else if !account_override.state_diff.is_empty() {
+                Some(overridden.get_storage_trie_with_hash_state(*hashed_address)?)
+            } else {
+                None
+            };
+
+            if let Some(mut storage_trie) = storage_trie {
+                let slots: BTreeMap<B256, Option<U256>> = account_override.state
+                    .iter()
+                    .flatten()
+                    .chain(&account_override.state_diff)
+                    .map(|(slot, value)| (*slot, (!value.is_zero()).then_some(*value)))
+                    .collect();
+                storage_trie.update_storages_with_hash_state(slots.into_iter().collect())?;
+                // Reads and `calculate_hash` pick the storage trie up from the cache
+                overridden.storage_tries.insert(*hashed_address, storage_trie);
+                overridden.accounts_with_storage_trie.insert(*hashed_address, account);
+            }
+            overridden.update_account_with_hash_state(*hashed_address, &account)?;
+        }
+        Ok(overridden)
+    }
+}
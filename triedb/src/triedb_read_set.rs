@@ -0,0 +1,145 @@
+//! Batched account and storage reads against a pinned state.
+
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use alloy_primitives::B256;
+use alloy_trie::EMPTY_ROOT_HASH;
+use rayon::prelude::*;
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::account::StateAccount;
+use rust_eth_triedb_state_trie::{SecureTrieBuilder, SecureTrieId, SecureTrieTrait};
+
+use crate::triedb::{TrieDB, TrieDBError};
+
+/// A mixed set of account and storage slot reads, all keys hashed.
+#[derive(Debug, Clone, Default)]
+pub struct ReadSet {
+    /// Hashed addresses of the accounts to read
+    pub accounts: Vec<B256>,
+    /// `(hashed address, hashed slot)` pairs of the storage slots to read
+    pub slots: Vec<(B256, B256)>,
+    /// Whether to build a Merkle proof for every read
+    pub with_proofs: bool,
+}
+
+/// Result of an account read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AccountRead {
+    /// The account, `None` if it doesn't exist
+    pub account: Option<StateAccount>,
+    /// Account trie proof, if requested
+    pub proof: Option<Vec<Vec<u8>>>,
+}
+
+/// Result of a storage slot read.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SlotRead {
+    /// The slot value in the format of `get_storage_with_hash_state`
+    pub value: Option<Vec<u8>>,
+    /// Storage trie proof, if requested
+    pub proof: Option<Vec<Vec<u8>>>,
+}
+
+/// Results of a `ReadSet`, all resolved against the same state root.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReadResults {
+    /// State root the reads were resolved against
+    pub root_hash: B256,
+    /// Account reads keyed by hashed address
+    pub accounts: HashMap<B256, AccountRead>,
+    /// Storage slot reads keyed by `(hashed address, hashed slot)`
+    pub slots: HashMap<(B256, B256), SlotRead>,
+}
+
+/// Batched reads
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Resolves `reads` in a single parallel pass over the accounts involved.
+    ///
+    /// All reads are served from the root and diff layers of the last
+    /// [`state_at`](Self::state_at) call using fresh tries, so they observe one
+    /// consistent view regardless of in-memory changes made through this
+    /// instance. Intended for parallel-EVM schedulers that need a consistent
+    /// read snapshot.
+    pub fn read_set(&self, reads: &ReadSet) -> Result<ReadResults, TrieDBError> {
+        let mut slots_by_address: HashMap<B256, Vec<B256>> = HashMap::new();
+        for (hashed_address, hashed_slot) in &reads.slots {
+            slots_by_address.entry(*hashed_address).or_default().push(*hashed_slot);
+        }
+        let addresses: BTreeSet<B256> = reads.accounts.iter().copied()
+            .chain(slots_by_address.keys().copied())
+            .collect();
+
+        // Capture only the pinned view, not `self`
+        let root_hash = self.root_hash;
+        let with_proofs = reads.with_proofs;
+        let path_db = &self.path_db;
+        let difflayer = self.difflayer.as_ref();
+        let hooks = &self.hooks;
+        let metrics = &self.metrics;
+        let witness_recorder = &self.witness_recorder;
+        let recorder = self.node_recorder();
+        // Built once, every task resolves its account on a clone of the unresolved trie
+        let account_trie = SecureTrieBuilder::new(path_db.clone())
+            .with_id(SecureTrieId::new(root_hash))
+            .with_hooks(hooks.clone())
+            .with_recorder(recorder.clone())
+            .build_with_difflayer(difflayer)?;
+        let per_address = addresses
+            .into_par_iter()
+            .map(|hashed_address| {
+                let mut account_trie = account_trie.clone();
+                let account = account_trie.get_account_with_hash_state(hashed_address)?;
+                if let Some(witness_recorder) = witness_recorder {
+                    witness_recorder.record_account(account.as_ref());
+                }
+                let account_proof = match with_proofs {
+                    true => Some(account_trie.prove_with_hash_state(hashed_address)?),
+                    false => None,
+                };
+
+                let mut slot_reads = Vec::new();
+                if let Some(hashed_slots) = slots_by_address.get(&hashed_address) {
+                    let storage_root = account.map_or(EMPTY_ROOT_HASH, |account| account.storage_root);
+                    // Nothing to read from an empty storage trie, and its proofs are empty
+                    if storage_root == EMPTY_ROOT_HASH {
+                        metrics.increment_empty_storage_root_counter();
+                        for hashed_slot in hashed_slots {
+                            let proof = with_proofs.then(Vec::new);
+                            slot_reads.push(((hashed_address, *hashed_slot), SlotRead { value: None, proof }));
+                        }
+                        return Ok((hashed_address, AccountRead { account, proof: account_proof }, slot_reads));
+                    }
+                    let mut storage_trie = SecureTrieBuilder::new(path_db.clone())
+                        .with_id(SecureTrieId::new(storage_root).with_owner(hashed_address))
+                        .with_hooks(hooks.clone())
+                        .with_recorder(recorder.clone())
+                        .build_with_difflayer(difflayer)?;
+                    for hashed_slot in hashed_slots {
+                        let value = storage_trie.get_storage_with_hash_state(hashed_address, *hashed_slot)?;
+                        let proof = match with_proofs {
+                            true => Some(storage_trie.prove_with_hash_state(*hashed_slot)?),
+                            false => None,
+                        };
+                        slot_reads.push(((hashed_address, *hashed_slot), SlotRead { value, proof }));
+                    }
+                }
+
+                Ok((hashed_address, AccountRead { account, proof: account_proof }, slot_reads))
+            })
+            .collect::<Result<Vec<_>, TrieDBError>>()?;
+
+        let requested_accounts: HashSet<&B256> = reads.accounts.iter().collect();
+        let mut results = ReadResults { root_hash, ..Default::default() };
+        for (hashed_address, account_read, slot_reads) in per_address {
+            if requested_accounts.contains(&hashed_address) {
+                results.accounts.insert(hashed_address, account_read);
+            }
+            results.slots.extend(slot_reads);
+        }
+        Ok(results)
+    }
+}
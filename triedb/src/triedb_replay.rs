@@ -0,0 +1,141 @@
+//! Replay verification of committed blocks against the audit log.
+
+use std::sync::Arc;
+
+use alloy_primitives::B256;
+use rust_eth_triedb_common::{AuditRecord, CancellationToken, TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::node::DiffLayers;
+
+use crate::triedb::{TrieDB, TrieDBError};
+use crate::triedb_reth::TrieDBHashedPostState;
+
+/// A block whose replayed state root differs from the audit log.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RootMismatch {
+    /// Block number
+    pub block_number: u64,
+    /// State root recorded in the audit log
+    pub expected: B256,
+    /// State root computed by the replay
+    pub computed: B256,
+}
+
+/// Result of [`TrieDB::replay_verify`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ReplayReport {
+    /// Number of blocks whose computed root matched the audit log
+    pub verified: u64,
+    /// First block whose computed root didn't match; the replay stops there
+    pub mismatch: Option<RootMismatch>,
+    /// Whether the replay was cancelled before reaching the last block
+    pub cancelled: bool,
+}
+
+impl ReplayReport {
+    /// Whether all blocks were replayed and matched.
+    pub fn is_ok(&self) -> bool {
+        self.mismatch.is_none() && !self.cancelled
+    }
+}
+
+/// Replay verification
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Re-applies `post_states` of blocks `from_block..=to_block` and checks
+    /// every computed state root against the audit log.
+    ///
+    /// The replay starts at the pre-state root recorded for `from_block`, so
+    /// the database must still hold that state, and follows the recorded
+    /// chain: the record checked at each block is the one on top of the
+    /// root computed for the block before. Results are kept in memory and
+    /// never written, so a database can be validated after a migration or
+    /// pruning without modifying it. The current state of this `TrieDB` is
+    /// left at the last replayed block.
+    pub fn replay_verify(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+        post_states: &[TrieDBHashedPostState],
+    ) -> Result<ReplayReport, TrieDBError> {
+        self.replay_verify_with_cancellation(from_block, to_block, post_states, &CancellationToken::new())
+    }
+
+    /// Replay like [`TrieDB::replay_verify`], stopping before the next block
+    /// once `cancel` is cancelled.
+    ///
+    /// The report of a cancelled replay counts the blocks verified until
+    /// then and has `cancelled` set.
+    pub fn replay_verify_with_cancellation(
+        &mut self,
+        from_block: u64,
+        to_block: u64,
+        post_states: &[TrieDBHashedPostState],
+        cancel: &CancellationToken,
+    ) -> Result<ReplayReport, TrieDBError> {
+        let block_count = to_block.checked_sub(from_block).map(|span| span + 1);
+        if block_count != Some(post_states.len() as u64) {
+            return Err(TrieDBError::InvalidData(format!(
+                "Expected one post state per block in {}..={}, got {}",
+                from_block, to_block, post_states.len()
+            )));
+        }
+
+        let mut root_hash = self.audited_pre_state_root(from_block)?;
+        let mut difflayers = DiffLayers::default();
+        let mut report = ReplayReport::default();
+        for (block_number, post_state) in (from_block..=to_block).zip(post_states) {
+            if cancel.is_cancelled() {
+                report.cancelled = true;
+                return Ok(report);
+            }
+            let expected: Vec<B256> = self.audit_records(block_number)?
+                .into_iter()
+                .filter(|record| record.root_before == root_hash)
+                .map(|record| record.root_after)
+                .collect();
+            if expected.is_empty() {
+                return Err(TrieDBError::InvalidData(format!(
+                    "No audit record for block {} on top of root {:?}",
+                    block_number, root_hash
+                )));
+            }
+
+            let (computed, node_set, diff_storage_roots) = self.batch_update_and_commit(
+                root_hash,
+                Some(&difflayers),
+                post_state.states.clone(),
+                post_state.states_rebuild.clone(),
+                post_state.storage_states.clone())?;
+            if !expected.contains(&computed) {
+                report.mismatch = Some(RootMismatch { block_number, expected: expected[0], computed });
+                return Ok(report);
+            }
+
+            // Most recent layer first
+            difflayers.diff_layers.insert(0, Arc::new(node_set.to_difflayer(diff_storage_roots)));
+            root_hash = computed;
+            report.verified += 1;
+        }
+        Ok(report)
+    }
+
+    /// The pre-state root recorded for `block_number`, which must be unique.
+    fn audited_pre_state_root(&self, block_number: u64) -> Result<B256, TrieDBError> {
+        let records = self.audit_records(block_number)?;
+        let root_before = records.first()
+            .ok_or_else(|| TrieDBError::InvalidData(format!("No audit record for block {}", block_number)))?
+            .root_before;
+        if records.iter().any(|record| record.root_before != root_before) {
+            return Err(TrieDBError::InvalidData(format!("Ambiguous pre-state root for block {}", block_number)));
+        }
+        Ok(root_before)
+    }
+
+    fn audit_records(&self, block_number: u64) -> Result<Vec<AuditRecord>, TrieDBError> {
+        self.path_db.get_audit_records(block_number)
+            .map_err(|e| TrieDBError::provider(format!("Failed to read audit records for block {}", block_number), e))
+    }
+}
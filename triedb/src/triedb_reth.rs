@@ -7,13 +7,15 @@ use std::time::Instant;
 
 use alloy_primitives::B256;
 use alloy_primitives::U256;
-use rust_eth_triedb_common::TrieDatabase;
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource, TriePhase};
 use rust_eth_triedb_state_trie::node::{MergedNodeSet, DiffLayer, DiffLayers};
 use rust_eth_triedb_state_trie::state_trie::StateTrie;
 use rust_eth_triedb_state_trie::account::StateAccount;
 use rust_eth_triedb_state_trie::{SecureTrieId, SecureTrieTrait, SecureTrieBuilder};
 
 use crate::triedb::{TrieDB, TrieDBError};
+use crate::triedb_audit::build_audit_record;
+use crate::triedb_slot_count::slot_count_delta;
 
 /// Reth-compatible interface functions using hashed keys for TrieDB.
 ///
@@ -68,10 +70,12 @@ use crate::triedb::{TrieDB, TrieDBError};
 impl<DB> TrieDB<DB>
 where
     DB: TrieDatabase + Clone + Send + Sync,
-    DB::Error: std::fmt::Debug,
+    DB::Error: TrieDBErrorSource,
 {
     pub fn get_account_with_hash_state(&mut self, hashed_address: B256) -> Result<Option<StateAccount>, TrieDBError> {
-        Ok(self.account_trie.as_mut().unwrap().get_account_with_hash_state(hashed_address)?)
+        let account = self.account_trie.as_mut().unwrap().get_account_with_hash_state(hashed_address)?;
+        self.record_account_read(account.as_ref());
+        Ok(account)
     }
 
     pub fn update_account_with_hash_state(&mut self, hashed_address: B256, account: &StateAccount) -> Result<(), TrieDBError> {
@@ -83,7 +87,17 @@ where
     }
 
     pub fn get_storage_with_hash_state(&mut self, hashed_address: B256, hashed_key: B256) -> Result<Option<Vec<u8>>, TrieDBError> {
-        let mut storage_trie = self.get_storage_trie_with_hash_state(hashed_address)?;
+        if let Some(value) = self.get_storage_with_flat_state(hashed_address, hashed_key)? {
+            return Ok(value);
+        }
+        self.get_storage_from_trie(hashed_address, hashed_key)
+    }
+
+    /// Reads a storage slot through the account's storage trie.
+    pub(crate) fn get_storage_from_trie(&mut self, hashed_address: B256, hashed_key: B256) -> Result<Option<Vec<u8>>, TrieDBError> {
+        let Some(mut storage_trie) = self.get_readable_storage_trie(hashed_address)? else {
+            return Ok(None);
+        };
         Ok(storage_trie.get_storage_with_hash_state(hashed_address, hashed_key)?)
     }
 
@@ -111,7 +125,7 @@ pub struct TrieDBHashedPostState {
 impl<DB> TrieDB<DB>
 where
     DB: TrieDatabase + Clone + Send + Sync,
-    DB::Error: std::fmt::Debug,
+    DB::Error: TrieDBErrorSource,
 {  
     /// Transfers HashedPostState to triedb structure and commits the changes
     /// Compatible with Reth usage scenarios
@@ -121,32 +135,52 @@ where
         difflayer: Option<&DiffLayers>, 
         hashed_post_state: &TrieDBHashedPostState) -> 
         Result<(B256, Option<Arc<DiffLayer>>), TrieDBError> {
+        self.commit_hashed_post_state_inner(root_hash, difflayer, hashed_post_state, None)
+    }
 
-        let (root_hash, node_set, diff_storage_roots) = self.batch_update_and_commit(
+    /// Commits `hashed_post_state` like `commit_hashed_post_state`, attaching
+    /// the audit record of `audit_block_number` to the difflayer when set.
+    pub(crate) fn commit_hashed_post_state_inner(
+        &mut self,
+        root_hash: B256,
+        difflayer: Option<&DiffLayers>,
+        hashed_post_state: &TrieDBHashedPostState,
+        audit_block_number: Option<u64>) ->
+        Result<(B256, Option<Arc<DiffLayer>>), TrieDBError> {
+
+        let (new_root_hash, node_set, diff_storage_roots) = self.batch_update_and_commit(
             root_hash, 
             difflayer, 
             hashed_post_state.states.clone(), 
             hashed_post_state.states_rebuild.clone(), 
             hashed_post_state.storage_states.clone())?;
 
-        let diff_nodes = (*node_set.to_diff_nodes()).clone();
-        let difflayer = Arc::new(DiffLayer::new(diff_nodes, diff_storage_roots));
+        let audit_record = audit_block_number.map(|block_number| {
+            build_audit_record(block_number, root_hash, new_root_hash, &node_set, diff_storage_roots.len())
+        });
+        let mut difflayer = node_set.to_difflayer(diff_storage_roots)
+            .with_code_hashes(self.code_hash_changes(&hashed_post_state.states))
+            .with_slot_count_changes(std::mem::take(&mut self.slot_count_changes));
+        if let Some(audit_record) = audit_record {
+            difflayer = difflayer.with_audit_record(audit_record);
+        }
         
         if difflayer.is_empty() {
-            return Ok((root_hash, None));
+            return Ok((new_root_hash, None));
         }
         
-        Ok((root_hash, Some(difflayer)))      
+        Ok((new_root_hash, Some(Arc::new(difflayer))))
     }
 
     /// Batch update the changes and commit
     /// Compatible with Reth usage scenarios
     /// 
     /// 1. Reset the trie db state
-    /// 2. Prepare accounts to be updated
-    /// 3. Prepare required data to avoid borrowing conflicts for parallel execution
-    /// 4. Parallel execution: update accounts and storage simultaneously
-    /// 5. Commit the changes
+    /// 2. Pre-resolve account trie paths of all touched accounts
+    /// 3. Prepare accounts to be updated
+    /// 4. Prepare required data to avoid borrowing conflicts for parallel execution
+    /// 5. Parallel execution: update accounts and storage simultaneously
+    /// 6. Commit the changes
     pub fn batch_update_and_commit(
         &mut self, 
         root_hash: B256, 
@@ -156,18 +190,36 @@ where
         storage_states: HashMap<B256, HashMap<B256, Option<U256>>>) -> 
         Result<(B256, Arc<MergedNodeSet>, HashMap<B256, B256>), TrieDBError> {
         
-        let update_prepare_start = Instant::now();
+        let phase = self.hook_phase(TriePhase::UpdatePrepare);
+
+        // Accounts and storage roots the root audit checks
+        let (audited_states, audited_storage): (HashMap<B256, Option<StateAccount>>, Vec<B256>) = if self.root_audit {
+            (states.clone(), storage_states.keys().chain(states_rebuild.iter()).copied().collect())
+        } else {
+            (HashMap::new(), Vec::new())
+        };
 
         // 1. Reset the trie db state
+        self.last_execution_witness = None;
         self.state_at(root_hash, difflayer)?;
 
-        // 2. Prepare accounts to be updated
+        // 2. Pre-resolve the account trie paths of all touched accounts, so shared
+        // branch nodes are loaded once instead of once per account update
+        let prefetch_start = Instant::now();
+        let mut hashed_addresses: Vec<B256> = states.keys().copied().collect();
+        hashed_addresses.extend(states_rebuild.iter().copied());
+        let resolved_nodes = self.account_trie.as_mut().unwrap().trie_mut()
+            .prefetch_paths(&hashed_addresses)?;
+        self.metrics.record_account_prefetch(prefetch_start.elapsed().as_secs_f64(), resolved_nodes);
+
+        // 3. Prepare accounts to be updated
         let mut update_accounts = HashMap::new();
         let mut update_accounts_with_storage = HashMap::new();
 
         for (hashed_address, new_account) in states {
             if new_account.is_none() {
                 update_accounts.insert(hashed_address, None);
+                self.wiped_storages.insert(hashed_address);
                 continue;
             }
 
@@ -190,53 +242,87 @@ where
             }
         }
         self.accounts_with_storage_trie = update_accounts_with_storage.clone();
+        // Storage of rebuilt accounts starts over, drop the old nodes on flush
+        self.wiped_storages.extend(states_rebuild.iter().copied());
 
-        self.metrics.record_update_prepare_duration(update_prepare_start.elapsed().as_secs_f64());
+        let update_prepare_elapsed = phase.finish();
+        self.metrics.record_update_prepare_duration(update_prepare_elapsed.as_secs_f64());
 
-        let update_start = Instant::now();
-        // 3. Prepare required data to avoid borrowing conflicts for parallel execution
+        let phase = self.hook_phase(TriePhase::Update);
+        // 4. Prepare required data to avoid borrowing conflicts for parallel execution
         let path_db_clone = self.path_db.clone();
         let difflayer_clone = self.difflayer.as_ref().map(|d| d.clone());
+        let hooks_clone = self.hooks.clone();
+        let recorder = self.node_recorder();
+        let bulk_storage_threshold = self.bulk_storage_threshold;
+        let verified_reads = self.verified_reads;
+        let slot_count_tracking = self.slot_count_tracking;
+        // Storage tries released by earlier blocks are re-targeted instead of built
+        let mut pooled_tries = self.take_pooled_storage_tries(storage_states.len());
+        let storage_updates: Vec<_> = storage_states
+            .into_iter()
+            .map(|(hashed_address, kvs)| (hashed_address, kvs, pooled_tries.pop()))
+            .collect();
+        let metrics = &self.metrics;
         let mut diff_account_storage_roots = HashMap::new();
 
-        // 4. Parallel execution: update accounts and storage simultaneously
-        let (account_result, storage_result): (Result<(), TrieDBError>, Result<HashMap<B256, StateTrie<DB>>, TrieDBError>) = rayon::join(
+        // 5. Parallel execution: update accounts and storage simultaneously
+        let (account_result, storage_result): (Result<(), TrieDBError>, Result<Vec<(B256, StateTrie<DB>, i64)>, TrieDBError>) = rayon::join(
             || {
-                // Task 1: Update account trie (serial execution)
-                // delete accounts that are being rebuilt, to collect deleted trie nodes
-                for hashed_address in states_rebuild {
-                    self.delete_account_with_hash_state(hashed_address)
-                        .map_err(|e| TrieDBError::Database(format!("Failed to delete rebuild account for hashed_address {:#x}, error: {}", hashed_address, e)))?;
-                }
+                // Task 1: Update account trie (parallel over the root subtries)
+                // delete accounts that are being rebuilt first, to collect deleted trie nodes
+                let mut account_updates: Vec<(B256, Option<StateAccount>)> = states_rebuild
+                    .into_iter()
+                    .map(|hashed_address| (hashed_address, None))
+                    .collect();
                 // update accounts that are being updated
                 for (hashed_address, account) in update_accounts {
-                    if let Some(account) = account {
-                        diff_account_storage_roots.insert(hashed_address, account.storage_root);
-                        self.update_account_with_hash_state(hashed_address, &account)
-                            .map_err(|e| TrieDBError::Database(format!("Failed to update account for hashed_address {:#x}, error: {}", hashed_address, e)))?;
-                    } else {
-                        diff_account_storage_roots.insert(hashed_address, alloy_trie::EMPTY_ROOT_HASH);
-                        self.delete_account_with_hash_state(hashed_address)
-                            .map_err(|e| TrieDBError::Database(format!("Failed to delete account for hashed_address {:#x}, error: {}", hashed_address, e)))?;
-                    }
+                    let storage_root = account.as_ref().map_or(alloy_trie::EMPTY_ROOT_HASH, |account| account.storage_root);
+                    diff_account_storage_roots.insert(hashed_address, storage_root);
+                    account_updates.push((hashed_address, account));
                 }
-                Ok(())
+                self.account_trie.as_mut().unwrap().update_accounts_with_hash_state(account_updates)
+                    .map_err(|e| TrieDBError::Database(format!("Failed to update account trie, error: {}", e)))
             },
             || {
                 // Task 2: Update storage states (parallel execution for addresses, serial for kvs)
-                storage_states
+                storage_updates
                     .into_par_iter()
-                    .map(|(hashed_address, kvs)| {
+                    .map(|(hashed_address, kvs, pooled_trie)| {
                         let account = update_accounts_with_storage.get(&hashed_address)
                             .ok_or_else(|| TrieDBError::Database(format!("Account not found for hashed_address: {:#x}", hashed_address)))?;
                         let storage_root = account.storage_root;
 
                         let id = SecureTrieId::new(storage_root)
                             .with_owner(hashed_address);
-                        let mut storage_trie = SecureTrieBuilder::new(path_db_clone.clone())
-                            .with_id(id)
-                            .build_with_difflayer(difflayer_clone.as_ref())
-                            .map_err(|e| TrieDBError::Database(format!("Failed to build storage trie for hashed_address {:#x}, error: {}", hashed_address, e)))?;
+                        let mut storage_trie = match pooled_trie {
+                            Some(mut storage_trie) => {
+                                metrics.increment_storage_trie_reuse_counter();
+                                storage_trie.reset(id, difflayer_clone.as_ref()).map(|_| storage_trie)
+                            }
+                            None => SecureTrieBuilder::new(path_db_clone.clone())
+                                .with_id(id)
+                                .with_hooks(hooks_clone.clone())
+                                .with_verified_reads(verified_reads)
+                                .with_recorder(recorder.clone())
+                                .build_with_difflayer(difflayer_clone.as_ref()),
+                        }
+                        .map_err(|e| TrieDBError::Database(format!("Failed to build storage trie for hashed_address {:#x}, error: {}", hashed_address, e)))?;
+
+                        // Slots have to be looked up before the update to be counted
+                        let slot_delta = if slot_count_tracking {
+                            slot_count_delta(&mut storage_trie, hashed_address, &kvs)?
+                        } else {
+                            0
+                        };
+
+                        // Accounts with many changed slots go through the bulk path
+                        if kvs.len() >= bulk_storage_threshold {
+                            metrics.increment_bulk_storage_update_counter();
+                            storage_trie.update_storages_with_hash_state(kvs.into_iter().collect())
+                                .map_err(|e| TrieDBError::Database(format!("Failed to update storage for hashed_address {:#x}, error: {}", hashed_address, e)))?;
+                            return Ok((hashed_address, storage_trie, slot_delta));
+                        }
 
                         // Serial execution for kvs within each address
                         for (hashed_key, new_value) in kvs {
@@ -249,27 +335,40 @@ where
                             }
                         }
 
-                        Ok((hashed_address, storage_trie))
+                        Ok((hashed_address, storage_trie, slot_delta))
                     })
                     .collect::<Result<Vec<_>, _>>()
-                    .map(|vec| vec.into_iter().collect())
             }
         );
         
         account_result?;
-        let update_storage = storage_result?;
-        self.storage_tries = update_storage;
+        let mut slot_deltas = HashMap::new();
+        self.storage_tries = storage_result?
+            .into_iter()
+            .map(|(hashed_address, storage_trie, slot_delta)| {
+                slot_deltas.insert(hashed_address, slot_delta);
+                (hashed_address, storage_trie)
+            })
+            .collect();
         self.updated_storage_roots.extend(diff_account_storage_roots);
+        self.slot_count_changes = self.slot_count_changes_from(slot_deltas);
 
         drop(path_db_clone);
         drop(difflayer_clone);
-        self.metrics.record_update_duration(update_start.elapsed().as_secs_f64());
+        drop(hooks_clone);
+        let update_elapsed = phase.finish();
+        self.metrics.record_update_duration(update_elapsed.as_secs_f64());
 
-        // 5. Commit the changes
-        let (root_hash, node_set) = self.commit(true)?;
+        // 6. Commit the changes
+        let (root_hash, node_set) = self.commit()?;
         let diff_storage_roots = self.updated_storage_roots.clone();
+        self.last_execution_witness = self.collect_execution_witness()?;
         self.clean();
 
+        if self.root_audit {
+            self.audit_committed_root(root_hash, difflayer, &node_set, &diff_storage_roots, &audited_states, &audited_storage)?;
+        }
+
         Ok((root_hash, node_set, diff_storage_roots))
     }
 }
@@ -0,0 +1,151 @@
+//! Independent state root re-computation for TrieDB commits.
+
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use alloy_primitives::{Bytes, B256, U256};
+use alloy_trie::proof::verify_proof;
+use alloy_trie::{HashBuilder, Nibbles};
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::account::StateAccount;
+use rust_eth_triedb_state_trie::node::{DiffLayers, MergedNodeSet};
+use tracing::error;
+
+use crate::triedb::{TrieDB, TrieDBError};
+
+/// Root audit configuration and re-computation
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Enables or disables the root audit mode.
+    ///
+    /// In audit mode every `batch_update_and_commit` checks the accounts it
+    /// touched against the new state root, independently of the trie
+    /// committer: the storage roots of accounts with changed storage are
+    /// re-computed from their slots with alloy-trie's `HashBuilder`, and a
+    /// proof of every touched account is verified against the root with the
+    /// expected account. A mismatch fails with `TrieDBError::RootDivergence`.
+    /// The cost grows with the touched accounts and their storage, not with
+    /// the state.
+    pub fn with_root_audit(mut self, enabled: bool) -> Self {
+        self.root_audit = enabled;
+        self
+    }
+
+    /// Returns whether the root audit mode is enabled.
+    pub fn root_audit(&self) -> bool {
+        self.root_audit
+    }
+
+    /// Re-computes the state root at `root_hash` from its leaves with a
+    /// `HashBuilder`.
+    ///
+    /// The storage roots of `storage_accounts` are re-computed from their
+    /// slots as well; other accounts contribute their stored storage root.
+    /// The current state is reset afterwards.
+    ///
+    /// Every account of the state is visited, so this is a debugging tool to
+    /// run on demand; commits are audited through the touched accounts only.
+    pub fn recompute_state_root(&mut self, root_hash: B256, difflayer: Option<&DiffLayers>, storage_accounts: &[B256]) -> Result<B256, TrieDBError> {
+        self.state_at(root_hash, difflayer)?;
+
+        let mut storage_roots = HashMap::with_capacity(storage_accounts.len());
+        for hashed_address in storage_accounts {
+            if self.get_account_with_hash_state(*hashed_address)?.is_some() {
+                storage_roots.insert(*hashed_address, self.recompute_storage_root(*hashed_address)?);
+            }
+        }
+
+        let mut builder = HashBuilder::default();
+        for item in self.iter_accounts()? {
+            let (hashed_address, mut account) = item?;
+            if let Some(storage_root) = storage_roots.get(&hashed_address) {
+                account.storage_root = *storage_root;
+            }
+            builder.add_leaf(Nibbles::unpack(hashed_address), &alloy_rlp::encode(account));
+        }
+        let root = builder.root();
+
+        self.clean();
+        Ok(root)
+    }
+
+    /// Re-computes the storage root of an account of the current state from its slots.
+    fn recompute_storage_root(&mut self, hashed_address: B256) -> Result<B256, TrieDBError> {
+        let mut builder = HashBuilder::default();
+        for item in self.iter_storage(hashed_address)? {
+            let (hashed_key, value) = item?;
+            builder.add_leaf(Nibbles::unpack(hashed_key), &alloy_rlp::encode(U256::from_be_slice(&value)));
+        }
+        Ok(builder.root())
+    }
+
+    /// Checks the accounts touched by a commit of `root_hash` on top of
+    /// `difflayer` against the root.
+    ///
+    /// `states` are the account changes of the commit and `storage_accounts`
+    /// the accounts whose storage changed, which get their storage roots
+    /// re-computed.
+    pub(crate) fn audit_committed_root(
+        &mut self,
+        root_hash: B256,
+        difflayer: Option<&DiffLayers>,
+        node_set: &MergedNodeSet,
+        diff_storage_roots: &HashMap<B256, B256>,
+        states: &HashMap<B256, Option<StateAccount>>,
+        storage_accounts: &[B256]) -> Result<(), TrieDBError> {
+
+        // The committed layer comes first, so it shadows `difflayer`
+        let committed = Arc::new(node_set.to_difflayer(diff_storage_roots.clone()));
+        let mut layers = DiffLayers::default();
+        layers.insert_difflayer(committed);
+        if let Some(difflayer) = difflayer {
+            layers.diff_layers.extend(difflayer.diff_layers.iter().cloned());
+        }
+        self.state_at(root_hash, Some(&layers))?;
+
+        let result = self.audit_touched_accounts(root_hash, states, storage_accounts);
+        self.clean();
+        if let Err(TrieDBError::RootDivergence { committed, hashed_address, reason }) = &result {
+            self.metrics.increment_root_audit_mismatch_counter();
+            error!(target: "triedb::root_audit", "State root divergence, committed: {:#x}, account: {:#x}, {}", committed, hashed_address, reason);
+        }
+        result
+    }
+
+    /// Verifies a proof of every touched account of the current state against `root_hash`.
+    fn audit_touched_accounts(
+        &mut self,
+        root_hash: B256,
+        states: &HashMap<B256, Option<StateAccount>>,
+        storage_accounts: &[B256]) -> Result<(), TrieDBError> {
+        let mut touched: Vec<B256> = states.keys().chain(storage_accounts.iter()).copied().collect();
+        touched.sort_unstable();
+        touched.dedup();
+
+        for hashed_address in touched {
+            let committed = self.get_account_with_hash_state(hashed_address)?;
+            let storage_root = match committed {
+                Some(_) if storage_accounts.contains(&hashed_address) => Some(self.recompute_storage_root(hashed_address)?),
+                Some(account) => Some(account.storage_root),
+                None => None,
+            };
+            let expected = match (states.get(&hashed_address), storage_root) {
+                (Some(None), _) | (_, None) => None,
+                (Some(Some(account)), Some(storage_root)) => Some(StateAccount { storage_root, ..*account }),
+                (None, Some(storage_root)) => committed.map(|account| StateAccount { storage_root, ..account }),
+            };
+
+            let proof: Vec<Bytes> = self.account_trie.as_ref().unwrap()
+                .prove_with_hash_state(hashed_address)?
+                .into_iter()
+                .map(Bytes::from)
+                .collect();
+            verify_proof(root_hash, Nibbles::unpack(hashed_address), expected.map(alloy_rlp::encode), &proof)
+                .map_err(|e| TrieDBError::RootDivergence { committed: root_hash, hashed_address, reason: e.to_string() })?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,89 @@
+//! Storage slot count tracking and queries for TrieDB.
+
+use std::collections::HashMap;
+
+use alloy_primitives::{B256, U256};
+use rust_eth_triedb_common::{SlotCountChange, TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::state_trie::StateTrie;
+use rust_eth_triedb_state_trie::SecureTrieTrait;
+
+use crate::triedb::{TrieDB, TrieDBError};
+
+/// Storage slot count configuration and queries
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Enables or disables tracking the storage slot count of accounts.
+    ///
+    /// When enabled, every changed slot is looked up before the update to
+    /// tell created and cleared slots apart, and diff layers returned by
+    /// `commit_hashed_post_state` carry the resulting count changes, which the
+    /// database applies once the layer is flushed. Counts are approximate:
+    /// slots written before tracking was enabled are only counted once the
+    /// account's storage is wiped.
+    pub fn with_slot_count_tracking(mut self, enabled: bool) -> Self {
+        self.slot_count_tracking = enabled;
+        self
+    }
+
+    /// Returns whether storage slot counts are tracked.
+    pub fn slot_count_tracking(&self) -> bool {
+        self.slot_count_tracking
+    }
+
+    /// Returns the approximate number of storage slots of the persisted
+    /// account `hashed_address`, e.g. to budget a storage wipe or prefetch
+    /// without walking its storage trie.
+    ///
+    /// Only flushed state is covered, see `with_slot_count_tracking`.
+    pub fn storage_slot_count(&self, hashed_address: B256) -> Result<Option<u64>, TrieDBError> {
+        self.path_db.get_storage_slot_count(hashed_address)
+            .map_err(|e| TrieDBError::provider(format!("Failed to read slot count for {:#x}", hashed_address), e))
+    }
+
+    /// Turns the slot count deltas of the storage tries updated by a block
+    /// into the count changes of its diff layer, empty if tracking is disabled.
+    ///
+    /// Accounts whose storage is wiped by the block restart from their delta.
+    pub(crate) fn slot_count_changes_from(&self, slot_deltas: HashMap<B256, i64>) -> HashMap<B256, SlotCountChange> {
+        if !self.slot_count_tracking {
+            return HashMap::new();
+        }
+        let mut changes: HashMap<B256, SlotCountChange> = self.wiped_storages
+            .iter()
+            .map(|hashed_address| {
+                let count = slot_deltas.get(hashed_address).copied().unwrap_or_default().max(0) as u64;
+                (*hashed_address, SlotCountChange::Reset(count))
+            })
+            .collect();
+        for (hashed_address, delta) in slot_deltas {
+            if delta != 0 {
+                changes.entry(hashed_address).or_insert(SlotCountChange::Delta(delta));
+            }
+        }
+        changes
+    }
+}
+
+/// Counts the slots created minus the slots cleared by applying `kvs` to
+/// `storage_trie`, which must not be updated yet.
+pub(crate) fn slot_count_delta<DB>(
+    storage_trie: &mut StateTrie<DB>,
+    hashed_address: B256,
+    kvs: &HashMap<B256, Option<U256>>,
+) -> Result<i64, TrieDBError>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    let mut delta = 0i64;
+    for (hashed_key, new_value) in kvs {
+        let existed = storage_trie.get_storage_with_hash_state(hashed_address, *hashed_key)
+            .map_err(|e| TrieDBError::Database(format!("Failed to read storage for hashed_address {:#x}, hashed_key {:#x}, error: {}", hashed_address, hashed_key, e)))?
+            .is_some();
+        delta += new_value.is_some() as i64 - existed as i64;
+    }
+    Ok(delta)
+}
@@ -0,0 +1,155 @@
+//! Consistency check of the flat state snapshot against the account trie.
+
+use alloy_primitives::{B256, U256};
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource};
+use tracing::warn;
+
+use crate::triedb::{TrieDB, TrieDBError};
+
+/// Number of snapshot entries read per range query while verifying.
+const VERIFY_PAGE_SIZE: usize = 1024;
+
+/// An account whose flat snapshot disagrees with the trie.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SnapshotMismatch {
+    /// The storage root of the account snapshot entry differs from the
+    /// account trie, which has no account if `trie` is `None`.
+    StorageRoot {
+        /// Hashed address of the account
+        hashed_address: B256,
+        /// Storage root in the account snapshot
+        snapshot: B256,
+        /// Storage root in the account trie
+        trie: Option<B256>,
+    },
+    /// The flat storage slots of the account differ from its storage trie.
+    Storage {
+        /// Hashed address of the account
+        hashed_address: B256,
+        /// First hashed key whose slot is missing from one side or differs
+        hashed_key: B256,
+    },
+}
+
+impl SnapshotMismatch {
+    /// Hashed address of the mismatched owner.
+    pub fn hashed_address(&self) -> B256 {
+        match self {
+            Self::StorageRoot { hashed_address, .. } | Self::Storage { hashed_address, .. } => *hashed_address,
+        }
+    }
+}
+
+/// Result of [`TrieDB::verify_against_trie`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SnapshotVerifyReport {
+    /// Number of account snapshot entries checked
+    pub accounts_checked: u64,
+    /// Number of storage snapshot entries checked
+    pub slots_checked: u64,
+    /// Mismatches in hashed address order, at most one per account
+    pub mismatches: Vec<SnapshotMismatch>,
+}
+
+impl SnapshotVerifyReport {
+    /// Whether the snapshot matched the trie.
+    pub fn is_ok(&self) -> bool {
+        self.mismatches.is_empty()
+    }
+
+    /// Hashed addresses of the mismatched owners.
+    pub fn mismatched_owners(&self) -> Vec<B256> {
+        self.mismatches.iter().map(SnapshotMismatch::hashed_address).collect()
+    }
+}
+
+/// Snapshot verification
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Cross-checks the flat snapshot of the database against the account
+    /// trie at `root`.
+    ///
+    /// The storage root of every account snapshot entry is compared with the
+    /// account at `root`. With `check_slots`, the flat storage slots of each
+    /// account whose storage root matched are also compared with its storage
+    /// trie, both ways. Neither side is modified, so this can run on a node
+    /// suspected of divergence, e.g. after a reorg, before deciding to
+    /// regenerate the snapshot. The current state is reset afterwards.
+    ///
+    /// Only entries of the snapshot are visited: trie accounts without a
+    /// snapshot entry are not reported.
+    pub fn verify_against_trie(&mut self, root: B256, check_slots: bool) -> Result<SnapshotVerifyReport, TrieDBError> {
+        self.state_at(root, None)?;
+        let report = self.verify_snapshot_accounts(check_slots);
+        self.clean();
+
+        let report = report?;
+        if !report.is_ok() {
+            warn!(
+                target: "triedb::snapshot",
+                "Snapshot diverges from the trie at {:#x} for {} of {} accounts",
+                root, report.mismatches.len(), report.accounts_checked
+            );
+        }
+        Ok(report)
+    }
+
+    fn verify_snapshot_accounts(&mut self, check_slots: bool) -> Result<SnapshotVerifyReport, TrieDBError> {
+        let mut report = SnapshotVerifyReport::default();
+        let mut start = Some(B256::ZERO);
+        while let Some(start_hash) = start {
+            let accounts = self.iter_account_snapshot(start_hash, VERIFY_PAGE_SIZE)?;
+            start = next_page_start(&accounts);
+            for (hashed_address, account) in accounts {
+                report.accounts_checked += 1;
+                let trie = self.get_account_with_hash_state(hashed_address)?.map(|account| account.storage_root);
+                if trie != Some(account.storage_root) {
+                    report.mismatches.push(SnapshotMismatch::StorageRoot { hashed_address, snapshot: account.storage_root, trie });
+                } else if check_slots {
+                    if let Some(hashed_key) = self.verify_snapshot_slots(hashed_address, &mut report.slots_checked)? {
+                        report.mismatches.push(SnapshotMismatch::Storage { hashed_address, hashed_key });
+                    }
+                }
+            }
+        }
+        Ok(report)
+    }
+
+    /// Returns the first hashed key at which the flat storage slots of
+    /// `hashed_address` and its storage trie differ.
+    fn verify_snapshot_slots(&mut self, hashed_address: B256, slots_checked: &mut u64) -> Result<Option<B256>, TrieDBError> {
+        let trie_slots = self.iter_storage(hashed_address)?
+            .map(|item| item.map(|(hashed_key, value)| (hashed_key, U256::from_be_slice(&value))))
+            .collect::<Result<Vec<_>, _>>()?;
+
+        let mut position = 0;
+        let mut start = Some(B256::ZERO);
+        while let Some(start_hash) = start {
+            let slots = self.iter_storage_snapshot(hashed_address, start_hash, VERIFY_PAGE_SIZE)?;
+            start = next_page_start(&slots);
+            for (hashed_key, value) in slots {
+                *slots_checked += 1;
+                match trie_slots.get(position) {
+                    Some(&(trie_key, trie_value)) if trie_key == hashed_key && trie_value == value => position += 1,
+                    // Both sides are in hashed key order, the smaller key is missing from the other
+                    Some(&(trie_key, _)) => return Ok(Some(trie_key.min(hashed_key))),
+                    None => return Ok(Some(hashed_key)),
+                }
+            }
+        }
+        Ok(trie_slots.get(position).map(|(hashed_key, _)| *hashed_key))
+    }
+}
+
+/// Start of the range after a full page of snapshot entries, `None` once the
+/// range is complete.
+fn next_page_start<T>(page: &[(B256, T)]) -> Option<B256> {
+    if page.len() < VERIFY_PAGE_SIZE {
+        return None;
+    }
+    let (last, _) = page.last()?;
+    U256::from_be_bytes(last.0).checked_add(U256::from(1)).map(|next| B256::new(next.to_be_bytes()))
+}
@@ -381,3 +381,1204 @@ fn test_multiple_accounts_update() {
     
 }
 
+
+#[test]
+#[serial]
+fn test_latest_persist_state_cache_and_notify() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db);
+
+    let mut receiver = triedb.subscribe_persist_state();
+    assert_eq!(*receiver.borrow(), None);
+    assert_eq!(triedb.latest_persist_state().unwrap(), (0, EMPTY_ROOT_HASH));
+
+    let state_root = keccak256(b"state_root");
+    triedb.flush(7, state_root, &None).unwrap();
+
+    assert!(receiver.has_changed().unwrap());
+    assert_eq!(*receiver.borrow_and_update(), Some((7, state_root)));
+    assert_eq!(triedb.latest_persist_state().unwrap(), (7, state_root));
+
+    // Clones share the cached state
+    let cloned = triedb.clone();
+    assert_eq!(cloned.latest_persist_state().unwrap(), (7, state_root));
+
+    // States persisted through other handles of the database are observed
+    let mut other = TrieDB::new(triedb.get_mut_path_db_ref().clone());
+    let next_root = keccak256(b"next_root");
+    other.flush(8, next_root, &None).unwrap();
+    assert_eq!(triedb.latest_persist_state().unwrap(), (8, next_root));
+    assert_eq!(cloned.latest_persist_state().unwrap(), (8, next_root));
+}
+
+#[test]
+#[serial]
+fn test_commit_hashed_post_state_with_audit() {
+    use crate::TrieDBHashedPostState;
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db.clone());
+
+    let mut post_state = TrieDBHashedPostState::default();
+    for i in 0..100u64 {
+        let account = StateAccount::default().with_nonce(i).with_balance(U256::from(i));
+        post_state.states.insert(keccak256(i.to_le_bytes()), Some(account));
+    }
+
+    let (root_hash, difflayer) = triedb
+        .commit_hashed_post_state_with_audit(1, EMPTY_ROOT_HASH, None, &post_state)
+        .unwrap();
+    assert!(difflayer.is_some());
+
+    // The record is written together with the layer
+    assert!(path_db.get_audit_records(1).unwrap().is_empty());
+    triedb.flush(1, root_hash, &difflayer).unwrap();
+    let records = path_db.get_audit_records(1).unwrap();
+    assert_eq!(records.len(), 1);
+    let record = records[0];
+    assert_eq!(record.block_number, 1);
+    assert_eq!(record.root_before, EMPTY_ROOT_HASH);
+    assert_eq!(record.root_after, root_hash);
+    assert_eq!(record.leaves, 100);
+    assert!(record.updates > 0);
+    assert!(path_db.get_audit_records(2).unwrap().is_empty());
+}
+
+#[test]
+#[serial]
+fn test_get_storage_with_flat_storage() {
+    use crate::{FlatReadMode, FlatStorageReader};
+
+    #[derive(Debug)]
+    struct MockFlatStorage(HashMap<(B256, B256), Vec<u8>>);
+
+    impl FlatStorageReader for MockFlatStorage {
+        fn get_flat_storage(&self, hashed_address: B256, hashed_key: B256) -> Result<Option<Vec<u8>>, TrieDBError> {
+            Ok(self.0.get(&(hashed_address, hashed_key)).cloned())
+        }
+    }
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+
+    let hashed_address = keccak256(b"account");
+    let (slot_a, slot_b) = (keccak256(b"slot_a"), keccak256(b"slot_b"));
+
+    let mut states = HashMap::new();
+    states.insert(hashed_address, Some(StateAccount::default().with_nonce(1)));
+    let mut storage_states = HashMap::new();
+    storage_states.insert(hashed_address, HashMap::from([(slot_a, Some(U256::from(7))), (slot_b, Some(U256::from(8)))]));
+
+    let mut triedb = TrieDB::new(path_db);
+    let (root_hash, merged_node_set, diff_storage_roots) = triedb
+        .batch_update_and_commit(EMPTY_ROOT_HASH, None, states, HashSet::new(), storage_states)
+        .unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*merged_node_set.to_diff_nodes()).clone(), diff_storage_roots));
+    triedb.flush(1, root_hash, &Some(difflayer)).unwrap();
+
+    // The flat table deliberately disagrees with the trie for slot_a and doesn't cover slot_b
+    let flat = MockFlatStorage(HashMap::from([((hashed_address, slot_a), vec![0x42])]));
+    let mut triedb = triedb.with_flat_storage(Arc::new(flat), FlatReadMode::Enabled);
+
+    triedb.state_at(root_hash, None).unwrap();
+    assert_eq!(triedb.get_storage_with_hash_state(hashed_address, slot_a).unwrap(), Some(vec![0x42]));
+    assert_eq!(triedb.get_storage_with_hash_state(hashed_address, slot_b).unwrap(), Some(vec![8]));
+
+    // Cross-check mode always returns the trie value
+    triedb.set_flat_read_mode(FlatReadMode::CrossCheck);
+    assert_eq!(triedb.get_storage_with_hash_state(hashed_address, slot_a).unwrap(), Some(vec![7]));
+
+    // Uncommitted diff layers bypass the flat table
+    triedb.set_flat_read_mode(FlatReadMode::Enabled);
+    triedb.state_at(root_hash, Some(&DiffLayers::default())).unwrap();
+    assert_eq!(triedb.get_storage_with_hash_state(hashed_address, slot_a).unwrap(), Some(vec![7]));
+
+    // So do reads at a root other than the persisted one
+    triedb.state_at(EMPTY_ROOT_HASH, None).unwrap();
+    assert_eq!(triedb.get_storage_with_hash_state(hashed_address, slot_a).unwrap(), None);
+}
+
+#[test]
+#[serial]
+fn test_flat_storage_from_path_db_snapshot() {
+    use rust_eth_triedb_common::TrieDatabase;
+    use crate::{FlatReadMode, StorageSnapshotReader};
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+
+    let hashed_address = keccak256(b"account");
+    let values = [U256::from(1), U256::from(0x4200), U256::MAX, U256::from(1) << 255];
+    let slots: Vec<B256> = (0..values.len() as u64).map(|i| keccak256(i.to_be_bytes())).collect();
+
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(hashed_address, Some(StateAccount::default().with_nonce(1)));
+    post_state.storage_states.insert(hashed_address, slots.iter().zip(values).map(|(slot, value)| (*slot, Some(value))).collect());
+    let mut triedb = TrieDB::new(path_db.clone());
+    let (root, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state).unwrap();
+    triedb.flush(1, root, &difflayer).unwrap();
+    for (slot, value) in slots.iter().zip(values) {
+        triedb.put_storage_snapshot(hashed_address, *slot, value).unwrap();
+    }
+
+    // The trimmed big-endian snapshot values decode to what the trie holds
+    let mut triedb = triedb.with_flat_storage(Arc::new(StorageSnapshotReader::new(path_db.clone())), FlatReadMode::CrossCheck);
+    triedb.state_at(root, None).unwrap();
+    let before = crate::triedb_metrics::snapshot().triedb;
+    let cross_checked: Vec<_> = slots.iter().map(|slot| triedb.get_storage_with_hash_state(hashed_address, *slot).unwrap()).collect();
+    let after = crate::triedb_metrics::snapshot().triedb;
+    assert_eq!(after.flat_storage_hit_counter, before.flat_storage_hit_counter + slots.len() as u64);
+    assert_eq!(after.flat_storage_mismatch_counter, before.flat_storage_mismatch_counter);
+
+    triedb.set_flat_read_mode(FlatReadMode::Enabled);
+    let flat: Vec<_> = slots.iter().map(|slot| triedb.get_storage_with_hash_state(hashed_address, *slot).unwrap()).collect();
+    assert_eq!(flat, cross_checked);
+    assert_eq!(flat[1], Some(vec![0x42, 0x00]));
+
+    // Slots without a snapshot entry fall back to the trie
+    path_db.put_storage_snapshot(hashed_address, slots[0], None).unwrap();
+    assert_eq!(triedb.get_storage_with_hash_state(hashed_address, slots[0]).unwrap(), Some(vec![1]));
+}
+
+#[test]
+#[serial]
+fn test_triedb_hooks() {
+    use std::sync::Mutex;
+    use std::time::Duration;
+    use rust_eth_triedb_common::{NodeReadSource, TrieHooks, TriePhase};
+
+    #[derive(Debug, Default)]
+    struct RecordingHooks {
+        phases: Mutex<Vec<(TriePhase, bool)>>,
+        node_reads: Mutex<Vec<NodeReadSource>>,
+    }
+
+    impl TrieHooks for RecordingHooks {
+        fn on_phase_start(&self, phase: TriePhase) {
+            self.phases.lock().unwrap().push((phase, true));
+        }
+
+        fn on_phase_end(&self, phase: TriePhase, _elapsed: Duration) {
+            self.phases.lock().unwrap().push((phase, false));
+        }
+
+        fn on_node_read(&self, _owner: B256, _path: &[u8], source: NodeReadSource, _size: usize) {
+            self.node_reads.lock().unwrap().push(source);
+        }
+    }
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+
+    let hooks = Arc::new(RecordingHooks::default());
+    let mut triedb = TrieDB::new(path_db);
+    triedb.set_hooks(hooks.clone());
+
+    let mut states = HashMap::new();
+    for i in 0..10u64 {
+        states.insert(keccak256(i.to_be_bytes()), Some(StateAccount::default().with_nonce(i + 1)));
+    }
+    let (root_hash, merged_node_set, diff_storage_roots) = triedb
+        .batch_update_and_commit(EMPTY_ROOT_HASH, None, states, HashSet::new(), HashMap::new())
+        .unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*merged_node_set.to_diff_nodes()).clone(), diff_storage_roots));
+    triedb.flush(1, root_hash, &Some(difflayer)).unwrap();
+
+    let phases = hooks.phases.lock().unwrap().clone();
+    assert_eq!(phases, vec![
+        (TriePhase::UpdatePrepare, true), (TriePhase::UpdatePrepare, false),
+        (TriePhase::Update, true), (TriePhase::Update, false),
+        (TriePhase::Hash, true), (TriePhase::Hash, false),
+        (TriePhase::Commit, true), (TriePhase::Commit, false),
+        (TriePhase::Flush, true), (TriePhase::Flush, false),
+    ]);
+
+    // Reads of persisted nodes are reported with their source
+    triedb.state_at(root_hash, None).unwrap();
+    assert!(triedb.get_account_with_hash_state(keccak256(3u64.to_be_bytes())).unwrap().is_some());
+    let node_reads = hooks.node_reads.lock().unwrap();
+    assert!(!node_reads.is_empty());
+    assert!(node_reads.iter().all(|source| *source == NodeReadSource::Database));
+    drop(node_reads);
+
+    // A phase cut short by an error still reports its end
+    hooks.phases.lock().unwrap().clear();
+    let missing_root = B256::repeat_byte(0x11);
+    assert!(triedb.batch_update_and_commit(missing_root, None, HashMap::new(), HashSet::new(), HashMap::new()).is_err());
+    assert_eq!(*hooks.phases.lock().unwrap(), vec![(TriePhase::UpdatePrepare, true), (TriePhase::UpdatePrepare, false)]);
+}
+
+#[test]
+#[serial]
+fn test_commit_config_leaf_collection() {
+    use crate::CommitConfig;
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+
+    let hashed_address = keccak256(b"account");
+    let leaf_counts = |commit_config: CommitConfig| {
+        let mut states = HashMap::new();
+        states.insert(hashed_address, Some(StateAccount::default().with_nonce(1)));
+        let mut storage_states = HashMap::new();
+        storage_states.insert(hashed_address, HashMap::from([(keccak256(b"slot"), Some(U256::from(1)))]));
+
+        let mut triedb = TrieDB::new(path_db.clone()).with_commit_config(commit_config);
+        let (_, merged_node_set, _) = triedb
+            .batch_update_and_commit(EMPTY_ROOT_HASH, None, states, HashSet::new(), storage_states)
+            .unwrap();
+        let account_leaves = merged_node_set.sets.get(&B256::ZERO).map_or(0, |set| set.leaf_count());
+        let storage_leaves = merged_node_set.sets.get(&hashed_address).map_or(0, |set| set.leaf_count());
+        (account_leaves, storage_leaves)
+    };
+
+    assert_eq!(leaf_counts(CommitConfig::default()), (1, 0));
+    assert_eq!(leaf_counts(CommitConfig { collect_account_leaves: true, collect_storage_leaves: true }), (1, 1));
+    assert_eq!(leaf_counts(CommitConfig { collect_account_leaves: false, collect_storage_leaves: false }), (0, 0));
+}
+
+#[test]
+#[serial]
+fn test_state_at_with_overrides() {
+    use crate::{AccountOverride, StateOverrides};
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+
+    let (alice, bob) = (keccak256(b"alice"), keccak256(b"bob"));
+    let (slot_a, slot_b) = (keccak256(b"slot_a"), keccak256(b"slot_b"));
+
+    let mut states = HashMap::new();
+    states.insert(alice, Some(StateAccount::default().with_nonce(1).with_balance(U256::from(100))));
+    let mut storage_states = HashMap::new();
+    storage_states.insert(alice, HashMap::from([(slot_a, Some(U256::from(1))), (slot_b, Some(U256::from(2)))]));
+
+    let mut triedb = TrieDB::new(path_db);
+    let (root_hash, merged_node_set, diff_storage_roots) = triedb
+        .batch_update_and_commit(EMPTY_ROOT_HASH, None, states, HashSet::new(), storage_states)
+        .unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*merged_node_set.to_diff_nodes()).clone(), diff_storage_roots));
+    triedb.flush(1, root_hash, &Some(difflayer)).unwrap();
+
+    let encoded = |value: u8| Some(vec![value]);
+
+    let mut overrides = StateOverrides::new();
+    overrides.insert(alice, AccountOverride {
+        balance: Some(U256::from(5)),
+        state_diff: HashMap::from([(slot_a, U256::from(9))]),
+        ..Default::default()
+    });
+    overrides.insert(bob, AccountOverride {
+        nonce: Some(7),
+        state: Some(HashMap::from([(slot_b, U256::from(3))])),
+        ..Default::default()
+    });
+
+    triedb.state_at(root_hash, None).unwrap();
+    let mut overridden = triedb.state_at_with_overrides(root_hash, None, &overrides).unwrap();
+    assert_ne!(overridden.calculate_hash().unwrap(), root_hash);
+
+    let alice_account = overridden.get_account_with_hash_state(alice).unwrap().unwrap();
+    assert_eq!((alice_account.nonce, alice_account.balance), (1, U256::from(5)));
+    assert_eq!(overridden.get_storage_with_hash_state(alice, slot_a).unwrap(), encoded(9));
+    assert_eq!(overridden.get_storage_with_hash_state(alice, slot_b).unwrap(), encoded(2));
+    assert_eq!(overridden.get_account_with_hash_state(bob).unwrap().unwrap().nonce, 7);
+    assert_eq!(overridden.get_storage_with_hash_state(bob, slot_b).unwrap(), encoded(3));
+
+    // Neither the trie db the overrides were taken from nor the persisted state moved
+    assert_eq!(triedb.root_hash, root_hash);
+    assert_eq!(triedb.get_account_with_hash_state(alice).unwrap().unwrap().balance, U256::from(100));
+    assert!(triedb.get_account_with_hash_state(bob).unwrap().is_none());
+    assert_eq!(triedb.get_storage_with_hash_state(alice, slot_a).unwrap(), encoded(1));
+}
+
+#[test]
+#[serial]
+fn test_read_set() {
+    use crate::ReadSet;
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+
+    let mut states = HashMap::new();
+    let mut storage_states = HashMap::new();
+    for i in 0..20u64 {
+        let hashed_address = keccak256(i.to_be_bytes());
+        states.insert(hashed_address, Some(StateAccount::default().with_nonce(i + 1)));
+        if i % 4 == 0 {
+            let slots = (0..10u64).map(|j| (keccak256(j.to_be_bytes()), Some(U256::from(j + 1)))).collect();
+            storage_states.insert(hashed_address, slots);
+        }
+    }
+
+    let mut triedb = TrieDB::new(path_db);
+    let (root_hash, merged_node_set, diff_storage_roots) = triedb
+        .batch_update_and_commit(EMPTY_ROOT_HASH, None, states, HashSet::new(), storage_states)
+        .unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*merged_node_set.to_diff_nodes()).clone(), diff_storage_roots));
+    triedb.flush(1, root_hash, &Some(difflayer)).unwrap();
+    triedb.state_at(root_hash, None).unwrap();
+
+    let (with_storage, without_storage, missing) = (keccak256(0u64.to_be_bytes()), keccak256(1u64.to_be_bytes()), keccak256(b"missing"));
+    let slot = keccak256(3u64.to_be_bytes());
+    let reads = ReadSet {
+        accounts: vec![with_storage, without_storage, missing],
+        slots: vec![(with_storage, slot), (with_storage, keccak256(b"missing")), (missing, slot)],
+        with_proofs: true,
+    };
+    let results = triedb.read_set(&reads).unwrap();
+    assert_eq!(results.root_hash, root_hash);
+    assert_eq!(results.accounts.len(), 3);
+    assert_eq!(results.slots.len(), 3);
+
+    let account = results.accounts[&with_storage].account.unwrap();
+    assert_eq!(account.nonce, 1);
+    assert_eq!(results.accounts[&without_storage].account.unwrap().nonce, 2);
+    assert!(results.accounts[&missing].account.is_none());
+    assert_eq!(results.slots[&(with_storage, slot)].value, Some(vec![4]));
+    assert_eq!(results.slots[&(with_storage, keccak256(b"missing"))].value, None);
+    assert_eq!(results.slots[&(missing, slot)].value, None);
+
+    // Proofs start at the account trie and storage trie roots
+    for account_read in results.accounts.values() {
+        assert_eq!(keccak256(&account_read.proof.as_ref().unwrap()[0]), root_hash);
+    }
+    let storage_proof = results.slots[&(with_storage, slot)].proof.as_ref().unwrap();
+    assert_eq!(keccak256(&storage_proof[0]), account.storage_root);
+    assert!(results.slots[&(missing, slot)].proof.as_ref().unwrap().is_empty());
+
+    // Values match the regular read path
+    assert_eq!(triedb.get_storage_with_hash_state(with_storage, slot).unwrap(), Some(vec![4]));
+}
+
+#[test]
+#[serial]
+fn test_iteration_across_difflayers() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let hashed_address = |i: u64| keccak256(i.to_be_bytes());
+    let slot = |j: u64| keccak256(j.to_be_bytes());
+
+    // Block 1 is flushed
+    let states = (0..50u64).map(|i| (hashed_address(i), Some(StateAccount::default().with_nonce(i + 1)))).collect();
+    let storage_states = HashMap::from([(hashed_address(0), (0..20u64).map(|j| (slot(j), Some(U256::from(j + 1)))).collect())]);
+    let mut triedb = TrieDB::new(path_db.clone());
+    let (root_1, node_set, storage_roots) = triedb
+        .batch_update_and_commit(EMPTY_ROOT_HASH, None, states, HashSet::new(), storage_states)
+        .unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*node_set.to_diff_nodes()).clone(), storage_roots));
+    triedb.flush(1, root_1, &Some(difflayer)).unwrap();
+
+    // Block 2 deletes and adds accounts and slots but stays in memory
+    let mut states: HashMap<B256, Option<StateAccount>> = (0..10u64).map(|i| (hashed_address(i + 1), None)).collect();
+    states.extend((50..60u64).map(|i| (hashed_address(i), Some(StateAccount::default().with_nonce(i + 1)))));
+    states.insert(hashed_address(0), Some(StateAccount::default().with_nonce(1)));
+    let mut slots: HashMap<B256, Option<U256>> = (0..10u64).map(|j| (slot(j), None)).collect();
+    slots.insert(slot(100), Some(U256::from(7)));
+    let storage_states = HashMap::from([(hashed_address(0), slots)]);
+    let (root_2, node_set, storage_roots) = triedb
+        .batch_update_and_commit(root_1, None, states, HashSet::new(), storage_states)
+        .unwrap();
+    let mut difflayers = DiffLayers::default();
+    difflayers.insert_difflayer(Arc::new(DiffLayer::new((*node_set.to_diff_nodes()).clone(), storage_roots)));
+
+    triedb.state_at(root_2, Some(&difflayers)).unwrap();
+    let accounts: Vec<(B256, StateAccount)> = triedb.iter_accounts().unwrap().map(Result::unwrap).collect();
+    let mut expected: Vec<B256> = (0..1u64).chain(11..60).map(hashed_address).collect();
+    expected.sort();
+    assert_eq!(accounts.iter().map(|(address, _)| *address).collect::<Vec<_>>(), expected);
+    for (address, account) in &accounts {
+        assert_eq!(triedb.get_account_with_hash_state(*address).unwrap().as_ref(), Some(account));
+    }
+
+    let storage: Vec<(B256, Vec<u8>)> = triedb.iter_storage(hashed_address(0)).unwrap().map(Result::unwrap).collect();
+    assert_eq!(storage.len(), 11);
+    assert!(storage.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    for (key, value) in &storage {
+        assert_eq!(triedb.get_storage_with_hash_state(hashed_address(0), *key).unwrap().as_ref(), Some(value));
+    }
+    assert!(storage.contains(&(slot(100), vec![7])));
+
+    // Raw trie nodes agree with the difflayer, deleted nodes are masked
+    let nodes: Vec<(Vec<u8>, Vec<u8>)> = path_db.iter_trie_nodes_with_difflayers(b"", &difflayers).unwrap().map(Result::unwrap).collect();
+    assert!(nodes.windows(2).all(|pair| pair[0].0 < pair[1].0));
+    for (key, node) in &difflayers.diff_layers[0].diff_nodes {
+        let found = nodes.iter().find(|(node_key, _)| node_key == key).map(|(_, blob)| blob);
+        if node.is_deleted() {
+            assert!(found.is_none());
+        } else {
+            assert_eq!(found.map(Vec::as_slice), node.blob.as_deref().map(|blob| &blob[..]));
+        }
+    }
+}
+
+#[test]
+#[serial]
+fn test_replay_verify() {
+    use crate::TrieDBHashedPostState;
+    use rust_eth_triedb_common::TrieDatabase;
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let post_state = |block: u64| {
+        let mut post_state = TrieDBHashedPostState::default();
+        for i in 0..20u64 {
+            let account = StateAccount::default().with_nonce(block).with_balance(U256::from(i));
+            post_state.states.insert(keccak256((block * 10 + i).to_be_bytes()), Some(account));
+        }
+        post_state
+    };
+
+    // Block 1 is flushed, blocks 2 and 3 stay in memory
+    let mut triedb = TrieDB::new(path_db.clone());
+    let (root_1, difflayer) = triedb.commit_hashed_post_state_with_audit(1, EMPTY_ROOT_HASH, None, &post_state(1)).unwrap();
+    triedb.flush(1, root_1, &difflayer).unwrap();
+    let mut difflayers = DiffLayers::default();
+    let mut root_hash = root_1;
+    for block in 2..=3u64 {
+        let (root, difflayer) = triedb
+            .commit_hashed_post_state_with_audit(block, root_hash, Some(&difflayers), &post_state(block))
+            .unwrap();
+        // Not flushed, so the record is logged by hand
+        let difflayer = difflayer.unwrap();
+        path_db.put_audit_record(&difflayer.audit_record.unwrap()).unwrap();
+        difflayers.diff_layers.insert(0, difflayer);
+        root_hash = root;
+    }
+
+    let mut replay = TrieDB::new(path_db.clone());
+    let report = replay.replay_verify(2, 3, &[post_state(2), post_state(3)]).unwrap();
+    assert!(report.is_ok());
+    assert_eq!(report.verified, 2);
+
+    // A post-state differing from the committed one is reported at its block
+    let mut tampered = post_state(3);
+    tampered.states.insert(keccak256(b"extra"), Some(StateAccount::default().with_nonce(1)));
+    let report = replay.replay_verify(2, 3, &[post_state(2), tampered]).unwrap();
+    assert_eq!(report.verified, 1);
+    let mismatch = report.mismatch.unwrap();
+    assert_eq!(mismatch.block_number, 3);
+    assert_eq!(mismatch.expected, root_hash);
+    assert_ne!(mismatch.computed, root_hash);
+
+    assert!(matches!(replay.replay_verify(2, 3, &[post_state(2)]), Err(TrieDBError::InvalidData(_))));
+    assert!(matches!(replay.replay_verify(4, 4, &[post_state(4)]), Err(TrieDBError::InvalidData(_))));
+}
+
+#[test]
+#[serial]
+fn test_self_destruct_wipes_storage_trie() {
+    use rust_eth_triedb_state_trie::encoding::storage_trie_node_range;
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db.clone());
+    let (destructed, rebuilt) = (keccak256(b"destructed"), keccak256(b"rebuilt"));
+    let slots = |range: std::ops::Range<u64>| -> HashMap<B256, Option<U256>> {
+        range.map(|j| (keccak256(j.to_be_bytes()), Some(U256::from(j + 1)))).collect()
+    };
+    let storage_nodes = |owner: B256| {
+        let (start, end) = storage_trie_node_range(owner.as_slice());
+        path_db.iter_range(&start, Some(&end)).unwrap().count()
+    };
+
+    let states = HashMap::from([
+        (destructed, Some(StateAccount::default().with_nonce(1))),
+        (rebuilt, Some(StateAccount::default().with_nonce(1))),
+    ]);
+    let storage_states = HashMap::from([(destructed, slots(0..50)), (rebuilt, slots(0..50))]);
+    let (root_1, node_set, storage_roots) = triedb
+        .batch_update_and_commit(EMPTY_ROOT_HASH, None, states, HashSet::new(), storage_states)
+        .unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*node_set.to_diff_nodes()).clone(), storage_roots));
+    triedb.flush(1, root_1, &Some(difflayer)).unwrap();
+    let rebuilt_nodes = storage_nodes(rebuilt);
+    assert!(storage_nodes(destructed) > 0 && rebuilt_nodes > 0);
+
+    // Block 2 destructs one account and recreates the other with fewer slots
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(destructed, None);
+    post_state.states.insert(rebuilt, Some(StateAccount::default().with_nonce(2)));
+    post_state.states_rebuild.insert(rebuilt);
+    post_state.storage_states.insert(rebuilt, slots(100..102));
+    let (root_2, difflayer) = triedb.commit_hashed_post_state(root_1, None, &post_state).unwrap();
+    assert_eq!(difflayer.as_ref().unwrap().deleted_ranges.len(), 2);
+
+    // Reads through the unflushed difflayer already miss the old nodes
+    let mut difflayers = DiffLayers::default();
+    difflayers.insert_difflayer(difflayer.clone().unwrap());
+    triedb.state_at(root_2, Some(&difflayers)).unwrap();
+    assert_eq!(triedb.iter_storage(rebuilt).unwrap().count(), 2);
+
+    triedb.flush(2, root_2, &difflayer).unwrap();
+    assert_eq!(storage_nodes(destructed), 0);
+    assert!(storage_nodes(rebuilt) < rebuilt_nodes);
+    triedb.state_at(root_2, None).unwrap();
+    assert_eq!(triedb.get_account_with_hash_state(destructed).unwrap(), None);
+    assert_eq!(triedb.iter_storage(rebuilt).unwrap().count(), 2);
+    assert!(triedb.get_storage_with_hash_state(rebuilt, keccak256(100u64.to_be_bytes())).unwrap().is_some());
+    assert!(triedb.get_storage_with_hash_state(rebuilt, keccak256(0u64.to_be_bytes())).unwrap().is_none());
+}
+
+#[test]
+#[serial]
+fn test_bulk_storage_update_matches_per_slot() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let contract = keccak256(b"contract");
+    let slot = |j: u64| keccak256(j.to_be_bytes());
+    let states = || HashMap::from([(contract, Some(StateAccount::default().with_nonce(1)))]);
+
+    let mut triedb = TrieDB::new(path_db.clone());
+    let storage_states = HashMap::from([(contract, (0..300).map(|j| (slot(j), Some(U256::from(j + 1)))).collect())]);
+    let (root_1, node_set, storage_roots) = triedb
+        .batch_update_and_commit(EMPTY_ROOT_HASH, None, states(), HashSet::new(), storage_states)
+        .unwrap();
+    let difflayer = Arc::new(DiffLayer::new((*node_set.to_diff_nodes()).clone(), storage_roots));
+    triedb.flush(1, root_1, &Some(difflayer)).unwrap();
+
+    // Deletes, updates and new slots in one block
+    let mut slots: HashMap<B256, Option<U256>> = (0..100).map(|j| (slot(j), None)).collect();
+    slots.extend((100..200).map(|j| (slot(j), Some(U256::from(j * 2)))));
+    slots.extend((300..2000).map(|j| (slot(j), Some(U256::from(j)))));
+    let commit = |threshold: usize| {
+        let mut triedb = TrieDB::new(path_db.clone()).with_bulk_storage_threshold(threshold);
+        let (root, _, storage_roots) = triedb
+            .batch_update_and_commit(root_1, None, states(), HashSet::new(), HashMap::from([(contract, slots.clone())]))
+            .unwrap();
+        (root, storage_roots)
+    };
+
+    let (bulk_root, bulk_storage_roots) = commit(1);
+    let (per_slot_root, per_slot_storage_roots) = commit(usize::MAX);
+    assert_eq!(bulk_root, per_slot_root);
+    assert_eq!(bulk_storage_roots, per_slot_storage_roots);
+    assert_ne!(bulk_root, root_1);
+}
+
+#[test]
+#[serial]
+fn test_code_hash_index() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db.clone()).with_code_hash_index(true);
+    let (code_a, code_b) = (keccak256(b"code a"), keccak256(b"code b"));
+    let (first, second, eoa) = (keccak256(b"first"), keccak256(b"second"), keccak256(b"eoa"));
+    let contract = |code_hash: B256| Some(StateAccount::default().with_code_hash(code_hash));
+
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(first, contract(code_a));
+    post_state.states.insert(second, contract(code_a));
+    post_state.states.insert(eoa, Some(StateAccount::default().with_nonce(1)));
+    let (root_1, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state).unwrap();
+    assert_eq!(difflayer.as_ref().unwrap().code_hashes.len(), 3);
+
+    // Nothing is indexed before the flush
+    assert!(triedb.contracts_with_code_hash(code_a).unwrap().is_empty());
+    triedb.flush(1, root_1, &difflayer).unwrap();
+    let mut expected = vec![first, second];
+    expected.sort();
+    assert_eq!(triedb.contracts_with_code_hash(code_a).unwrap(), expected);
+    assert!(triedb.contracts_with_code_hash(alloy_trie::KECCAK_EMPTY).unwrap().is_empty());
+
+    // Accounts move between lists and leave the index when deleted
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(first, contract(code_b));
+    post_state.states.insert(second, None);
+    let (root_2, difflayer) = triedb.commit_hashed_post_state(root_1, None, &post_state).unwrap();
+    triedb.flush(2, root_2, &difflayer).unwrap();
+    assert!(triedb.contracts_with_code_hash(code_a).unwrap().is_empty());
+    assert_eq!(triedb.contracts_with_code_hash(code_b).unwrap(), vec![first]);
+
+    // Without the index, diff layers carry no code hashes
+    let mut triedb = TrieDB::new(path_db.clone());
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(second, contract(code_b));
+    let (_, difflayer) = triedb.commit_hashed_post_state(root_2, None, &post_state).unwrap();
+    assert!(difflayer.unwrap().code_hashes.is_empty());
+}
+
+#[test]
+#[serial]
+fn test_storage_slot_count() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db.clone()).with_slot_count_tracking(true);
+    let contract = keccak256(b"contract");
+    let slot = |index: u8| keccak256([index]);
+    let post_state = |slots: &[(u8, Option<u64>)]| {
+        let mut post_state = crate::TrieDBHashedPostState::default();
+        post_state.states.insert(contract, Some(StateAccount::default().with_nonce(1)));
+        post_state.storage_states.insert(
+            contract,
+            slots.iter().map(|(index, value)| (slot(*index), value.map(U256::from))).collect(),
+        );
+        post_state
+    };
+
+    let (root_1, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state(&[(1, Some(1)), (2, Some(2)), (3, Some(3))])).unwrap();
+    // Nothing is counted before the flush
+    assert_eq!(triedb.storage_slot_count(contract).unwrap(), None);
+    triedb.flush(1, root_1, &difflayer).unwrap();
+    assert_eq!(triedb.storage_slot_count(contract).unwrap(), Some(3));
+
+    // Overwrites don't count, cleared slots do
+    let (root_2, difflayer) = triedb.commit_hashed_post_state(root_1, None, &post_state(&[(1, None), (2, Some(20)), (4, Some(4)), (5, Some(5))])).unwrap();
+    triedb.flush(2, root_2, &difflayer).unwrap();
+    assert_eq!(triedb.storage_slot_count(contract).unwrap(), Some(4));
+
+    // A rebuilt account starts over
+    let mut rebuild = post_state(&[(6, Some(6))]);
+    rebuild.states_rebuild.insert(contract);
+    let (root_3, difflayer) = triedb.commit_hashed_post_state(root_2, None, &rebuild).unwrap();
+    triedb.flush(3, root_3, &difflayer).unwrap();
+    assert_eq!(triedb.storage_slot_count(contract).unwrap(), Some(1));
+
+    // A deleted account has no slots left
+    let mut delete = crate::TrieDBHashedPostState::default();
+    delete.states.insert(contract, None);
+    let (root_4, difflayer) = triedb.commit_hashed_post_state(root_3, None, &delete).unwrap();
+    triedb.flush(4, root_4, &difflayer).unwrap();
+    assert_eq!(triedb.storage_slot_count(contract).unwrap(), None);
+
+    // Without tracking, diff layers carry no slot count changes
+    let mut triedb = TrieDB::new(path_db.clone());
+    let (_, difflayer) = triedb.commit_hashed_post_state(root_4, None, &post_state(&[(1, Some(1))])).unwrap();
+    assert!(difflayer.unwrap().slot_count_changes.is_empty());
+}
+
+#[test]
+#[serial]
+fn test_account_snapshot() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db.clone());
+    let hashed_address = keccak256(b"account");
+    let account = StateAccount::default().with_nonce(7).with_balance(U256::from(1000));
+
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(hashed_address, Some(account));
+    let (root, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state).unwrap();
+    triedb.flush(1, root, &difflayer).unwrap();
+
+    // Commits don't populate the snapshot
+    assert_eq!(triedb.get_account_snapshot(hashed_address).unwrap(), None);
+
+    triedb.put_account_snapshot(hashed_address, account).unwrap();
+    assert_eq!(triedb.get_account_snapshot(hashed_address).unwrap(), Some(account));
+    triedb.state_at(root, None).unwrap();
+    assert_eq!(triedb.get_account_snapshot(hashed_address).unwrap(), triedb.get_account_with_hash_state(hashed_address).unwrap());
+
+    triedb.delete_account_snapshot(hashed_address).unwrap();
+    assert_eq!(triedb.get_account_snapshot(hashed_address).unwrap(), None);
+
+    // Ranges come back in hashed order
+    let mut accounts: Vec<(B256, StateAccount)> = (0u64..5)
+        .map(|nonce| (keccak256(nonce.to_be_bytes()), StateAccount::default().with_nonce(nonce)))
+        .collect();
+    for (hashed_address, account) in &accounts {
+        triedb.put_account_snapshot(*hashed_address, *account).unwrap();
+    }
+    accounts.sort_by_key(|(hashed_address, _)| *hashed_address);
+    assert_eq!(triedb.iter_account_snapshot(B256::ZERO, 10).unwrap(), accounts);
+    assert_eq!(triedb.iter_account_snapshot(accounts[1].0, 2).unwrap(), accounts[1..3]);
+
+    let slots: Vec<(B256, U256)> = (1u64..4).map(|i| (B256::with_last_byte(i as u8), U256::from(i))).collect();
+    for (hashed_key, value) in &slots {
+        triedb.put_storage_snapshot(hashed_address, *hashed_key, *value).unwrap();
+    }
+    assert_eq!(triedb.get_storage_snapshot(hashed_address, slots[0].0).unwrap(), Some(U256::from(1)));
+    assert_eq!(triedb.iter_storage_snapshot(hashed_address, slots[1].0, 10).unwrap(), slots[1..]);
+    // Zero values are removed
+    triedb.put_storage_snapshot(hashed_address, slots[0].0, U256::ZERO).unwrap();
+    assert_eq!(triedb.get_storage_snapshot(hashed_address, slots[0].0).unwrap(), None);
+    assert_eq!(triedb.iter_storage_snapshot(hashed_address, B256::ZERO, 10).unwrap(), slots[1..]);
+
+    // Database errors keep their typed cause
+    let read_only = PathDB::open_read_only(path_db_path, PathProviderConfig::default()).expect("Failed to open read-only PathDB");
+    let err = TrieDB::new(read_only).put_account_snapshot(hashed_address, account).unwrap_err();
+    assert!(matches!(err, crate::TrieDBError::Provider { .. }));
+    assert!(err.provider_error().and_then(|e| e.rocksdb_error()).is_some());
+    assert!(std::error::Error::source(&err).is_some());
+    assert!(!err.is_retryable() && !err.is_corruption());
+}
+
+#[test]
+#[serial]
+fn test_verify_snapshot_against_trie() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db);
+    let (first, second) = (keccak256(b"first"), keccak256(b"second"));
+    let slots: Vec<(B256, U256)> = (1u64..4).map(|i| (keccak256(i.to_be_bytes()), U256::from(i))).collect();
+
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(first, Some(StateAccount::default().with_nonce(1)));
+    post_state.states.insert(second, Some(StateAccount::default().with_nonce(2)));
+    post_state.storage_states.insert(first, slots.iter().map(|(key, value)| (*key, Some(*value))).collect());
+    let (root, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state).unwrap();
+    triedb.flush(1, root, &difflayer).unwrap();
+
+    triedb.state_at(root, None).unwrap();
+    for hashed_address in [first, second] {
+        let account = triedb.get_account_with_hash_state(hashed_address).unwrap().unwrap();
+        triedb.put_account_snapshot(hashed_address, account).unwrap();
+    }
+    for (hashed_key, value) in &slots {
+        triedb.put_storage_snapshot(first, *hashed_key, *value).unwrap();
+    }
+
+    let report = triedb.verify_against_trie(root, true).unwrap();
+    assert!(report.is_ok());
+    assert_eq!((report.accounts_checked, report.slots_checked), (2, 3));
+
+    // A diverged storage root is reported without checking the slots
+    let diverged = StateAccount::default().with_nonce(2).with_storage_root(B256::with_last_byte(1));
+    triedb.put_account_snapshot(second, diverged).unwrap();
+    let report = triedb.verify_against_trie(root, true).unwrap();
+    assert_eq!(report.mismatched_owners(), vec![second]);
+    assert_eq!(report.mismatches[0], crate::SnapshotMismatch::StorageRoot {
+        hashed_address: second,
+        snapshot: B256::with_last_byte(1),
+        trie: Some(EMPTY_ROOT_HASH),
+    });
+
+    // Missing and changed slots are only found when checking slots
+    let missing = slots.iter().map(|(key, _)| *key).min().unwrap();
+    triedb.delete_storage_snapshot(first, missing).unwrap();
+    assert_eq!(triedb.verify_against_trie(root, false).unwrap().mismatched_owners(), vec![second]);
+    let report = triedb.verify_against_trie(root, true).unwrap();
+    assert!(report.mismatches.contains(&crate::SnapshotMismatch::Storage { hashed_address: first, hashed_key: missing }));
+    assert_eq!(report.mismatches.len(), 2);
+}
+
+#[test]
+#[serial]
+fn test_snapshot_overlay() {
+    use rust_eth_triedb_common::TrieDatabase;
+    use rust_eth_triedb_pathdb::SnapshotOverlay;
+    use rust_eth_triedb_state_trie::encoding::account_trie_node_key;
+
+    init_empty_root_node();
+
+    let (trie_dir, snapshot_dir) = (TempDir::new().unwrap(), TempDir::new().unwrap());
+    let trie_db = PathDB::new(trie_dir.path().to_str().unwrap(), PathProviderConfig::default()).expect("Failed to create PathDB");
+    let snapshot_db = PathDB::new(snapshot_dir.path().to_str().unwrap(), PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(SnapshotOverlay::new(trie_db.clone(), snapshot_db.clone()));
+    let hashed_address = keccak256(b"account");
+    let slot = keccak256(b"slot");
+
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(hashed_address, Some(StateAccount::default().with_nonce(1)));
+    post_state.storage_states.insert(hashed_address, HashMap::from([(slot, Some(U256::from(42)))]));
+    let (root, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state).unwrap();
+    triedb.flush(1, root, &difflayer).unwrap();
+
+    // Storage roots go to the snapshot database, trie nodes to the trie database
+    let storage_root = snapshot_db.get_storage_root(hashed_address).unwrap();
+    assert!(storage_root.is_some_and(|storage_root| storage_root != EMPTY_ROOT_HASH));
+    assert_eq!(trie_db.get_storage_root(hashed_address).unwrap(), None);
+    assert!(trie_db.contains_trie_node(&account_trie_node_key(&[])).unwrap());
+    assert!(!snapshot_db.contains_trie_node(&account_trie_node_key(&[])).unwrap());
+    assert_eq!(triedb.latest_persist_state().unwrap(), (1, root));
+
+    triedb.state_at(root, None).unwrap();
+    assert_eq!(triedb.get_storage_root(hashed_address).unwrap(), storage_root);
+    assert_eq!(
+        triedb.get_storage_with_hash_state(hashed_address, slot).unwrap().map(|value| U256::from_be_slice(&value)),
+        Some(U256::from(42))
+    );
+}
+
+#[test]
+#[serial]
+fn test_empty_storage_root_fast_path() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db);
+    let (with_storage, without_storage) = (keccak256(b"with storage"), keccak256(b"without storage"));
+    let slot = keccak256(b"slot");
+
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(with_storage, Some(StateAccount::default().with_nonce(1)));
+    post_state.states.insert(without_storage, Some(StateAccount::default().with_nonce(1)));
+    post_state.storage_states.insert(with_storage, HashMap::from([(slot, Some(U256::from(1)))]));
+    let (root, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state).unwrap();
+    triedb.flush(1, root, &difflayer).unwrap();
+    triedb.state_at(root, None).unwrap();
+
+    let before = crate::triedb_metrics::snapshot().triedb.empty_storage_root_counter;
+    assert!(triedb.get_storage_with_hash_state(with_storage, slot).unwrap().is_some());
+    assert_eq!(crate::triedb_metrics::snapshot().triedb.empty_storage_root_counter, before);
+
+    // Fresh and missing accounts neither build nor cache a storage trie
+    assert_eq!(triedb.get_storage_with_hash_state(without_storage, slot).unwrap(), None);
+    assert_eq!(triedb.get_storage_with_hash_state(keccak256(b"missing"), slot).unwrap(), None);
+    assert_eq!(triedb.iter_storage(without_storage).unwrap().count(), 0);
+    assert!(!triedb.storage_tries.contains_key(&without_storage));
+    assert_eq!(crate::triedb_metrics::snapshot().triedb.empty_storage_root_counter, before + 3);
+}
+
+#[test]
+#[serial]
+fn test_metrics_snapshot() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db);
+
+    let before = crate::triedb_metrics::snapshot();
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(keccak256(b"account"), Some(StateAccount::default().with_nonce(1)));
+    let (root, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state).unwrap();
+    triedb.flush(1, root, &difflayer).unwrap();
+    let after = crate::triedb_metrics::snapshot();
+
+    assert!(after.triedb.update_histogram.count > before.triedb.update_histogram.count);
+    assert!(after.triedb.flush_histogram.count > before.triedb.flush_histogram.count);
+    assert!(after.pathdb.commit_bytes > before.pathdb.commit_bytes);
+}
+
+#[test]
+#[serial]
+fn test_root_audit() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db).with_root_audit(true);
+    let (contract, eoa) = (keccak256(b"contract"), keccak256(b"eoa"));
+
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(contract, Some(StateAccount::default().with_nonce(1)));
+    post_state.states.insert(eoa, Some(StateAccount::default().with_nonce(2)));
+    post_state.storage_states.insert(contract, (0u64..50).map(|i| (keccak256(i.to_be_bytes()), Some(U256::from(i + 1)))).collect());
+    let (root_1, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state).unwrap();
+    let mut difflayers = DiffLayers::default();
+    difflayers.insert_difflayer(difflayer.unwrap());
+
+    // Audited commits on top of unflushed layers, with cleared slots and deleted accounts
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(contract, Some(StateAccount::default().with_nonce(3)));
+    post_state.states.insert(eoa, None);
+    post_state.storage_states.insert(contract, (0u64..25).map(|i| (keccak256(i.to_be_bytes()), None)).collect());
+    let (root_2, _) = triedb.commit_hashed_post_state(root_1, Some(&difflayers), &post_state).unwrap();
+    assert_ne!(root_2, root_1);
+
+    assert_eq!(triedb.recompute_state_root(root_1, Some(&difflayers), &[contract, eoa]).unwrap(), root_1);
+}
+
+#[test]
+#[serial]
+fn test_execution_witness() {
+    use alloy_primitives::Bytes;
+    use crate::{BytecodeReader, ReadSet};
+
+    #[derive(Debug)]
+    struct Bytecodes(HashMap<B256, Bytes>);
+
+    impl BytecodeReader for Bytecodes {
+        fn get_bytecode(&self, code_hash: B256) -> Result<Option<Bytes>, TrieDBError> {
+            Ok(self.0.get(&code_hash).cloned())
+        }
+    }
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let code = Bytes::from_static(&[0x60, 0x00, 0x60, 0x00, 0xf3]);
+    let bytecodes = Bytecodes([(keccak256(&code), code.clone())].into_iter().collect());
+    let mut triedb = TrieDB::new(path_db)
+        .with_execution_witness(true)
+        .with_bytecode_reader(Arc::new(bytecodes));
+    let contract = keccak256(b"contract");
+    let (eoa_read, eoa_proved) = (keccak256(7u64.to_be_bytes()), keccak256(8u64.to_be_bytes()));
+
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(contract, Some(StateAccount::default().with_nonce(1).with_code_hash(keccak256(&code))));
+    for i in 0u64..50 {
+        post_state.states.insert(keccak256(i.to_be_bytes()), Some(StateAccount::default().with_nonce(i)));
+    }
+    post_state.storage_states.insert(contract, (0u64..50).map(|i| (keccak256(i.to_be_bytes()), Some(U256::from(i + 1)))).collect());
+    let (root_1, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state).unwrap();
+    let difflayer = difflayer.unwrap();
+    let storage_root_1 = difflayer.diff_storage_roots[&contract];
+    let mut difflayers = DiffLayers::default();
+    difflayers.insert_difflayer(difflayer);
+    // Nothing is read from an empty pre-state
+    assert_eq!(triedb.take_execution_witness(), Some(crate::ExecutionWitness::default()));
+
+    // The executor reads accounts and slots the block doesn't update
+    triedb.state_at(root_1, Some(&difflayers)).unwrap();
+    assert!(triedb.get_account_with_hash_state(eoa_read).unwrap().is_some());
+    assert!(triedb.get_storage_with_hash_state(contract, keccak256(40u64.to_be_bytes())).unwrap().is_some());
+    let reads = ReadSet { accounts: vec![eoa_proved], slots: Vec::new(), with_proofs: true };
+    let proved = triedb.read_set(&reads).unwrap().accounts[&eoa_proved].proof.clone().unwrap();
+
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(contract, Some(StateAccount::default().with_nonce(2).with_code_hash(keccak256(&code))));
+    post_state.storage_states.insert(contract, (0u64..5).map(|i| (keccak256(i.to_be_bytes()), None)).collect());
+    triedb.commit_hashed_post_state(root_1, Some(&difflayers), &post_state).unwrap();
+
+    let witness = triedb.take_execution_witness().unwrap();
+    assert_eq!(witness.codes, vec![code]);
+    let hashes: Vec<B256> = witness.state_nodes.iter().map(keccak256).collect();
+    assert!(hashes.windows(2).all(|pair| pair[0] < pair[1]));
+    assert!(hashes.contains(&root_1));
+    assert!(hashes.contains(&storage_root_1));
+    assert!(proved.iter().all(|node| witness.state_nodes.iter().any(|state_node| state_node.as_ref() == node.as_slice())));
+
+    // The paths of the plain reads are covered too, as their proofs show
+    let mut prover = TrieDB::new(triedb.get_mut_path_db_ref().clone());
+    prover.state_at(root_1, Some(&difflayers)).unwrap();
+    let reads = ReadSet { accounts: vec![eoa_read, contract], slots: vec![(contract, keccak256(40u64.to_be_bytes()))], with_proofs: true };
+    let results = prover.read_set(&reads).unwrap();
+    for proof in results.accounts.values().filter_map(|read| read.proof.as_ref()).chain(results.slots.values().filter_map(|read| read.proof.as_ref())) {
+        assert!(proof.iter().all(|node| witness.state_nodes.iter().any(|state_node| state_node.as_ref() == node.as_slice())));
+    }
+    assert_eq!(triedb.take_execution_witness(), None);
+
+    // Nothing is recorded once disabled
+    let mut triedb = triedb.with_execution_witness(false);
+    triedb.commit_hashed_post_state(root_1, Some(&difflayers), &post_state).unwrap();
+    assert_eq!(triedb.take_execution_witness(), None);
+}
+
+#[test]
+#[serial]
+fn test_storage_trie_pool_reuse() {
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let contracts: Vec<B256> = (0..8u64).map(|i| keccak256(i.to_be_bytes())).collect();
+    let slot = |j: u64| keccak256(j.to_be_bytes());
+    let block = |value: u64| {
+        let states = contracts.iter().map(|contract| (*contract, Some(StateAccount::default().with_nonce(value)))).collect();
+        let storage_states = contracts.iter()
+            .map(|contract| (*contract, (0..20).map(|j| (slot(j), Some(U256::from(j + value)))).collect()))
+            .collect();
+        (states, storage_states)
+    };
+
+    let mut pooled = TrieDB::new(path_db.clone());
+    let mut unpooled = TrieDB::new(path_db.clone()).with_storage_trie_pool_size(0);
+    let (mut pooled_root, mut unpooled_root) = (EMPTY_ROOT_HASH, EMPTY_ROOT_HASH);
+    let (mut pooled_layers, mut unpooled_layers) = (DiffLayers::default(), DiffLayers::default());
+    for value in 1..4 {
+        let (states, storage_states) = block(value);
+        let reused_before = crate::triedb_metrics::snapshot().triedb.storage_trie_reuse_counter;
+        let (root, node_set, storage_roots) = pooled
+            .batch_update_and_commit(pooled_root, Some(&pooled_layers), states, HashSet::new(), storage_states)
+            .unwrap();
+        if value > 1 {
+            assert!(crate::triedb_metrics::snapshot().triedb.storage_trie_reuse_counter >= reused_before + contracts.len() as u64);
+        }
+        // Newest layer first
+        pooled_layers.diff_layers.insert(0, Arc::new(DiffLayer::new((*node_set.to_diff_nodes()).clone(), storage_roots)));
+        pooled_root = root;
+        assert_eq!(pooled.pooled_storage_tries(), contracts.len());
+
+        let (states, storage_states) = block(value);
+        let (root, node_set, storage_roots) = unpooled
+            .batch_update_and_commit(unpooled_root, Some(&unpooled_layers), states, HashSet::new(), storage_states)
+            .unwrap();
+        unpooled_layers.diff_layers.insert(0, Arc::new(DiffLayer::new((*node_set.to_diff_nodes()).clone(), storage_roots)));
+        unpooled_root = root;
+        assert_eq!(unpooled.pooled_storage_tries(), 0);
+
+        assert_eq!(pooled_root, unpooled_root);
+    }
+}
+
+#[test]
+#[serial]
+fn test_verified_reads() {
+    use rust_eth_triedb_common::TrieDatabase;
+
+    init_empty_root_node();
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db_path = path_db_temp_dir.path().to_str().unwrap();
+    let path_db = PathDB::new(path_db_path, PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut verified = TrieDB::new(path_db.clone()).with_verified_reads(true);
+    let mut unverified = TrieDB::new(path_db);
+    assert!(verified.verified_reads());
+    assert!(!unverified.verified_reads());
+
+    let contract = keccak256(b"contract");
+    let block = |value: u64| {
+        let mut post_state = crate::TrieDBHashedPostState::default();
+        post_state.states.insert(contract, Some(StateAccount::default().with_nonce(value)));
+        post_state.states.insert(keccak256(value.to_be_bytes()), Some(StateAccount::default().with_balance(U256::from(value))));
+        post_state.storage_states.insert(contract, (0u64..20).map(|i| (keccak256(i.to_be_bytes()), Some(U256::from(i + value)))).collect());
+        post_state
+    };
+
+    // The first block is flushed, so the second one resolves its nodes from disk
+    let (root_1, difflayer) = unverified.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &block(1)).unwrap();
+    unverified.get_mut_path_db_ref().commit_difflayer(1, root_1, &difflayer).unwrap();
+
+    let (verified_root, _) = verified.commit_hashed_post_state(root_1, None, &block(2)).unwrap();
+    let (unverified_root, _) = unverified.commit_hashed_post_state(root_1, None, &block(2)).unwrap();
+    assert_eq!(verified_root, unverified_root);
+}
+
+#[cfg(feature = "debug-http")]
+#[test]
+#[serial]
+fn test_debug_http_handler() {
+    use std::io::{Read, Write};
+    use rust_eth_triedb_common::TrieDatabase;
+    use crate::triedb_debug_http::{DebugHandler, DebugHttpServer};
+
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db = PathDB::new(path_db_temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).expect("Failed to create PathDB");
+    let state_root = B256::repeat_byte(0xab);
+    path_db.commit_difflayer(7, state_root, &None).unwrap();
+    path_db.put_raw_trie_node(&[0x01, 0x02], &[0xc0, 0xff]).unwrap();
+
+    let handler = DebugHandler::new(path_db);
+    let state = handler.handle("GET", "/state");
+    assert_eq!(state.status, 200);
+    assert_eq!(state.body, format!("{{\"block_number\":7,\"state_root\":\"{:#x}\"}}", state_root));
+    assert_eq!(handler.handle("GET", "/node/0x0102?pretty").body, "{\"path\":\"0x0102\",\"blob\":\"0xc0ff\"}");
+    assert_eq!(handler.handle("GET", "/node/0x0103").status, 404);
+    assert_eq!(handler.handle("GET", "/node/0xzz").status, 400);
+    assert_eq!(handler.handle("GET", "/cache").status, 200);
+    assert!(handler.handle("GET", "/stats").body.starts_with("{\"update\":{\"count\":"));
+    assert_eq!(handler.handle("POST", "/state").status, 405);
+    assert_eq!(handler.handle("GET", "/unknown").status, 404);
+
+    let server = DebugHttpServer::spawn(handler, ([127, 0, 0, 1], 0)).unwrap();
+    let mut stream = std::net::TcpStream::connect(server.local_addr()).unwrap();
+    stream.write_all(b"GET /state HTTP/1.1\r\nHost: localhost\r\n\r\n").unwrap();
+    let mut response = String::new();
+    stream.read_to_string(&mut response).unwrap();
+    assert!(response.starts_with("HTTP/1.1 200 OK\r\n"));
+    assert!(response.ends_with("\"block_number\":7,\"state_root\":\"0xabababababababababababababababababababababababababababababababab\"}"));
+    server.stop();
+}
+
+#[test]
+#[serial]
+fn test_vectors_round_trip() {
+    use crate::triedb_vectors::{TestVector, VectorMismatch};
+
+    init_empty_root_node();
+    let path_db_temp_dir = TempDir::new().expect("Failed to create temp directory for PathDB");
+    let path_db = PathDB::new(path_db_temp_dir.path().to_str().unwrap(), PathProviderConfig::default()).expect("Failed to create PathDB");
+    let mut triedb = TrieDB::new(path_db);
+
+    // The reference vector matches the BSC implementation and survives serialization
+    let reference = TestVector::bsc_reference();
+    let report = reference.verify(&mut triedb).unwrap();
+    assert!(report.is_ok(), "{:?}", report.mismatch);
+    assert_eq!(report.verified, 2);
+    let parsed = TestVector::from_text(&reference.to_text()).unwrap();
+    assert_eq!(parsed, reference);
+
+    // Generated vectors are deterministic and verify once recorded
+    let mut generated = TestVector::generate("generated", 7, 3, 40, 8);
+    assert_eq!(generated, TestVector::generate("generated", 7, 3, 40, 8));
+    assert!(generated.verify(&mut triedb).is_err());
+    generated.record(&mut triedb).unwrap();
+    let parsed = TestVector::from_text(&generated.to_text()).unwrap();
+    assert_eq!(parsed, generated);
+    assert!(parsed.verify(&mut triedb).unwrap().is_ok());
+
+    // A tampered root is reported
+    let mut tampered = reference;
+    tampered.blocks[1].expected_root = Some(B256::repeat_byte(0x11));
+    let report = tampered.verify(&mut triedb).unwrap();
+    assert_eq!(report.verified, 1);
+    assert!(matches!(report.mismatch, Some(VectorMismatch::Root { block: 1, .. })));
+
+    assert!(TestVector::from_text("triedb-test-vector 2\n").is_err());
+    assert!(TestVector::from_text("triedb-test-vector 1\nblock\nroot 0x12\n").is_err());
+}
+
+#[test]
+#[serial]
+fn test_triedb_over_memory_db() {
+    use rust_eth_triedb_common::MemoryDB;
+
+    init_empty_root_node();
+
+    let db = MemoryDB::new();
+    let mut triedb = TrieDB::new(db.clone());
+    let contract = keccak256(b"contract");
+    let mut post_state = crate::TrieDBHashedPostState::default();
+    post_state.states.insert(contract, Some(StateAccount::default().with_nonce(1)));
+    post_state.storage_states.insert(contract, (0u64..10).map(|i| (keccak256(i.to_be_bytes()), Some(U256::from(i + 1)))).collect());
+    let (root, difflayer) = triedb.commit_hashed_post_state(EMPTY_ROOT_HASH, None, &post_state).unwrap();
+    triedb.flush(1, root, &difflayer).unwrap();
+    assert_eq!(triedb.latest_persist_state().unwrap(), (1, root));
+
+    // A fresh TrieDB reads the block back from the in-memory store
+    let mut triedb = TrieDB::new(db);
+    triedb.state_at(root, None).unwrap();
+    assert_eq!(triedb.get_account_with_hash_state(contract).unwrap().unwrap().nonce, 1);
+    for i in 0u64..10 {
+        assert_eq!(triedb.get_storage_with_hash_state(contract, keccak256(i.to_be_bytes())).unwrap(), Some(vec![i as u8 + 1]));
+    }
+}
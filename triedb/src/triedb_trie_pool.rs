@@ -0,0 +1,57 @@
+//! Reuse of storage trie allocations across blocks.
+
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::state_trie::StateTrie;
+
+use crate::triedb::TrieDB;
+
+/// Default number of released storage tries kept for reuse, see
+/// `TrieDB::with_storage_trie_pool_size`.
+pub const DEFAULT_STORAGE_TRIE_POOL_SIZE: usize = 4096;
+
+/// Storage trie pool configuration and maintenance
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Sets the number of released storage tries kept for reuse.
+    ///
+    /// `batch_update_and_commit` builds a storage trie per changed account
+    /// per block. Instead of dropping them once the block is committed, up to
+    /// `size` of them are kept and re-targeted at the next block's accounts,
+    /// so their tracer maps don't have to be allocated again. `0` disables
+    /// the pool.
+    pub fn with_storage_trie_pool_size(mut self, size: usize) -> Self {
+        self.storage_trie_pool_size = size;
+        self.storage_trie_pool.truncate(size);
+        self
+    }
+
+    /// Returns the number of released storage tries kept for reuse.
+    pub fn storage_trie_pool_size(&self) -> usize {
+        self.storage_trie_pool_size
+    }
+
+    /// Returns the number of storage tries currently pooled.
+    pub fn pooled_storage_tries(&self) -> usize {
+        self.storage_trie_pool.len()
+    }
+
+    /// Moves the cached storage tries into the pool, dropping those beyond
+    /// its size.
+    pub(crate) fn release_storage_tries(&mut self) {
+        let free = self.storage_trie_pool_size.saturating_sub(self.storage_trie_pool.len());
+        for (_, mut storage_trie) in self.storage_tries.drain().take(free) {
+            storage_trie.release();
+            self.storage_trie_pool.push(storage_trie);
+        }
+    }
+
+    /// Takes up to `count` pooled storage tries, to be re-targeted with
+    /// `StateTrie::reset` before use.
+    pub(crate) fn take_pooled_storage_tries(&mut self, count: usize) -> Vec<StateTrie<DB>> {
+        let start = self.storage_trie_pool.len().saturating_sub(count);
+        self.storage_trie_pool.split_off(start)
+    }
+}
@@ -0,0 +1,396 @@
+//! Deterministic test vectors for byte-for-byte compatibility checks.
+//!
+//! A [`TestVector`] is a sequence of blocks of account and storage changes
+//! together with the state root and the NodeSet signature of every trie each
+//! block is expected to produce. [`TestVector::bsc_reference`] holds the
+//! vector computed by the BSC implementation, [`TestVector::generate`]
+//! derives larger ones from a seed and [`TestVector::record`] fills in the
+//! expectations on a trusted platform. A vector serialized with
+//! [`TestVector::to_text`] can be shipped and checked with
+//! [`TestVector::verify`] on a new platform or architecture at install time.
+//!
+//! The text format is line based, fields are separated by a single space and
+//! lines starting with `#` are comments:
+//!
+//! ```text
+//! triedb-test-vector 1
+//! name <name>
+//! block
+//! account <hashed address> <nonce> <balance> <storage root> <code hash>
+//! account <hashed address> deleted
+//! slot <hashed address> <hashed slot> <value>
+//! slot <hashed address> <hashed slot> deleted
+//! root <state root>
+//! signature <owner> <signature>
+//! ```
+
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::fmt::Write as _;
+use std::str::FromStr;
+use std::sync::Arc;
+
+use alloy_primitives::{keccak256, Address, B256, U256};
+use alloy_trie::EMPTY_ROOT_HASH;
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::account::StateAccount;
+use rust_eth_triedb_state_trie::node::DiffLayers;
+
+use crate::triedb::{TrieDB, TrieDBError};
+
+/// Version written to and expected in the header of the text format.
+const TEXT_FORMAT_VERSION: u32 = 1;
+
+/// Changes of one block and the results they are expected to produce.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VectorBlock {
+    /// Account changes keyed by hashed address, `None` deletes the account
+    pub states: BTreeMap<B256, Option<StateAccount>>,
+    /// Storage changes keyed by hashed address and hashed slot, `None` deletes the slot
+    pub storage_states: BTreeMap<B256, BTreeMap<B256, Option<U256>>>,
+    /// Expected state root, `None` until recorded
+    pub expected_root: Option<B256>,
+    /// Expected NodeSet signature per trie owner, `B256::ZERO` is the account trie
+    pub expected_signatures: BTreeMap<B256, B256>,
+}
+
+/// A named sequence of blocks applied on top of the empty state.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct TestVector {
+    /// Name of the vector
+    pub name: String,
+    /// Blocks, applied in order
+    pub blocks: Vec<VectorBlock>,
+}
+
+/// A block whose result differs from the vector.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum VectorMismatch {
+    /// The computed state root differs
+    Root {
+        /// Index of the block in the vector
+        block: usize,
+        /// State root recorded in the vector
+        expected: B256,
+        /// State root computed by the TrieDB
+        computed: B256,
+    },
+    /// The NodeSet of a trie differs, or only one side has one
+    Signature {
+        /// Index of the block in the vector
+        block: usize,
+        /// Owner of the trie, `B256::ZERO` is the account trie
+        owner: B256,
+        /// Signature recorded in the vector
+        expected: Option<B256>,
+        /// Signature computed by the TrieDB
+        computed: Option<B256>,
+    },
+}
+
+/// Result of [`TestVector::verify`].
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct VectorReport {
+    /// Number of blocks whose root and signatures matched
+    pub verified: u64,
+    /// First mismatch; the verification stops there
+    pub mismatch: Option<VectorMismatch>,
+}
+
+impl VectorReport {
+    /// Whether all blocks matched.
+    pub fn is_ok(&self) -> bool {
+        self.mismatch.is_none()
+    }
+}
+
+impl TestVector {
+    /// The two-block vector computed by the BSC implementation.
+    ///
+    /// The first block creates 100 default accounts at addresses `[i; 20]`
+    /// and gives five of them ten storage slots each. The second deletes the
+    /// last ten accounts, deletes half of the slots and rewrites the others.
+    pub fn bsc_reference() -> Self {
+        let hashed_address = |i: u8| keccak256(Address::from_slice(&[i; 20]).as_slice());
+        let hashed_slot = |j: u8| keccak256([j]);
+
+        let mut first = VectorBlock::default();
+        for i in 1..=100u8 {
+            first.states.insert(hashed_address(i), Some(StateAccount::default()));
+        }
+        for i in 1..=5u8 {
+            let slots = (1..=10u8).map(|j| (hashed_slot(j), Some(U256::from_be_bytes([j; 32])))).collect();
+            first.storage_states.insert(hashed_address(i), slots);
+        }
+        first.expected_root = Some(b256("0xadcc848b76bace28ea81dd449a735bad44663a36f18f40980d586d5315eb3800"));
+        first.expected_signatures = signatures(&[
+            ("0x685e6e68197229ce85c17dc36118fe13f0bfde48652d7e991793b6710233fe1c", "0xd0ae98bff7b58f014068421e4e51ee4534a8a328f9dde9b053b135a2638feb19"),
+            ("0xe9654a4d194318e8ef7e64c6cbc31c341c650a6a039ea448faf8101af403da4d", "0x69f3330ba3766603e32f4c3fbe0ce6dd33f7d493315935c31a20e4a3d3193fe3"),
+            ("0xab40727044881a0015f3d04d723757bf0fd40eac11565ede1640f7fd76410e93", "0x77c211bee4f6b55f6e5c59c4bfcb315a72852f6c7b0e9572b8e5bf6ee3f33625"),
+            ("0x92c2f498f37adab9c7a4bf0aae161bb929b33867f5b5976848450005f577b8cb", "0x1573cf1c97f9e906504d24410a6439536f109cef9136c312e3d614672a04ac8c"),
+            ("0x096172dff854a4d9f67fb972ad494924c83beb6624b06ec2b047119c5c20978e", "0xe795383fef0402e55890a95e36ec24c5908e8b041dea294d89a28774b2a9aa5c"),
+            ("0x0000000000000000000000000000000000000000000000000000000000000000", "0x8d8a3ac91309a1315bc5f01021c44066679d4a7070a39a6db4c09e9dd28ec178"),
+        ]);
+
+        let mut second = VectorBlock::default();
+        for i in 91..=100u8 {
+            second.states.insert(hashed_address(i), None);
+        }
+        for i in 1..=5u8 {
+            second.states.insert(hashed_address(i), Some(StateAccount::default()));
+            let deleted = (1..=5u8).map(|j| (hashed_slot(j), None));
+            let updated = (6..=10u8).map(|j| (hashed_slot(j), Some(U256::from_be_bytes([j * 2; 32]))));
+            second.storage_states.insert(hashed_address(i), deleted.chain(updated).collect());
+        }
+        second.expected_root = Some(b256("0x626ca0a9ca91a1fe5e3a4f438f11015e6e64510b6a29c3a6362d98abad5e4875"));
+        second.expected_signatures = signatures(&[
+            ("0xe9654a4d194318e8ef7e64c6cbc31c341c650a6a039ea448faf8101af403da4d", "0xa88cc2dd758e2d22a983252f13124334c173d7570901c5802ee49b7b831e3911"),
+            ("0xab40727044881a0015f3d04d723757bf0fd40eac11565ede1640f7fd76410e93", "0x2c90170468991f1c11f6a8af4a920b0b8b852d98bf613099f3b84575a8eb65c7"),
+            ("0x92c2f498f37adab9c7a4bf0aae161bb929b33867f5b5976848450005f577b8cb", "0x8ed378fb4c0fa800eed175f6978d71f027f4a9d07ac50a3f6c2844ea50a74818"),
+            ("0x096172dff854a4d9f67fb972ad494924c83beb6624b06ec2b047119c5c20978e", "0x7feaec82f5a4c977f98fbba6e71dae61eb7b3ec61b2bd88e7e5b06bdf91e50ed"),
+            ("0x685e6e68197229ce85c17dc36118fe13f0bfde48652d7e991793b6710233fe1c", "0x523824b05d0da3067cec66c12988e2ceb10116b5fc017a85cbbe17b34760a07b"),
+            ("0x0000000000000000000000000000000000000000000000000000000000000000", "0x857f4c28235bc6eb3bc3e8f08a85102c62e0ff505a6eb0e6daaa7886a5ed4207"),
+        ]);
+
+        Self { name: "bsc-reference".to_string(), blocks: vec![first, second] }
+    }
+
+    /// Derive a vector of `block_count` blocks from `seed`, without expectations.
+    ///
+    /// Every block touches the same `account_count` accounts. The first
+    /// quarter of them carry `slot_count` storage slots that are rewritten,
+    /// and from the second block on partly deleted, in every block; a fifth
+    /// of the other accounts is deleted in each block after the first. The
+    /// same arguments always produce the same vector.
+    pub fn generate(name: &str, seed: u64, block_count: usize, account_count: usize, slot_count: usize) -> Self {
+        let derive = |parts: &[u64]| {
+            let mut preimage = seed.to_be_bytes().to_vec();
+            for part in parts {
+                preimage.extend_from_slice(&part.to_be_bytes());
+            }
+            keccak256(preimage)
+        };
+        let storage_accounts = account_count / 4;
+
+        let blocks = (0..block_count as u64)
+            .map(|block| {
+                let mut vector_block = VectorBlock::default();
+                for i in 0..account_count as u64 {
+                    let hashed_address = derive(&[0, i]);
+                    if block > 0 && i >= storage_accounts as u64 && (i + block) % 5 == 0 {
+                        vector_block.states.insert(hashed_address, None);
+                        continue;
+                    }
+                    let balance = derive(&[1, block, i]);
+                    let account = StateAccount::default()
+                        .with_nonce(block)
+                        .with_balance(U256::from_be_slice(&balance[..16]));
+                    vector_block.states.insert(hashed_address, Some(account));
+
+                    if i < storage_accounts as u64 {
+                        let slots = (0..slot_count as u64)
+                            .map(|j| {
+                                let value = (block == 0 || (j + block) % 4 != 0)
+                                    .then(|| U256::from_be_bytes(derive(&[2, block, i, j]).0));
+                                (derive(&[3, i, j]), value)
+                            })
+                            .collect();
+                        vector_block.storage_states.insert(hashed_address, slots);
+                    }
+                }
+                vector_block
+            })
+            .collect();
+
+        Self { name: name.to_string(), blocks }
+    }
+
+    /// Apply the vector to `triedb` and record the computed roots and
+    /// signatures as its expectations, replacing any recorded before.
+    pub fn record<DB>(&mut self, triedb: &mut TrieDB<DB>) -> Result<(), TrieDBError>
+    where
+        DB: TrieDatabase + Clone + Send + Sync,
+        DB::Error: TrieDBErrorSource,
+    {
+        let mut root_hash = EMPTY_ROOT_HASH;
+        let mut difflayers = DiffLayers::default();
+        for block in self.blocks.iter_mut() {
+            let (computed, signatures) = apply_block(triedb, root_hash, &mut difflayers, block)?;
+            block.expected_root = Some(computed);
+            block.expected_signatures = signatures;
+            root_hash = computed;
+        }
+        Ok(())
+    }
+
+    /// Apply the vector to `triedb` and compare the computed roots and
+    /// signatures with its expectations.
+    ///
+    /// Blocks are applied on top of the empty state and kept in memory as
+    /// difflayers, nothing is written to the database. Fails if a block has
+    /// no recorded root.
+    pub fn verify<DB>(&self, triedb: &mut TrieDB<DB>) -> Result<VectorReport, TrieDBError>
+    where
+        DB: TrieDatabase + Clone + Send + Sync,
+        DB::Error: TrieDBErrorSource,
+    {
+        let mut root_hash = EMPTY_ROOT_HASH;
+        let mut difflayers = DiffLayers::default();
+        let mut report = VectorReport::default();
+        for (index, block) in self.blocks.iter().enumerate() {
+            let expected = block.expected_root
+                .ok_or_else(|| TrieDBError::InvalidData(format!("Block {} of vector {} has no expected root", index, self.name)))?;
+            let (computed, signatures) = apply_block(triedb, root_hash, &mut difflayers, block)?;
+            if computed != expected {
+                report.mismatch = Some(VectorMismatch::Root { block: index, expected, computed });
+                return Ok(report);
+            }
+
+            let owners: BTreeSet<B256> = block.expected_signatures.keys().chain(signatures.keys()).copied().collect();
+            for owner in owners {
+                let expected = block.expected_signatures.get(&owner).copied();
+                let computed = signatures.get(&owner).copied();
+                if expected != computed {
+                    report.mismatch = Some(VectorMismatch::Signature { block: index, owner, expected, computed });
+                    return Ok(report);
+                }
+            }
+
+            root_hash = computed;
+            report.verified += 1;
+        }
+        Ok(report)
+    }
+
+    /// Serialize the vector to the text format.
+    pub fn to_text(&self) -> String {
+        let mut text = format!("triedb-test-vector {}\nname {}\n", TEXT_FORMAT_VERSION, self.name);
+        for block in &self.blocks {
+            text.push_str("block\n");
+            for (hashed_address, account) in &block.states {
+                let _ = match account {
+                    Some(account) => writeln!(
+                        text,
+                        "account {:#x} {} {:#x} {:#x} {:#x}",
+                        hashed_address, account.nonce, account.balance, account.storage_root, account.code_hash
+                    ),
+                    None => writeln!(text, "account {:#x} deleted", hashed_address),
+                };
+            }
+            for (hashed_address, slots) in &block.storage_states {
+                for (hashed_slot, value) in slots {
+                    let _ = match value {
+                        Some(value) => writeln!(text, "slot {:#x} {:#x} {:#x}", hashed_address, hashed_slot, value),
+                        None => writeln!(text, "slot {:#x} {:#x} deleted", hashed_address, hashed_slot),
+                    };
+                }
+            }
+            if let Some(root) = block.expected_root {
+                let _ = writeln!(text, "root {:#x}", root);
+            }
+            for (owner, signature) in &block.expected_signatures {
+                let _ = writeln!(text, "signature {:#x} {:#x}", owner, signature);
+            }
+        }
+        text
+    }
+
+    /// Parse a vector from the text format.
+    pub fn from_text(text: &str) -> Result<Self, TrieDBError> {
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(index, line)| (index + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let header = lines.next().map(|(_, line)| line).unwrap_or_default();
+        if header != format!("triedb-test-vector {}", TEXT_FORMAT_VERSION) {
+            return Err(TrieDBError::InvalidData(format!("Unsupported test vector header: {:?}", header)));
+        }
+
+        let mut vector = Self::default();
+        for (line_number, line) in lines {
+            let invalid = || TrieDBError::InvalidData(format!("Invalid test vector line {}: {:?}", line_number, line));
+            let (kind, rest) = line.split_once(' ').unwrap_or((line, ""));
+            if kind == "name" {
+                vector.name = rest.to_string();
+                continue;
+            }
+            if kind == "block" {
+                vector.blocks.push(VectorBlock::default());
+                continue;
+            }
+
+            let block = vector.blocks.last_mut().ok_or_else(invalid)?;
+            let fields: Vec<&str> = rest.split(' ').collect();
+            match (kind, fields.as_slice()) {
+                ("account", [hashed_address, "deleted"]) => {
+                    block.states.insert(parse(hashed_address).ok_or_else(invalid)?, None);
+                }
+                ("account", [hashed_address, nonce, balance, storage_root, code_hash]) => {
+                    let account = StateAccount {
+                        nonce: nonce.parse().map_err(|_| invalid())?,
+                        balance: parse(balance).ok_or_else(invalid)?,
+                        storage_root: parse(storage_root).ok_or_else(invalid)?,
+                        code_hash: parse(code_hash).ok_or_else(invalid)?,
+                    };
+                    block.states.insert(parse(hashed_address).ok_or_else(invalid)?, Some(account));
+                }
+                ("slot", [hashed_address, hashed_slot, value]) => {
+                    let value = match *value {
+                        "deleted" => None,
+                        value => Some(parse(value).ok_or_else(invalid)?),
+                    };
+                    block.storage_states
+                        .entry(parse(hashed_address).ok_or_else(invalid)?)
+                        .or_default()
+                        .insert(parse(hashed_slot).ok_or_else(invalid)?, value);
+                }
+                ("root", [root]) => block.expected_root = Some(parse(root).ok_or_else(invalid)?),
+                ("signature", [owner, signature]) => {
+                    block.expected_signatures.insert(parse(owner).ok_or_else(invalid)?, parse(signature).ok_or_else(invalid)?);
+                }
+                _ => return Err(invalid()),
+            }
+        }
+        Ok(vector)
+    }
+}
+
+/// Apply `block` on top of `root_hash`, push its difflayer and return the
+/// computed root with the signature of every changed trie.
+fn apply_block<DB>(
+    triedb: &mut TrieDB<DB>,
+    root_hash: B256,
+    difflayers: &mut DiffLayers,
+    block: &VectorBlock,
+) -> Result<(B256, BTreeMap<B256, B256>), TrieDBError>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    let states: HashMap<B256, Option<StateAccount>> = block.states.iter().map(|(k, v)| (*k, *v)).collect();
+    let storage_states: HashMap<B256, HashMap<B256, Option<U256>>> = block.storage_states
+        .iter()
+        .map(|(owner, slots)| (*owner, slots.iter().map(|(k, v)| (*k, *v)).collect()))
+        .collect();
+
+    let (computed, node_set, diff_storage_roots) =
+        triedb.batch_update_and_commit(root_hash, Some(&*difflayers), states, HashSet::new(), storage_states)?;
+    let signatures = node_set.sets.iter().map(|(owner, nodes)| (*owner, nodes.signature())).collect();
+
+    // Most recent layer first
+    difflayers.diff_layers.insert(0, Arc::new(node_set.to_difflayer(diff_storage_roots)));
+    Ok((computed, signatures))
+}
+
+fn parse<T: FromStr>(field: &str) -> Option<T> {
+    field.parse().ok()
+}
+
+fn b256(hex: &str) -> B256 {
+    B256::from_str(hex).expect("valid reference hash")
+}
+
+fn signatures(pairs: &[(&str, &str)]) -> BTreeMap<B256, B256> {
+    pairs.iter().map(|(owner, signature)| (b256(owner), b256(signature))).collect()
+}
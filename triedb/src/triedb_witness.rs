@@ -0,0 +1,126 @@
+//! Execution witnesses of committed blocks.
+
+use std::collections::BTreeSet;
+use std::sync::{Arc, Mutex};
+
+use alloy_primitives::{Bytes, B256};
+use alloy_trie::KECCAK_EMPTY;
+use rust_eth_triedb_common::{TrieDatabase, TrieDBErrorSource};
+use rust_eth_triedb_state_trie::account::StateAccount;
+use rust_eth_triedb_state_trie::NodeReadRecorder;
+
+use crate::triedb::{TrieDB, TrieDBError};
+
+/// Source of contract bytecode by code hash, e.g. the bytecode table of the
+/// client. TrieDB stores no bytecode, execution witnesses get their `codes`
+/// from this reader.
+pub trait BytecodeReader: Send + Sync + std::fmt::Debug {
+    /// Returns the bytecode with hash `code_hash`, `None` if it is unknown.
+    fn get_bytecode(&self, code_hash: B256) -> Result<Option<Bytes>, TrieDBError>;
+}
+
+/// The state a block read, enough to re-execute it without a database.
+///
+/// Mirrors the `debug_executionWitness` format: `state_nodes` holds the
+/// encoded account and storage trie nodes read through the tries of TrieDB,
+/// by the reads of the executor and proofs as well as by the updates of
+/// the block. `codes` holds the bytecode of the accounts read. Both are
+/// deduplicated and ordered by hash, so witnesses of the same block compare
+/// equal across clients.
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct ExecutionWitness {
+    /// Bytecodes of the contracts the block read.
+    pub codes: Vec<Bytes>,
+    /// Encoded trie nodes read from the pre-state.
+    pub state_nodes: Vec<Bytes>,
+}
+
+/// What the reads of a TrieDB touched since its last witness, shared with
+/// the tries it builds and with its parallel reads.
+#[derive(Debug, Clone, Default)]
+pub(crate) struct WitnessRecorder {
+    /// Trie nodes read, recorded by the tries themselves
+    pub(crate) nodes: NodeReadRecorder,
+    /// Code hashes of the accounts read
+    code_hashes: Arc<Mutex<BTreeSet<B256>>>,
+}
+
+impl WitnessRecorder {
+    /// Records the code hash of `account`, if it has code.
+    pub(crate) fn record_account(&self, account: Option<&StateAccount>) {
+        if let Some(account) = account.filter(|account| account.code_hash != KECCAK_EMPTY) {
+            self.code_hashes.lock().unwrap().insert(account.code_hash);
+        }
+    }
+}
+
+/// Execution witness recording
+impl<DB> TrieDB<DB>
+where
+    DB: TrieDatabase + Clone + Send + Sync,
+    DB::Error: TrieDBErrorSource,
+{
+    /// Enables or disables execution witness recording.
+    ///
+    /// When enabled, the tries built from the next `state_at` on record
+    /// every node they read: through `get_account`, `get_storage`, their
+    /// hashed variants, `read_set` and its proofs, and the updates of
+    /// `batch_update_and_commit`. Storage reads skip the flat storage reader
+    /// meanwhile, it yields no nodes. Each `batch_update_and_commit` exports
+    /// what was read since the previous one as the witness of its block, to
+    /// be picked up with `take_execution_witness`.
+    pub fn with_execution_witness(mut self, enabled: bool) -> Self {
+        self.witness_recorder = enabled.then(WitnessRecorder::default);
+        self.last_execution_witness = None;
+        // Pooled storage tries keep the recorder they were built with
+        self.storage_trie_pool.clear();
+        self
+    }
+
+    /// Sets the reader filling the `codes` of execution witnesses. Without
+    /// one, the `codes` are left empty.
+    pub fn with_bytecode_reader(mut self, reader: Arc<dyn BytecodeReader>) -> Self {
+        self.bytecode_reader = Some(reader);
+        self
+    }
+
+    /// Returns whether execution witness recording is enabled.
+    pub fn execution_witness(&self) -> bool {
+        self.witness_recorder.is_some()
+    }
+
+    /// Takes the witness of the last `batch_update_and_commit`, `None` if
+    /// recording is disabled or the witness was already taken.
+    pub fn take_execution_witness(&mut self) -> Option<ExecutionWitness> {
+        self.last_execution_witness.take()
+    }
+
+    /// The recorder to build tries with, `None` if recording is disabled.
+    pub(crate) fn node_recorder(&self) -> Option<NodeReadRecorder> {
+        self.witness_recorder.as_ref().map(|recorder| recorder.nodes.clone())
+    }
+
+    /// Records the code hash of an account read, if recording is enabled.
+    pub(crate) fn record_account_read(&self, account: Option<&StateAccount>) {
+        if let Some(recorder) = &self.witness_recorder {
+            recorder.record_account(account);
+        }
+    }
+
+    /// Builds the witness of everything read since the previous one,
+    /// leaving the recorder empty. `None` if recording is disabled.
+    pub(crate) fn collect_execution_witness(&self) -> Result<Option<ExecutionWitness>, TrieDBError> {
+        let Some(recorder) = &self.witness_recorder else {
+            return Ok(None);
+        };
+        let state_nodes = recorder.nodes.take();
+        let code_hashes = std::mem::take(&mut *recorder.code_hashes.lock().unwrap());
+        let mut codes = Vec::new();
+        if let Some(reader) = &self.bytecode_reader {
+            for code_hash in code_hashes {
+                codes.extend(reader.get_bytecode(code_hash)?);
+            }
+        }
+        Ok(Some(ExecutionWitness { codes, state_nodes }))
+    }
+}